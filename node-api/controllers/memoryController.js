@@ -61,8 +61,10 @@ const recallMemories = asyncHandler(async (req, res) => {
     dateFrom,
     dateTo,
     limit,
+    offset,
     minImportance,
-    keywords
+    keywords,
+    metadata
   } = req.body
 
   const rustBridge = req.app.locals.rustBridge
@@ -76,8 +78,10 @@ const recallMemories = asyncHandler(async (req, res) => {
       dateFrom: dateFrom ? new Date(dateFrom) : null,
       dateTo: dateTo ? new Date(dateTo) : null,
       limit: limit || 50,
+      offset: offset || null,
       minImportance: minImportance || null,
-      keywords: keywords || null
+      keywords: keywords || null,
+      metadata: metadata || {}
     }
 
     // Recall memories using Rust bridge
@@ -3,6 +3,9 @@ const ref = require('ref-napi')
 const path = require('path')
 const fs = require('fs')
 
+// Highest mindcache_abi_version() this bridge knows how to call
+const SUPPORTED_ABI_VERSION = 1
+
 /**
  * Rust Bridge - FFI interface to MindCache Rust core
  *
@@ -62,6 +65,23 @@ class RustBridge {
       throw new Error(`Rust library not found at: ${libPath}. Please build the Rust core first.`)
     }
 
+    this.capabilities = this.readCapabilities(libPath)
+    if (this.capabilities.abi_version > SUPPORTED_ABI_VERSION) {
+      throw new Error(`Rust library ABI ${this.capabilities.abi_version} is newer than this bridge supports (${SUPPORTED_ABI_VERSION})`)
+    }
+
+    // Functions added after the first release; only bound when the library has them
+    const optionalFunctions = {
+      mindcache_save_ex: ['string', ['pointer', 'string', 'string', 'string', 'string', 'float', 'int']],
+      mindcache_recall_advanced: ['string', ['pointer', 'string']],
+      mindcache_decay_async: ['int', ['pointer', 'pointer', 'pointer']],
+      mindcache_export_async: ['int', ['pointer', 'string', 'pointer', 'pointer']],
+      mindcache_last_error: ['string', []]
+    }
+    const availableFunctions = Object.fromEntries(
+      Object.entries(optionalFunctions).filter(([name]) => this.capabilities.functions.includes(name))
+    )
+
     // Define FFI interface
     this.rustLib = ffi.Library(libPath, {
       // Core functions
@@ -77,10 +97,56 @@ class RustBridge {
       mindcache_get_stats: ['string', ['pointer']],
 
       // Utility functions
-      mindcache_free_string: ['void', ['string']]
+      mindcache_free_string: ['void', ['string']],
+
+      ...availableFunctions
     })
 
-    console.log('✅ Rust library loaded successfully')
+    console.log(`✅ Rust library loaded successfully (ABI ${this.capabilities.abi_version})`)
+  }
+
+  /**
+     * Ask the library what it supports; libraries from before
+     * mindcache_features() report ABI 0 and no optional functions
+     */
+  readCapabilities (libPath) {
+    try {
+      const probe = ffi.Library(libPath, { mindcache_features: ['string', []] })
+      return JSON.parse(probe.mindcache_features())
+    } catch (error) {
+      return { abi_version: 0, functions: [], features: [] }
+    }
+  }
+
+  /**
+     * Whether the loaded library exports an optional function
+     */
+  hasFunction (name) {
+    return typeof this.rustLib[name] === 'function'
+  }
+
+  /**
+     * Run an async Rust call and resolve with its parsed JSON result
+     *
+     * `start` receives the native callback and must return the call's status code.
+     * The Rust core must not be called with the same handle until it calls back.
+     */
+  callAsync (start) {
+    return new Promise((resolve, reject) => {
+      // Keep a reference until the worker thread has called back
+      let callback = ffi.Callback('void', ['pointer', 'string', 'string'], (userData, resultJson, error) => {
+        callback = null
+        if (error) {
+          reject(new Error(error))
+        } else {
+          resolve(JSON.parse(resultJson))
+        }
+      })
+      if (start(callback) !== 0) {
+        callback = null
+        reject(new Error('Rust core rejected the call'))
+      }
+    })
   }
 
   /**
@@ -160,11 +226,13 @@ class RustBridge {
 
     if (this.cachePtr.isNull()) {
       // Fallback to default initialization
-      console.warn('⚠️ Config initialization failed, trying default...')
+      const reason = this.hasFunction('mindcache_last_error') ? this.rustLib.mindcache_last_error() : null
+      console.warn(`⚠️ Config initialization failed${reason ? `: ${reason}` : ''}, trying default...`)
       this.cachePtr = this.rustLib.mindcache_init()
 
       if (this.cachePtr.isNull()) {
-        throw new Error('Failed to initialize MindCache')
+        const reason = this.hasFunction('mindcache_last_error') ? this.rustLib.mindcache_last_error() : null
+        throw new Error(`Failed to initialize MindCache${reason ? `: ${reason}` : ''}`)
       }
     }
 
@@ -182,13 +250,17 @@ class RustBridge {
 
       console.log(`💾 Saving memory for user ${userId}, session ${sessionId}`)
 
-      const result = this.rustLib.mindcache_save(
-        this.cachePtr,
-        userId,
-        sessionId,
-        content,
-        metadataJson
-      )
+      const result = this.hasFunction('mindcache_save_ex')
+        ? this.rustLib.mindcache_save_ex(
+          this.cachePtr,
+          userId,
+          sessionId,
+          content,
+          metadataJson,
+          importance,
+          ttlHours || 0
+        )
+        : this.rustLib.mindcache_save(this.cachePtr, userId, sessionId, content, metadataJson)
 
       if (!result) {
         throw new Error('Failed to save memory - no result returned')
@@ -214,18 +286,35 @@ class RustBridge {
         userId,
         query = null,
         sessionId = null,
-        limit = 50
+        limit = 50,
+        offset = null,
+        dateFrom = null,
+        dateTo = null,
+        minImportance = null,
+        keywords = null,
+        metadata = {}
       } = filter
 
       console.log(`🔍 Recalling memories for user ${userId}${query ? ` with query "${query}"` : ''}`)
 
-      const result = this.rustLib.mindcache_recall(
-        this.cachePtr,
-        userId,
-        query,
-        sessionId,
-        limit
-      )
+      // Serialized QueryFilter; metadata values are exact matches
+      const queryFilter = {
+        user_id: userId,
+        session_id: sessionId,
+        keywords: keywords || (query ? query.split(/\s+/).filter(Boolean) : null),
+        date_from: dateFrom ? new Date(dateFrom).toISOString() : null,
+        date_to: dateTo ? new Date(dateTo).toISOString() : null,
+        limit: limit > 0 ? limit : null,
+        offset,
+        min_importance: minImportance,
+        metadata_filters: Object.fromEntries(
+          Object.entries(metadata).map(([key, value]) => [key, { equals: String(value) }])
+        )
+      }
+
+      const result = this.hasFunction('mindcache_recall_advanced')
+        ? this.rustLib.mindcache_recall_advanced(this.cachePtr, JSON.stringify(queryFilter))
+        : this.rustLib.mindcache_recall(this.cachePtr, userId, query, sessionId, limit)
 
       if (!result) {
         return []
@@ -303,14 +392,10 @@ class RustBridge {
     try {
       console.log(`🧹 Running memory decay process${force ? ' (forced)' : ''}`)
 
-      const result = this.rustLib.mindcache_decay(this.cachePtr)
-
-      if (!result) {
-        throw new Error('No decay stats returned')
-      }
-
-      // Parse the JSON result
-      const decayStats = JSON.parse(result)
+      // Decay can take seconds; run it off the event loop when the library can
+      const decayStats = this.hasFunction('mindcache_decay_async')
+        ? await this.callAsync(callback => this.rustLib.mindcache_decay_async(this.cachePtr, callback, null))
+        : JSON.parse(this.rustLib.mindcache_decay(this.cachePtr))
       console.log(`✅ Decay process completed - expired: ${decayStats.memories_expired}, compressed: ${decayStats.memories_compressed}`)
 
       return decayStats
@@ -488,7 +573,9 @@ class RustBridge {
     this.ensureInitialized()
 
     try {
-      const memories = await this.recallMemories({ userId, limit: 100000 })
+      const memories = this.hasFunction('mindcache_export_async')
+        ? await this.callAsync(callback => this.rustLib.mindcache_export_async(this.cachePtr, userId, callback, null))
+        : await this.recallMemories({ userId, limit: 100000 })
       const exportData = JSON.stringify(memories, null, 2)
 
       console.log(`✅ Exported ${memories.length} memories for user ${userId}`)
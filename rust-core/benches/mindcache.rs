@@ -0,0 +1,222 @@
+//! Criterion benchmarks for MindCache
+//!
+//! Run with `cargo bench`. To track regressions, save a named baseline
+//! before a change and compare against it after:
+//!
+//! ```text
+//! cargo bench -- --save-baseline main
+//! cargo bench -- --baseline main
+//! ```
+//!
+//! To compare storage backends or config settings on your own hardware,
+//! use the `mindcache-bench` binary (`--features bench`) instead.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use mindcache_core::*;
+use std::ffi::CString;
+use std::ptr;
+use std::time::{Duration, Instant};
+use tempfile::TempDir;
+
+fn test_config(temp_dir: &TempDir) -> MindCacheConfig {
+    MindCacheConfig {
+        storage_path: temp_dir.path().to_str().unwrap().to_string(),
+        auto_decay_enabled: false,
+        decay_interval_hours: 24,
+        default_memory_ttl_hours: Some(24),
+        enable_compression: true,
+        max_memories_per_user: 10000,
+        importance_threshold: 0.3,
+        ..Default::default()
+    }
+}
+
+fn create_test_cache() -> (MindCache, TempDir) {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let cache = MindCache::with_config(test_config(&temp_dir)).expect("Failed to create test cache");
+    (cache, temp_dir)
+}
+
+fn bench_save_operations(c: &mut Criterion) {
+    let (mut cache, _temp_dir) = create_test_cache();
+    let user_id = "bench_user";
+    let session_id = cache.create_session(user_id, Some("Benchmark Session")).expect("Should create session");
+
+    c.bench_function("save_memory", |b| {
+        let mut counter = 0;
+        b.iter(|| {
+            let content = format!("Benchmark memory {}", counter);
+            counter += 1;
+            cache.save(black_box(user_id), black_box(&session_id), black_box(&content), black_box(None))
+                .expect("Should save memory")
+        })
+    });
+}
+
+fn bench_recall_operations(c: &mut Criterion) {
+    let (mut cache, _temp_dir) = create_test_cache();
+    let user_id = "bench_recall_user";
+    let session_id = cache.create_session(user_id, Some("Recall Benchmark")).expect("Should create session");
+
+    // Pre-populate with test data
+    for i in 0..1000 {
+        let content = format!("Benchmark recall memory {} about trading stocks", i);
+        cache.save(user_id, &session_id, &content, None).expect("Should save memory");
+    }
+
+    let mut group = c.benchmark_group("recall_operations");
+
+    group.bench_function("recall_with_keyword", |b| {
+        b.iter(|| {
+            cache.recall(black_box(user_id), black_box(Some("trading")), black_box(None), black_box(Some(10)))
+                .expect("Should recall memories")
+        })
+    });
+
+    group.bench_function("recall_with_phrase", |b| {
+        let filter = QueryFilter {
+            user_id: Some(user_id.to_string()),
+            keywords: Some(vec!["trading stocks".to_string()]),
+            limit: Some(10),
+            ..Default::default()
+        };
+        b.iter(|| cache.recall_advanced(black_box(filter.clone())).expect("Should recall memories"))
+    });
+
+    group.bench_function("recall_all_session", |b| {
+        b.iter(|| {
+            cache.recall(black_box(user_id), black_box(None), black_box(Some(&session_id)), black_box(Some(50)))
+                .expect("Should recall memories")
+        })
+    });
+
+    group.bench_function("recall_no_filter", |b| {
+        b.iter(|| {
+            cache.recall(black_box(user_id), black_box(None), black_box(None), black_box(Some(20)))
+                .expect("Should recall memories")
+        })
+    });
+
+    group.finish();
+}
+
+fn bench_session_operations(c: &mut Criterion) {
+    let (mut cache, _temp_dir) = create_test_cache();
+    let user_id = "bench_session_user";
+
+    // Pre-populate with sessions and memories
+    let mut session_ids = Vec::new();
+    for i in 0..10 {
+        let session_id = cache.create_session(user_id, Some(&format!("Benchmark Session {}", i)))
+            .expect("Should create session");
+        for j in 0..50 {
+            let content = format!("Session {} memory {} about various topics", i, j);
+            cache.save(user_id, &session_id, &content, None).expect("Should save memory");
+        }
+        session_ids.push(session_id);
+    }
+
+    let mut group = c.benchmark_group("session_operations");
+
+    group.bench_function("get_user_sessions", |b| {
+        b.iter(|| cache.get_user_sessions(black_box(user_id)).expect("Should get user sessions"))
+    });
+
+    group.bench_function("summarize_session", |b| {
+        let session_id = &session_ids[0];
+        b.iter(|| cache.summarize_session(black_box(session_id)).expect("Should summarize session"))
+    });
+
+    group.bench_function("search_sessions", |b| {
+        b.iter(|| {
+            cache.search_sessions(black_box(user_id), black_box(vec!["memory".to_string()]))
+                .expect("Should search sessions")
+        })
+    });
+
+    group.finish();
+}
+
+fn bench_decay_operations(c: &mut Criterion) {
+    let (mut cache, _temp_dir) = create_test_cache();
+    let user_id = "bench_decay_user";
+    let session_id = cache.create_session(user_id, Some("Decay Benchmark")).expect("Should create session");
+
+    c.bench_function("decay_process", |b| {
+        b.iter_custom(|iters| {
+            let mut elapsed = Duration::ZERO;
+            for _ in 0..iters {
+                // Setup: Add memories with different importance levels
+                for i in 0..100 {
+                    let importance = (i % 10) as f32 / 10.0;
+                    let content = format!("Decay benchmark memory {}", i);
+                    cache.save_with_options(user_id, &session_id, &content, None, importance, Some(1))
+                        .expect("Should save memory");
+                }
+                let start = Instant::now();
+                black_box(cache.decay().expect("Should run decay"));
+                elapsed += start.elapsed();
+            }
+            elapsed
+        })
+    });
+}
+
+fn bench_c_api_operations(c: &mut Criterion) {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let config = CString::new(serde_json::to_string(&test_config(&temp_dir)).unwrap()).unwrap();
+    let cache_ptr = mindcache_init_with_config(config.as_ptr());
+    assert!(!cache_ptr.is_null());
+
+    let user_id = CString::new("c_api_bench_user").unwrap();
+    let session_id = CString::new("c_api_bench_session").unwrap();
+
+    let mut group = c.benchmark_group("c_api_operations");
+
+    group.bench_function("c_api_save", |b| {
+        let mut counter = 0;
+        b.iter(|| {
+            let content = CString::new(format!("C API benchmark memory {}", counter)).unwrap();
+            counter += 1;
+            let result = mindcache_save(
+                black_box(cache_ptr),
+                black_box(user_id.as_ptr()),
+                black_box(session_id.as_ptr()),
+                black_box(content.as_ptr()),
+                black_box(ptr::null()),
+            );
+            mindcache_free_string(result);
+        })
+    });
+
+    group.bench_function("c_api_recall", |b| {
+        let query = CString::new("benchmark").unwrap();
+        b.iter(|| {
+            let result = mindcache_recall(
+                black_box(cache_ptr),
+                black_box(user_id.as_ptr()),
+                black_box(query.as_ptr()),
+                black_box(ptr::null()),
+                black_box(10),
+            );
+            mindcache_free_string(result);
+        })
+    });
+
+    group.bench_function("c_api_get_stats", |b| {
+        b.iter(|| mindcache_free_string(mindcache_get_stats(black_box(cache_ptr))))
+    });
+
+    group.finish();
+    mindcache_destroy(cache_ptr);
+}
+
+criterion_group!(
+    benches,
+    bench_save_operations,
+    bench_recall_operations,
+    bench_session_operations,
+    bench_decay_operations,
+    bench_c_api_operations
+);
+criterion_main!(benches);
@@ -0,0 +1,37 @@
+// Generates the gRPC service stubs for the `grpc` feature.
+//
+// Messages are defined by hand in src/grpc.rs (mirroring proto/mindcache.proto)
+// so building does not need `protoc`; only the service plumbing is generated.
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+
+    #[cfg(feature = "grpc")]
+    generate_grpc_service();
+}
+
+#[cfg(feature = "grpc")]
+fn generate_grpc_service() {
+    use tonic_build::manual::{Builder, Method, Service};
+
+    let method = |name: &str, route: &str, input: &str, output: &str| {
+        Method::builder()
+            .name(name)
+            .route_name(route)
+            .input_type(format!("crate::grpc::{}", input))
+            .output_type(format!("crate::grpc::{}", output))
+            .codec_path("tonic_prost::ProstCodec")
+    };
+
+    let service = Service::builder()
+        .name("MindCache")
+        .package("mindcache.v1")
+        .method(method("save", "Save", "SaveRequest", "SaveResponse").build())
+        .method(method("recall", "Recall", "RecallRequest", "RecallResponse").build())
+        .method(method("summarize", "Summarize", "SummarizeRequest", "SessionSummary").build())
+        .method(method("decay", "Decay", "DecayRequest", "DecayStats").build())
+        .method(method("watch", "Watch", "WatchRequest", "Memory").server_streaming().build())
+        .build();
+
+    Builder::new().compile(&[service]);
+}
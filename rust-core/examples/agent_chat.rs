@@ -0,0 +1,60 @@
+//! Memory-augmented chat agent example
+//!
+//! Type messages on stdin; each turn shows the context the agent recalled
+//! before replying. Run it twice with different session names to see memories
+//! carry over:
+//!
+//!   cargo run --example agent_chat --features agent -- monday
+//!   cargo run --example agent_chat --features agent -- tuesday
+
+use std::io::{self, BufRead, Write};
+
+use mindcache_core::agent::ChatAgent;
+use mindcache_core::context::ContextBuilder;
+use mindcache_core::{MindCache, MindCacheConfig};
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let session = std::env::args().nth(1).unwrap_or_else(|| "chat".to_string());
+
+    let config = MindCacheConfig {
+        storage_path: "./agent_chat_data".to_string(),
+        // The agent runs decay itself every few turns
+        auto_decay_enabled: false,
+        default_memory_ttl_hours: Some(24 * 7),
+        ..Default::default()
+    };
+    let cache = MindCache::with_config(config)?;
+
+    // Keep the prompt context small so the budget is visible in a short chat
+    let mut agent = ChatAgent::new(cache, "example_user", &session)
+        .with_context_builder(ContextBuilder::new().with_token_budget(150))
+        .with_decay_every(10);
+
+    println!("🧠 MindCache agent (session '{}'). Type a message, or 'quit'.", session);
+    let stdin = io::stdin();
+    loop {
+        print!("> ");
+        io::stdout().flush()?;
+
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line)? == 0 {
+            break;
+        }
+        let message = line.trim();
+        if message.is_empty() {
+            continue;
+        }
+        if message == "quit" {
+            break;
+        }
+
+        let turn = agent.respond(message)?;
+        println!("{}", turn.reply);
+        println!("  (saved with importance {:.2}, {} memories left out of the context)", turn.importance, turn.context.omitted);
+        if let Some(decay) = turn.decay {
+            println!("  (decay pass: {} expired, {} compressed)", decay.memories_expired, decay.memories_compressed);
+        }
+    }
+
+    Ok(())
+}
@@ -19,6 +19,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         enable_compression: true,
         max_memories_per_user: 1000,
         importance_threshold: 0.2,
+        ..Default::default()
     };
 
     let mut cache = MindCache::with_config(config)?;
@@ -78,7 +78,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             let memory_id = memory_id_cstr.to_str()?;
             memory_ids.push(memory_id.to_string());
             println!("   ✅ Saved: {} (ID: {})", 
-                    if content.len() > 30 { &content[..30] } else { content },
+                    text::truncate(content, 30),
                     memory_id);
             
             // Free the returned string
@@ -110,7 +110,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         if recall_json.contains("AAPL") {
             println!("   ✅ Successfully recalled AAPL-related memories");
             println!("   📄 Result preview: {}...", 
-                    if recall_json.len() > 100 { &recall_json[..100] } else { recall_json });
+                    text::truncate(recall_json, 100));
         } else {
             println!("   ⚠️ No AAPL memories found");
         }
@@ -149,7 +149,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         
         println!("   ✅ Summary generated successfully");
         println!("   📄 Summary preview: {}...", 
-                if summary_json.len() > 150 { &summary_json[..150] } else { summary_json });
+                text::truncate(summary_json, 150));
         
         mindcache_free_string(summary_result);
     }
@@ -168,7 +168,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         
         println!("   ✅ Decay process completed");
         println!("   📊 Decay stats: {}...", 
-                if decay_json.len() > 100 { &decay_json[..100] } else { decay_json });
+                text::truncate(decay_json, 100));
         
         mindcache_free_string(decay_result);
     }
@@ -187,7 +187,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         
         println!("   ✅ Statistics retrieved successfully");
         println!("   📈 Stats preview: {}...", 
-                if stats_json.len() > 200 { &stats_json[..200] } else { stats_json });
+                text::truncate(stats_json, 200));
         
         mindcache_free_string(stats_result);
     }
@@ -6,9 +6,9 @@
 //! - Custom decay policies
 //! - Storage optimization
 
-use mindcache_core::{MindCache, MindCacheConfig, DecayPolicy};
+use mindcache_core::{text, MindCache, MindCacheConfig, DecayPolicy};
 use std::collections::HashMap;
-use chrono::{Utc, Duration};
+use chrono::Utc;
 use std::thread::sleep;
 use std::time::Duration as StdDuration;
 
@@ -27,6 +27,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         enable_compression: true,
         max_memories_per_user: 50, // Low limit for demo
         importance_threshold: 0.4,
+        ..Default::default()
     };
 
     let mut cache = MindCache::with_config(config.clone())?;
@@ -79,11 +80,9 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("✅ Created {} memories with varying importance levels\n", memories_data.len());
 
     // Show initial statistics
-    let initial_stats = cache.get_stats();
+    let initial_stats = cache.get_stats()?;
     println!("📊 Initial Statistics:");
-    if let Some(storage_stats) = initial_stats.get("storage") {
-        println!("   💾 {}", serde_json::to_string_pretty(storage_stats)?);
-    }
+    println!("   💾 {}", serde_json::to_string_pretty(&initial_stats.storage)?);
     println!();
 
     // Wait a bit to let some memories "age"
@@ -99,11 +98,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                 i + 1, 
                 memory.importance, 
                 age_seconds,
-                if memory.content.len() > 50 { 
-                    format!("{}...", &memory.content[..50]) 
-                } else { 
-                    memory.content.clone() 
-                });
+                text::preview(&memory.content, 50));
     }
     println!("   Total: {} memories\n", all_memories.len());
 
@@ -112,17 +107,23 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     
     let custom_policy = DecayPolicy {
         max_age_hours: 0, // Very aggressive - expire everything older than 0 hours
-        importance_threshold: 0.5, // Only preserve high importance
+        delete_below: 0.5, // Only preserve high importance
+        compress_below: 0.6,
+        protect_above: 0.9, // Never touch critical memories
         max_memories_per_user: 20,
         compression_enabled: true,
+        cluster_similarity: 0.1, // Only merge memories about the same topic
         auto_summarize_sessions: true,
     };
 
     // Update cache with custom decay policy
     let updated_config = MindCacheConfig {
-        importance_threshold: custom_policy.importance_threshold,
+        importance_threshold: custom_policy.delete_below,
+        compress_threshold: Some(custom_policy.compress_below),
+        protect_threshold: Some(custom_policy.protect_above),
         max_memories_per_user: custom_policy.max_memories_per_user,
         enable_compression: custom_policy.compression_enabled,
+        compression_similarity: custom_policy.cluster_similarity,
         ..config
     };
     cache.update_config(updated_config)?;
@@ -238,33 +239,23 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
        sorted_memories.sort_by(|a, b| b.importance.partial_cmp(&a.importance).unwrap());
        for memory in sorted_memories.iter().take(5) {
            println!("     • [⭐{:.1}] {}", memory.importance, 
-                   if memory.content.len() > 40 { 
-                       format!("{}...", &memory.content[..40]) 
-                   } else { 
-                       memory.content.clone() 
-                   });
+                   text::preview(&memory.content, 40));
        }
    }
    println!();
 
    // Final statistics
    println!("📊 Final System Statistics...\n");
-   let final_stats = cache.get_stats();
+   let final_stats = cache.get_stats()?;
    
-   if let Some(storage_stats) = final_stats.get("storage") {
-       println!("Storage Stats:");
-       println!("   {}", serde_json::to_string_pretty(storage_stats)?);
-   }
+   println!("Storage Stats:");
+   println!("   {}", serde_json::to_string_pretty(&final_stats.storage)?);
    
-   if let Some(session_stats) = final_stats.get("sessions") {
-       println!("Session Stats:");
-       println!("   {}", serde_json::to_string_pretty(session_stats)?);
-   }
+   println!("Session Stats:");
+   println!("   {}", serde_json::to_string_pretty(&final_stats.sessions)?);
    
-   if let Some(decay_stats) = final_stats.get("decay") {
-       println!("Decay Stats:");
-       println!("   {}", serde_json::to_string_pretty(decay_stats)?);
-   }
+   println!("Decay Stats:");
+   println!("   {}", serde_json::to_string_pretty(&final_stats.decay)?);
 
    println!("\n✅ Memory decay example completed!");
    println!("🔍 Key Insights:");
@@ -174,6 +174,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         date_to: None,
         limit: Some(5),
         min_importance: Some(0.7),
+        ..Default::default()
     };
 
     let important_memories = cache.recall_advanced(filter)?;
@@ -206,7 +207,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     for memory in &all_memories {
         session_timeline
             .entry(memory.session_id.clone())
-            .or_insert_with(Vec::new)
+            .or_default()
             .push(memory.timestamp);
     }
 
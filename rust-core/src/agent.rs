@@ -0,0 +1,276 @@
+//! A minimal memory-augmented chat agent
+//!
+//! Each turn recalls memories related to the user's message, packs them into a
+//! context window, produces a reply and saves the message with a heuristic
+//! importance score. There is no language model here: the reply just reports
+//! what the agent remembers, which makes it easy to see recall, context
+//! building, importance and decay working together. Swap `reply` for a model
+//! call to turn it into a real assistant.
+//!
+//! Sessions that went quiet a while ago are brought in through their stored
+//! summary, when `summarize_session` has made one, instead of message by
+//! message.
+
+use std::collections::HashMap;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use crate::context::{ContextBuilder, ContextWindow};
+use crate::session::{SUMMARIZED_SESSION_KEY, SUMMARY_SESSION};
+use crate::stopwords::StopWords;
+use crate::{DecayStats, MemoryItem, MindCache, QueryFilter, SessionSummary};
+
+/// Phrases that suggest the user wants something remembered
+const REMEMBER_CUES: &[&str] = &["remember", "don't forget", "always", "never", "my name", "i prefer", "i like", "allergic"];
+
+/// Decay runs after this many turns
+pub const DEFAULT_DECAY_EVERY: usize = 20;
+
+/// Days after its last related memory that a session is recalled through its summary
+pub const DEFAULT_SUMMARY_AFTER_DAYS: i64 = 7;
+
+/// Result of one chat turn
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AgentTurn {
+    pub reply: String,
+    pub context: ContextWindow,
+    pub saved_id: String,
+    pub importance: f32,
+    /// Set when this turn triggered a decay pass
+    pub decay: Option<DecayStats>,
+}
+
+pub struct ChatAgent {
+    cache: MindCache,
+    user_id: String,
+    session_id: String,
+    context_builder: ContextBuilder,
+    decay_every: usize,
+    summary_after: Duration,
+    turns: usize,
+}
+
+/// Heuristic importance of a chat message, 0.0 to 1.0
+///
+/// Explicit "remember this" cues, questions and longer messages score higher
+/// than small talk.
+pub fn score_importance(message: &str) -> f32 {
+    let lower = message.to_lowercase();
+    let mut score: f32 = 0.3;
+    if REMEMBER_CUES.iter().any(|cue| lower.contains(cue)) {
+        score += 0.4;
+    }
+    if message.trim_end().ends_with('?') {
+        score += 0.1;
+    }
+    if message.split_whitespace().count() > 12 {
+        score += 0.1;
+    }
+    score.min(1.0)
+}
+
+/// Content words of a message, used as recall keywords
+fn keywords(message: &str, stop_words: &StopWords) -> Vec<String> {
+    let is_stop_word = stop_words.for_text(message);
+    let mut words: Vec<String> = message
+        .split(|c: char| !c.is_alphanumeric())
+        .map(str::to_lowercase)
+        .filter(|word| word.len() > 3 && !is_stop_word(word))
+        .collect();
+    words.sort();
+    words.dedup();
+    words
+}
+
+impl ChatAgent {
+    pub fn new(cache: MindCache, user_id: &str, session_id: &str) -> Self {
+        ChatAgent {
+            cache,
+            user_id: user_id.to_string(),
+            session_id: session_id.to_string(),
+            context_builder: ContextBuilder::new(),
+            decay_every: DEFAULT_DECAY_EVERY,
+            summary_after: Duration::days(DEFAULT_SUMMARY_AFTER_DAYS),
+            turns: 0,
+        }
+    }
+
+    pub fn with_context_builder(mut self, context_builder: ContextBuilder) -> Self {
+        self.context_builder = context_builder;
+        self
+    }
+
+    /// Run decay every `turns` turns (0 disables it)
+    pub fn with_decay_every(mut self, turns: usize) -> Self {
+        self.decay_every = turns;
+        self
+    }
+
+    /// Recall other sessions through their stored summary once their
+    /// matching memories are all older than `age`
+    pub fn with_summaries_after(mut self, age: Duration) -> Self {
+        self.summary_after = age;
+        self
+    }
+
+    pub fn cache(&mut self) -> &mut MindCache {
+        &mut self.cache
+    }
+
+    /// Handle one user message
+    pub fn respond(&mut self, message: &str) -> Result<AgentTurn, Box<dyn std::error::Error>> {
+        let context = self.build_context(message)?;
+        let reply = Self::reply(&context);
+
+        let importance = score_importance(message);
+        let saved_id = self.cache.save_message(&self.user_id, &self.session_id, "user", message, importance)?;
+
+        self.turns += 1;
+        let decay = if self.decay_every > 0 && self.turns.is_multiple_of(self.decay_every) {
+            Some(self.cache.decay()?)
+        } else {
+            None
+        };
+
+        Ok(AgentTurn { reply, context, saved_id, importance, decay })
+    }
+
+    /// Context for a message: related memories from any session, old
+    /// sessions summarized where possible, plus the latest turns of this one
+    pub fn build_context(&self, message: &str) -> Result<ContextWindow, Box<dyn std::error::Error>> {
+        let mut candidates = Vec::new();
+
+        let keywords = keywords(message, &self.cache.stop_words());
+        if !keywords.is_empty() {
+            let related = self.cache.recall_advanced(QueryFilter {
+                user_id: Some(self.user_id.clone()),
+                session_id: None,
+                keywords: Some(keywords),
+                date_from: None,
+                date_to: None,
+                limit: Some(50),
+                min_importance: None,
+                ..Default::default()
+            })?;
+            candidates.extend(self.summarize_old_sessions(related)?);
+        }
+
+        let mut recent = self.cache.get_session_memories(&self.user_id, &self.session_id)?;
+        recent.sort_by_key(|memory| std::cmp::Reverse(memory.timestamp));
+        candidates.extend(recent.into_iter().take(5));
+
+        Ok(self.context_builder.build(&candidates))
+    }
+
+    /// Replace the memories of sessions last matched before the summary
+    /// cutoff with those sessions' stored summaries
+    ///
+    /// Summaries matched directly are kept only for sessions old enough to be summarized.
+    fn summarize_old_sessions(&self, memories: Vec<MemoryItem>) -> Result<Vec<MemoryItem>, Box<dyn std::error::Error>> {
+        let cutoff = self.cache.storage.now() - self.summary_after;
+        let (summarized, memories): (Vec<MemoryItem>, Vec<MemoryItem>) =
+            memories.into_iter().partition(|memory| memory.session_id == SUMMARY_SESSION);
+        let mut newest: HashMap<&str, DateTime<Utc>> = HashMap::new();
+        for memory in &memories {
+            let entry = newest.entry(memory.session_id.as_str()).or_insert(memory.timestamp);
+            *entry = (*entry).max(memory.timestamp);
+        }
+
+        let mut summaries: HashMap<String, MemoryItem> = HashMap::new();
+        for (session_id, last_matched) in newest {
+            if session_id == self.session_id || last_matched >= cutoff {
+                continue;
+            }
+            if let Some(summary) = self.cache.get_memory(&SessionSummary::memory_id(session_id))? {
+                if summary.user_id == self.user_id {
+                    summaries.insert(session_id.to_string(), summary);
+                }
+            }
+        }
+
+        let mut kept: Vec<MemoryItem> = memories
+            .into_iter()
+            .filter(|memory| !summaries.contains_key(&memory.session_id))
+            .collect();
+        kept.extend(summaries.into_values());
+        kept.extend(summarized.into_iter().filter(|summary| {
+            summary.timestamp < cutoff
+                && summary.metadata.get(SUMMARIZED_SESSION_KEY).is_some_and(|session_id| *session_id != self.session_id)
+        }));
+        Ok(kept)
+    }
+
+    fn reply(context: &ContextWindow) -> String {
+        if context.is_empty() {
+            return "Noted. I don't remember anything related yet.".to_string();
+        }
+        format!(
+            "Noted. Here is what I remember that seems related ({} memories):\n{}",
+            context.included_ids.len(),
+            context.text
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{test_config, temp_cache};
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_agent_recalls_earlier_turns_across_sessions() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = test_config(&temp_dir);
+
+        let mut agent = ChatAgent::new(MindCache::with_config(config.clone()).unwrap(), "alice", "monday")
+            .with_decay_every(2);
+        let first = agent.respond("Please remember that my favourite editor is Helix").unwrap();
+        assert!(first.context.is_empty());
+        assert!(first.importance > 0.5);
+        assert!(first.decay.is_none());
+        assert!(agent.respond("Nice weather today").unwrap().decay.is_some());
+
+        drop(agent);
+        let mut agent = ChatAgent::new(MindCache::with_config(config).unwrap(), "alice", "tuesday");
+        let turn = agent.respond("Which editor should I configure?").unwrap();
+        assert_eq!(turn.context.included_ids, vec![first.saved_id]);
+        assert!(turn.reply.contains("Helix"));
+    }
+
+    #[test]
+    fn test_old_sessions_are_recalled_through_their_summary() {
+        let (mut cache, _temp_dir) = temp_cache();
+        let month_ago = Utc::now() - Duration::days(30);
+        for content in ["Planning the Lisbon trip", "Lisbon hotel is booked"] {
+            cache.storage.save(MemoryItem {
+                user_id: "alice".to_string(),
+                session_id: "march".to_string(),
+                content: content.to_string(),
+                timestamp: month_ago,
+                importance: 0.5,
+                ..Default::default()
+            }).unwrap();
+        }
+        let recent = cache.save("alice", "yesterday", "Lisbon weather looks sunny", None).unwrap();
+
+        // Without a stored summary the raw memories are used
+        let agent = ChatAgent::new(cache, "alice", "today");
+        assert_eq!(agent.build_context("Tell me about Lisbon").unwrap().included_ids.len(), 3);
+
+        let mut agent = agent;
+        agent.cache().summarize_session("march").unwrap();
+        agent.cache().summarize_session("yesterday").unwrap();
+        let context = agent.build_context("Tell me about Lisbon").unwrap();
+        let mut included = context.included_ids.clone();
+        included.sort();
+        let mut expected = vec![recent, SessionSummary::memory_id("march")];
+        expected.sort();
+        assert_eq!(included, expected);
+    }
+
+    #[test]
+    fn test_score_importance() {
+        assert!(score_importance("Remember I'm allergic to peanuts") > score_importance("ok"));
+        assert!(score_importance("What time is it?") > score_importance("it is late"));
+    }
+}
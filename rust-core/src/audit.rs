@@ -0,0 +1,262 @@
+//! Append-only audit log of mutations
+//!
+//! When enabled, every save, update, delete, import, quota eviction, decay
+//! expiry, user erasure and trash operation appends an [`AuditEntry`] to
+//! `audit.log` (one JSON object per line): who did it, when, and to which
+//! memory. Entries hold ids only, never memory contents, so they are kept
+//! even when a user is erased. `MindCache::audit_trail` queries the log.
+//!
+//! "Who" is the actor set with `MindCache::set_audit_actor`, `"system"`
+//! unless changed.
+
+use std::sync::{Arc, Mutex};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use crate::backend::StorageBackend;
+use crate::clock::SharedClock;
+use crate::jsonl;
+use crate::storage::MemoryItem;
+use crate::MindCache;
+
+pub const AUDIT_LOG: &str = "audit.log";
+
+/// Actor recorded until `set_audit_actor` is called
+pub const DEFAULT_ACTOR: &str = "system";
+
+/// What happened to a memory
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditAction {
+    Save,
+    Update,
+    Delete,
+    /// Saved by `import_memories`
+    Import,
+    /// Deleted to keep the user within their quota
+    Evict,
+    /// Deleted by a decay run, past its TTL or over a limit
+    Expire,
+    /// Erased with the rest of its user by `delete_user`
+    EraseUser,
+    /// Moved to the trash by `delete_memory_soft`
+    Trash,
+    /// Brought back from the trash
+    Restore,
+    /// Dropped from the trash for good
+    Purge,
+}
+
+/// One entry of the audit log
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub timestamp: DateTime<Utc>,
+    pub action: AuditAction,
+    pub actor: String,
+    pub user_id: String,
+    pub session_id: String,
+    pub memory_id: String,
+}
+
+/// Which audit entries `MindCache::audit_trail` returns; unset fields match everything
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AuditFilter {
+    pub user_id: Option<String>,
+    pub memory_id: Option<String>,
+    pub actor: Option<String>,
+    /// Any of these actions
+    pub actions: Option<Vec<AuditAction>>,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    /// At most this many entries, the most recent ones
+    pub limit: Option<usize>,
+}
+
+impl AuditFilter {
+    pub fn matches(&self, entry: &AuditEntry) -> bool {
+        self.user_id.as_ref().is_none_or(|user_id| entry.user_id == *user_id)
+            && self.memory_id.as_ref().is_none_or(|memory_id| entry.memory_id == *memory_id)
+            && self.actor.as_ref().is_none_or(|actor| entry.actor == *actor)
+            && self.actions.as_ref().is_none_or(|actions| actions.contains(&entry.action))
+            && self.from.is_none_or(|from| entry.timestamp >= from)
+            && self.to.is_none_or(|to| entry.timestamp <= to)
+    }
+}
+
+struct AuditState {
+    enabled: bool,
+    actor: String,
+}
+
+/// Handle to the audit log, shared by every clone of a storage handle
+#[derive(Clone)]
+pub struct AuditLog {
+    backend: Arc<dyn StorageBackend>,
+    clock: SharedClock,
+    state: Arc<Mutex<AuditState>>,
+}
+
+impl AuditLog {
+    /// A disabled log; call `enable` to start recording
+    pub fn new(backend: Arc<dyn StorageBackend>, clock: SharedClock) -> Self {
+        AuditLog {
+            backend,
+            clock,
+            state: Arc::new(Mutex::new(AuditState { enabled: false, actor: DEFAULT_ACTOR.to_string() })),
+        }
+    }
+
+    pub fn enable(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let mut state = self.lock_state();
+        if state.enabled {
+            return Ok(());
+        }
+
+        jsonl::seal(self.backend.as_ref(), AUDIT_LOG)?;
+        state.enabled = true;
+        Ok(())
+    }
+
+    pub fn disable(&self) {
+        self.lock_state().enabled = false;
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.lock_state().enabled
+    }
+
+    /// Attribute entries recorded from now on to `actor`
+    pub fn set_actor(&self, actor: &str) {
+        self.lock_state().actor = actor.to_string();
+    }
+
+    pub fn actor(&self) -> String {
+        self.lock_state().actor.clone()
+    }
+
+    /// Append an entry for `memory`; does nothing while disabled
+    pub fn record(&self, action: AuditAction, memory: &MemoryItem) -> Result<(), Box<dyn std::error::Error>> {
+        let state = self.lock_state();
+        if !state.enabled {
+            return Ok(());
+        }
+
+        let entry = AuditEntry {
+            timestamp: self.clock.now(),
+            action,
+            actor: state.actor.clone(),
+            user_id: memory.user_id.clone(),
+            session_id: memory.session_id.clone(),
+            memory_id: memory.id.clone(),
+        };
+        jsonl::append(self.backend.as_ref(), AUDIT_LOG, &entry)
+    }
+
+    /// Entries matching `filter`, oldest first
+    pub fn query(&self, filter: &AuditFilter) -> Result<Vec<AuditEntry>, Box<dyn std::error::Error>> {
+        let mut entries: Vec<AuditEntry> = jsonl::read(self.backend.as_ref(), AUDIT_LOG)?;
+        entries.retain(|entry| filter.matches(entry));
+        if let Some(limit) = filter.limit {
+            entries.drain(..entries.len().saturating_sub(limit));
+        }
+        Ok(entries)
+    }
+
+    fn lock_state(&self) -> std::sync::MutexGuard<'_, AuditState> {
+        self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl MindCache {
+    /// Attribute mutations from now on to `actor` in the audit log,
+    /// e.g. the agent worker or operator making them
+    pub fn set_audit_actor(&self, actor: &str) {
+        self.storage.audit().set_actor(actor);
+    }
+
+    /// Audit log entries matching `filter`, oldest first
+    ///
+    /// Needs `audit_log_enabled`; mutations made while it was off are not recorded.
+    pub fn audit_trail(&self, filter: AuditFilter) -> Result<Vec<AuditEntry>, Box<dyn std::error::Error>> {
+        self.storage.audit().query(&filter)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{MemoryUpdate, MindCacheConfig, QuotaPolicy};
+    use crate::test_support::temp_cache_with;
+    use tempfile::TempDir;
+
+    fn audited_cache() -> (MindCache, TempDir) {
+        temp_cache_with(MindCacheConfig {
+            audit_log_enabled: true,
+            max_memories_per_user: 2,
+            quota_policy: QuotaPolicy::EvictOldest,
+            ..Default::default()
+        })
+    }
+
+    fn actions(cache: &MindCache, memory_id: &str) -> Vec<AuditAction> {
+        let filter = AuditFilter { memory_id: Some(memory_id.to_string()), ..Default::default() };
+        cache.audit_trail(filter).unwrap().into_iter().map(|entry| entry.action).collect()
+    }
+
+    #[test]
+    fn test_saves_and_updates_are_recorded() {
+        let (mut cache, _temp_dir) = audited_cache();
+        let id = cache.save("alice", "s1", "first", None).unwrap();
+        cache.update_memory(&id, MemoryUpdate { importance: Some(0.9), ..Default::default() }).unwrap();
+
+        assert_eq!(actions(&cache, &id), [AuditAction::Save, AuditAction::Update]);
+    }
+
+    #[test]
+    fn test_quota_eviction_is_recorded() {
+        let (mut cache, _temp_dir) = audited_cache();
+        let first = cache.save("alice", "s1", "first", None).unwrap();
+        cache.save("alice", "s1", "second", None).unwrap();
+        cache.save("alice", "s1", "third evicts the first", None).unwrap();
+
+        assert_eq!(actions(&cache, &first), [AuditAction::Save, AuditAction::Evict]);
+    }
+
+    #[test]
+    fn test_erasure_and_import_are_recorded() {
+        let (mut cache, _temp_dir) = audited_cache();
+        let id = cache.save("alice", "s1", "first", None).unwrap();
+        let export = cache.export_user_memories("alice").unwrap();
+        cache.delete_user("alice").unwrap();
+        cache.import_memories(&export).unwrap();
+
+        assert_eq!(actions(&cache, &id), [AuditAction::Save, AuditAction::EraseUser, AuditAction::Import]);
+    }
+
+    #[test]
+    fn test_entries_carry_the_current_actor() {
+        let (mut cache, _temp_dir) = audited_cache();
+        cache.set_audit_actor("worker-1");
+        let id = cache.save("alice", "s1", "first", None).unwrap();
+        cache.set_audit_actor("worker-2");
+        cache.update_memory(&id, MemoryUpdate { importance: Some(0.9), ..Default::default() }).unwrap();
+
+        let by_first_worker = cache.audit_trail(AuditFilter { actor: Some("worker-1".to_string()), ..Default::default() }).unwrap();
+        assert_eq!(by_first_worker.len(), 1);
+        assert_eq!(by_first_worker[0].action, AuditAction::Save);
+    }
+
+    #[test]
+    fn test_trail_filters_by_action_and_limit() {
+        let (mut cache, _temp_dir) = audited_cache();
+        cache.save("alice", "s1", "first", None).unwrap();
+        cache.save("bob", "s1", "second", None).unwrap();
+        cache.delete_user("alice").unwrap();
+        cache.delete_user("bob").unwrap();
+
+        let erasures = AuditFilter { actions: Some(vec![AuditAction::EraseUser]), ..Default::default() };
+        assert_eq!(cache.audit_trail(erasures.clone()).unwrap().len(), 2);
+        let latest = cache.audit_trail(AuditFilter { limit: Some(1), ..erasures }).unwrap();
+        assert_eq!(latest.len(), 1);
+        assert_eq!(latest[0].user_id, "bob");
+    }
+}
@@ -0,0 +1,353 @@
+//! API keys and scopes for multi-tenant deployments
+//!
+//! With `api_keys` left empty every caller may do anything, as before. Once
+//! keys are configured, callers present one (the servers read it from an
+//! `Authorization: Bearer` or `x-api-key` header) and each call goes through
+//! the [`Scoped`] cache [`MindCache::with_key`] returns for it: the key must
+//! reach the users (or spaces) the call touches and carry the scope of the
+//! operation. Operations spanning the whole store, such as decay, need a key
+//! reaching [`ALL_USERS`]. The C API has `_with_key` variants of its calls
+//! that check keys the same way.
+
+use std::fmt;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use crate::decay::DecayStats;
+use crate::erasure::DeletionReport;
+use crate::session::SessionSummary;
+use crate::spaces::space_user_id;
+use crate::storage::{CompactionReport, MemoryItem, QueryFilter, ALL_USERS};
+use crate::MindCache;
+
+/// Kind of operation a key may perform
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Scope {
+    Read,
+    Write,
+    /// Deleting users and store maintenance; also allows reads and writes
+    Admin,
+}
+
+impl fmt::Display for Scope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Scope::Read => "read",
+            Scope::Write => "write",
+            Scope::Admin => "admin",
+        })
+    }
+}
+
+/// A key and what it is allowed to do
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ApiKey {
+    pub key: String,
+    /// Shown in errors instead of the key
+    #[serde(default)]
+    pub name: String,
+    /// Users whose memories the key reaches; [`ALL_USERS`] reaches everyone
+    #[serde(default)]
+    pub users: Vec<String>,
+    /// Shared spaces whose memories the key reaches
+    #[serde(default)]
+    pub spaces: Vec<String>,
+    pub scopes: Vec<Scope>,
+}
+
+impl ApiKey {
+    pub fn allows(&self, scope: Scope) -> bool {
+        self.scopes.contains(&scope) || self.scopes.contains(&Scope::Admin)
+    }
+
+    /// Whether the key reaches `user_id`, which may be a space's user id
+    pub fn reaches(&self, user_id: &str) -> bool {
+        self.users.iter().any(|user| user == ALL_USERS || user == user_id)
+            || self.spaces.iter().any(|space_id| space_user_id(space_id) == user_id)
+    }
+
+    fn reaches_everyone(&self) -> bool {
+        self.users.iter().any(|user| user == ALL_USERS)
+    }
+}
+
+/// A call refused by access control
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AuthError {
+    MissingKey,
+    UnknownKey,
+    /// The key is valid but does not allow this call
+    Forbidden(String),
+}
+
+impl fmt::Display for AuthError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AuthError::MissingKey => write!(f, "an API key is required"),
+            AuthError::UnknownKey => write!(f, "unknown API key"),
+            AuthError::Forbidden(reason) => write!(f, "forbidden: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for AuthError {}
+
+/// What an authenticated caller may do
+#[derive(Debug, Clone)]
+pub struct Access {
+    /// `None` when access control is off
+    key: Option<ApiKey>,
+}
+
+impl Access {
+    /// Full access, as when no keys are configured
+    pub fn unrestricted() -> Self {
+        Access { key: None }
+    }
+
+    /// Check a `scope` operation on `user_id`, or on the whole store with `None`
+    pub fn check(&self, scope: Scope, user_id: Option<&str>) -> Result<(), AuthError> {
+        let Some(key) = &self.key else {
+            return Ok(());
+        };
+        if !key.allows(scope) {
+            return Err(AuthError::Forbidden(format!("key {} lacks the {} scope", key.name, scope)));
+        }
+        let reached = match user_id {
+            Some(user_id) => key.reaches(user_id),
+            None => key.reaches_everyone(),
+        };
+        if !reached {
+            let target = user_id.map_or("all users".to_string(), |user_id| format!("user {}", user_id));
+            return Err(AuthError::Forbidden(format!("key {} does not reach {}", key.name, target)));
+        }
+        Ok(())
+    }
+
+    /// Check a recall with `filter`, which must only cover users the key reaches
+    pub fn check_filter(&self, filter: &QueryFilter) -> Result<(), AuthError> {
+        match filter.users() {
+            Some(users) => users.into_iter().try_for_each(|user_id| self.check(Scope::Read, Some(user_id))),
+            None => self.check(Scope::Read, None),
+        }
+    }
+}
+
+/// Compare without stopping at the first difference, so timing does not leak how much of a key matched
+fn keys_match(presented: &str, key: &str) -> bool {
+    let (presented, key) = (presented.as_bytes(), key.as_bytes());
+    presented.len() == key.len() && presented.iter().zip(key).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+impl MindCache {
+    /// Whether `api_keys` are configured, so calls need a key
+    pub fn auth_enabled(&self) -> bool {
+        !self.config.api_keys.is_empty()
+    }
+
+    /// Resolve the key a caller presented into what they may do
+    pub fn authenticate(&self, key: Option<&str>) -> Result<Access, AuthError> {
+        if !self.auth_enabled() {
+            return Ok(Access::unrestricted());
+        }
+        let presented = key.ok_or(AuthError::MissingKey)?;
+        self.config
+            .api_keys
+            .iter()
+            .find(|api_key| keys_match(presented, &api_key.key))
+            .map(|api_key| Access { key: Some(api_key.clone()) })
+            .ok_or(AuthError::UnknownKey)
+    }
+
+    /// The cache as seen by a caller presenting `key`, checking each call against it
+    pub fn with_key(&mut self, key: Option<&str>) -> Result<Scoped<'_>, AuthError> {
+        let access = self.authenticate(key)?;
+        Ok(Scoped { cache: self, access })
+    }
+}
+
+/// A cache whose calls are checked against one caller's key
+///
+/// Calls the key does not allow fail with an [`AuthError`] before touching
+/// the store. Operations without a method here go through
+/// [`Scoped::authorize`].
+pub struct Scoped<'a> {
+    cache: &'a mut MindCache,
+    access: Access,
+}
+
+impl Scoped<'_> {
+    pub fn access(&self) -> &Access {
+        &self.access
+    }
+
+    /// The underlying cache, once the key allows a `scope` call on `user_id` (`None` for the whole store)
+    pub fn authorize(&mut self, scope: Scope, user_id: Option<&str>) -> Result<&mut MindCache, AuthError> {
+        self.access.check(scope, user_id)?;
+        Ok(self.cache)
+    }
+
+    pub fn save(&mut self, user_id: &str, session_id: &str, content: &str, metadata: Option<HashMap<String, String>>) -> Result<String, Box<dyn std::error::Error>> {
+        self.authorize(Scope::Write, Some(user_id))?.save(user_id, session_id, content, metadata)
+    }
+
+    pub fn save_with_options(&mut self, user_id: &str, session_id: &str, content: &str, metadata: Option<HashMap<String, String>>, importance: f32, ttl_hours: Option<u32>) -> Result<String, Box<dyn std::error::Error>> {
+        self.authorize(Scope::Write, Some(user_id))?.save_with_options(user_id, session_id, content, metadata, importance, ttl_hours)
+    }
+
+    pub fn recall(&self, user_id: &str, query: Option<&str>, session_id: Option<&str>, limit: Option<usize>) -> Result<Vec<MemoryItem>, Box<dyn std::error::Error>> {
+        self.access.check(Scope::Read, Some(user_id))?;
+        self.cache.recall(user_id, query, session_id, limit)
+    }
+
+    pub fn recall_advanced(&self, filter: QueryFilter) -> Result<Vec<MemoryItem>, Box<dyn std::error::Error>> {
+        self.access.check_filter(&filter)?;
+        self.cache.recall_advanced(filter)
+    }
+
+    /// Move a memory to the trash; needs write access to the memory's user
+    pub fn delete_memory_soft(&mut self, memory_id: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let memory = self.cache.get_memory(memory_id)?.ok_or_else(|| format!("memory {} not found", memory_id))?;
+        self.authorize(Scope::Write, Some(&memory.user_id))?.delete_memory_soft(memory_id)
+    }
+
+    /// Summarize a session; needs read access to the session's user
+    pub fn summarize_session(&mut self, session_id: &str) -> Result<SessionSummary, Box<dyn std::error::Error>> {
+        // The session's owner is only known once it is found
+        let summary = self.cache.summarize_session(session_id)?;
+        self.access.check(Scope::Read, Some(&summary.user_id))?;
+        Ok(summary)
+    }
+
+    pub fn delete_user(&mut self, user_id: &str) -> Result<DeletionReport, Box<dyn std::error::Error>> {
+        self.authorize(Scope::Admin, Some(user_id))?.delete_user(user_id)
+    }
+
+    pub fn decay(&mut self) -> Result<DecayStats, Box<dyn std::error::Error>> {
+        self.authorize(Scope::Admin, None)?.decay()
+    }
+
+    pub fn compact(&mut self) -> Result<CompactionReport, Box<dyn std::error::Error>> {
+        self.authorize(Scope::Admin, None)?.compact()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MindCacheConfig;
+    use crate::test_support::{test_config, temp_cache};
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_keys_limit_users_and_scopes() {
+        let temp_dir = TempDir::new().unwrap();
+        let key = |key: &str, users: &[&str], spaces: &[&str], scopes: &[Scope]| ApiKey {
+            key: key.to_string(),
+            name: key.to_string(),
+            users: users.iter().map(|user| user.to_string()).collect(),
+            spaces: spaces.iter().map(|space| space.to_string()).collect(),
+            scopes: scopes.to_vec(),
+        };
+        let cache = MindCache::with_config(MindCacheConfig {
+            api_keys: vec![
+                key("reader-key", &["alice"], &["desk"], &[Scope::Read]),
+                key("ops-key", &[ALL_USERS], &[], &[Scope::Admin]),
+            ],
+            ..test_config(&temp_dir)
+        }).unwrap();
+
+        assert_eq!(cache.authenticate(None).unwrap_err(), AuthError::MissingKey);
+        assert_eq!(cache.authenticate(Some("reader-kez")).unwrap_err(), AuthError::UnknownKey);
+
+        let reader = cache.authenticate(Some("reader-key")).unwrap();
+        assert!(reader.check(Scope::Read, Some("alice")).is_ok());
+        assert!(reader.check(Scope::Read, Some(&space_user_id("desk"))).is_ok());
+        assert!(reader.check(Scope::Read, Some("bob")).is_err());
+        assert!(reader.check(Scope::Write, Some("alice")).is_err());
+        assert!(reader.check_filter(&QueryFilter { user_id: Some("alice".to_string()), ..Default::default() }).is_ok());
+        assert!(reader.check_filter(&QueryFilter::default()).is_err());
+        let both = QueryFilter { user_ids: vec!["alice".to_string(), "bob".to_string()], ..Default::default() };
+        assert!(matches!(reader.check_filter(&both), Err(AuthError::Forbidden(_))));
+
+        let ops = cache.authenticate(Some("ops-key")).unwrap();
+        assert!(ops.check(Scope::Admin, None).is_ok());
+        assert!(ops.check(Scope::Write, Some("bob")).is_ok());
+        assert!(ops.check_filter(&both).is_ok());
+    }
+
+    fn keyed_cache() -> (MindCache, TempDir) {
+        let temp_dir = TempDir::new().unwrap();
+        let key = |key: &str, users: &[&str], scopes: &[Scope]| ApiKey {
+            key: key.to_string(),
+            name: key.to_string(),
+            users: users.iter().map(|user| user.to_string()).collect(),
+            spaces: Vec::new(),
+            scopes: scopes.to_vec(),
+        };
+        let cache = MindCache::with_config(MindCacheConfig {
+            api_keys: vec![
+                key("alice-rw", &["alice"], &[Scope::Read, Scope::Write]),
+                key("ops-key", &[ALL_USERS], &[Scope::Admin]),
+            ],
+            ..test_config(&temp_dir)
+        }).unwrap();
+        (cache, temp_dir)
+    }
+
+    fn forbidden<T: fmt::Debug>(result: Result<T, Box<dyn std::error::Error>>) -> bool {
+        matches!(result.unwrap_err().downcast_ref::<AuthError>(), Some(AuthError::Forbidden(_)))
+    }
+
+    #[test]
+    fn test_with_key_needs_a_known_key() {
+        let (mut cache, _temp_dir) = keyed_cache();
+        assert_eq!(cache.with_key(None).err().unwrap(), AuthError::MissingKey);
+        assert_eq!(cache.with_key(Some("alice-rx")).err().unwrap(), AuthError::UnknownKey);
+
+        let (mut open, _open_dir) = temp_cache();
+        open.with_key(None).unwrap().save("bob", "s1", "No keys, no checks", None).unwrap();
+        open.with_key(None).unwrap().decay().unwrap();
+    }
+
+    #[test]
+    fn test_scoped_saves_and_recalls_stay_within_the_keys_users() {
+        let (mut cache, _temp_dir) = keyed_cache();
+        let mut alice = cache.with_key(Some("alice-rw")).unwrap();
+        alice.save("alice", "s1", "Prefers window seats", None).unwrap();
+        assert!(forbidden(alice.save("bob", "s1", "Not alice's to write", None)));
+
+        assert_eq!(alice.recall("alice", None, None, None).unwrap().len(), 1);
+        assert!(forbidden(alice.recall("bob", None, None, None)));
+        assert!(forbidden(alice.recall_advanced(QueryFilter::default())));
+    }
+
+    #[test]
+    fn test_scoped_soft_delete_needs_write_access_to_the_memorys_user() {
+        let (mut cache, _temp_dir) = keyed_cache();
+        let bobs = cache.save("bob", "s1", "Bob's note", None).unwrap();
+        let alices = cache.save("alice", "s1", "Alice's note", None).unwrap();
+
+        let mut alice = cache.with_key(Some("alice-rw")).unwrap();
+        assert!(forbidden(alice.delete_memory_soft(&bobs)));
+        alice.delete_memory_soft(&alices).unwrap();
+        assert!(cache.get_memory(&bobs).unwrap().is_some());
+    }
+
+    #[test]
+    fn test_scoped_admin_calls_need_the_admin_scope() {
+        let (mut cache, _temp_dir) = keyed_cache();
+        cache.save("alice", "s1", "Prefers window seats", None).unwrap();
+
+        let mut alice = cache.with_key(Some("alice-rw")).unwrap();
+        assert!(forbidden(alice.decay()));
+        assert!(forbidden(alice.compact()));
+        assert!(forbidden(alice.delete_user("alice")));
+        assert_eq!(alice.authorize(Scope::Admin, None).err().unwrap(), AuthError::Forbidden("key alice-rw lacks the admin scope".to_string()));
+
+        let mut ops = cache.with_key(Some("ops-key")).unwrap();
+        ops.decay().unwrap();
+        ops.compact().unwrap();
+        assert_eq!(ops.delete_user("alice").unwrap().memories_deleted, 1);
+    }
+}
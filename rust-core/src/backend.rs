@@ -0,0 +1,346 @@
+//! Storage backends
+//!
+//! `MemoryStorage` keeps its record logs and index files behind the
+//! [`StorageBackend`] trait, so the byte-level persistence can be swapped
+//! (local files, object storage, fault-injecting wrappers for tests) without
+//! touching indexing, recall or decay.
+
+use std::fmt;
+use std::fs::{self, File, OpenOptions, TryLockError};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Lock file held by the process writing to a storage directory
+pub const LOCK_FILE: &str = "LOCK";
+
+/// Byte-level persistence used by `MemoryStorage`
+///
+/// A backend stores two kinds of named objects:
+/// - append-only logs (`append`, `read_at`, `flush`), used for memory records
+/// - small blobs replaced as a whole (`read_blob`, `write_blob`), used for indices
+///
+/// Names are relative paths such as `memories.bin` or `index.bin`.
+pub trait StorageBackend: Send + Sync {
+    /// Append `data` to the named log, returning the offset it was written at
+    fn append(&self, name: &str, data: &[u8]) -> io::Result<u64>;
+
+    /// Read exactly `len` bytes of the named log starting at `offset`
+    fn read_at(&self, name: &str, offset: u64, len: usize) -> io::Result<Vec<u8>>;
+
+    /// Make appended data to the named log durable
+    fn flush(&self, name: &str) -> io::Result<()>;
+
+    /// Read a whole blob, or `None` if it does not exist
+    fn read_blob(&self, name: &str) -> io::Result<Option<Vec<u8>>>;
+
+    /// Replace a whole blob atomically
+    fn write_blob(&self, name: &str, data: &[u8]) -> io::Result<()>;
+
+    /// Size in bytes of a log or blob (0 if it does not exist)
+    fn size(&self, name: &str) -> io::Result<u64>;
+
+    /// Delete a log or blob if it exists
+    fn remove(&self, name: &str) -> io::Result<()>;
+
+    /// Atomically replace `to` with `from`
+    fn rename(&self, from: &str, to: &str) -> io::Result<()>;
+
+    /// Overwrite a log or blob before deleting it, for data that must not
+    /// be recoverable; backends that can't overwrite just delete
+    fn shred(&self, name: &str) -> io::Result<()> {
+        self.remove(name)
+    }
+
+    /// Human-readable location, used in log and error messages
+    fn location(&self) -> String;
+
+    /// Whether every write will be refused; storage then skips housekeeping
+    /// writes such as index upgrades
+    fn is_read_only(&self) -> bool {
+        false
+    }
+}
+
+/// How a [`FileBackend`] may use its directory
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AccessMode {
+    /// Single writer: holds the directory's lock for as long as it is open
+    #[default]
+    ReadWrite,
+    /// Takes no lock and refuses every write; safe next to a running writer
+    ReadOnly,
+}
+
+/// Another process (or another instance in this one) has the directory open for writing
+#[derive(Debug)]
+pub struct StorageLocked {
+    pub path: PathBuf,
+    /// Process holding the lock, when it could be read from the lock file
+    pub pid: Option<u32>,
+}
+
+impl fmt::Display for StorageLocked {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "storage at {} is locked by another writer", self.path.display())?;
+        if let Some(pid) = self.pid {
+            write!(f, " (pid {})", pid)?;
+        }
+        write!(f, "; stop it or open read-only")
+    }
+}
+
+impl std::error::Error for StorageLocked {}
+
+/// Backend storing every object as a file under a root directory
+#[derive(Debug, Clone)]
+pub struct FileBackend {
+    root: PathBuf,
+    mode: AccessMode,
+    /// Released when the last clone is dropped
+    lock: Option<Arc<File>>,
+}
+
+impl FileBackend {
+    /// Create the root directory if needed, without taking its lock
+    ///
+    /// Callers must make sure no one else writes to the directory; prefer
+    /// [`FileBackend::open`].
+    pub fn new<P: AsRef<Path>>(root: P) -> io::Result<Self> {
+        fs::create_dir_all(root.as_ref())?;
+        Ok(FileBackend {
+            root: root.as_ref().to_path_buf(),
+            mode: AccessMode::ReadWrite,
+            lock: None,
+        })
+    }
+
+    /// Open a storage directory, locking it for writing in `ReadWrite` mode
+    ///
+    /// Fails with [`StorageLocked`] when another writer holds the lock. The
+    /// lock is advisory: it only keeps out other MindCache writers.
+    pub fn open<P: AsRef<Path>>(root: P, mode: AccessMode) -> Result<Self, Box<dyn std::error::Error>> {
+        let root = root.as_ref().to_path_buf();
+        if mode == AccessMode::ReadOnly {
+            if !root.is_dir() {
+                return Err(format!("storage directory {} does not exist", root.display()).into());
+            }
+            return Ok(FileBackend { root, mode, lock: None });
+        }
+
+        fs::create_dir_all(&root)?;
+        let mut lock = OpenOptions::new().create(true).truncate(false).read(true).write(true).open(root.join(LOCK_FILE))?;
+        match lock.try_lock() {
+            Ok(()) => {}
+            Err(TryLockError::WouldBlock) => {
+                let mut holder = String::new();
+                let pid = lock.read_to_string(&mut holder).ok().and_then(|_| holder.trim().parse().ok());
+                return Err(Box::new(StorageLocked { path: root, pid }));
+            }
+            Err(TryLockError::Error(e)) => return Err(e.into()),
+        }
+        // Record the holder for the error other writers will see
+        lock.set_len(0)?;
+        write!(lock, "{}", std::process::id())?;
+
+        Ok(FileBackend { root, mode, lock: Some(Arc::new(lock)) })
+    }
+
+    pub fn mode(&self) -> AccessMode {
+        self.mode
+    }
+
+    /// Whether this backend holds the directory's writer lock
+    pub fn holds_lock(&self) -> bool {
+        self.lock.is_some()
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    fn path(&self, name: &str) -> PathBuf {
+        self.root.join(name)
+    }
+
+    fn check_writable(&self) -> io::Result<()> {
+        if self.mode == AccessMode::ReadOnly {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                format!("storage at {} is open read-only", self.root.display()),
+            ));
+        }
+        Ok(())
+    }
+
+    fn ensure_parent(&self, path: &Path) -> io::Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        Ok(())
+    }
+}
+
+impl StorageBackend for FileBackend {
+    fn append(&self, name: &str, data: &[u8]) -> io::Result<u64> {
+        self.check_writable()?;
+        let path = self.path(name);
+        self.ensure_parent(&path)?;
+
+        let mut file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let position = file.seek(SeekFrom::End(0))?;
+        file.write_all(data)?;
+        Ok(position)
+    }
+
+    fn read_at(&self, name: &str, offset: u64, len: usize) -> io::Result<Vec<u8>> {
+        let mut file = File::open(self.path(name))?;
+        file.seek(SeekFrom::Start(offset))?;
+
+        let mut data = vec![0u8; len];
+        file.read_exact(&mut data)?;
+        Ok(data)
+    }
+
+    fn flush(&self, name: &str) -> io::Result<()> {
+        // Appends go straight to the OS; only push them to disk when the file exists
+        match File::open(self.path(name)) {
+            Ok(file) => file.sync_data(),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn read_blob(&self, name: &str) -> io::Result<Option<Vec<u8>>> {
+        match fs::read(self.path(name)) {
+            Ok(data) => Ok(Some(data)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn write_blob(&self, name: &str, data: &[u8]) -> io::Result<()> {
+        self.check_writable()?;
+        let path = self.path(name);
+        self.ensure_parent(&path)?;
+
+        // Write to a sibling temp file and rename so readers never see a torn blob
+        let tmp_path = path.with_extension("tmp");
+        fs::write(&tmp_path, data)?;
+        fs::rename(&tmp_path, &path)
+    }
+
+    fn size(&self, name: &str) -> io::Result<u64> {
+        match fs::metadata(self.path(name)) {
+            Ok(metadata) => Ok(metadata.len()),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(0),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn remove(&self, name: &str) -> io::Result<()> {
+        self.check_writable()?;
+        match fs::remove_file(self.path(name)) {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn rename(&self, from: &str, to: &str) -> io::Result<()> {
+        self.check_writable()?;
+        fs::rename(self.path(from), self.path(to))
+    }
+
+    fn shred(&self, name: &str) -> io::Result<()> {
+        self.check_writable()?;
+        let path = self.path(name);
+        let mut file = match OpenOptions::new().write(true).open(&path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e),
+        };
+        // Zero the blocks in place; journaling and copy-on-write filesystems
+        // may still hold older copies
+        let len = file.metadata()?.len();
+        let zeros = [0u8; 64 * 1024];
+        let mut written = 0;
+        while written < len {
+            let chunk = zeros.len().min((len - written) as usize);
+            file.write_all(&zeros[..chunk])?;
+            written += chunk as u64;
+        }
+        file.sync_data()?;
+        drop(file);
+        fs::remove_file(&path)?;
+
+        // Per-user directories are named after the user; don't leave them behind
+        if let Some(parent) = path.parent().filter(|parent| *parent != self.root) {
+            if fs::read_dir(parent)?.next().is_none() {
+                fs::remove_dir(parent)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn location(&self) -> String {
+        self.root.display().to_string()
+    }
+
+    fn is_read_only(&self) -> bool {
+        self.mode == AccessMode::ReadOnly
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_file_backend_logs_and_blobs() {
+        let temp_dir = TempDir::new().unwrap();
+        let backend = FileBackend::new(temp_dir.path()).unwrap();
+
+        assert_eq!(backend.append("log.bin", b"hello").unwrap(), 0);
+        assert_eq!(backend.append("log.bin", b"world").unwrap(), 5);
+        backend.flush("log.bin").unwrap();
+        assert_eq!(backend.read_at("log.bin", 5, 5).unwrap(), b"world");
+        assert_eq!(backend.size("log.bin").unwrap(), 10);
+
+        assert_eq!(backend.read_blob("index.bin").unwrap(), None);
+        backend.write_blob("nested/index.bin", b"a:0").unwrap();
+        assert_eq!(backend.read_blob("nested/index.bin").unwrap(), Some(b"a:0".to_vec()));
+
+        backend.rename("nested/index.bin", "index.bin").unwrap();
+        assert_eq!(backend.read_blob("index.bin").unwrap(), Some(b"a:0".to_vec()));
+
+        backend.remove("log.bin").unwrap();
+        assert_eq!(backend.size("log.bin").unwrap(), 0);
+    }
+
+    #[test]
+    fn test_single_writer_lock_and_read_only_mode() {
+        let temp_dir = TempDir::new().unwrap();
+        let writer = FileBackend::open(temp_dir.path(), AccessMode::ReadWrite).unwrap();
+        assert!(writer.holds_lock());
+        writer.write_blob("index.bin", b"a:0").unwrap();
+
+        let err = FileBackend::open(temp_dir.path(), AccessMode::ReadWrite).unwrap_err();
+        let locked = err.downcast_ref::<StorageLocked>().expect("lock error");
+        assert_eq!(locked.pid, Some(std::process::id()));
+
+        let reader = FileBackend::open(temp_dir.path(), AccessMode::ReadOnly).unwrap();
+        assert!(reader.is_read_only());
+        assert_eq!(reader.read_blob("index.bin").unwrap(), Some(b"a:0".to_vec()));
+        assert_eq!(reader.write_blob("index.bin", b"").unwrap_err().kind(), io::ErrorKind::PermissionDenied);
+        assert!(reader.append("log.bin", b"x").is_err());
+
+        // The lock goes with the last clone of the writer
+        let clone = writer.clone();
+        drop(writer);
+        assert!(FileBackend::open(temp_dir.path(), AccessMode::ReadWrite).is_err());
+        drop(clone);
+        FileBackend::open(temp_dir.path(), AccessMode::ReadWrite).unwrap();
+    }
+}
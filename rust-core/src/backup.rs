@@ -0,0 +1,166 @@
+//! Point-in-time backups
+//!
+//! A backup is a single file: the `MCBACKUP` magic followed by a
+//! bincode-encoded manifest and the store files (memory log, indices,
+//! provenance, links). Sessions are derived from the memories and need no file of
+//! their own. Backups can be taken while the cache is live; writers are only
+//! paused while the files are copied into memory.
+
+use std::fs;
+use std::path::Path;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use crate::MindCache;
+
+const BACKUP_MAGIC: &[u8; 8] = b"MCBACKUP";
+
+/// Bumped when the archive layout changes; older versions stay readable
+pub const BACKUP_FORMAT_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupFileInfo {
+    pub name: String,
+    pub size: u64,
+}
+
+/// Description of a backup archive
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupManifest {
+    pub format_version: u32,
+    pub created_at: DateTime<Utc>,
+    /// Version of mindcache-core that wrote the backup
+    pub crate_version: String,
+    pub memory_count: usize,
+    pub files: Vec<BackupFileInfo>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct BackupArchive {
+    manifest: BackupManifest,
+    files: Vec<(String, Vec<u8>)>,
+}
+
+fn write_archive(path: &Path, archive: &BackupArchive) -> Result<(), Box<dyn std::error::Error>> {
+    let mut data = BACKUP_MAGIC.to_vec();
+    data.extend(bincode::serialize(archive)?);
+
+    // Never leave a half-written archive under the final name
+    let mut tmp_name = path.as_os_str().to_owned();
+    tmp_name.push(".tmp");
+    fs::write(&tmp_name, &data)?;
+    fs::rename(&tmp_name, path)?;
+    Ok(())
+}
+
+fn read_archive(path: &Path) -> Result<BackupArchive, Box<dyn std::error::Error>> {
+    let data = fs::read(path)?;
+    let body = data
+        .strip_prefix(BACKUP_MAGIC.as_slice())
+        .ok_or_else(|| format!("{} is not a MindCache backup", path.display()))?;
+    let archive: BackupArchive = bincode::deserialize(body)?;
+    if archive.manifest.format_version > BACKUP_FORMAT_VERSION {
+        return Err(format!(
+            "backup format {} is newer than supported format {}",
+            archive.manifest.format_version, BACKUP_FORMAT_VERSION
+        )
+        .into());
+    }
+    Ok(archive)
+}
+
+/// Read a backup's manifest without restoring it
+pub fn read_manifest<P: AsRef<Path>>(path: P) -> Result<BackupManifest, Box<dyn std::error::Error>> {
+    Ok(read_archive(path.as_ref())?.manifest)
+}
+
+impl MindCache {
+    /// Write a consistent snapshot of the whole store to `path`
+    pub fn create_backup<P: AsRef<Path>>(&self, path: P) -> Result<BackupManifest, Box<dyn std::error::Error>> {
+        let files = self.storage.snapshot_files()?;
+        let manifest = BackupManifest {
+            format_version: BACKUP_FORMAT_VERSION,
+            created_at: self.storage.now(),
+            crate_version: env!("CARGO_PKG_VERSION").to_string(),
+            memory_count: self.storage.get_stats().values().sum(),
+            files: files
+                .iter()
+                .map(|(name, data)| BackupFileInfo { name: name.clone(), size: data.len() as u64 })
+                .collect(),
+        };
+
+        let archive = BackupArchive { manifest, files };
+        write_archive(path.as_ref(), &archive)?;
+        println!("Backup written to {} ({} memories)", path.as_ref().display(), archive.manifest.memory_count);
+        Ok(archive.manifest)
+    }
+
+    /// Replace the store's contents with a backup
+    ///
+    /// Everything saved since the backup was taken is lost. Session names set
+    /// with `create_session` are not part of the store and are reset. Consumers
+    /// of `changes_since` should re-sync from scratch after a restore.
+    pub fn restore_backup<P: AsRef<Path>>(&mut self, path: P) -> Result<BackupManifest, Box<dyn std::error::Error>> {
+        let archive = read_archive(path.as_ref())?;
+        self.storage.restore_files(&archive.files)?;
+        self.reset_derived_state();
+        println!("Restored backup from {} ({} memories)", path.as_ref().display(), archive.manifest.memory_count);
+        Ok(archive.manifest)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::test_config;
+    use tempfile::TempDir;
+
+    fn open(dir: &TempDir) -> MindCache {
+        MindCache::with_config(test_config(dir)).unwrap()
+    }
+
+    #[test]
+    fn test_backup_and_restore_round_trip() {
+        let store_dir = TempDir::new().unwrap();
+        let backup_dir = TempDir::new().unwrap();
+        let backup_path = backup_dir.path().join("mindcache.bak");
+
+        let mut cache = open(&store_dir);
+        let kept = cache.save("alice", "s1", "Before the backup", None).unwrap();
+        cache.summarize_session("s1").unwrap();
+        let manifest = cache.create_backup(&backup_path).unwrap();
+        // The memory and the stored summary of its session
+        assert_eq!(manifest.memory_count, 2);
+        assert!(manifest.files.iter().any(|file| file.name.starts_with("users/") && file.name.ends_with("/memories.bin")));
+
+        cache.save("alice", "s1", "After the backup", None).unwrap();
+        cache.save("bob", "s2", "Another user", None).unwrap();
+
+        cache.restore_backup(&backup_path).unwrap();
+        let memories = cache.recall("alice", None, None, None).unwrap();
+        assert_eq!(memories.len(), 2);
+        assert!(memories.iter().any(|memory| memory.id == kept));
+        assert!(cache.recall("bob", None, None, None).unwrap().is_empty());
+        assert_eq!(cache.recall("alice", Some("backup"), None, None).unwrap().len(), 2);
+        assert!(cache.get_provenance("summary-s1").is_some());
+
+        // The restored store is what a fresh open sees, and it keeps working
+        drop(cache);
+        let mut cache = open(&store_dir);
+        assert_eq!(cache.recall("alice", None, None, None).unwrap().len(), 2);
+        cache.save("alice", "s1", "After the restore", None).unwrap();
+        assert_eq!(cache.recall("alice", None, None, None).unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_restore_rejects_other_files() {
+        let store_dir = TempDir::new().unwrap();
+        let bogus = store_dir.path().join("not-a-backup");
+        fs::write(&bogus, b"hello").unwrap();
+
+        let mut cache = open(&store_dir);
+        cache.save("alice", "s1", "Untouched", None).unwrap();
+        assert!(cache.restore_backup(&bogus).is_err());
+        assert!(read_manifest(&bogus).is_err());
+        assert_eq!(cache.recall("alice", None, None, None).unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,242 @@
+//! Write batching and group fsync
+//!
+//! By default every save appends its record, fsyncs the log and rewrites
+//! the index before returning, so a save that returned survives a crash.
+//! That caps throughput at a few hundred saves a second. With
+//! `write_batch_size` above 1, saves only append and update the in-memory
+//! index; the logs they touched are fsynced together and the index written
+//! once per batch, when `write_batch_size` saves are pending or the oldest
+//! has waited `write_batch_delay_ms`.
+//!
+//! Pending saves are visible to recalls straight away, but a crash loses
+//! them: their records stay in the log unreferenced until compaction drops
+//! them. Call `MindCache::flush` when a save must be durable before moving
+//! on. Any other write (updates, deletes, transactions) persists the index
+//! and so flushes pending saves with it, and the index on disk never points
+//! at records that were not fsynced.
+//!
+//! The index changes of a batch reach disk as one append to the index log
+//! (see [`crate::index_log`]).
+//!
+//! Recalls and counts choose per call with `QueryFilter::consistency`:
+//! [`ReadConsistency::Buffered`] reads the in-memory index as it is, and
+//! [`ReadConsistency::Strong`] first flushes pending saves, so everything
+//! returned is durable and matches what a reader opening the store sees.
+
+use std::collections::BTreeSet;
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::{Mutex, MutexGuard};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use serde::{Deserialize, Serialize};
+use crate::index_log::IndexDelta;
+use crate::storage::MemoryStorage;
+
+/// When pending saves are flushed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BatchPolicy {
+    /// Saves pending at most; 1 flushes every save on its own
+    pub max_writes: usize,
+    /// Longest a save stays pending
+    pub max_delay: Duration,
+}
+
+impl Default for BatchPolicy {
+    fn default() -> Self {
+        BatchPolicy { max_writes: 1, max_delay: Duration::from_millis(50) }
+    }
+}
+
+impl BatchPolicy {
+    pub fn is_batching(&self) -> bool {
+        self.max_writes > 1
+    }
+}
+
+/// What a recall waits for before reading
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReadConsistency {
+    /// Read straight away, pending saves included
+    #[default]
+    Buffered,
+    /// Flush pending saves first
+    Strong,
+}
+
+#[derive(Default)]
+struct Pending {
+    logs: BTreeSet<String>,
+    deltas: Vec<IndexDelta>,
+    saves: usize,
+    oldest: Option<Instant>,
+}
+
+/// Saves made since the index was last persisted, shared by every clone of a storage
+#[derive(Default)]
+pub struct WriteBuffer {
+    policy: Mutex<BatchPolicy>,
+    pending: Mutex<Pending>,
+}
+
+impl WriteBuffer {
+    fn lock(&self) -> MutexGuard<'_, Pending> {
+        self.pending.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    pub fn policy(&self) -> BatchPolicy {
+        *self.policy.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    pub fn set_policy(&self, policy: BatchPolicy) {
+        *self.policy.lock().unwrap_or_else(|poisoned| poisoned.into_inner()) = policy;
+    }
+
+    /// Note a save appended to `log` but not persisted; true once the batch is due
+    pub(crate) fn add(&self, log: &str, delta: IndexDelta) -> bool {
+        let mut pending = self.lock();
+        pending.logs.insert(log.to_string());
+        pending.deltas.push(delta);
+        pending.saves += 1;
+        pending.oldest.get_or_insert_with(Instant::now);
+        drop(pending);
+        self.is_due()
+    }
+
+    /// Whether pending saves have reached the batch size or waited long enough
+    pub fn is_due(&self) -> bool {
+        let policy = self.policy();
+        let pending = self.lock();
+        pending.saves >= policy.max_writes || pending.oldest.is_some_and(|oldest| oldest.elapsed() >= policy.max_delay)
+    }
+
+    /// Number of saves not yet persisted
+    pub fn pending_saves(&self) -> usize {
+        self.lock().saves
+    }
+
+    /// Logs holding records not yet fsynced
+    pub fn pending_logs(&self) -> Vec<String> {
+        self.lock().logs.iter().cloned().collect()
+    }
+
+    /// Index changes of the pending saves
+    pub(crate) fn pending_deltas(&self) -> Vec<IndexDelta> {
+        self.lock().deltas.clone()
+    }
+
+    /// Forget the pending saves once they are persisted
+    pub fn clear(&self) {
+        *self.lock() = Pending::default();
+    }
+}
+
+/// Thread flushing a storage's pending saves once they are due
+///
+/// Stopping it, by dropping it, flushes whatever is still pending.
+pub(crate) struct Flusher {
+    stop: Option<Sender<()>>,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl Flusher {
+    pub(crate) fn start(storage: MemoryStorage, interval: Duration) -> std::io::Result<Self> {
+        let (stop, stopped) = mpsc::channel::<()>();
+        let worker = thread::Builder::new().name("mindcache-flusher".to_string()).spawn(move || loop {
+            let stopping = matches!(stopped.recv_timeout(interval), Err(RecvTimeoutError::Disconnected));
+            if stopping || storage.write_buffer().is_due() {
+                if let Err(e) = storage.flush() {
+                    println!("Warning: flushing pending saves failed: {}", e);
+                }
+            }
+            if stopping {
+                break;
+            }
+        })?;
+        Ok(Flusher { stop: Some(stop), worker: Some(worker) })
+    }
+}
+
+impl Drop for Flusher {
+    fn drop(&mut self) {
+        drop(self.stop.take());
+        // Joined so the storage's files are released once the cache is dropped
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ReadConsistency;
+    use crate::{MindCache, MindCacheConfig, QueryFilter};
+    use crate::test_support::{test_config, temp_cache_with};
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_batched_saves_flush_by_size_and_on_demand() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = MindCacheConfig {
+            write_batch_size: 3,
+            write_batch_delay_ms: 60_000,
+            ..test_config(&temp_dir)
+        };
+        let mut cache = MindCache::with_config(config.clone()).unwrap();
+        cache.save("alice", "s1", "Gold is up", None).unwrap();
+        cache.save("alice", "s1", "Silver is flat", None).unwrap();
+        // Visible before they are persisted
+        assert_eq!(cache.storage.write_buffer().pending_saves(), 2);
+        assert_eq!(cache.recall("alice", None, None, None).unwrap().len(), 2);
+
+        cache.save("alice", "s1", "Bronze is down", None).unwrap();
+        assert_eq!(cache.storage.write_buffer().pending_saves(), 0);
+
+        cache.save("bob", "s2", "Likes jazz", None).unwrap();
+        cache.flush().unwrap();
+        assert_eq!(cache.storage.write_buffer().pending_saves(), 0);
+
+        // Dropping the cache flushes the rest
+        cache.save("bob", "s2", "Likes blues", None).unwrap();
+        drop(cache);
+        let reopened = MindCache::with_config(config).unwrap();
+        assert_eq!(reopened.recall("alice", None, None, None).unwrap().len(), 3);
+        assert_eq!(reopened.recall("bob", None, None, None).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_strong_reads_flush_pending_saves() {
+        let (mut cache, _temp_dir) = temp_cache_with(MindCacheConfig {
+            write_batch_size: 100,
+            write_batch_delay_ms: 60_000,
+            ..Default::default()
+        });
+        cache.save("alice", "s1", "Gold is up", None).unwrap();
+        let alice = QueryFilter { user_id: Some("alice".to_string()), ..Default::default() };
+
+        assert_eq!(cache.recall_advanced(alice.clone()).unwrap().len(), 1);
+        assert_eq!(cache.storage.write_buffer().pending_saves(), 1);
+        let strong = QueryFilter { consistency: ReadConsistency::Strong, ..alice };
+        assert_eq!(cache.count(strong.clone()).unwrap(), 1);
+        assert_eq!(cache.storage.write_buffer().pending_saves(), 0);
+
+        cache.save("alice", "s1", "Silver is flat", None).unwrap();
+        assert_eq!(cache.recall_advanced(strong).unwrap().len(), 2);
+        assert_eq!(cache.storage.write_buffer().pending_saves(), 0);
+    }
+
+    #[test]
+    fn test_flusher_persists_after_the_delay() {
+        let (mut cache, _temp_dir) = temp_cache_with(MindCacheConfig {
+            write_batch_size: 100,
+            write_batch_delay_ms: 10,
+            ..Default::default()
+        });
+        cache.save("alice", "s1", "Gold is up", None).unwrap();
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(10);
+        while cache.storage.write_buffer().pending_saves() > 0 {
+            assert!(std::time::Instant::now() < deadline, "pending save was never flushed");
+            std::thread::sleep(std::time::Duration::from_millis(5));
+        }
+    }
+}
@@ -0,0 +1,260 @@
+//! Benchmarks to run on your own hardware
+//!
+//! The criterion suite in `benches/` is for work on MindCache itself. This
+//! module backs the `mindcache-bench` binary, which times the common
+//! operations against a store opened with any [`MindCacheConfig`], so
+//! storage paths (and the disks behind them), cache sizes, write batching or
+//! a hot tier can be compared. Results are JSON; a [`BenchReport`] kept from
+//! an earlier run serves as the baseline a later one is checked against.
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use crate::{MemoryUpdate, MindCache, MindCacheConfig, QueryFilter};
+
+/// What to run
+#[derive(Debug, Clone)]
+pub struct BenchOptions {
+    /// Name recorded in the report, e.g. `"nvme, batch 32"`
+    pub label: String,
+    /// Memories saved, spread across `users`
+    pub records: usize,
+    pub users: usize,
+    /// Calls timed for each read operation
+    pub reads: usize,
+    /// Config of the store under test; its `storage_path` must be empty
+    pub config: MindCacheConfig,
+}
+
+impl Default for BenchOptions {
+    fn default() -> Self {
+        BenchOptions {
+            label: "default".to_string(),
+            records: 10_000,
+            users: 10,
+            reads: 1_000,
+            config: MindCacheConfig { auto_decay_enabled: false, ..Default::default() },
+        }
+    }
+}
+
+/// Timings of one operation
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OperationResult {
+    pub name: String,
+    pub operations: usize,
+    pub total_ms: f64,
+    pub ops_per_sec: f64,
+    pub p50_us: f64,
+    pub p95_us: f64,
+    pub p99_us: f64,
+}
+
+impl OperationResult {
+    fn from_samples(name: &str, mut samples: Vec<Duration>) -> Self {
+        samples.sort_unstable();
+        let total: Duration = samples.iter().sum();
+        let percentile = |p: f64| {
+            let rank = ((samples.len() as f64 * p).ceil() as usize).clamp(1, samples.len().max(1)) - 1;
+            samples.get(rank).map_or(0.0, |sample| sample.as_secs_f64() * 1e6)
+        };
+        OperationResult {
+            name: name.to_string(),
+            operations: samples.len(),
+            total_ms: total.as_secs_f64() * 1e3,
+            ops_per_sec: if total.is_zero() { 0.0 } else { samples.len() as f64 / total.as_secs_f64() },
+            p50_us: percentile(0.50),
+            p95_us: percentile(0.95),
+            p99_us: percentile(0.99),
+        }
+    }
+}
+
+/// Results of a run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BenchReport {
+    pub label: String,
+    pub crate_version: String,
+    pub started_at: DateTime<Utc>,
+    pub records: usize,
+    pub users: usize,
+    /// The config the store was opened with
+    pub config: MindCacheConfig,
+    pub operations: Vec<OperationResult>,
+}
+
+/// An operation slower than in the baseline by more than the tolerance
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Regression {
+    pub operation: String,
+    pub baseline_ops_per_sec: f64,
+    pub ops_per_sec: f64,
+    /// Throughput lost, as a fraction of the baseline's
+    pub slowdown: f64,
+}
+
+impl BenchReport {
+    /// Operations at least `tolerance` (e.g. 0.1 for 10%) slower than in `baseline`
+    ///
+    /// Operations only one of the reports has are ignored.
+    pub fn regressions(&self, baseline: &BenchReport, tolerance: f64) -> Vec<Regression> {
+        let baseline: HashMap<&str, &OperationResult> = baseline.operations.iter().map(|op| (op.name.as_str(), op)).collect();
+        self.operations
+            .iter()
+            .filter_map(|op| {
+                let before = baseline.get(op.name.as_str())?;
+                let slowdown = 1.0 - op.ops_per_sec / before.ops_per_sec;
+                (before.ops_per_sec > 0.0 && slowdown > tolerance).then(|| Regression {
+                    operation: op.name.clone(),
+                    baseline_ops_per_sec: before.ops_per_sec,
+                    ops_per_sec: op.ops_per_sec,
+                    slowdown,
+                })
+            })
+            .collect()
+    }
+}
+
+const WORDS: [&str; 24] = [
+    "gold", "silver", "trading", "meeting", "project", "deadline", "coffee", "flight", "budget", "report", "client", "invoice",
+    "python", "rust", "garden", "recipe", "doctor", "holiday", "concert", "jazz", "market", "portfolio", "weekly", "review",
+];
+
+/// Content of the `i`th memory: a few words picked by a fixed pseudo-random sequence
+fn content(i: usize) -> String {
+    let mut state = (i as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15) | 1;
+    let mut words = Vec::with_capacity(8);
+    for _ in 0..8 {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        words.push(WORDS[(state % WORDS.len() as u64) as usize]);
+    }
+    format!("Memory {} about {}", i, words.join(" "))
+}
+
+fn time<T>(samples: &mut Vec<Duration>, op: impl FnOnce() -> Result<T, Box<dyn std::error::Error>>) -> Result<T, Box<dyn std::error::Error>> {
+    let started = Instant::now();
+    let result = op()?;
+    samples.push(started.elapsed());
+    Ok(result)
+}
+
+/// Fill a fresh store and time saves, reads, updates, deletes, decay and compaction
+pub fn run(options: &BenchOptions) -> Result<BenchReport, Box<dyn std::error::Error>> {
+    let path = std::path::Path::new(&options.config.storage_path);
+    if path.read_dir().is_ok_and(|mut entries| entries.next().is_some()) {
+        return Err(format!("{} is not empty; benchmarks need a fresh store", path.display()).into());
+    }
+    if options.records == 0 || options.users == 0 {
+        return Err("records and users must be at least 1".into());
+    }
+    let started_at = Utc::now();
+    let mut cache = MindCache::with_config(options.config.clone())?;
+    let user = |i: usize| format!("user{}", i % options.users);
+    let session = |i: usize| format!("session{}", (i / options.users) % 10);
+    let mut operations = Vec::new();
+
+    let mut samples = Vec::with_capacity(options.records);
+    let mut ids = Vec::with_capacity(options.records);
+    for i in 0..options.records {
+        ids.push(time(&mut samples, || cache.save(&user(i), &session(i), &content(i), None))?);
+    }
+    operations.push(OperationResult::from_samples("save", samples));
+    // Only takes time with write batching
+    let mut samples = Vec::new();
+    time(&mut samples, || cache.flush())?;
+    operations.push(OperationResult::from_samples("flush", samples));
+
+    // A prime stride, so reads don't follow the order of the saves
+    let pick = |n: usize| (n * 7919) % options.records;
+    let mut samples = Vec::with_capacity(options.reads);
+    for n in 0..options.reads {
+        time(&mut samples, || cache.get_memory(&ids[pick(n)]))?;
+    }
+    operations.push(OperationResult::from_samples("get_memory", samples));
+
+    let recalls: [(&str, &dyn Fn(usize) -> QueryFilter); 4] = [
+        ("recall_keyword", &|i| QueryFilter { user_id: Some(user(i)), keywords: Some(vec![WORDS[i % WORDS.len()].to_string()]), limit: Some(10), ..Default::default() }),
+        ("recall_phrase", &|i| QueryFilter { user_id: Some(user(i)), keywords: Some(vec![format!("{} {}", WORDS[i % WORDS.len()], WORDS[(i + 1) % WORDS.len()])]), limit: Some(10), ..Default::default() }),
+        ("recall_session", &|i| QueryFilter { user_id: Some(user(i)), session_id: Some(session(i)), limit: Some(20), ..Default::default() }),
+        ("recall_recent", &|i| QueryFilter { user_id: Some(user(i)), limit: Some(20), ..Default::default() }),
+    ];
+    for (name, filter) in recalls {
+        let mut samples = Vec::with_capacity(options.reads);
+        for n in 0..options.reads {
+            time(&mut samples, || cache.recall_advanced(filter(pick(n))))?;
+        }
+        operations.push(OperationResult::from_samples(name, samples));
+    }
+
+    let writes = (options.records / 10).max(1);
+    let mut samples = Vec::with_capacity(writes);
+    for n in 0..writes {
+        let update = MemoryUpdate { content: Some(format!("{} (edited)", content(pick(n)))), ..Default::default() };
+        time(&mut samples, || cache.update_memory(&ids[pick(n)], update))?;
+    }
+    operations.push(OperationResult::from_samples("update", samples));
+
+    let mut samples = Vec::with_capacity(writes);
+    for id in ids.iter().rev().take(writes) {
+        time(&mut samples, || cache.delete_memory_soft(id))?;
+    }
+    operations.push(OperationResult::from_samples("delete", samples));
+
+    let mut samples = Vec::new();
+    time(&mut samples, || cache.decay())?;
+    operations.push(OperationResult::from_samples("decay", samples));
+    let mut samples = Vec::new();
+    time(&mut samples, || cache.compact())?;
+    operations.push(OperationResult::from_samples("compact", samples));
+
+    Ok(BenchReport {
+        label: options.label.clone(),
+        crate_version: env!("CARGO_PKG_VERSION").to_string(),
+        started_at,
+        records: options.records,
+        users: options.users,
+        config: options.config.clone(),
+        operations,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_small_run_and_regression_check() {
+        let temp_dir = TempDir::new().unwrap();
+        let options = BenchOptions {
+            records: 50,
+            users: 3,
+            reads: 10,
+            config: MindCacheConfig {
+                storage_path: temp_dir.path().to_string_lossy().into_owned(),
+                auto_decay_enabled: false,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let report = run(&options).unwrap();
+        let names: Vec<&str> = report.operations.iter().map(|op| op.name.as_str()).collect();
+        assert_eq!(names, ["save", "flush", "get_memory", "recall_keyword", "recall_phrase", "recall_session", "recall_recent", "update", "delete", "decay", "compact"]);
+        assert_eq!(report.operations[0].operations, 50);
+        assert!(report.operations.iter().all(|op| op.p50_us <= op.p99_us));
+        // The store is no longer empty
+        assert!(run(&options).is_err());
+
+        let json = serde_json::to_string(&report).unwrap();
+        let mut slower: BenchReport = serde_json::from_str(&json).unwrap();
+        assert!(slower.regressions(&report, 0.1).is_empty());
+        slower.operations[3].ops_per_sec = report.operations[3].ops_per_sec * 0.5;
+        let regressions = slower.regressions(&report, 0.1);
+        assert_eq!(regressions.len(), 1);
+        assert_eq!(regressions[0].operation, "recall_keyword");
+        assert!((regressions[0].slowdown - 0.5).abs() < 1e-9);
+    }
+}
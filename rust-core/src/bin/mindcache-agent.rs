@@ -0,0 +1,104 @@
+//! mindcache-agent: a memory-augmented chat loop on stdin/stdout
+//!
+//! Usage:
+//!   mindcache-agent [--data-dir DIR] [--user ID] [--session ID] [--budget-tokens N] [--decay-every N] [--show-context]
+//!
+//! Each line read is one user message. The agent recalls related memories,
+//! builds a context window, replies, and saves the message.
+
+use std::io::{self, BufRead, Write};
+use std::process::ExitCode;
+
+use mindcache_core::agent::{ChatAgent, DEFAULT_DECAY_EVERY};
+use mindcache_core::context::ContextBuilder;
+use mindcache_core::{MindCache, MindCacheConfig};
+
+struct Args {
+    data_dir: String,
+    user: String,
+    session: String,
+    budget_tokens: usize,
+    decay_every: usize,
+    show_context: bool,
+}
+
+fn usage() -> String {
+    "usage: mindcache-agent [--data-dir DIR] [--user ID] [--session ID] [--budget-tokens N] [--decay-every N] [--show-context]".to_string()
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut args = Args {
+        data_dir: MindCacheConfig::default().storage_path,
+        user: "user".to_string(),
+        session: format!("chat-{}", chrono::Utc::now().format("%Y%m%d")),
+        budget_tokens: 500,
+        decay_every: DEFAULT_DECAY_EVERY,
+        show_context: false,
+    };
+
+    let mut iter = std::env::args().skip(1);
+    while let Some(flag) = iter.next() {
+        let mut value = || iter.next().ok_or_else(|| format!("{} needs a value\n{}", flag, usage()));
+        match flag.as_str() {
+            "--data-dir" => args.data_dir = value()?,
+            "--user" => args.user = value()?,
+            "--session" => args.session = value()?,
+            "--budget-tokens" => args.budget_tokens = value()?.parse().map_err(|e| format!("--budget-tokens: {}", e))?,
+            "--decay-every" => args.decay_every = value()?.parse().map_err(|e| format!("--decay-every: {}", e))?,
+            "--show-context" => args.show_context = true,
+            "-h" | "--help" => return Err(usage()),
+            other => return Err(format!("unknown argument: {}\n{}", other, usage())),
+        }
+    }
+
+    Ok(args)
+}
+
+fn main() -> ExitCode {
+    let args = match parse_args() {
+        Ok(args) => args,
+        Err(message) => {
+            eprintln!("{}", message);
+            return ExitCode::from(2);
+        }
+    };
+
+    match run(args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("mindcache-agent: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(args: Args) -> Result<(), Box<dyn std::error::Error>> {
+    let config = MindCacheConfig {
+        storage_path: args.data_dir,
+        // Decay is driven by the agent's turn count
+        auto_decay_enabled: false,
+        ..Default::default()
+    };
+    let mut agent = ChatAgent::new(MindCache::with_config(config)?, &args.user, &args.session)
+        .with_context_builder(ContextBuilder::new().with_token_budget(args.budget_tokens))
+        .with_decay_every(args.decay_every);
+
+    let stdin = io::stdin();
+    let mut stdout = io::stdout();
+    for line in stdin.lock().lines() {
+        let line = line?;
+        let message = line.trim();
+        if message.is_empty() {
+            continue;
+        }
+
+        let turn = agent.respond(message)?;
+        if args.show_context && !turn.context.is_empty() {
+            writeln!(stdout, "[context: {} memories, {} omitted]", turn.context.included_ids.len(), turn.context.omitted)?;
+        }
+        writeln!(stdout, "{}", turn.reply)?;
+        stdout.flush()?;
+    }
+
+    Ok(())
+}
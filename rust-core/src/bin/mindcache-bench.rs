@@ -0,0 +1,131 @@
+//! mindcache-bench: time MindCache on this machine and print JSON results
+//!
+//! Usage:
+//!   mindcache-bench [--config FILE] [--data-dir DIR] [--label NAME] [--records N] [--users N] [--reads N]
+//!                   [--output FILE] [--baseline FILE] [--tolerance PERCENT]
+//!
+//! The store is opened with the config file's settings (defaults otherwise)
+//! in `--data-dir`, which must be empty or missing; without it, a scratch
+//! directory is used and removed afterwards. With `--baseline`, operations
+//! more than `--tolerance` percent (default 10) slower than in an earlier
+//! report are listed on stderr and the exit code is 1.
+
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use mindcache_core::bench::{self, BenchOptions, BenchReport};
+use mindcache_core::config::read_config_file;
+use mindcache_core::MindCacheConfig;
+
+struct Args {
+    options: BenchOptions,
+    config: Option<PathBuf>,
+    data_dir: Option<PathBuf>,
+    output: Option<PathBuf>,
+    baseline: Option<PathBuf>,
+    tolerance: f64,
+}
+
+fn usage() -> String {
+    "usage: mindcache-bench [--config FILE] [--data-dir DIR] [--label NAME] [--records N] [--users N] [--reads N] \
+     [--output FILE] [--baseline FILE] [--tolerance PERCENT]"
+        .to_string()
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut args = Args {
+        options: BenchOptions::default(),
+        config: None,
+        data_dir: None,
+        output: None,
+        baseline: None,
+        tolerance: 10.0,
+    };
+
+    let mut iter = std::env::args().skip(1);
+    while let Some(flag) = iter.next() {
+        let mut value = || iter.next().ok_or_else(|| format!("{} needs a value\n{}", flag, usage()));
+        match flag.as_str() {
+            "--config" => args.config = Some(value()?.into()),
+            "--data-dir" => args.data_dir = Some(value()?.into()),
+            "--label" => args.options.label = value()?,
+            "--records" => args.options.records = value()?.parse().map_err(|e| format!("--records: {}", e))?,
+            "--users" => args.options.users = value()?.parse().map_err(|e| format!("--users: {}", e))?,
+            "--reads" => args.options.reads = value()?.parse().map_err(|e| format!("--reads: {}", e))?,
+            "--output" => args.output = Some(value()?.into()),
+            "--baseline" => args.baseline = Some(value()?.into()),
+            "--tolerance" => args.tolerance = value()?.parse().map_err(|e| format!("--tolerance: {}", e))?,
+            "-h" | "--help" => return Err(usage()),
+            other => return Err(format!("unknown argument: {}\n{}", other, usage())),
+        }
+    }
+
+    Ok(args)
+}
+
+fn main() -> ExitCode {
+    let args = match parse_args() {
+        Ok(args) => args,
+        Err(message) => {
+            eprintln!("{}", message);
+            return ExitCode::from(2);
+        }
+    };
+
+    match run(args) {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(e) => {
+            eprintln!("mindcache-bench: {}", e);
+            ExitCode::from(2)
+        }
+    }
+}
+
+/// Run the benchmarks; false if they regressed against the baseline
+fn run(mut args: Args) -> Result<bool, Box<dyn std::error::Error>> {
+    // Read first, so a bad baseline doesn't cost a whole run
+    let baseline: Option<BenchReport> = match &args.baseline {
+        Some(path) => Some(serde_json::from_str(&std::fs::read_to_string(path)?)?),
+        None => None,
+    };
+    let mut config = match &args.config {
+        Some(path) => read_config_file(path)?,
+        None => MindCacheConfig::default(),
+    };
+    // Decay is timed as an operation of its own
+    config.auto_decay_enabled = false;
+
+    let scratch = args.data_dir.is_none().then(|| std::env::temp_dir().join(format!("mindcache-bench-{}", std::process::id())));
+    let data_dir = args.data_dir.clone().or_else(|| scratch.clone()).unwrap_or_default();
+    config.storage_path = data_dir.to_string_lossy().into_owned();
+    args.options.config = config;
+
+    let report = bench::run(&args.options);
+    if let Some(scratch) = &scratch {
+        let _ = std::fs::remove_dir_all(scratch);
+    }
+    let report = report?;
+
+    let json = serde_json::to_string_pretty(&report)?;
+    match &args.output {
+        Some(path) => std::fs::write(path, json + "\n")?,
+        None => println!("{}", json),
+    }
+
+    let Some(baseline) = baseline else {
+        return Ok(true);
+    };
+    let regressions = report.regressions(&baseline, args.tolerance / 100.0);
+    for regression in &regressions {
+        eprintln!(
+            "regression: {} at {:.0} ops/s, {:.1}% slower than {:.0} ops/s in \"{}\"",
+            regression.operation,
+            regression.ops_per_sec,
+            regression.slowdown * 100.0,
+            regression.baseline_ops_per_sec,
+            baseline.label
+        );
+    }
+    Ok(regressions.is_empty())
+}
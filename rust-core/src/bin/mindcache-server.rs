@@ -0,0 +1,210 @@
+//! mindcache-server: MindCache as a standalone HTTP/JSON memory service
+//!
+//! Usage:
+//!   mindcache-server [--bind ADDR] [--grpc-bind ADDR] [--config FILE] [--watch-config] [--pid-file FILE]
+//!
+//! `--grpc-bind` also serves the gRPC API and needs the `grpc` feature.
+//! SIGHUP reloads the config file, as does any edit to it with
+//! `--watch-config`. SIGTERM/SIGINT shut down gracefully.
+
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use mindcache_core::daemon::{Daemon, DaemonError, DaemonExitCode, DaemonOptions};
+use mindcache_core::{reminders, review};
+use mindcache_core::server::{self, SharedCache};
+use mindcache_core::MindCache;
+
+const DEFAULT_BIND: &str = "127.0.0.1:7878";
+const SIGNAL_POLL_INTERVAL: Duration = Duration::from_millis(250);
+const REMINDER_POLL_INTERVAL: Duration = Duration::from_secs(reminders::REMINDER_POLL_INTERVAL_SECS);
+const REVIEW_POLL_INTERVAL: Duration = Duration::from_secs(review::REVIEW_POLL_INTERVAL_SECS);
+
+struct Args {
+    bind: String,
+    grpc_bind: Option<String>,
+    daemon: DaemonOptions,
+}
+
+fn usage() -> String {
+    "usage: mindcache-server [--bind ADDR] [--grpc-bind ADDR] [--config FILE] [--watch-config] [--pid-file FILE]".to_string()
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut args = Args {
+        bind: DEFAULT_BIND.to_string(),
+        grpc_bind: None,
+        daemon: DaemonOptions::default(),
+    };
+
+    let mut iter = std::env::args().skip(1);
+    while let Some(flag) = iter.next() {
+        let mut value = || iter.next().ok_or_else(|| format!("{} needs a value\n{}", flag, usage()));
+        match flag.as_str() {
+            "--bind" => args.bind = value()?,
+            "--grpc-bind" => args.grpc_bind = Some(value()?),
+            "--config" => args.daemon.config_path = Some(PathBuf::from(value()?)),
+            "--watch-config" => args.daemon.watch_config = true,
+            "--pid-file" => args.daemon.pid_file = Some(PathBuf::from(value()?)),
+            "-h" | "--help" => return Err(usage()),
+            other => return Err(format!("unknown argument: {}\n{}", other, usage())),
+        }
+    }
+
+    Ok(args)
+}
+
+fn main() -> ExitCode {
+    let args = match parse_args() {
+        Ok(args) => args,
+        Err(message) => {
+            eprintln!("{}", message);
+            return DaemonExitCode::Usage.into();
+        }
+    };
+
+    match run(args) {
+        Ok(()) => DaemonExitCode::Success.into(),
+        Err(e) => {
+            eprintln!("mindcache-server: {}", e);
+            e.exit_code().into()
+        }
+    }
+}
+
+fn run(args: Args) -> Result<(), DaemonError> {
+    let daemon = Arc::new(Daemon::start(args.daemon)?);
+    let config = daemon.load_config()?;
+    let cache = MindCache::with_config(config).map_err(|e| DaemonError::Storage(e.to_string()))?;
+    let cache: SharedCache = Arc::new(Mutex::new(cache));
+
+    #[cfg(not(feature = "grpc"))]
+    if args.grpc_bind.is_some() {
+        return Err(DaemonError::Config("--grpc-bind needs a build with the grpc feature".to_string()));
+    }
+
+    let runtime = tokio::runtime::Runtime::new()?;
+    runtime.block_on(async move {
+        let listener = tokio::net::TcpListener::bind(&args.bind).await?;
+        println!("mindcache-server listening on {}", listener.local_addr()?);
+
+        let (stop_tx, stop_rx) = tokio::sync::watch::channel(false);
+        let stopped = move || {
+            let mut stop_rx = stop_rx.clone();
+            async move {
+                let _ = stop_rx.wait_for(|stop| *stop).await;
+            }
+        };
+
+        #[cfg(feature = "grpc")]
+        let grpc_task = match &args.grpc_bind {
+            Some(addr) => {
+                let addr = addr
+                    .parse()
+                    .map_err(|e| DaemonError::Config(format!("invalid --grpc-bind {}: {}", addr, e)))?;
+                println!("mindcache-server gRPC listening on {}", addr);
+                Some(tokio::spawn(mindcache_core::grpc::serve(addr, Arc::clone(&cache), stopped())))
+            }
+            None => None,
+        };
+
+        let signals = watch_signals(Arc::clone(&daemon), Arc::clone(&cache));
+        let mut http = tokio::spawn(server::serve(listener, cache, stopped()));
+        let http_result = tokio::select! {
+            _ = signals => {
+                let _ = stop_tx.send(true);
+                (&mut http).await
+            }
+            result = &mut http => result,
+        };
+        let _ = stop_tx.send(true);
+
+        http_result.map_err(|e| DaemonError::Storage(e.to_string()))??;
+        #[cfg(feature = "grpc")]
+        if let Some(grpc_task) = grpc_task {
+            grpc_task
+                .await
+                .map_err(|e| DaemonError::Storage(e.to_string()))?
+                .map_err(|e| DaemonError::Io(std::io::Error::other(e)))?;
+        }
+
+        println!("mindcache-server stopped");
+        Ok(())
+    })
+}
+
+/// Handle reload/stop requests and scheduled jobs; completes when the server should stop
+async fn watch_signals(daemon: Arc<Daemon>, cache: SharedCache) {
+    let mut decay_interval = decay_schedule(&cache.lock().unwrap_or_else(|poisoned| poisoned.into_inner()));
+    let mut last_decay = Instant::now();
+    let mut last_reminder_check = Instant::now();
+    let mut last_review_check = Instant::now();
+    let mut ticker = tokio::time::interval(SIGNAL_POLL_INTERVAL);
+
+    loop {
+        ticker.tick().await;
+        if daemon.should_stop() {
+            println!("Shutdown requested");
+            return;
+        }
+
+        let reload = daemon.take_reload_request();
+        let decay_due = decay_interval.is_some_and(|interval| last_decay.elapsed() >= interval);
+        let reminders_due = last_reminder_check.elapsed() >= REMINDER_POLL_INTERVAL;
+        let reviews_due = last_review_check.elapsed() >= REVIEW_POLL_INTERVAL;
+        if !reload && !decay_due && !reminders_due && !reviews_due {
+            continue;
+        }
+
+        let daemon = Arc::clone(&daemon);
+        let cache = Arc::clone(&cache);
+        let schedule = tokio::task::spawn_blocking(move || {
+            let mut cache = cache.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+            if reload {
+                if let Err(e) = daemon.reload(&mut cache) {
+                    eprintln!("Config reload failed, keeping previous config: {}", e);
+                }
+            }
+            if decay_due {
+                if let Err(e) = cache.decay() {
+                    eprintln!("Scheduled decay failed: {}", e);
+                }
+            }
+            if reminders_due {
+                if let Err(e) = cache.fire_due_reminders(chrono::Utc::now()) {
+                    eprintln!("Reminder check failed: {}", e);
+                }
+            }
+            if reviews_due {
+                if let Err(e) = cache.run_due_reviews(chrono::Utc::now()) {
+                    eprintln!("Scheduled reviews failed: {}", e);
+                }
+            }
+            decay_schedule(&cache)
+        })
+        .await;
+
+        if let Ok(schedule) = schedule {
+            decay_interval = schedule;
+        }
+        if decay_due {
+            last_decay = Instant::now();
+        }
+        if reminders_due {
+            last_reminder_check = Instant::now();
+        }
+        if reviews_due {
+            last_review_check = Instant::now();
+        }
+    }
+}
+
+/// How often to run decay under the current config, if at all
+fn decay_schedule(cache: &MindCache) -> Option<Duration> {
+    let config = cache.config();
+    config
+        .auto_decay_enabled
+        .then(|| Duration::from_secs(config.decay_interval_hours as u64 * 3600))
+}
@@ -0,0 +1,332 @@
+//! mindcache: inspect and administer a MindCache storage directory
+//!
+//! Operates on the store directly (no server needed), e.g.
+//!   mindcache --data-dir ./mindcache_data recall --user alice --query gold
+//!
+//! A writer locks the directory while it is open. Read commands can run next
+//! to a server with --read-only; stop the server before running write commands.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use clap::{Parser, Subcommand};
+use mindcache_core::{MemoryItem, MemoryType, MindCache, MindCacheConfig, RelativeDuration, TimeBucket};
+
+#[derive(Parser)]
+#[command(name = "mindcache", version, about = "Inspect and manage a MindCache store")]
+struct Cli {
+    /// Storage directory (overrides storage_path from --config)
+    #[arg(long, env = "MINDCACHE_DATA_DIR")]
+    data_dir: Option<PathBuf>,
+
+    /// JSON config file (MindCacheConfig)
+    #[arg(long)]
+    config: Option<PathBuf>,
+
+    /// Open without the writer lock; write commands will fail
+    #[arg(long)]
+    read_only: bool,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Save a memory
+    Save {
+        #[arg(long)]
+        user: String,
+        #[arg(long)]
+        session: String,
+        #[arg(long)]
+        content: String,
+        /// 0.0 to 1.0
+        #[arg(long, default_value_t = 0.5)]
+        importance: f32,
+        #[arg(long)]
+        ttl_hours: Option<u32>,
+        /// Metadata entry as key=value (repeatable)
+        #[arg(long = "meta", value_parser = parse_key_value)]
+        metadata: Vec<(String, String)>,
+    },
+    /// Recall memories for a user
+    Recall {
+        #[arg(long)]
+        user: String,
+        #[arg(long)]
+        query: Option<String>,
+        /// Boolean search, e.g. '"stop loss" AND AAPL NOT crypto'
+        #[arg(long)]
+        search: Option<String>,
+        /// Only memories whose metadata has key=value (repeatable)
+        #[arg(long = "meta", value_parser = parse_key_value)]
+        metadata: Vec<(String, String)>,
+        /// Only memories from this far back, e.g. 24h, 7d or 2w
+        #[arg(long)]
+        within: Option<RelativeDuration>,
+        /// Only conversation turns with this role, e.g. user
+        #[arg(long)]
+        role: Option<String>,
+        /// Only memories of this type: observation, fact, task, message or summary
+        #[arg(long = "type")]
+        memory_type: Option<MemoryType>,
+        #[arg(long)]
+        session: Option<String>,
+        #[arg(long)]
+        limit: Option<usize>,
+        /// Show how the query would be executed instead of running it
+        #[arg(long)]
+        explain: bool,
+    },
+    /// List a user's sessions
+    Sessions {
+        #[arg(long)]
+        user: String,
+    },
+    /// Summarize a session
+    Summarize {
+        #[arg(long)]
+        session: String,
+    },
+    /// Run a decay pass
+    Decay,
+    /// Show store statistics
+    Stats,
+    /// Count a user's memories per hour, day or week
+    Histogram {
+        #[arg(long)]
+        user: String,
+        #[arg(long, default_value = "day")]
+        bucket: TimeBucket,
+    },
+    /// Export a user's memories as JSON
+    Export {
+        #[arg(long)]
+        user: String,
+        /// Output file
+        #[arg(long)]
+        output: PathBuf,
+    },
+    /// Import memories from a JSON export
+    Import {
+        /// Input file
+        #[arg(long)]
+        input: PathBuf,
+    },
+    /// Rewrite the memory log without unreferenced records
+    Compact {
+        /// Only report how many bytes would be reclaimed
+        #[arg(long)]
+        dry_run: bool,
+        /// Only rewrite this user's segment log
+        #[arg(long, conflicts_with = "dry_run")]
+        user: Option<String>,
+    },
+    /// Write a point-in-time backup of the store
+    Backup {
+        /// Backup file
+        #[arg(long)]
+        output: PathBuf,
+    },
+    /// Replace the store with a backup
+    Restore {
+        /// Backup file
+        #[arg(long)]
+        input: PathBuf,
+    },
+    /// Erase all of a user's data and print the deletion report
+    DeleteUser {
+        #[arg(long)]
+        user: String,
+    },
+    /// Remove near-duplicate memories of a user
+    Dedupe {
+        #[arg(long)]
+        user: String,
+        /// Share of matching fingerprint bits, 0.0 to 1.0
+        #[arg(long, default_value_t = mindcache_core::dedupe::DEFAULT_SIMILARITY_THRESHOLD)]
+        threshold: f32,
+        /// Only report what would be removed
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+fn parse_key_value(entry: &str) -> Result<(String, String), String> {
+    entry
+        .split_once('=')
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .ok_or_else(|| format!("expected key=value, got '{}'", entry))
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    match run(cli) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("mindcache: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn open(cli: &Cli) -> Result<MindCache, Box<dyn std::error::Error>> {
+    let config: MindCacheConfig = match &cli.config {
+        Some(path) => serde_json::from_str(&std::fs::read_to_string(path)?)?,
+        None => MindCacheConfig::default(),
+    };
+    let mut config = config.overlay_env()?;
+    if let Some(data_dir) = &cli.data_dir {
+        config.storage_path = data_dir.to_string_lossy().into_owned();
+    }
+    if cli.read_only {
+        config.read_only = true;
+    }
+    // Decay only runs when asked for explicitly
+    config.auto_decay_enabled = false;
+    MindCache::with_config(config)
+}
+
+fn run(cli: Cli) -> Result<(), Box<dyn std::error::Error>> {
+    let mut cache = open(&cli)?;
+
+    match cli.command {
+        Command::Save { user, session, content, importance, ttl_hours, metadata } => {
+            if !(0.0..=1.0).contains(&importance) {
+                return Err("importance must be between 0.0 and 1.0".into());
+            }
+            let metadata: HashMap<String, String> = metadata.into_iter().collect();
+            let ttl_hours = ttl_hours.or(cache.config().default_memory_ttl_hours);
+            let id = cache.save_with_options(&user, &session, &content, Some(metadata), importance, ttl_hours)?;
+            println!("{}", id);
+        }
+        Command::Recall { user, query, search, metadata, within, role, memory_type, session, limit, explain } => {
+            let filter = mindcache_core::QueryFilter {
+                user_id: Some(user),
+                session_id: session,
+                keywords: query.map(|q| q.split_whitespace().map(str::to_string).collect()),
+                date_from: None,
+                date_to: None,
+                limit,
+                min_importance: None,
+                query: search,
+                metadata_filters: metadata
+                    .into_iter()
+                    .map(|(key, value)| (key, mindcache_core::MetadataCondition::Equals(value)))
+                    .collect(),
+                within,
+                role,
+                memory_type,
+                ..Default::default()
+            };
+            if explain {
+                println!("{}", cache.explain(&filter));
+                return Ok(());
+            }
+
+            let memories = cache.recall_advanced(filter)?;
+            for memory in &memories {
+                print_memory(memory);
+            }
+        }
+        Command::Sessions { user } => {
+            for session in cache.get_user_sessions(&user)? {
+                println!(
+                    "{}  {} memories  last active {}{}",
+                    session.id,
+                    session.memory_count,
+                    session.last_active.format("%Y-%m-%d %H:%M"),
+                    session.name.map(|name| format!("  ({})", name)).unwrap_or_default(),
+                );
+            }
+        }
+        Command::Summarize { session } => {
+            let summary = cache.summarize_session(&session)?;
+            println!("{}", summary.summary_text);
+            println!("topics: {}", summary.key_topics.join(", "));
+            println!("memories: {}, importance: {:.2}", summary.memory_count, summary.importance_score);
+        }
+        Command::Decay => {
+            let stats = cache.decay()?;
+            println!("{}", serde_json::to_string_pretty(&stats)?);
+        }
+        Command::Stats => {
+            println!("{}", serde_json::to_string_pretty(&cache.get_stats()?)?);
+        }
+        Command::Histogram { user, bucket } => {
+            for entry in cache.memory_histogram(&user, bucket) {
+                println!("{}  {}", entry.start.format("%Y-%m-%d %H:%M"), entry.count);
+            }
+        }
+        Command::Export { user, output } => {
+            let data = cache.export_user_memories(&user)?;
+            std::fs::write(&output, data)?;
+            println!("Exported memories for {} to {}", user, output.display());
+        }
+        Command::Import { input } => {
+            let count = cache.import_memories(&std::fs::read_to_string(&input)?)?;
+            println!("Imported {} memories from {}", count, input.display());
+        }
+        Command::Compact { dry_run: true, .. } => {
+            println!("{} bytes reclaimable", cache.reclaimable_bytes()?);
+        }
+        Command::Compact { dry_run: false, user } => {
+            let report = match user {
+                Some(user) => cache.compact_user(&user)?,
+                None => cache.compact()?,
+            };
+            println!(
+                "Kept {} records, {} -> {} bytes ({} reclaimed)",
+                report.records_kept,
+                report.bytes_before,
+                report.bytes_after,
+                report.bytes_reclaimed()
+            );
+        }
+        Command::Backup { output } => {
+            let manifest = cache.create_backup(&output)?;
+            println!("Backed up {} memories to {}", manifest.memory_count, output.display());
+        }
+        Command::Restore { input } => {
+            let manifest = cache.restore_backup(&input)?;
+            println!(
+                "Restored {} memories from backup taken {}",
+                manifest.memory_count,
+                manifest.created_at.format("%Y-%m-%d %H:%M")
+            );
+        }
+        Command::DeleteUser { user } => {
+            println!("{}", serde_json::to_string_pretty(&cache.delete_user(&user)?)?);
+        }
+        Command::Dedupe { user, threshold, dry_run } => {
+            let report = cache.dedupe(&user, threshold, dry_run)?;
+            for cluster in &report.clusters {
+                println!("keep {}  remove {}", cluster.kept_id, cluster.removed_ids.join(", "));
+            }
+            println!(
+                "{} {} duplicates in {} clusters, {} bytes {}",
+                if dry_run { "Would remove" } else { "Removed" },
+                report.memories_removed,
+                report.clusters.len(),
+                report.bytes_reclaimed,
+                if dry_run { "reclaimable" } else { "reclaimed" }
+            );
+        }
+    }
+
+    Ok(())
+}
+
+fn print_memory(memory: &MemoryItem) {
+    println!(
+        "[{}] {} ({}, importance {:.2}){}\n    {}",
+        memory.timestamp.format("%Y-%m-%d %H:%M"),
+        memory.id,
+        memory.session_id,
+        memory.importance,
+        memory.role.as_ref().map(|role| format!(" {}:", role)).unwrap_or_default(),
+        memory.content
+    );
+}
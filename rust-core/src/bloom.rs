@@ -0,0 +1,138 @@
+//! Bloom filters over each user's terms
+//!
+//! Keywords that aren't a single alphanumeric word (`"AAPL position"`,
+//! `"e-mail"`) can't be answered by the inverted index, so such recalls read
+//! every record of the users in scope. Each user also keeps a Bloom filter of
+//! the character trigrams of their terms. A keyword matches as a substring,
+//! so each of its alphanumeric runs lies inside a single term; a user whose
+//! filter lacks a trigram of one of the runs can't hold a match, and their
+//! records are skipped without being read.
+//!
+//! Filters are kept in memory only and built along with the term index.
+//! They never forget: trigrams of deleted memories stay until the filter is
+//! rebuilt by compaction or on open, which costs false positives but never
+//! a missed match.
+
+use crate::dedupe::fnv1a;
+use crate::matching::MatchMode;
+
+/// Bits in a new filter; doubled whenever a filter fills up
+const INITIAL_BITS: usize = 4096;
+/// Probes per trigram
+const HASHES: u64 = 3;
+/// Trigrams per bit past which a filter is rebuilt larger (about 3% false positives)
+const MAX_LOAD: usize = 8;
+
+/// Trigrams of one user's terms
+#[derive(Debug, Clone)]
+pub struct TermFilter {
+    bits: Vec<u64>,
+    trigrams: usize,
+}
+
+impl Default for TermFilter {
+    fn default() -> Self {
+        Self::with_bits(INITIAL_BITS)
+    }
+}
+
+/// Character trigrams of a run; empty for runs shorter than three characters
+fn trigrams(run: &str) -> Vec<&str> {
+    let bounds: Vec<usize> = run.char_indices().map(|(i, _)| i).chain(std::iter::once(run.len())).collect();
+    (0..bounds.len().saturating_sub(3)).map(|i| &run[bounds[i]..bounds[i + 3]]).collect()
+}
+
+impl TermFilter {
+    fn with_bits(bits: usize) -> Self {
+        TermFilter { bits: vec![0; bits.div_ceil(64)], trigrams: 0 }
+    }
+
+    /// The smallest filter of at least `bits` bits that holds `terms` without filling up
+    fn sized<'a>(mut bits: usize, terms: impl IntoIterator<Item = &'a String> + Clone) -> Self {
+        loop {
+            let mut filter = Self::with_bits(bits);
+            if terms.clone().into_iter().all(|term| filter.insert_term(term)) {
+                return filter;
+            }
+            bits *= 2;
+        }
+    }
+
+    /// A filter sized for `terms`
+    pub fn from_terms<'a>(terms: impl IntoIterator<Item = &'a String> + Clone) -> Self {
+        Self::sized(INITIAL_BITS, terms)
+    }
+
+    fn probes(&self, trigram: &str) -> impl Iterator<Item = usize> {
+        let hash = fnv1a(trigram);
+        let (first, step) = (hash, hash.rotate_left(32) | 1);
+        let len = self.bits.len() as u64 * 64;
+        (0..HASHES).map(move |i| (first.wrapping_add(i.wrapping_mul(step)) % len) as usize)
+    }
+
+    /// Add the trigrams of a (lowercased) term
+    ///
+    /// Returns false once the filter is too full to be useful and should be
+    /// rebuilt with [`TermFilter::grown`].
+    pub fn insert_term(&mut self, term: &str) -> bool {
+        for trigram in trigrams(term) {
+            let probes: Vec<usize> = self.probes(trigram).collect();
+            if probes.iter().any(|&bit| self.bits[bit / 64] & (1 << (bit % 64)) == 0) {
+                probes.iter().for_each(|&bit| self.bits[bit / 64] |= 1 << (bit % 64));
+                self.trigrams += 1;
+            }
+        }
+        self.trigrams * MAX_LOAD <= self.bits.len() * 64
+    }
+
+    /// A filter twice the size holding `terms`, for when this one filled up
+    pub fn grown<'a>(&self, terms: impl IntoIterator<Item = &'a String> + Clone) -> Self {
+        Self::sized(self.bits.len() * 64 * 2, terms)
+    }
+
+    fn contains(&self, trigram: &str) -> bool {
+        self.probes(trigram).all(|bit| self.bits[bit / 64] & (1 << (bit % 64)) != 0)
+    }
+
+    /// Whether content with these terms could match a (lowercased) keyword
+    pub fn may_match(&self, keyword: &str, match_mode: MatchMode) -> bool {
+        if !match_mode.needs_substring(keyword) {
+            return true;
+        }
+        keyword
+            .split(|c: char| !c.is_alphanumeric())
+            .all(|run| trigrams(run).into_iter().all(|trigram| self.contains(trigram)))
+    }
+
+    /// Bytes taken by the filter
+    pub fn size(&self) -> usize {
+        self.bits.len() * 8
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_filter_rules_out_absent_runs_only() {
+        let terms: Vec<String> = ["bought", "aapl", "position", "today"].map(String::from).to_vec();
+        let filter = TermFilter::from_terms(&terms);
+        for keyword in ["aapl position", "t aap", "sition,", "ok", "-"] {
+            assert!(filter.may_match(keyword, MatchMode::Exact), "{}", keyword);
+        }
+        assert!(!filter.may_match("tsla position", MatchMode::Exact));
+        assert!(!filter.may_match("bitcoin", MatchMode::Prefix));
+        // Fuzzy words may match without sharing a trigram
+        assert!(filter.may_match("tody", MatchMode::Fuzzy(1)));
+        assert!(!filter.may_match("tody ago", MatchMode::Fuzzy(1)));
+
+        let many: Vec<String> = (0..5000).map(|i| format!("term{}", i)).collect();
+        let mut filter = TermFilter::default();
+        let full = !many.iter().all(|term| filter.insert_term(term));
+        assert!(full);
+        let grown = filter.grown(&many);
+        assert!(grown.size() > filter.size());
+        assert!(many.iter().all(|term| grown.may_match(term, MatchMode::Exact)));
+    }
+}
@@ -0,0 +1,206 @@
+//! Builder-style construction of [`MindCache`]
+//!
+//! `MindCache::builder().storage_path(path).max_memories(n).build()` sets
+//! the common options by name and plugs in backends, scorers, extractors,
+//! summarizers, hooks and clocks before the instance is first used. Any other config
+//! field is reachable through [`MindCacheBuilder::configure`].
+
+use std::sync::Arc;
+use crate::backend::StorageBackend;
+use crate::clock::Clock;
+use crate::expiry::ExpiryHook;
+use crate::extraction::EntityExtractor;
+use crate::facts::FactExtractor;
+use crate::importance::ImportanceScorer;
+use crate::summarizer::Summarizer;
+use crate::tiering::HotTier;
+use crate::{MindCache, MindCacheConfig};
+
+/// Options for a [`MindCache`], applied by [`build`](MindCacheBuilder::build)
+#[derive(Default)]
+pub struct MindCacheBuilder {
+    config: MindCacheConfig,
+    backend: Option<Arc<dyn StorageBackend>>,
+    importance_scorer: Option<Arc<dyn ImportanceScorer>>,
+    entity_extractor: Option<Arc<dyn EntityExtractor>>,
+    fact_extractor: Option<Arc<dyn FactExtractor>>,
+    summarizer: Option<Arc<dyn Summarizer>>,
+    expiry_hook: Option<Arc<dyn ExpiryHook>>,
+    hot_tier: Option<Arc<dyn HotTier>>,
+    clock: Option<Arc<dyn Clock>>,
+}
+
+impl MindCacheBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start from `config` instead of the defaults
+    pub fn config(mut self, config: MindCacheConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Change any config field, for options without a method of their own
+    pub fn configure(mut self, change: impl FnOnce(&mut MindCacheConfig)) -> Self {
+        change(&mut self.config);
+        self
+    }
+
+    pub fn storage_path(mut self, path: impl Into<String>) -> Self {
+        self.config.storage_path = path.into();
+        self
+    }
+
+    /// Most memories a user may keep (`max_memories_per_user`)
+    pub fn max_memories(mut self, max_memories: usize) -> Self {
+        self.config.max_memories_per_user = max_memories;
+        self
+    }
+
+    /// TTL of memories saved without one; `None` keeps them until decay removes them
+    pub fn default_ttl_hours(mut self, ttl_hours: Option<u32>) -> Self {
+        self.config.default_memory_ttl_hours = ttl_hours;
+        self
+    }
+
+    pub fn auto_decay(mut self, enabled: bool) -> Self {
+        self.config.auto_decay_enabled = enabled;
+        self
+    }
+
+    /// Items kept in the LRU cache of hot memories (0 disables it)
+    pub fn cache_capacity(mut self, capacity: usize) -> Self {
+        self.config.memory_cache_capacity = capacity;
+        self
+    }
+
+    /// Open the store without taking the writer lock; writes fail
+    pub fn read_only(mut self, read_only: bool) -> Self {
+        self.config.read_only = read_only;
+        self
+    }
+
+    /// Keep recent and important memories in `tier` (see [`crate::tiering`]),
+    /// with the `hot_tier_*` config fields as its policy
+    pub fn hot_tier(mut self, tier: Arc<dyn HotTier>) -> Self {
+        self.hot_tier = Some(tier);
+        self
+    }
+
+    /// Store memories in `backend` instead of files under `storage_path`
+    pub fn backend(mut self, backend: Arc<dyn StorageBackend>) -> Self {
+        self.backend = Some(backend);
+        self
+    }
+
+    pub fn importance_scorer<S: ImportanceScorer + 'static>(mut self, scorer: S) -> Self {
+        self.importance_scorer = Some(Arc::new(scorer));
+        self
+    }
+
+    pub fn entity_extractor<E: EntityExtractor + 'static>(mut self, extractor: E) -> Self {
+        self.entity_extractor = Some(Arc::new(extractor));
+        self
+    }
+
+    pub fn fact_extractor<E: FactExtractor + 'static>(mut self, extractor: E) -> Self {
+        self.fact_extractor = Some(Arc::new(extractor));
+        self
+    }
+
+    pub fn summarizer<S: Summarizer + 'static>(mut self, summarizer: S) -> Self {
+        self.summarizer = Some(Arc::new(summarizer));
+        self
+    }
+
+    pub fn expiry_hook<H: ExpiryHook + 'static>(mut self, hook: H) -> Self {
+        self.expiry_hook = Some(Arc::new(hook));
+        self
+    }
+
+    /// Read the current time from `clock` (see [`crate::clock`])
+    pub fn clock<C: Clock + 'static>(mut self, clock: C) -> Self {
+        self.clock = Some(Arc::new(clock));
+        self
+    }
+
+    /// Validate the config and open the cache
+    ///
+    /// Fails as [`MindCache::with_config`] does.
+    pub fn build(self) -> Result<MindCache, Box<dyn std::error::Error>> {
+        let mut cache = match self.backend {
+            Some(backend) => {
+                self.config.validate()?;
+                MindCache::with_backend(self.config, backend)?
+            }
+            None => MindCache::with_config(self.config)?,
+        };
+        if let Some(scorer) = self.importance_scorer {
+            cache.importance_scorer = scorer;
+        }
+        if let Some(extractor) = self.entity_extractor {
+            cache.entity_extractor = extractor;
+        }
+        if let Some(extractor) = self.fact_extractor {
+            cache.fact_extractor = extractor;
+        }
+        if let Some(summarizer) = self.summarizer {
+            cache.summarizer = summarizer;
+        }
+        if let Some(clock) = self.clock {
+            cache.storage.set_clock(clock);
+        }
+        if let Some(tier) = self.hot_tier {
+            cache.storage.set_hot_tier(Some(tier), MindCache::hot_tier_policy(&cache.config));
+        }
+        cache.expiry_hook = self.expiry_hook;
+        // Hands the summarizer and hook to the session manager and decay engine
+        cache.reset_derived_state();
+        Ok(cache)
+    }
+}
+
+impl MindCache {
+    /// Start building a cache; see [`MindCacheBuilder`]
+    pub fn builder() -> MindCacheBuilder {
+        MindCacheBuilder::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::FileBackend;
+    use crate::summarizer::SummaryRequest;
+    use crate::InvalidConfig;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_builder_sets_options_and_plugins() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut cache = MindCache::builder()
+            .storage_path(temp_dir.path().to_string_lossy())
+            .max_memories(50)
+            .auto_decay(false)
+            .configure(|config| config.max_content_bytes = Some(1000))
+            .summarizer(|request: &SummaryRequest| Ok(format!("{} memories", request.memories.len())))
+            .build()
+            .unwrap();
+        assert_eq!(cache.config().max_memories_per_user, 50);
+        assert_eq!(cache.config().max_content_bytes, Some(1000));
+
+        cache.save("alice", "s1", "Gold is up", None).unwrap();
+        cache.save("alice", "s1", "Silver is flat", None).unwrap();
+        assert_eq!(cache.summarize_session("s1").unwrap().summary_text, "2 memories");
+        drop(cache);
+
+        let backend = FileBackend::new(temp_dir.path()).unwrap();
+        let reader = MindCache::builder().backend(Arc::new(backend)).auto_decay(false).build().unwrap();
+        // Both memories and the session's stored summary
+        assert_eq!(reader.recall("alice", None, None, None).unwrap().len(), 3);
+
+        let error = MindCache::builder().storage_path(temp_dir.path().to_string_lossy()).max_memories(0).build();
+        assert!(error.err().is_some_and(|error| error.is::<InvalidConfig>()));
+    }
+}
@@ -0,0 +1,262 @@
+//! In-memory LRU cache of recently used memory items
+//!
+//! Recalls that repeatedly touch the same memories (an agent re-reading its
+//! context every turn) are served from RAM instead of re-reading and
+//! deserializing the record from disk.
+//!
+//! A [`HotTier`](crate::tiering::HotTier) can sit behind the LRU: misses
+//! are looked up there before falling back to disk (see [`crate::tiering`]).
+
+use std::collections::{BTreeMap, HashMap};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use crate::storage::MemoryItem;
+use crate::tiering::{HotSet, HotTier, HotTierPolicy};
+
+/// Default number of memory items kept in the cache
+pub const DEFAULT_CACHE_CAPACITY: usize = 1024;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CacheStats {
+    pub capacity: usize,
+    pub entries: usize,
+    pub hits: u64,
+    pub misses: u64,
+    /// Memories in the hot tier, if one is attached
+    #[serde(default)]
+    pub hot_entries: usize,
+    /// LRU misses served by the hot tier
+    #[serde(default)]
+    pub hot_hits: u64,
+}
+
+struct CacheEntry {
+    memory: MemoryItem,
+    position: usize,
+    last_used: u64,
+}
+
+/// Least-recently-used cache keyed by memory id
+pub struct MemoryCache {
+    capacity: usize,
+    entries: HashMap<String, CacheEntry>,
+    ids_by_position: HashMap<usize, String>,
+    recency: BTreeMap<u64, String>,
+    tick: u64,
+    hits: u64,
+    misses: u64,
+    hot: Option<HotSet>,
+}
+
+impl MemoryCache {
+    /// Create a cache holding at most `capacity` items (0 disables caching)
+    pub fn new(capacity: usize) -> Self {
+        MemoryCache {
+            capacity,
+            entries: HashMap::new(),
+            ids_by_position: HashMap::new(),
+            recency: BTreeMap::new(),
+            tick: 0,
+            hits: 0,
+            misses: 0,
+            hot: None,
+        }
+    }
+
+    /// Put `tier` behind the LRU, or detach the current one with `None`
+    ///
+    /// The tier is emptied first; it fills again as memories are saved and read.
+    pub fn set_hot_tier(&mut self, tier: Option<std::sync::Arc<dyn HotTier>>, policy: HotTierPolicy) {
+        self.hot = tier.map(|tier| HotSet::new(tier, policy));
+    }
+
+    pub fn set_hot_tier_policy(&mut self, policy: HotTierPolicy) {
+        if let Some(hot) = &mut self.hot {
+            hot.set_policy(policy);
+        }
+    }
+
+    /// Move memories the hot tier policy no longer admits at `now` out of the hot tier
+    pub fn demote(&mut self, now: DateTime<Utc>) -> usize {
+        self.hot.as_mut().map_or(0, |hot| hot.demote(now))
+    }
+
+    /// Look up a memory by id, marking it as recently used
+    pub fn get(&mut self, id: &str) -> Option<MemoryItem> {
+        let tick = self.next_tick();
+        match self.entries.get_mut(id) {
+            Some(entry) => {
+                self.recency.remove(&entry.last_used);
+                entry.last_used = tick;
+                self.recency.insert(tick, id.to_string());
+                self.hits += 1;
+                Some(entry.memory.clone())
+            }
+            None => {
+                self.misses += 1;
+                let (position, memory) = self.hot.as_mut()?.get(id)?;
+                self.insert_lru(position, memory.clone());
+                Some(memory)
+            }
+        }
+    }
+
+    /// Look up the memory stored at a log position
+    pub fn get_at_position(&mut self, position: usize) -> Option<MemoryItem> {
+        let id = self
+            .ids_by_position
+            .get(&position)
+            .or_else(|| self.hot.as_ref().and_then(|hot| hot.id_at(position)))
+            .cloned();
+        match id {
+            Some(id) => self.get(&id),
+            None => {
+                self.misses += 1;
+                None
+            }
+        }
+    }
+
+    /// Insert or refresh a memory, evicting the least recently used item if full
+    ///
+    /// The memory is also copied to the hot tier if its policy admits it at `now`.
+    pub fn insert(&mut self, position: usize, memory: MemoryItem, now: DateTime<Utc>) {
+        if let Some(hot) = &mut self.hot {
+            hot.admit(position, &memory, now);
+        }
+        self.insert_lru(position, memory);
+    }
+
+    fn insert_lru(&mut self, position: usize, memory: MemoryItem) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        let tick = self.next_tick();
+        let id = memory.id.clone();
+
+        if let Some(old) = self.entries.remove(&id) {
+            self.recency.remove(&old.last_used);
+            self.ids_by_position.remove(&old.position);
+        }
+
+        while self.entries.len() >= self.capacity {
+            let Some((_, evicted_id)) = self.recency.pop_first() else { break };
+            if let Some(evicted) = self.entries.remove(&evicted_id) {
+                self.ids_by_position.remove(&evicted.position);
+            }
+        }
+
+        self.recency.insert(tick, id.clone());
+        self.ids_by_position.insert(position, id.clone());
+        self.entries.insert(id, CacheEntry { memory, position, last_used: tick });
+    }
+
+    /// Drop a memory from the cache and the hot tier
+    pub fn invalidate(&mut self, id: &str) {
+        if let Some(hot) = &mut self.hot {
+            hot.remove(id);
+        }
+        if let Some(entry) = self.entries.remove(id) {
+            self.recency.remove(&entry.last_used);
+            self.ids_by_position.remove(&entry.position);
+        }
+    }
+
+    /// Drop the memory cached for a log position
+    pub fn invalidate_position(&mut self, position: usize) {
+        let id = self
+            .ids_by_position
+            .get(&position)
+            .or_else(|| self.hot.as_ref().and_then(|hot| hot.id_at(position)))
+            .cloned();
+        if let Some(id) = id {
+            self.invalidate(&id);
+        }
+    }
+
+    /// Drop every cached memory, hot tier included (counters are kept)
+    pub fn clear(&mut self) {
+        if let Some(hot) = &mut self.hot {
+            hot.clear();
+        }
+        self.entries.clear();
+        self.ids_by_position.clear();
+        self.recency.clear();
+    }
+
+    /// Change the capacity, evicting least recently used items as needed
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        while self.entries.len() > self.capacity {
+            let Some((_, evicted_id)) = self.recency.pop_first() else { break };
+            if let Some(evicted) = self.entries.remove(&evicted_id) {
+                self.ids_by_position.remove(&evicted.position);
+            }
+        }
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            capacity: self.capacity,
+            entries: self.entries.len(),
+            hits: self.hits,
+            misses: self.misses,
+            hot_entries: self.hot.as_ref().map_or(0, HotSet::len),
+            hot_hits: self.hot.as_ref().map_or(0, HotSet::hits),
+        }
+    }
+
+    fn next_tick(&mut self) -> u64 {
+        self.tick += 1;
+        self.tick
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn memory(id: &str) -> MemoryItem {
+        MemoryItem {
+            id: id.to_string(),
+            user_id: "user".to_string(),
+            session_id: "session".to_string(),
+            content: format!("content {}", id),
+            metadata: HashMap::new(),
+            timestamp: Utc::now(),
+            ttl_hours: None,
+            importance: 0.5,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_lru_eviction_and_counters() {
+        let mut cache = MemoryCache::new(2);
+        cache.insert(0, memory("a"), Utc::now());
+        cache.insert(10, memory("b"), Utc::now());
+
+        // Touch "a" so "b" becomes the eviction candidate
+        assert!(cache.get("a").is_some());
+        cache.insert(20, memory("c"), Utc::now());
+
+        assert!(cache.get("b").is_none());
+        assert!(cache.get_at_position(0).is_some());
+        assert!(cache.get_at_position(20).is_some());
+
+        let stats = cache.stats();
+        assert_eq!(stats.entries, 2);
+        assert_eq!(stats.hits, 3);
+        assert_eq!(stats.misses, 1);
+    }
+
+    #[test]
+    fn test_zero_capacity_disables_cache() {
+        let mut cache = MemoryCache::new(0);
+        cache.insert(0, memory("a"), Utc::now());
+        assert!(cache.get("a").is_none());
+        assert_eq!(cache.stats().entries, 0);
+    }
+}
@@ -0,0 +1,295 @@
+//! Persisted session records and paged session listing
+//!
+//! Sessions created with `create_session` (or from a template, or on first
+//! save under the auto-create policy) are recorded in `sessions.json` with
+//! their name, tags and metadata, so they survive a restart. Listing joins
+//! these records with the memory counts and time spans kept in the storage
+//! index, which lets [`SessionQuery`] filter, sort and page a user's sessions
+//! without reading any memories.
+
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use crate::backend::StorageBackend;
+use crate::session::{Session, SessionManager};
+use crate::MindCache;
+
+pub const SESSIONS_BLOB: &str = "sessions.json";
+
+/// What a session was registered with
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SessionRecord {
+    pub user_id: String,
+    pub name: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub metadata: HashMap<String, String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Registered sessions, keyed by session id
+#[derive(Clone)]
+pub struct SessionCatalog {
+    backend: Arc<dyn StorageBackend>,
+    sessions: Arc<RwLock<HashMap<String, SessionRecord>>>,
+}
+
+impl SessionCatalog {
+    pub fn load(backend: Arc<dyn StorageBackend>) -> Result<Self, Box<dyn std::error::Error>> {
+        let sessions = Self::read_sessions(backend.as_ref())?;
+        Ok(SessionCatalog { backend, sessions: Arc::new(RwLock::new(sessions)) })
+    }
+
+    /// Re-read the records from disk, e.g. after a restore
+    pub fn reload(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let sessions = Self::read_sessions(self.backend.as_ref())?;
+        *self.sessions.write().unwrap_or_else(|poisoned| poisoned.into_inner()) = sessions;
+        Ok(())
+    }
+
+    fn read_sessions(backend: &dyn StorageBackend) -> Result<HashMap<String, SessionRecord>, Box<dyn std::error::Error>> {
+        match backend.read_blob(SESSIONS_BLOB)? {
+            Some(data) => Ok(serde_json::from_slice(&data)?),
+            None => Ok(HashMap::new()),
+        }
+    }
+
+    /// Apply `change` to the records and write them out
+    fn update(&self, change: impl FnOnce(&mut HashMap<String, SessionRecord>)) -> Result<(), Box<dyn std::error::Error>> {
+        let mut sessions = self.sessions.write().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let mut updated = sessions.clone();
+        change(&mut updated);
+        if updated != *sessions {
+            self.backend.write_blob(SESSIONS_BLOB, &serde_json::to_vec(&updated)?)?;
+            *sessions = updated;
+        }
+        Ok(())
+    }
+
+    /// Record a session, replacing any earlier record of it
+    pub fn set(&self, session_id: &str, record: SessionRecord) -> Result<(), Box<dyn std::error::Error>> {
+        self.update(|sessions| {
+            sessions.insert(session_id.to_string(), record);
+        })
+    }
+
+    pub fn get(&self, session_id: &str) -> Option<SessionRecord> {
+        self.sessions.read().unwrap_or_else(|poisoned| poisoned.into_inner()).get(session_id).cloned()
+    }
+
+    pub fn remove(&self, session_id: &str) -> Result<(), Box<dyn std::error::Error>> {
+        self.update(|sessions| {
+            sessions.remove(session_id);
+        })
+    }
+
+    /// Drop every record of a user, returning the session ids
+    pub fn remove_user(&self, user_id: &str) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        let mut removed = Vec::new();
+        self.update(|sessions| {
+            sessions.retain(|session_id, record| {
+                let keep = record.user_id != user_id;
+                if !keep {
+                    removed.push(session_id.clone());
+                }
+                keep
+            })
+        })?;
+        Ok(removed)
+    }
+
+    /// Every recorded session of a user, by id
+    pub fn for_user(&self, user_id: &str) -> HashMap<String, SessionRecord> {
+        let sessions = self.sessions.read().unwrap_or_else(|poisoned| poisoned.into_inner());
+        sessions.iter().filter(|(_, record)| record.user_id == user_id).map(|(id, record)| (id.clone(), record.clone())).collect()
+    }
+}
+
+/// Order of listed sessions
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SessionSort {
+    /// Most recently active first
+    #[default]
+    LastActive,
+    /// Newest first
+    CreatedAt,
+    /// Largest first
+    MemoryCount,
+    /// Alphabetical, unnamed sessions last
+    Name,
+}
+
+/// Filters, order and page of a session listing
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SessionQuery {
+    /// Keep sessions whose name contains this, ignoring case
+    pub name_contains: Option<String>,
+    /// Keep sessions registered with this tag
+    pub tag: Option<String>,
+    /// Keep sessions active at or after this time
+    pub active_from: Option<DateTime<Utc>>,
+    /// Keep sessions active at or before this time
+    pub active_to: Option<DateTime<Utc>>,
+    pub min_memories: Option<usize>,
+    pub sort: SessionSort,
+    /// Reverse the order of `sort`
+    pub reverse: bool,
+    /// Sessions to skip, after filtering and sorting
+    pub offset: usize,
+    pub limit: Option<usize>,
+}
+
+impl SessionQuery {
+    fn matches(&self, session: &Session) -> bool {
+        let name_matches = self.name_contains.as_ref().is_none_or(|needle| {
+            session.name.as_ref().is_some_and(|name| name.to_lowercase().contains(&needle.to_lowercase()))
+        });
+        name_matches
+            && self.tag.as_ref().is_none_or(|tag| session.tags.contains(tag))
+            && self.active_from.is_none_or(|from| session.last_active >= from)
+            && self.active_to.is_none_or(|to| session.created_at <= to)
+            && self.min_memories.is_none_or(|min| session.memory_count >= min)
+    }
+
+    fn sort(&self, sessions: &mut [Session]) {
+        // Ties stay in id order, so pages of the same listing do not overlap
+        sessions.sort_by(|a, b| a.id.cmp(&b.id));
+        match self.sort {
+            SessionSort::LastActive => sessions.sort_by_key(|session| std::cmp::Reverse(session.last_active)),
+            SessionSort::CreatedAt => sessions.sort_by_key(|session| std::cmp::Reverse(session.created_at)),
+            SessionSort::MemoryCount => sessions.sort_by_key(|session| std::cmp::Reverse(session.memory_count)),
+            SessionSort::Name => sessions.sort_by(|a, b| match (&a.name, &b.name) {
+                (Some(a), Some(b)) => a.to_lowercase().cmp(&b.to_lowercase()),
+                (a, b) => b.is_some().cmp(&a.is_some()),
+            }),
+        }
+        if self.reverse {
+            sessions.reverse();
+        }
+    }
+}
+
+/// One page of a session listing
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionPage {
+    pub sessions: Vec<Session>,
+    /// Sessions matching the filters, on every page
+    pub total: usize,
+    /// Offset of the next page; `None` on the last one
+    pub next_offset: Option<usize>,
+}
+
+impl SessionManager {
+    /// A page of a user's sessions, filtered and sorted by `query`
+    ///
+    /// Counts and times come from the storage index, and names, tags and
+    /// metadata from the recorded sessions, so no memory is read. Tags found
+    /// only in memory metadata, which `get_user_sessions` collects, are not
+    /// matched.
+    pub fn list_sessions(&self, user_id: &str, query: &SessionQuery) -> SessionPage {
+        let mut records = self.storage().sessions().for_user(user_id);
+        let mut sessions: Vec<Session> = self
+            .storage()
+            .session_activity(user_id)
+            .into_iter()
+            .map(|activity| {
+                let record = records.remove(&activity.session_id);
+                Session {
+                    id: activity.session_id,
+                    user_id: user_id.to_string(),
+                    name: record.as_ref().and_then(|record| record.name.clone()),
+                    created_at: record.as_ref().map_or(activity.first_memory_at, |record| record.created_at.min(activity.first_memory_at)),
+                    last_active: activity.last_memory_at,
+                    memory_count: activity.memory_count,
+                    tags: record.as_ref().map(|record| record.tags.clone()).unwrap_or_default(),
+                    metadata: record.map(|record| record.metadata).unwrap_or_default(),
+                }
+            })
+            .collect();
+        // Registered sessions nothing was saved into yet
+        sessions.extend(records.into_iter().map(|(id, record)| Session {
+            id,
+            user_id: user_id.to_string(),
+            name: record.name,
+            created_at: record.created_at,
+            last_active: record.created_at,
+            memory_count: 0,
+            tags: record.tags,
+            metadata: record.metadata,
+        }));
+
+        sessions.retain(|session| query.matches(session));
+        query.sort(&mut sessions);
+        let total = sessions.len();
+        let end = query.limit.map_or(total, |limit| query.offset.saturating_add(limit).min(total));
+        let page: Vec<Session> = sessions.drain(query.offset.min(end)..end).collect();
+        SessionPage { sessions: page, total, next_offset: (end < total).then_some(end) }
+    }
+}
+
+impl MindCache {
+    /// A page of a user's sessions; see [`SessionQuery`]
+    pub fn list_sessions(&self, user_id: &str, query: &SessionQuery) -> SessionPage {
+        self.session_manager.list_sessions(user_id, query)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::templates::SessionTemplate;
+    use crate::test_support::test_config;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_list_sessions_filters_sorts_and_pages() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = test_config(&temp_dir);
+        let mut cache = MindCache::with_config(config.clone()).unwrap();
+        let trading = cache.create_session("alice", Some("Trading desk")).unwrap();
+        let journal = cache.create_session_from_template("alice", &SessionTemplate {
+            name_pattern: Some("Journal".to_string()),
+            tags: vec!["journal".to_string()],
+            ..SessionTemplate::new("journal")
+        }).unwrap();
+        let empty = cache.create_session("alice", Some("Empty")).unwrap();
+        for content in ["Bought gold", "Sold silver", "Gold up"] {
+            cache.save("alice", &trading, content, None).unwrap();
+        }
+        cache.save("alice", &journal, "Slept well", None).unwrap();
+        cache.save("alice", "adhoc", "Untracked session", None).unwrap();
+        cache.save("bob", "other", "Not alice", None).unwrap();
+        drop(cache);
+
+        // Names and tags come back from disk after a restart
+        let cache = MindCache::with_config(config).unwrap();
+        let all = cache.list_sessions("alice", &SessionQuery::default());
+        assert_eq!(all.total, 4);
+        assert_eq!(all.next_offset, None);
+
+        let by_size = SessionQuery { sort: SessionSort::MemoryCount, ..Default::default() };
+        let ids: Vec<String> = cache.list_sessions("alice", &by_size).sessions.into_iter().map(|session| session.id).collect();
+        assert_eq!(ids[0], trading);
+        assert_eq!(ids[3], empty);
+
+        let named = cache.list_sessions("alice", &SessionQuery { name_contains: Some("JOURNAL".to_string()), ..Default::default() });
+        assert_eq!(named.sessions.len(), 1);
+        assert_eq!(named.sessions[0].tags, ["journal"]);
+        assert_eq!(cache.list_sessions("alice", &SessionQuery { tag: Some("journal".to_string()), ..Default::default() }).total, 1);
+        assert_eq!(cache.list_sessions("alice", &SessionQuery { min_memories: Some(1), ..Default::default() }).total, 3);
+
+        let by_name = SessionQuery { sort: SessionSort::Name, limit: Some(2), ..Default::default() };
+        let first = cache.list_sessions("alice", &by_name);
+        let names: Vec<Option<String>> = first.sessions.iter().map(|session| session.name.clone()).collect();
+        assert_eq!(names, [Some("Empty".to_string()), Some("Journal".to_string())]);
+        assert_eq!(first.next_offset, Some(2));
+        let second = cache.list_sessions("alice", &SessionQuery { offset: 2, ..by_name });
+        assert_eq!(second.sessions.len(), 2);
+        assert_eq!(second.sessions[1].id, "adhoc");
+        assert_eq!(second.next_offset, None);
+    }
+}
@@ -0,0 +1,195 @@
+//! Change-data-capture log
+//!
+//! When enabled, every save, update and delete appends a [`ChangeRecord`] to
+//! `changes.log` (one JSON object per line) with a durable, strictly
+//! increasing sequence number. External systems poll
+//! `MindCache::changes_since(last_seen)` to replicate incrementally.
+//!
+//! A write that fails after its change was logged gets a compensating change
+//! (a `deleted` for a failed save, and so on), so replaying the log in order
+//! always ends in the store's actual state.
+
+use std::sync::{Arc, Mutex};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use crate::backend::StorageBackend;
+use crate::clock::SharedClock;
+use crate::jsonl;
+use crate::storage::MemoryItem;
+
+pub const CHANGES_LOG: &str = "changes.log";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeKind {
+    Saved,
+    Updated,
+    Deleted,
+}
+
+/// One entry of the change log
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChangeRecord {
+    pub sequence: u64,
+    pub kind: ChangeKind,
+    pub memory_id: String,
+    pub user_id: String,
+    pub session_id: String,
+    /// Full memory after the change; `None` for deletes
+    pub memory: Option<MemoryItem>,
+    pub timestamp: DateTime<Utc>,
+}
+
+struct ChangeLogState {
+    enabled: bool,
+    last_sequence: u64,
+}
+
+/// Handle to the change log, shared by every clone of a storage handle
+#[derive(Clone)]
+pub struct ChangeLog {
+    backend: Arc<dyn StorageBackend>,
+    clock: SharedClock,
+    state: Arc<Mutex<ChangeLogState>>,
+}
+
+impl ChangeLog {
+    /// A disabled log; call `enable` to start recording
+    pub fn new(backend: Arc<dyn StorageBackend>, clock: SharedClock) -> Self {
+        ChangeLog {
+            backend,
+            clock,
+            state: Arc::new(Mutex::new(ChangeLogState { enabled: false, last_sequence: 0 })),
+        }
+    }
+
+    /// Start recording, continuing after the last sequence already on disk
+    pub fn enable(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let mut state = self.lock_state();
+        if state.enabled {
+            return Ok(());
+        }
+
+        let changes: Vec<ChangeRecord> = jsonl::read(self.backend.as_ref(), CHANGES_LOG)?;
+        state.last_sequence = changes.last().map_or(0, |change| change.sequence);
+        jsonl::seal(self.backend.as_ref(), CHANGES_LOG)?;
+        state.enabled = true;
+        Ok(())
+    }
+
+    /// Stop recording; sequence numbers continue where they left off if re-enabled
+    pub fn disable(&self) {
+        self.lock_state().enabled = false;
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.lock_state().enabled
+    }
+
+    /// Sequence of the most recent change (0 if none)
+    pub fn latest_sequence(&self) -> u64 {
+        self.lock_state().last_sequence
+    }
+
+    /// Append a change for `memory`; does nothing while disabled
+    pub fn record(&self, kind: ChangeKind, memory: &MemoryItem) -> Result<(), Box<dyn std::error::Error>> {
+        let mut state = self.lock_state();
+        if !state.enabled {
+            return Ok(());
+        }
+
+        let change = ChangeRecord {
+            sequence: state.last_sequence + 1,
+            kind,
+            memory_id: memory.id.clone(),
+            user_id: memory.user_id.clone(),
+            session_id: memory.session_id.clone(),
+            memory: (kind != ChangeKind::Deleted).then(|| memory.clone()),
+            timestamp: self.clock.now(),
+        };
+        jsonl::append(self.backend.as_ref(), CHANGES_LOG, &change)?;
+        state.last_sequence = change.sequence;
+        Ok(())
+    }
+
+    /// Strip the memory contents from every change of a user
+    ///
+    /// Ids, kinds and sequence numbers stay, so consumers can still replay
+    /// the log in order. Returns how many changes were redacted.
+    pub fn redact_user(&self, user_id: &str) -> Result<usize, Box<dyn std::error::Error>> {
+        let _state = self.lock_state();
+        let mut changes: Vec<ChangeRecord> = jsonl::read(self.backend.as_ref(), CHANGES_LOG)?;
+        let mut redacted = 0;
+        for change in changes.iter_mut().filter(|change| change.user_id == user_id) {
+            if change.memory.take().is_some() {
+                redacted += 1;
+            }
+        }
+        if redacted == 0 {
+            return Ok(0);
+        }
+
+        jsonl::rewrite(self.backend.as_ref(), CHANGES_LOG, &changes)?;
+        Ok(redacted)
+    }
+
+    /// Changes with a sequence greater than `sequence`, oldest first
+    pub fn since(&self, sequence: u64) -> Result<Vec<ChangeRecord>, Box<dyn std::error::Error>> {
+        let mut changes: Vec<ChangeRecord> = jsonl::read(self.backend.as_ref(), CHANGES_LOG)?;
+        changes.retain(|change| change.sequence > sequence);
+        Ok(changes)
+    }
+
+    fn lock_state(&self) -> std::sync::MutexGuard<'_, ChangeLogState> {
+        self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use crate::backend::FileBackend;
+    use tempfile::TempDir;
+
+    fn memory(id: &str) -> MemoryItem {
+        MemoryItem {
+            id: id.to_string(),
+            user_id: "alice".to_string(),
+            session_id: "s1".to_string(),
+            content: "content".to_string(),
+            metadata: HashMap::new(),
+            timestamp: Utc::now(),
+            ttl_hours: None,
+            importance: 0.5,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_sequence_survives_reopen_and_torn_lines() {
+        let temp_dir = TempDir::new().unwrap();
+        let backend: Arc<dyn StorageBackend> = Arc::new(FileBackend::new(temp_dir.path()).unwrap());
+
+        let log = ChangeLog::new(Arc::clone(&backend), SharedClock::default());
+        log.record(ChangeKind::Saved, &memory("ignored")).unwrap();
+        assert_eq!(log.latest_sequence(), 0, "disabled logs record nothing");
+
+        log.enable().unwrap();
+        log.record(ChangeKind::Saved, &memory("m1")).unwrap();
+        log.record(ChangeKind::Deleted, &memory("m1")).unwrap();
+        backend.append(CHANGES_LOG, b"{\"sequence\":3,\"ki").unwrap();
+
+        let reopened = ChangeLog::new(Arc::clone(&backend), SharedClock::default());
+        reopened.enable().unwrap();
+        assert_eq!(reopened.latest_sequence(), 2);
+        reopened.record(ChangeKind::Saved, &memory("m2")).unwrap();
+
+        let changes = reopened.since(1).unwrap();
+        assert_eq!(changes.len(), 2);
+        assert_eq!(changes[0].kind, ChangeKind::Deleted);
+        assert!(changes[0].memory.is_none());
+        assert_eq!(changes[1].sequence, 3);
+        assert_eq!(changes[1].memory_id, "m2");
+    }
+}
@@ -0,0 +1,206 @@
+//! Limits on the size of a memory's content
+//!
+//! Every scan of the log reads whole records, so one multi-megabyte paste
+//! slows down all later recalls of its user. With `max_content_bytes` set,
+//! saves over the limit are handled by the [`ContentLimitPolicy`]: rejected,
+//! cut short, or split into chunks saved as separate memories, each linked
+//! to the next with the [`NEXT_CHUNK`] relation. Cuts fall on grapheme
+//! boundaries, and chunks break after whitespace where they can.
+
+use std::fmt;
+use serde::{Deserialize, Serialize};
+use crate::storage::MemoryItem;
+use crate::text;
+use crate::MindCache;
+
+/// Relation linking each chunk of a split memory to the next
+pub const NEXT_CHUNK: &str = "next_chunk";
+
+/// Metadata key holding a chunk's position, counting from 1
+pub const CHUNK_INDEX_KEY: &str = "chunk_index";
+
+/// Metadata key holding how many chunks a memory was split into
+pub const CHUNK_COUNT_KEY: &str = "chunk_count";
+
+/// Metadata key holding the size in bytes of content that was truncated
+pub const ORIGINAL_BYTES_KEY: &str = "original_bytes";
+
+/// What a save does with content over `max_content_bytes`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ContentLimitPolicy {
+    /// Fail the save with [`ContentTooLarge`]
+    #[default]
+    Reject,
+    /// Keep only as much as fits
+    Truncate,
+    /// Save the content as several linked memories
+    Chunk,
+}
+
+/// A save was refused because its content is over `max_content_bytes`
+#[derive(Debug)]
+pub struct ContentTooLarge {
+    pub user_id: String,
+    pub bytes: usize,
+    pub limit: usize,
+}
+
+impl fmt::Display for ContentTooLarge {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "content of {} bytes from user {} is over the limit of {} bytes", self.bytes, self.user_id, self.limit)
+    }
+}
+
+impl std::error::Error for ContentTooLarge {}
+
+/// Split `content` into pieces of at most `max_bytes` bytes
+///
+/// A single grapheme longer than `max_bytes` becomes a piece of its own.
+pub fn split(content: &str, max_bytes: usize) -> Vec<&str> {
+    let mut pieces = Vec::new();
+    let mut rest = content;
+    while !rest.is_empty() {
+        let mut end = text::truncate_bytes(rest, max_bytes).len();
+        if end < rest.len() {
+            // Break after the last whitespace, unless that leaves a short piece
+            let whitespace_end = rest[..end]
+                .char_indices()
+                .rev()
+                .find(|(_, c)| c.is_whitespace())
+                .map(|(i, c)| i + c.len_utf8());
+            if let Some(whitespace_end) = whitespace_end.filter(|whitespace_end| *whitespace_end > end / 2) {
+                end = whitespace_end;
+            }
+        }
+        if end == 0 {
+            end = text::truncate(rest, 1).len();
+        }
+        pieces.push(&rest[..end]);
+        rest = &rest[end..];
+    }
+    pieces
+}
+
+impl MindCache {
+    /// The memories to store for `memory` under the content limit: itself,
+    /// cut short, or its chunks in order, to be linked with `link_chunks`
+    /// once stored
+    pub(crate) fn limit_content(&self, mut memory: MemoryItem) -> Result<Vec<MemoryItem>, Box<dyn std::error::Error>> {
+        let Some(limit) = self.config.max_content_bytes.filter(|limit| memory.content.len() > *limit) else {
+            return Ok(vec![memory]);
+        };
+        match self.config.content_limit_policy {
+            ContentLimitPolicy::Reject => Err(Box::new(ContentTooLarge {
+                user_id: memory.user_id,
+                bytes: memory.content.len(),
+                limit,
+            })),
+            ContentLimitPolicy::Truncate => {
+                memory.metadata.insert(ORIGINAL_BYTES_KEY.to_string(), memory.content.len().to_string());
+                memory.content = text::truncate_bytes(&memory.content, limit).to_string();
+                Ok(vec![memory])
+            }
+            ContentLimitPolicy::Chunk => {
+                let pieces = split(&memory.content, limit);
+                Ok(pieces
+                    .iter()
+                    .enumerate()
+                    .map(|(i, piece)| {
+                        let mut chunk = MemoryItem { content: piece.to_string(), ..memory.clone() };
+                        chunk.metadata.insert(CHUNK_INDEX_KEY.to_string(), (i + 1).to_string());
+                        chunk.metadata.insert(CHUNK_COUNT_KEY.to_string(), pieces.len().to_string());
+                        chunk
+                    })
+                    .collect())
+            }
+        }
+    }
+
+    /// Link each stored chunk of a split memory to the next
+    pub(crate) fn link_chunks(&self, ids: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+        for pair in ids.windows(2) {
+            self.storage.links().link(&pair[0], &pair[1], NEXT_CHUNK)?;
+        }
+        Ok(())
+    }
+
+    /// The chunks of a split memory in order, starting from `first_id`
+    ///
+    /// A memory that was not split is its own only chunk.
+    pub fn chunks_of(&self, first_id: &str) -> Result<Vec<MemoryItem>, Box<dyn std::error::Error>> {
+        let mut chunks = Vec::new();
+        let mut next = Some(first_id.to_string());
+        while let Some(id) = next.take() {
+            let Some(chunk) = self.storage.get_memory(&id)? else {
+                break;
+            };
+            next = self
+                .storage
+                .links()
+                .outgoing(&id)
+                .into_iter()
+                .find(|link| link.relation == NEXT_CHUNK)
+                .map(|link| link.to_id);
+            chunks.push(chunk);
+        }
+        Ok(chunks)
+    }
+
+    /// The content of a memory with its chunks joined back together
+    pub fn full_content(&self, first_id: &str) -> Result<Option<String>, Box<dyn std::error::Error>> {
+        let chunks = self.chunks_of(first_id)?;
+        Ok((!chunks.is_empty()).then(|| chunks.iter().map(|chunk| chunk.content.as_str()).collect()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MindCacheConfig;
+    use crate::test_support::test_config;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_split_respects_limit_and_whitespace() {
+        assert_eq!(split("alpha beta gamma", 11), ["alpha beta ", "gamma"]);
+        assert_eq!(split("abcdefgh", 3), ["abc", "def", "gh"]);
+        // Three bytes per character, so a four-byte limit fits one of them
+        assert_eq!(split("黄金价", 4), ["黄", "金", "价"]);
+        assert_eq!(split("👨‍👩‍👧", 4), ["👨‍👩‍👧"]);
+        assert!(split("", 4).is_empty());
+    }
+
+    #[test]
+    fn test_oversized_content_by_policy() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = |policy: ContentLimitPolicy| MindCacheConfig {
+            max_content_bytes: Some(16),
+            content_limit_policy: policy,
+            ..test_config(&temp_dir)
+        };
+        let paste = "Gold broke out above resistance on heavy volume";
+
+        let mut cache = MindCache::with_config(config(ContentLimitPolicy::Reject)).unwrap();
+        let error = cache.save("alice", "s1", paste, None).unwrap_err();
+        assert!(error.is::<ContentTooLarge>());
+        cache.save("alice", "s1", "Short enough", None).unwrap();
+        drop(cache);
+
+        let mut cache = MindCache::with_config(config(ContentLimitPolicy::Truncate)).unwrap();
+        let id = cache.save("alice", "s1", paste, None).unwrap();
+        let truncated = cache.get_memory(&id).unwrap().unwrap();
+        assert_eq!(truncated.content, "Gold broke out a");
+        assert_eq!(truncated.metadata[ORIGINAL_BYTES_KEY], paste.len().to_string());
+        drop(cache);
+
+        let mut cache = MindCache::with_config(config(ContentLimitPolicy::Chunk)).unwrap();
+        let first = cache.save("alice", "s2", paste, None).unwrap();
+        let chunks = cache.chunks_of(&first).unwrap();
+        assert_eq!(chunks.len(), 3);
+        assert!(chunks.iter().all(|chunk| chunk.content.len() <= 16 && chunk.session_id == "s2"));
+        assert_eq!(chunks[2].metadata[CHUNK_INDEX_KEY], "3");
+        assert_eq!(chunks[0].metadata[CHUNK_COUNT_KEY], "3");
+        assert_eq!(cache.full_content(&first).unwrap().unwrap(), paste);
+    }
+}
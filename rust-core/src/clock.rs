@@ -0,0 +1,156 @@
+//! Where MindCache reads the current time
+//!
+//! Save timestamps, TTL expiry, decay cutoffs, trash retention and session
+//! activity all come from the [`Clock`] set with `MindCache::set_clock`
+//! (or the builder's `clock`), [`SystemClock`] by default. Tests set a
+//! [`TestClock`] and move it forward to expire memories and run decay
+//! deterministically instead of saving with back-dated timestamps and
+//! hoping the timing works out. The clock is shared by every clone of a
+//! storage and by the logs and tables it owns (audit, changes, links,
+//! facts, spaces), so the session manager, decay engine and every record
+//! they write see the same time.
+
+use std::sync::{Arc, Mutex, RwLock};
+use chrono::{DateTime, Duration, Utc};
+use crate::MindCache;
+
+/// Source of the current time
+pub trait Clock: Send + Sync {
+    fn now(&self) -> DateTime<Utc>;
+}
+
+impl<F> Clock for F
+where
+    F: Fn() -> DateTime<Utc> + Send + Sync,
+{
+    fn now(&self) -> DateTime<Utc> {
+        self()
+    }
+}
+
+/// The system's wall clock
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+/// A clock that only moves when told to; clones share the same time
+#[derive(Debug, Clone)]
+pub struct TestClock {
+    now: Arc<Mutex<DateTime<Utc>>>,
+}
+
+impl TestClock {
+    pub fn new(start: DateTime<Utc>) -> Self {
+        TestClock { now: Arc::new(Mutex::new(start)) }
+    }
+
+    pub fn set(&self, now: DateTime<Utc>) {
+        *self.now.lock().unwrap_or_else(|poisoned| poisoned.into_inner()) = now;
+    }
+
+    pub fn advance(&self, by: Duration) {
+        *self.now.lock().unwrap_or_else(|poisoned| poisoned.into_inner()) += by;
+    }
+}
+
+impl Default for TestClock {
+    /// Starts at the current wall-clock time
+    fn default() -> Self {
+        TestClock::new(Utc::now())
+    }
+}
+
+impl Clock for TestClock {
+    fn now(&self) -> DateTime<Utc> {
+        *self.now.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// A clock that can be swapped for every holder at once; clones share it
+#[derive(Clone)]
+pub struct SharedClock(Arc<RwLock<Arc<dyn Clock>>>);
+
+impl SharedClock {
+    pub fn now(&self) -> DateTime<Utc> {
+        self.0.read().unwrap_or_else(|poisoned| poisoned.into_inner()).now()
+    }
+
+    pub fn set(&self, clock: Arc<dyn Clock>) {
+        *self.0.write().unwrap_or_else(|poisoned| poisoned.into_inner()) = clock;
+    }
+}
+
+impl Default for SharedClock {
+    /// Reads [`SystemClock`]
+    fn default() -> Self {
+        SharedClock(Arc::new(RwLock::new(Arc::new(SystemClock))))
+    }
+}
+
+impl MindCache {
+    /// Read the current time from `clock` from now on, e.g. a [`TestClock`]
+    pub fn set_clock<C: Clock + 'static>(&mut self, clock: C) {
+        self.storage.set_clock(Arc::new(clock));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{MindCacheConfig, QueryFilter, RelativeDuration};
+    use crate::test_support::temp_cache_with;
+
+    #[test]
+    fn test_saves_recall_and_trash_follow_the_clock() {
+        let (mut cache, _temp_dir) = temp_cache_with(MindCacheConfig {
+            trash_retention_days: 1,
+            ..Default::default()
+        });
+        let start = Utc::now() - Duration::days(30);
+        let clock = TestClock::new(start);
+        cache.set_clock(clock.clone());
+
+        let first = cache.save("alice", "s1", "Bought gold", None).unwrap();
+        assert_eq!(cache.get_memory(&first).unwrap().unwrap().timestamp, start);
+        clock.advance(Duration::hours(3));
+        cache.save("alice", "s1", "Sold silver", None).unwrap();
+
+        let last_hour = QueryFilter {
+            user_id: Some("alice".to_string()),
+            within: Some(RelativeDuration::hours(1)),
+            ..Default::default()
+        };
+        assert_eq!(cache.count(last_hour).unwrap(), 1);
+
+        cache.delete_memory_soft(&first).unwrap();
+        assert!(cache.purge_expired_trash(clock.now()).unwrap().is_empty());
+        clock.advance(Duration::days(2));
+        assert_eq!(cache.purge_expired_trash(clock.now()).unwrap(), vec![first]);
+    }
+
+    #[test]
+    fn test_logs_and_links_follow_the_clock() {
+        let (mut cache, _temp_dir) = temp_cache_with(MindCacheConfig {
+            audit_log_enabled: true,
+            change_log_enabled: true,
+            ..Default::default()
+        });
+        let start = Utc::now() - Duration::days(30);
+        cache.set_clock(TestClock::new(start));
+
+        let first = cache.save("alice", "s1", "Bought gold", None).unwrap();
+        let second = cache.save("alice", "s1", "Sold gold", None).unwrap();
+        cache.link_memories(&first, &second, "follows").unwrap();
+        cache.record_provenance("summary-1", vec![first.clone()], crate::provenance::DerivationMethod::Compression).unwrap();
+
+        assert!(cache.audit_trail(crate::AuditFilter::default()).unwrap().iter().all(|entry| entry.timestamp == start));
+        assert!(cache.changes_since(0).unwrap().iter().all(|change| change.timestamp == start));
+        assert_eq!(cache.storage.links().outgoing(&first)[0].created_at, start);
+        assert_eq!(cache.get_provenance("summary-1").unwrap().created_at, start);
+    }
+}
@@ -0,0 +1,155 @@
+//! Topic clustering of memories
+//!
+//! Each memory becomes a TF-IDF vector over its lowercased content words,
+//! IDF taken across the memories being clustered. Memories are then assigned
+//! in time order to the most similar existing cluster (cosine similarity to
+//! the cluster's summed vector) if that reaches the threshold, and start a new
+//! cluster otherwise. Decay compresses each cluster separately, so a summary
+//! only ever covers memories about the same thing.
+
+use std::collections::HashMap;
+use crate::stopwords::StopWords;
+use crate::storage::MemoryItem;
+
+/// Similarity decay compresses with unless configured otherwise
+pub const DEFAULT_CLUSTER_SIMILARITY: f32 = 0.1;
+
+type TermVector = HashMap<String, f32>;
+
+fn terms(content: &str, stop_words: &StopWords) -> Vec<String> {
+    let is_stop_word = stop_words.for_text(content);
+    content
+        .to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| word.len() > 2 && !is_stop_word(word))
+        .map(str::to_string)
+        .collect()
+}
+
+/// Unit-length TF-IDF vectors of `contents`, in order
+fn tf_idf(contents: &[&str], stop_words: &StopWords) -> Vec<TermVector> {
+    let documents: Vec<Vec<String>> = contents.iter().map(|content| terms(content, stop_words)).collect();
+    let mut document_frequency: HashMap<&str, usize> = HashMap::new();
+    for document in &documents {
+        let mut seen: Vec<&str> = document.iter().map(String::as_str).collect();
+        seen.sort_unstable();
+        seen.dedup();
+        for term in seen {
+            *document_frequency.entry(term).or_insert(0) += 1;
+        }
+    }
+
+    let count = documents.len() as f32;
+    documents
+        .iter()
+        .map(|document| {
+            let mut vector = TermVector::new();
+            for term in document {
+                *vector.entry(term.clone()).or_insert(0.0) += 1.0;
+            }
+            for (term, weight) in vector.iter_mut() {
+                // Smoothed so a term every memory shares still counts a little
+                let idf = (1.0 + count / document_frequency[term.as_str()] as f32).ln();
+                *weight *= idf;
+            }
+            normalize(&mut vector);
+            vector
+        })
+        .collect()
+}
+
+fn normalize(vector: &mut TermVector) {
+    let norm = vector.values().map(|weight| weight * weight).sum::<f32>().sqrt();
+    if norm > 0.0 {
+        vector.values_mut().for_each(|weight| *weight /= norm);
+    }
+}
+
+fn cosine(a: &TermVector, b: &TermVector) -> f32 {
+    let (small, large) = if a.len() <= b.len() { (a, b) } else { (b, a) };
+    small.iter().filter_map(|(term, weight)| large.get(term).map(|other| weight * other)).sum()
+}
+
+/// Split `memories` into groups about the same topic, oldest memory first in
+/// each group and groups in order of their oldest memory
+///
+/// A `min_similarity` of 0.0 keeps everything in one group.
+pub fn cluster_by_topic(mut memories: Vec<MemoryItem>, min_similarity: f32, stop_words: &StopWords) -> Vec<Vec<MemoryItem>> {
+    if memories.len() < 2 || min_similarity <= 0.0 {
+        return if memories.is_empty() { Vec::new() } else { vec![memories] };
+    }
+    memories.sort_by_key(|memory| memory.timestamp);
+
+    let contents: Vec<&str> = memories.iter().map(|memory| memory.content.as_str()).collect();
+    let vectors = tf_idf(&contents, stop_words);
+    let mut centroids: Vec<TermVector> = Vec::new();
+    let mut assignments: Vec<usize> = Vec::with_capacity(memories.len());
+    for vector in vectors {
+        let best = centroids
+            .iter()
+            .enumerate()
+            .map(|(cluster, centroid)| (cluster, cosine(&vector, centroid)))
+            .filter(|(_, similarity)| *similarity >= min_similarity)
+            .max_by(|a, b| a.1.total_cmp(&b.1));
+        match best {
+            Some((cluster, _)) => {
+                let centroid = &mut centroids[cluster];
+                for (term, weight) in vector {
+                    *centroid.entry(term).or_insert(0.0) += weight;
+                }
+                normalize(centroid);
+                assignments.push(cluster);
+            }
+            None => {
+                centroids.push(vector);
+                assignments.push(centroids.len() - 1);
+            }
+        }
+    }
+
+    let mut clusters: Vec<Vec<MemoryItem>> = vec![Vec::new(); centroids.len()];
+    for (memory, cluster) in memories.into_iter().zip(assignments) {
+        clusters[cluster].push(memory);
+    }
+    clusters
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, Utc};
+
+    fn memory(content: &str, minutes_ago: i64) -> MemoryItem {
+        MemoryItem {
+            id: content.to_string(),
+            content: content.to_string(),
+            timestamp: Utc::now() - Duration::minutes(minutes_ago),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_cluster_by_topic_separates_topics() {
+        let memories = vec![
+            memory("Baked sourdough bread with rye flour", 60),
+            memory("Booked flights to Lisbon for the conference", 50),
+            memory("Sourdough starter needs more flour daily", 40),
+            memory("Lisbon hotel near the conference venue", 30),
+            memory("Rye bread came out dense again", 20),
+        ];
+
+        let stop_words = StopWords::default();
+        let clusters = cluster_by_topic(memories.clone(), DEFAULT_CLUSTER_SIMILARITY, &stop_words);
+        let ids: Vec<Vec<&str>> = clusters
+            .iter()
+            .map(|cluster| cluster.iter().map(|memory| memory.id.as_str()).collect())
+            .collect();
+        assert_eq!(ids, [
+            vec!["Baked sourdough bread with rye flour", "Sourdough starter needs more flour daily", "Rye bread came out dense again"],
+            vec!["Booked flights to Lisbon for the conference", "Lisbon hotel near the conference venue"],
+        ]);
+
+        assert_eq!(cluster_by_topic(memories, 0.0, &stop_words).len(), 1);
+        assert!(cluster_by_topic(Vec::new(), DEFAULT_CLUSTER_SIMILARITY, &stop_words).is_empty());
+    }
+}
@@ -0,0 +1,650 @@
+//! Validation and reloading of [`MindCacheConfig`]
+//!
+//! A config is checked as a whole so that every problem is reported at once,
+//! each naming the field it is about.
+//!
+//! A running instance can re-read its config from a JSON file with
+//! `MindCache::load_config_file`, or watch the file and pick up edits with
+//! `watch_config_file` and `reload_config_if_changed`. Everything except
+//! `storage_path`, `read_only` and `replica_path` can change this way.
+//!
+//! Every field can also be set from a `MINDCACHE_*` environment variable,
+//! see [`ENV_VARS`], which is handy in containers where writing a JSON file
+//! is a chore.
+
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, SystemTime};
+use serde::{Deserialize, Serialize};
+use crate::events::MemoryEvent;
+use crate::{DecayPolicy, MindCache, MindCacheConfig};
+
+/// One problem found in a config
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConfigIssue {
+    /// Name of the offending field, as in the JSON config
+    pub field: String,
+    pub message: String,
+}
+
+/// A config that failed [`MindCacheConfig::validate`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InvalidConfig {
+    pub issues: Vec<ConfigIssue>,
+}
+
+impl fmt::Display for InvalidConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid config: ")?;
+        for (i, issue) in self.issues.iter().enumerate() {
+            if i > 0 {
+                write!(f, "; ")?;
+            }
+            write!(f, "{}: {}", issue.field, issue.message)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for InvalidConfig {}
+
+impl MindCacheConfig {
+    /// Check that every value makes sense, reporting all problems found
+    pub fn validate(&self) -> Result<(), InvalidConfig> {
+        let mut issues = Vec::new();
+        let mut issue = |field: &str, message: &str| {
+            issues.push(ConfigIssue { field: field.to_string(), message: message.to_string() });
+        };
+
+        if self.storage_path.trim().is_empty() {
+            issue("storage_path", "must not be empty");
+        }
+        if self.auto_decay_enabled && self.decay_interval_hours == 0 {
+            issue("decay_interval_hours", "must be at least 1 while auto_decay_enabled is set");
+        }
+        if self.default_memory_ttl_hours == Some(0) {
+            issue("default_memory_ttl_hours", "must be at least 1; save memories with a TTL of 0 to keep them forever");
+        }
+        if !(0.0..=1.0).contains(&self.compression_similarity) {
+            issue("compression_similarity", "must be between 0.0 and 1.0");
+        }
+        if self.max_memories_per_user == 0 {
+            issue("max_memories_per_user", "must be at least 1");
+        }
+        if !(0.0..=1.0).contains(&self.importance_threshold) {
+            issue("importance_threshold", "must be between 0.0 and 1.0");
+        }
+        if self.compress_threshold.is_some_and(|threshold| !(0.0..=1.0).contains(&threshold)) {
+            issue("compress_threshold", "must be between 0.0 and 1.0");
+        }
+        if self.protect_threshold.is_some_and(|threshold| !(0.0..=1.0).contains(&threshold)) {
+            issue("protect_threshold", "must be between 0.0 and 1.0");
+        }
+        if self.max_saves_per_minute == Some(0) {
+            issue("max_saves_per_minute", "must be at least 1; use null for no limit");
+        }
+        if self.max_recalls_per_minute == Some(0) {
+            issue("max_recalls_per_minute", "must be at least 1; use null for no limit");
+        }
+        if let Some(replica_path) = &self.replica_path {
+            if replica_path.trim().is_empty() {
+                issue("replica_path", "must not be empty; use null for no replica");
+            } else if Path::new(replica_path) == Path::new(&self.storage_path) {
+                issue("replica_path", "must differ from storage_path");
+            }
+            if self.read_only {
+                issue("replica_path", "a read-only instance makes no writes to replicate");
+            }
+        }
+        if self.write_batch_size == 0 {
+            issue("write_batch_size", "must be at least 1; use 1 to persist every save on its own");
+        }
+        if self.write_batch_size > 1 && self.write_batch_delay_ms == 0 {
+            issue("write_batch_delay_ms", "must be at least 1 while write_batch_size is above 1");
+        }
+        if self.hot_tier_url.is_some() && !cfg!(feature = "redis") {
+            issue("hot_tier_url", "needs mindcache built with the redis feature");
+        }
+        if !(0.0..=1.0).contains(&self.hot_tier_min_importance) {
+            issue("hot_tier_min_importance", "must be between 0.0 and 1.0");
+        }
+        if self.max_content_bytes == Some(0) {
+            issue("max_content_bytes", "must be at least 1; use null for no limit");
+        }
+        if self.review_interval_hours == Some(0) {
+            issue("review_interval_hours", "must be at least 1; use null for no reviews");
+        }
+        if let Some(Err(invalid)) = self.decay_policy.as_ref().map(DecayPolicy::validate) {
+            for policy_issue in invalid.issues {
+                issue(&policy_issue.field, &policy_issue.message);
+            }
+        }
+        for (i, api_key) in self.api_keys.iter().enumerate() {
+            if api_key.key.trim().is_empty() {
+                issue("api_keys", "keys must not be empty");
+            } else if self.api_keys[..i].iter().any(|other| other.key == api_key.key) {
+                issue("api_keys", "keys must be unique");
+            }
+            if api_key.scopes.is_empty() {
+                issue("api_keys", "every key needs at least one scope");
+            }
+        }
+
+        if issues.is_empty() {
+            Ok(())
+        } else {
+            Err(InvalidConfig { issues })
+        }
+    }
+}
+
+impl DecayPolicy {
+    /// Check the policy as `MindCacheConfig::validate` checks its
+    /// `decay_policy`, with fields named `decay_policy.<field>`
+    pub fn validate(&self) -> Result<(), InvalidConfig> {
+        let mut issues = Vec::new();
+        let mut issue = |field: &str, message: &str| {
+            issues.push(ConfigIssue { field: format!("decay_policy.{}", field), message: message.to_string() });
+        };
+
+        if self.max_age_hours == 0 {
+            issue("max_age_hours", "must be at least 1");
+        }
+        for (field, value) in [
+            ("delete_below", self.delete_below),
+            ("compress_below", self.compress_below),
+            ("protect_above", self.protect_above),
+            ("cluster_similarity", self.cluster_similarity),
+        ] {
+            if !(0.0..=1.0).contains(&value) {
+                issue(field, "must be between 0.0 and 1.0");
+            }
+        }
+        if self.max_memories_per_user == 0 {
+            issue("max_memories_per_user", "must be at least 1");
+        }
+
+        if issues.is_empty() {
+            Ok(())
+        } else {
+            Err(InvalidConfig { issues })
+        }
+    }
+}
+
+/// Environment variables read by `from_env` and `overlay_env`, with the
+/// field each one sets
+///
+/// Booleans accept `true`/`false`, `1`/`0`, `yes`/`no` and `on`/`off`;
+/// `MINDCACHE_QUOTA_POLICY` takes the snake_case name of a [`QuotaPolicy`](crate::QuotaPolicy)
+/// and `MINDCACHE_CONTENT_LIMIT_POLICY`, `MINDCACHE_SESSION_POLICY` and
+/// `MINDCACHE_REVIEW_DELIVERY` those of a [`ContentLimitPolicy`](crate::ContentLimitPolicy),
+/// a [`SessionPolicy`](crate::SessionPolicy) and a [`ReviewDelivery`](crate::ReviewDelivery),
+/// `MINDCACHE_STOP_WORD_LANGUAGE` that of a [`Language`](crate::Language) or
+/// `auto`, `MINDCACHE_EXTRA_STOP_WORDS` a comma-separated list and
+/// `MINDCACHE_API_KEYS` a JSON array of [`ApiKey`](crate::ApiKey)s and
+/// `MINDCACHE_DECAY_POLICY` a JSON [`DecayPolicy`](crate::DecayPolicy), or
+/// `none` to use the separate decay fields.
+/// `MINDCACHE_DEFAULT_TTL_HOURS` set to `none` or to an empty string means
+/// memories never expire, and likewise no limit for the rate limits, no
+/// reviews for `MINDCACHE_REVIEW_INTERVAL_HOURS`, no
+/// override for the compress and protect thresholds and no hot tier or
+/// replica for `MINDCACHE_HOT_TIER_URL` and `MINDCACHE_REPLICA_PATH`.
+pub const ENV_VARS: &[(&str, &str)] = &[
+    ("MINDCACHE_STORAGE_PATH", "storage_path"),
+    ("MINDCACHE_AUTO_DECAY", "auto_decay_enabled"),
+    ("MINDCACHE_DECAY_INTERVAL_HOURS", "decay_interval_hours"),
+    ("MINDCACHE_DEFAULT_TTL_HOURS", "default_memory_ttl_hours"),
+    ("MINDCACHE_COMPRESSION", "enable_compression"),
+    ("MINDCACHE_COMPRESSION_SIMILARITY", "compression_similarity"),
+    ("MINDCACHE_MAX_MEMORIES", "max_memories_per_user"),
+    ("MINDCACHE_IMPORTANCE_THRESHOLD", "importance_threshold"),
+    ("MINDCACHE_COMPRESS_THRESHOLD", "compress_threshold"),
+    ("MINDCACHE_PROTECT_THRESHOLD", "protect_threshold"),
+    ("MINDCACHE_CACHE_CAPACITY", "memory_cache_capacity"),
+    ("MINDCACHE_WRITE_BATCH_SIZE", "write_batch_size"),
+    ("MINDCACHE_WRITE_BATCH_DELAY_MS", "write_batch_delay_ms"),
+    ("MINDCACHE_HOT_TIER_URL", "hot_tier_url"),
+    ("MINDCACHE_HOT_TIER_MIN_IMPORTANCE", "hot_tier_min_importance"),
+    ("MINDCACHE_HOT_TIER_RECENT_HOURS", "hot_tier_recent_hours"),
+    ("MINDCACHE_HOT_TIER_MAX_MEMORIES", "hot_tier_max_memories"),
+    ("MINDCACHE_CHANGE_LOG", "change_log_enabled"),
+    ("MINDCACHE_HISTORY", "history_enabled"),
+    ("MINDCACHE_AUDIT_LOG", "audit_log_enabled"),
+    ("MINDCACHE_TRASH_RETENTION_DAYS", "trash_retention_days"),
+    ("MINDCACHE_AUTO_IMPORTANCE", "auto_importance_enabled"),
+    ("MINDCACHE_ENTITY_EXTRACTION", "entity_extraction_enabled"),
+    ("MINDCACHE_STOP_WORD_LANGUAGE", "stop_word_language"),
+    ("MINDCACHE_EXTRA_STOP_WORDS", "extra_stop_words"),
+    ("MINDCACHE_READ_ONLY", "read_only"),
+    ("MINDCACHE_REPLICA_PATH", "replica_path"),
+    ("MINDCACHE_QUOTA_POLICY", "quota_policy"),
+    ("MINDCACHE_SESSION_POLICY", "session_policy"),
+    ("MINDCACHE_MAX_SAVES_PER_MINUTE", "max_saves_per_minute"),
+    ("MINDCACHE_MAX_RECALLS_PER_MINUTE", "max_recalls_per_minute"),
+    ("MINDCACHE_ALLOW_WILDCARD_RECALL", "allow_wildcard_recall"),
+    ("MINDCACHE_API_KEYS", "api_keys"),
+    ("MINDCACHE_MAX_CONTENT_BYTES", "max_content_bytes"),
+    ("MINDCACHE_CONTENT_LIMIT_POLICY", "content_limit_policy"),
+    ("MINDCACHE_REVIEW_INTERVAL_HOURS", "review_interval_hours"),
+    ("MINDCACHE_REVIEW_DELIVERY", "review_delivery"),
+    ("MINDCACHE_DECAY_POLICY", "decay_policy"),
+];
+
+impl MindCacheConfig {
+    /// The defaults with any `MINDCACHE_*` environment variables applied
+    pub fn from_env() -> Result<Self, InvalidConfig> {
+        MindCacheConfig::default().overlay_env()
+    }
+
+    /// Apply any `MINDCACHE_*` environment variables on top of this config
+    ///
+    /// Variables that are not set leave their field alone. Every variable
+    /// that does not parse is reported; the result is also validated.
+    pub fn overlay_env(self) -> Result<Self, InvalidConfig> {
+        self.overlay_vars(|name| std::env::var(name).ok())
+    }
+
+    /// `overlay_env` with variables looked up through `var`
+    pub fn overlay_vars(mut self, var: impl Fn(&str) -> Option<String>) -> Result<Self, InvalidConfig> {
+        let mut issues = Vec::new();
+        for (name, field) in ENV_VARS {
+            let Some(value) = var(name) else { continue };
+            if let Err(message) = self.set_field(field, value.trim()) {
+                issues.push(ConfigIssue { field: field.to_string(), message: format!("{} {}", name, message) });
+            }
+        }
+        if !issues.is_empty() {
+            return Err(InvalidConfig { issues });
+        }
+        self.validate()?;
+        Ok(self)
+    }
+
+    fn set_field(&mut self, field: &str, value: &str) -> Result<(), String> {
+        fn parse<T: std::str::FromStr>(value: &str, kind: &str) -> Result<T, String> {
+            value.parse().map_err(|_| format!("must be {}, got {:?}", kind, value))
+        }
+        fn optional<T: std::str::FromStr>(value: &str, kind: &str) -> Result<Option<T>, String> {
+            match value {
+                "" => Ok(None),
+                none if none.eq_ignore_ascii_case("none") => Ok(None),
+                value => parse(value, kind).map(Some),
+            }
+        }
+        fn flag(value: &str) -> Result<bool, String> {
+            match value.to_ascii_lowercase().as_str() {
+                "true" | "1" | "yes" | "on" => Ok(true),
+                "false" | "0" | "no" | "off" => Ok(false),
+                _ => Err(format!("must be true or false, got {:?}", value)),
+            }
+        }
+
+        match field {
+            "storage_path" => self.storage_path = value.to_string(),
+            "auto_decay_enabled" => self.auto_decay_enabled = flag(value)?,
+            "decay_interval_hours" => self.decay_interval_hours = parse(value, "a whole number of hours")?,
+            "default_memory_ttl_hours" => {
+                self.default_memory_ttl_hours = optional(value, "a whole number of hours or none")?
+            }
+            "enable_compression" => self.enable_compression = flag(value)?,
+            "compression_similarity" => self.compression_similarity = parse(value, "a number")?,
+            "max_memories_per_user" => self.max_memories_per_user = parse(value, "a whole number")?,
+            "importance_threshold" => self.importance_threshold = parse(value, "a number")?,
+            "compress_threshold" => self.compress_threshold = optional(value, "a number or none")?,
+            "protect_threshold" => self.protect_threshold = optional(value, "a number or none")?,
+            "memory_cache_capacity" => self.memory_cache_capacity = parse(value, "a whole number")?,
+            "write_batch_size" => self.write_batch_size = parse(value, "a whole number")?,
+            "write_batch_delay_ms" => self.write_batch_delay_ms = parse(value, "a whole number of milliseconds")?,
+            "hot_tier_url" => self.hot_tier_url = optional(value, "a redis:// URL or none")?,
+            "hot_tier_min_importance" => self.hot_tier_min_importance = parse(value, "a number")?,
+            "hot_tier_recent_hours" => self.hot_tier_recent_hours = parse(value, "a whole number of hours")?,
+            "hot_tier_max_memories" => self.hot_tier_max_memories = parse(value, "a whole number")?,
+            "change_log_enabled" => self.change_log_enabled = flag(value)?,
+            "history_enabled" => self.history_enabled = flag(value)?,
+            "audit_log_enabled" => self.audit_log_enabled = flag(value)?,
+            "trash_retention_days" => self.trash_retention_days = parse(value, "a whole number of days")?,
+            "auto_importance_enabled" => self.auto_importance_enabled = flag(value)?,
+            "entity_extraction_enabled" => self.entity_extraction_enabled = flag(value)?,
+            "stop_word_language" => {
+                self.stop_word_language = match value.to_ascii_lowercase().as_str() {
+                    "" | "none" | "auto" => None,
+                    language => Some(serde_json::from_value(serde_json::Value::String(language.to_string())).map_err(|_| {
+                        format!("must be english, spanish, french, german, portuguese, italian or auto, got {:?}", value)
+                    })?),
+                }
+            }
+            "extra_stop_words" => {
+                self.extra_stop_words = value.split(',').map(str::trim).filter(|word| !word.is_empty()).map(str::to_string).collect()
+            }
+            "read_only" => self.read_only = flag(value)?,
+            "replica_path" => self.replica_path = optional(value, "a directory or none")?,
+            "max_saves_per_minute" => self.max_saves_per_minute = optional(value, "a whole number or none")?,
+            "max_recalls_per_minute" => self.max_recalls_per_minute = optional(value, "a whole number or none")?,
+            "allow_wildcard_recall" => self.allow_wildcard_recall = flag(value)?,
+            "max_content_bytes" => self.max_content_bytes = optional(value, "a whole number of bytes or none")?,
+            "content_limit_policy" => {
+                self.content_limit_policy = serde_json::from_value(serde_json::Value::String(value.to_ascii_lowercase()))
+                    .map_err(|_| format!("must be reject, truncate or chunk, got {:?}", value))?
+            }
+            "review_interval_hours" => self.review_interval_hours = optional(value, "a whole number of hours or none")?,
+            "review_delivery" => {
+                self.review_delivery = serde_json::from_value(serde_json::Value::String(value.to_ascii_lowercase()))
+                    .map_err(|_| format!("must be event, store or both, got {:?}", value))?
+            }
+            "api_keys" => {
+                self.api_keys = serde_json::from_str(value).map_err(|e| format!("must be a JSON array of API keys: {}", e))?
+            }
+            "decay_policy" => {
+                self.decay_policy = match value {
+                    "" => None,
+                    none if none.eq_ignore_ascii_case("none") => None,
+                    value => Some(serde_json::from_str(value).map_err(|e| format!("must be a JSON decay policy or none: {}", e))?),
+                }
+            }
+            "quota_policy" => {
+                self.quota_policy = serde_json::from_value(serde_json::Value::String(value.to_ascii_lowercase()))
+                    .map_err(|_| format!("must be decay, reject, evict_least_important or evict_oldest, got {:?}", value))?
+            }
+            "session_policy" => {
+                self.session_policy = serde_json::from_value(serde_json::Value::String(value.to_ascii_lowercase()))
+                    .map_err(|_| format!("must be implicit, auto_create or strict, got {:?}", value))?
+            }
+            _ => unreachable!("ENV_VARS names an unknown field"),
+        }
+        Ok(())
+    }
+}
+
+/// Read a config from a JSON file and validate it
+pub fn read_config_file<P: AsRef<Path>>(path: P) -> Result<MindCacheConfig, Box<dyn std::error::Error>> {
+    let path = path.as_ref();
+    let contents = fs::read_to_string(path).map_err(|e| format!("cannot read {}: {}", path.display(), e))?;
+    let config: MindCacheConfig =
+        serde_json::from_str(&contents).map_err(|e| format!("cannot parse {}: {}", path.display(), e))?;
+    config.validate()?;
+    Ok(config)
+}
+
+/// Names of the fields that differ between two configs, sorted
+pub fn changed_fields(old: &MindCacheConfig, new: &MindCacheConfig) -> Vec<String> {
+    let (Ok(serde_json::Value::Object(old)), Ok(serde_json::Value::Object(new))) =
+        (serde_json::to_value(old), serde_json::to_value(new))
+    else {
+        return Vec::new();
+    };
+    let mut changed: Vec<String> = new
+        .iter()
+        .filter(|(field, value)| old.get(field.as_str()) != Some(value))
+        .map(|(field, _)| field.clone())
+        .collect();
+    changed.sort();
+    changed
+}
+
+/// Polls a config file in the background and notes when it changes
+///
+/// Changes are detected by modification time and size. The polling thread
+/// stops when the watcher is dropped.
+pub struct ConfigWatcher {
+    path: PathBuf,
+    changed: Arc<AtomicBool>,
+    stop: Arc<AtomicBool>,
+}
+
+impl ConfigWatcher {
+    pub fn start<P: AsRef<Path>>(path: P, poll_interval: Duration) -> std::io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let changed = Arc::new(AtomicBool::new(false));
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let mut last_seen = file_stamp(&path);
+        let (watched, flag, stopped) = (path.clone(), Arc::clone(&changed), Arc::clone(&stop));
+        thread::Builder::new().name("mindcache-config-watcher".to_string()).spawn(move || {
+            while !stopped.load(Ordering::SeqCst) {
+                thread::sleep(poll_interval);
+                let stamp = file_stamp(&watched);
+                if stamp != last_seen {
+                    last_seen = stamp;
+                    flag.store(true, Ordering::SeqCst);
+                }
+            }
+        })?;
+
+        Ok(ConfigWatcher { path, changed, stop })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns true once per detected change
+    pub fn take_change(&self) -> bool {
+        self.changed.swap(false, Ordering::SeqCst)
+    }
+}
+
+impl Drop for ConfigWatcher {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+    }
+}
+
+fn file_stamp(path: &Path) -> Option<(SystemTime, u64)> {
+    let metadata = fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+impl MindCache {
+    /// Re-read the config from a JSON file and apply it
+    ///
+    /// Returns the fields that changed and emits `ConfigReloaded` if any did.
+    /// An invalid file leaves the current config in place.
+    pub fn load_config_file<P: AsRef<Path>>(&mut self, path: P) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        let path = path.as_ref();
+        let config = read_config_file(path)?;
+        self.apply_reloaded_config(config, &path.display().to_string())
+    }
+
+    /// Apply a config re-read from `source`, reporting what changed
+    pub(crate) fn apply_reloaded_config(
+        &mut self,
+        config: MindCacheConfig,
+        source: &str,
+    ) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        if config.storage_path != self.config.storage_path {
+            return Err("storage_path cannot change on an open instance; reopen it instead".into());
+        }
+
+        let changed = changed_fields(&self.config, &config);
+        self.update_config(config)?;
+        if !changed.is_empty() {
+            println!("Configuration reloaded from {}: {} changed", source, changed.join(", "));
+            self.storage.events().emit(MemoryEvent::ConfigReloaded {
+                path: source.to_string(),
+                changed_fields: changed.clone(),
+            });
+        }
+        Ok(changed)
+    }
+
+    /// Load a config file now and keep watching it for changes
+    ///
+    /// Changes are applied by `reload_config_if_changed`, which the
+    /// application calls from its own loop; nothing is applied behind its back.
+    pub fn watch_config_file<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        poll_interval: Duration,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.load_config_file(&path)?;
+        self.config_watcher = Some(ConfigWatcher::start(path, poll_interval)?);
+        Ok(())
+    }
+
+    /// Stop watching the config file
+    pub fn unwatch_config_file(&mut self) {
+        self.config_watcher = None;
+    }
+
+    /// Apply the watched config file if it changed since the last call
+    ///
+    /// Returns the fields that changed, empty when there was nothing to do.
+    pub fn reload_config_if_changed(&mut self) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        let path = match &self.config_watcher {
+            Some(watcher) if watcher.take_change() => watcher.path().to_path_buf(),
+            _ => return Ok(Vec::new()),
+        };
+        self.load_config_file(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::temp_cache_with;
+
+    #[test]
+    fn test_validate_reports_every_problem() {
+        assert!(MindCacheConfig::default().validate().is_ok());
+
+        let config = MindCacheConfig {
+            storage_path: " ".to_string(),
+            max_memories_per_user: 0,
+            importance_threshold: f32::NAN,
+            protect_threshold: Some(1.5),
+            default_memory_ttl_hours: Some(0),
+            decay_interval_hours: 0,
+            ..Default::default()
+        };
+        let invalid = config.validate().unwrap_err();
+        let fields: Vec<&str> = invalid.issues.iter().map(|issue| issue.field.as_str()).collect();
+        assert_eq!(fields, [
+            "storage_path",
+            "decay_interval_hours",
+            "default_memory_ttl_hours",
+            "max_memories_per_user",
+            "importance_threshold",
+            "protect_threshold",
+        ]);
+        assert!(invalid.to_string().starts_with("invalid config: storage_path: must not be empty; "));
+
+        let manual_decay = MindCacheConfig { auto_decay_enabled: false, decay_interval_hours: 0, ..Default::default() };
+        assert!(manual_decay.validate().is_ok());
+    }
+
+    #[test]
+    fn test_watched_config_file_is_reloaded() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let config = MindCacheConfig {
+            storage_path: temp_dir.path().join("store").to_string_lossy().into_owned(),
+            auto_decay_enabled: false,
+            ..Default::default()
+        };
+        let config_path = temp_dir.path().join("mindcache.json");
+        fs::write(&config_path, serde_json::to_string(&config).unwrap()).unwrap();
+
+        let mut cache = MindCache::with_config(config.clone()).unwrap();
+        let events = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let seen = Arc::clone(&events);
+        cache.subscribe(move |event: &MemoryEvent| {
+            if let MemoryEvent::ConfigReloaded { changed_fields, .. } = event {
+                seen.lock().unwrap().push(changed_fields.clone());
+            }
+        });
+        cache.watch_config_file(&config_path, Duration::from_millis(10)).unwrap();
+        assert!(cache.reload_config_if_changed().unwrap().is_empty());
+
+        let tuned = MindCacheConfig { importance_threshold: 0.6, max_memories_per_user: 50, ..config.clone() };
+        fs::write(&config_path, serde_json::to_string_pretty(&tuned).unwrap()).unwrap();
+        let mut changed = Vec::new();
+        for _ in 0..200 {
+            changed = cache.reload_config_if_changed().unwrap();
+            if !changed.is_empty() {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(changed, ["importance_threshold", "max_memories_per_user"]);
+        assert_eq!(cache.config().max_memories_per_user, 50);
+        assert_eq!(*events.lock().unwrap(), [changed]);
+
+        let broken = MindCacheConfig { importance_threshold: 2.0, ..tuned.clone() };
+        fs::write(&config_path, serde_json::to_string(&broken).unwrap()).unwrap();
+        assert!(cache.load_config_file(&config_path).is_err());
+        assert_eq!(cache.config().importance_threshold, 0.6);
+
+        let moved = MindCacheConfig { storage_path: "./elsewhere".to_string(), ..tuned };
+        fs::write(&config_path, serde_json::to_string(&moved).unwrap()).unwrap();
+        assert!(cache.load_config_file(&config_path).is_err());
+    }
+
+    #[test]
+    fn test_env_overlay() {
+        let env = |vars: &'static [(&'static str, &'static str)]| {
+            move |name: &str| vars.iter().find(|(var, _)| *var == name).map(|(_, value)| value.to_string())
+        };
+
+        let file = MindCacheConfig { enable_compression: false, ..Default::default() };
+        let config = file
+            .overlay_vars(env(&[
+                ("MINDCACHE_STORAGE_PATH", "/data/mindcache"),
+                ("MINDCACHE_MAX_MEMORIES", " 500 "),
+                ("MINDCACHE_AUTO_DECAY", "off"),
+                ("MINDCACHE_DEFAULT_TTL_HOURS", "none"),
+                ("MINDCACHE_IMPORTANCE_THRESHOLD", "0.25"),
+                ("MINDCACHE_QUOTA_POLICY", "Evict_Oldest"),
+            ]))
+            .unwrap();
+        assert_eq!(config.storage_path, "/data/mindcache");
+        assert_eq!(config.max_memories_per_user, 500);
+        assert!(!config.auto_decay_enabled);
+        assert_eq!(config.default_memory_ttl_hours, None);
+        assert_eq!(config.importance_threshold, 0.25);
+        assert_eq!(config.quota_policy, crate::QuotaPolicy::EvictOldest);
+        assert!(!config.enable_compression, "unset variables keep the file's value");
+
+        let invalid = MindCacheConfig::default()
+            .overlay_vars(env(&[("MINDCACHE_MAX_MEMORIES", "lots"), ("MINDCACHE_READ_ONLY", "maybe")]))
+            .unwrap_err();
+        let fields: Vec<&str> = invalid.issues.iter().map(|issue| issue.field.as_str()).collect();
+        assert_eq!(fields, ["max_memories_per_user", "read_only"]);
+        assert!(invalid.issues[0].message.starts_with("MINDCACHE_MAX_MEMORIES must be a whole number"));
+
+        let out_of_range = MindCacheConfig::default().overlay_vars(env(&[("MINDCACHE_IMPORTANCE_THRESHOLD", "3")]));
+        assert_eq!(out_of_range.unwrap_err().issues[0].field, "importance_threshold");
+
+        let with_policy = MindCacheConfig::default()
+            .overlay_vars(env(&[("MINDCACHE_DECAY_POLICY", r#"{"max_age_hours": 48, "compression_enabled": false}"#)]))
+            .unwrap();
+        let policy = with_policy.decay_policy.unwrap();
+        assert_eq!(policy.max_age_hours, 48);
+        assert!(!policy.compression_enabled);
+        let bad_policy = MindCacheConfig::default().overlay_vars(env(&[("MINDCACHE_DECAY_POLICY", r#"{"delete_below": 2}"#)]));
+        assert_eq!(bad_policy.unwrap_err().issues[0].field, "decay_policy.delete_below");
+    }
+
+    #[test]
+    fn test_set_decay_policy_at_runtime() {
+        let (mut cache, _temp_dir) = temp_cache_with(MindCacheConfig {
+            importance_threshold: 0.4,
+            ..Default::default()
+        });
+        assert_eq!(cache.decay_policy().delete_below, 0.4);
+
+        let policy = DecayPolicy { max_age_hours: 12, auto_summarize_sessions: false, ..cache.decay_policy() };
+        cache.set_decay_policy(policy.clone()).unwrap();
+        assert_eq!(cache.decay_policy(), policy);
+        assert_eq!(cache.config().decay_policy.as_ref(), Some(&policy));
+        assert!(cache.set_decay_policy(DecayPolicy { protect_above: -1.0, ..policy.clone() }).is_err());
+        assert_eq!(cache.decay_policy(), policy);
+
+        // Config updates without a policy go back to the separate fields
+        cache.update_config(MindCacheConfig { decay_policy: None, ..cache.config().clone() }).unwrap();
+        assert_eq!(cache.decay_policy().max_age_hours, 24 * 30);
+    }
+}
@@ -0,0 +1,167 @@
+//! Context-window building
+//!
+//! Turns recalled memories into a block of text that fits a prompt budget.
+//! The most important (then most recent) memories are picked first; the ones
+//! that fit are rendered oldest first so the context reads like a timeline.
+
+use std::collections::HashSet;
+use serde::{Deserialize, Serialize};
+use crate::storage::MemoryItem;
+
+/// Rough characters-per-token ratio used by `with_token_budget`
+const CHARS_PER_TOKEN: usize = 4;
+
+/// Packs memories into a size-limited prompt context
+#[derive(Debug, Clone)]
+pub struct ContextBuilder {
+    max_chars: usize,
+    max_memories: usize,
+    header: String,
+}
+
+/// A built context and what went into it
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ContextWindow {
+    pub text: String,
+    pub included_ids: Vec<String>,
+    /// Memories left out because the budget ran out
+    pub omitted: usize,
+}
+
+impl ContextWindow {
+    pub fn is_empty(&self) -> bool {
+        self.included_ids.is_empty()
+    }
+}
+
+impl Default for ContextBuilder {
+    fn default() -> Self {
+        ContextBuilder {
+            max_chars: 2000,
+            max_memories: 20,
+            header: "Relevant memories:".to_string(),
+        }
+    }
+}
+
+impl ContextBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Limit the rendered context to `max_chars` characters
+    pub fn with_max_chars(mut self, max_chars: usize) -> Self {
+        self.max_chars = max_chars;
+        self
+    }
+
+    /// Limit the rendered context to roughly `tokens` tokens
+    pub fn with_token_budget(self, tokens: usize) -> Self {
+        self.with_max_chars(tokens * CHARS_PER_TOKEN)
+    }
+
+    pub fn with_max_memories(mut self, max_memories: usize) -> Self {
+        self.max_memories = max_memories;
+        self
+    }
+
+    /// First line of the context (empty for none)
+    pub fn with_header(mut self, header: &str) -> Self {
+        self.header = header.to_string();
+        self
+    }
+
+    /// Build a context from candidate memories; duplicates by id are ignored
+    pub fn build(&self, memories: &[MemoryItem]) -> ContextWindow {
+        let mut seen = HashSet::new();
+        let mut candidates: Vec<&MemoryItem> = memories
+            .iter()
+            .filter(|memory| seen.insert(memory.id.as_str()))
+            .collect();
+        candidates.sort_by(|a, b| {
+            b.importance
+                .partial_cmp(&a.importance)
+                .unwrap_or(std::cmp::Ordering::Equal)
+                .then(b.timestamp.cmp(&a.timestamp))
+        });
+
+        let mut used = self.header.chars().count();
+        let mut selected = Vec::new();
+        for memory in &candidates {
+            if selected.len() >= self.max_memories {
+                break;
+            }
+            let line_len = Self::render_line(memory).chars().count() + 1;
+            if used + line_len > self.max_chars {
+                continue;
+            }
+            used += line_len;
+            selected.push(*memory);
+        }
+
+        if selected.is_empty() {
+            return ContextWindow {
+                omitted: candidates.len(),
+                ..ContextWindow::default()
+            };
+        }
+
+        selected.sort_by_key(|memory| memory.timestamp);
+        let mut lines: Vec<String> = Vec::with_capacity(selected.len() + 1);
+        if !self.header.is_empty() {
+            lines.push(self.header.clone());
+        }
+        lines.extend(selected.iter().map(|memory| Self::render_line(memory)));
+
+        ContextWindow {
+            text: lines.join("\n"),
+            included_ids: selected.iter().map(|memory| memory.id.clone()).collect(),
+            omitted: candidates.len() - selected.len(),
+        }
+    }
+
+    fn render_line(memory: &MemoryItem) -> String {
+        format!("- [{}] {}", memory.timestamp.format("%Y-%m-%d"), memory.content)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use chrono::{Duration, Utc};
+
+    fn memory(id: &str, content: &str, importance: f32, days_ago: i64) -> MemoryItem {
+        MemoryItem {
+            id: id.to_string(),
+            user_id: "alice".to_string(),
+            session_id: "s1".to_string(),
+            content: content.to_string(),
+            metadata: HashMap::new(),
+            timestamp: Utc::now() - Duration::days(days_ago),
+            ttl_hours: None,
+            importance,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_budget_prefers_important_memories_and_renders_chronologically() {
+        let memories = vec![
+            memory("a", "Prefers window seats", 0.9, 1),
+            memory("b", "Mentioned the weather", 0.1, 0),
+            memory("c", "Allergic to peanuts", 0.95, 3),
+            memory("a", "Prefers window seats", 0.9, 1),
+        ];
+
+        let window = ContextBuilder::new().with_header("").with_max_chars(75).build(&memories);
+        assert_eq!(window.included_ids, vec!["c", "a"]);
+        assert_eq!(window.omitted, 1);
+        assert!(window.text.starts_with("- ["));
+        assert!(window.text.ends_with("Prefers window seats"));
+
+        let empty = ContextBuilder::new().with_max_chars(5).build(&memories);
+        assert!(empty.is_empty());
+        assert_eq!(empty.omitted, 3);
+    }
+}
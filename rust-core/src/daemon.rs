@@ -0,0 +1,328 @@
+//! Daemon support for running MindCache under a service manager
+//!
+//! Provides a PID file guard, config reload on SIGHUP or when the config
+//! file changes, and sysexits-style exit codes so a long-running MindCache process can be
+//! managed by systemd, runit or launchd without wrapper scripts.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use thiserror::Error;
+
+use crate::config::{self, ConfigWatcher};
+use crate::{MindCache, MindCacheConfig};
+
+/// How often a watched config file is checked for changes
+const CONFIG_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Exit codes reported by a daemonized MindCache process (see sysexits.h)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DaemonExitCode {
+    Success = 0,
+    Usage = 64,
+    Internal = 70,
+    Storage = 74,
+    AlreadyRunning = 75,
+    Config = 78,
+}
+
+impl DaemonExitCode {
+    pub fn code(self) -> i32 {
+        self as i32
+    }
+}
+
+impl From<DaemonExitCode> for std::process::ExitCode {
+    fn from(code: DaemonExitCode) -> Self {
+        std::process::ExitCode::from(code as u8)
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum DaemonError {
+    #[error("another instance is already running (pid {pid}, pid file {path})")]
+    AlreadyRunning { pid: u32, path: String },
+    #[error("invalid configuration: {0}")]
+    Config(String),
+    #[error("storage error: {0}")]
+    Storage(String),
+    #[error("io error: {0}")]
+    Io(#[from] io::Error),
+}
+
+impl DaemonError {
+    /// Exit code a service manager should see for this error
+    pub fn exit_code(&self) -> DaemonExitCode {
+        match self {
+            DaemonError::AlreadyRunning { .. } => DaemonExitCode::AlreadyRunning,
+            DaemonError::Config(_) => DaemonExitCode::Config,
+            DaemonError::Storage(_) => DaemonExitCode::Storage,
+            DaemonError::Io(_) => DaemonExitCode::Internal,
+        }
+    }
+}
+
+/// PID file that is removed again when dropped
+#[derive(Debug)]
+pub struct PidFile {
+    path: PathBuf,
+}
+
+impl PidFile {
+    /// Write the current process id to `path`, refusing if a live process already owns it
+    pub fn acquire<P: AsRef<Path>>(path: P) -> Result<Self, DaemonError> {
+        let path = path.as_ref().to_path_buf();
+
+        if let Ok(existing) = fs::read_to_string(&path) {
+            if let Ok(pid) = existing.trim().parse::<u32>() {
+                if pid != std::process::id() && process_alive(pid) {
+                    return Err(DaemonError::AlreadyRunning {
+                        pid,
+                        path: path.display().to_string(),
+                    });
+                }
+            }
+            // Stale or unreadable PID file left behind by a crashed process
+            println!("Removing stale pid file {}", path.display());
+        }
+
+        if let Some(parent) = path.parent() {
+            if !parent.as_os_str().is_empty() {
+                fs::create_dir_all(parent)?;
+            }
+        }
+        fs::write(&path, format!("{}\n", std::process::id()))?;
+
+        Ok(PidFile { path })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for PidFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+#[cfg(unix)]
+fn process_alive(pid: u32) -> bool {
+    // Signal 0 performs the permission and existence checks without delivering anything
+    let result = unsafe { libc::kill(pid as libc::pid_t, 0) };
+    result == 0 || io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+#[cfg(not(unix))]
+fn process_alive(_pid: u32) -> bool {
+    // Without a portable liveness check, assume the recorded process is still running
+    true
+}
+
+/// Options for running MindCache as a daemon
+#[derive(Debug, Clone, Default)]
+pub struct DaemonOptions {
+    pub pid_file: Option<PathBuf>,
+    pub config_path: Option<PathBuf>,
+    /// Reload when the config file changes, not only on SIGHUP
+    pub watch_config: bool,
+}
+
+/// Daemon runtime state: PID file ownership plus signal flags
+pub struct Daemon {
+    options: DaemonOptions,
+    _pid_file: Option<PidFile>,
+    reload: Arc<AtomicBool>,
+    shutdown: Arc<AtomicBool>,
+    config_watcher: Option<ConfigWatcher>,
+}
+
+impl Daemon {
+    /// Acquire the PID file, install SIGHUP/SIGTERM/SIGINT handlers and
+    /// start watching the config file if asked to
+    pub fn start(options: DaemonOptions) -> Result<Self, DaemonError> {
+        let pid_file = match &options.pid_file {
+            Some(path) => Some(PidFile::acquire(path)?),
+            None => None,
+        };
+        let config_watcher = match (&options.config_path, options.watch_config) {
+            (Some(path), true) => Some(ConfigWatcher::start(path, CONFIG_POLL_INTERVAL)?),
+            (None, true) => return Err(DaemonError::Config("watching the config needs a config file".to_string())),
+            _ => None,
+        };
+
+        let reload = Arc::new(AtomicBool::new(false));
+        let shutdown = Arc::new(AtomicBool::new(false));
+
+        #[cfg(unix)]
+        {
+            use signal_hook::consts::{SIGHUP, SIGINT, SIGTERM};
+            signal_hook::flag::register(SIGHUP, Arc::clone(&reload))?;
+            signal_hook::flag::register(SIGTERM, Arc::clone(&shutdown))?;
+            signal_hook::flag::register(SIGINT, Arc::clone(&shutdown))?;
+        }
+
+        Ok(Daemon {
+            options,
+            _pid_file: pid_file,
+            reload,
+            shutdown,
+            config_watcher,
+        })
+    }
+
+    /// Load the configuration file, or the defaults when none was given,
+    /// with any `MINDCACHE_*` environment variables applied on top
+    pub fn load_config(&self) -> Result<MindCacheConfig, DaemonError> {
+        let config = match &self.options.config_path {
+            Some(path) => load_config_file(path)?,
+            None => MindCacheConfig::default(),
+        };
+        config.overlay_env().map_err(|e| DaemonError::Config(e.to_string()))
+    }
+
+    /// Whether SIGTERM or SIGINT has been received
+    pub fn should_stop(&self) -> bool {
+        self.shutdown.load(Ordering::SeqCst)
+    }
+
+    /// Returns true once per received SIGHUP or detected config file change
+    pub fn take_reload_request(&self) -> bool {
+        let changed = self.config_watcher.as_ref().is_some_and(|watcher| watcher.take_change());
+        self.reload.swap(false, Ordering::SeqCst) || changed
+    }
+
+    /// Request a config reload as if SIGHUP had been received
+    pub fn request_reload(&self) {
+        self.reload.store(true, Ordering::SeqCst);
+    }
+
+    /// Request a shutdown as if SIGTERM had been received
+    pub fn request_stop(&self) {
+        self.shutdown.store(true, Ordering::SeqCst);
+    }
+
+    /// Re-read the config file and apply it to a running cache
+    pub fn reload(&self, cache: &mut MindCache) -> Result<(), DaemonError> {
+        let config = self.load_config()?;
+        let source = match &self.options.config_path {
+            Some(path) => path.display().to_string(),
+            None => "environment".to_string(),
+        };
+        cache
+            .apply_reloaded_config(config, &source)
+            .map(|_| ())
+            .map_err(|e| DaemonError::Config(e.to_string()))
+    }
+}
+
+/// Read and validate a `MindCacheConfig` from a JSON file
+pub fn load_config_file<P: AsRef<Path>>(path: P) -> Result<MindCacheConfig, DaemonError> {
+    config::read_config_file(path).map_err(|e| DaemonError::Config(e.to_string()))
+}
+
+/// Apply a reloaded config, rejecting changes that need a restart
+pub fn reload_config(cache: &mut MindCache, config: MindCacheConfig) -> Result<(), DaemonError> {
+    if config.storage_path != cache.config().storage_path {
+        return Err(DaemonError::Config(
+            "storage_path cannot change on reload; restart the daemon instead".to_string(),
+        ));
+    }
+
+    cache
+        .update_config(config)
+        .map_err(|e| DaemonError::Config(e.to_string()))?;
+    println!("Configuration reloaded");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_pid_file_lifecycle() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("run/mindcache.pid");
+
+        let pid_file = PidFile::acquire(&path).unwrap();
+        let contents = fs::read_to_string(&path).unwrap();
+        assert_eq!(contents.trim(), std::process::id().to_string());
+
+        drop(pid_file);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_stale_pid_file_is_replaced() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("mindcache.pid");
+        fs::write(&path, "not-a-pid").unwrap();
+
+        let _pid_file = PidFile::acquire(&path).unwrap();
+        let contents = fs::read_to_string(&path).unwrap();
+        assert_eq!(contents.trim(), std::process::id().to_string());
+    }
+
+    #[test]
+    fn test_reload_rejects_storage_path_change() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = MindCacheConfig {
+            storage_path: temp_dir.path().to_str().unwrap().to_string(),
+            ..MindCacheConfig::default()
+        };
+        let mut cache = MindCache::with_config(config.clone()).unwrap();
+
+        let moved = MindCacheConfig {
+            storage_path: "./somewhere_else".to_string(),
+            ..config.clone()
+        };
+        let err = reload_config(&mut cache, moved).unwrap_err();
+        assert_eq!(err.exit_code(), DaemonExitCode::Config);
+
+        let tuned = MindCacheConfig {
+            importance_threshold: 0.5,
+            ..config
+        };
+        reload_config(&mut cache, tuned).unwrap();
+        assert_eq!(cache.config().importance_threshold, 0.5);
+    }
+
+    #[test]
+    fn test_watched_config_change_requests_reload() {
+        let temp_dir = TempDir::new().unwrap();
+        let config_path = temp_dir.path().join("mindcache.json");
+        let config = MindCacheConfig {
+            storage_path: temp_dir.path().join("store").to_str().unwrap().to_string(),
+            ..MindCacheConfig::default()
+        };
+        fs::write(&config_path, serde_json::to_string(&config).unwrap()).unwrap();
+
+        let daemon = Daemon::start(DaemonOptions {
+            config_path: Some(config_path.clone()),
+            watch_config: true,
+            ..DaemonOptions::default()
+        })
+        .unwrap();
+        let mut cache = MindCache::with_config(daemon.load_config().unwrap()).unwrap();
+        assert!(!daemon.take_reload_request());
+
+        let tuned = MindCacheConfig { importance_threshold: 0.5, ..config };
+        fs::write(&config_path, serde_json::to_string_pretty(&tuned).unwrap()).unwrap();
+        let deadline = std::time::Instant::now() + Duration::from_secs(10);
+        while !daemon.take_reload_request() {
+            assert!(std::time::Instant::now() < deadline, "config change was not noticed");
+            std::thread::sleep(Duration::from_millis(50));
+        }
+        daemon.reload(&mut cache).unwrap();
+        assert_eq!(cache.config().importance_threshold, 0.5);
+
+        let unwatched = Daemon::start(DaemonOptions { watch_config: true, ..DaemonOptions::default() });
+        assert!(unwatched.is_err());
+    }
+}
@@ -1,15 +1,36 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
 use chrono::{DateTime, Utc, Duration};
 use serde::{Deserialize, Serialize};
-use crate::storage::{MemoryStorage, MemoryItem, QueryFilter};
+use crate::storage::{MemoryStorage, MemoryItem, MemoryType, QueryFilter};
 use crate::session::SessionManager; // Remove unused Session import
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
+use crate::audit::AuditAction;
+use crate::events::MemoryEvent;
+use crate::expiry::{ExpiryDecision, ExpiryHook, ExpiryReason};
+use crate::pins;
+use crate::versioning::MemoryUpdate;
+use crate::journal::AffectedMemories;
+use crate::cluster::{self, DEFAULT_CLUSTER_SIMILARITY};
+use crate::provenance::{self, DerivationMethod, ProvenanceRecord};
+use crate::summarizer::{self, ExtractiveSummarizer, Summarizer, SummaryKind, SummaryRequest};
+
+/// Every setting of a decay run; fields missing from JSON take their defaults
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct DecayPolicy {
     pub max_age_hours: u32,
-    pub importance_threshold: f32,
+    /// Expired memories below this importance are deleted
+    pub delete_below: f32,
+    /// Old memories below this importance are compressed
+    pub compress_below: f32,
+    /// Memories above this importance are never expired, compressed or
+    /// dropped over a limit; 1.0 protects nothing
+    pub protect_above: f32,
     pub max_memories_per_user: usize,
     pub compression_enabled: bool,
+    /// How similar memories of a session must be to be compressed together
+    /// (see [`cluster::cluster_by_topic`]); 0.0 compresses whole sessions
+    pub cluster_similarity: f32,
     pub auto_summarize_sessions: bool,
 }
 
@@ -22,10 +43,24 @@ pub struct DecayStats {
     pub total_memories_after: usize,
     pub storage_saved_bytes: usize,
     pub last_decay_run: DateTime<Utc>,
+    /// Memories moved out of the hot tier (see [`crate::tiering`])
+    #[serde(default)]
+    pub memories_demoted: usize,
+}
+
+/// What a decay run at a given time would do, from `MindCache::decay_at`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DecayReport {
+    pub as_of: DateTime<Utc>,
+    pub stats: DecayStats,
+    /// Memories the run would delete or compress, and trash it would purge
+    pub affected: AffectedMemories,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CompressedMemory {
+    /// Provenance id, derived from the original ids
+    pub id: String,
     pub original_ids: Vec<String>,
     pub user_id: String,
     pub session_id: String,
@@ -37,12 +72,35 @@ pub struct CompressedMemory {
     pub compressed_at: DateTime<Utc>,
 }
 
+impl CompressedMemory {
+    /// The summary memory stored in place of the originals, dated when they
+    /// were compressed
+    fn to_memory(&self) -> MemoryItem {
+        MemoryItem {
+            id: self.id.clone(),
+            user_id: self.user_id.clone(),
+            session_id: self.session_id.clone(),
+            content: self.summary.clone(),
+            timestamp: self.compressed_at,
+            importance: self.combined_importance,
+            memory_type: MemoryType::Summary,
+            ..Default::default()
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct MemoryDecayEngine {
     storage: MemoryStorage,
     session_manager: SessionManager,
     policy: DecayPolicy,
     stats: DecayStats,
+    summarizer: Arc<dyn Summarizer>,
+    expiry_hook: Option<Arc<dyn ExpiryHook>>,
+    /// Memories touched by the current or last run
+    affected: AffectedMemories,
+    /// Only work out what the current run would do
+    dry_run: bool,
 }
 
  
@@ -50,21 +108,36 @@ impl Default for DecayPolicy {
     fn default() -> Self {
         DecayPolicy {
             max_age_hours: 24 * 30, // 30 days
-            importance_threshold: 0.3,
+            delete_below: 0.3,
+            compress_below: 0.3,
+            protect_above: 1.0,
             max_memories_per_user: 10000,
             compression_enabled: true,
+            cluster_similarity: DEFAULT_CLUSTER_SIMILARITY,
             auto_summarize_sessions: true,
         }
     }
 }
 
+impl DecayPolicy {
+    /// Whether decay must leave a memory of this importance alone
+    pub fn protects(&self, importance: f32) -> bool {
+        importance > self.protect_above
+    }
+}
+
 impl MemoryDecayEngine {
     /// Create new decay engine with default policy
     pub fn new(storage: MemoryStorage, session_manager: SessionManager) -> Self {
+        let now = storage.now();
         MemoryDecayEngine {
             storage,
             session_manager,
             policy: DecayPolicy::default(),
+            summarizer: Arc::new(ExtractiveSummarizer),
+            expiry_hook: None,
+            affected: AffectedMemories::default(),
+            dry_run: false,
             stats: DecayStats {
                 memories_expired: 0,
                 memories_compressed: 0,
@@ -72,7 +145,8 @@ impl MemoryDecayEngine {
                 total_memories_before: 0,
                 total_memories_after: 0,
                 storage_saved_bytes: 0,
-                last_decay_run: Utc::now(),
+                last_decay_run: now,
+                memories_demoted: 0,
             },
         }
     }
@@ -84,10 +158,50 @@ impl MemoryDecayEngine {
         engine
     }
 
+    /// Replace the summarizer used for compression and session summaries
+    pub fn set_summarizer(&mut self, summarizer: Arc<dyn Summarizer>) {
+        self.session_manager.set_summarizer(summarizer.clone());
+        self.summarizer = summarizer;
+    }
+
+    /// Consult `hook` before expiring or compressing memories; `None` removes it
+    pub fn set_expiry_hook(&mut self, hook: Option<Arc<dyn ExpiryHook>>) {
+        self.expiry_hook = hook;
+    }
+
+    /// Ids of the memories the last run touched
+    pub fn last_run_affected(&self) -> &AffectedMemories {
+        &self.affected
+    }
+
+    /// Drop a deleted user's cached sessions
+    pub fn forget_user(&mut self, user_id: &str) -> Result<(), Box<dyn std::error::Error>> {
+        self.session_manager.forget_user(user_id)?;
+        Ok(())
+    }
+
     /// Run full decay process
     pub fn run_decay(&mut self) -> Result<DecayStats, Box<dyn std::error::Error>> {
-        let start_time = Utc::now();
-        println!("Starting memory decay process...");
+        self.run(self.storage.now())
+    }
+
+    /// Work out what a run at `as_of` would do, changing nothing
+    ///
+    /// The expiry hook is not consulted, and the last run's stats and
+    /// affected memories are left as they were.
+    pub fn plan_decay_at(&mut self, as_of: DateTime<Utc>) -> Result<(DecayStats, AffectedMemories), Box<dyn std::error::Error>> {
+        let last_affected = std::mem::take(&mut self.affected);
+        self.dry_run = true;
+        let result = self.run(as_of);
+        self.dry_run = false;
+        let planned = std::mem::replace(&mut self.affected, last_affected);
+        result.map(|stats| (stats, planned))
+    }
+
+    fn run(&mut self, now: DateTime<Utc>) -> Result<DecayStats, Box<dyn std::error::Error>> {
+        let started = std::time::Instant::now();
+        println!("Starting memory decay process{}...", if self.dry_run { " (dry run)" } else { "" });
+        self.affected = AffectedMemories::default();
 
         // Reset stats for this run
         let mut run_stats = DecayStats {
@@ -97,7 +211,8 @@ impl MemoryDecayEngine {
             total_memories_before: 0,
             total_memories_after: 0,
             storage_saved_bytes: 0,
-            last_decay_run: start_time,
+            last_decay_run: now,
+            memories_demoted: 0,
         };
 
         // Get initial memory count
@@ -105,22 +220,30 @@ impl MemoryDecayEngine {
         run_stats.total_memories_before = storage_stats.values().sum();
 
         // Step 1: Remove expired memories based on TTL
-        run_stats.memories_expired = self.expire_old_memories()?;
+        run_stats.memories_expired = self.expire_old_memories(now)?;
 
         // Step 2: Compress low-importance memories if enabled
+        let mut summaries_stored = 0;
         if self.policy.compression_enabled {
-            run_stats.memories_compressed = self.compress_old_memories()?;
+            (run_stats.memories_compressed, summaries_stored) = self.compress_old_memories(now)?;
         }
 
         // Step 3: Auto-summarize old sessions if enabled
         if self.policy.auto_summarize_sessions {
-            run_stats.sessions_summarized = self.summarize_old_sessions()?;
+            run_stats.sessions_summarized = self.summarize_old_sessions(now)?;
         }
 
         // Step 4: Enforce per-user memory limits
         let limited = self.enforce_memory_limits()?;
         run_stats.memories_expired += limited;
 
+        // Step 5: Demote memories that have gone cold from the hot tier
+        if self.dry_run {
+            run_stats.total_memories_after = run_stats.total_memories_before - run_stats.memories_expired + summaries_stored;
+            return Ok(run_stats);
+        }
+        run_stats.memories_demoted = self.storage.demote_hot_tier(now);
+
         // Update final stats
         let final_stats = self.storage.get_stats();
         run_stats.total_memories_after = final_stats.values().sum();
@@ -128,8 +251,7 @@ impl MemoryDecayEngine {
         // Update internal stats
         self.stats = run_stats.clone();
 
-        let duration = Utc::now() - start_time;
-        println!("Decay process completed in {}ms", duration.num_milliseconds());
+        println!("Decay process completed in {}ms", started.elapsed().as_millis());
         println!("Expired: {}, Compressed: {}, Sessions summarized: {}", 
                 run_stats.memories_expired, 
                 run_stats.memories_compressed,
@@ -139,8 +261,7 @@ impl MemoryDecayEngine {
     }
 
     /// Remove memories that have exceeded their TTL
-    fn expire_old_memories(&mut self) -> Result<usize, Box<dyn std::error::Error>> {
-        let now = Utc::now();
+    fn expire_old_memories(&mut self, now: DateTime<Utc>) -> Result<usize, Box<dyn std::error::Error>> {
         let mut expired_count = 0;
 
         // Get all memories to check for expiration
@@ -152,14 +273,29 @@ impl MemoryDecayEngine {
             date_to: None,
             limit: None,
             min_importance: None,
+            include_superseded: true,
+            ..Default::default()
         };
 
         let memories = self.storage.recall(filter)?;
+        let overrides = self.storage.session_retention().all();
 
+        let mut selected = Vec::new();
         for memory in memories {
-            let should_expire = if let Some(ttl_hours) = memory.ttl_hours {
+            if self.spares(&memory) || memory.never_expires() {
+                continue;
+            }
+            // A session TTL applies whatever the memory's importance
+            let session_expired = overrides.get(&memory.session_id).and_then(|retention| retention.is_expired(&memory, now));
+            if let Some(expired) = session_expired {
+                if expired {
+                    selected.push(memory);
+                }
+                continue;
+            }
+
+            let should_expire = if let Some(expiry_time) = memory.ttl_expiry() {
                 // Memory has explicit TTL
-                let expiry_time = memory.timestamp + Duration::hours(ttl_hours as i64);
                 now > expiry_time
             } else {
                 // Use default policy max age
@@ -167,25 +303,32 @@ impl MemoryDecayEngine {
                 age_hours > self.policy.max_age_hours
             };
 
-            if should_expire && memory.importance < self.policy.importance_threshold {
-                // Mark for deletion (in a real implementation, you'd remove from storage)
-                expired_count += 1;
-                println!("Expiring memory {} (age: {}h, importance: {})", 
-                        memory.id, 
-                        (now - memory.timestamp).num_hours(),
-                        memory.importance);
+            if should_expire && memory.importance < self.policy.delete_below {
+                selected.push(memory);
             }
         }
 
-        // Call storage cleanup
-        let cleaned = self.storage.cleanup_expired()?;
-        Ok(expired_count.max(cleaned))
+        let selected = self.screen(selected, ExpiryReason::Expired)?;
+        for memory in self.remove(selected)? {
+            expired_count += 1;
+            self.affected.expired_ids.push(memory.id.clone());
+            println!("Expired memory {} (age: {}h, importance: {})", 
+                    memory.id, 
+                    (now - memory.timestamp).num_hours(),
+                    memory.importance);
+            self.emit_expired(&memory);
+        }
+
+        Ok(expired_count)
     }
 
-    /// Compress groups of old, low-importance memories
-    fn compress_old_memories(&mut self) -> Result<usize, Box<dyn std::error::Error>> {
-        let cutoff_date = Utc::now() - Duration::hours(self.policy.max_age_hours as i64 / 2);
+    /// Compress groups of old, low-importance memories into stored summary
+    /// memories, returning how many memories were compressed and into how
+    /// many summaries
+    fn compress_old_memories(&mut self, now: DateTime<Utc>) -> Result<(usize, usize), Box<dyn std::error::Error>> {
+        let cutoff_date = now - Duration::hours(self.policy.max_age_hours as i64 / 2);
         let mut compressed_count = 0;
+        let mut summary_count = 0;
 
         // Get memories older than cutoff with low importance
         let filter = QueryFilter {
@@ -196,6 +339,8 @@ impl MemoryDecayEngine {
             date_to: Some(cutoff_date),
             limit: None,
             min_importance: None,
+            include_superseded: true,
+            ..Default::default()
         };
 
         let old_memories = self.storage.recall(filter)?;
@@ -203,31 +348,74 @@ impl MemoryDecayEngine {
         // Group by user and session for compression
         let mut memory_groups: HashMap<(String, String), Vec<MemoryItem>> = HashMap::new();
         
+        // Memories this run expired are gone, or would be in a dry run, and
+        // ones an earlier run compressed already have their summary
+        let expired: HashSet<&String> = self.affected.expired_ids.iter().collect();
+        let provenance = self.storage.provenance();
         for memory in old_memories {
-            if memory.importance < self.policy.importance_threshold {
+            if memory.importance < self.policy.compress_below
+                && !self.spares(&memory)
+                && !expired.contains(&memory.id)
+                && !provenance
+                    .derived_from(&memory.id)
+                    .iter()
+                    .any(|record| record.method == DerivationMethod::Compression)
+            {
                 let key = (memory.user_id.clone(), memory.session_id.clone());
-                memory_groups.entry(key).or_insert_with(Vec::new).push(memory);
+                memory_groups.entry(key).or_default().push(memory);
             }
         }
 
-        // Compress groups with 3+ memories
-        for ((_user_id, session_id), memories) in memory_groups {
+        // Compress topic clusters with 3+ memories within each group
+        let stop_words = self.storage.stop_words();
+        let clusters = memory_groups
+            .into_iter()
+            .filter(|(_, memories)| memories.len() >= 3)
+            .flat_map(|((_user_id, session_id), memories)| {
+                cluster::cluster_by_topic(memories, self.policy.cluster_similarity, &stop_words)
+                    .into_iter()
+                    .map(move |cluster| (session_id.clone(), cluster))
+            })
+            .collect::<Vec<_>>();
+        for (session_id, memories) in clusters {
+            if memories.len() < 3 {
+                continue;
+            }
+            let memories = self.screen(memories, ExpiryReason::Compression)?;
             if memories.len() >= 3 {
-                let compressed = self.create_compressed_memory(memories)?;
-                
-                // In a real implementation, you'd replace the original memories with the compressed version
-                println!("Compressed {} memories from session {} into summary", 
-                        compressed.original_count, session_id);
+                let compressed = self.create_compressed_memory(memories, now)?;
                 compressed_count += compressed.original_count;
+                summary_count += 1;
+                self.affected.compressed_ids.extend(compressed.original_ids.iter().cloned());
+                if self.dry_run {
+                    continue;
+                }
+
+                self.storage.save(compressed.to_memory())?;
+                println!("Compressed {} memories from session {} into summary {}",
+                        compressed.original_count, session_id, compressed.id);
+                self.storage.provenance().record(ProvenanceRecord {
+                    derived_id: compressed.id.clone(),
+                    derived_from: compressed.original_ids.clone(),
+                    method: DerivationMethod::Compression,
+                    created_at: compressed.compressed_at,
+                })?;
+                self.storage.events().emit(MemoryEvent::MemoryCompressed {
+                    compressed_id: compressed.id,
+                    original_ids: compressed.original_ids,
+                    user_id: compressed.user_id,
+                    session_id: compressed.session_id,
+                    summary: compressed.summary,
+                });
             }
         }
 
-        Ok(compressed_count)
+        Ok((compressed_count, summary_count))
     }
 
     /// Auto-summarize sessions that haven't been active recently
-    fn summarize_old_sessions(&mut self) -> Result<usize, Box<dyn std::error::Error>> {
-        let cutoff_date = Utc::now() - Duration::days(7); // Sessions inactive for 7+ days
+    fn summarize_old_sessions(&mut self, now: DateTime<Utc>) -> Result<usize, Box<dyn std::error::Error>> {
+        let cutoff_date = now - Duration::days(7); // Sessions inactive for 7+ days
         let mut summarized_count = 0;
 
         // Get all users from storage stats
@@ -238,6 +426,10 @@ impl MemoryDecayEngine {
             
             for session in sessions {
                 if session.last_active < cutoff_date && session.memory_count > 5 {
+                    if self.dry_run {
+                        summarized_count += 1;
+                        continue;
+                    }
                     // Generate summary for old, substantial sessions
                     match self.session_manager.generate_session_summary(&session.id) {
                         Ok(_summary) => {
@@ -259,14 +451,38 @@ impl MemoryDecayEngine {
         Ok(summarized_count)
     }
 
-    /// Enforce per-user memory limits
+    /// Enforce per-session, then per-user memory limits
     fn enforce_memory_limits(&mut self) -> Result<usize, Box<dyn std::error::Error>> {
-        let mut removed_count = 0;
-        let storage_stats = self.storage.get_stats();
+        let mut removed: HashSet<String> = HashSet::new();
+        // Still stored in a dry run
+        let expired: HashSet<String> = self.affected.expired_ids.iter().cloned().collect();
 
+        for (session_id, retention) in self.storage.session_retention().all() {
+            let Some(max_memories) = retention.max_memories else {
+                continue;
+            };
+            let mut memories = self.storage.get_memories_by_session(&session_id)?;
+            memories.retain(|memory| !expired.contains(&memory.id));
+            if memories.len() <= max_memories {
+                continue;
+            }
+            let excess = memories.len() - max_memories;
+            memories.retain(|memory| !self.spares(memory));
+            memories.sort_by(|a, b| a.importance.total_cmp(&b.importance));
+            memories.truncate(excess);
+            let memories = self.screen(memories, ExpiryReason::OverLimit)?;
+            for memory in self.remove(memories)?.iter() {
+                self.affected.over_limit_ids.push(memory.id.clone());
+                println!("Removed low-importance memory {} over the limit of session {} (importance: {})",
+                        memory.id, session_id, memory.importance);
+                removed.insert(memory.id.clone());
+                self.emit_expired(memory);
+            }
+        }
+
+        let storage_stats = self.storage.get_stats();
         for (user_id, memory_count) in storage_stats {
             if memory_count > self.policy.max_memories_per_user {
-                let excess = memory_count - self.policy.max_memories_per_user;
                 
                 // Get user's memories sorted by importance (ascending)
                 let filter = QueryFilter {
@@ -277,25 +493,103 @@ impl MemoryDecayEngine {
                     date_to: None,
                     limit: None,
                     min_importance: None,
+                    include_superseded: true,
+                    ..Default::default()
                 };
 
+                // Memories dropped for their session's limit already count
                 let mut memories = self.storage.recall(filter)?;
-                memories.sort_by(|a, b| a.importance.partial_cmp(&b.importance).unwrap());
+                memories.retain(|memory| !removed.contains(&memory.id) && !expired.contains(&memory.id));
+                if memories.len() <= self.policy.max_memories_per_user {
+                    continue;
+                }
+                let excess = memories.len() - self.policy.max_memories_per_user;
+                memories.retain(|memory| !self.spares(memory));
+                memories.sort_by(|a, b| a.importance.total_cmp(&b.importance));
 
                 // Remove least important memories
-                for memory in memories.iter().take(excess) {
-                    println!("Removing low-importance memory {} for user {} (importance: {})", 
+                memories.truncate(excess);
+                let memories = self.screen(memories, ExpiryReason::OverLimit)?;
+                for memory in self.remove(memories)?.iter() {
+                    self.affected.over_limit_ids.push(memory.id.clone());
+                    println!("Removed low-importance memory {} for user {} (importance: {})", 
                             memory.id, user_id, memory.importance);
-                    removed_count += 1;
+                    removed.insert(memory.id.clone());
+                    self.emit_expired(memory);
+                }
+            }
+        }
+
+        Ok(removed.len())
+    }
+
+    /// Whether decay must leave a memory alone, being pinned or protected by the policy
+    fn spares(&self, memory: &MemoryItem) -> bool {
+        pins::is_pinned(memory) || self.policy.protects(memory.importance)
+    }
+
+    /// Let the expiry hook veto or rescore memories selected for `reason`;
+    /// returns the ones decay may go ahead with
+    fn screen(&mut self, memories: Vec<MemoryItem>, reason: ExpiryReason) -> Result<Vec<MemoryItem>, Box<dyn std::error::Error>> {
+        let Some(hook) = self.expiry_hook.as_ref().filter(|_| !self.dry_run) else {
+            return Ok(memories);
+        };
+        if memories.is_empty() {
+            return Ok(memories);
+        }
+
+        let decisions = hook.before_expiry(&memories, reason);
+        let mut proceed = Vec::with_capacity(memories.len());
+        for (i, memory) in memories.into_iter().enumerate() {
+            match decisions.get(i).copied().unwrap_or(ExpiryDecision::Proceed) {
+                ExpiryDecision::Proceed => proceed.push(memory),
+                ExpiryDecision::Veto => {
+                    println!("Expiry hook kept memory {}", memory.id);
+                    self.affected.kept_ids.push(memory.id);
+                }
+                ExpiryDecision::SetImportance(importance) => {
+                    println!("Expiry hook set importance of memory {} to {}", memory.id, importance);
+                    self.storage.update_memory(&memory.id, None, |memory| {
+                        MemoryUpdate { importance: Some(importance), ..Default::default() }.apply(memory)
+                    })?;
+                    self.affected.kept_ids.push(memory.id);
                 }
             }
         }
+        Ok(proceed)
+    }
 
-        Ok(removed_count)
+    /// Delete memories decay selected, audited as expired; returns the ones
+    /// still stored, which are the ones removed
+    fn remove(&mut self, memories: Vec<MemoryItem>) -> Result<Vec<MemoryItem>, Box<dyn std::error::Error>> {
+        if self.dry_run {
+            return Ok(memories);
+        }
+        let (positions, removed): (HashSet<usize>, Vec<MemoryItem>) = memories
+            .into_iter()
+            .filter_map(|memory| self.storage.position_of(&memory.id).map(|position| (position, memory)))
+            .unzip();
+        if !positions.is_empty() {
+            self.storage.delete_positions(&positions, AuditAction::Expire)?;
+        }
+        Ok(removed)
+    }
+
+    /// Count a removed memory against its user and tell observers
+    fn emit_expired(&mut self, memory: &MemoryItem) {
+        *self.affected.expired_by_user.entry(memory.user_id.clone()).or_default() += 1;
+        if self.dry_run {
+            return;
+        }
+        self.storage.events().emit(MemoryEvent::MemoryExpired {
+            memory_id: memory.id.clone(),
+            user_id: memory.user_id.clone(),
+            session_id: memory.session_id.clone(),
+        });
     }
 
     /// Create a compressed memory from multiple memories
-    fn create_compressed_memory(&self, memories: Vec<MemoryItem>) -> Result<CompressedMemory, Box<dyn std::error::Error>> {
+    fn create_compressed_memory(&self, memories: Vec<MemoryItem>, now: DateTime<Utc>) -> Result<CompressedMemory, Box<dyn std::error::Error>> {
         if memories.is_empty() {
             return Err("Cannot compress empty memory list".into());
         }
@@ -305,22 +599,12 @@ impl MemoryDecayEngine {
         
         // Extract key information
         let original_ids: Vec<String> = memories.iter().map(|m| m.id.clone()).collect();
-        let combined_content: String = memories.iter()
-            .map(|m| m.content.as_str())
-            .collect::<Vec<&str>>()
-            .join(" | ");
-
-        // Simple summary generation (first memory + count + key themes)
-        let summary = if combined_content.len() > 200 {
-            format!("{}... [+{} more memories]", 
-                   &combined_content[..200], 
-                   memories.len() - 1)
-        } else {
-            combined_content
-        };
-
-        // Extract key points (simple keyword extraction)
         let key_points = self.extract_key_points(&memories);
+        let summary = summarizer::summarize_or_fallback(self.summarizer.as_ref(), &SummaryRequest {
+            kind: SummaryKind::Compression,
+            memories: &memories,
+            key_topics: &key_points,
+        });
 
         // Date range
         let timestamps: Vec<DateTime<Utc>> = memories.iter().map(|m| m.timestamp).collect();
@@ -335,6 +619,7 @@ impl MemoryDecayEngine {
             .sum::<f32>() / memories.len() as f32;
 
         Ok(CompressedMemory {
+            id: provenance::derived_id("compressed", &original_ids),
             original_ids,
             user_id,
             session_id,
@@ -343,15 +628,17 @@ impl MemoryDecayEngine {
             date_range,
             original_count: memories.len(),
             combined_importance,
-            compressed_at: Utc::now(),
+            compressed_at: now,
         })
     }
 
     /// Extract key points from a group of memories
     fn extract_key_points(&self, memories: &[MemoryItem]) -> Vec<String> {
         let mut word_counts: HashMap<String, usize> = HashMap::new();
-        
+        let stop_words = self.storage.stop_words();
+
         for memory in memories {
+            let is_stop_word = stop_words.for_text(&memory.content);
             // Fix: Create owned string first, then split
             let content_lower = memory.content.to_lowercase();
             let words: Vec<&str> = content_lower
@@ -366,7 +653,7 @@ impl MemoryDecayEngine {
 
         // Return top 5 most frequent meaningful words
         let mut sorted_words: Vec<(String, usize)> = word_counts.into_iter().collect();
-        sorted_words.sort_by(|a, b| b.1.cmp(&a.1));
+        sorted_words.sort_by_key(|w| std::cmp::Reverse(w.1));
         sorted_words.into_iter().take(5).map(|(word, _)| word).collect()
     }
 
@@ -378,15 +665,17 @@ impl MemoryDecayEngine {
     /// Update decay policy
     pub fn update_policy(&mut self, policy: DecayPolicy) {
         self.policy = policy;
-        println!("Updated decay policy: max_age={}h, threshold={}, compression={}", 
-                self.policy.max_age_hours, 
-                self.policy.importance_threshold,
+        println!("Updated decay policy: max_age={}h, delete<{}, compress<{}, protect>{}, compression={}",
+                self.policy.max_age_hours,
+                self.policy.delete_below,
+                self.policy.compress_below,
+                self.policy.protect_above,
                 self.policy.compression_enabled);
     }
 
     /// Calculate memory age distribution
     pub fn analyze_memory_age_distribution(&self) -> Result<HashMap<String, usize>, Box<dyn std::error::Error>> {
-        let now = Utc::now();
+        let now = self.storage.now();
         let mut age_buckets: HashMap<String, usize> = HashMap::new();
 
         let filter = QueryFilter {
@@ -397,6 +686,8 @@ impl MemoryDecayEngine {
             date_to: None,
             limit: None,
             min_importance: None,
+            include_superseded: true,
+            ..Default::default()
         };
 
         let memories = self.storage.recall(filter)?;
@@ -418,40 +709,144 @@ impl MemoryDecayEngine {
     }
 }
 
-fn is_stop_word(word: &str) -> bool {
-    matches!(word, 
-        "the" | "and" | "or" | "but" | "in" | "on" | "at" | "to" | "for" | 
-        "of" | "with" | "by" | "from" | "up" | "about" | "into" | "through" | 
-        "during" | "before" | "after" | "above" | "below" | "between" | "among" |
-        "this" | "that" | "these" | "those" | "i" | "you" | "he" | "she" | "it" |
-        "we" | "they" | "am" | "is" | "are" | "was" | "were" | "be" | "been" |
-        "being" | "have" | "has" | "had" | "do" | "does" | "did" | "will" | "would"
-    )
-}
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::storage::MemoryStorage;
-    use crate::session::SessionManager;
+    use crate::MindCacheConfig;
+    use crate::test_support::temp_cache_with;
 
     #[test]
     fn test_decay_policy_creation() {
         let policy = DecayPolicy::default();
         assert_eq!(policy.max_age_hours, 24 * 30);
-        assert_eq!(policy.importance_threshold, 0.3);
+        assert_eq!(policy.delete_below, 0.3);
+        assert_eq!(policy.compress_below, 0.3);
+        assert!(!policy.protects(1.0));
         assert!(policy.compression_enabled);
     }
 
+    #[test]
+    fn test_thresholds_split_delete_compress_protect() {
+        let (mut cache, _temp_dir) = temp_cache_with(MindCacheConfig {
+            importance_threshold: 0.2,
+            compress_threshold: Some(0.5),
+            protect_threshold: Some(0.8),
+            max_memories_per_user: 1,
+            ..Default::default()
+        });
+        let old = Utc::now() - Duration::days(40);
+        let mut ids = Vec::new();
+        for importance in [0.1, 0.3, 0.35, 0.4, 0.9] {
+            ids.push(cache.storage.save(MemoryItem {
+                user_id: "alice".to_string(),
+                session_id: "s1".to_string(),
+                content: format!("memory with importance {}", importance),
+                timestamp: old,
+                ttl_hours: Some(24),
+                importance,
+                ..Default::default()
+            }).unwrap());
+        }
+
+        cache.decay().unwrap();
+        let affected = &cache.decay_history(1).unwrap()[0].affected;
+        assert_eq!(affected.expired_ids, ids[..1]);
+        let mut compressed = affected.compressed_ids.clone();
+        compressed.sort();
+        let mut expected = ids[1..4].to_vec();
+        expected.sort();
+        assert_eq!(compressed, expected);
+        // The expired memory is gone and the summary of the compressed ones
+        // stored, leaving four over the limit of one
+        assert_eq!(affected.over_limit_ids.len(), 4);
+        assert!(!affected.contains(&ids[4]));
+    }
+
+    #[test]
+    fn test_nan_importance_does_not_stop_limit_enforcement() {
+        let (mut cache, _temp_dir) = temp_cache_with(MindCacheConfig {
+            enable_compression: false,
+            max_memories_per_user: 1,
+            ..Default::default()
+        });
+        for importance in [f32::NAN, 0.4, 0.6] {
+            cache.storage.save(MemoryItem {
+                user_id: "alice".to_string(),
+                session_id: "s1".to_string(),
+                content: "note".to_string(),
+                importance,
+                ..Default::default()
+            }).unwrap();
+        }
+
+        assert_eq!(cache.decay().unwrap().memories_expired, 2);
+        assert_eq!(cache.recall("alice", None, None, None).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_expired_memories_are_reported_once() {
+        let (mut cache, _temp_dir) = temp_cache_with(MindCacheConfig {
+            audit_log_enabled: true,
+            ..Default::default()
+        });
+        let id = cache.save_with_options("alice", "s1", "Parking spot is B4", None, 0.1, Some(1)).unwrap();
+        let expired = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sink = Arc::clone(&expired);
+        cache.subscribe(move |event: &MemoryEvent| {
+            if let MemoryEvent::MemoryExpired { memory_id, .. } = event {
+                sink.lock().unwrap().push(memory_id.clone());
+            }
+        });
+
+        let clock = crate::TestClock::default();
+        cache.set_clock(clock.clone());
+        clock.advance(Duration::hours(2));
+        assert_eq!(cache.decay().unwrap().memories_expired, 1);
+        assert_eq!(cache.decay().unwrap().memories_expired, 0);
+
+        assert!(cache.get_memory(&id).unwrap().is_none());
+        assert_eq!(*expired.lock().unwrap(), std::slice::from_ref(&id));
+        let audited = cache.audit_trail(crate::AuditFilter {
+            actions: Some(vec![AuditAction::Expire]),
+            ..Default::default()
+        }).unwrap();
+        assert_eq!(audited.iter().map(|entry| &entry.memory_id).collect::<Vec<_>>(), [&id]);
+    }
+
     #[test]
     fn test_memory_compression() {
-    let storage = MemoryStorage::new("./test_decay").unwrap();
-    let session_manager = SessionManager::new(storage.clone()); // Clone storage
-    let _decay_engine = MemoryDecayEngine::new(storage, session_manager);
-        
-        // Test would need actual memories to compress
-        // This is a placeholder for integration testing
-        
-    std::fs::remove_dir_all("./test_decay").ok();
+        let (mut cache, _temp_dir) = temp_cache_with(MindCacheConfig {
+            compression_similarity: 0.0,
+            ..Default::default()
+        });
+        let old = Utc::now() - Duration::days(20);
+        let mut ids = Vec::new();
+        for topic in ["coffee", "tea", "juice"] {
+            ids.push(cache.storage.save(MemoryItem {
+                user_id: "alice".to_string(),
+                session_id: "s1".to_string(),
+                content: format!("Drank {} in the morning", topic),
+                timestamp: old,
+                importance: 0.2,
+                ..Default::default()
+            }).unwrap());
+        }
+
+        let stats = cache.decay().unwrap();
+        assert_eq!(stats.memories_compressed, 3);
+        assert_eq!(stats.total_memories_after, 4);
+        let crate::MemoryState::Compressed { into } = cache.memory_state(&ids[0]).unwrap().unwrap() else {
+            panic!("memory was not compressed");
+        };
+        let summary = cache.get_memory(&into).unwrap().expect("compressed memory is stored");
+        assert_eq!(summary.memory_type, MemoryType::Summary);
+        assert_eq!(summary.session_id, "s1");
+        assert_eq!(cache.get_provenance(&into).unwrap().derived_from.len(), 3);
+
+        // A later run leaves the already compressed memories alone
+        let stats = cache.decay().unwrap();
+        assert_eq!(stats.memories_compressed, 0);
+        assert_eq!(stats.total_memories_after, 4);
     }
 }
\ No newline at end of file
@@ -0,0 +1,276 @@
+//! Near-duplicate detection and cleanup
+//!
+//! Memories are fingerprinted with a 64-bit simhash over their lowercased
+//! words; two memories are near-duplicates when the share of matching
+//! fingerprint bits reaches the similarity threshold. Each cluster keeps its
+//! best member (most important, then longest, then newest) and folds the
+//! others' metadata into it.
+
+use std::collections::HashSet;
+use serde::{Deserialize, Serialize};
+use crate::audit::AuditAction;
+use crate::provenance::{DerivationMethod, ProvenanceRecord};
+use crate::storage::{MemoryItem, MemoryStorage};
+use crate::MindCache;
+
+/// Similarity used when callers have no better value
+pub const DEFAULT_SIMILARITY_THRESHOLD: f32 = 0.9;
+
+/// Metadata key whose comma-separated values are unioned across a cluster
+const TAGS_KEY: &str = "tags";
+
+/// One group of near-identical memories
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DedupeCluster {
+    pub kept_id: String,
+    pub removed_ids: Vec<String>,
+}
+
+/// Outcome of a dedupe run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DedupeReport {
+    pub user_id: String,
+    pub dry_run: bool,
+    pub clusters: Vec<DedupeCluster>,
+    pub memories_removed: usize,
+    /// Estimated for dry runs, measured by the follow-up compaction otherwise
+    pub bytes_reclaimed: u64,
+}
+
+/// 64-bit simhash of a text's lowercased words
+pub fn simhash(text: &str) -> u64 {
+    let mut weights = [0i32; 64];
+    for word in text
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+    {
+        let hash = fnv1a(&word.to_lowercase());
+        for (bit, weight) in weights.iter_mut().enumerate() {
+            if hash & (1 << bit) != 0 {
+                *weight += 1;
+            } else {
+                *weight -= 1;
+            }
+        }
+    }
+
+    weights
+        .iter()
+        .enumerate()
+        .filter(|(_, weight)| **weight > 0)
+        .fold(0, |hash, (bit, _)| hash | (1 << bit))
+}
+
+/// Share of equal bits between two fingerprints, 0.0 to 1.0
+pub fn similarity(a: u64, b: u64) -> f32 {
+    1.0 - (a ^ b).count_ones() as f32 / 64.0
+}
+
+pub(crate) fn fnv1a(word: &str) -> u64 {
+    word.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Group records into clusters of near-duplicates, best member first
+///
+/// Records are visited best-first and each one joins the first cluster whose
+/// representative is similar enough, so a cluster never chains away from its
+/// representative. Singletons are dropped.
+fn find_clusters(records: Vec<(usize, MemoryItem)>, threshold: f32) -> Vec<Vec<(usize, MemoryItem)>> {
+    let mut records = records;
+    records.sort_by(|(_, a), (_, b)| {
+        b.importance
+            .partial_cmp(&a.importance)
+            .unwrap_or(std::cmp::Ordering::Equal)
+            .then(b.content.len().cmp(&a.content.len()))
+            .then(b.timestamp.cmp(&a.timestamp))
+    });
+
+    let mut clusters: Vec<(u64, Vec<(usize, MemoryItem)>)> = Vec::new();
+    for record in records {
+        let fingerprint = simhash(&record.1.content);
+        match clusters
+            .iter_mut()
+            .find(|(representative, _)| similarity(*representative, fingerprint) >= threshold)
+        {
+            Some((_, members)) => members.push(record),
+            None => clusters.push((fingerprint, vec![record])),
+        }
+    }
+
+    clusters
+        .into_iter()
+        .map(|(_, members)| members)
+        .filter(|members| members.len() > 1)
+        .collect()
+}
+
+/// Fold the duplicates' metadata and importance into the kept memory
+fn merge_into(kept: &mut MemoryItem, duplicates: &[&MemoryItem]) {
+    let mut tags: Vec<String> = Vec::new();
+    for memory in std::iter::once(&*kept).chain(duplicates.iter().copied()) {
+        if let Some(value) = memory.metadata.get(TAGS_KEY) {
+            for tag in value.split(',').map(str::trim).filter(|tag| !tag.is_empty()) {
+                if !tags.iter().any(|existing| existing == tag) {
+                    tags.push(tag.to_string());
+                }
+            }
+        }
+    }
+
+    for duplicate in duplicates {
+        for (key, value) in &duplicate.metadata {
+            kept.metadata.entry(key.clone()).or_insert_with(|| value.clone());
+        }
+        kept.importance = kept.importance.max(duplicate.importance);
+    }
+    if !tags.is_empty() {
+        kept.metadata.insert(TAGS_KEY.to_string(), tags.join(","));
+    }
+}
+
+fn dedupe_storage(
+    storage: &MemoryStorage,
+    user_id: &str,
+    similarity_threshold: f32,
+    dry_run: bool,
+) -> Result<DedupeReport, Box<dyn std::error::Error>> {
+    if !(0.0..=1.0).contains(&similarity_threshold) {
+        return Err("similarity_threshold must be between 0.0 and 1.0".into());
+    }
+
+    let clusters = find_clusters(storage.user_records(user_id)?, similarity_threshold);
+
+    let mut report = DedupeReport {
+        user_id: user_id.to_string(),
+        dry_run,
+        clusters: Vec::with_capacity(clusters.len()),
+        memories_removed: 0,
+        bytes_reclaimed: 0,
+    };
+    let mut removed_positions = HashSet::new();
+
+    for members in clusters {
+        let (kept_position, kept) = &members[0];
+        let duplicates: Vec<&MemoryItem> = members[1..].iter().map(|(_, memory)| memory).collect();
+
+        if !dry_run {
+            let mut merged = kept.clone();
+            merge_into(&mut merged, &duplicates);
+            if merged.metadata != kept.metadata || merged.importance != kept.importance {
+                storage.replace_at(*kept_position, merged)?;
+            }
+
+            // Earlier merges into the same memory stay traceable
+            let mut derived_from = storage
+                .provenance()
+                .get(&kept.id)
+                .filter(|record| record.method == DerivationMethod::Dedupe)
+                .map(|record| record.derived_from)
+                .unwrap_or_default();
+            derived_from.extend(duplicates.iter().map(|memory| memory.id.clone()));
+            storage.provenance().record(ProvenanceRecord {
+                derived_id: kept.id.clone(),
+                derived_from,
+                method: DerivationMethod::Dedupe,
+                created_at: storage.now(),
+            })?;
+        }
+
+        report.memories_removed += duplicates.len();
+        report.bytes_reclaimed += duplicates.iter().map(|memory| MemoryStorage::record_size(memory)).sum::<u64>();
+        removed_positions.extend(members[1..].iter().map(|(position, _)| *position));
+        report.clusters.push(DedupeCluster {
+            kept_id: kept.id.clone(),
+            removed_ids: duplicates.iter().map(|memory| memory.id.clone()).collect(),
+        });
+    }
+
+    if !dry_run && !removed_positions.is_empty() {
+        storage.delete_positions(&removed_positions, AuditAction::Delete)?;
+        report.bytes_reclaimed = storage.compact()?.bytes_reclaimed();
+    }
+
+    Ok(report)
+}
+
+impl MindCache {
+    /// Remove near-identical memories of a user
+    ///
+    /// `similarity_threshold` is the share of matching simhash bits (0.0 to 1.0)
+    /// needed to treat two memories as duplicates. With `dry_run` nothing is
+    /// changed and the report only describes what would be removed; otherwise
+    /// the store is compacted afterwards to reclaim the space.
+    pub fn dedupe(
+        &mut self,
+        user_id: &str,
+        similarity_threshold: f32,
+        dry_run: bool,
+    ) -> Result<DedupeReport, Box<dyn std::error::Error>> {
+        dedupe_storage(&self.storage, user_id, similarity_threshold, dry_run)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use crate::test_support::temp_cache;
+
+    fn save(cache: &mut MindCache, content: &str, importance: f32, tags: &str) -> String {
+        let metadata = HashMap::from([("tags".to_string(), tags.to_string())]);
+        cache
+            .save_with_options("alice", "s1", content, Some(metadata), importance, None)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_simhash_similarity() {
+        let a = simhash("The user prefers dark mode in the editor");
+        let b = simhash("the user prefers dark mode in the editor!");
+        let c = simhash("Quarterly revenue grew by twelve percent");
+
+        assert_eq!(similarity(a, b), 1.0);
+        assert!(similarity(a, c) < 0.9);
+    }
+
+    #[test]
+    fn test_dedupe_keeps_best_and_merges_metadata() {
+        let (mut cache, _temp_dir) = temp_cache();
+        let low = save(&mut cache, "User prefers dark mode in the editor", 0.3, "ui,beta");
+        let high = save(&mut cache, "user prefers dark mode in the editor.", 0.8, "prefs,ui");
+        let other = save(&mut cache, "Meeting with the finance team on Friday", 0.5, "work");
+
+        let preview = cache.dedupe("alice", DEFAULT_SIMILARITY_THRESHOLD, true).unwrap();
+        assert_eq!(preview.memories_removed, 1);
+        assert!(preview.bytes_reclaimed > 0);
+        assert_eq!(cache.recall("alice", None, None, None).unwrap().len(), 3);
+
+        let report = cache.dedupe("alice", DEFAULT_SIMILARITY_THRESHOLD, false).unwrap();
+        assert_eq!(report.clusters.len(), 1);
+        assert_eq!(report.clusters[0].kept_id, high);
+        assert_eq!(report.clusters[0].removed_ids, vec![low.clone()]);
+        assert!(report.bytes_reclaimed > 0);
+
+        let remaining = cache.recall("alice", None, None, None).unwrap();
+        assert_eq!(remaining.len(), 2);
+        assert!(remaining.iter().any(|m| m.id == other));
+        let kept = remaining.iter().find(|m| m.id == high).unwrap();
+        assert_eq!(kept.metadata["tags"], "prefs,ui,beta");
+        assert_eq!(kept.importance, 0.8);
+
+        let provenance = cache.get_provenance(&high).unwrap();
+        assert_eq!(provenance.method, DerivationMethod::Dedupe);
+        assert_eq!(provenance.derived_from, vec![low.clone()]);
+
+        // Keyword lookups no longer see the removed copy
+        assert_eq!(cache.recall("alice", Some("dark"), None, None).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_dedupe_rejects_invalid_threshold() {
+        let (mut cache, _temp_dir) = temp_cache();
+        assert!(cache.dedupe("alice", 1.5, true).is_err());
+    }
+}
@@ -0,0 +1,174 @@
+//! Demo data seeding
+//!
+//! Generates the trading-journal style corpus used throughout the examples so
+//! new users and UI developers can explore a populated store in one call.
+
+use std::collections::HashMap;
+use chrono::{Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::storage::MemoryItem;
+use crate::MindCache;
+
+/// User id that all demo memories are stored under
+pub const DEMO_USER_ID: &str = "demo_trader";
+
+/// Result of seeding the demo corpus
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DemoSeedReport {
+    pub user_id: String,
+    pub session_ids: Vec<String>,
+    pub memories_created: usize,
+}
+
+struct DemoSession {
+    name: &'static str,
+    session_type: &'static str,
+    type_key: &'static str,
+    days_ago: i64,
+    entries: &'static [(&'static str, &'static str, f32)],
+}
+
+const DEMO_SESSIONS: &[DemoSession] = &[
+    DemoSession {
+        name: "Day Trading Journal",
+        session_type: "trading",
+        type_key: "type",
+        days_ago: 2,
+        entries: &[
+            ("Opened AAPL position at $175.50. Stop loss at $170. Target $185.", "entry", 0.8),
+            ("AAPL hit first target. Took partial profits. Moving stop to breakeven.", "management", 0.9),
+            ("Closed AAPL position at $184.20. +$8.70 per share. Good discipline.", "exit", 0.7),
+            ("TSLA breaking out of consolidation. Watching for volume confirmation.", "watchlist", 0.6),
+            ("Market showing weakness near close. Staying flat into tomorrow.", "market-analysis", 0.5),
+        ],
+    },
+    DemoSession {
+        name: "Market Research",
+        session_type: "research",
+        type_key: "research_type",
+        days_ago: 6,
+        entries: &[
+            ("Fed meeting minutes suggest dovish pivot. Rate cuts possible by Q2.", "fed", 0.9),
+            ("Semiconductor industry showing signs of recovery. NVDA earnings key.", "sector", 0.8),
+            ("Oil inventory data bearish. WTI could test $70 support.", "commodities", 0.7),
+            ("Consumer spending data mixed. Retail stocks under pressure.", "economic", 0.6),
+            ("Geopolitical tensions rising. Safe havens getting bid.", "geopolitical", 0.8),
+        ],
+    },
+    DemoSession {
+        name: "Investment Learning",
+        session_type: "education",
+        type_key: "learning_type",
+        days_ago: 12,
+        entries: &[
+            ("Read about momentum investing. Key is buying strength, selling weakness.", "strategy", 0.7),
+            ("Risk management rule: Never risk more than 2% per trade.", "risk", 0.9),
+            ("Position sizing formula: (Account Size × Risk %) ÷ (Entry - Stop)", "formula", 0.8),
+            ("Market cycles: Accumulation → Markup → Distribution → Markdown", "theory", 0.6),
+            ("Learned about sector rotation. Technology leads in growth phases.", "sectors", 0.5),
+        ],
+    },
+    DemoSession {
+        name: "Personal Finance",
+        session_type: "personal",
+        type_key: "personal_type",
+        days_ago: 20,
+        entries: &[
+            ("Emergency fund goal: $50k. Currently at $32k. Need $18k more.", "emergency", 0.8),
+            ("401k contribution increased to 15%. Company matches 5%.", "retirement", 0.7),
+            ("Mortgage rate locked at 6.5%. Considering refinance if rates drop.", "mortgage", 0.6),
+            ("Tax loss harvesting opportunity in December. Review positions.", "taxes", 0.9),
+            ("Insurance review due. Life insurance needs update after promotion.", "insurance", 0.5),
+        ],
+    },
+    DemoSession {
+        name: "Casual Market Notes",
+        session_type: "casual",
+        type_key: "type",
+        days_ago: 25,
+        entries: &[
+            ("Coffee shop was crowded today. Market sentiment seems positive.", "observation", 0.2),
+            ("Checked portfolio. Everything looking stable.", "observation", 0.1),
+            ("Random thought: should diversify more into international markets.", "idea", 0.3),
+            ("Weather is nice today. Good for market psychology.", "observation", 0.1),
+            ("Microsoft earnings call was boring but solid.", "earnings", 0.3),
+        ],
+    },
+];
+
+impl MindCache {
+    /// Populate the store with a demo trading-journal corpus
+    ///
+    /// Creates one session per theme under [`DEMO_USER_ID`] and backdates the
+    /// memories over the last few weeks so recall, summaries and decay all
+    /// have something realistic to work with.
+    pub fn seed_demo_data(&mut self) -> Result<DemoSeedReport, Box<dyn std::error::Error>> {
+        let now = Utc::now();
+        let mut session_ids = Vec::new();
+        let mut memories_created = 0;
+
+        for demo_session in DEMO_SESSIONS {
+            let session_id = self.create_session(DEMO_USER_ID, Some(demo_session.name))?;
+            let session_start = now - Duration::days(demo_session.days_ago);
+
+            for (offset, (content, tag, importance)) in demo_session.entries.iter().enumerate() {
+                let mut metadata = HashMap::new();
+                metadata.insert(demo_session.type_key.to_string(), tag.to_string());
+                metadata.insert("session_type".to_string(), demo_session.session_type.to_string());
+                metadata.insert("tags".to_string(), format!("{},{}", demo_session.session_type, tag));
+
+                let memory = MemoryItem {
+                    id: String::new(),
+                    user_id: DEMO_USER_ID.to_string(),
+                    session_id: session_id.clone(),
+                    content: content.to_string(),
+                    metadata,
+                    timestamp: session_start + Duration::minutes(offset as i64 * 45),
+                    ttl_hours: self.config.default_memory_ttl_hours,
+                    importance: *importance,
+                    ..Default::default()
+                };
+
+                self.storage.save(memory)?;
+                memories_created += 1;
+            }
+
+            session_ids.push(session_id);
+        }
+
+        println!("Seeded {} demo memories across {} sessions", memories_created, session_ids.len());
+        Ok(DemoSeedReport {
+            user_id: DEMO_USER_ID.to_string(),
+            session_ids,
+            memories_created,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MindCacheConfig;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_seed_demo_data() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut cache = MindCache::with_config(MindCacheConfig {
+            storage_path: temp_dir.path().to_str().unwrap().to_string(),
+            ..MindCacheConfig::default()
+        })
+        .unwrap();
+
+        let report = cache.seed_demo_data().unwrap();
+        assert_eq!(report.session_ids.len(), DEMO_SESSIONS.len());
+        assert_eq!(report.memories_created, 25);
+
+        let memories = cache.recall(DEMO_USER_ID, Some("AAPL"), None, None).unwrap();
+        assert_eq!(memories.len(), 3);
+
+        let sessions = cache.get_user_sessions(DEMO_USER_ID).unwrap();
+        assert_eq!(sessions.len(), DEMO_SESSIONS.len());
+    }
+}
@@ -0,0 +1,407 @@
+//! Hierarchical digests
+//!
+//! An agent that has worked with a user for months can't put every memory
+//! into a prompt. Digests compress that history in levels: each day's
+//! memories are summarized per session, the session summaries roll up into a
+//! daily digest, and the daily digests of a period roll up into a user
+//! digest. All text comes from the configured [`crate::Summarizer`].
+//!
+//! Digests are stored as [`MemoryType::Summary`] memories in the
+//! [`DIGEST_SESSION`] session under stable ids, so they are recallable like
+//! any other memory. A daily digest is only regenerated when its day gains
+//! memories; it outlives the raw memories it was built from.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use crate::provenance::{DerivationMethod, ProvenanceRecord};
+use crate::session;
+use crate::storage::{MemoryItem, MemoryType, QueryFilter};
+use crate::summarizer::{self, SummaryKind, SummaryRequest};
+use crate::timeline::RelativeDuration;
+use crate::MindCache;
+
+/// Session that digest memories are saved under
+pub const DIGEST_SESSION: &str = "_digests";
+
+/// Key topics kept per digest
+const DIGEST_TOPICS: usize = 5;
+
+const LEVEL_KEY: &str = "digest_level";
+const PERIOD_KEY: &str = "digest_period";
+const TOPICS_KEY: &str = "key_topics";
+const MEMORY_COUNT_KEY: &str = "memory_count";
+const FIRST_MEMORY_KEY: &str = "first_memory_at";
+const LAST_MEMORY_KEY: &str = "last_memory_at";
+
+/// Level of the digest hierarchy
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DigestLevel {
+    /// One user's memories from one UTC day
+    Day,
+    /// Daily digests of a period rolled together
+    User,
+}
+
+impl DigestLevel {
+    fn as_str(self) -> &'static str {
+        match self {
+            DigestLevel::Day => "day",
+            DigestLevel::User => "user",
+        }
+    }
+}
+
+/// Which days a digest covers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DigestPeriod {
+    /// One UTC day; returns that day's daily digest
+    Day(NaiveDate),
+    /// Every day touched by the last span of time, e.g. `7d`
+    Last(RelativeDuration),
+    /// The user's whole history
+    All,
+}
+
+impl DigestPeriod {
+    fn label(self) -> String {
+        match self {
+            DigestPeriod::Day(day) => day.to_string(),
+            DigestPeriod::Last(duration) => format!("last-{}", duration),
+            DigestPeriod::All => "all".to_string(),
+        }
+    }
+
+    fn contains(self, day: NaiveDate, now: DateTime<Utc>) -> bool {
+        match self {
+            DigestPeriod::Day(period_day) => day == period_day,
+            DigestPeriod::Last(duration) => day >= duration.before(now).date_naive(),
+            DigestPeriod::All => true,
+        }
+    }
+}
+
+/// A stored summary of a day or period of a user's memories
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Digest {
+    /// Id of the memory holding this digest
+    pub id: String,
+    pub user_id: String,
+    pub level: DigestLevel,
+    /// The day (`2024-05-01`), `last-7d` or `all`
+    pub period: String,
+    pub summary_text: String,
+    pub key_topics: Vec<String>,
+    /// Raw memories covered
+    pub memory_count: usize,
+    /// Timestamps of the oldest and newest memory covered
+    pub date_range: (DateTime<Utc>, DateTime<Utc>),
+}
+
+impl Digest {
+    fn memory_id(user_id: &str, level: DigestLevel, period: &str) -> String {
+        format!("digest-{}-{}-{}", level.as_str(), user_id, period)
+    }
+
+    /// Read a digest back from its stored memory
+    pub fn from_memory(memory: &MemoryItem) -> Option<Digest> {
+        let metadata = &memory.metadata;
+        let level = match metadata.get(LEVEL_KEY)?.as_str() {
+            "day" => DigestLevel::Day,
+            "user" => DigestLevel::User,
+            _ => return None,
+        };
+        let timestamp = |key: &str| {
+            DateTime::parse_from_rfc3339(metadata.get(key)?)
+                .ok()
+                .map(|timestamp| timestamp.with_timezone(&Utc))
+        };
+
+        Some(Digest {
+            id: memory.id.clone(),
+            user_id: memory.user_id.clone(),
+            level,
+            period: metadata.get(PERIOD_KEY)?.clone(),
+            summary_text: memory.content.clone(),
+            key_topics: metadata
+                .get(TOPICS_KEY)
+                .filter(|topics| !topics.is_empty())
+                .map(|topics| topics.split(',').map(str::to_string).collect())
+                .unwrap_or_default(),
+            memory_count: metadata.get(MEMORY_COUNT_KEY)?.parse().ok()?,
+            date_range: (timestamp(FIRST_MEMORY_KEY)?, timestamp(LAST_MEMORY_KEY)?),
+        })
+    }
+
+    fn to_memory(&self, importance: f32) -> MemoryItem {
+        let metadata = HashMap::from([
+            (LEVEL_KEY.to_string(), self.level.as_str().to_string()),
+            (PERIOD_KEY.to_string(), self.period.clone()),
+            (TOPICS_KEY.to_string(), self.key_topics.join(",")),
+            (MEMORY_COUNT_KEY.to_string(), self.memory_count.to_string()),
+            (FIRST_MEMORY_KEY.to_string(), self.date_range.0.to_rfc3339()),
+            (LAST_MEMORY_KEY.to_string(), self.date_range.1.to_rfc3339()),
+        ]);
+
+        MemoryItem {
+            id: self.id.clone(),
+            user_id: self.user_id.clone(),
+            session_id: DIGEST_SESSION.to_string(),
+            content: self.summary_text.clone(),
+            metadata,
+            // Dated by the newest memory covered, so it sits in its period
+            timestamp: self.date_range.1,
+            importance,
+            memory_type: MemoryType::Summary,
+            ..Default::default()
+        }
+    }
+}
+
+/// Topics across digests, most common first, ties in first-seen order
+fn rank_topics(digests: &[Digest]) -> Vec<String> {
+    let mut counts: HashMap<&str, (usize, usize)> = HashMap::new();
+    for (seen, topic) in digests.iter().flat_map(|digest| &digest.key_topics).enumerate() {
+        counts.entry(topic).or_insert((0, seen)).0 += 1;
+    }
+
+    let mut topics: Vec<(&str, (usize, usize))> = counts.into_iter().collect();
+    topics.sort_by_key(|&(_, (count, seen))| (std::cmp::Reverse(count), seen));
+    topics.into_iter().take(DIGEST_TOPICS).map(|(topic, _)| topic.to_string()).collect()
+}
+
+fn mean_importance(memories: &[MemoryItem]) -> f32 {
+    memories.iter().map(|m| m.importance).sum::<f32>() / memories.len().max(1) as f32
+}
+
+impl MindCache {
+    /// Digest of a user's memories over `period`, or `None` if there are none
+    ///
+    /// Daily digests for the period are brought up to date first, then rolled
+    /// into a user digest. Unchanged digests are read back from storage
+    /// rather than summarized again.
+    pub fn get_user_digest(&mut self, user_id: &str, period: DigestPeriod) -> Result<Option<Digest>, Box<dyn std::error::Error>> {
+        let now = self.storage.now();
+        let days: BTreeSet<NaiveDate> = self
+            .storage
+            .memory_timestamps(user_id)
+            .into_iter()
+            .map(|timestamp| timestamp.date_naive())
+            .filter(|day| period.contains(*day, now))
+            .collect();
+
+        let mut daily = Vec::new();
+        for day in days {
+            if let Some(digest) = self.refresh_daily_digest(user_id, day)? {
+                daily.push(digest);
+            }
+        }
+        if let DigestPeriod::Day(_) = period {
+            return Ok(daily.pop());
+        }
+        if daily.is_empty() {
+            return Ok(None);
+        }
+
+        let memory_count = daily.iter().map(|digest| digest.memory_count).sum();
+        let date_range = (
+            daily.iter().map(|digest| digest.date_range.0).min().unwrap(),
+            daily.iter().map(|digest| digest.date_range.1).max().unwrap(),
+        );
+        let label = period.label();
+        let id = Digest::memory_id(user_id, DigestLevel::User, &label);
+        if let Some(existing) = self.storage.get_memory(&id)?.as_ref().and_then(Digest::from_memory) {
+            if existing.memory_count == memory_count && existing.date_range == date_range {
+                return Ok(Some(existing));
+            }
+        }
+
+        let parts: Vec<MemoryItem> = daily.iter().map(|digest| digest.to_memory(0.5)).collect();
+        let digest = Digest {
+            id,
+            user_id: user_id.to_string(),
+            level: DigestLevel::User,
+            period: label,
+            summary_text: String::new(),
+            key_topics: rank_topics(&daily),
+            memory_count,
+            date_range,
+        };
+        self.store_digest(digest, &parts).map(Some)
+    }
+
+    /// Bring one day's digest up to date with the memories stored for it
+    fn refresh_daily_digest(&mut self, user_id: &str, day: NaiveDate) -> Result<Option<Digest>, Box<dyn std::error::Error>> {
+        let id = Digest::memory_id(user_id, DigestLevel::Day, &day.to_string());
+        let existing = self.storage.get_memory(&id)?.as_ref().and_then(Digest::from_memory);
+
+        let start = day.and_hms_opt(0, 0, 0).unwrap().and_utc();
+        let mut memories: Vec<MemoryItem> = self
+            .storage
+            .recall(QueryFilter {
+                user_id: Some(user_id.to_string()),
+                date_from: Some(start),
+                date_to: Some(start + Duration::days(1) - Duration::nanoseconds(1)),
+                ..Default::default()
+            })?
+            .into_iter()
+            .filter(|memory| memory.memory_type != MemoryType::Summary)
+            .collect();
+        memories.sort_by_key(|memory| memory.timestamp);
+
+        let (Some(first), Some(last)) = (memories.first(), memories.last()) else {
+            return Ok(existing);
+        };
+        let date_range = (first.timestamp, last.timestamp);
+        if let Some(existing) = existing {
+            // Nothing new since the digest was built (decay may have removed some sources)
+            if memories.len() <= existing.memory_count && date_range.1 <= existing.date_range.1 {
+                return Ok(Some(existing));
+            }
+        }
+
+        let stop_words = self.storage.stop_words();
+        let mut sessions: BTreeMap<&str, Vec<MemoryItem>> = BTreeMap::new();
+        for memory in &memories {
+            sessions.entry(memory.session_id.as_str()).or_default().push(memory.clone());
+        }
+        let mut session_summaries: Vec<MemoryItem> = sessions
+            .into_iter()
+            .map(|(session_id, session_memories)| {
+                let key_topics = session::key_topics(&session_memories, DIGEST_TOPICS, &stop_words);
+                let content = summarizer::summarize_or_fallback(self.summarizer.as_ref(), &SummaryRequest {
+                    kind: SummaryKind::Session,
+                    memories: &session_memories,
+                    key_topics: &key_topics,
+                });
+                MemoryItem {
+                    id: format!("summary-{}", session_id),
+                    user_id: user_id.to_string(),
+                    session_id: session_id.to_string(),
+                    content,
+                    timestamp: session_memories.last().unwrap().timestamp,
+                    importance: mean_importance(&session_memories),
+                    ..Default::default()
+                }
+            })
+            .collect();
+        session_summaries.sort_by_key(|summary| summary.timestamp);
+
+        let digest = Digest {
+            id,
+            user_id: user_id.to_string(),
+            level: DigestLevel::Day,
+            period: day.to_string(),
+            summary_text: String::new(),
+            key_topics: session::key_topics(&memories, DIGEST_TOPICS, &stop_words),
+            memory_count: memories.len(),
+            date_range,
+        };
+        let sources: Vec<String> = memories.iter().map(|memory| memory.id.clone()).collect();
+        let digest = self.store_digest(digest, &session_summaries)?;
+        // Daily digests trace back to raw memories, not the transient session summaries
+        self.storage.provenance().record(ProvenanceRecord {
+            derived_id: digest.id.clone(),
+            derived_from: sources,
+            method: DerivationMethod::Digest,
+            created_at: self.storage.now(),
+        })?;
+        Ok(Some(digest))
+    }
+
+    /// Summarize `parts` into the digest's text and save or replace its memory
+    fn store_digest(&mut self, mut digest: Digest, parts: &[MemoryItem]) -> Result<Digest, Box<dyn std::error::Error>> {
+        digest.summary_text = summarizer::summarize_or_fallback(self.summarizer.as_ref(), &SummaryRequest {
+            kind: SummaryKind::Digest,
+            memories: parts,
+            key_topics: &digest.key_topics,
+        });
+
+        let memory = digest.to_memory(mean_importance(parts));
+        match self.storage.position_of(&digest.id) {
+            Some(position) => {
+                self.storage.replace_at(position, memory)?;
+            }
+            None => {
+                self.storage.save(memory)?;
+            }
+        }
+        if digest.level == DigestLevel::User {
+            self.storage.provenance().record(ProvenanceRecord {
+                derived_id: digest.id.clone(),
+                derived_from: parts.iter().map(|part| part.id.clone()).collect(),
+                method: DerivationMethod::Digest,
+                created_at: self.storage.now(),
+            })?;
+        }
+        Ok(digest)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use chrono::TimeZone;
+    use crate::test_support::temp_cache;
+
+    fn save_at(cache: &mut MindCache, session_id: &str, content: &str, timestamp: DateTime<Utc>) {
+        cache.storage.save(MemoryItem {
+            user_id: "alice".to_string(),
+            session_id: session_id.to_string(),
+            content: content.to_string(),
+            timestamp,
+            importance: 0.5,
+            ..Default::default()
+        }).unwrap();
+    }
+
+    #[test]
+    fn test_digests_roll_up_and_refresh() {
+        let (mut cache, _temp_dir) = temp_cache();
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        cache.set_summarizer(move |request: &SummaryRequest| -> Result<String, Box<dyn std::error::Error>> {
+            counter.fetch_add(1, Ordering::SeqCst);
+            Ok(summarizer::ExtractiveSummarizer::summary(request))
+        });
+
+        let day1 = Utc.with_ymd_and_hms(2024, 3, 1, 9, 0, 0).unwrap();
+        let day2 = Utc.with_ymd_and_hms(2024, 3, 2, 9, 0, 0).unwrap();
+        save_at(&mut cache, "s1", "Bought gold futures", day1);
+        save_at(&mut cache, "s1", "Gold futures rallied", day1 + Duration::hours(1));
+        save_at(&mut cache, "s2", "Reviewed bond ladder", day1 + Duration::hours(2));
+        save_at(&mut cache, "s3", "Sold gold futures", day2);
+
+        let digest = cache.get_user_digest("alice", DigestPeriod::All).unwrap().unwrap();
+        assert_eq!(digest.level, DigestLevel::User);
+        assert_eq!(digest.memory_count, 4);
+        assert!(digest.key_topics.contains(&"gold".to_string()));
+        assert!(digest.summary_text.starts_with("2 summaries"), "{}", digest.summary_text);
+
+        let first_day = cache.get_user_digest("alice", DigestPeriod::Day(day1.date_naive())).unwrap().unwrap();
+        assert_eq!((first_day.level, first_day.memory_count), (DigestLevel::Day, 3));
+        assert!(cache.get_user_digest("alice", DigestPeriod::Last(RelativeDuration::days(1))).unwrap().is_none());
+
+        // Unchanged digests come back from storage
+        let before = calls.load(Ordering::SeqCst);
+        assert_eq!(cache.get_user_digest("alice", DigestPeriod::All).unwrap().unwrap(), digest);
+        assert_eq!(calls.load(Ordering::SeqCst), before);
+
+        save_at(&mut cache, "s3", "Closed the gold position", day2 + Duration::hours(3));
+        let refreshed = cache.get_user_digest("alice", DigestPeriod::All).unwrap().unwrap();
+        assert_eq!(refreshed.memory_count, 5);
+        assert_eq!(refreshed.id, digest.id);
+
+        let stored = cache.recall_advanced(QueryFilter {
+            user_id: Some("alice".to_string()),
+            memory_type: Some(MemoryType::Summary),
+            ..Default::default()
+        }).unwrap();
+        assert_eq!(stored.len(), 3);
+        assert_eq!(cache.get_provenance(&first_day.id).unwrap().derived_from.len(), 3);
+    }
+}
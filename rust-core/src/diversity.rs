@@ -0,0 +1,123 @@
+//! Diverse recall
+//!
+//! When many near-identical memories match, the newest `limit` of them can
+//! all say the same thing, which wastes a prompt's context. Setting
+//! `QueryFilter::diversity` re-ranks the matches by maximal marginal
+//! relevance: each next result is the one that best trades its relevance
+//! off against repeating the results picked before it. Relevance is the
+//! share of the filter's search words (`keywords` and the words of `query`)
+//! a memory contains; without search words, newer is more relevant.
+//! Repetition is the overlap of content words, as in
+//! `MindCache::find_similar`.
+//!
+//! All matches are read and compared with each result picked, so pair
+//! `diversity` with a `limit`.
+
+use std::collections::HashSet;
+use crate::planner;
+use crate::query;
+use crate::similar::{content_terms, jaccard};
+use crate::stopwords::StopWords;
+use crate::storage::{MemoryItem, QueryFilter};
+
+/// The words a filter searches for, lowercased
+pub(crate) fn search_terms(filter: &QueryFilter) -> Result<HashSet<String>, Box<dyn std::error::Error>> {
+    let mut terms: HashSet<String> = filter
+        .keywords
+        .iter()
+        .flatten()
+        .flat_map(|keyword| planner::index_terms(keyword))
+        .collect();
+    if let Some(expression) = &filter.query {
+        for term in query::parse(expression)?.positive_terms() {
+            terms.extend(planner::index_terms(term));
+        }
+    }
+    Ok(terms)
+}
+
+/// The first `wanted` of `matches` (newest first) in maximal-marginal-relevance order
+///
+/// `weight` is how much repetition counts against relevance, 0.0 to 1.0.
+pub(crate) fn rerank(matches: Vec<MemoryItem>, search_terms: &HashSet<String>, weight: f32, wanted: usize, stop_words: &StopWords) -> Vec<MemoryItem> {
+    let count = matches.len();
+    let terms: Vec<HashSet<String>> = matches.iter().map(|memory| content_terms(&memory.content, stop_words)).collect();
+    let relevance: Vec<f32> = matches
+        .iter()
+        .enumerate()
+        .map(|(rank, memory)| {
+            if search_terms.is_empty() {
+                return 1.0 - rank as f32 / count as f32;
+            }
+            let words = planner::index_terms(&memory.content);
+            search_terms.iter().filter(|term| words.contains(*term)).count() as f32 / search_terms.len() as f32
+        })
+        .collect();
+
+    // Highest overlap of each candidate with any result picked so far
+    let mut repetition = vec![0.0f32; count];
+    let mut remaining: Vec<usize> = (0..count).collect();
+    let mut picked = Vec::with_capacity(wanted.min(count));
+    while picked.len() < wanted && !remaining.is_empty() {
+        let mut best = 0;
+        let mut best_score = f32::NEG_INFINITY;
+        for (slot, candidate) in remaining.iter().enumerate() {
+            let score = (1.0 - weight) * relevance[*candidate] - weight * repetition[*candidate];
+            // Ties go to the newer memory
+            if score > best_score {
+                best = slot;
+                best_score = score;
+            }
+        }
+        let chosen = remaining.remove(best);
+        for candidate in &remaining {
+            repetition[*candidate] = repetition[*candidate].max(jaccard(&terms[chosen], &terms[*candidate]));
+        }
+        picked.push(chosen);
+    }
+
+    let mut matches: Vec<Option<MemoryItem>> = matches.into_iter().map(Some).collect();
+    picked.into_iter().filter_map(|index| matches[index].take()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::QueryFilter;
+    use crate::test_support::temp_cache;
+
+    #[test]
+    fn test_diversity_spreads_limited_recall_over_distinct_memories() {
+        let (mut cache, _temp_dir) = temp_cache();
+        let miners = cache.save("alice", "s1", "Gold miners reported strong earnings", None).unwrap();
+        let coins = cache.save("alice", "s1", "Started a gold coin collection", None).unwrap();
+        let mut repeats = Vec::new();
+        for _ in 0..3 {
+            repeats.push(cache.save("alice", "s1", "Gold price rose sharply at the open", None).unwrap());
+        }
+
+        let gold = QueryFilter {
+            user_id: Some("alice".to_string()),
+            keywords: Some(vec!["gold".to_string()]),
+            limit: Some(3),
+            ..Default::default()
+        };
+        let ids = |filter: QueryFilter| -> Vec<String> {
+            cache.recall_advanced(filter).unwrap().into_iter().map(|memory| memory.id).collect()
+        };
+        assert!(ids(gold.clone()).iter().all(|id| repeats.contains(id)));
+
+        let diverse = ids(QueryFilter { diversity: Some(0.5), ..gold.clone() });
+        assert_eq!(diverse.len(), 3);
+        assert!(repeats.contains(&diverse[0]));
+        let mut rest = diverse[1..].to_vec();
+        rest.sort();
+        let mut distinct = vec![miners, coins];
+        distinct.sort();
+        assert_eq!(rest, distinct);
+
+        // Pages continue the same ranking; out-of-range weights are refused
+        let second_page = ids(QueryFilter { diversity: Some(0.5), offset: Some(1), limit: Some(1), ..gold.clone() });
+        assert_eq!(second_page, diverse[1..2]);
+        assert!(cache.recall_advanced(QueryFilter { diversity: Some(1.5), ..gold }).is_err());
+    }
+}
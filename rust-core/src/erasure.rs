@@ -0,0 +1,189 @@
+//! Full erasure of a user's data
+//!
+//! `MindCache::delete_user` is for right-to-erasure requests. Unlike
+//! deleting memories, which only unlinks records until the next compaction,
+//! it removes everything the store holds about a user at once:
+//!
+//! - every memory, including digests and compressed summaries, and their
+//!   index entries
+//! - the user's segment log, overwritten with zeros before it is deleted
+//! - provenance records and links that involve those memories, which covers
+//!   session summaries
+//! - the memory contents in `changes.log`; a `deleted` change is appended
+//!   for each memory so replicas erase it too
+//! - earlier versions of the memories kept in `history.log`
+//! - the user's trash
+//! - cached sessions
+//!
+//! Backups taken earlier still contain the user and must be handled
+//! separately.
+
+use std::collections::{BTreeSet, HashSet};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use crate::events::MemoryEvent;
+use crate::provenance::DerivationMethod;
+use crate::storage::MemoryType;
+use crate::MindCache;
+
+/// What `delete_user` removed, for compliance records
+///
+/// Holds counts and ids only, never memory contents.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeletionReport {
+    pub user_id: String,
+    pub memories_deleted: usize,
+    pub memory_ids: Vec<String>,
+    pub sessions_deleted: usize,
+    /// Session summaries and stored summary memories such as digests
+    pub summaries_deleted: usize,
+    pub provenance_records_removed: usize,
+    pub links_removed: usize,
+    #[serde(default)]
+    pub facts_removed: usize,
+    /// Change log entries whose memory contents were stripped
+    pub changes_redacted: usize,
+    /// Superseded versions dropped from the version history
+    pub history_versions_removed: usize,
+    /// Soft-deleted memories purged from the trash
+    pub trashed_memories_purged: usize,
+    /// Size of the segment log that was overwritten and deleted
+    pub bytes_erased: u64,
+    pub started_at: DateTime<Utc>,
+    pub completed_at: DateTime<Utc>,
+}
+
+impl MindCache {
+    /// Erase every trace of a user from the store
+    ///
+    /// Deleting a user that has no data succeeds with an empty report.
+    pub fn delete_user(&mut self, user_id: &str) -> Result<DeletionReport, Box<dyn std::error::Error>> {
+        let started_at = self.storage.now();
+        let (memories, bytes_erased) = self.storage.erase_user(user_id)?;
+
+        let memory_ids: HashSet<String> = memories.iter().map(|memory| memory.id.clone()).collect();
+        let derived = self.storage.provenance().remove_involving(&memory_ids)?;
+        let links_removed = self.storage.links().remove_involving(&memory_ids)?;
+        let facts_removed = self.storage.facts().remove_user(user_id)?;
+        self.storage.summaries().remove_user(user_id)?;
+        self.storage.reviews().remove_user(user_id)?;
+        let changes_redacted = self.storage.changes().redact_user(user_id)?;
+        let history_versions_removed = self.storage.history().remove_user(user_id)?;
+        let trashed_memories_purged = self.storage.trash().purge_where(|item| item.memory.user_id == user_id)?.len();
+
+        let mut sessions: BTreeSet<String> = memories.iter().map(|memory| memory.session_id.clone()).collect();
+        sessions.extend(self.session_manager.forget_user(user_id)?);
+        self.decay_engine.forget_user(user_id)?;
+
+        // A session summary has both a provenance record and a stored memory
+        let summaries: HashSet<&str> = derived
+            .iter()
+            .filter(|record| record.method == DerivationMethod::SessionSummary)
+            .map(|record| record.derived_id.as_str())
+            .chain(memories.iter().filter(|memory| memory.memory_type == MemoryType::Summary).map(|memory| memory.id.as_str()))
+            .collect();
+        let mut ids: Vec<String> = memory_ids.into_iter().collect();
+        ids.sort();
+
+        let report = DeletionReport {
+            user_id: user_id.to_string(),
+            memories_deleted: memories.len(),
+            memory_ids: ids,
+            sessions_deleted: sessions.len(),
+            summaries_deleted: summaries.len(),
+            provenance_records_removed: derived.len(),
+            links_removed,
+            facts_removed,
+            changes_redacted,
+            history_versions_removed,
+            trashed_memories_purged,
+            bytes_erased,
+            started_at,
+            completed_at: self.storage.now(),
+        };
+        println!(
+            "Deleted user {}: {} memories, {} sessions, {} bytes erased",
+            user_id, report.memories_deleted, report.sessions_deleted, report.bytes_erased
+        );
+        self.storage.events().emit(MemoryEvent::UserDeleted {
+            user_id: user_id.to_string(),
+            memory_ids: report.memory_ids.clone(),
+        });
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MindCacheConfig;
+    use crate::test_support::temp_cache_with;
+    use tempfile::TempDir;
+
+    /// Alice's two memories, sessions, summary and links, and one memory of Bob's linking to hers
+    fn alice_and_bob() -> (MindCache, TempDir) {
+        let (mut cache, temp_dir) = temp_cache_with(MindCacheConfig { change_log_enabled: true, ..Default::default() });
+        let first = cache.save("alice", "s1", "Alice lives in Lisbon", None).unwrap();
+        let second = cache.save("alice", "s2", "Alice is allergic to peanuts", None).unwrap();
+        let kept = cache.save("bob", "s3", "Bob likes tea", None).unwrap();
+        cache.create_session("alice", Some("Notes")).unwrap();
+        cache.summarize_session("s1").unwrap();
+        cache.link_memories(&first, &second, "follow_up").unwrap();
+        cache.link_memories(&kept, &first, "mentions").unwrap();
+        (cache, temp_dir)
+    }
+
+    #[test]
+    fn test_delete_user_reports_what_it_removed() {
+        let (mut cache, _temp_dir) = alice_and_bob();
+        let report = cache.delete_user("alice").unwrap();
+        // Including the stored summary of s1, and the session holding it
+        assert_eq!(report.memories_deleted, 3);
+        assert_eq!(report.sessions_deleted, 4);
+        assert_eq!(report.summaries_deleted, 1);
+        assert_eq!(report.links_removed, 2);
+        assert_eq!(report.changes_redacted, 3);
+        assert!(report.bytes_erased > 0);
+    }
+
+    #[test]
+    fn test_delete_user_erases_memories_sessions_and_provenance() {
+        let (mut cache, _temp_dir) = alice_and_bob();
+        cache.delete_user("alice").unwrap();
+        assert!(cache.recall("alice", None, None, None).unwrap().is_empty());
+        assert!(cache.get_user_sessions("alice").unwrap().is_empty());
+        assert!(cache.get_provenance("summary-s1").is_none());
+        assert_eq!(cache.recall("bob", None, None, None).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_delete_user_removes_the_segment_log() {
+        let (mut cache, temp_dir) = alice_and_bob();
+        let alice_log = temp_dir.path().join(crate::segments::user_log("alice"));
+        assert!(alice_log.exists());
+        cache.delete_user("alice").unwrap();
+        assert!(!alice_log.exists() && !alice_log.parent().unwrap().exists());
+    }
+
+    #[test]
+    fn test_delete_user_redacts_the_change_log() {
+        let (mut cache, _temp_dir) = alice_and_bob();
+        cache.delete_user("alice").unwrap();
+        let changes = cache.changes_since(0).unwrap();
+        assert!(changes.iter().filter(|change| change.user_id == "alice").all(|change| change.memory.is_none()));
+    }
+
+    #[test]
+    fn test_delete_user_holds_across_a_reopen() {
+        let (mut cache, temp_dir) = alice_and_bob();
+        cache.delete_user("alice").unwrap();
+        let config = cache.config.clone();
+        drop(cache);
+
+        let reopened = MindCache::with_config(config).unwrap();
+        assert!(reopened.recall("alice", None, None, None).unwrap().is_empty());
+        assert_eq!(reopened.recall("bob", None, None, None).unwrap().len(), 1);
+        assert!(!std::fs::read_to_string(temp_dir.path().join("index.bin")).unwrap().contains("alice"));
+        assert!(!std::fs::read_to_string(temp_dir.path().join("segments.json")).unwrap().contains("alice"));
+    }
+}
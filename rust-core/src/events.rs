@@ -0,0 +1,154 @@
+//! Memory lifecycle events
+//!
+//! Observers registered with `MindCache::subscribe` are called synchronously,
+//! on the thread doing the work, after the change has been persisted. Keep
+//! them quick; hand anything slow off to a channel or a worker.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+use serde::{Deserialize, Serialize};
+use crate::review::Review;
+use crate::session::SessionSummary;
+use crate::storage::MemoryItem;
+
+/// Something that happened to a memory or session
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum MemoryEvent {
+    MemorySaved {
+        memory: MemoryItem,
+    },
+    /// A decay pass deleted the memory, past its TTL or over a limit; sent
+    /// once, after the deletion
+    MemoryExpired {
+        memory_id: String,
+        user_id: String,
+        session_id: String,
+    },
+    /// A decay pass folded a group of memories into one compressed summary
+    MemoryCompressed {
+        compressed_id: String,
+        original_ids: Vec<String>,
+        user_id: String,
+        session_id: String,
+        summary: String,
+    },
+    SessionSummarized {
+        summary: SessionSummary,
+    },
+    /// Every memory of a user was erased by `MindCache::delete_user`
+    UserDeleted {
+        user_id: String,
+        memory_ids: Vec<String>,
+    },
+    /// A reminder's due time arrived; see `MindCache::fire_due_reminders`
+    ReminderDue {
+        memory: MemoryItem,
+    },
+    /// `MindCache::run_due_reviews` reviewed a user's recent memories
+    ReviewReady {
+        review: Review,
+    },
+    /// The config was re-read from a file and some fields changed
+    ConfigReloaded {
+        path: String,
+        changed_fields: Vec<String>,
+    },
+}
+
+/// Receives memory lifecycle events
+pub trait MemoryObserver: Send + Sync {
+    fn on_event(&self, event: &MemoryEvent);
+}
+
+impl<F> MemoryObserver for F
+where
+    F: Fn(&MemoryEvent) + Send + Sync,
+{
+    fn on_event(&self, event: &MemoryEvent) {
+        self(event)
+    }
+}
+
+/// Handle returned by `subscribe`, used to unsubscribe
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SubscriptionId(u64);
+
+type Observers = Vec<(SubscriptionId, Arc<dyn MemoryObserver>)>;
+
+/// Set of observers shared by every clone of a storage handle
+#[derive(Clone, Default)]
+pub struct EventBus {
+    observers: Arc<RwLock<Observers>>,
+    next_id: Arc<AtomicU64>,
+}
+
+impl EventBus {
+    pub fn subscribe(&self, observer: Arc<dyn MemoryObserver>) -> SubscriptionId {
+        let id = SubscriptionId(self.next_id.fetch_add(1, Ordering::Relaxed));
+        self.observers
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .push((id, observer));
+        id
+    }
+
+    /// Returns false if the subscription was already gone
+    pub fn unsubscribe(&self, id: SubscriptionId) -> bool {
+        let mut observers = self.observers.write().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let before = observers.len();
+        observers.retain(|(existing, _)| *existing != id);
+        observers.len() != before
+    }
+
+    pub fn has_observers(&self) -> bool {
+        !self.observers.read().unwrap_or_else(|poisoned| poisoned.into_inner()).is_empty()
+    }
+
+    pub fn emit(&self, event: MemoryEvent) {
+        // Snapshot so observers can subscribe or unsubscribe from inside a callback
+        let observers: Vec<Arc<dyn MemoryObserver>> = self
+            .observers
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .iter()
+            .map(|(_, observer)| Arc::clone(observer))
+            .collect();
+
+        for observer in observers {
+            observer.on_event(&event);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[test]
+    fn test_subscribe_and_unsubscribe() {
+        let bus = EventBus::default();
+        let seen = Arc::new(Mutex::new(Vec::new()));
+
+        let sink = Arc::clone(&seen);
+        let id = bus.subscribe(Arc::new(move |event: &MemoryEvent| {
+            if let MemoryEvent::MemoryExpired { memory_id, .. } = event {
+                sink.lock().unwrap().push(memory_id.clone());
+            }
+        }));
+
+        let expired = |id: &str| MemoryEvent::MemoryExpired {
+            memory_id: id.to_string(),
+            user_id: "alice".to_string(),
+            session_id: "s1".to_string(),
+        };
+        bus.emit(expired("m1"));
+        assert!(bus.unsubscribe(id));
+        assert!(!bus.unsubscribe(id));
+        bus.emit(expired("m2"));
+
+        assert_eq!(*seen.lock().unwrap(), vec!["m1".to_string()]);
+        assert!(!bus.has_observers());
+    }
+}
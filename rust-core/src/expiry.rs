@@ -0,0 +1,130 @@
+//! Hooks run before decay expires or compresses memories
+//!
+//! A decay run first selects memories (past their TTL, over the user's
+//! limit, or old and unimportant enough to compress), then hands each batch
+//! to the [`ExpiryHook`] set with `MindCache::set_expiry_hook` before acting
+//! on it. The hook can export what it is given, and decide per memory to let
+//! decay go ahead, veto it, or give the memory a new importance, which also
+//! spares it this run.
+
+use std::sync::Arc;
+use serde::{Deserialize, Serialize};
+use crate::storage::MemoryItem;
+use crate::MindCache;
+
+/// Why decay selected a batch of memories
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExpiryReason {
+    /// Past their TTL, or the policy's maximum age, and unimportant
+    Expired,
+    /// The least important memories of a user over `max_memories_per_user`
+    OverLimit,
+    /// Old, unimportant memories of one session about to be compressed together
+    Compression,
+}
+
+/// What to do with one memory selected by decay
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExpiryDecision {
+    Proceed,
+    /// Keep the memory as it is
+    Veto,
+    /// Keep the memory and store it with this importance
+    SetImportance(f32),
+}
+
+/// Called by decay between selecting memories and acting on them
+pub trait ExpiryHook: Send + Sync {
+    /// One decision per memory, in order; memories without one proceed
+    fn before_expiry(&self, memories: &[MemoryItem], reason: ExpiryReason) -> Vec<ExpiryDecision>;
+}
+
+impl<F> ExpiryHook for F
+where
+    F: Fn(&[MemoryItem], ExpiryReason) -> Vec<ExpiryDecision> + Send + Sync,
+{
+    fn before_expiry(&self, memories: &[MemoryItem], reason: ExpiryReason) -> Vec<ExpiryDecision> {
+        self(memories, reason)
+    }
+}
+
+impl MindCache {
+    /// Have decay consult `hook` before expiring or compressing memories
+    pub fn set_expiry_hook<H: ExpiryHook + 'static>(&mut self, hook: H) {
+        let hook: Arc<dyn ExpiryHook> = Arc::new(hook);
+        self.expiry_hook = Some(hook.clone());
+        self.decay_engine.set_expiry_hook(Some(hook));
+    }
+
+    /// Let decay act without asking
+    pub fn clear_expiry_hook(&mut self) {
+        self.expiry_hook = None;
+        self.decay_engine.set_expiry_hook(None);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use chrono::{Duration, Utc};
+    use crate::events::MemoryEvent;
+    use crate::MindCacheConfig;
+    use crate::test_support::temp_cache_with;
+
+    #[test]
+    fn test_expiry_hook_can_veto_and_rescore() {
+        let (mut cache, _temp_dir) = temp_cache_with(MindCacheConfig {
+            enable_compression: false,
+            ..Default::default()
+        });
+        let old = Utc::now() - Duration::hours(10);
+        let mut ids = Vec::new();
+        for content in ["export me", "keep me", "rescore me"] {
+            ids.push(cache.storage.save(MemoryItem {
+                user_id: "alice".to_string(),
+                session_id: "s1".to_string(),
+                content: content.to_string(),
+                timestamp: old,
+                ttl_hours: Some(1),
+                importance: 0.1,
+                ..Default::default()
+            }).unwrap());
+        }
+
+        let exported = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&exported);
+        cache.set_expiry_hook(move |memories: &[MemoryItem], reason: ExpiryReason| {
+            assert_eq!(reason, ExpiryReason::Expired);
+            memories.iter().map(|memory| match memory.content.as_str() {
+                "keep me" => ExpiryDecision::Veto,
+                "rescore me" => ExpiryDecision::SetImportance(0.9),
+                _ => {
+                    sink.lock().unwrap().push(memory.id.clone());
+                    ExpiryDecision::Proceed
+                }
+            }).collect()
+        });
+        let expired = Arc::new(Mutex::new(Vec::new()));
+        let seen = Arc::clone(&expired);
+        cache.subscribe(move |event: &MemoryEvent| {
+            if let MemoryEvent::MemoryExpired { memory_id, .. } = event {
+                seen.lock().unwrap().push(memory_id.clone());
+            }
+        });
+
+        cache.decay().unwrap();
+        assert_eq!(*exported.lock().unwrap(), [ids[0].clone()]);
+        assert_eq!(*expired.lock().unwrap(), [ids[0].clone()]);
+        assert_eq!(cache.get_memory(&ids[2]).unwrap().unwrap().importance, 0.9);
+
+        // Without the hook the vetoed memory expires too
+        assert!(cache.get_memory(&ids[0]).unwrap().is_none());
+        cache.clear_expiry_hook();
+        expired.lock().unwrap().clear();
+        cache.decay().unwrap();
+        assert_eq!(*expired.lock().unwrap(), [ids[1].clone()]);
+    }
+}
@@ -0,0 +1,156 @@
+//! Human-readable session transcripts
+//!
+//! `export_session` renders a session as Markdown or HTML for sharing or
+//! archiving outside the store: its name, details, tags and metadata, then
+//! the session summary, then every memory in the order it was saved. Unlike
+//! `export_user_memories`, the output is not meant to be imported again.
+
+use std::fmt::Write as _;
+use serde::{Deserialize, Serialize};
+use crate::session::{Session, SessionSummary};
+use crate::storage::MemoryItem;
+use crate::MindCache;
+
+const TIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S UTC";
+
+/// Output format of [`MindCache::export_session`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportFormat {
+    #[default]
+    Markdown,
+    /// A standalone page
+    Html,
+}
+
+impl MindCache {
+    /// A transcript of a session, with its summary at the top
+    ///
+    /// Fails if the session holds no memories.
+    pub fn export_session(&mut self, session_id: &str, format: ExportFormat) -> Result<String, Box<dyn std::error::Error>> {
+        let session = self
+            .session_manager
+            .get_session(session_id)?
+            .ok_or_else(|| format!("session {} not found", session_id))?;
+        let mut memories = self.storage.get_memories_by_session(session_id)?;
+        if memories.is_empty() {
+            return Err(format!("session {} has no memories to export", session_id).into());
+        }
+        memories.sort_by(|a, b| a.timestamp.cmp(&b.timestamp).then_with(|| a.id.cmp(&b.id)));
+        let summary = self.session_manager.generate_session_summary(session_id)?;
+        Ok(match format {
+            ExportFormat::Markdown => markdown(&session, &summary, &memories)?,
+            ExportFormat::Html => html(&session, &summary, &memories)?,
+        })
+    }
+}
+
+/// Label, value pairs shown under the session title
+fn details(session: &Session, memories: &[MemoryItem]) -> Vec<(String, String)> {
+    let mut details = vec![
+        ("Session".to_string(), session.id.clone()),
+        ("User".to_string(), session.user_id.clone()),
+        ("From".to_string(), memories[0].timestamp.format(TIME_FORMAT).to_string()),
+        ("To".to_string(), memories[memories.len() - 1].timestamp.format(TIME_FORMAT).to_string()),
+        ("Memories".to_string(), memories.len().to_string()),
+    ];
+    if !session.tags.is_empty() {
+        details.push(("Tags".to_string(), session.tags.join(", ")));
+    }
+    let mut metadata: Vec<(&String, &String)> = session.metadata.iter().collect();
+    metadata.sort();
+    details.extend(metadata.into_iter().map(|(key, value)| (key.clone(), value.clone())));
+    details
+}
+
+/// Heading of one memory: when it was saved, and by whom or of what kind
+fn heading(memory: &MemoryItem) -> String {
+    let label = memory.role.clone().unwrap_or_else(|| format!("{:?}", memory.memory_type).to_lowercase());
+    format!("{} · {}", memory.timestamp.format(TIME_FORMAT), label)
+}
+
+fn markdown(session: &Session, summary: &SessionSummary, memories: &[MemoryItem]) -> Result<String, std::fmt::Error> {
+    let mut out = String::new();
+    writeln!(out, "# {}\n", session.name.as_deref().unwrap_or(&session.id))?;
+    for (label, value) in details(session, memories) {
+        writeln!(out, "- **{}:** {}", label, value)?;
+    }
+    writeln!(out, "\n## Summary\n\n{}", summary.summary_text)?;
+    if !summary.key_topics.is_empty() {
+        writeln!(out, "\nKey topics: {}", summary.key_topics.join(", "))?;
+    }
+    writeln!(out, "\n## Transcript")?;
+    for memory in memories {
+        writeln!(out, "\n### {}\n\n{}", heading(memory), memory.content)?;
+    }
+    Ok(out)
+}
+
+fn html(session: &Session, summary: &SessionSummary, memories: &[MemoryItem]) -> Result<String, std::fmt::Error> {
+    let title = escape(session.name.as_deref().unwrap_or(&session.id));
+    let mut out = String::new();
+    writeln!(out, "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n</head>\n<body>", title)?;
+    writeln!(out, "<h1>{}</h1>\n<dl>", title)?;
+    for (label, value) in details(session, memories) {
+        writeln!(out, "<dt>{}</dt><dd>{}</dd>", escape(&label), escape(&value))?;
+    }
+    writeln!(out, "</dl>\n<h2>Summary</h2>\n<p>{}</p>", escape(&summary.summary_text))?;
+    if !summary.key_topics.is_empty() {
+        writeln!(out, "<p>Key topics: {}</p>", escape(&summary.key_topics.join(", ")))?;
+    }
+    writeln!(out, "<h2>Transcript</h2>")?;
+    for memory in memories {
+        writeln!(out, "<h3>{}</h3>\n<p>{}</p>", escape(&heading(memory)), escape(&memory.content).replace('\n', "<br>\n"))?;
+    }
+    out.push_str("</body>\n</html>\n");
+    Ok(out)
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::temp_cache;
+
+    #[test]
+    fn test_export_session_transcripts() {
+        let (mut cache, _temp_dir) = temp_cache();
+        let template = crate::SessionTemplate {
+            name_pattern: Some("Gold & silver".to_string()),
+            tags: vec!["metals".to_string()],
+            ..crate::SessionTemplate::new("metals")
+        };
+        let session_id = cache.create_session_from_template("alice", &template).unwrap();
+        cache.save("alice", &session_id, "Bought gold <futures>", None).unwrap();
+        cache.save("alice", &session_id, "Sold silver", None).unwrap();
+
+        let markdown = cache.export_session(&session_id, ExportFormat::Markdown).unwrap();
+        assert!(markdown.starts_with("# Gold & silver\n"));
+        assert!(markdown.contains("- **Tags:** metals"));
+        assert!(markdown.contains("## Summary"));
+        let (top, transcript) = markdown.split_once("## Transcript").unwrap();
+        assert!(top.contains("## Summary"));
+        assert!(transcript.find("Bought gold <futures>").unwrap() < transcript.find("Sold silver").unwrap());
+
+        let html = cache.export_session(&session_id, ExportFormat::Html).unwrap();
+        assert!(html.contains("<h1>Gold &amp; silver</h1>"));
+        assert!(html.contains("Bought gold &lt;futures&gt;"));
+        assert!(html.trim_end().ends_with("</html>"));
+
+        assert!(cache.export_session("missing", ExportFormat::Markdown).is_err());
+    }
+}
@@ -0,0 +1,305 @@
+//! Entity and keyword extraction into memory metadata
+//!
+//! An [`EntityExtractor`] pulls tickers, dates, amounts, names and keywords
+//! out of a memory's content. With `entity_extraction_enabled` every save
+//! runs it, and `MindCache::extract_entities` runs it on a stored memory on
+//! demand. Each kind lands comma-separated under its own metadata key
+//! (`tickers`, `dates`, ...), so recall can filter on it with
+//! `MetadataCondition::Contains`, and session summaries rank extracted
+//! tickers and names as key topics. Keys the caller already set are left
+//! alone.
+
+use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use crate::stopwords::StopWords;
+use crate::storage::MemoryItem;
+use crate::versioning::MemoryUpdate;
+use crate::MindCache;
+
+pub const TICKERS_KEY: &str = "tickers";
+pub const DATES_KEY: &str = "dates";
+pub const AMOUNTS_KEY: &str = "amounts";
+pub const NAMES_KEY: &str = "names";
+pub const KEYWORDS_KEY: &str = "keywords";
+
+/// Keywords kept per memory by [`RuleBasedExtractor`]
+const MAX_KEYWORDS: usize = 5;
+
+/// What an extracted value is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EntityKind {
+    Ticker,
+    Date,
+    Amount,
+    Name,
+    Keyword,
+}
+
+impl EntityKind {
+    /// Metadata key values of this kind are stored under
+    pub fn metadata_key(self) -> &'static str {
+        match self {
+            EntityKind::Ticker => TICKERS_KEY,
+            EntityKind::Date => DATES_KEY,
+            EntityKind::Amount => AMOUNTS_KEY,
+            EntityKind::Name => NAMES_KEY,
+            EntityKind::Keyword => KEYWORDS_KEY,
+        }
+    }
+}
+
+/// One value found in a memory's content
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Entity {
+    pub kind: EntityKind,
+    pub value: String,
+}
+
+/// Finds entities in memory content
+pub trait EntityExtractor: Send + Sync {
+    fn extract(&self, content: &str) -> Vec<Entity>;
+}
+
+impl<F> EntityExtractor for F
+where
+    F: Fn(&str) -> Vec<Entity> + Send + Sync,
+{
+    fn extract(&self, content: &str) -> Vec<Entity> {
+        self(content)
+    }
+}
+
+/// Default extractor: pattern rules, no models
+///
+/// - tickers: 2-5 capital letters, optionally after `$` (`AAPL`, `$TSLA`)
+/// - dates: `YYYY-MM-DD`, or a month name followed by a day (`June 30`)
+/// - amounts: a currency symbol followed by a number (`$1,200.50`), or a
+///   number followed by `%` or a currency code (`15%`, `200 EUR`)
+/// - names: runs of capitalized words, ignoring the first word of a sentence
+///   when it stands alone
+/// - keywords: the most frequent longer words that are not stop words
+#[derive(Debug, Clone, Default)]
+pub struct RuleBasedExtractor {
+    stop_words: StopWords,
+}
+
+impl RuleBasedExtractor {
+    /// An extractor skipping `stop_words` when picking keywords
+    pub fn new(stop_words: StopWords) -> Self {
+        RuleBasedExtractor { stop_words }
+    }
+}
+
+const MONTHS: &[&str] = &[
+    "january", "february", "march", "april", "may", "june", "july", "august", "september", "october", "november",
+    "december", "jan", "feb", "mar", "apr", "jun", "jul", "aug", "sep", "sept", "oct", "nov", "dec",
+];
+const WEEKDAYS: &[&str] = &["monday", "tuesday", "wednesday", "thursday", "friday", "saturday", "sunday"];
+const CURRENCY_SYMBOLS: &[char] = &['$', '€', '£', '¥'];
+const CURRENCY_CODES: &[&str] = &["USD", "EUR", "GBP", "JPY", "CHF", "CAD", "AUD", "BTC", "ETH"];
+/// Capitalized words that are not names
+const NOT_NAMES: &[&str] = &["I", "I'm", "I've", "I'll", "I'd", "OK"];
+
+fn is_month(word: &str) -> bool {
+    MONTHS.contains(&word.to_lowercase().as_str())
+}
+
+fn is_iso_date(word: &str) -> bool {
+    let bytes = word.as_bytes();
+    bytes.len() == 10
+        && bytes[4] == b'-'
+        && bytes[7] == b'-'
+        && bytes.iter().enumerate().all(|(i, byte)| i == 4 || i == 7 || byte.is_ascii_digit())
+}
+
+fn is_number(word: &str) -> bool {
+    !word.is_empty()
+        && word.chars().next().is_some_and(|c| c.is_ascii_digit())
+        && word.chars().all(|c| c.is_ascii_digit() || c == ',' || c == '.')
+}
+
+fn is_ticker(word: &str) -> bool {
+    let symbol = word.strip_prefix('$').unwrap_or(word);
+    (2..=5).contains(&symbol.len())
+        && symbol.chars().all(|c| c.is_ascii_uppercase())
+        && !CURRENCY_CODES.contains(&symbol)
+        && !NOT_NAMES.contains(&symbol)
+}
+
+fn is_capitalized(word: &str) -> bool {
+    let mut chars = word.chars();
+    chars.next().is_some_and(char::is_uppercase) && chars.any(char::is_lowercase)
+}
+
+impl EntityExtractor for RuleBasedExtractor {
+    fn extract(&self, content: &str) -> Vec<Entity> {
+        let mut entities = Vec::new();
+        let mut push = |kind: EntityKind, value: String| {
+            let entity = Entity { kind, value };
+            if !entities.contains(&entity) {
+                entities.push(entity);
+            }
+        };
+
+        // Words with surrounding punctuation trimmed, and whether a sentence ended after each
+        let raw: Vec<&str> = content.split_whitespace().collect();
+        let words: Vec<&str> = raw
+            .iter()
+            .map(|word| word.trim_matches(|c: char| !c.is_alphanumeric() && !CURRENCY_SYMBOLS.contains(&c) && c != '%' && c != '\''))
+            .collect();
+        let ends_sentence: Vec<bool> = raw.iter().map(|word| word.ends_with(['.', '!', '?', ':'])).collect();
+
+        fn flush_name(name: &mut Vec<&str>, push: &mut dyn FnMut(EntityKind, String)) {
+            if !name.is_empty() {
+                push(EntityKind::Name, name.join(" "));
+                name.clear();
+            }
+        }
+        let mut name: Vec<&str> = Vec::new();
+        for (i, word) in words.iter().enumerate() {
+            let next = words.get(i + 1).copied().unwrap_or_default();
+            let sentence_start = i == 0 || ends_sentence[i - 1];
+
+            if is_ticker(word) {
+                push(EntityKind::Ticker, word.trim_start_matches('$').to_string());
+            } else if is_iso_date(word) {
+                push(EntityKind::Date, word.to_string());
+            } else if is_month(word) && next.trim_end_matches(|c: char| c.is_alphabetic()).parse::<u32>().is_ok_and(|day| (1..=31).contains(&day)) {
+                push(EntityKind::Date, format!("{} {}", word, next));
+            } else if (word.starts_with(CURRENCY_SYMBOLS) && is_number(word.trim_start_matches(CURRENCY_SYMBOLS)))
+                || (word.ends_with('%') && is_number(word.trim_end_matches('%')))
+            {
+                push(EntityKind::Amount, word.to_string());
+            } else if is_number(word) && CURRENCY_CODES.contains(&next) {
+                push(EntityKind::Amount, format!("{} {}", word, next));
+            }
+
+            let name_word = is_capitalized(word)
+                && !is_month(word)
+                && !WEEKDAYS.contains(&word.to_lowercase().as_str())
+                && !NOT_NAMES.contains(word)
+                // A lone capital at the start of a sentence is just grammar
+                && (!sentence_start || !name.is_empty() || is_capitalized(next));
+            if name_word {
+                name.push(word);
+                if ends_sentence[i] {
+                    flush_name(&mut name, &mut push);
+                }
+            } else {
+                flush_name(&mut name, &mut push);
+            }
+        }
+        flush_name(&mut name, &mut push);
+
+        let is_stop_word = self.stop_words.for_text(content);
+        let mut counts: HashMap<String, usize> = HashMap::new();
+        for word in &words {
+            let word = word.to_lowercase();
+            if word.len() > 3 && word.chars().all(char::is_alphabetic) && !is_stop_word(&word) {
+                *counts.entry(word).or_insert(0) += 1;
+            }
+        }
+        let mut keywords: Vec<(String, usize)> = counts.into_iter().collect();
+        keywords.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        for (keyword, _) in keywords.into_iter().take(MAX_KEYWORDS) {
+            push(EntityKind::Keyword, keyword);
+        }
+        entities
+    }
+}
+
+/// Write `entities` into `memory`'s metadata, skipping keys it already has
+pub fn annotate(memory: &mut MemoryItem, entities: &[Entity]) {
+    let mut by_key: Vec<(&str, Vec<&str>)> = Vec::new();
+    for entity in entities {
+        let key = entity.kind.metadata_key();
+        match by_key.iter_mut().find(|(existing, _)| *existing == key) {
+            Some((_, values)) => values.push(&entity.value),
+            None => by_key.push((key, vec![&entity.value])),
+        }
+    }
+    for (key, values) in by_key {
+        memory.metadata.entry(key.to_string()).or_insert_with(|| values.join(","));
+    }
+}
+
+/// Values stored under a comma-separated entity key
+pub fn entity_values<'a>(memory: &'a MemoryItem, key: &str) -> impl Iterator<Item = &'a str> {
+    memory.metadata.get(key).into_iter().flat_map(|values| values.split(',')).map(str::trim).filter(|value| !value.is_empty())
+}
+
+impl MindCache {
+    /// Replace the extractor used on save and by `extract_entities`
+    pub fn set_entity_extractor<E: EntityExtractor + 'static>(&mut self, extractor: E) {
+        self.entity_extractor = std::sync::Arc::new(extractor);
+    }
+
+    /// Entities the extractor finds in `content`
+    pub fn entities_in(&self, content: &str) -> Vec<Entity> {
+        self.entity_extractor.extract(content)
+    }
+
+    /// Run the extractor on a stored memory and add what it finds to its metadata
+    pub fn extract_entities(&mut self, memory_id: &str) -> Result<MemoryItem, Box<dyn std::error::Error>> {
+        let Some(mut memory) = self.storage.get_memory(memory_id)? else {
+            return Err(format!("memory {} not found", memory_id).into());
+        };
+        let entities = self.entity_extractor.extract(&memory.content);
+        annotate(&mut memory, &entities);
+        self.update_memory(memory_id, MemoryUpdate { metadata: Some(memory.metadata), ..Default::default() })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{MetadataCondition, QueryFilter};
+    use crate::MindCacheConfig;
+    use crate::test_support::temp_cache_with;
+
+    fn values(entities: &[Entity], kind: EntityKind) -> Vec<&str> {
+        entities.iter().filter(|entity| entity.kind == kind).map(|entity| entity.value.as_str()).collect()
+    }
+
+    #[test]
+    fn test_rule_based_extraction() {
+        let entities = RuleBasedExtractor::default().extract(
+            "Bought AAPL at $175.20 on 2024-03-15. Sarah Chen thinks $TSLA will drop 15% before June 30, so I sold 200 EUR of it.",
+        );
+        assert_eq!(values(&entities, EntityKind::Ticker), ["AAPL", "TSLA"]);
+        assert_eq!(values(&entities, EntityKind::Date), ["2024-03-15", "June 30"]);
+        assert_eq!(values(&entities, EntityKind::Amount), ["$175.20", "15%", "200 EUR"]);
+        assert_eq!(values(&entities, EntityKind::Name), ["Sarah Chen"]);
+        assert!(values(&entities, EntityKind::Keyword).contains(&"bought"));
+    }
+
+    #[test]
+    fn test_extraction_on_save_and_on_demand() {
+        let (mut cache, _temp_dir) = temp_cache_with(MindCacheConfig {
+            entity_extraction_enabled: true,
+            ..Default::default()
+        });
+
+        let given = HashMap::from([(TICKERS_KEY.to_string(), "GOLD".to_string())]);
+        let kept = cache.save("alice", "s1", "Sold NVDA and MSFT today", Some(given)).unwrap();
+        cache.save("alice", "s1", "Bought NVDA on the dip", None).unwrap();
+        assert_eq!(cache.get_memory(&kept).unwrap().unwrap().metadata[TICKERS_KEY], "GOLD");
+
+        let nvda = cache.recall_advanced(QueryFilter {
+            user_id: Some("alice".to_string()),
+            metadata_filters: HashMap::from([(TICKERS_KEY.to_string(), MetadataCondition::Contains("NVDA".to_string()))]),
+            ..Default::default()
+        }).unwrap();
+        assert_eq!(nvda.len(), 1);
+
+        cache.update_config(MindCacheConfig { entity_extraction_enabled: false, ..cache.config().clone() }).unwrap();
+        cache.set_entity_extractor(|content: &str| {
+            vec![Entity { kind: EntityKind::Name, value: content.split_whitespace().last().unwrap_or_default().to_string() }]
+        });
+        let later = cache.save("alice", "s1", "Lunch with Priya", None).unwrap();
+        assert!(!cache.get_memory(&later).unwrap().unwrap().metadata.contains_key(NAMES_KEY));
+        let memory = cache.extract_entities(&later).unwrap();
+        assert_eq!(entity_values(&memory, NAMES_KEY).collect::<Vec<_>>(), ["Priya"]);
+    }
+}
@@ -0,0 +1,345 @@
+//! Facts: (subject, predicate, object) triples drawn from memories
+//!
+//! Free-text memories answer "what did we talk about", but not "what do we
+//! know about AAPL". Facts store what memories say as triples, each with
+//! the ids of the memories it came from. They are saved explicitly with
+//! `MindCache::save_fact`, or parsed out of a stored memory by
+//! `MindCache::extract_facts` with a [`FactExtractor`]; the default
+//! [`RuleBasedFactExtractor`] looks for sentences like "AAPL is a long-term
+//! hold" or "Alice lives in Austin". `MindCache::query_facts` lists what a
+//! user's facts say about a subject.
+//!
+//! Facts live in `facts.json` next to the memory log, keyed by id. Saving a
+//! triple the user already has adds its sources to the existing fact.
+
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use crate::backend::StorageBackend;
+use crate::clock::SharedClock;
+use crate::MindCache;
+
+pub const FACTS_BLOB: &str = "facts.json";
+
+/// Words a subject or object may have at most, so whole sentences aren't mistaken for either
+const MAX_TERM_WORDS: usize = 6;
+
+/// A stored triple
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Fact {
+    pub id: String,
+    pub user_id: String,
+    pub subject: String,
+    pub predicate: String,
+    pub object: String,
+    /// Memories the fact was taken from; empty if none were given
+    pub source_ids: Vec<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl Fact {
+    fn same_triple(&self, user_id: &str, triple: &Triple) -> bool {
+        self.user_id == user_id
+            && self.subject.eq_ignore_ascii_case(&triple.subject)
+            && self.predicate.eq_ignore_ascii_case(&triple.predicate)
+            && self.object.eq_ignore_ascii_case(&triple.object)
+    }
+}
+
+/// A (subject, predicate, object) statement, not yet stored
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Triple {
+    pub subject: String,
+    pub predicate: String,
+    pub object: String,
+}
+
+impl Triple {
+    pub fn new(subject: &str, predicate: &str, object: &str) -> Self {
+        Triple {
+            subject: subject.trim().to_string(),
+            predicate: predicate.trim().to_lowercase(),
+            object: object.trim().to_string(),
+        }
+    }
+}
+
+/// Finds triples in memory content
+pub trait FactExtractor: Send + Sync {
+    fn extract(&self, content: &str) -> Vec<Triple>;
+}
+
+impl<F> FactExtractor for F
+where
+    F: Fn(&str) -> Vec<Triple> + Send + Sync,
+{
+    fn extract(&self, content: &str) -> Vec<Triple> {
+        self(content)
+    }
+}
+
+/// Verb phrases the default extractor splits sentences on, with the predicate each becomes
+const PREDICATES: &[(&str, &str)] = &[
+    ("lives in", "lives_in"),
+    ("works at", "works_at"),
+    ("works for", "works_at"),
+    ("moved to", "lives_in"),
+    ("is allergic to", "allergic_to"),
+    ("is", "is"),
+    ("are", "is"),
+    ("was", "was"),
+    ("has", "has"),
+    ("owns", "owns"),
+    ("prefers", "prefers"),
+    ("likes", "likes"),
+    ("bought", "bought"),
+    ("sold", "sold"),
+];
+
+/// Default extractor: one triple per sentence of the form `<subject> <verb> <object>`
+///
+/// Each sentence is split at its first verb phrase from [`PREDICATES`],
+/// longer phrases winning over the plain verbs they start with. Sentences
+/// whose subject or object runs to more than a few words are skipped.
+#[derive(Debug, Clone, Default)]
+pub struct RuleBasedFactExtractor;
+
+impl RuleBasedFactExtractor {
+    fn split(sentence: &str) -> Option<Triple> {
+        let words: Vec<&str> = sentence.split_whitespace().collect();
+        let lower: Vec<String> = words.iter().map(|word| word.to_lowercase()).collect();
+        let (at, phrase_len, predicate) = (1..words.len()).find_map(|at| {
+            PREDICATES.iter().find_map(|(phrase, predicate)| {
+                let phrase: Vec<&str> = phrase.split(' ').collect();
+                let matches = lower.len() >= at + phrase.len() && lower[at..at + phrase.len()].iter().zip(&phrase).all(|(word, part)| word == part);
+                matches.then_some((at, phrase.len(), *predicate))
+            })
+        })?;
+        let subject = &words[..at];
+        let object = &words[at + phrase_len..];
+        if object.is_empty() || subject.len() > MAX_TERM_WORDS || object.len() > MAX_TERM_WORDS {
+            return None;
+        }
+        let trim = |part: &[&str]| part.join(" ").trim_matches(|c: char| !c.is_alphanumeric() && c != '$').to_string();
+        let (subject, object) = (trim(subject), trim(object));
+        (!subject.is_empty() && !object.is_empty()).then(|| Triple::new(&subject, predicate, &object))
+    }
+}
+
+impl FactExtractor for RuleBasedFactExtractor {
+    fn extract(&self, content: &str) -> Vec<Triple> {
+        content
+            .split(['.', '!', '?', ';', '\n'])
+            .filter_map(Self::split)
+            .collect()
+    }
+}
+
+/// Persistent facts, keyed by id
+#[derive(Clone)]
+pub struct FactStore {
+    backend: Arc<dyn StorageBackend>,
+    clock: SharedClock,
+    facts: Arc<RwLock<HashMap<String, Fact>>>,
+}
+
+impl FactStore {
+    pub fn load(backend: Arc<dyn StorageBackend>, clock: SharedClock) -> Result<Self, Box<dyn std::error::Error>> {
+        let facts = Self::read_facts(backend.as_ref())?;
+        Ok(FactStore { backend, clock, facts: Arc::new(RwLock::new(facts)) })
+    }
+
+    /// Re-read the facts from disk, e.g. after a restore
+    pub fn reload(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let facts = Self::read_facts(self.backend.as_ref())?;
+        *self.facts.write().unwrap_or_else(|poisoned| poisoned.into_inner()) = facts;
+        Ok(())
+    }
+
+    fn read_facts(backend: &dyn StorageBackend) -> Result<HashMap<String, Fact>, Box<dyn std::error::Error>> {
+        let mut facts = HashMap::new();
+        if let Some(data) = backend.read_blob(FACTS_BLOB)? {
+            for fact in serde_json::from_slice::<Vec<Fact>>(&data)? {
+                facts.insert(fact.id.clone(), fact);
+            }
+        }
+        Ok(facts)
+    }
+
+    /// Store a triple for a user, or add `source_ids` to the fact already holding it
+    pub fn add(&self, user_id: &str, triple: &Triple, source_ids: &[String]) -> Result<Fact, Box<dyn std::error::Error>> {
+        let mut stored = None;
+        self.update(|facts| {
+            let existing = facts.values().find(|fact| fact.same_triple(user_id, triple)).map(|fact| fact.id.clone());
+            let id = existing.unwrap_or_else(|| Uuid::new_v4().to_string());
+            let fact = facts.entry(id.clone()).or_insert_with(|| Fact {
+                id,
+                user_id: user_id.to_string(),
+                subject: triple.subject.clone(),
+                predicate: triple.predicate.clone(),
+                object: triple.object.clone(),
+                source_ids: Vec::new(),
+                created_at: self.clock.now(),
+            });
+            for source_id in source_ids {
+                if !fact.source_ids.contains(source_id) {
+                    fact.source_ids.push(source_id.clone());
+                }
+            }
+            stored = Some(fact.clone());
+        })?;
+        Ok(stored.expect("update ran the edit"))
+    }
+
+    pub fn remove(&self, fact_id: &str) -> Result<Option<Fact>, Box<dyn std::error::Error>> {
+        let mut removed = None;
+        self.update(|facts| removed = facts.remove(fact_id))?;
+        Ok(removed)
+    }
+
+    /// Drop all of a user's facts; returns how many there were
+    pub fn remove_user(&self, user_id: &str) -> Result<usize, Box<dyn std::error::Error>> {
+        let mut removed = 0;
+        self.update(|facts| {
+            let before = facts.len();
+            facts.retain(|_, fact| fact.user_id != user_id);
+            removed = before - facts.len();
+        })?;
+        Ok(removed)
+    }
+
+    /// A user's facts about `subject` (case-insensitive), oldest first
+    pub fn about(&self, user_id: &str, subject: &str) -> Vec<Fact> {
+        let facts = self.facts.read().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let mut found: Vec<Fact> = facts
+            .values()
+            .filter(|fact| fact.user_id == user_id && fact.subject.eq_ignore_ascii_case(subject.trim()))
+            .cloned()
+            .collect();
+        found.sort_by(|a, b| a.created_at.cmp(&b.created_at).then_with(|| a.id.cmp(&b.id)));
+        found
+    }
+
+    /// Apply `edit` and persist, leaving the facts as they were if that fails
+    fn update<F>(&self, edit: F) -> Result<(), Box<dyn std::error::Error>>
+    where
+        F: FnOnce(&mut HashMap<String, Fact>),
+    {
+        let mut facts = self.facts.write().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let previous = facts.clone();
+        edit(&mut facts);
+        let mut sorted: Vec<&Fact> = facts.values().collect();
+        sorted.sort_by(|a, b| a.created_at.cmp(&b.created_at).then_with(|| a.id.cmp(&b.id)));
+        let persisted = serde_json::to_vec(&sorted)
+            .map_err(Box::<dyn std::error::Error>::from)
+            .and_then(|data| Ok(self.backend.write_blob(FACTS_BLOB, &data)?));
+        if let Err(e) = persisted {
+            *facts = previous;
+            return Err(e);
+        }
+        Ok(())
+    }
+}
+
+impl MindCache {
+    /// Store a fact for a user, citing the memories it comes from
+    ///
+    /// Every source must be one of the user's stored memories. Returns the
+    /// fact, which is the existing one if the user already has the triple.
+    pub fn save_fact(&mut self, user_id: &str, triple: Triple, source_ids: &[&str]) -> Result<Fact, Box<dyn std::error::Error>> {
+        if triple.subject.is_empty() || triple.predicate.is_empty() || triple.object.is_empty() {
+            return Err("subject, predicate and object must not be empty".into());
+        }
+        for id in source_ids {
+            match self.storage.get_memory(id)? {
+                Some(memory) if memory.user_id == user_id => {}
+                _ => return Err(format!("memory {} not found for user {}", id, user_id).into()),
+            }
+        }
+        let source_ids: Vec<String> = source_ids.iter().map(|id| id.to_string()).collect();
+        self.storage.facts().add(user_id, &triple, &source_ids)
+    }
+
+    /// Parse facts out of a stored memory with the fact extractor and store them
+    ///
+    /// Returns the facts found, each citing the memory.
+    pub fn extract_facts(&mut self, memory_id: &str) -> Result<Vec<Fact>, Box<dyn std::error::Error>> {
+        let memory = self.storage.get_memory(memory_id)?.ok_or_else(|| format!("memory {} not found", memory_id))?;
+        let source = [memory.id.clone()];
+        self.fact_extractor
+            .extract(&memory.content)
+            .iter()
+            .map(|triple| self.storage.facts().add(&memory.user_id, triple, &source))
+            .collect()
+    }
+
+    /// Replace the extractor used by `extract_facts`
+    pub fn set_fact_extractor<E: FactExtractor + 'static>(&mut self, extractor: E) {
+        self.fact_extractor = Arc::new(extractor);
+    }
+
+    /// What a user's facts say about `subject`, oldest first
+    pub fn query_facts(&self, user_id: &str, subject: &str) -> Vec<Fact> {
+        self.storage.facts().about(user_id, subject)
+    }
+
+    /// Delete a fact; false if there was none with this id
+    pub fn delete_fact(&mut self, fact_id: &str) -> Result<bool, Box<dyn std::error::Error>> {
+        Ok(self.storage.facts().remove(fact_id)?.is_some())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::test_config;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_rule_based_extraction() {
+        let triples = RuleBasedFactExtractor.extract("AAPL is a long-term hold. Alice lives in Austin! ok then; Bob works for Acme Corp.");
+        assert_eq!(triples, vec![
+            Triple::new("AAPL", "is", "a long-term hold"),
+            Triple::new("Alice", "lives_in", "Austin"),
+            Triple::new("Bob", "works_at", "Acme Corp"),
+        ]);
+        assert_eq!(RuleBasedFactExtractor.extract("Alice has a dog that is cute"), vec![Triple::new("Alice", "has", "a dog that is cute")]);
+        assert!(RuleBasedFactExtractor.extract("Is it raining").is_empty());
+        assert!(RuleBasedFactExtractor.extract("The long meeting about the quarterly numbers and the budget was over").is_empty());
+    }
+
+    #[test]
+    fn test_facts_cite_memories_and_persist() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = test_config(&temp_dir);
+        let mut cache = MindCache::with_config(config.clone()).unwrap();
+        let first = cache.save("alice", "s1", "AAPL is a long-term hold. Bought AAPL at 175", None).unwrap();
+        let second = cache.save("alice", "s2", "Still think AAPL is a long-term hold", None).unwrap();
+        let bob = cache.save("bob", "s1", "AAPL is overvalued", None).unwrap();
+
+        let extracted = cache.extract_facts(&first).unwrap();
+        assert_eq!(extracted.len(), 1);
+        let explicit = cache.save_fact("alice", Triple::new("aapl", "IS", "A long-term hold"), &[&second]).unwrap();
+        assert_eq!(explicit.id, extracted[0].id);
+        assert_eq!(explicit.source_ids, [first.clone(), second.clone()]);
+        cache.save_fact("alice", Triple::new("AAPL", "target_price", "$210"), &[]).unwrap();
+        assert!(cache.save_fact("alice", Triple::new("AAPL", "is", "cheap"), &[&bob]).is_err());
+        assert!(cache.save_fact("alice", Triple::new("AAPL", "", "cheap"), &[]).is_err());
+        cache.extract_facts(&bob).unwrap();
+
+        drop(cache);
+        let mut cache = MindCache::with_config(config).unwrap();
+        let facts = cache.query_facts("alice", "Aapl");
+        let objects: Vec<&str> = facts.iter().map(|fact| fact.object.as_str()).collect();
+        assert_eq!(objects, ["a long-term hold", "$210"]);
+        assert_eq!(cache.query_facts("bob", "AAPL")[0].object, "overvalued");
+
+        assert!(cache.delete_fact(&facts[1].id).unwrap());
+        assert!(!cache.delete_fact(&facts[1].id).unwrap());
+        cache.delete_user("alice").unwrap();
+        assert!(cache.query_facts("alice", "AAPL").is_empty());
+        assert_eq!(cache.query_facts("bob", "AAPL").len(), 1);
+    }
+}
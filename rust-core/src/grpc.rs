@@ -0,0 +1,534 @@
+//! gRPC interface (tonic), defined by `proto/mindcache.proto`
+//!
+//! The message types below mirror the proto file field-for-field; the service
+//! stubs (`mind_cache_server`, `mind_cache_client`) are generated by `build.rs`.
+//! When changing one, change the other.
+//!
+//! With `api_keys` configured, calls need a key in `authorization: Bearer`
+//! or `x-api-key` metadata, checked as by the HTTP API (see [`crate::auth`]);
+//! a missing or unknown key is `UNAUTHENTICATED` and a key that does not
+//! allow the call `PERMISSION_DENIED`.
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+
+use chrono::{DateTime, Utc};
+use tokio::sync::broadcast;
+use tokio_stream::wrappers::BroadcastStream;
+use tokio_stream::{Stream, StreamExt};
+use tonic::{Request, Response, Status};
+
+use crate::{AuthError, ContentTooLarge, MindCache, MemoryItem, MetadataCondition, QueryFilter, QuotaExceeded, RateLimited, Scope, UnknownSession};
+
+#[allow(clippy::all)]
+mod generated {
+    include!(concat!(env!("OUT_DIR"), "/mindcache.v1.MindCache.rs"));
+}
+pub use generated::{mind_cache_client, mind_cache_server};
+
+use mind_cache_server::{MindCache as MindCacheRpc, MindCacheServer};
+
+/// Saved memories buffered for slow `Watch` subscribers before they start missing events
+const WATCH_BUFFER: usize = 256;
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Memory {
+    #[prost(string, tag = "1")]
+    pub id: String,
+    #[prost(string, tag = "2")]
+    pub user_id: String,
+    #[prost(string, tag = "3")]
+    pub session_id: String,
+    #[prost(string, tag = "4")]
+    pub content: String,
+    #[prost(map = "string, string", tag = "5")]
+    pub metadata: HashMap<String, String>,
+    #[prost(int64, tag = "6")]
+    pub timestamp_ms: i64,
+    #[prost(uint32, optional, tag = "7")]
+    pub ttl_hours: Option<u32>,
+    #[prost(float, tag = "8")]
+    pub importance: f32,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct SaveRequest {
+    #[prost(string, tag = "1")]
+    pub user_id: String,
+    #[prost(string, tag = "2")]
+    pub session_id: String,
+    #[prost(string, tag = "3")]
+    pub content: String,
+    #[prost(map = "string, string", tag = "4")]
+    pub metadata: HashMap<String, String>,
+    #[prost(float, optional, tag = "5")]
+    pub importance: Option<f32>,
+    #[prost(uint32, optional, tag = "6")]
+    pub ttl_hours: Option<u32>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct SaveResponse {
+    #[prost(string, tag = "1")]
+    pub id: String,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct RecallRequest {
+    #[prost(string, optional, tag = "1")]
+    pub user_id: Option<String>,
+    #[prost(string, optional, tag = "2")]
+    pub session_id: Option<String>,
+    #[prost(string, repeated, tag = "3")]
+    pub keywords: Vec<String>,
+    #[prost(int64, optional, tag = "4")]
+    pub date_from_ms: Option<i64>,
+    #[prost(int64, optional, tag = "5")]
+    pub date_to_ms: Option<i64>,
+    #[prost(uint32, optional, tag = "6")]
+    pub limit: Option<u32>,
+    #[prost(float, optional, tag = "7")]
+    pub min_importance: Option<f32>,
+    /// Metadata values the memories must have
+    #[prost(map = "string, string", tag = "8")]
+    pub metadata: HashMap<String, String>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct RecallResponse {
+    #[prost(message, repeated, tag = "1")]
+    pub memories: Vec<Memory>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct SummarizeRequest {
+    #[prost(string, tag = "1")]
+    pub session_id: String,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct SessionSummary {
+    #[prost(string, tag = "1")]
+    pub session_id: String,
+    #[prost(string, tag = "2")]
+    pub user_id: String,
+    #[prost(string, tag = "3")]
+    pub summary_text: String,
+    #[prost(string, repeated, tag = "4")]
+    pub key_topics: Vec<String>,
+    #[prost(uint64, tag = "5")]
+    pub memory_count: u64,
+    #[prost(int64, tag = "6")]
+    pub start_ms: i64,
+    #[prost(int64, tag = "7")]
+    pub end_ms: i64,
+    #[prost(float, tag = "8")]
+    pub importance_score: f32,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct DecayRequest {}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct DecayStats {
+    #[prost(uint64, tag = "1")]
+    pub memories_expired: u64,
+    #[prost(uint64, tag = "2")]
+    pub memories_compressed: u64,
+    #[prost(uint64, tag = "3")]
+    pub sessions_summarized: u64,
+    #[prost(uint64, tag = "4")]
+    pub total_memories_before: u64,
+    #[prost(uint64, tag = "5")]
+    pub total_memories_after: u64,
+    #[prost(uint64, tag = "6")]
+    pub storage_saved_bytes: u64,
+    #[prost(int64, tag = "7")]
+    pub last_decay_run_ms: i64,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct WatchRequest {
+    #[prost(string, tag = "1")]
+    pub user_id: String,
+    #[prost(string, optional, tag = "2")]
+    pub session_id: Option<String>,
+}
+
+impl From<MemoryItem> for Memory {
+    fn from(memory: MemoryItem) -> Self {
+        Memory {
+            id: memory.id,
+            user_id: memory.user_id,
+            session_id: memory.session_id,
+            content: memory.content,
+            metadata: memory.metadata,
+            timestamp_ms: memory.timestamp.timestamp_millis(),
+            ttl_hours: memory.ttl_hours,
+            importance: memory.importance,
+        }
+    }
+}
+
+impl From<crate::SessionSummary> for SessionSummary {
+    fn from(summary: crate::SessionSummary) -> Self {
+        SessionSummary {
+            session_id: summary.session_id,
+            user_id: summary.user_id,
+            summary_text: summary.summary_text,
+            key_topics: summary.key_topics,
+            memory_count: summary.memory_count as u64,
+            start_ms: summary.date_range.0.timestamp_millis(),
+            end_ms: summary.date_range.1.timestamp_millis(),
+            importance_score: summary.importance_score,
+        }
+    }
+}
+
+impl From<crate::DecayStats> for DecayStats {
+    fn from(stats: crate::DecayStats) -> Self {
+        DecayStats {
+            memories_expired: stats.memories_expired as u64,
+            memories_compressed: stats.memories_compressed as u64,
+            sessions_summarized: stats.sessions_summarized as u64,
+            total_memories_before: stats.total_memories_before as u64,
+            total_memories_after: stats.total_memories_after as u64,
+            storage_saved_bytes: stats.storage_saved_bytes as u64,
+            last_decay_run_ms: stats.last_decay_run.timestamp_millis(),
+        }
+    }
+}
+
+impl TryFrom<RecallRequest> for QueryFilter {
+    type Error = Status;
+
+    fn try_from(request: RecallRequest) -> Result<Self, Status> {
+        let timestamp = |ms: Option<i64>, field: &str| -> Result<Option<DateTime<Utc>>, Status> {
+            ms.map(|ms| {
+                DateTime::from_timestamp_millis(ms)
+                    .ok_or_else(|| Status::invalid_argument(format!("{} is out of range", field)))
+            })
+            .transpose()
+        };
+
+        Ok(QueryFilter {
+            user_id: request.user_id,
+            session_id: request.session_id,
+            keywords: (!request.keywords.is_empty()).then_some(request.keywords),
+            date_from: timestamp(request.date_from_ms, "date_from_ms")?,
+            date_to: timestamp(request.date_to_ms, "date_to_ms")?,
+            limit: request.limit.map(|limit| limit as usize),
+            min_importance: request.min_importance,
+            metadata_filters: request.metadata
+                .into_iter()
+                .map(|(key, value)| (key, MetadataCondition::Equals(value)))
+                .collect(),
+            ..Default::default()
+        })
+    }
+}
+
+/// gRPC service backed by a shared `MindCache`
+#[derive(Clone)]
+pub struct MindCacheService {
+    cache: Arc<Mutex<MindCache>>,
+    saved: broadcast::Sender<Memory>,
+}
+
+impl MindCacheService {
+    pub fn new(cache: Arc<Mutex<MindCache>>) -> Self {
+        let (saved, _) = broadcast::channel(WATCH_BUFFER);
+        MindCacheService { cache, saved }
+    }
+
+    /// Wrap the service for `tonic::transport::Server::add_service`
+    pub fn into_server(self) -> MindCacheServer<Self> {
+        MindCacheServer::new(self)
+    }
+
+    /// Run a cache operation on the blocking pool, since storage does synchronous IO
+    async fn with_cache<T, F>(&self, operation: F) -> Result<T, Status>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut MindCache) -> Result<T, Status> + Send + 'static,
+    {
+        let cache = Arc::clone(&self.cache);
+        tokio::task::spawn_blocking(move || {
+            let mut cache = cache.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+            operation(&mut cache)
+        })
+        .await
+        .map_err(|e| Status::internal(format!("request task failed: {}", e)))?
+    }
+}
+
+fn internal(error: Box<dyn std::error::Error>) -> Status {
+    if let Some(auth) = error.downcast_ref::<AuthError>() {
+        return unauthorized(auth.clone());
+    }
+    if error.is::<ContentTooLarge>() {
+        return Status::invalid_argument(error.to_string());
+    }
+    if error.is::<UnknownSession>() {
+        return Status::not_found(error.to_string());
+    }
+    if error.is::<QuotaExceeded>() || error.is::<RateLimited>() {
+        return Status::resource_exhausted(error.to_string());
+    }
+    Status::internal(error.to_string())
+}
+
+fn unauthorized(error: AuthError) -> Status {
+    match error {
+        AuthError::MissingKey | AuthError::UnknownKey => Status::unauthenticated(error.to_string()),
+        AuthError::Forbidden(_) => Status::permission_denied(error.to_string()),
+    }
+}
+
+/// The API key sent as `authorization: Bearer <key>` or in `x-api-key`
+fn api_key<T>(request: &Request<T>) -> Option<String> {
+    let metadata = |name| request.metadata().get(name).and_then(|value| value.to_str().ok());
+    metadata("authorization")
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .or_else(|| metadata("x-api-key"))
+        .map(str::to_string)
+}
+
+type WatchStream = Pin<Box<dyn Stream<Item = Result<Memory, Status>> + Send + 'static>>;
+
+#[tonic::async_trait]
+impl MindCacheRpc for MindCacheService {
+    async fn save(&self, request: Request<SaveRequest>) -> Result<Response<SaveResponse>, Status> {
+        let key = api_key(&request);
+        let request = request.into_inner();
+        if request.content.trim().is_empty() {
+            return Err(Status::invalid_argument("content must not be empty"));
+        }
+        if let Some(importance) = request.importance {
+            if !(0.0..=1.0).contains(&importance) {
+                return Err(Status::invalid_argument("importance must be between 0.0 and 1.0"));
+            }
+        }
+
+        let memory = self.with_cache(move |cache| {
+            let importance = request.importance.unwrap_or_else(|| {
+                cache.default_importance_for(&request.user_id, &request.session_id, &request.content)
+            });
+            let ttl_hours = request.ttl_hours.or(cache.config().default_memory_ttl_hours);
+            let id = cache
+                .with_key(key.as_deref())
+                .map_err(unauthorized)?
+                .save_with_options(
+                    &request.user_id,
+                    &request.session_id,
+                    &request.content,
+                    Some(request.metadata.clone()),
+                    importance,
+                    ttl_hours,
+                )
+                .map_err(internal)?;
+
+            Ok(Memory {
+                id,
+                user_id: request.user_id,
+                session_id: request.session_id,
+                content: request.content,
+                metadata: request.metadata,
+                timestamp_ms: Utc::now().timestamp_millis(),
+                ttl_hours,
+                importance,
+            })
+        })
+        .await?;
+
+        let id = memory.id.clone();
+        // No subscribers is not an error
+        let _ = self.saved.send(memory);
+        Ok(Response::new(SaveResponse { id }))
+    }
+
+    async fn recall(&self, request: Request<RecallRequest>) -> Result<Response<RecallResponse>, Status> {
+        let key = api_key(&request);
+        let filter = QueryFilter::try_from(request.into_inner())?;
+        let memories = self
+            .with_cache(move |cache| {
+                cache.with_key(key.as_deref()).map_err(unauthorized)?.recall_advanced(filter).map_err(internal)
+            })
+            .await?;
+        Ok(Response::new(RecallResponse {
+            memories: memories.into_iter().map(Memory::from).collect(),
+        }))
+    }
+
+    async fn summarize(&self, request: Request<SummarizeRequest>) -> Result<Response<SessionSummary>, Status> {
+        let key = api_key(&request);
+        let session_id = request.into_inner().session_id;
+        if session_id.is_empty() {
+            return Err(Status::invalid_argument("session_id must not be empty"));
+        }
+        let summary = self
+            .with_cache(move |cache| {
+                cache
+                    .with_key(key.as_deref())
+                    .map_err(unauthorized)?
+                    .summarize_session(&session_id)
+                    .map_err(|e| if e.is::<AuthError>() { internal(e) } else { Status::not_found(e.to_string()) })
+            })
+            .await?;
+        Ok(Response::new(summary.into()))
+    }
+
+    async fn decay(&self, request: Request<DecayRequest>) -> Result<Response<DecayStats>, Status> {
+        let key = api_key(&request);
+        let stats = self
+            .with_cache(move |cache| {
+                cache.with_key(key.as_deref()).map_err(unauthorized)?.decay().map_err(internal)
+            })
+            .await?;
+        Ok(Response::new(stats.into()))
+    }
+
+    type WatchStream = WatchStream;
+
+    async fn watch(&self, request: Request<WatchRequest>) -> Result<Response<Self::WatchStream>, Status> {
+        let key = api_key(&request);
+        let request = request.into_inner();
+        if request.user_id.is_empty() {
+            return Err(Status::invalid_argument("user_id must not be empty"));
+        }
+        let user_id = request.user_id.clone();
+        self.with_cache(move |cache| {
+            cache
+                .with_key(key.as_deref())
+                .and_then(|scoped| scoped.access().check(Scope::Read, Some(&user_id)))
+                .map_err(unauthorized)
+        })
+        .await?;
+
+        let stream = BroadcastStream::new(self.saved.subscribe()).filter_map(move |event| match event {
+            Ok(memory) => {
+                let wanted = memory.user_id == request.user_id
+                    && request.session_id.as_ref().is_none_or(|session_id| *session_id == memory.session_id);
+                wanted.then_some(Ok(memory))
+            }
+            Err(e) => Some(Err(Status::data_loss(format!("watch stream fell behind: {}", e)))),
+        });
+        Ok(Response::new(Box::pin(stream)))
+    }
+}
+
+/// Serve the gRPC API on `addr` until `shutdown` completes
+pub async fn serve<F>(addr: SocketAddr, cache: Arc<Mutex<MindCache>>, shutdown: F) -> Result<(), tonic::transport::Error>
+where
+    F: std::future::Future<Output = ()>,
+{
+    tonic::transport::Server::builder()
+        .add_service(MindCacheService::new(cache).into_server())
+        .serve_with_shutdown(addr, shutdown)
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MindCacheConfig;
+    use crate::test_support::test_config;
+    use tempfile::TempDir;
+
+    fn service(temp_dir: &TempDir) -> MindCacheService {
+        let cache = MindCache::with_config(test_config(temp_dir)).unwrap();
+        MindCacheService::new(Arc::new(Mutex::new(cache)))
+    }
+
+    fn save_request(session_id: &str, content: &str) -> Request<SaveRequest> {
+        Request::new(SaveRequest {
+            user_id: "alice".to_string(),
+            session_id: session_id.to_string(),
+            content: content.to_string(),
+            importance: Some(0.8),
+            ..SaveRequest::default()
+        })
+    }
+
+    #[tokio::test]
+    async fn test_save_recall_summarize() {
+        let temp_dir = TempDir::new().unwrap();
+        let service = service(&temp_dir);
+
+        service.save(save_request("s1", "Bought gold futures")).await.unwrap();
+        service.save(save_request("s1", "Gold hit the target")).await.unwrap();
+
+        let recalled = service
+            .recall(Request::new(RecallRequest {
+                user_id: Some("alice".to_string()),
+                keywords: vec!["gold".to_string()],
+                ..RecallRequest::default()
+            }))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(recalled.memories.len(), 2);
+
+        let summary = service
+            .summarize(Request::new(SummarizeRequest { session_id: "s1".to_string() }))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(summary.memory_count, 2);
+
+        let error = service.save(save_request("s1", " ")).await.unwrap_err();
+        assert_eq!(error.code(), tonic::Code::InvalidArgument);
+    }
+
+    #[tokio::test]
+    async fn test_api_keys_are_enforced() {
+        let temp_dir = TempDir::new().unwrap();
+        let cache = MindCache::with_config(MindCacheConfig {
+            api_keys: vec![crate::ApiKey {
+                key: "alice-rw".to_string(),
+                name: "alice".to_string(),
+                users: vec!["alice".to_string()],
+                spaces: Vec::new(),
+                scopes: vec![Scope::Read, Scope::Write],
+            }],
+            ..test_config(&temp_dir)
+        })
+        .unwrap();
+        let service = MindCacheService::new(Arc::new(Mutex::new(cache)));
+        let with_key = |mut request: Request<SaveRequest>, key: &str| {
+            request.metadata_mut().insert("x-api-key", key.parse().unwrap());
+            request
+        };
+
+        let error = service.save(save_request("s1", "Bought gold")).await.unwrap_err();
+        assert_eq!(error.code(), tonic::Code::Unauthenticated);
+        service.save(with_key(save_request("s1", "Bought gold"), "alice-rw")).await.unwrap();
+
+        let mut decay = Request::new(DecayRequest::default());
+        decay.metadata_mut().insert("authorization", "Bearer alice-rw".parse().unwrap());
+        assert_eq!(service.decay(decay).await.unwrap_err().code(), tonic::Code::PermissionDenied);
+    }
+
+    #[tokio::test]
+    async fn test_watch_streams_saved_memories() {
+        let temp_dir = TempDir::new().unwrap();
+        let service = service(&temp_dir);
+
+        let mut stream = service
+            .watch(Request::new(WatchRequest {
+                user_id: "alice".to_string(),
+                session_id: Some("s2".to_string()),
+            }))
+            .await
+            .unwrap()
+            .into_inner();
+
+        service.save(save_request("s1", "other session")).await.unwrap();
+        service.save(save_request("s2", "watched session")).await.unwrap();
+
+        let memory = stream.next().await.unwrap().unwrap();
+        assert_eq!(memory.content, "watched session");
+    }
+}
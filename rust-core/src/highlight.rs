@@ -0,0 +1,171 @@
+//! Highlighted recall
+//!
+//! `MindCache::recall_highlighted` returns each recalled memory with where
+//! the filter's search words (`keywords` and the words of `query`) occur in
+//! its content, compared the way recall compares them under the filter's
+//! `match_mode`: as substrings, or whole words for prefix and fuzzy matches.
+//! UIs can highlight the spans, and agents can quote the sentences holding
+//! them instead of whole memories.
+
+use serde::{Deserialize, Serialize};
+use unicode_segmentation::UnicodeSegmentation;
+use crate::matching::MatchMode;
+use crate::planner;
+use crate::query;
+use crate::storage::{MemoryItem, QueryFilter};
+use crate::MindCache;
+
+/// Where a search word occurs in a memory's content, as byte offsets
+/// (`&content[start..end]`), always on character boundaries
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MatchSpan {
+    pub start: usize,
+    pub end: usize,
+}
+
+/// A recalled memory with where it matched
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HighlightedMemory {
+    pub memory: MemoryItem,
+    /// Matches in content order; overlapping matches are merged
+    pub spans: Vec<MatchSpan>,
+    /// The sentences of the content holding a match, trimmed, in order
+    pub snippets: Vec<String>,
+}
+
+/// The words and phrases a filter searches for, lowercased
+fn search_words(filter: &QueryFilter) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let mut words: Vec<String> = filter.keywords.iter().flatten().map(|keyword| keyword.to_lowercase()).collect();
+    if let Some(expression) = &filter.query {
+        words.extend(query::parse(expression)?.positive_terms().into_iter().map(str::to_lowercase));
+    }
+    words.retain(|word| !word.is_empty());
+    words.sort();
+    words.dedup();
+    Ok(words)
+}
+
+/// Where `words` occur in `content`, merged and in order
+pub fn find_spans(content: &str, words: &[String], mode: MatchMode) -> Vec<MatchSpan> {
+    // Lowercasing can change a character's length, so keep the span of the
+    // original character behind every byte of the lowercased text
+    let mut lowered = String::with_capacity(content.len());
+    let mut origins = Vec::with_capacity(content.len());
+    for (start, c) in content.char_indices() {
+        let before = lowered.len();
+        lowered.extend(c.to_lowercase());
+        origins.extend(std::iter::repeat_n((start, start + c.len_utf8()), lowered.len() - before));
+    }
+    let original = |start: usize, end: usize| MatchSpan { start: origins[start].0, end: origins[end - 1].1 };
+
+    let mut spans = Vec::new();
+    for word in words {
+        if mode == MatchMode::Exact || !planner::is_indexable_keyword(word) {
+            spans.extend(lowered.match_indices(word.as_str()).map(|(start, found)| original(start, start + found.len())));
+            continue;
+        }
+        let mut word_start = None;
+        for (position, c) in lowered.char_indices().chain(std::iter::once((lowered.len(), ' '))) {
+            match (c.is_alphanumeric(), word_start) {
+                (true, None) => word_start = Some(position),
+                (false, Some(start)) => {
+                    if mode.term_matches(&lowered[start..position], word) {
+                        spans.push(original(start, position));
+                    }
+                    word_start = None;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    spans.sort_by_key(|span| (span.start, span.end));
+    let mut merged: Vec<MatchSpan> = Vec::with_capacity(spans.len());
+    for span in spans {
+        match merged.last_mut() {
+            Some(last) if span.start < last.end => last.end = last.end.max(span.end),
+            _ => merged.push(span),
+        }
+    }
+    merged
+}
+
+/// The trimmed sentences of `content` holding any of `spans`
+pub fn snippets(content: &str, spans: &[MatchSpan]) -> Vec<String> {
+    content
+        .split_sentence_bound_indices()
+        .filter(|(start, sentence)| {
+            let end = start + sentence.len();
+            spans.iter().any(|span| span.start < end && span.end > *start)
+        })
+        .map(|(_, sentence)| sentence.trim().to_string())
+        .filter(|sentence| !sentence.is_empty())
+        .collect()
+}
+
+impl MindCache {
+    /// Recall memories with where the filter's keywords and query matched them
+    ///
+    /// Memories come back as from `recall_advanced`. Filters without search
+    /// words, or whose `fields` leave out `content`, give no spans.
+    pub fn recall_highlighted(&self, filter: QueryFilter) -> Result<Vec<HighlightedMemory>, Box<dyn std::error::Error>> {
+        let words = search_words(&filter)?;
+        let mode = filter.match_mode;
+        Ok(self
+            .recall_advanced(filter)?
+            .into_iter()
+            .map(|memory| {
+                let spans = find_spans(&memory.content, &words, mode);
+                let snippets = snippets(&memory.content, &spans);
+                HighlightedMemory { memory, spans, snippets }
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::temp_cache;
+
+    #[test]
+    fn test_spans_and_snippets_follow_the_match_mode() {
+        let content = "Bought GOLD at the open. Weather was nice. Gold traders were trading İstanbul gold.";
+        let words = vec!["gold".to_string()];
+        let spans = find_spans(content, &words, MatchMode::Exact);
+        let found: Vec<&str> = spans.iter().map(|span| &content[span.start..span.end]).collect();
+        assert_eq!(found, ["GOLD", "Gold", "gold"]);
+        assert_eq!(snippets(content, &spans), ["Bought GOLD at the open.", "Gold traders were trading İstanbul gold."]);
+
+        // Prefix and fuzzy modes highlight whole words
+        let spans = find_spans(content, &["trad".to_string()], MatchMode::Prefix);
+        let found: Vec<&str> = spans.iter().map(|span| &content[span.start..span.end]).collect();
+        assert_eq!(found, ["traders", "trading"]);
+        let spans = find_spans(content, &["wether".to_string()], MatchMode::Fuzzy(1));
+        assert_eq!(&content[spans[0].start..spans[0].end], "Weather");
+
+        // A match inside a character whose lowercase is longer maps back whole
+        let spans = find_spans(content, &["i\u{307}stanbul".to_string()], MatchMode::Exact);
+        assert_eq!(&content[spans[0].start..spans[0].end], "İstanbul");
+    }
+
+    #[test]
+    fn test_recall_highlighted() {
+        let (mut cache, _temp_dir) = temp_cache();
+        cache.save("alice", "s1", "Set a stop loss on AAPL. Lunch was long.", None).unwrap();
+        cache.save("alice", "s1", "Unrelated note", None).unwrap();
+
+        let highlighted = cache.recall_highlighted(QueryFilter {
+            user_id: Some("alice".to_string()),
+            query: Some("\"stop loss\" AND aapl".to_string()),
+            ..Default::default()
+        }).unwrap();
+        assert_eq!(highlighted.len(), 1);
+        let hit = &highlighted[0];
+        assert_eq!(hit.spans, [MatchSpan { start: 6, end: 15 }, MatchSpan { start: 19, end: 23 }]);
+        assert_eq!(hit.snippets, ["Set a stop loss on AAPL."]);
+
+        let plain = cache.recall_highlighted(QueryFilter { user_id: Some("alice".to_string()), ..Default::default() }).unwrap();
+        assert!(plain.iter().all(|hit| hit.spans.is_empty() && hit.snippets.is_empty()));
+    }
+}
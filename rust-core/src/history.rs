@@ -0,0 +1,200 @@
+//! Superseded versions of memories, for as-of recall
+//!
+//! When enabled, every update and delete appends the version it replaces to
+//! `history.log` (one JSON object per line) together with the time it
+//! stopped being current. A version is current from the moment the previous
+//! one was superseded (or from the memory's `timestamp` for the first one)
+//! until its own `superseded_at`, which is enough to rebuild what the store
+//! held at any point since history was turned on; see `QueryFilter::as_of`.
+//!
+//! Changes made while history is off leave no trace, so an as-of query
+//! reaching back before it was enabled sees the oldest version it knows of.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use crate::backend::StorageBackend;
+use crate::jsonl;
+use crate::storage::MemoryItem;
+
+pub const HISTORY_LOG: &str = "history.log";
+
+/// A version of a memory that was replaced by an update or removed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SupersededVersion {
+    pub memory: MemoryItem,
+    /// When this version stopped being current
+    pub superseded_at: DateTime<Utc>,
+    /// Whether the memory was deleted rather than updated
+    pub deleted: bool,
+}
+
+/// Handle to the version history, shared by every clone of a storage handle
+#[derive(Clone)]
+pub struct VersionHistory {
+    backend: Arc<dyn StorageBackend>,
+    enabled: Arc<Mutex<bool>>,
+}
+
+impl VersionHistory {
+    /// A disabled history; call `enable` to start recording
+    pub fn new(backend: Arc<dyn StorageBackend>) -> Self {
+        VersionHistory { backend, enabled: Arc::new(Mutex::new(false)) }
+    }
+
+    pub fn enable(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let mut enabled = self.lock_enabled();
+        if *enabled {
+            return Ok(());
+        }
+
+        jsonl::seal(self.backend.as_ref(), HISTORY_LOG)?;
+        *enabled = true;
+        Ok(())
+    }
+
+    /// Stop recording; versions already kept stay queryable
+    pub fn disable(&self) {
+        *self.lock_enabled() = false;
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        *self.lock_enabled()
+    }
+
+    /// Keep `memory` as superseded at `superseded_at`; does nothing while disabled
+    pub fn record(&self, memory: &MemoryItem, deleted: bool, superseded_at: DateTime<Utc>) -> Result<(), Box<dyn std::error::Error>> {
+        let enabled = self.lock_enabled();
+        if !*enabled {
+            return Ok(());
+        }
+
+        let version = SupersededVersion { memory: memory.clone(), superseded_at, deleted };
+        jsonl::append(self.backend.as_ref(), HISTORY_LOG, &version)
+    }
+
+    /// Every kept version, grouped by memory id, oldest first
+    pub fn versions(&self) -> Result<HashMap<String, Vec<SupersededVersion>>, Box<dyn std::error::Error>> {
+        let mut by_id: HashMap<String, Vec<SupersededVersion>> = HashMap::new();
+        for version in self.read_all()? {
+            by_id.entry(version.memory.id.clone()).or_default().push(version);
+        }
+        Ok(by_id)
+    }
+
+    /// Kept versions of one memory, oldest first
+    pub fn versions_of(&self, memory_id: &str) -> Result<Vec<SupersededVersion>, Box<dyn std::error::Error>> {
+        Ok(self.read_all()?.into_iter().filter(|version| version.memory.id == memory_id).collect())
+    }
+
+    /// Drop every kept version of a user's memories; returns how many were removed
+    pub fn remove_user(&self, user_id: &str) -> Result<usize, Box<dyn std::error::Error>> {
+        let _enabled = self.lock_enabled();
+        let versions = self.read_all()?;
+        let before = versions.len();
+        let kept: Vec<SupersededVersion> = versions.into_iter().filter(|version| version.memory.user_id != user_id).collect();
+        let removed = before - kept.len();
+        if removed == 0 {
+            return Ok(0);
+        }
+
+        jsonl::rewrite(self.backend.as_ref(), HISTORY_LOG, &kept)?;
+        Ok(removed)
+    }
+
+    fn read_all(&self) -> Result<Vec<SupersededVersion>, Box<dyn std::error::Error>> {
+        let mut versions: Vec<SupersededVersion> = jsonl::read(self.backend.as_ref(), HISTORY_LOG)?;
+        versions.sort_by_key(|version| version.superseded_at);
+        Ok(versions)
+    }
+
+    fn lock_enabled(&self) -> std::sync::MutexGuard<'_, bool> {
+        self.enabled.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// The version of a memory that was current at `at`, if it existed then
+///
+/// `versions` are its superseded versions, oldest first, and `current` the
+/// version stored now (`None` once it has been deleted).
+pub fn version_at(versions: &[SupersededVersion], current: Option<&MemoryItem>, at: DateTime<Utc>) -> Option<MemoryItem> {
+    let created = versions.first().map(|version| &version.memory).or(current)?.timestamp;
+    if created > at {
+        return None;
+    }
+    versions
+        .iter()
+        .find(|version| at < version.superseded_at)
+        .map(|version| version.memory.clone())
+        .or_else(|| current.cloned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MindCacheConfig;
+    use chrono::Duration;
+    use crate::test_support::temp_cache_with;
+
+    #[test]
+    fn test_version_at_walks_superseded_versions() {
+        let start = Utc::now() - Duration::hours(10);
+        let version = |content: &str, hours: i64, deleted: bool| SupersededVersion {
+            memory: MemoryItem { id: "m1".to_string(), content: content.to_string(), timestamp: start, ..Default::default() },
+            superseded_at: start + Duration::hours(hours),
+            deleted,
+        };
+        let versions = [version("first", 2, false), version("second", 5, false)];
+        let current = MemoryItem { id: "m1".to_string(), content: "third".to_string(), timestamp: start, ..Default::default() };
+
+        let content_at = |hours: i64, current: Option<&MemoryItem>| {
+            version_at(&versions, current, start + Duration::hours(hours)).map(|memory| memory.content)
+        };
+        assert_eq!(content_at(-1, Some(&current)), None);
+        assert_eq!(content_at(1, Some(&current)).as_deref(), Some("first"));
+        assert_eq!(content_at(3, Some(&current)).as_deref(), Some("second"));
+        assert_eq!(content_at(6, Some(&current)).as_deref(), Some("third"));
+        // Deleted after the last version was superseded
+        assert_eq!(content_at(6, None), None);
+        assert_eq!(content_at(4, None).as_deref(), Some("second"));
+    }
+
+    #[test]
+    fn test_recall_as_of_sees_updated_and_deleted_memories() {
+        let (mut cache, _temp_dir) = temp_cache_with(MindCacheConfig {
+            history_enabled: true,
+            ..Default::default()
+        });
+        let as_of = |cache: &crate::MindCache, at| {
+            let filter = crate::QueryFilter { user_id: Some("alice".to_string()), as_of: Some(at), ..Default::default() };
+            let mut contents: Vec<String> = cache.recall_advanced(filter).unwrap().into_iter().map(|m| m.content).collect();
+            contents.sort();
+            contents
+        };
+
+        let before = Utc::now();
+        let plan = cache.save("alice", "s1", "Plan: buy AAPL", None).unwrap();
+        let note = cache.save("alice", "s1", "Risk limit is 2%", None).unwrap();
+        let saved = Utc::now();
+        cache.update_memory(&plan, crate::MemoryUpdate { content: Some("Plan: sell AAPL".to_string()), ..Default::default() }).unwrap();
+        let updated = Utc::now();
+        cache.transaction(|tx| {
+            tx.delete(&note);
+            Ok(())
+        }).unwrap();
+
+        assert!(as_of(&cache, before).is_empty());
+        assert_eq!(as_of(&cache, saved), ["Plan: buy AAPL", "Risk limit is 2%"]);
+        assert_eq!(as_of(&cache, updated), ["Plan: sell AAPL", "Risk limit is 2%"]);
+        assert_eq!(as_of(&cache, Utc::now()), ["Plan: sell AAPL"]);
+
+        let filter = crate::QueryFilter { keywords: Some(vec!["buy".to_string()]), as_of: Some(saved), ..Default::default() };
+        assert_eq!(cache.recall_advanced(filter).unwrap().len(), 1);
+        assert_eq!(cache.memory_history(&plan).unwrap().len(), 1);
+
+        let report = cache.delete_user("alice").unwrap();
+        assert_eq!(report.history_versions_removed, 2);
+        assert!(as_of(&cache, saved).is_empty());
+    }
+}
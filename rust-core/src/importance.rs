@@ -0,0 +1,210 @@
+//! Importance estimation for memories saved without an explicit score
+//!
+//! Decay drops low-importance memories first, so a store where everything
+//! is saved at the default 0.5 decays in plain age order. `MindCache::save`
+//! asks an [`ImportanceScorer`] instead; the default [`HeuristicScorer`]
+//! looks at length, numbers and dates, weighted keywords and how much the
+//! memory differs from what the user already has stored.
+//!
+//! Scores are fixed at save time. After changing the scorer, or for stores
+//! whose scorer looks at things that change over time such as recency,
+//! `MindCache::recalculate_importance` rescores a user's memories.
+
+use std::collections::HashMap;
+use crate::dedupe;
+use crate::storage::MemoryItem;
+use crate::{MindCache, QueryFilter};
+
+/// How many of the user's most similar memories are passed to scorers
+pub const SIMILAR_MEMORY_LIMIT: usize = 10;
+
+/// What a scorer gets to look at besides the new memory
+pub struct ScoringContext<'a> {
+    /// The user's existing memories sharing the most words with the new one,
+    /// most similar first (at most [`SIMILAR_MEMORY_LIMIT`])
+    pub similar: &'a [MemoryItem],
+}
+
+/// Estimates a memory's importance, 0.0 to 1.0
+pub trait ImportanceScorer: Send + Sync {
+    fn score(&self, memory: &MemoryItem, context: &ScoringContext<'_>) -> f32;
+}
+
+impl<F> ImportanceScorer for F
+where
+    F: Fn(&MemoryItem, &ScoringContext<'_>) -> f32 + Send + Sync,
+{
+    fn score(&self, memory: &MemoryItem, context: &ScoringContext<'_>) -> f32 {
+        self(memory, context)
+    }
+}
+
+/// Words that usually mark something worth keeping
+const DEFAULT_KEYWORD_WEIGHTS: &[(&str, f32)] = &[
+    ("important", 0.2),
+    ("remember", 0.2),
+    ("always", 0.1),
+    ("never", 0.1),
+    ("deadline", 0.15),
+    ("birthday", 0.15),
+    ("allergic", 0.2),
+    ("password", 0.1),
+    ("prefer", 0.1),
+    ("decided", 0.1),
+];
+
+/// Default scorer: a few cheap signals added to a base score
+#[derive(Debug, Clone)]
+pub struct HeuristicScorer {
+    keyword_weights: HashMap<String, f32>,
+}
+
+impl Default for HeuristicScorer {
+    fn default() -> Self {
+        HeuristicScorer {
+            keyword_weights: DEFAULT_KEYWORD_WEIGHTS
+                .iter()
+                .map(|(word, weight)| (word.to_string(), *weight))
+                .collect(),
+        }
+    }
+}
+
+impl HeuristicScorer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add or replace the weight of a (lowercase) keyword
+    pub fn with_keyword_weight(mut self, keyword: &str, weight: f32) -> Self {
+        self.keyword_weights.insert(keyword.to_lowercase(), weight);
+        self
+    }
+}
+
+impl ImportanceScorer for HeuristicScorer {
+    fn score(&self, memory: &MemoryItem, context: &ScoringContext<'_>) -> f32 {
+        let content = memory.content.to_lowercase();
+        let words: Vec<&str> = content
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|word| !word.is_empty())
+            .collect();
+
+        let mut score = 0.3;
+
+        // Longer memories tend to carry more detail
+        score += (words.len() as f32 / 40.0).min(1.0) * 0.15;
+
+        // Amounts, prices and dates
+        if content.chars().any(|c| c.is_ascii_digit()) {
+            score += 0.1;
+        }
+
+        let keyword_score: f32 = words
+            .iter()
+            .filter_map(|word| self.keyword_weights.get(*word))
+            .sum();
+        score += keyword_score.clamp(-0.3, 0.3);
+
+        // Something the user has said before adds less than something new
+        let fingerprint = dedupe::simhash(&memory.content);
+        let max_similarity = context
+            .similar
+            .iter()
+            .map(|other| dedupe::similarity(fingerprint, dedupe::simhash(&other.content)))
+            .fold(0.0, f32::max);
+        score += (1.0 - max_similarity) * 0.15;
+
+        score.clamp(0.0, 1.0)
+    }
+}
+
+/// Changes smaller than this are left unsaved, so rescoring with the same scorer writes nothing
+const RESCORE_EPSILON: f32 = 1e-4;
+
+impl MindCache {
+    /// Rescore all of a user's memories and save the ones whose importance changed
+    ///
+    /// Uses `scorer`, or the scorer set with `set_importance_scorer` if
+    /// `None`. Each memory is scored against the user's other memories, as
+    /// on save. Every changed memory gets a new version, like any update.
+    /// Returns the number of memories updated.
+    pub fn recalculate_importance(&mut self, user_id: &str, scorer: Option<&dyn ImportanceScorer>) -> Result<usize, Box<dyn std::error::Error>> {
+        let scorer = scorer.unwrap_or(self.importance_scorer.as_ref());
+        let memories = self.storage.recall(QueryFilter { user_id: Some(user_id.to_string()), include_superseded: true, ..Default::default() })?;
+
+        let mut rescored = Vec::new();
+        for memory in &memories {
+            let mut similar = self.storage.most_similar(user_id, &memory.content, SIMILAR_MEMORY_LIMIT + 1)?;
+            similar.retain(|other| other.id != memory.id);
+            similar.truncate(SIMILAR_MEMORY_LIMIT);
+            let importance = scorer.score(memory, &ScoringContext { similar: &similar }).clamp(0.0, 1.0);
+            if (importance - memory.importance).abs() > RESCORE_EPSILON {
+                rescored.push((memory.id.clone(), importance));
+            }
+        }
+
+        // Scored first and saved after, so each memory is compared with the others as they were
+        for (id, importance) in &rescored {
+            self.storage.update_memory(id, None, |memory| memory.importance = *importance)?;
+        }
+        println!("Recalculated importance of {} memories for user {}, {} changed", memories.len(), user_id, rescored.len());
+        Ok(rescored.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::temp_cache;
+
+    fn memory(content: &str) -> MemoryItem {
+        MemoryItem {
+            content: content.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_heuristic_signals() {
+        let scorer = HeuristicScorer::new();
+        let none = ScoringContext { similar: &[] };
+        let small_talk = scorer.score(&memory("ok sounds good"), &none);
+        let detailed = scorer.score(&memory("Remember: the visa deadline is 2024-06-30, bring two photos"), &none);
+        assert!(detailed > small_talk + 0.3, "{} vs {}", detailed, small_talk);
+        assert!((0.0..=1.0).contains(&detailed));
+
+        let repeated = memory("Bought AAPL at 175 today");
+        let seen = [memory("Bought AAPL at 175 today")];
+        assert!(scorer.score(&repeated, &ScoringContext { similar: &seen }) < scorer.score(&repeated, &none));
+
+        let custom = HeuristicScorer::new().with_keyword_weight("aapl", 0.3);
+        assert!(custom.score(&repeated, &none) > scorer.score(&repeated, &none));
+    }
+
+    #[test]
+    fn test_recalculate_importance_rescores_a_users_memories() {
+        let (mut cache, _temp_dir) = temp_cache();
+        let trade = cache.save_with_options("alice", "s1", "Bought AAPL at 175", None, 0.5, None).unwrap();
+        let lunch = cache.save_with_options("alice", "s1", "Had a sandwich for lunch", None, 0.5, None).unwrap();
+        let other = cache.save_with_options("bob", "s1", "Bought AAPL too", None, 0.5, None).unwrap();
+
+        let trades_matter = |memory: &MemoryItem, _: &ScoringContext<'_>| {
+            if memory.content.contains("AAPL") { 0.9 } else { 0.2 }
+        };
+        assert_eq!(cache.recalculate_importance("alice", Some(&trades_matter)).unwrap(), 2);
+        let importance = |id: &str| cache.get_memory(id).unwrap().unwrap().importance;
+        assert_eq!((importance(&trade), importance(&lunch), importance(&other)), (0.9, 0.2, 0.5));
+        assert_eq!(cache.get_memory(&trade).unwrap().unwrap().version, 1);
+        // Nothing changes the second time
+        assert_eq!(cache.recalculate_importance("alice", Some(&trades_matter)).unwrap(), 0);
+
+        // Without a scorer, the one set on the cache is used
+        cache.set_importance_scorer(|_: &MemoryItem, context: &ScoringContext<'_>| {
+            assert!(context.similar.len() <= 1);
+            0.7
+        });
+        assert_eq!(cache.recalculate_importance("alice", None).unwrap(), 2);
+        assert_eq!(cache.recall("alice", Some("AAPL"), None, None).unwrap()[0].importance, 0.7);
+    }
+}
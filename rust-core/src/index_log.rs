@@ -0,0 +1,209 @@
+//! Append-only log of index changes
+//!
+//! Rewriting `index.bin` and `session_index.bin` on every save costs time
+//! in proportion to the whole store. Instead, saves, updates and deletes
+//! append the positions they add and remove to `index.log`, and the
+//! snapshot is only rewritten once the log has grown as long as the index
+//! itself (and always by compaction, restores and transactions).
+//!
+//! The log starts with `@<generation>`, the generation the snapshot wrote
+//! into its first line; a log from another generation is stale and ignored.
+//! Each append is a batch of lines, `+<TAB>pos<TAB>user_id<TAB>session_id`
+//! or `-<TAB>pos`, closed by a `.` line. A batch cut short by a crash is
+//! dropped whole, and the log is rewritten before anything else is appended.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write as _;
+use std::sync::{Mutex, MutexGuard};
+
+pub const INDEX_LOG: &str = "index.log";
+
+/// Entries the log may hold before it is folded into the snapshot, however small the index
+const MIN_COMPACT_ENTRIES: usize = 1024;
+
+/// A change to the persisted position lists
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum IndexDelta {
+    Add { position: usize, user_id: String, session_id: String },
+    Remove { position: usize },
+}
+
+/// Position lists by user and by (user, session), as persisted
+pub(crate) type PositionLists<'a> = (&'a mut HashMap<String, Vec<usize>>, &'a mut HashMap<(String, String), Vec<usize>>);
+
+/// A fresh snapshot generation
+pub(crate) fn new_generation() -> u64 {
+    uuid::Uuid::new_v4().as_u64_pair().0
+}
+
+/// First line of a snapshot or log of `generation`
+pub(crate) fn header(generation: u64) -> String {
+    format!("@{}\n", generation)
+}
+
+/// Generation a snapshot or log was written at, if it has a header
+pub(crate) fn generation_of(contents: &str) -> Option<u64> {
+    contents.lines().next()?.strip_prefix('@')?.parse().ok()
+}
+
+/// Encode one batch of deltas
+pub(crate) fn encode(deltas: &[IndexDelta]) -> Result<String, std::fmt::Error> {
+    let mut batch = String::new();
+    for delta in deltas {
+        match delta {
+            IndexDelta::Add { position, user_id, session_id } => writeln!(batch, "+\t{}\t{}\t{}", position, user_id, session_id)?,
+            IndexDelta::Remove { position } => writeln!(batch, "-\t{}", position)?,
+        }
+    }
+    batch.push_str(".\n");
+    Ok(batch)
+}
+
+fn parse_delta(line: &str) -> Option<IndexDelta> {
+    let parts: Vec<&str> = line.split('\t').collect();
+    match parts.as_slice() {
+        ["+", position, user_id, session_id] => Some(IndexDelta::Add {
+            position: position.parse().ok()?,
+            user_id: user_id.to_string(),
+            session_id: session_id.to_string(),
+        }),
+        ["-", position] => Some(IndexDelta::Remove { position: position.parse().ok()? }),
+        _ => None,
+    }
+}
+
+/// The committed deltas of a log, and whether it ended cleanly
+///
+/// Returns `None` for a log that doesn't belong to the snapshot at `generation`.
+pub(crate) fn parse(contents: &str, generation: u64) -> Option<(Vec<IndexDelta>, bool)> {
+    if generation_of(contents) != Some(generation) {
+        return None;
+    }
+    let mut committed = Vec::new();
+    let mut batch = Vec::new();
+    let mut broken = false;
+    for line in contents.split_inclusive('\n').skip(1) {
+        let Some(line) = line.strip_suffix('\n') else {
+            // Torn append
+            broken = true;
+            break;
+        };
+        if line == "." {
+            if !broken {
+                committed.append(&mut batch);
+            }
+            batch.clear();
+            broken = false;
+        } else {
+            match parse_delta(line) {
+                Some(delta) if !broken => batch.push(delta),
+                _ => broken = true,
+            }
+        }
+    }
+    let clean = batch.is_empty() && !broken;
+    Some((committed, clean))
+}
+
+/// Replay deltas onto the position lists of their snapshot
+///
+/// Positions already listed are not added twice, as the session index may
+/// have been written by a save that crashed before replacing `index.bin`.
+pub(crate) fn apply((by_user, by_session): PositionLists<'_>, deltas: &[IndexDelta]) {
+    if deltas.is_empty() {
+        return;
+    }
+    let mut in_user: HashSet<usize> = by_user.values().flatten().copied().collect();
+    let mut in_session: HashSet<usize> = by_session.values().flatten().copied().collect();
+    let mut removed = HashSet::new();
+    for delta in deltas {
+        match delta {
+            IndexDelta::Add { position, user_id, session_id } => {
+                // Positions are never reused within a generation, so a removal is final
+                if removed.contains(position) {
+                    continue;
+                }
+                if in_user.insert(*position) {
+                    by_user.entry(user_id.clone()).or_default().push(*position);
+                }
+                if in_session.insert(*position) {
+                    by_session.entry((user_id.clone(), session_id.clone())).or_default().push(*position);
+                }
+            }
+            IndexDelta::Remove { position } => {
+                removed.insert(*position);
+            }
+        }
+    }
+    if !removed.is_empty() {
+        by_user.values_mut().for_each(|list| list.retain(|p| !removed.contains(p)));
+        by_session.values_mut().for_each(|list| list.retain(|p| !removed.contains(p)));
+        by_user.retain(|_, list| !list.is_empty());
+        by_session.retain(|_, list| !list.is_empty());
+    }
+}
+
+#[derive(Default)]
+struct LogState {
+    /// Generation of the snapshot on disk, if the log on disk extends it cleanly
+    generation: Option<u64>,
+    entries: usize,
+}
+
+/// Whether deltas can be appended to the log on disk
+#[derive(Default)]
+pub(crate) struct IndexLog {
+    state: Mutex<LogState>,
+}
+
+impl IndexLog {
+    fn lock(&self) -> MutexGuard<'_, LogState> {
+        self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// The log on disk extends the snapshot at `generation` with `entries` deltas
+    pub(crate) fn started(&self, generation: u64, entries: usize) {
+        *self.lock() = LogState { generation: Some(generation), entries };
+    }
+
+    /// The snapshot is being replaced; the log must be rewritten before the next append
+    pub(crate) fn invalidate(&self) {
+        *self.lock() = LogState::default();
+    }
+
+    /// Whether `count` more deltas may be appended, given `records` indexed records
+    pub(crate) fn accepts(&self, count: usize, records: usize) -> bool {
+        let state = self.lock();
+        state.generation.is_some() && state.entries + count <= records.max(MIN_COMPACT_ENTRIES)
+    }
+
+    pub(crate) fn appended(&self, count: usize) {
+        self.lock().entries += count;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_replays_committed_batches_of_its_generation() {
+        let add = |position: usize| IndexDelta::Add { position, user_id: "alice".to_string(), session_id: "s1".to_string() };
+        let mut log = header(7);
+        log.push_str(&encode(&[add(1), add(2)]).unwrap());
+        log.push_str(&encode(&[IndexDelta::Remove { position: 1 }, add(3)]).unwrap());
+        assert!(parse(&log, 8).is_none());
+        let (deltas, clean) = parse(&log, 7).unwrap();
+        assert!(clean);
+
+        let (mut by_user, mut by_session) = (HashMap::new(), HashMap::new());
+        apply((&mut by_user, &mut by_session), &deltas);
+        assert_eq!(by_user["alice"], [2, 3]);
+        assert_eq!(by_session[&("alice".to_string(), "s1".to_string())], [2, 3]);
+
+        // A torn batch is dropped whole
+        log.push_str("+\t4\talice\ts1\n-\t2");
+        let (torn, clean) = parse(&log, 7).unwrap();
+        assert_eq!((torn, clean), (deltas, false));
+    }
+}
@@ -0,0 +1,56 @@
+//! Worker threads for the C API's async functions
+//!
+//! The pool starts on first use and lives for the rest of the process. Jobs
+//! run in submission order as workers free up; a job that panics does not
+//! take its worker down.
+
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::thread;
+
+type Job = Box<dyn FnOnce() + Send>;
+
+/// Upper bound on workers; decay and compaction are mostly I/O bound
+const MAX_WORKERS: usize = 4;
+
+static POOL: OnceLock<Option<Sender<Job>>> = OnceLock::new();
+
+/// Queue a job; false if no worker thread could be started
+pub(crate) fn spawn<F: FnOnce() + Send + 'static>(job: F) -> bool {
+    match POOL.get_or_init(start_workers) {
+        Some(sender) => sender.send(Box::new(job)).is_ok(),
+        None => false,
+    }
+}
+
+fn start_workers() -> Option<Sender<Job>> {
+    let (sender, receiver) = mpsc::channel::<Job>();
+    let receiver = Arc::new(Mutex::new(receiver));
+    let workers = thread::available_parallelism().map_or(1, |n| n.get()).min(MAX_WORKERS);
+
+    let mut started = 0;
+    for i in 0..workers {
+        let receiver = Arc::clone(&receiver);
+        let spawned = thread::Builder::new()
+            .name(format!("mindcache-worker-{}", i))
+            .spawn(move || run_worker(&receiver));
+        match spawned {
+            Ok(_) => started += 1,
+            Err(e) => println!("Failed to start worker thread: {}", e),
+        }
+    }
+    (started > 0).then_some(sender)
+}
+
+fn run_worker(receiver: &Mutex<Receiver<Job>>) {
+    loop {
+        let job = match receiver.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).recv() {
+            Ok(job) => job,
+            Err(_) => return,
+        };
+        if panic::catch_unwind(AssertUnwindSafe(job)).is_err() {
+            println!("Background job panicked");
+        }
+    }
+}
@@ -0,0 +1,139 @@
+//! Journal of decay runs
+//!
+//! Every `MindCache::decay` call appends a [`DecayRun`] to
+//! `decay_journal.log` (one JSON object per line): when it ran, its stats,
+//! the ids of the memories it affected and why, or the error it failed
+//! with. `MindCache::decay_history` reads it back, so a memory that
+//! disappeared can be traced to the run that removed it.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use crate::backend::StorageBackend;
+use crate::jsonl;
+use crate::decay::DecayStats;
+use crate::MindCache;
+
+pub const DECAY_JOURNAL: &str = "decay_journal.log";
+
+/// Ids of the memories a decay run touched, by what it did to them
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AffectedMemories {
+    /// Deleted past their TTL or maximum age
+    pub expired_ids: Vec<String>,
+    /// Deleted to bring a session or user back under its memory limit
+    pub over_limit_ids: Vec<String>,
+    /// Folded into compressed summaries
+    pub compressed_ids: Vec<String>,
+    /// Selected, but vetoed or rescored by the expiry hook
+    pub kept_ids: Vec<String>,
+    /// Purged from the trash after `trash_retention_days`
+    pub trash_purged_ids: Vec<String>,
+    /// Expired and over-limit memories of each user
+    #[serde(default)]
+    pub expired_by_user: HashMap<String, usize>,
+}
+
+impl AffectedMemories {
+    /// Whether the run did anything to this memory
+    pub fn contains(&self, memory_id: &str) -> bool {
+        [&self.expired_ids, &self.over_limit_ids, &self.compressed_ids, &self.kept_ids, &self.trash_purged_ids]
+            .iter()
+            .any(|ids| ids.iter().any(|id| id == memory_id))
+    }
+}
+
+/// One entry of the decay journal
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DecayRun {
+    pub started_at: DateTime<Utc>,
+    pub finished_at: DateTime<Utc>,
+    /// `None` if the run failed
+    pub stats: Option<DecayStats>,
+    #[serde(flatten)]
+    pub affected: AffectedMemories,
+    pub error: Option<String>,
+}
+
+/// Handle to the decay journal, shared by every clone of a storage handle
+#[derive(Clone)]
+pub struct DecayJournal {
+    backend: Arc<dyn StorageBackend>,
+    lock: Arc<Mutex<()>>,
+}
+
+impl DecayJournal {
+    pub fn new(backend: Arc<dyn StorageBackend>) -> Self {
+        DecayJournal { backend, lock: Arc::new(Mutex::new(())) }
+    }
+
+    pub fn append(&self, run: &DecayRun) -> Result<(), Box<dyn std::error::Error>> {
+        let _guard = self.lock.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        jsonl::seal(self.backend.as_ref(), DECAY_JOURNAL)?;
+        jsonl::append(self.backend.as_ref(), DECAY_JOURNAL, run)
+    }
+
+    /// The most recent runs, newest first; all of them without a limit
+    pub fn recent(&self, limit: Option<usize>) -> Result<Vec<DecayRun>, Box<dyn std::error::Error>> {
+        let mut runs: Vec<DecayRun> = jsonl::read(self.backend.as_ref(), DECAY_JOURNAL)?;
+        runs.reverse();
+        if let Some(limit) = limit {
+            runs.truncate(limit);
+        }
+        Ok(runs)
+    }
+}
+
+impl MindCache {
+    /// The last `limit` decay runs, newest first
+    pub fn decay_history(&self, limit: usize) -> Result<Vec<DecayRun>, Box<dyn std::error::Error>> {
+        self.storage.decay_journal().recent(Some(limit))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+    use crate::storage::MemoryItem;
+    use crate::{MindCache, MindCacheConfig};
+    use crate::test_support::test_config;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_decay_runs_are_journaled() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = MindCacheConfig {
+            enable_compression: false,
+            ..test_config(&temp_dir)
+        };
+        let mut cache = MindCache::with_config(config.clone()).unwrap();
+        let stale = cache.storage.save(MemoryItem {
+            user_id: "alice".to_string(),
+            session_id: "s1".to_string(),
+            content: "stale".to_string(),
+            timestamp: chrono::Utc::now() - Duration::hours(10),
+            ttl_hours: Some(1),
+            importance: 0.1,
+            ..Default::default()
+        }).unwrap();
+        let fresh = cache.save("alice", "s1", "fresh", None).unwrap();
+
+        cache.decay().unwrap();
+        cache.decay().unwrap();
+        drop(cache);
+
+        let cache = MindCache::with_config(config).unwrap();
+        let history = cache.decay_history(10).unwrap();
+        assert_eq!(history.len(), 2);
+        assert!(history[0].started_at >= history[1].started_at);
+        assert_eq!(history[1].affected.expired_ids, std::slice::from_ref(&stale));
+        assert!(history[1].affected.contains(&stale));
+        assert!(!history[1].affected.contains(&fresh));
+        assert!(history[1].stats.is_some() && history[1].error.is_none());
+        // The second run found it already deleted
+        assert!(!history[0].affected.contains(&stale));
+        assert!(history[0].affected.expired_by_user.is_empty());
+        assert_eq!(cache.decay_history(1).unwrap().len(), 1);
+    }
+}
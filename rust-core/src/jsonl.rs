@@ -0,0 +1,77 @@
+//! JSON Lines logs kept in a storage backend
+//!
+//! The audit log, change log, version history and decay journal each hold
+//! one JSON value per line. A crash mid-append can leave a torn last line:
+//! `seal` ends it so the next entry starts on a fresh one, and reads skip
+//! lines that don't parse.
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use crate::backend::StorageBackend;
+
+/// End a torn last line of `log`, if it has one
+pub(crate) fn seal(backend: &dyn StorageBackend, log: &str) -> Result<(), Box<dyn std::error::Error>> {
+    if let Some(data) = backend.read_blob(log)? {
+        if data.last().is_some_and(|byte| *byte != b'\n') {
+            backend.append(log, b"\n")?;
+        }
+    }
+    Ok(())
+}
+
+/// Append `value` as one line and flush it
+pub(crate) fn append<T: Serialize>(backend: &dyn StorageBackend, log: &str, value: &T) -> Result<(), Box<dyn std::error::Error>> {
+    let mut line = serde_json::to_vec(value)?;
+    line.push(b'\n');
+    backend.append(log, &line)?;
+    backend.flush(log)?;
+    Ok(())
+}
+
+/// Every complete value in `log`, oldest first
+pub(crate) fn read<T: DeserializeOwned>(backend: &dyn StorageBackend, log: &str) -> Result<Vec<T>, Box<dyn std::error::Error>> {
+    Ok(backend.read_blob(log)?.map(|data| parse(&data)).unwrap_or_default())
+}
+
+/// The values of JSON Lines `data`, skipping torn appends that never completed
+pub(crate) fn parse<T: DeserializeOwned>(data: &[u8]) -> Vec<T> {
+    data.split(|byte| *byte == b'\n')
+        .filter_map(|line| serde_json::from_slice(line).ok())
+        .collect()
+}
+
+/// Replace the contents of `log` with `values`, one per line
+pub(crate) fn rewrite<T: Serialize>(backend: &dyn StorageBackend, log: &str, values: &[T]) -> Result<(), Box<dyn std::error::Error>> {
+    let mut data = Vec::new();
+    for value in values {
+        data.extend(serde_json::to_vec(value)?);
+        data.push(b'\n');
+    }
+    backend.write_blob(log, &data)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::FileBackend;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_torn_lines_are_sealed_and_skipped() {
+        let temp_dir = TempDir::new().unwrap();
+        let backend = FileBackend::new(temp_dir.path()).unwrap();
+        assert!(read::<u32>(&backend, "log.jsonl").unwrap().is_empty());
+        seal(&backend, "log.jsonl").unwrap();
+
+        append(&backend, "log.jsonl", &1).unwrap();
+        backend.append("log.jsonl", b"{\"torn").unwrap();
+        seal(&backend, "log.jsonl").unwrap();
+        seal(&backend, "log.jsonl").unwrap();
+        append(&backend, "log.jsonl", &2).unwrap();
+        assert_eq!(read::<u32>(&backend, "log.jsonl").unwrap(), [1, 2]);
+
+        rewrite(&backend, "log.jsonl", &[3]).unwrap();
+        assert_eq!(read::<u32>(&backend, "log.jsonl").unwrap(), [3]);
+    }
+}
@@ -1,20 +1,158 @@
 //! MindCache - A lightweight, local-first memory engine for AI applications
 
+// The C API null-checks every pointer it receives before dereferencing it
+#![allow(clippy::not_unsafe_ptr_arg_deref)]
+
 pub mod storage;
+pub mod record;
+pub mod segments;
+pub mod migrations;
+pub mod backend;
+pub mod testing;
+pub mod cache;
+pub mod bloom;
+pub mod index_log;
+pub mod batching;
+pub mod tiering;
+pub mod planner;
+pub mod matching;
+pub mod query;
+pub mod timeline;
+pub mod topics;
+pub mod links;
+pub mod supersession;
+pub mod trash;
+pub mod reminders;
+pub mod pins;
+pub mod highlight;
+pub mod lifecycle;
+pub mod clock;
+pub mod expiry;
+pub mod journal;
+pub mod cluster;
+pub mod extraction;
+pub mod facts;
+pub mod stopwords;
+pub mod text;
+pub mod chunking;
+pub mod retention;
+pub mod spaces;
+pub mod auth;
 pub mod session;
+pub mod catalog;
+pub mod export;
+pub mod templates;
 pub mod decay;
+pub mod dedupe;
+pub mod similar;
+pub mod diversity;
+pub mod importance;
+pub mod summarizer;
+pub mod summary_cache;
+pub mod digest;
+pub mod review;
+pub mod erasure;
+pub mod config;
+pub mod builder;
+pub mod metrics;
+pub mod stats;
+pub mod quota;
+pub mod ratelimit;
+pub mod transaction;
+pub mod versioning;
+pub mod events;
+pub mod provenance;
+pub mod changes;
+pub mod vector_sync;
+pub mod audit;
+pub mod history;
+pub mod context;
+pub mod backup;
+pub mod merge;
+pub mod replication;
+mod jobs;
+mod jsonl;
+#[cfg(test)]
+mod test_support;
+#[cfg(feature = "daemon")]
+pub mod daemon;
+#[cfg(feature = "demo")]
+pub mod demo;
+#[cfg(feature = "agent")]
+pub mod agent;
+#[cfg(feature = "bench")]
+pub mod bench;
+#[cfg(feature = "s3")]
+pub mod s3;
+#[cfg(feature = "server")]
+pub mod server;
+#[cfg(feature = "grpc")]
+pub mod grpc;
 
 use std::collections::HashMap;
+use std::sync::Arc;
 use std::ffi::{CStr, CString};
-use std::os::raw::c_char;
-use chrono::Utc; // Remove unused DateTime import
+use std::os::raw::{c_char, c_void};
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use serde_json;
 
 // Re-export main types for easier usage
-pub use storage::{MemoryStorage, MemoryItem, QueryFilter};
-pub use session::{SessionManager, Session, SessionSummary};
-pub use decay::{MemoryDecayEngine, DecayPolicy, DecayStats};
+pub use storage::{ALL_USERS, MemoryStorage, MemoryItem, MemoryType, MemoryField, QueryFilter, MetadataCondition, CompactionReport, RecallIter};
+pub use backend::{StorageBackend, FileBackend, AccessMode, StorageLocked};
+pub use session::{MemoryHit, SessionManager, Session, SessionMatch, SessionPolicy, SessionSummary, UnknownSession, SUMMARY_SESSION};
+pub use decay::{MemoryDecayEngine, DecayPolicy, DecayReport, DecayStats};
+pub use cache::CacheStats;
+pub use dedupe::{DedupeReport, DedupeCluster};
+pub use similar::SimilarMemory;
+pub use highlight::{HighlightedMemory, MatchSpan};
+pub use lifecycle::{MemoryState, MemoryWithState};
+pub use batching::ReadConsistency;
+pub use clock::{Clock, SystemClock, TestClock};
+pub use events::{MemoryEvent, MemoryObserver, SubscriptionId};
+pub use provenance::{DerivationMethod, ProvenanceRecord};
+pub use changes::{ChangeKind, ChangeRecord};
+pub use vector_sync::{Embedder, HashingEmbedder, VectorRecord, VectorSink, VectorSync, VectorSyncReport};
+pub use history::SupersededVersion;
+pub use audit::{AuditAction, AuditEntry, AuditFilter};
+pub use context::{ContextBuilder, ContextWindow};
+pub use backup::BackupManifest;
+pub use merge::{ConflictPolicy, MergeReport};
+pub use replication::{ReplicatingBackend, ReplicationStatus};
+pub use planner::{AccessPath, QueryPlan};
+pub use matching::MatchMode;
+pub use query::QueryExpr;
+pub use timeline::{TimeBucket, HistogramBucket, RelativeDuration};
+pub use topics::UserTopic;
+pub use links::{MemoryLink, LinkDirection, RelatedMemory};
+pub use trash::TrashedMemory;
+pub use expiry::{ExpiryDecision, ExpiryHook, ExpiryReason};
+pub use extraction::{Entity, EntityExtractor, EntityKind, RuleBasedExtractor};
+pub use facts::{Fact, FactExtractor, RuleBasedFactExtractor, Triple};
+pub use stopwords::{Language, StopWords};
+pub use journal::{AffectedMemories, DecayRun};
+pub use retention::SessionRetention;
+pub use templates::SessionTemplate;
+pub use catalog::{SessionPage, SessionQuery, SessionSort};
+pub use export::ExportFormat;
+pub use tiering::{HotTier, HotTierPolicy, InMemoryTier};
+#[cfg(feature = "redis")]
+pub use tiering::RedisTier;
+pub use spaces::{Space, SpaceRole};
+pub use auth::{Access, ApiKey, AuthError, Scope, Scoped};
+pub use importance::{ImportanceScorer, HeuristicScorer, ScoringContext};
+pub use digest::{Digest, DigestLevel, DigestPeriod};
+pub use review::{Review, ReviewDelivery};
+pub use erasure::DeletionReport;
+pub use config::{ConfigIssue, InvalidConfig, ConfigWatcher};
+pub use builder::MindCacheBuilder;
+pub use metrics::{MetricsSnapshot, HistogramSnapshot, StoreGauges};
+pub use stats::{MindCacheStats, SessionStats, IndexStats, FileStats, DiskUsage};
+pub use quota::{QuotaPolicy, QuotaExceeded};
+pub use chunking::{ContentLimitPolicy, ContentTooLarge};
+pub use ratelimit::RateLimited;
+pub use transaction::Transaction;
+pub use versioning::{MemoryUpdate, VersionConflict};
+pub use summarizer::{Summarizer, ExtractiveSummarizer, SummaryKind, SummaryRequest, OwnedSummaryRequest};
 
 /// Main MindCache client that orchestrates all memory operations
 pub struct MindCache {
@@ -22,6 +160,16 @@ pub struct MindCache {
     session_manager: SessionManager,
     decay_engine: MemoryDecayEngine,
     config: MindCacheConfig,
+    importance_scorer: Arc<dyn ImportanceScorer>,
+    entity_extractor: Arc<dyn EntityExtractor>,
+    fact_extractor: Arc<dyn FactExtractor>,
+    expiry_hook: Option<Arc<dyn ExpiryHook>>,
+    summarizer: Arc<dyn Summarizer>,
+    config_watcher: Option<config::ConfigWatcher>,
+    save_limiter: ratelimit::RateLimiter,
+    recall_limiter: ratelimit::RateLimiter,
+    replication: Option<replication::Replicator>,
+    flusher: Option<batching::Flusher>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -31,8 +179,160 @@ pub struct MindCacheConfig {
     pub decay_interval_hours: u32,
     pub default_memory_ttl_hours: Option<u32>,
     pub enable_compression: bool,
+    /// How similar old memories of a session must be for decay to compress
+    /// them into one summary; 0.0 compresses whole sessions together
+    #[serde(default = "default_compression_similarity")]
+    pub compression_similarity: f32,
     pub max_memories_per_user: usize,
+    /// Expired memories below this importance are deleted by decay
     pub importance_threshold: f32,
+    /// Old memories below this importance are compressed by decay;
+    /// `None` uses `importance_threshold`
+    #[serde(default)]
+    pub compress_threshold: Option<f32>,
+    /// Memories above this importance are never touched by decay;
+    /// `None` protects nothing
+    #[serde(default)]
+    pub protect_threshold: Option<f32>,
+    #[serde(default = "default_memory_cache_capacity")]
+    pub memory_cache_capacity: usize,
+    /// Saves persisted together, with one fsync per log and one index
+    /// write; 1 makes every save durable before it returns (see [`batching`])
+    #[serde(default = "default_write_batch_size")]
+    pub write_batch_size: usize,
+    /// Longest a batched save waits to be persisted, in milliseconds
+    #[serde(default = "default_write_batch_delay_ms")]
+    pub write_batch_delay_ms: u64,
+    /// Redis server holding recent and important memories, e.g.
+    /// `redis://127.0.0.1:6379/0`; `None` keeps everything on disk (see
+    /// [`tiering`]). Needs the `redis` feature
+    #[serde(default)]
+    pub hot_tier_url: Option<String>,
+    /// Memories at least this important stay in the hot tier regardless of age
+    #[serde(default = "default_hot_tier_min_importance")]
+    pub hot_tier_min_importance: f32,
+    /// Memories saved within this many hours are in the hot tier
+    #[serde(default = "default_hot_tier_recent_hours")]
+    pub hot_tier_recent_hours: u32,
+    /// Most memories the hot tier holds
+    #[serde(default = "default_hot_tier_max_memories")]
+    pub hot_tier_max_memories: usize,
+    /// Record every save, update and delete in `changes.log` for `changes_since`
+    #[serde(default)]
+    pub change_log_enabled: bool,
+    /// Keep every version an update or delete replaces in `history.log`, so
+    /// `QueryFilter::as_of` can see the store as it was
+    #[serde(default)]
+    pub history_enabled: bool,
+    /// Append who changed which memory, and how, to `audit.log` for `audit_trail`
+    #[serde(default)]
+    pub audit_log_enabled: bool,
+    /// Days a soft-deleted memory stays restorable before decay purges it
+    #[serde(default = "default_trash_retention_days")]
+    pub trash_retention_days: u32,
+    /// Estimate importance with the importance scorer when `save` is given none
+    /// (otherwise such memories get 0.5)
+    #[serde(default = "default_true")]
+    pub auto_importance_enabled: bool,
+    /// Add tickers, dates, amounts, names and keywords found by the entity
+    /// extractor to the metadata of every saved memory
+    #[serde(default)]
+    pub entity_extraction_enabled: bool,
+    /// Language of the stop words skipped by key topics, clustering and
+    /// keyword extraction; `None` detects it for each text
+    #[serde(default)]
+    pub stop_word_language: Option<stopwords::Language>,
+    /// Words skipped as stop words on top of the language's list. The
+    /// built-in entity extractor takes these and `stop_word_language` as the
+    /// instance is opened
+    #[serde(default)]
+    pub extra_stop_words: Vec<String>,
+    /// Open the store without taking the writer lock, e.g. for a sidecar
+    /// next to a running server; every write then fails
+    #[serde(default)]
+    pub read_only: bool,
+    /// Directory kept as an asynchronous copy of the store, e.g. on another
+    /// disk or a network mount, to open in its place if the primary fails
+    /// (see [`replication`])
+    #[serde(default)]
+    pub replica_path: Option<String>,
+    /// How saves treat a user already at `max_memories_per_user`
+    #[serde(default)]
+    pub quota_policy: QuotaPolicy,
+    /// How saves treat a session id that was never created and holds no memories
+    #[serde(default)]
+    pub session_policy: SessionPolicy,
+    /// Saves each user may make per minute, in bursts of up to that many;
+    /// `None` means unlimited. Over the limit, saves fail with [`RateLimited`]
+    #[serde(default)]
+    pub max_saves_per_minute: Option<u32>,
+    /// Recalls each user may make per minute, like `max_saves_per_minute`
+    #[serde(default)]
+    pub max_recalls_per_minute: Option<u32>,
+    /// Accept the [`ALL_USERS`] wildcard in `QueryFilter::user_ids`,
+    /// e.g. for admin tooling; otherwise such recalls fail
+    #[serde(default)]
+    pub allow_wildcard_recall: bool,
+    /// Keys callers must present, each limited to some users and scopes;
+    /// empty turns access control off (see [`auth`])
+    #[serde(default)]
+    pub api_keys: Vec<auth::ApiKey>,
+    /// Largest content a memory may have, in bytes; `None` means no limit
+    #[serde(default)]
+    pub max_content_bytes: Option<usize>,
+    /// How saves treat content over `max_content_bytes`
+    #[serde(default)]
+    pub content_limit_policy: ContentLimitPolicy,
+    /// Hours between reviews of each user by `run_due_reviews`; `None`
+    /// turns periodic reviews off (see [`review`])
+    #[serde(default)]
+    pub review_interval_hours: Option<u32>,
+    /// Whether reviews are emitted, stored as memories or both
+    #[serde(default)]
+    pub review_delivery: ReviewDelivery,
+    /// Every decay setting at once. When set, decay uses it in place of
+    /// `default_memory_ttl_hours` (as the maximum age), `importance_threshold`,
+    /// `compress_threshold`, `protect_threshold`, `enable_compression`,
+    /// `compression_similarity` and `max_memories_per_user`; saves still
+    /// check `max_memories_per_user` against `quota_policy`
+    #[serde(default)]
+    pub decay_policy: Option<DecayPolicy>,
+}
+
+fn default_memory_cache_capacity() -> usize {
+    cache::DEFAULT_CACHE_CAPACITY
+}
+
+fn default_write_batch_size() -> usize {
+    1
+}
+
+fn default_write_batch_delay_ms() -> u64 {
+    50
+}
+
+fn default_hot_tier_min_importance() -> f32 {
+    HotTierPolicy::default().min_importance
+}
+
+fn default_hot_tier_recent_hours() -> u32 {
+    HotTierPolicy::default().recent_hours
+}
+
+fn default_hot_tier_max_memories() -> usize {
+    HotTierPolicy::default().max_memories
+}
+
+fn default_compression_similarity() -> f32 {
+    cluster::DEFAULT_CLUSTER_SIMILARITY
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_trash_retention_days() -> u32 {
+    30
 }
 
 impl Default for MindCacheConfig {
@@ -43,8 +343,39 @@ impl Default for MindCacheConfig {
             decay_interval_hours: 24,
             default_memory_ttl_hours: Some(24 * 30), // 30 days
             enable_compression: true,
+            compression_similarity: default_compression_similarity(),
             max_memories_per_user: 10000,
             importance_threshold: 0.3,
+            compress_threshold: None,
+            protect_threshold: None,
+            memory_cache_capacity: cache::DEFAULT_CACHE_CAPACITY,
+            write_batch_size: default_write_batch_size(),
+            write_batch_delay_ms: default_write_batch_delay_ms(),
+            hot_tier_url: None,
+            hot_tier_min_importance: default_hot_tier_min_importance(),
+            hot_tier_recent_hours: default_hot_tier_recent_hours(),
+            hot_tier_max_memories: default_hot_tier_max_memories(),
+            change_log_enabled: false,
+            history_enabled: false,
+            audit_log_enabled: false,
+            trash_retention_days: default_trash_retention_days(),
+            auto_importance_enabled: true,
+            entity_extraction_enabled: false,
+            stop_word_language: None,
+            extra_stop_words: Vec::new(),
+            read_only: false,
+            replica_path: None,
+            quota_policy: QuotaPolicy::Decay,
+            session_policy: SessionPolicy::Implicit,
+            max_saves_per_minute: None,
+            max_recalls_per_minute: None,
+            allow_wildcard_recall: false,
+            api_keys: Vec::new(),
+            max_content_bytes: None,
+            content_limit_policy: ContentLimitPolicy::Reject,
+            review_interval_hours: None,
+            review_delivery: ReviewDelivery::Event,
+            decay_policy: None,
         }
     }
 }
@@ -56,48 +387,185 @@ impl MindCache {
     }
 
     /// Create a new MindCache instance with custom configuration
+    ///
+    /// Fails with [`InvalidConfig`] if the config does not validate, and with
+    /// [`StorageLocked`] if another writer has the directory open, unless
+    /// `config.read_only` is set.
     pub fn with_config(config: MindCacheConfig) -> Result<Self, Box<dyn std::error::Error>> {
-        let storage = MemoryStorage::new(&config.storage_path)?;
-        let session_manager = SessionManager::new(storage.clone());
-        
-        let decay_policy = DecayPolicy {
-            max_age_hours: config.default_memory_ttl_hours.unwrap_or(24 * 30),
-            importance_threshold: config.importance_threshold,
-            max_memories_per_user: config.max_memories_per_user,
-            compression_enabled: config.enable_compression,
-            auto_summarize_sessions: true,
+        config.validate()?;
+        let mode = if config.read_only { AccessMode::ReadOnly } else { AccessMode::ReadWrite };
+        let backend = FileBackend::open(&config.storage_path, mode)?;
+        Self::with_backend(config, Arc::new(backend))
+    }
+
+    /// Create a MindCache instance on top of a custom storage backend
+    ///
+    /// `config.storage_path` is kept for reference but not used to open files.
+    /// With `config.replica_path` set, the backend's writes are replicated
+    /// to that directory.
+    pub fn with_backend(
+        config: MindCacheConfig,
+        backend: Arc<dyn StorageBackend>,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let (backend, replicating) = match &config.replica_path {
+            Some(replica_path) => {
+                let replicating = Arc::new(ReplicatingBackend::new(backend, replica_path)?);
+                (Arc::clone(&replicating) as Arc<dyn StorageBackend>, Some(replicating))
+            }
+            None => (backend, None),
         };
+        let storage = MemoryStorage::with_backend(backend, config.memory_cache_capacity)?;
+        let stop_words = Self::stop_words_for(&config);
+        storage.set_stop_words(stop_words.clone());
+        if config.change_log_enabled {
+            storage.changes().enable()?;
+        }
+        if config.history_enabled {
+            storage.history().enable()?;
+        }
+        if config.audit_log_enabled {
+            storage.audit().enable()?;
+        }
+        storage.set_hot_tier(Self::hot_tier_for(&config)?, Self::hot_tier_policy(&config));
+        let session_manager = SessionManager::new(storage.clone());
 
         // Fix: Clone the session_manager instead of moving it
         let decay_engine = MemoryDecayEngine::with_policy(
             storage.clone(),
             session_manager.clone(), // Fix: clone here
-            decay_policy
+            Self::decay_policy_for(&config)
         );
 
-        Ok(MindCache {
+        let mut cache = MindCache {
             storage,
             session_manager,
             decay_engine,
             config,
-        })
+            importance_scorer: Arc::new(HeuristicScorer::default()),
+            entity_extractor: Arc::new(RuleBasedExtractor::new(stop_words)),
+            fact_extractor: Arc::new(RuleBasedFactExtractor),
+            expiry_hook: None,
+            summarizer: Arc::new(ExtractiveSummarizer),
+            config_watcher: None,
+            save_limiter: ratelimit::RateLimiter::new("save"),
+            recall_limiter: ratelimit::RateLimiter::new("recall"),
+            replication: None,
+            flusher: None,
+        };
+        cache.apply_write_batching()?;
+        if let Some(replicating) = replicating {
+            // Nobody else has the instance yet, so no write can slip in between
+            replicating.seed(&cache.replicated_file_names())?;
+            cache.replication = Some(replicating.replicator());
+        }
+        Ok(cache)
+    }
+
+
+    fn decay_policy_for(config: &MindCacheConfig) -> DecayPolicy {
+        if let Some(policy) = &config.decay_policy {
+            return policy.clone();
+        }
+        DecayPolicy {
+            max_age_hours: config.default_memory_ttl_hours.unwrap_or(24 * 30),
+            delete_below: config.importance_threshold,
+            compress_below: config.compress_threshold.unwrap_or(config.importance_threshold),
+            protect_above: config.protect_threshold.unwrap_or(1.0),
+            max_memories_per_user: config.max_memories_per_user,
+            compression_enabled: config.enable_compression,
+            cluster_similarity: config.compression_similarity,
+            auto_summarize_sessions: true,
+        }
+    }
+
+    /// Hand the batching settings to storage, running the flusher while saves are batched
+    fn apply_write_batching(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        let policy = batching::BatchPolicy {
+            max_writes: self.config.write_batch_size,
+            max_delay: std::time::Duration::from_millis(self.config.write_batch_delay_ms),
+        };
+        self.storage.write_buffer().set_policy(policy);
+        if !policy.is_batching() || self.config.read_only {
+            // Dropping the flusher persists anything still pending
+            self.flusher = None;
+            return self.storage.flush();
+        }
+        if self.flusher.is_none() {
+            self.flusher = Some(batching::Flusher::start(self.storage.clone(), policy.max_delay)?);
+        }
+        Ok(())
+    }
+
+    /// Persist saves still pending in the write batch (see [`batching`])
+    ///
+    /// A no-op unless `write_batch_size` is above 1; call it before relying
+    /// on a batched save surviving a crash.
+    pub fn flush(&self) -> Result<(), Box<dyn std::error::Error>> {
+        self.storage.flush()
+    }
+
+    fn hot_tier_policy(config: &MindCacheConfig) -> HotTierPolicy {
+        HotTierPolicy {
+            min_importance: config.hot_tier_min_importance,
+            recent_hours: config.hot_tier_recent_hours,
+            max_memories: config.hot_tier_max_memories,
+        }
+    }
+
+    /// Connect to the hot tier named by `hot_tier_url`, if any
+    fn hot_tier_for(config: &MindCacheConfig) -> Result<Option<Arc<dyn HotTier>>, Box<dyn std::error::Error>> {
+        let Some(url) = &config.hot_tier_url else { return Ok(None) };
+        #[cfg(feature = "redis")]
+        {
+            Ok(Some(Arc::new(RedisTier::open(url)?)))
+        }
+        #[cfg(not(feature = "redis"))]
+        {
+            Err(format!("hot_tier_url {} needs the redis feature", url).into())
+        }
+    }
+
+    fn stop_words_for(config: &MindCacheConfig) -> StopWords {
+        StopWords::new(config.stop_word_language, &config.extra_stop_words)
+    }
+
+    /// Stop words this instance skips
+    pub fn stop_words(&self) -> StopWords {
+        self.storage.stop_words()
     }
 
+    /// Drop session and decay state derived from the old store contents
+    fn reset_derived_state(&mut self) {
+        self.session_manager = SessionManager::new(self.storage.clone());
+        self.decay_engine = MemoryDecayEngine::with_policy(
+            self.storage.clone(),
+            self.session_manager.clone(),
+            Self::decay_policy_for(&self.config),
+        );
+        self.session_manager.set_summarizer(self.summarizer.clone());
+        self.decay_engine.set_summarizer(self.summarizer.clone());
+        self.decay_engine.set_expiry_hook(self.expiry_hook.clone());
+    }
 
     /// Save a memory item
+    ///
+    /// Importance is estimated by the importance scorer (see
+    /// `set_importance_scorer`) unless `auto_importance_enabled` is off.
     pub fn save(&mut self, user_id: &str, session_id: &str, content: &str, metadata: Option<HashMap<String, String>>) -> Result<String, Box<dyn std::error::Error>> {
-        let memory = MemoryItem {
+        let mut memory = MemoryItem {
             id: String::new(), // Will be generated by storage
             user_id: user_id.to_string(),
             session_id: session_id.to_string(),
             content: content.to_string(),
             metadata: metadata.unwrap_or_default(),
-            timestamp: Utc::now(),
+            timestamp: self.storage.now(),
             ttl_hours: self.config.default_memory_ttl_hours,
             importance: 0.5, // Default importance
+            ..Default::default()
         };
+        memory.importance = self.default_importance(&memory);
 
-        self.storage.save(memory)
+        self.store(memory)
     }
 
     /// Save a memory item with custom importance and TTL
@@ -109,12 +577,145 @@ impl MindCache {
             session_id: session_id.to_string(),
             content: content.to_string(),
             metadata: metadata.unwrap_or_default(),
-            timestamp: Utc::now(),
+            timestamp: self.storage.now(),
             ttl_hours,
             importance: importance.clamp(0.0, 1.0),
+            ..Default::default()
+        };
+
+        self.store(memory)
+    }
+
+    /// Save a conversation turn spoken by `role` ("user", "assistant" or "system")
+    pub fn save_message(&mut self, user_id: &str, session_id: &str, role: &str, content: &str, importance: f32) -> Result<String, Box<dyn std::error::Error>> {
+        let memory = MemoryItem {
+            id: String::new(),
+            user_id: user_id.to_string(),
+            session_id: session_id.to_string(),
+            content: content.to_string(),
+            metadata: HashMap::new(),
+            timestamp: self.storage.now(),
+            ttl_hours: self.config.default_memory_ttl_hours,
+            importance: importance.clamp(0.0, 1.0),
+            role: Some(role.to_string()),
+            memory_type: MemoryType::Message,
+            ..Default::default()
+        };
+
+        self.store(memory)
+    }
+
+    /// Save a memory of a specific type, e.g. a fact or a task
+    pub fn save_typed(&mut self, user_id: &str, session_id: &str, content: &str, memory_type: MemoryType,
+                      metadata: Option<HashMap<String, String>>) -> Result<String, Box<dyn std::error::Error>> {
+        let mut memory = MemoryItem {
+            id: String::new(),
+            user_id: user_id.to_string(),
+            session_id: session_id.to_string(),
+            content: content.to_string(),
+            metadata: metadata.unwrap_or_default(),
+            timestamp: self.storage.now(),
+            ttl_hours: self.config.default_memory_ttl_hours,
+            importance: 0.5,
+            role: None,
+            memory_type,
+            ..Default::default()
+        };
+        memory.importance = self.default_importance(&memory);
+
+        self.store(memory)
+    }
+
+    /// Save a memory after making room for it under the user's quota,
+    /// returning the id of the saved memory or of its first chunk
+    fn store(&mut self, memory: MemoryItem) -> Result<String, Box<dyn std::error::Error>> {
+        self.save_limiter.check(&memory.user_id, self.config.max_saves_per_minute)?;
+        self.admit_session(&memory.user_id, &memory.session_id)?;
+        let mut ids = Vec::new();
+        for piece in self.prepare(memory)? {
+            self.enforce_quota(&piece.user_id)?;
+            ids.push(self.storage.save(piece)?);
+        }
+        self.link_chunks(&ids)?;
+        Ok(ids.swap_remove(0))
+    }
+
+    /// What a save stores for `memory`: the memories the content limit
+    /// leaves, raised to the session's importance floor and annotated with
+    /// extracted entities
+    pub(crate) fn prepare(&self, mut memory: MemoryItem) -> Result<Vec<MemoryItem>, Box<dyn std::error::Error>> {
+        if let Some(floor) = self.storage.session_retention().get(&memory.session_id).importance_floor {
+            memory.importance = memory.importance.max(floor);
+        }
+        let mut pieces = self.limit_content(memory)?;
+        if self.config.entity_extraction_enabled {
+            for piece in &mut pieces {
+                let entities = self.entity_extractor.extract(&piece.content);
+                extraction::annotate(piece, &entities);
+            }
+        }
+        Ok(pieces)
+    }
+
+    /// Apply `session_policy` to a save into `session_id`
+    pub(crate) fn admit_session(&mut self, user_id: &str, session_id: &str) -> Result<(), Box<dyn std::error::Error>> {
+        if self.config.session_policy == SessionPolicy::Implicit || self.session_manager.has_session(user_id, session_id)? {
+            return Ok(());
+        }
+        if self.config.session_policy == SessionPolicy::Strict {
+            return Err(Box::new(UnknownSession { user_id: user_id.to_string(), session_id: session_id.to_string() }));
+        }
+        let metadata = HashMap::from([(session::AUTO_CREATED_KEY.to_string(), "true".to_string())]);
+        self.session_manager.register_session(user_id, session_id, None, metadata)?;
+        println!("Registered session {} for user {} on first save", session_id, user_id);
+        Ok(())
+    }
+
+    /// Replace the scorer used for memories saved without an importance
+    pub fn set_importance_scorer<S: ImportanceScorer + 'static>(&mut self, scorer: S) {
+        self.importance_scorer = Arc::new(scorer);
+    }
+
+    /// Replace the summarizer used by `summarize_session` and decay compression
+    ///
+    /// Use a closure for a synchronous LLM call, or
+    /// `summarizer::from_async` to wrap an async one.
+    pub fn set_summarizer<S: Summarizer + 'static>(&mut self, summarizer: S) {
+        self.summarizer = Arc::new(summarizer);
+        self.session_manager.set_summarizer(self.summarizer.clone());
+        self.decay_engine.set_summarizer(self.summarizer.clone());
+    }
+
+    /// Importance `save` would give `content` right now: the scorer's
+    /// estimate, or 0.5 with `auto_importance_enabled` off
+    pub fn default_importance_for(&self, user_id: &str, session_id: &str, content: &str) -> f32 {
+        let memory = MemoryItem {
+            user_id: user_id.to_string(),
+            session_id: session_id.to_string(),
+            content: content.to_string(),
+            timestamp: self.storage.now(),
+            importance: 0.5,
+            ..Default::default()
         };
+        self.default_importance(&memory)
+    }
+
+    fn score(&self, memory: &MemoryItem) -> f32 {
+        // A failed lookup only costs the novelty signal, never the save
+        let similar = self.storage
+            .most_similar(&memory.user_id, &memory.content, importance::SIMILAR_MEMORY_LIMIT)
+            .unwrap_or_default();
+        self.importance_scorer
+            .score(memory, &ScoringContext { similar: &similar })
+            .clamp(0.0, 1.0)
+    }
 
-        self.storage.save(memory)
+    fn default_importance(&self, memory: &MemoryItem) -> f32 {
+        if self.config.auto_importance_enabled {
+            self.score(memory)
+        } else {
+            memory.importance
+        }
     }
 
     /// Recall memories with flexible filtering
@@ -133,16 +734,65 @@ impl MindCache {
             date_to: None,
             limit,
             min_importance: None,
+            ..Default::default()
         };
 
-        self.storage.recall(filter)
+        self.recall_advanced(filter)
     }
 
     /// Recall memories with advanced filtering
     pub fn recall_advanced(&self, filter: QueryFilter) -> Result<Vec<MemoryItem>, Box<dyn std::error::Error>> {
+        self.check_recall(&filter)?;
         self.storage.recall(filter)
     }
 
+    /// Recall results read one at a time instead of all at once
+    pub fn recall_iter(&self, filter: QueryFilter) -> Result<RecallIter, Box<dyn std::error::Error>> {
+        self.check_recall(&filter)?;
+        self.storage.recall_iter(filter)
+    }
+
+    /// Refuse a wildcard recall unless allowed, then count the recall
+    /// against the rate limit of each user it names
+    fn check_recall(&self, filter: &QueryFilter) -> Result<(), Box<dyn std::error::Error>> {
+        if filter.is_wildcard() && !self.config.allow_wildcard_recall {
+            return Err("recalling across all users needs allow_wildcard_recall".into());
+        }
+        Ok(self.check_recall_rate(filter)?)
+    }
+
+    /// Count a recall against its users' rate limits; queries across all
+    /// users are not limited
+    fn check_recall_rate(&self, filter: &QueryFilter) -> Result<(), RateLimited> {
+        for user_id in filter.users().unwrap_or_default() {
+            self.recall_limiter.check(user_id, self.config.max_recalls_per_minute)?;
+        }
+        Ok(())
+    }
+
+    /// Number of memories `recall_advanced` would return, without reading
+    /// them where the indexes can answer
+    pub fn count(&self, filter: QueryFilter) -> Result<usize, Box<dyn std::error::Error>> {
+        self.check_recall(&filter)?;
+        self.storage.count(filter)
+    }
+
+    /// Whether any memory matches a filter, e.g. to check a fact is already noted
+    pub fn exists(&self, filter: QueryFilter) -> Result<bool, Box<dyn std::error::Error>> {
+        self.check_recall(&filter)?;
+        self.storage.exists(filter)
+    }
+
+    /// Look up a single memory by id
+    pub fn get_memory(&self, id: &str) -> Result<Option<MemoryItem>, Box<dyn std::error::Error>> {
+        self.storage.get_memory(id)
+    }
+
+    /// Show how `recall_advanced` would execute a filter without running it
+    pub fn explain(&self, filter: &QueryFilter) -> QueryPlan {
+        self.storage.explain(filter)
+    }
+
     /// Get memories for a specific session
     pub fn get_session_memories(&self, user_id: &str, session_id: &str) -> Result<Vec<MemoryItem>, Box<dyn std::error::Error>> {
         // Use the main storage instead of session manager's storage
@@ -164,33 +814,108 @@ impl MindCache {
         self.session_manager.generate_session_summary(session_id)
     }
 
+    /// Stored session summaries matching `filter`, newest first
+    ///
+    /// User, date, keyword and importance filters apply to the summaries
+    /// themselves; `session_id` picks the summary of that session.
+    pub fn recall_summaries(&self, mut filter: QueryFilter) -> Result<Vec<SessionSummary>, Box<dyn std::error::Error>> {
+        if let Some(session_id) = filter.session_id.take() {
+            filter.metadata_filters.insert(session::SUMMARIZED_SESSION_KEY.to_string(), MetadataCondition::Equals(session_id));
+        }
+        filter.session_id = Some(SUMMARY_SESSION.to_string());
+        filter.memory_type = Some(MemoryType::Summary);
+        let memories = self.recall_advanced(filter)?;
+        Ok(memories.iter().filter_map(SessionSummary::from_memory).collect())
+    }
+
     /// Search sessions by content
     pub fn search_sessions(&mut self, user_id: &str, keywords: Vec<String>) -> Result<Vec<Session>, Box<dyn std::error::Error>> {
         self.session_manager.search_sessions(user_id, keywords)
     }
 
+    /// Search sessions by content, ranked, with the memories that matched
+    pub fn search_sessions_ranked(&mut self, user_id: &str, keywords: Vec<String>, max_hits: usize) -> Result<Vec<SessionMatch>, Box<dyn std::error::Error>> {
+        self.session_manager.search_sessions_ranked(user_id, keywords, max_hits)
+    }
+
     /// Run memory decay process
     pub fn decay(&mut self) -> Result<DecayStats, Box<dyn std::error::Error>> {
-        self.decay_engine.run_decay()
+        let now = self.storage.now();
+        let started = std::time::Instant::now();
+        let result = self.decay_engine.run_decay().and_then(|stats| {
+            let purged = self.purge_expired_trash(now)?;
+            Ok((stats, purged))
+        });
+        let elapsed = started.elapsed();
+        self.storage.metrics().record_decay(elapsed, result.is_ok());
+
+        let mut run = DecayRun {
+            started_at: now,
+            finished_at: now + chrono::Duration::from_std(elapsed).unwrap_or_default(),
+            stats: None,
+            affected: self.decay_engine.last_run_affected().clone(),
+            error: None,
+        };
+        match &result {
+            Ok((stats, purged)) => {
+                run.stats = Some(stats.clone());
+                run.affected.trash_purged_ids = purged.clone();
+            }
+            Err(e) => run.error = Some(e.to_string()),
+        }
+        if let Err(e) = self.storage.decay_journal().append(&run) {
+            println!("Failed to journal decay run: {}", e);
+        }
+        result.map(|(stats, _)| stats)
     }
 
-    /// Get storage and decay statistics
-    pub fn get_stats(&self) -> HashMap<String, serde_json::Value> {
-        let mut stats = HashMap::new();
-        
-        // Storage stats
-        let storage_stats = self.storage.get_stats();
-        stats.insert("storage".to_string(), serde_json::to_value(storage_stats).unwrap());
-        
-        // Session stats
-        let session_stats = self.session_manager.get_session_stats();
-        stats.insert("sessions".to_string(), serde_json::to_value(session_stats).unwrap());
-        
-        // Decay stats
-        let decay_stats = self.decay_engine.get_stats();
-        stats.insert("decay".to_string(), serde_json::to_value(decay_stats).unwrap());
-        
-        stats
+    /// What a decay run at `as_of` would do, e.g. a month ahead to see what
+    /// would expire or be compressed by then
+    ///
+    /// A dry run: TTLs, decay cutoffs and trash retention are judged at
+    /// `as_of`, but nothing is deleted, compressed, summarized or purged,
+    /// journaled, audited or emitted, and the expiry hook is not consulted.
+    pub fn decay_at(&mut self, as_of: DateTime<Utc>) -> Result<DecayReport, Box<dyn std::error::Error>> {
+        let (stats, mut affected) = self.decay_engine.plan_decay_at(as_of)?;
+        affected.trash_purged_ids = self.expired_trash(as_of);
+        Ok(DecayReport { as_of, stats, affected })
+    }
+
+    /// Operation counters, latency histograms and store gauges for monitoring
+    ///
+    /// `MetricsSnapshot::to_prometheus` renders them for a scrape endpoint.
+    pub fn metrics(&self) -> Result<MetricsSnapshot, Box<dyn std::error::Error>> {
+        Ok(self.storage.metrics().snapshot(self.storage.store_gauges()?))
+    }
+
+    /// Format version of the store on disk; see [`migrations`]
+    pub fn storage_version(&self) -> Result<u32, Box<dyn std::error::Error>> {
+        self.storage.storage_version()
+    }
+
+    /// Get storage, session, decay, index and file statistics
+    ///
+    /// Fails only if the store's file sizes cannot be read.
+    pub fn get_stats(&self) -> Result<MindCacheStats, Box<dyn std::error::Error>> {
+        Ok(MindCacheStats {
+            storage: self.storage.get_stats(),
+            sessions: self.session_manager.get_session_stats(),
+            decay: self.decay_engine.get_stats().clone(),
+            cache: self.storage.cache_stats(),
+            index: self.storage.index_stats(),
+            files: self.storage.file_stats()?,
+            disk: self.storage.disk_usage(),
+        })
+    }
+
+    /// Bytes on disk taken by a user's memories, e.g. for billing or quotas
+    pub fn user_disk_usage(&self, user_id: &str) -> u64 {
+        self.storage.user_disk_usage(user_id)
+    }
+
+    /// Bytes on disk taken by the memories of one of a user's sessions
+    pub fn session_disk_usage(&self, user_id: &str, session_id: &str) -> u64 {
+        self.storage.session_disk_usage(user_id, session_id)
     }
 
     /// Export all memories for a user (for backup/migration)
@@ -203,6 +928,8 @@ impl MindCache {
             date_to: None,
             limit: None,
             min_importance: None,
+            include_superseded: true,
+            ..Default::default()
         };
 
         let memories = self.storage.recall(filter)?;
@@ -210,19 +937,159 @@ impl MindCache {
         Ok(export_data)
     }
 
+    /// Import memories produced by `export_user_memories`
+    ///
+    /// Ids, timestamps and importance are kept as exported. Memories whose id
+    /// this store already holds, or has in its trash, are skipped, so
+    /// importing the same export again adds nothing. Returns the number of
+    /// memories imported.
+    pub fn import_memories(&mut self, export_data: &str) -> Result<usize, Box<dyn std::error::Error>> {
+        let memories: Vec<MemoryItem> = serde_json::from_str(export_data)?;
+        let mut imported = 0;
+        for memory in memories {
+            if !memory.id.is_empty()
+                && (self.storage.trash().contains(&memory.id) || self.storage.get_memory(&memory.id)?.is_some())
+            {
+                continue;
+            }
+            self.storage.import(memory)?;
+            imported += 1;
+        }
+        Ok(imported)
+    }
+
+    /// Rewrite the memory log without unreferenced records
+    pub fn compact(&mut self) -> Result<CompactionReport, Box<dyn std::error::Error>> {
+        self.storage.compact()
+    }
+
+    /// Rewrite only one user's segment log, e.g. after a bulk delete
+    pub fn compact_user(&mut self, user_id: &str) -> Result<CompactionReport, Box<dyn std::error::Error>> {
+        self.storage.compact_user(user_id)
+    }
+
+    /// Bytes held by deleted, superseded or orphaned records that `compact` would free
+    pub fn reclaimable_bytes(&self) -> Result<u64, Box<dyn std::error::Error>> {
+        self.storage.reclaimable_bytes()
+    }
+
+    /// Register an observer for memory lifecycle events
+    ///
+    /// Accepts a `MemoryObserver` or any `Fn(&MemoryEvent) + Send + Sync` closure.
+    pub fn subscribe<O: MemoryObserver + 'static>(&self, observer: O) -> SubscriptionId {
+        self.storage.events().subscribe(Arc::new(observer))
+    }
+
+    /// Remove an observer; returns false if it was not registered
+    pub fn unsubscribe(&self, id: SubscriptionId) -> bool {
+        self.storage.events().unsubscribe(id)
+    }
+
+    /// Changes recorded after `sequence`, oldest first
+    ///
+    /// Pass 0 to read the whole log, then the last `sequence` seen to continue.
+    /// Needs `change_log_enabled`; changes made while it was off are not recorded.
+    pub fn changes_since(&self, sequence: u64) -> Result<Vec<ChangeRecord>, Box<dyn std::error::Error>> {
+        self.storage.changes().since(sequence)
+    }
+
+    /// Earlier versions of a memory, oldest first
+    ///
+    /// Needs `history_enabled`; versions replaced while it was off are not kept.
+    pub fn memory_history(&self, memory_id: &str) -> Result<Vec<SupersededVersion>, Box<dyn std::error::Error>> {
+        self.storage.history().versions_of(memory_id)
+    }
+
+    /// Sequence number of the most recent recorded change (0 if none)
+    pub fn latest_change_sequence(&self) -> u64 {
+        self.storage.changes().latest_sequence()
+    }
+
+    /// Where a derived item (summary, compressed memory, dedupe merge) came from
+    pub fn get_provenance(&self, id: &str) -> Option<ProvenanceRecord> {
+        self.storage.provenance().get(id)
+    }
+
+    /// Items derived from a memory, oldest first
+    pub fn get_derivatives(&self, source_id: &str) -> Vec<ProvenanceRecord> {
+        self.storage.provenance().derived_from(source_id)
+    }
+
+    /// Record that `derived_id` was produced from `derived_from`
+    ///
+    /// For items the application derives itself, e.g. facts extracted by an LLM.
+    pub fn record_provenance(
+        &mut self,
+        derived_id: &str,
+        derived_from: Vec<String>,
+        method: DerivationMethod,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.storage.provenance().record(ProvenanceRecord {
+            derived_id: derived_id.to_string(),
+            derived_from,
+            method,
+            created_at: self.storage.now(),
+        })
+    }
+
+    /// Get the active configuration
+    pub fn config(&self) -> &MindCacheConfig {
+        &self.config
+    }
+
+    /// The policy decay runs with
+    pub fn decay_policy(&self) -> DecayPolicy {
+        Self::decay_policy_for(&self.config)
+    }
+
+    /// Run decay with `policy` from now on, in place of the decay fields of
+    /// the config; it is kept as the config's `decay_policy`
+    pub fn set_decay_policy(&mut self, policy: DecayPolicy) -> Result<(), Box<dyn std::error::Error>> {
+        policy.validate()?;
+        self.decay_engine.update_policy(policy.clone());
+        self.config.decay_policy = Some(policy);
+        Ok(())
+    }
+
     /// Update configuration
     pub fn update_config(&mut self, config: MindCacheConfig) -> Result<(), Box<dyn std::error::Error>> {
+        config.validate()?;
+        if config.read_only != self.config.read_only {
+            return Err("read_only cannot change on an open instance; reopen it instead".into());
+        }
+        if config.replica_path != self.config.replica_path {
+            return Err("replica_path cannot change on an open instance; reopen it instead".into());
+        }
         // Update decay policy based on new config
-        let decay_policy = DecayPolicy {
-            max_age_hours: config.default_memory_ttl_hours.unwrap_or(24 * 30),
-            importance_threshold: config.importance_threshold,
-            max_memories_per_user: config.max_memories_per_user,
-            compression_enabled: config.enable_compression,
-            auto_summarize_sessions: true,
-        };
-
-        self.decay_engine.update_policy(decay_policy);
+        self.decay_engine.update_policy(Self::decay_policy_for(&config));
+        self.storage.set_cache_capacity(config.memory_cache_capacity);
+        if config.hot_tier_url != self.config.hot_tier_url {
+            self.storage.set_hot_tier(Self::hot_tier_for(&config)?, Self::hot_tier_policy(&config));
+        } else {
+            self.storage.set_hot_tier_policy(Self::hot_tier_policy(&config));
+        }
+        self.storage.set_stop_words(Self::stop_words_for(&config));
+        if config.change_log_enabled {
+            self.storage.changes().enable()?;
+        } else {
+            self.storage.changes().disable();
+        }
+        if config.history_enabled {
+            self.storage.history().enable()?;
+        } else {
+            self.storage.history().disable();
+        }
+        if config.audit_log_enabled {
+            self.storage.audit().enable()?;
+        } else {
+            self.storage.audit().disable();
+        }
+        let restart_flusher = config.write_batch_delay_ms != self.config.write_batch_delay_ms;
         self.config = config;
+        if restart_flusher {
+            self.flusher = None;
+        }
+        self.apply_write_batching()?;
         
         Ok(())
     }
@@ -231,36 +1098,166 @@ impl MindCache {
 // C API for FFI integration with Node.js
 // These functions provide a C-compatible interface for the Node.js bridge
 
+/// Version of the C ABI, bumped when an existing function changes its
+/// signature or meaning. New functions don't bump it; bindings find them in
+/// `mindcache_features()` instead.
+pub const ABI_VERSION: u32 = 1;
+
+/// Every function of the C API, reported by `mindcache_features()`
+const C_FUNCTIONS: &[&str] = &[
+    "mindcache_abi_version",
+    "mindcache_features",
+    "mindcache_last_error",
+    "mindcache_init",
+    "mindcache_init_with_config",
+    "mindcache_save",
+    "mindcache_save_ex",
+    "mindcache_recall",
+    "mindcache_recall_advanced",
+    "mindcache_recall_open",
+    "mindcache_recall_next",
+    "mindcache_recall_close",
+    "mindcache_summarize",
+    "mindcache_decay",
+    "mindcache_dedupe",
+    "mindcache_compact",
+    "mindcache_save_with_key",
+    "mindcache_recall_advanced_with_key",
+    "mindcache_delete_user_with_key",
+    "mindcache_decay_with_key",
+    "mindcache_compact_with_key",
+    "mindcache_decay_async",
+    "mindcache_compact_async",
+    "mindcache_export_async",
+    "mindcache_create_backup",
+    "mindcache_restore_backup",
+    "mindcache_get_stats",
+    "mindcache_free_string",
+    "mindcache_destroy",
+];
+
+/// Every optional cargo feature, and whether this build has it
+const CARGO_FEATURES: &[(&str, bool)] = &[
+    ("compression", cfg!(feature = "compression")),
+    ("logging", cfg!(feature = "logging")),
+    ("daemon", cfg!(feature = "daemon")),
+    ("server", cfg!(feature = "server")),
+    ("grpc", cfg!(feature = "grpc")),
+    ("s3", cfg!(feature = "s3")),
+    ("redis", cfg!(feature = "redis")),
+    ("cli", cfg!(feature = "cli")),
+    ("agent", cfg!(feature = "agent")),
+    ("demo", cfg!(feature = "demo")),
+    ("benchmarks", cfg!(feature = "benchmarks")),
+    ("bench", cfg!(feature = "bench")),
+];
+
+/// Version of the C ABI this library implements
+#[no_mangle]
+pub extern "C" fn mindcache_abi_version() -> u32 {
+    ABI_VERSION
+}
+
+/// What this build of the library supports, as JSON
+///
+/// `{"abi_version": 1, "version": "0.1.0", "functions": [...], "features": [...]}`
+/// where `features` lists the optional cargo features compiled in. Free the
+/// result with `mindcache_free_string`.
+#[no_mangle]
+pub extern "C" fn mindcache_features() -> *mut c_char {
+    let features: Vec<&str> = CARGO_FEATURES
+        .iter()
+        .filter_map(|&(name, enabled)| enabled.then_some(name))
+        .collect();
+
+    let capabilities = serde_json::json!({
+        "abi_version": ABI_VERSION,
+        "version": env!("CARGO_PKG_VERSION"),
+        "functions": C_FUNCTIONS,
+        "features": features,
+    });
+    match CString::new(capabilities.to_string()) {
+        Ok(c_string) => c_string.into_raw(),
+        Err(_) => std::ptr::null_mut(),
+    }
+}
+
+thread_local! {
+    static LAST_ERROR: std::cell::RefCell<Option<String>> = const { std::cell::RefCell::new(None) };
+}
+
+fn set_last_error(error: impl ToString) {
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(error.to_string()));
+}
+
+fn clear_last_error() {
+    LAST_ERROR.with(|last| *last.borrow_mut() = None);
+}
+
+/// Why the last failed `mindcache_init*` or `*_with_key` call on this thread failed
+///
+/// Null if the last call succeeded. For an invalid config the message lists
+/// every offending field. Free the result with `mindcache_free_string`.
+#[no_mangle]
+pub extern "C" fn mindcache_last_error() -> *mut c_char {
+    LAST_ERROR.with(|last| match last.borrow().as_deref() {
+        Some(message) => CString::new(message.replace('\0', " ")).map_or(std::ptr::null_mut(), CString::into_raw),
+        None => std::ptr::null_mut(),
+    })
+}
+
 /// Initialize MindCache with default config
 #[no_mangle]
 pub extern "C" fn mindcache_init() -> *mut MindCache {
     match MindCache::new() {
-        Ok(cache) => Box::into_raw(Box::new(cache)),
-        Err(_) => std::ptr::null_mut(),
+        Ok(cache) => {
+            clear_last_error();
+            Box::into_raw(Box::new(cache))
+        }
+        Err(e) => {
+            set_last_error(e);
+            std::ptr::null_mut()
+        }
     }
 }
 
 /// Initialize MindCache with config JSON string
+///
+/// Returns null if the JSON does not parse, the config does not validate or
+/// the store cannot be opened; `mindcache_last_error` says which.
 #[no_mangle]
 pub extern "C" fn mindcache_init_with_config(config_json: *const c_char) -> *mut MindCache {
     if config_json.is_null() {
+        set_last_error("config_json is null");
         return std::ptr::null_mut();
     }
 
     let c_str = unsafe { CStr::from_ptr(config_json) };
     let config_str = match c_str.to_str() {
         Ok(s) => s,
-        Err(_) => return std::ptr::null_mut(),
+        Err(e) => {
+            set_last_error(format!("config is not valid UTF-8: {}", e));
+            return std::ptr::null_mut();
+        }
     };
 
     let config: MindCacheConfig = match serde_json::from_str(config_str) {
         Ok(c) => c,
-        Err(_) => return std::ptr::null_mut(),
+        Err(e) => {
+            set_last_error(format!("config is not valid JSON: {}", e));
+            return std::ptr::null_mut();
+        }
     };
 
     match MindCache::with_config(config) {
-        Ok(cache) => Box::into_raw(Box::new(cache)),
-        Err(_) => std::ptr::null_mut(),
+        Ok(cache) => {
+            clear_last_error();
+            Box::into_raw(Box::new(cache))
+        }
+        Err(e) => {
+            set_last_error(e);
+            std::ptr::null_mut()
+        }
     }
 }
 
@@ -298,27 +1295,74 @@ pub extern "C" fn mindcache_save(
     }
 }
 
-/// Recall memories
+/// Save a memory with explicit importance (0.0 to 1.0) and TTL
+///
+/// `ttl_hours` of 0 saves a memory that never expires, whatever the decay
+/// policy; a negative TTL leaves its expiry to the policy. Unlike
+/// `mindcache_save`, metadata that is not a JSON object of strings is
+/// rejected. Returns the new memory id, or null on error.
 #[no_mangle]
-pub extern "C" fn mindcache_recall(
+pub extern "C" fn mindcache_save_ex(
     cache: *mut MindCache,
     user_id: *const c_char,
-    query: *const c_char,
     session_id: *const c_char,
-    limit: i32,
+    content: *const c_char,
+    metadata_json: *const c_char,
+    importance: f32,
+    ttl_hours: i32,
 ) -> *mut c_char {
-    if cache.is_null() || user_id.is_null() {
+    if cache.is_null() || user_id.is_null() || session_id.is_null() || content.is_null() || importance.is_nan() {
         return std::ptr::null_mut();
     }
 
-    let cache = unsafe { &*cache };
-    let user_id = unsafe { CStr::from_ptr(user_id).to_str().unwrap_or("") };
-    let query = if query.is_null() {
-        None
-    } else {
-        Some(unsafe { CStr::from_ptr(query).to_str().unwrap_or("") })
+    let cache = unsafe { &mut *cache };
+    let (user_id, session_id, content) = match unsafe {
+        (CStr::from_ptr(user_id).to_str(), CStr::from_ptr(session_id).to_str(), CStr::from_ptr(content).to_str())
+    } {
+        (Ok(user_id), Ok(session_id), Ok(content)) => (user_id, session_id, content),
+        _ => return std::ptr::null_mut(),
     };
-    let session_id = if session_id.is_null() {
+
+    let metadata: Option<HashMap<String, String>> = if metadata_json.is_null() {
+        None
+    } else {
+        match unsafe { CStr::from_ptr(metadata_json) }.to_str().map(serde_json::from_str) {
+            Ok(Ok(metadata)) => Some(metadata),
+            _ => return std::ptr::null_mut(),
+        }
+    };
+    let ttl_hours = u32::try_from(ttl_hours).ok();
+
+    match cache.save_with_options(user_id, session_id, content, metadata, importance, ttl_hours) {
+        Ok(id) => {
+            let c_string = CString::new(id).unwrap();
+            c_string.into_raw()
+        }
+        Err(_) => std::ptr::null_mut(),
+    }
+}
+
+/// Recall memories
+#[no_mangle]
+pub extern "C" fn mindcache_recall(
+    cache: *mut MindCache,
+    user_id: *const c_char,
+    query: *const c_char,
+    session_id: *const c_char,
+    limit: i32,
+) -> *mut c_char {
+    if cache.is_null() || user_id.is_null() {
+        return std::ptr::null_mut();
+    }
+
+    let cache = unsafe { &*cache };
+    let user_id = unsafe { CStr::from_ptr(user_id).to_str().unwrap_or("") };
+    let query = if query.is_null() {
+        None
+    } else {
+        Some(unsafe { CStr::from_ptr(query).to_str().unwrap_or("") })
+    };
+    let session_id = if session_id.is_null() {
         None
     } else {
         Some(unsafe { CStr::from_ptr(session_id).to_str().unwrap_or("") })
@@ -339,6 +1383,92 @@ pub extern "C" fn mindcache_recall(
     }
 }
 
+/// Recall memories matching a JSON QueryFilter; returns a JSON array
+///
+/// Every field is optional, e.g.
+/// `{"user_id": "alice", "date_from": "2024-01-01T00:00:00Z", "min_importance": 0.5,
+/// "metadata_filters": {"ticker": {"equals": "AAPL"}}, "offset": 20, "limit": 10}`.
+/// Returns null if the filter does not parse or recall fails.
+#[no_mangle]
+pub extern "C" fn mindcache_recall_advanced(cache: *mut MindCache, filter_json: *const c_char) -> *mut c_char {
+    if cache.is_null() || filter_json.is_null() {
+        return std::ptr::null_mut();
+    }
+
+    let cache = unsafe { &*cache };
+    let filter: QueryFilter = match unsafe { CStr::from_ptr(filter_json) }.to_str().map(serde_json::from_str) {
+        Ok(Ok(filter)) => filter,
+        _ => return std::ptr::null_mut(),
+    };
+
+    match cache.recall_advanced(filter) {
+        Ok(memories) => {
+            match serde_json::to_string(&memories) {
+                Ok(json) => {
+                    let c_string = CString::new(json).unwrap();
+                    c_string.into_raw()
+                }
+                Err(_) => std::ptr::null_mut(),
+            }
+        }
+        Err(_) => std::ptr::null_mut(),
+    }
+}
+
+/// Start a recall whose results are read one at a time with `mindcache_recall_next`
+///
+/// Takes the same JSON QueryFilter as `mindcache_recall_advanced`. Returns a
+/// cursor to pass to `mindcache_recall_close` when done, or null on error.
+/// Close every cursor before `mindcache_destroy`: an open cursor keeps the
+/// store open.
+#[no_mangle]
+pub extern "C" fn mindcache_recall_open(cache: *mut MindCache, filter_json: *const c_char) -> *mut RecallIter {
+    if cache.is_null() || filter_json.is_null() {
+        return std::ptr::null_mut();
+    }
+
+    let cache = unsafe { &*cache };
+    let filter: QueryFilter = match unsafe { CStr::from_ptr(filter_json) }.to_str().map(serde_json::from_str) {
+        Ok(Ok(filter)) => filter,
+        _ => return std::ptr::null_mut(),
+    };
+
+    match cache.recall_iter(filter) {
+        Ok(cursor) => Box::into_raw(Box::new(cursor)),
+        Err(_) => std::ptr::null_mut(),
+    }
+}
+
+/// Next memory of a recall cursor as a JSON object, or null when there are no more
+///
+/// Memories that were deleted or can't be read since the cursor was opened
+/// are skipped. Free each result with `mindcache_free_string`.
+#[no_mangle]
+pub extern "C" fn mindcache_recall_next(cursor: *mut RecallIter) -> *mut c_char {
+    if cursor.is_null() {
+        return std::ptr::null_mut();
+    }
+
+    let cursor = unsafe { &mut *cursor };
+    for memory in cursor.by_ref().flatten() {
+        if let Ok(json) = serde_json::to_string(&memory) {
+            let c_string = CString::new(json).unwrap();
+            return c_string.into_raw();
+        }
+    }
+    std::ptr::null_mut()
+}
+
+/// Release a recall cursor
+#[no_mangle]
+pub extern "C" fn mindcache_recall_close(cursor: *mut RecallIter) {
+    if !cursor.is_null() {
+        unsafe {
+            let _ = Box::from_raw(cursor);
+        }
+    }
+}
+
 /// Generate session summary
 #[no_mangle]
 pub extern "C" fn mindcache_summarize(
@@ -389,6 +1519,302 @@ pub extern "C" fn mindcache_decay(cache: *mut MindCache) -> *mut c_char {
     }
 }
 
+/// Remove near-duplicate memories of a user; returns the JSON DedupeReport
+#[no_mangle]
+pub extern "C" fn mindcache_dedupe(
+    cache: *mut MindCache,
+    user_id: *const c_char,
+    similarity_threshold: f32,
+    dry_run: i32,
+) -> *mut c_char {
+    if cache.is_null() || user_id.is_null() {
+        return std::ptr::null_mut();
+    }
+
+    let cache = unsafe { &mut *cache };
+    let user_id = match unsafe { CStr::from_ptr(user_id) }.to_str() {
+        Ok(s) => s,
+        Err(_) => return std::ptr::null_mut(),
+    };
+
+    match cache.dedupe(user_id, similarity_threshold, dry_run != 0) {
+        Ok(report) => {
+            match serde_json::to_string(&report) {
+                Ok(json) => {
+                    let c_string = CString::new(json).unwrap();
+                    c_string.into_raw()
+                }
+                Err(_) => std::ptr::null_mut(),
+            }
+        }
+        Err(_) => std::ptr::null_mut(),
+    }
+}
+
+/// Compact the memory log; returns the JSON CompactionReport
+#[no_mangle]
+pub extern "C" fn mindcache_compact(cache: *mut MindCache) -> *mut c_char {
+    if cache.is_null() {
+        return std::ptr::null_mut();
+    }
+
+    let cache = unsafe { &mut *cache };
+
+    match cache.compact() {
+        Ok(report) => {
+            match serde_json::to_string(&report) {
+                Ok(json) => {
+                    let c_string = CString::new(json).unwrap();
+                    c_string.into_raw()
+                }
+                Err(_) => std::ptr::null_mut(),
+            }
+        }
+        Err(_) => std::ptr::null_mut(),
+    }
+}
+
+/// A string argument of a C API call
+fn c_str_arg<'a>(value: *const c_char, name: &str) -> Result<&'a str, Box<dyn std::error::Error>> {
+    if value.is_null() {
+        return Err(format!("{} is null", name).into());
+    }
+    Ok(unsafe { CStr::from_ptr(value) }.to_str().map_err(|e| format!("{} is not valid UTF-8: {}", name, e))?)
+}
+
+/// Run `operation` on the cache as seen by `api_key`
+///
+/// Returns its result, or null with the reason in `mindcache_last_error`.
+fn call_with_key<F>(cache: *mut MindCache, api_key: *const c_char, operation: F) -> *mut c_char
+where
+    F: FnOnce(Scoped<'_>) -> Result<String, Box<dyn std::error::Error>>,
+{
+    if cache.is_null() {
+        set_last_error("cache is null");
+        return std::ptr::null_mut();
+    }
+    let cache = unsafe { &mut *cache };
+    let result = (|| {
+        let key = if api_key.is_null() { None } else { Some(c_str_arg(api_key, "api_key")?) };
+        let result = operation(cache.with_key(key)?)?;
+        Ok::<_, Box<dyn std::error::Error>>(CString::new(result)?)
+    })();
+    match result {
+        Ok(c_string) => {
+            clear_last_error();
+            c_string.into_raw()
+        }
+        Err(e) => {
+            set_last_error(e);
+            std::ptr::null_mut()
+        }
+    }
+}
+
+/// `mindcache_save`, if `api_key` may write the user's memories
+///
+/// A null `api_key` only works while no keys are configured. Returns the new
+/// memory id, or null with the reason in `mindcache_last_error`.
+#[no_mangle]
+pub extern "C" fn mindcache_save_with_key(
+    cache: *mut MindCache,
+    api_key: *const c_char,
+    user_id: *const c_char,
+    session_id: *const c_char,
+    content: *const c_char,
+    metadata_json: *const c_char,
+) -> *mut c_char {
+    call_with_key(cache, api_key, |mut scoped| {
+        let metadata = if metadata_json.is_null() {
+            None
+        } else {
+            Some(serde_json::from_str(c_str_arg(metadata_json, "metadata_json")?)?)
+        };
+        scoped.save(c_str_arg(user_id, "user_id")?, c_str_arg(session_id, "session_id")?, c_str_arg(content, "content")?, metadata)
+    })
+}
+
+/// `mindcache_recall_advanced`, if `api_key` may read every user the filter covers
+#[no_mangle]
+pub extern "C" fn mindcache_recall_advanced_with_key(cache: *mut MindCache, api_key: *const c_char, filter_json: *const c_char) -> *mut c_char {
+    call_with_key(cache, api_key, |scoped| {
+        let filter: QueryFilter = serde_json::from_str(c_str_arg(filter_json, "filter_json")?)?;
+        Ok(serde_json::to_string(&scoped.recall_advanced(filter)?)?)
+    })
+}
+
+/// Erase every trace of a user, if `api_key` has the admin scope for them; returns the JSON DeletionReport
+#[no_mangle]
+pub extern "C" fn mindcache_delete_user_with_key(cache: *mut MindCache, api_key: *const c_char, user_id: *const c_char) -> *mut c_char {
+    call_with_key(cache, api_key, |mut scoped| {
+        Ok(serde_json::to_string(&scoped.delete_user(c_str_arg(user_id, "user_id")?)?)?)
+    })
+}
+
+/// `mindcache_decay`, if `api_key` has the admin scope for all users
+#[no_mangle]
+pub extern "C" fn mindcache_decay_with_key(cache: *mut MindCache, api_key: *const c_char) -> *mut c_char {
+    call_with_key(cache, api_key, |mut scoped| Ok(serde_json::to_string(&scoped.decay()?)?))
+}
+
+/// `mindcache_compact`, if `api_key` has the admin scope for all users
+#[no_mangle]
+pub extern "C" fn mindcache_compact_with_key(cache: *mut MindCache, api_key: *const c_char) -> *mut c_char {
+    call_with_key(cache, api_key, |mut scoped| Ok(serde_json::to_string(&scoped.compact()?)?))
+}
+
+/// Receives the outcome of an async C API call
+///
+/// Exactly one of `result_json` and `error` is non-null. Both strings belong
+/// to the library and are only valid during the call; copy them to keep
+/// them. Callbacks run on one of the library's worker threads.
+pub type MindCacheCallback = extern "C" fn(user_data: *mut c_void, result_json: *const c_char, error: *const c_char);
+
+/// An async call's handle and callback, moved to a worker thread
+struct AsyncCall {
+    cache: *mut MindCache,
+    callback: MindCacheCallback,
+    user_data: *mut c_void,
+}
+
+// The caller promises not to touch the handle until the callback has run
+unsafe impl Send for AsyncCall {}
+
+impl AsyncCall {
+    /// Queue `operation` on the worker pool; returns the C API status code
+    fn queue<F>(self, operation: F) -> i32
+    where
+        F: FnOnce(&mut MindCache) -> Result<String, Box<dyn std::error::Error>> + Send + 'static,
+    {
+        let queued = jobs::spawn(move || {
+            let cache = unsafe { &mut *self.cache };
+            let outcome = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| operation(cache)))
+                .unwrap_or_else(|_| Err("operation panicked".into()));
+            self.finish(outcome);
+        });
+        if queued { 0 } else { -1 }
+    }
+
+    fn finish(&self, outcome: Result<String, Box<dyn std::error::Error>>) {
+        match outcome {
+            Ok(json) => {
+                let json = CString::new(json).unwrap_or_default();
+                (self.callback)(self.user_data, json.as_ptr(), std::ptr::null());
+            }
+            Err(e) => {
+                let message = CString::new(e.to_string().replace('\0', "")).unwrap_or_default();
+                (self.callback)(self.user_data, std::ptr::null(), message.as_ptr());
+            }
+        }
+    }
+}
+
+/// Run a decay pass on a worker thread; the callback gets the JSON DecayStats
+///
+/// Returns 0 once queued, or -1 (without calling back) on a null argument or
+/// when no worker could be started. The handle must not be used or destroyed
+/// until the callback has run.
+#[no_mangle]
+pub extern "C" fn mindcache_decay_async(
+    cache: *mut MindCache,
+    callback: Option<MindCacheCallback>,
+    user_data: *mut c_void,
+) -> i32 {
+    let Some(callback) = callback.filter(|_| !cache.is_null()) else {
+        return -1;
+    };
+    AsyncCall { cache, callback, user_data }.queue(|cache| Ok(serde_json::to_string(&cache.decay()?)?))
+}
+
+/// Compact the memory log on a worker thread; the callback gets the JSON CompactionReport
+///
+/// Same contract as `mindcache_decay_async`.
+#[no_mangle]
+pub extern "C" fn mindcache_compact_async(
+    cache: *mut MindCache,
+    callback: Option<MindCacheCallback>,
+    user_data: *mut c_void,
+) -> i32 {
+    let Some(callback) = callback.filter(|_| !cache.is_null()) else {
+        return -1;
+    };
+    AsyncCall { cache, callback, user_data }.queue(|cache| Ok(serde_json::to_string(&cache.compact()?)?))
+}
+
+/// Export a user's memories on a worker thread; the callback gets a JSON array
+///
+/// Same contract as `mindcache_decay_async`.
+#[no_mangle]
+pub extern "C" fn mindcache_export_async(
+    cache: *mut MindCache,
+    user_id: *const c_char,
+    callback: Option<MindCacheCallback>,
+    user_data: *mut c_void,
+) -> i32 {
+    let Some(callback) = callback.filter(|_| !cache.is_null() && !user_id.is_null()) else {
+        return -1;
+    };
+    let user_id = match unsafe { CStr::from_ptr(user_id) }.to_str() {
+        Ok(s) => s.to_string(),
+        Err(_) => return -1,
+    };
+    AsyncCall { cache, callback, user_data }.queue(move |cache| cache.export_user_memories(&user_id))
+}
+
+/// Write a backup of the store to `path`; returns the JSON BackupManifest
+#[no_mangle]
+pub extern "C" fn mindcache_create_backup(cache: *mut MindCache, path: *const c_char) -> *mut c_char {
+    if cache.is_null() || path.is_null() {
+        return std::ptr::null_mut();
+    }
+
+    let cache = unsafe { &*cache };
+    let path = match unsafe { CStr::from_ptr(path) }.to_str() {
+        Ok(s) => s,
+        Err(_) => return std::ptr::null_mut(),
+    };
+
+    match cache.create_backup(path) {
+        Ok(manifest) => {
+            match serde_json::to_string(&manifest) {
+                Ok(json) => {
+                    let c_string = CString::new(json).unwrap();
+                    c_string.into_raw()
+                }
+                Err(_) => std::ptr::null_mut(),
+            }
+        }
+        Err(_) => std::ptr::null_mut(),
+    }
+}
+
+/// Replace the store with the backup at `path`; returns the JSON BackupManifest
+#[no_mangle]
+pub extern "C" fn mindcache_restore_backup(cache: *mut MindCache, path: *const c_char) -> *mut c_char {
+    if cache.is_null() || path.is_null() {
+        return std::ptr::null_mut();
+    }
+
+    let cache = unsafe { &mut *cache };
+    let path = match unsafe { CStr::from_ptr(path) }.to_str() {
+        Ok(s) => s,
+        Err(_) => return std::ptr::null_mut(),
+    };
+
+    match cache.restore_backup(path) {
+        Ok(manifest) => {
+            match serde_json::to_string(&manifest) {
+                Ok(json) => {
+                    let c_string = CString::new(json).unwrap();
+                    c_string.into_raw()
+                }
+                Err(_) => std::ptr::null_mut(),
+            }
+        }
+        Err(_) => std::ptr::null_mut(),
+    }
+}
+
 /// Get statistics
 #[no_mangle]
 pub extern "C" fn mindcache_get_stats(cache: *mut MindCache) -> *mut c_char {
@@ -398,7 +1824,10 @@ pub extern "C" fn mindcache_get_stats(cache: *mut MindCache) -> *mut c_char {
 
     let cache = unsafe { &*cache };
 
-    let stats = cache.get_stats();
+    let stats = match cache.get_stats() {
+        Ok(stats) => stats,
+        Err(_) => return std::ptr::null_mut(),
+    };
     match serde_json::to_string(&stats) {
         Ok(json) => {
             let c_string = CString::new(json).unwrap();
@@ -431,8 +1860,40 @@ pub extern "C" fn mindcache_destroy(cache: *mut MindCache) {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_support::{test_config, temp_cache};
     use tempfile::TempDir;
 
+    #[test]
+    fn test_c_functions_list_matches_exports() {
+        let source = include_str!("lib.rs");
+        let mut exported: Vec<&str> = source
+            .split("pub extern \"C\" fn ")
+            .skip(1)
+            .filter_map(|rest| rest.split('(').next())
+            .collect();
+        exported.sort_unstable();
+        let mut listed = C_FUNCTIONS.to_vec();
+        listed.sort_unstable();
+        assert_eq!(exported, listed);
+    }
+
+    #[test]
+    fn test_cargo_features_list_matches_manifest() {
+        let manifest = include_str!("../Cargo.toml");
+        let section = manifest.split("[features]").nth(1).unwrap();
+        let section = section.split("\n[").next().unwrap();
+        let mut declared: Vec<&str> = section
+            .lines()
+            .filter_map(|line| line.split_once(" = "))
+            .map(|(name, _)| name.trim())
+            .filter(|name| !name.starts_with('#') && *name != "default")
+            .collect();
+        declared.sort_unstable();
+        let mut listed: Vec<&str> = CARGO_FEATURES.iter().map(|&(name, _)| name).collect();
+        listed.sort_unstable();
+        assert_eq!(declared, listed);
+    }
+
     #[test]
     fn test_mindcache_basic_operations() {
         // Create a temporary directory for testing
@@ -445,6 +1906,7 @@ mod tests {
             enable_compression: false, // Disable for simpler testing
             max_memories_per_user: 1000,
             importance_threshold: 0.3,
+            ..Default::default()
         };
         
         let mut cache = MindCache::with_config(config).unwrap();
@@ -483,4 +1945,294 @@ mod tests {
         // Cleanup - but don't panic if it fails
         let _ = std::fs::remove_dir_all("./mindcache_data");
     }
+
+    #[test]
+    fn test_subscribe_receives_lifecycle_events() {
+        let (mut cache, _temp_dir) = temp_cache();
+
+        let events = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sink = Arc::clone(&events);
+        let subscription = cache.subscribe(move |event: &MemoryEvent| sink.lock().unwrap().push(event.clone()));
+
+        let memory_id = cache.save("alice", "s1", "Observed memory", None).unwrap();
+        cache.summarize_session("s1").unwrap();
+        assert!(cache.unsubscribe(subscription));
+        cache.save("alice", "s1", "Unobserved memory", None).unwrap();
+
+        let events = events.lock().unwrap();
+        assert_eq!(events.len(), 3);
+        assert!(matches!(&events[0], MemoryEvent::MemorySaved { memory } if memory.id == memory_id));
+        assert!(matches!(&events[1], MemoryEvent::MemorySaved { memory } if memory.id == "summary-s1"));
+        assert!(matches!(&events[2], MemoryEvent::SessionSummarized { summary } if summary.session_id == "s1"));
+    }
+
+    #[test]
+    fn test_summary_provenance_is_persisted() {
+        let temp_dir = TempDir::new().expect("Should create temp dir");
+        let config = test_config(&temp_dir);
+        let mut cache = MindCache::with_config(config.clone()).unwrap();
+        let first = cache.save("alice", "s1", "Booked flights to Lisbon", None).unwrap();
+        let second = cache.save("alice", "s1", "Hotel near the river", None).unwrap();
+        let summary = cache.summarize_session("s1").unwrap();
+
+        drop(cache);
+        let cache = MindCache::with_config(config).unwrap();
+        let provenance = cache.get_provenance(&summary.id).expect("summary provenance");
+        assert_eq!(provenance.method, DerivationMethod::SessionSummary);
+        let mut sources = provenance.derived_from.clone();
+        sources.sort();
+        let mut expected = vec![first.clone(), second];
+        expected.sort();
+        assert_eq!(sources, expected);
+        assert_eq!(cache.get_derivatives(&first)[0].derived_id, summary.id);
+    }
+
+    #[test]
+    fn test_stored_summaries_are_recallable() {
+        let temp_dir = TempDir::new().expect("Should create temp dir");
+        let config = test_config(&temp_dir);
+        let mut cache = MindCache::with_config(config.clone()).unwrap();
+        cache.save("alice", "trip", "Booked flights to Lisbon", None).unwrap();
+        cache.save("alice", "work", "Reviewed the quarterly budget", None).unwrap();
+        cache.save("bob", "bob-trip", "Packed for Lisbon", None).unwrap();
+        cache.summarize_session("work").unwrap();
+        let trip = cache.summarize_session("trip").unwrap();
+        assert!(cache.summarize_session(SUMMARY_SESSION).is_err());
+
+        drop(cache);
+        let mut cache = MindCache::with_config(config).unwrap();
+        let alice = QueryFilter { user_id: Some("alice".to_string()), ..Default::default() };
+        assert_eq!(cache.recall_summaries(alice.clone()).unwrap().len(), 2);
+        let lisbon = cache.recall_summaries(QueryFilter { keywords: Some(vec!["lisbon".to_string()]), ..alice.clone() }).unwrap();
+        assert_eq!(lisbon.len(), 1);
+        assert_eq!(lisbon[0].session_id, "trip");
+        assert_eq!(lisbon[0].summary_text, trip.summary_text);
+        assert_eq!(lisbon[0].date_range, trip.date_range);
+        let by_session = cache.recall_summaries(QueryFilter { session_id: Some("work".to_string()), ..alice.clone() }).unwrap();
+        assert_eq!(by_session[0].id, SessionSummary::memory_id("work"));
+        let later = QueryFilter { date_from: Some(Utc::now() + chrono::Duration::hours(1)), ..alice.clone() };
+        assert!(cache.recall_summaries(later).unwrap().is_empty());
+
+        // Regenerating replaces the stored summary, deleting the session drops it
+        cache.save("alice", "trip", "Hotel near the river", None).unwrap();
+        assert_eq!(cache.summarize_session("trip").unwrap().memory_count, 2);
+        let trips = cache.recall_summaries(QueryFilter { session_id: Some("trip".to_string()), ..alice.clone() }).unwrap();
+        assert_eq!(trips.len(), 1);
+        assert_eq!(trips[0].memory_count, 2);
+        cache.session_manager.delete_session("trip").unwrap();
+        assert_eq!(cache.recall_summaries(alice).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_role_and_memory_type_filters() {
+        let temp_dir = TempDir::new().expect("Should create temp dir");
+        let config = test_config(&temp_dir);
+        let mut cache = MindCache::with_config(config.clone()).unwrap();
+        cache.save_message("alice", "chat", "user", "I live in Porto", 0.6).unwrap();
+        cache.save_message("alice", "chat", "assistant", "Noted, you live in Porto", 0.3).unwrap();
+        cache.save_typed("alice", "chat", "Lives in Porto", MemoryType::Fact, None).unwrap();
+        cache.save("alice", "chat", "Porto weather is mild", None).unwrap();
+
+        // Types and roles are stored in the record, so they survive a reopen
+        drop(cache);
+        let cache = MindCache::with_config(config).unwrap();
+        let filter = |role: Option<&str>, memory_type: Option<MemoryType>| QueryFilter {
+            user_id: Some("alice".to_string()),
+            role: role.map(str::to_string),
+            memory_type,
+            ..Default::default()
+        };
+        let user_turns = cache.recall_advanced(filter(Some("user"), None)).unwrap();
+        assert_eq!(user_turns.len(), 1);
+        assert_eq!(user_turns[0].memory_type, MemoryType::Message);
+        assert_eq!(cache.recall_advanced(filter(None, Some(MemoryType::Message))).unwrap().len(), 2);
+        assert_eq!(cache.recall_advanced(filter(None, Some(MemoryType::Fact))).unwrap()[0].content, "Lives in Porto");
+        assert_eq!(cache.recall_advanced(filter(None, Some(MemoryType::Observation))).unwrap().len(), 1);
+        assert!(cache.recall_advanced(filter(Some("system"), None)).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_decay_at_judges_expiry_at_the_given_time() {
+        let temp_dir = TempDir::new().expect("Should create temp dir");
+        let config = MindCacheConfig {
+            enable_compression: false,
+            ..test_config(&temp_dir)
+        };
+        let mut cache = MindCache::with_config(config).unwrap();
+        let short = cache.save_with_options("alice", "s1", "Parking spot is B4", None, 0.1, Some(24)).unwrap();
+        cache.save_with_options("alice", "s1", "Allergic to peanuts", None, 0.1, Some(24 * 90)).unwrap();
+        let trashed = cache.save("alice", "s1", "Old phone number", None).unwrap();
+        cache.delete_memory_soft(&trashed).unwrap();
+
+        assert_eq!(cache.decay().unwrap().memories_expired, 0);
+        let month_ahead = Utc::now() + chrono::Duration::days(30);
+        let report = cache.decay_at(month_ahead).unwrap();
+        assert_eq!(report.as_of, month_ahead);
+        assert_eq!(report.stats.memories_expired, 1);
+        assert_eq!(report.stats.total_memories_after, 1);
+        assert_eq!(report.affected.expired_ids, std::slice::from_ref(&short));
+        assert_eq!(report.affected.trash_purged_ids, std::slice::from_ref(&trashed));
+
+        // Nothing was actually deleted, purged or journaled
+        assert!(cache.get_memory(&short).unwrap().is_some());
+        assert_eq!(cache.decay_history(10).unwrap().len(), 1);
+        assert!(cache.decay_history(1).unwrap()[0].affected.expired_ids.is_empty());
+        cache.restore_memory(&trashed).unwrap();
+    }
+
+    #[test]
+    fn test_forever_ttl_outlives_the_decay_policy() {
+        let temp_dir = TempDir::new().expect("Should create temp dir");
+        let config = MindCacheConfig {
+            enable_compression: false,
+            default_memory_ttl_hours: Some(24),
+            ..test_config(&temp_dir)
+        };
+        let mut cache = MindCache::with_config(config).unwrap();
+        let policy = cache.save_with_options("alice", "s1", "Parking spot is B4", None, 0.1, None).unwrap();
+        let forever = cache
+            .save_with_options("alice", "s1", "Allergic to peanuts", None, 0.1, Some(MemoryItem::TTL_FOREVER))
+            .unwrap();
+        cache.set_session_retention("s1", SessionRetention { ttl_hours: Some(1), ..Default::default() }).unwrap();
+
+        let kept = cache.get_memory(&forever).unwrap().unwrap();
+        assert!(kept.never_expires());
+        assert_eq!(kept.expires_at, None);
+
+        let report = cache.decay_at(Utc::now() + chrono::Duration::days(365)).unwrap();
+        assert_eq!(report.stats.memories_expired, 1);
+        assert_eq!(report.affected.expired_ids, [policy]);
+
+        // Forever still counts as a TTL of its own
+        let with_ttl = QueryFilter { user_id: Some("alice".to_string()), has_ttl: Some(true), ..Default::default() };
+        let found: Vec<String> = cache.recall_advanced(with_ttl).unwrap().into_iter().map(|memory| memory.id).collect();
+        assert_eq!(found, [forever]);
+    }
+
+    #[test]
+    fn test_exclusion_filters() {
+        let (mut cache, _temp_dir) = temp_cache();
+        let msft = cache.save("alice", "research", "MSFT earnings beat estimates", None).unwrap();
+        cache.save("alice", "research", "AAPL earnings missed", None).unwrap();
+        cache.save("alice", "scratch", "Draft: earnings calendar", None).unwrap();
+        cache.save("bob", "scratch", "Bob's scratch note", None).unwrap();
+
+        let earnings = QueryFilter {
+            user_id: Some("alice".to_string()),
+            keywords: Some(vec!["earnings".to_string()]),
+            exclude_keywords: vec!["aapl".to_string()],
+            exclude_sessions: vec!["scratch".to_string()],
+            ..Default::default()
+        };
+        let recalled = cache.recall_advanced(earnings.clone()).unwrap();
+        assert_eq!(recalled.len(), 1);
+        assert_eq!(recalled[0].id, msft);
+        assert_eq!(cache.count(earnings).unwrap(), 1);
+
+        // Session exclusions alone are answered from the index
+        let no_scratch = QueryFilter { exclude_sessions: vec!["scratch".to_string()], ..Default::default() };
+        assert_eq!(cache.count(no_scratch.clone()).unwrap(), 2);
+        assert_eq!(cache.recall_advanced(no_scratch).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_recall_across_several_users() {
+        let temp_dir = TempDir::new().expect("Should create temp dir");
+        let config = test_config(&temp_dir);
+        let mut cache = MindCache::with_config(config.clone()).unwrap();
+        for user_id in ["alice", "bob", "carol"] {
+            cache.save(user_id, "team", &format!("{} reviewed the gold hedge", user_id), None).unwrap();
+        }
+
+        let team = |user_ids: &[&str]| QueryFilter {
+            user_ids: user_ids.iter().map(|user_id| user_id.to_string()).collect(),
+            keywords: Some(vec!["hedge".to_string()]),
+            ..Default::default()
+        };
+        let mut users: Vec<String> = cache.recall_advanced(team(&["alice", "carol"])).unwrap().into_iter().map(|m| m.user_id).collect();
+        users.sort();
+        assert_eq!(users, ["alice", "carol"]);
+        assert_eq!(cache.count(QueryFilter { session_id: Some("team".to_string()), ..team(&["bob", "dave"]) }).unwrap(), 1);
+        // user_id narrows the list further
+        assert_eq!(cache.count(QueryFilter { user_id: Some("bob".to_string()), ..team(&["alice"]) }).unwrap(), 0);
+
+        assert!(cache.recall_advanced(team(&[ALL_USERS])).is_err());
+        drop(cache);
+        let cache = MindCache::with_config(MindCacheConfig { allow_wildcard_recall: true, ..config }).unwrap();
+        assert_eq!(cache.recall_advanced(team(&[ALL_USERS])).unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_save_estimates_importance() {
+        let temp_dir = TempDir::new().expect("Should create temp dir");
+        let config = test_config(&temp_dir);
+        let mut cache = MindCache::with_config(config.clone()).unwrap();
+        let chatter = cache.save("alice", "s1", "ok", None).unwrap();
+        let deadline = cache.save("alice", "s1", "Remember the tax deadline is 2024-04-15", None).unwrap();
+        let chatter = cache.get_memory(&chatter).unwrap().unwrap();
+        let deadline = cache.get_memory(&deadline).unwrap().unwrap();
+        assert!(deadline.importance > chatter.importance);
+
+        // Explicit importance and custom scorers win over the heuristic
+        let explicit = cache.save_with_options("alice", "s1", "ok", None, 0.95, None).unwrap();
+        assert_eq!(cache.get_memory(&explicit).unwrap().unwrap().importance, 0.95);
+        cache.set_importance_scorer(|memory: &MemoryItem, _: &ScoringContext| {
+            if memory.content.contains("AAPL") { 0.9 } else { 0.1 }
+        });
+        assert_eq!(cache.default_importance_for("alice", "s1", "Sold AAPL"), 0.9);
+
+        drop(cache);
+        let mut cache = MindCache::with_config(MindCacheConfig { auto_importance_enabled: false, ..config }).unwrap();
+        let flat = cache.save("alice", "s1", "Remember the tax deadline", None).unwrap();
+        assert_eq!(cache.get_memory(&flat).unwrap().unwrap().importance, 0.5);
+    }
+
+    #[test]
+    fn test_custom_summarizer_is_used_for_sessions() {
+        let (mut cache, _temp_dir) = temp_cache();
+        let session_id = cache.create_session("alice", None).unwrap();
+        cache.save("alice", &session_id, "Bought gold at 1900", None).unwrap();
+        cache.save("alice", &session_id, "Sold gold at 1950", None).unwrap();
+
+        cache.set_summarizer(|request: &SummaryRequest| -> Result<String, Box<dyn std::error::Error>> {
+            Ok(format!("LLM: {} trades", request.memories.len()))
+        });
+        assert_eq!(cache.summarize_session(&session_id).unwrap().summary_text, "LLM: 2 trades");
+
+        // Restores rebuild the session manager but keep the summarizer
+        cache.reset_derived_state();
+        assert_eq!(cache.summarize_session(&session_id).unwrap().summary_text, "LLM: 2 trades");
+    }
+
+    #[test]
+    fn test_changes_since_replays_saves_updates_and_deletes() {
+        let temp_dir = TempDir::new().expect("Should create temp dir");
+        let config = MindCacheConfig {
+            change_log_enabled: true,
+            ..test_config(&temp_dir)
+        };
+        let mut cache = MindCache::with_config(config.clone()).unwrap();
+        let low = cache.save_with_options("alice", "s1", "Likes green tea", None, 0.2, None).unwrap();
+        let high = cache.save_with_options("alice", "s1", "likes green tea", None, 0.9, None).unwrap();
+        let checkpoint = cache.latest_change_sequence();
+        assert_eq!(checkpoint, 2);
+
+        cache.dedupe("alice", 0.9, false).unwrap();
+
+        // Sequence numbers continue across reopen
+        drop(cache);
+        let mut cache = MindCache::with_config(config).unwrap();
+        cache.save("alice", "s2", "Another memory", None).unwrap();
+
+        let changes = cache.changes_since(checkpoint).unwrap();
+        let summary: Vec<(u64, ChangeKind, &str)> = changes
+            .iter()
+            .map(|change| (change.sequence, change.kind, change.memory_id.as_str()))
+            .collect();
+        assert_eq!(summary[0], (3, ChangeKind::Deleted, low.as_str()));
+        assert_eq!(summary.len(), 2);
+        assert_eq!(summary[1].0, 4);
+        assert_eq!(summary[1].1, ChangeKind::Saved);
+        assert!(cache.changes_since(0).unwrap().iter().any(|change| change.memory_id == high));
+    }
 }
\ No newline at end of file
@@ -0,0 +1,130 @@
+//! Memory lifecycle states
+//!
+//! A memory stops showing up as it was saved for one of a few reasons:
+//! decay folded it into a compressed summary, it was soft-deleted, or a
+//! newer memory superseded it. `MindCache::memory_state` says which, with
+//! the id of the summary or replacement, and `recall_with_state` returns
+//! the state alongside each recalled memory, so clients can explain to end
+//! users where a memory went.
+
+use std::collections::HashSet;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use crate::provenance::DerivationMethod;
+use crate::storage::{MemoryItem, QueryFilter};
+use crate::supersession::SUPERSEDES;
+use crate::MindCache;
+
+/// Where a memory is in its lifecycle
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MemoryState {
+    /// Recalled as it was saved
+    Live,
+    /// Folded by decay into the compressed memory `into`; its provenance
+    /// (`MindCache::get_provenance`) lists the memories it was built from
+    Compressed { into: String },
+    /// In the trash since `deleted_at`, until restored or purged
+    SoftDeleted { deleted_at: DateTime<Utc> },
+    /// Replaced by the memory `by`, and left out of recall unless
+    /// `QueryFilter::include_superseded` is set
+    Superseded { by: String },
+}
+
+/// A recalled memory with its lifecycle state
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MemoryWithState {
+    pub memory: MemoryItem,
+    pub state: MemoryState,
+}
+
+impl MindCache {
+    /// The lifecycle state of a memory, `None` if it is neither stored nor in the trash
+    ///
+    /// A memory that is both superseded and compressed reports superseded.
+    pub fn memory_state(&self, memory_id: &str) -> Result<Option<MemoryState>, Box<dyn std::error::Error>> {
+        if let Some(trashed) = self.storage.trash().get(memory_id) {
+            return Ok(Some(MemoryState::SoftDeleted { deleted_at: trashed.deleted_at }));
+        }
+        if self.storage.get_memory(memory_id)?.is_none() {
+            return Ok(None);
+        }
+        let superseded = self.storage.links().targets(SUPERSEDES);
+        Ok(Some(self.state_of(memory_id, &superseded)?))
+    }
+
+    /// Recall memories with their lifecycle states
+    ///
+    /// Memories come back as from `recall_advanced`, so soft-deleted ones
+    /// never do, and superseded ones only with `include_superseded`.
+    pub fn recall_with_state(&self, filter: QueryFilter) -> Result<Vec<MemoryWithState>, Box<dyn std::error::Error>> {
+        let superseded = self.storage.links().targets(SUPERSEDES);
+        self.recall_advanced(filter)?
+            .into_iter()
+            .map(|memory| {
+                let state = self.state_of(&memory.id, &superseded)?;
+                Ok(MemoryWithState { memory, state })
+            })
+            .collect()
+    }
+
+    /// State of a stored memory, given the ids of every superseded memory
+    fn state_of(&self, memory_id: &str, superseded: &HashSet<String>) -> Result<MemoryState, Box<dyn std::error::Error>> {
+        if superseded.contains(memory_id) {
+            if let Some(by) = self.superseded_by(memory_id)? {
+                return Ok(MemoryState::Superseded { by });
+            }
+        }
+        let compressed_into = self
+            .storage
+            .provenance()
+            .derived_from(memory_id)
+            .into_iter()
+            .rfind(|record| record.method == DerivationMethod::Compression);
+        Ok(match compressed_into {
+            Some(record) => MemoryState::Compressed { into: record.derived_id },
+            None => MemoryState::Live,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::temp_cache;
+
+    #[test]
+    fn test_memory_states() {
+        let (mut cache, _temp_dir) = temp_cache();
+        let live = cache.save("alice", "s1", "Likes jazz", None).unwrap();
+        let old_city = cache.save("alice", "s1", "Lives in NYC", None).unwrap();
+        let new_city = cache.save("alice", "s1", "Moved to Austin", None).unwrap();
+        let compressed = cache.save("alice", "s1", "Had coffee", None).unwrap();
+        let deleted = cache.save("alice", "s1", "Old phone number", None).unwrap();
+
+        cache.supersede(&old_city, &new_city).unwrap();
+        cache.record_provenance("summary-1", vec![compressed.clone()], DerivationMethod::Compression).unwrap();
+        cache.record_provenance("facts-1", vec![live.clone()], DerivationMethod::FactExtraction).unwrap();
+        cache.delete_memory_soft(&deleted).unwrap();
+
+        assert_eq!(cache.memory_state(&live).unwrap(), Some(MemoryState::Live));
+        assert_eq!(cache.memory_state(&old_city).unwrap(), Some(MemoryState::Superseded { by: new_city.clone() }));
+        assert_eq!(cache.memory_state(&compressed).unwrap(), Some(MemoryState::Compressed { into: "summary-1".to_string() }));
+        assert!(matches!(cache.memory_state(&deleted).unwrap(), Some(MemoryState::SoftDeleted { .. })));
+        assert_eq!(cache.memory_state("missing").unwrap(), None);
+
+        let recalled = cache.recall_with_state(QueryFilter {
+            user_id: Some("alice".to_string()),
+            include_superseded: true,
+            ..Default::default()
+        }).unwrap();
+        assert_eq!(recalled.len(), 4);
+        let old = recalled.iter().find(|recalled| recalled.memory.id == old_city).unwrap();
+        assert_eq!(serde_json::to_value(&old.state).unwrap(), serde_json::json!({ "superseded": { "by": new_city } }));
+        assert_eq!(serde_json::to_value(MemoryState::Live).unwrap(), "live");
+
+        // Restoring brings a memory back live
+        cache.restore_memory(&deleted).unwrap();
+        assert_eq!(cache.memory_state(&deleted).unwrap(), Some(MemoryState::Live));
+    }
+}
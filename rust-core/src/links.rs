@@ -0,0 +1,323 @@
+//! Links between memories
+//!
+//! Agents can connect memories with a named relation ("entry" → "exit",
+//! "caused_by", "follow_up") and later walk those links to recall a whole
+//! thread. Links are directed and live in `links.json` next to the memory
+//! log, keyed by memory id so they survive compaction.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::{Arc, RwLock};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use crate::backend::StorageBackend;
+use crate::clock::SharedClock;
+use crate::storage::MemoryItem;
+use crate::MindCache;
+
+pub const LINKS_BLOB: &str = "links.json";
+
+/// A directed, named edge between two memories
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MemoryLink {
+    pub from_id: String,
+    pub to_id: String,
+    pub relation: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Which end of a link the queried memory is on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LinkDirection {
+    /// The queried memory links to the related one
+    Outgoing,
+    /// The related memory links to the queried one
+    Incoming,
+}
+
+/// A memory reached through a link
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RelatedMemory {
+    pub memory: MemoryItem,
+    pub relation: String,
+    pub direction: LinkDirection,
+}
+
+/// Persistent adjacency list of memory links
+#[derive(Clone)]
+pub struct LinkGraph {
+    backend: Arc<dyn StorageBackend>,
+    clock: SharedClock,
+    /// from_id -> links leaving that memory
+    outgoing: Arc<RwLock<HashMap<String, Vec<MemoryLink>>>>,
+}
+
+impl LinkGraph {
+    pub fn load(backend: Arc<dyn StorageBackend>, clock: SharedClock) -> Result<Self, Box<dyn std::error::Error>> {
+        let outgoing = Self::read_links(backend.as_ref())?;
+        Ok(LinkGraph {
+            backend,
+            clock,
+            outgoing: Arc::new(RwLock::new(outgoing)),
+        })
+    }
+
+    /// Re-read the links from disk, e.g. after a restore
+    pub fn reload(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let outgoing = Self::read_links(self.backend.as_ref())?;
+        *self.outgoing.write().unwrap_or_else(|poisoned| poisoned.into_inner()) = outgoing;
+        Ok(())
+    }
+
+    fn read_links(backend: &dyn StorageBackend) -> Result<HashMap<String, Vec<MemoryLink>>, Box<dyn std::error::Error>> {
+        let mut outgoing: HashMap<String, Vec<MemoryLink>> = HashMap::new();
+        if let Some(data) = backend.read_blob(LINKS_BLOB)? {
+            for link in serde_json::from_slice::<Vec<MemoryLink>>(&data)? {
+                outgoing.entry(link.from_id.clone()).or_default().push(link);
+            }
+        }
+        Ok(outgoing)
+    }
+
+    /// Add a link; linking the same pair with the same relation again is a no-op
+    pub fn link(&self, from_id: &str, to_id: &str, relation: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let mut outgoing = self.outgoing.write().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let links = outgoing.entry(from_id.to_string()).or_default();
+        if links.iter().any(|link| link.to_id == to_id && link.relation == relation) {
+            return Ok(());
+        }
+
+        links.push(MemoryLink {
+            from_id: from_id.to_string(),
+            to_id: to_id.to_string(),
+            relation: relation.to_string(),
+            created_at: self.clock.now(),
+        });
+        if let Err(e) = self.persist(&outgoing) {
+            if let Some(links) = outgoing.get_mut(from_id) {
+                links.pop();
+            }
+            outgoing.retain(|_, links| !links.is_empty());
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    /// Remove links from `from_id` to `to_id`, of one relation or all of them
+    ///
+    /// Returns how many links were removed.
+    pub fn unlink(&self, from_id: &str, to_id: &str, relation: Option<&str>) -> Result<usize, Box<dyn std::error::Error>> {
+        let mut outgoing = self.outgoing.write().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let Some(links) = outgoing.get(from_id) else {
+            return Ok(0);
+        };
+        let previous = links.clone();
+        let kept: Vec<MemoryLink> = previous
+            .iter()
+            .filter(|link| link.to_id != to_id || relation.is_some_and(|relation| link.relation != relation))
+            .cloned()
+            .collect();
+        let removed = previous.len() - kept.len();
+        if removed == 0 {
+            return Ok(0);
+        }
+
+        Self::set_links(&mut outgoing, from_id, kept);
+        if let Err(e) = self.persist(&outgoing) {
+            Self::set_links(&mut outgoing, from_id, previous);
+            return Err(e);
+        }
+        Ok(removed)
+    }
+
+    /// Remove every link from or to one of `ids`
+    ///
+    /// Returns how many links were removed.
+    pub fn remove_involving(&self, ids: &HashSet<String>) -> Result<usize, Box<dyn std::error::Error>> {
+        let mut outgoing = self.outgoing.write().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let previous = outgoing.clone();
+        outgoing.retain(|from_id, _| !ids.contains(from_id));
+        for links in outgoing.values_mut() {
+            links.retain(|link| !ids.contains(&link.to_id));
+        }
+        outgoing.retain(|_, links| !links.is_empty());
+
+        let count = |links: &HashMap<String, Vec<MemoryLink>>| links.values().map(Vec::len).sum::<usize>();
+        let removed = count(&previous) - count(&outgoing);
+        if removed == 0 {
+            return Ok(0);
+        }
+        if let Err(e) = self.persist(&outgoing) {
+            *outgoing = previous;
+            return Err(e);
+        }
+        Ok(removed)
+    }
+
+    fn set_links(outgoing: &mut HashMap<String, Vec<MemoryLink>>, from_id: &str, links: Vec<MemoryLink>) {
+        if links.is_empty() {
+            outgoing.remove(from_id);
+        } else {
+            outgoing.insert(from_id.to_string(), links);
+        }
+    }
+
+    /// Links leaving `memory_id`, oldest first
+    pub fn outgoing(&self, memory_id: &str) -> Vec<MemoryLink> {
+        self.outgoing
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .get(memory_id)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Links pointing at `memory_id`, oldest first
+    pub fn incoming(&self, memory_id: &str) -> Vec<MemoryLink> {
+        let mut links: Vec<MemoryLink> = self
+            .outgoing
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .values()
+            .flatten()
+            .filter(|link| link.to_id == memory_id)
+            .cloned()
+            .collect();
+        links.sort_by_key(|link| link.created_at);
+        links
+    }
+
+    /// Ids of the memories that links of `relation` point at
+    pub fn targets(&self, relation: &str) -> HashSet<String> {
+        self.outgoing
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .values()
+            .flatten()
+            .filter(|link| link.relation == relation)
+            .map(|link| link.to_id.clone())
+            .collect()
+    }
+
+    fn persist(&self, outgoing: &HashMap<String, Vec<MemoryLink>>) -> Result<(), Box<dyn std::error::Error>> {
+        let mut sorted: Vec<&MemoryLink> = outgoing.values().flatten().collect();
+        sorted.sort_by(|a, b| a.from_id.cmp(&b.from_id).then(a.created_at.cmp(&b.created_at)));
+        self.backend.write_blob(LINKS_BLOB, &serde_json::to_vec(&sorted)?)?;
+        Ok(())
+    }
+}
+
+impl MindCache {
+    /// Link two stored memories with a named relation, e.g. "entry" -> "exit"
+    pub fn link_memories(&mut self, from_id: &str, to_id: &str, relation: &str) -> Result<(), Box<dyn std::error::Error>> {
+        if relation.trim().is_empty() {
+            return Err("relation must not be empty".into());
+        }
+        for id in [from_id, to_id] {
+            if self.storage.get_memory(id)?.is_none() {
+                return Err(format!("memory {} not found", id).into());
+            }
+        }
+        self.storage.links().link(from_id, to_id, relation)
+    }
+
+    /// Remove links between two memories; `None` removes every relation
+    pub fn unlink_memories(&mut self, from_id: &str, to_id: &str, relation: Option<&str>) -> Result<usize, Box<dyn std::error::Error>> {
+        self.storage.links().unlink(from_id, to_id, relation)
+    }
+
+    /// Memories directly linked to or from `memory_id`
+    ///
+    /// Links to memories that have since been removed are skipped.
+    pub fn get_related(&self, memory_id: &str) -> Result<Vec<RelatedMemory>, Box<dyn std::error::Error>> {
+        let links = self.storage.links();
+        let neighbours = links
+            .outgoing(memory_id)
+            .into_iter()
+            .map(|link| (link.to_id, link.relation, LinkDirection::Outgoing))
+            .chain(
+                links
+                    .incoming(memory_id)
+                    .into_iter()
+                    .map(|link| (link.from_id, link.relation, LinkDirection::Incoming)),
+            );
+
+        let mut related = Vec::new();
+        for (id, relation, direction) in neighbours {
+            if let Some(memory) = self.storage.get_memory(&id)? {
+                related.push(RelatedMemory { memory, relation, direction });
+            }
+        }
+        Ok(related)
+    }
+
+    /// Every memory connected to `memory_id` through links in either
+    /// direction, including itself, oldest first
+    pub fn get_thread(&self, memory_id: &str) -> Result<Vec<MemoryItem>, Box<dyn std::error::Error>> {
+        let links = self.storage.links();
+        let mut seen = HashSet::from([memory_id.to_string()]);
+        let mut queue = VecDeque::from([memory_id.to_string()]);
+        let mut thread = Vec::new();
+
+        while let Some(id) = queue.pop_front() {
+            if let Some(memory) = self.storage.get_memory(&id)? {
+                thread.push(memory);
+            }
+            let neighbours = links
+                .outgoing(&id)
+                .into_iter()
+                .map(|link| link.to_id)
+                .chain(links.incoming(&id).into_iter().map(|link| link.from_id));
+            for neighbour in neighbours {
+                if seen.insert(neighbour.clone()) {
+                    queue.push_back(neighbour);
+                }
+            }
+        }
+
+        thread.sort_by_key(|memory| memory.timestamp);
+        Ok(thread)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::test_config;
+    use tempfile::TempDir;
+
+    fn open(dir: &TempDir) -> MindCache {
+        MindCache::with_config(test_config(dir)).unwrap()
+    }
+
+    #[test]
+    fn test_links_form_threads_and_persist() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut cache = open(&temp_dir);
+        let entry = cache.save("alice", "s1", "Entered AAPL at 170", None).unwrap();
+        let manage = cache.save("alice", "s2", "Moved AAPL stop to breakeven", None).unwrap();
+        let exit = cache.save("alice", "s3", "Exited AAPL at 184", None).unwrap();
+        let other = cache.save("alice", "s3", "Unrelated note", None).unwrap();
+
+        cache.link_memories(&entry, &manage, "managed_by").unwrap();
+        cache.link_memories(&manage, &exit, "closed_by").unwrap();
+        cache.link_memories(&manage, &exit, "closed_by").unwrap();
+        assert!(cache.link_memories(&entry, "missing", "closed_by").is_err());
+
+        let related = cache.get_related(&manage).unwrap();
+        assert_eq!(related.len(), 2);
+        assert!(related.iter().any(|r| r.memory.id == entry && r.direction == LinkDirection::Incoming));
+        assert!(related.iter().any(|r| r.memory.id == exit && r.relation == "closed_by"));
+
+        // Links are by id, so they survive compaction and reopening
+        cache.compact().unwrap();
+        drop(cache);
+        let mut cache = open(&temp_dir);
+        let thread: Vec<String> = cache.get_thread(&exit).unwrap().into_iter().map(|m| m.id).collect();
+        assert_eq!(thread, vec![entry.clone(), manage.clone(), exit.clone()]);
+        assert_eq!(cache.get_thread(&other).unwrap().len(), 1);
+
+        assert_eq!(cache.unlink_memories(&manage, &exit, None).unwrap(), 1);
+        assert_eq!(cache.get_thread(&exit).unwrap().len(), 1);
+    }
+}
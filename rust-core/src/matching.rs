@@ -0,0 +1,99 @@
+//! Keyword matching modes for recall
+//!
+//! `Exact` keeps the original behaviour: a keyword matches when it occurs
+//! anywhere in the content. `Prefix` matches words starting with the keyword,
+//! and `Fuzzy` also accepts words within an edit distance, so typos like
+//! "tradign" still find "trading".
+
+use serde::{Deserialize, Serialize};
+use crate::planner;
+
+/// How recall keywords are compared with memory content
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MatchMode {
+    /// Case-insensitive substring match
+    #[default]
+    Exact,
+    /// A word of the content starts with the keyword
+    Prefix,
+    /// Substring match, or a word within `max_distance` edits of the keyword
+    Fuzzy(u8),
+}
+
+impl MatchMode {
+    /// Whether an indexed term matches a (lowercased) keyword
+    pub fn term_matches(self, term: &str, keyword: &str) -> bool {
+        match self {
+            MatchMode::Exact => term.contains(keyword),
+            MatchMode::Prefix => term.starts_with(keyword),
+            MatchMode::Fuzzy(max_distance) => {
+                term.contains(keyword) || within_distance(term, keyword, max_distance as usize)
+            }
+        }
+    }
+
+    /// Whether content can only match `keyword` by containing it
+    pub fn needs_substring(self, keyword: &str) -> bool {
+        !matches!(self, MatchMode::Fuzzy(_)) || !planner::is_indexable_keyword(keyword)
+    }
+
+    /// Whether lowercased content matches a lowercased keyword
+    ///
+    /// Keywords spanning several words are always matched as substrings.
+    pub fn content_matches(self, content: &str, keyword: &str) -> bool {
+        if self == MatchMode::Exact || !planner::is_indexable_keyword(keyword) {
+            return content.contains(keyword);
+        }
+        planner::index_terms(content).iter().any(|term| self.term_matches(term, keyword))
+    }
+}
+
+/// Levenshtein distance between `a` and `b` is at most `max_distance`
+pub fn within_distance(a: &str, b: &str, max_distance: usize) -> bool {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    if a.len().abs_diff(b.len()) > max_distance {
+        return false;
+    }
+
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.iter().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        // Every later row is at least the minimum of this one
+        if current.iter().min().is_some_and(|&min| min > max_distance) {
+            return false;
+        }
+        previous = current;
+    }
+    previous[b.len()] <= max_distance
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_within_distance() {
+        assert!(within_distance("trading", "tradign", 2));
+        assert!(!within_distance("trading", "tradign", 1));
+        assert!(within_distance("gold", "gold", 0));
+        assert!(within_distance("gold", "bold", 1));
+        assert!(!within_distance("gold", "silver", 3));
+    }
+
+    #[test]
+    fn test_modes() {
+        let content = "opened a gold position before trading hours";
+        assert!(MatchMode::Exact.content_matches(content, "rading"));
+        assert!(!MatchMode::Prefix.content_matches(content, "rading"));
+        assert!(MatchMode::Prefix.content_matches(content, "trad"));
+        assert!(!MatchMode::Exact.content_matches(content, "tradign"));
+        assert!(MatchMode::Fuzzy(2).content_matches(content, "tradign"));
+        assert!(MatchMode::Fuzzy(1).content_matches(content, "positon"));
+    }
+}
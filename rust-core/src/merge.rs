@@ -0,0 +1,279 @@
+//! Merging another store into this one
+//!
+//! `merge_from` brings in the memories and sessions of a store written
+//! elsewhere, e.g. by an agent that ran offline on its own copy. Memories
+//! are matched by id: new ones are imported as they are, and a memory both
+//! stores hold is settled by the [`ConflictPolicy`]. Merging is idempotent,
+//! so two stores can sync by merging each into the other.
+//!
+//! Links between memories, such as supersession, and provenance records
+//! come along with the memories they involve, so a memory replaced in the
+//! other store stays replaced here. Ids are shared between the stores, so
+//! they carry over as they are, except that nothing involving a skipped
+//! memory is brought in.
+//!
+//! Deletions don't travel: a memory deleted on one side comes back from the
+//! other unless it is still in this store's trash.
+
+use std::collections::{BTreeMap, HashSet};
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+use crate::storage::MemoryItem;
+use crate::{MindCache, MindCacheConfig, QueryFilter};
+
+/// Which copy wins when both stores changed a memory
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictPolicy {
+    /// The copy at the higher version, then the later timestamp; this
+    /// store's copy on a tie
+    #[default]
+    Newest,
+    /// Always this store's copy
+    KeepLocal,
+    /// Always the other store's copy
+    KeepRemote,
+}
+
+impl ConflictPolicy {
+    fn remote_wins(self, local: &MemoryItem, remote: &MemoryItem) -> bool {
+        match self {
+            ConflictPolicy::Newest => (remote.version, remote.timestamp) > (local.version, local.timestamp),
+            ConflictPolicy::KeepLocal => false,
+            ConflictPolicy::KeepRemote => true,
+        }
+    }
+}
+
+/// What [`MindCache::merge_from`] did
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MergeReport {
+    /// Memories only the other store had
+    pub memories_imported: usize,
+    /// Memories replaced by the other store's copy
+    pub memories_updated: usize,
+    /// Memories the stores disagreed on where this store's copy was kept
+    pub memories_kept: usize,
+    pub memories_unchanged: usize,
+    /// Memories deleted here, or held by another user or session here
+    pub memories_skipped: usize,
+    /// Sessions only the other store had registered
+    pub sessions_imported: usize,
+}
+
+/// Whether two copies of a memory hold the same thing
+fn same(a: &MemoryItem, b: &MemoryItem) -> bool {
+    a.version == b.version
+        && a.content == b.content
+        && a.metadata == b.metadata
+        && a.importance == b.importance
+        && a.ttl_hours == b.ttl_hours
+        && a.role == b.role
+        && a.memory_type == b.memory_type
+}
+
+impl MindCache {
+    /// Import the memories and sessions of the store at `other_path`
+    ///
+    /// The other store is opened read-only, so it may be in use. Sessions
+    /// registered there but not here are copied with their retention
+    /// settings; sessions registered in both keep this store's record unless
+    /// `policy` is [`ConflictPolicy::KeepRemote`].
+    pub fn merge_from(&mut self, other_path: impl AsRef<Path>, policy: ConflictPolicy) -> Result<MergeReport, Box<dyn std::error::Error>> {
+        let other_path = other_path.as_ref();
+        if other_path.canonicalize().ok() == Path::new(&self.config.storage_path).canonicalize().ok() {
+            return Err("cannot merge a store into itself".into());
+        }
+        let other = MindCache::with_config(MindCacheConfig {
+            storage_path: other_path.to_string_lossy().into_owned(),
+            read_only: true,
+            auto_decay_enabled: false,
+            ..Default::default()
+        })?;
+
+        let mut report = MergeReport::default();
+        // Remote ids of memories now stored here, and of those skipped
+        let mut merged: HashSet<String> = HashSet::new();
+        let mut skipped: HashSet<String> = HashSet::new();
+        let mut users: Vec<String> = other.storage.get_stats().into_keys().collect();
+        users.sort();
+        for user_id in &users {
+            // Sessions first, so imported memories pick up their retention
+            for (session_id, record) in other.storage.sessions().for_user(user_id) {
+                let known = self.storage.sessions().get(&session_id).is_some();
+                if known && policy != ConflictPolicy::KeepRemote {
+                    continue;
+                }
+                self.storage.sessions().set(&session_id, record)?;
+                let retention = other.storage.session_retention().get(&session_id);
+                if !retention.is_default() {
+                    self.storage.session_retention().set(&session_id, retention)?;
+                }
+                if !known {
+                    report.sessions_imported += 1;
+                }
+            }
+
+            let memories = other.storage.recall(QueryFilter { user_id: Some(user_id.clone()), include_superseded: true, ..Default::default() })?;
+            for remote in memories {
+                let remote_id = remote.id.clone();
+                if self.storage.trash().contains(&remote.id) {
+                    report.memories_skipped += 1;
+                    skipped.insert(remote_id);
+                    continue;
+                }
+                match self.storage.get_memory(&remote.id)? {
+                    None => {
+                        self.storage.import(remote)?;
+                        report.memories_imported += 1;
+                    }
+                    Some(local) if same(&local, &remote) => report.memories_unchanged += 1,
+                    Some(local) if (&local.user_id, &local.session_id) != (&remote.user_id, &remote.session_id) => {
+                        report.memories_skipped += 1;
+                        skipped.insert(remote_id);
+                        continue;
+                    }
+                    Some(local) if policy.remote_wins(&local, &remote) => {
+                        self.storage.overwrite(remote)?;
+                        report.memories_updated += 1;
+                    }
+                    Some(_) => report.memories_kept += 1,
+                }
+                merged.insert(remote_id);
+            }
+        }
+
+        // Links between merged memories, and provenance of or from them
+        let mut records = BTreeMap::new();
+        for id in &merged {
+            for link in other.storage.links().outgoing(id) {
+                if merged.contains(&link.to_id) {
+                    self.storage.links().link(&link.from_id, &link.to_id, &link.relation)?;
+                }
+            }
+            for record in other.storage.provenance().get(id).into_iter().chain(other.storage.provenance().derived_from(id)) {
+                records.insert(record.derived_id.clone(), record);
+            }
+        }
+        for (derived_id, record) in records {
+            let involves_skipped = skipped.contains(&derived_id) || record.derived_from.iter().any(|id| skipped.contains(id));
+            if !involves_skipped && self.storage.provenance().get(&derived_id).is_none() {
+                self.storage.provenance().record(record)?;
+            }
+        }
+        // Cached sessions no longer match their memories
+        self.reset_derived_state();
+
+        println!(
+            "Merged {}: {} imported, {} updated, {} kept, {} skipped",
+            other_path.display(),
+            report.memories_imported,
+            report.memories_updated,
+            report.memories_kept,
+            report.memories_skipped
+        );
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provenance::DerivationMethod;
+    use crate::{MemoryState, MemoryUpdate};
+    use crate::test_support::test_config;
+    use tempfile::TempDir;
+
+    fn open(dir: &TempDir) -> MindCache {
+        MindCache::with_config(test_config(dir)).unwrap()
+    }
+
+    #[test]
+    fn test_offline_copies_sync_both_ways() {
+        let (laptop_dir, server_dir) = (TempDir::new().unwrap(), TempDir::new().unwrap());
+        let mut server = open(&server_dir);
+        let shared = server.save("alice", "s1", "Prefers tea", None).unwrap();
+        let deleted = server.save("alice", "s1", "Old address", None).unwrap();
+        drop(server);
+
+        // The laptop starts from the server's store, then works offline
+        let mut laptop = open(&laptop_dir);
+        assert_eq!(laptop.merge_from(server_dir.path(), ConflictPolicy::Newest).unwrap().memories_imported, 2);
+        laptop.update_memory(&shared, MemoryUpdate { content: Some("Prefers green tea".to_string()), ..Default::default() }).unwrap();
+        laptop.delete_memory_soft(&deleted).unwrap();
+        let template = crate::SessionTemplate { tags: vec!["travel".to_string()], ..crate::SessionTemplate::new("trip") };
+        let trip = laptop.create_session_from_template("alice", &template).unwrap();
+        laptop.save("alice", &trip, "Flight on Monday", None).unwrap();
+        drop(laptop);
+
+        let mut server = open(&server_dir);
+        server.save("bob", "s2", "Likes jazz", None).unwrap();
+        let report = server.merge_from(laptop_dir.path(), ConflictPolicy::Newest).unwrap();
+        assert_eq!(
+            (report.memories_imported, report.memories_updated, report.memories_unchanged, report.sessions_imported),
+            (1, 1, 0, 1)
+        );
+        assert_eq!(server.get_memory(&shared).unwrap().unwrap().content, "Prefers green tea");
+        assert_eq!(server.session_manager.get_session(&trip).unwrap().unwrap().tags, ["travel"]);
+        // Deletions don't travel
+        assert!(server.get_memory(&deleted).unwrap().is_some());
+        drop(server);
+
+        // Back the other way; the deleted memory stays in the laptop's trash
+        let mut laptop = open(&laptop_dir);
+        let report = laptop.merge_from(server_dir.path(), ConflictPolicy::KeepLocal).unwrap();
+        assert_eq!((report.memories_imported, report.memories_skipped, report.memories_unchanged), (1, 1, 2));
+        assert_eq!(laptop.recall("bob", None, None, None).unwrap().len(), 1);
+        assert_eq!(laptop.merge_from(server_dir.path(), ConflictPolicy::KeepLocal).unwrap().memories_imported, 0);
+
+        assert!(laptop.merge_from(laptop_dir.path(), ConflictPolicy::Newest).is_err());
+    }
+
+    #[test]
+    fn test_conflict_policies() {
+        let (local_dir, remote_dir) = (TempDir::new().unwrap(), TempDir::new().unwrap());
+        let mut remote = open(&remote_dir);
+        let id = remote.save("alice", "s1", "Meeting at 10", None).unwrap();
+        drop(remote);
+        let mut local = open(&local_dir);
+        local.merge_from(remote_dir.path(), ConflictPolicy::Newest).unwrap();
+        local.update_memory(&id, MemoryUpdate { content: Some("Meeting at 11".to_string()), ..Default::default() }).unwrap();
+
+        // Local is at version 1, remote at 0
+        let report = local.merge_from(remote_dir.path(), ConflictPolicy::Newest).unwrap();
+        assert_eq!(report.memories_kept, 1);
+        let report = local.merge_from(remote_dir.path(), ConflictPolicy::KeepRemote).unwrap();
+        assert_eq!(report.memories_updated, 1);
+        let memory = local.get_memory(&id).unwrap().unwrap();
+        assert_eq!((memory.content.as_str(), memory.version), ("Meeting at 10", 0));
+    }
+
+    #[test]
+    fn test_links_and_provenance_come_along() {
+        let (local_dir, remote_dir) = (TempDir::new().unwrap(), TempDir::new().unwrap());
+        let mut remote = open(&remote_dir);
+        let old_city = remote.save("alice", "s1", "Lives in NYC", None).unwrap();
+        let new_city = remote.save("alice", "s1", "Moved to Austin", None).unwrap();
+        let note = remote.save("alice", "s1", "Had coffee", None).unwrap();
+        let dropped = remote.save("alice", "s1", "Old phone number", None).unwrap();
+        remote.supersede(&old_city, &new_city).unwrap();
+        remote.record_provenance("summary-1", vec![note.clone()], DerivationMethod::Compression).unwrap();
+        remote.link_memories(&note, &dropped, "related").unwrap();
+        drop(remote);
+
+        let mut local = open(&local_dir);
+        local.merge_from(remote_dir.path(), ConflictPolicy::Newest).unwrap();
+        assert_eq!(local.memory_state(&old_city).unwrap(), Some(MemoryState::Superseded { by: new_city.clone() }));
+        assert_eq!(local.recall("alice", Some("NYC"), None, None).unwrap().len(), 0);
+        assert_eq!(local.memory_state(&note).unwrap(), Some(MemoryState::Compressed { into: "summary-1".to_string() }));
+        assert_eq!(local.get_related(&note).unwrap().len(), 1);
+
+        // Nothing involving a memory skipped by the merge comes along
+        local.delete_memory_soft(&dropped).unwrap();
+        let mut remote = open(&remote_dir);
+        remote.link_memories(&note, &dropped, "follows").unwrap();
+        drop(remote);
+        local.merge_from(remote_dir.path(), ConflictPolicy::Newest).unwrap();
+        assert!(local.storage.links().outgoing(&note).iter().all(|link| link.relation != "follows"));
+    }
+}
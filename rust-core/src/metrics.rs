@@ -0,0 +1,212 @@
+//! Operational metrics: operation counters, latency histograms and store gauges
+//!
+//! Counters live in a [`Metrics`] registry shared by every clone of a storage
+//! handle and are updated lock-free. `MindCache::metrics()` takes a
+//! [`MetricsSnapshot`] that also reads the current store size and cache
+//! counters, and [`MetricsSnapshot::to_prometheus`] renders it in the
+//! Prometheus text exposition format.
+
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+use crate::cache::CacheStats;
+
+/// Upper bounds of the latency buckets, in seconds
+pub const LATENCY_BUCKETS: [f64; 10] = [0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.5, 1.0];
+
+/// Latency histogram with fixed buckets
+#[derive(Debug, Default)]
+struct Histogram {
+    buckets: [AtomicU64; LATENCY_BUCKETS.len()],
+    count: AtomicU64,
+    sum_micros: AtomicU64,
+}
+
+impl Histogram {
+    fn observe(&self, elapsed: Duration) {
+        let seconds = elapsed.as_secs_f64();
+        if let Some(bucket) = LATENCY_BUCKETS.iter().position(|bound| seconds <= *bound) {
+            self.buckets[bucket].fetch_add(1, Ordering::Relaxed);
+        }
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.sum_micros.fetch_add(elapsed.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    fn snapshot(&self) -> HistogramSnapshot {
+        // Buckets are cumulative, as in Prometheus
+        let mut cumulative = 0;
+        let buckets = self
+            .buckets
+            .iter()
+            .map(|bucket| {
+                cumulative += bucket.load(Ordering::Relaxed);
+                cumulative
+            })
+            .collect();
+        HistogramSnapshot {
+            buckets,
+            count: self.count.load(Ordering::Relaxed),
+            sum_seconds: self.sum_micros.load(Ordering::Relaxed) as f64 / 1_000_000.0,
+        }
+    }
+}
+
+/// Counters and histograms updated as operations run
+#[derive(Debug, Default)]
+pub struct Metrics {
+    saves: AtomicU64,
+    save_errors: AtomicU64,
+    recalls: AtomicU64,
+    recall_errors: AtomicU64,
+    decay_runs: AtomicU64,
+    decay_errors: AtomicU64,
+    save_latency: Histogram,
+    recall_latency: Histogram,
+    decay_latency: Histogram,
+}
+
+impl Metrics {
+    pub fn record_save(&self, elapsed: Duration, ok: bool) {
+        Self::record(&self.saves, &self.save_errors, &self.save_latency, elapsed, ok);
+    }
+
+    pub fn record_recall(&self, elapsed: Duration, ok: bool) {
+        Self::record(&self.recalls, &self.recall_errors, &self.recall_latency, elapsed, ok);
+    }
+
+    pub fn record_decay(&self, elapsed: Duration, ok: bool) {
+        Self::record(&self.decay_runs, &self.decay_errors, &self.decay_latency, elapsed, ok);
+    }
+
+    fn record(total: &AtomicU64, errors: &AtomicU64, latency: &Histogram, elapsed: Duration, ok: bool) {
+        total.fetch_add(1, Ordering::Relaxed);
+        if !ok {
+            errors.fetch_add(1, Ordering::Relaxed);
+        }
+        latency.observe(elapsed);
+    }
+
+    /// Current counter values together with the given store gauges
+    pub fn snapshot(&self, store: StoreGauges) -> MetricsSnapshot {
+        MetricsSnapshot {
+            saves_total: self.saves.load(Ordering::Relaxed),
+            save_errors_total: self.save_errors.load(Ordering::Relaxed),
+            recalls_total: self.recalls.load(Ordering::Relaxed),
+            recall_errors_total: self.recall_errors.load(Ordering::Relaxed),
+            decay_runs_total: self.decay_runs.load(Ordering::Relaxed),
+            decay_errors_total: self.decay_errors.load(Ordering::Relaxed),
+            save_latency: self.save_latency.snapshot(),
+            recall_latency: self.recall_latency.snapshot(),
+            decay_latency: self.decay_latency.snapshot(),
+            store,
+        }
+    }
+}
+
+/// Point-in-time state of the store
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StoreGauges {
+    pub memories: u64,
+    pub users: u64,
+    /// Size of the memory logs, including space `compact` would reclaim
+    pub storage_bytes: u64,
+    pub cache: CacheStats,
+}
+
+/// Cumulative bucket counts for [`LATENCY_BUCKETS`]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct HistogramSnapshot {
+    pub buckets: Vec<u64>,
+    pub count: u64,
+    pub sum_seconds: f64,
+}
+
+/// Everything `MindCache::metrics()` reports
+///
+/// Counters and histograms start at zero when the store is opened.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MetricsSnapshot {
+    pub saves_total: u64,
+    pub save_errors_total: u64,
+    pub recalls_total: u64,
+    pub recall_errors_total: u64,
+    pub decay_runs_total: u64,
+    pub decay_errors_total: u64,
+    pub save_latency: HistogramSnapshot,
+    pub recall_latency: HistogramSnapshot,
+    pub decay_latency: HistogramSnapshot,
+    pub store: StoreGauges,
+}
+
+impl MetricsSnapshot {
+    /// Render in the Prometheus text exposition format (version 0.0.4)
+    pub fn to_prometheus(&self) -> String {
+        let mut out = String::new();
+        let counters = [
+            ("mindcache_saves_total", "Memories saved", self.saves_total),
+            ("mindcache_save_errors_total", "Saves that failed", self.save_errors_total),
+            ("mindcache_recalls_total", "Recall queries run", self.recalls_total),
+            ("mindcache_recall_errors_total", "Recall queries that failed", self.recall_errors_total),
+            ("mindcache_decay_runs_total", "Decay passes run", self.decay_runs_total),
+            ("mindcache_decay_errors_total", "Decay passes that failed", self.decay_errors_total),
+            ("mindcache_cache_hits_total", "Memory cache hits", self.store.cache.hits),
+            ("mindcache_cache_misses_total", "Memory cache misses", self.store.cache.misses),
+        ];
+        for (name, help, value) in counters {
+            let _ = writeln!(out, "# HELP {} {}\n# TYPE {} counter\n{} {}", name, help, name, name, value);
+        }
+
+        let gauges = [
+            ("mindcache_memories", "Memories currently stored", self.store.memories),
+            ("mindcache_users", "Users with at least one memory", self.store.users),
+            ("mindcache_storage_bytes", "Size of the memory logs in bytes", self.store.storage_bytes),
+            ("mindcache_cache_entries", "Memories held in the cache", self.store.cache.entries as u64),
+        ];
+        for (name, help, value) in gauges {
+            let _ = writeln!(out, "# HELP {} {}\n# TYPE {} gauge\n{} {}", name, help, name, name, value);
+        }
+
+        let histograms = [
+            ("mindcache_save_duration_seconds", "Time taken by saves", &self.save_latency),
+            ("mindcache_recall_duration_seconds", "Time taken by recall queries", &self.recall_latency),
+            ("mindcache_decay_duration_seconds", "Time taken by decay passes", &self.decay_latency),
+        ];
+        for (name, help, histogram) in histograms {
+            let _ = writeln!(out, "# HELP {} {}\n# TYPE {} histogram", name, help, name);
+            for (bound, count) in LATENCY_BUCKETS.iter().zip(&histogram.buckets) {
+                let _ = writeln!(out, "{}_bucket{{le=\"{}\"}} {}", name, bound, count);
+            }
+            let _ = writeln!(out, "{}_bucket{{le=\"+Inf\"}} {}", name, histogram.count);
+            let _ = writeln!(out, "{}_sum {}\n{}_count {}", name, histogram.sum_seconds, name, histogram.count);
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_histogram_and_prometheus_output() {
+        let metrics = Metrics::default();
+        metrics.record_save(Duration::from_micros(300), true);
+        metrics.record_save(Duration::from_millis(20), false);
+        metrics.record_save(Duration::from_secs(3), true);
+        metrics.record_recall(Duration::from_millis(2), true);
+
+        let snapshot = metrics.snapshot(StoreGauges { memories: 2, users: 1, storage_bytes: 512, ..Default::default() });
+        assert_eq!(snapshot.saves_total, 3);
+        assert_eq!(snapshot.save_errors_total, 1);
+        assert_eq!(snapshot.save_latency.buckets, [1, 1, 1, 1, 1, 2, 2, 2, 2, 2]);
+        assert_eq!(snapshot.save_latency.count, 3);
+
+        let text = snapshot.to_prometheus();
+        assert!(text.contains("# TYPE mindcache_saves_total counter\nmindcache_saves_total 3\n"));
+        assert!(text.contains("mindcache_storage_bytes 512\n"));
+        assert!(text.contains("mindcache_save_duration_seconds_bucket{le=\"0.0005\"} 1\n"));
+        assert!(text.contains("mindcache_save_duration_seconds_bucket{le=\"+Inf\"} 3\n"));
+        assert!(text.contains("mindcache_recall_duration_seconds_count 1\n"));
+    }
+}
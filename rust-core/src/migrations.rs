@@ -0,0 +1,121 @@
+//! Versioned upgrades of the on-disk format
+//!
+//! A store records its format version in `VERSION`. When it is opened for
+//! writing, every migration from that version up to [`STORAGE_VERSION`] runs
+//! in order. Before each one the store's files are copied to
+//! `migrations/v<version>/`, and the version is recorded as soon as the
+//! migration finishes, so a crash midway only repeats the migration that was
+//! running. Stores from before `VERSION` existed are recognised by their
+//! logs. Opening a store that needs migrating read-only fails.
+//!
+//! To change the format, bump [`STORAGE_VERSION`] and append a [`Migration`]
+//! from the previous version to [`MIGRATIONS`].
+
+use crate::record;
+use crate::storage::MemoryStorage;
+
+/// Blob holding the store's format version
+pub const VERSION_BLOB: &str = "VERSION";
+
+/// Directory the files of a store are copied to before a migration
+pub const BACKUP_DIR: &str = "migrations";
+
+/// Format version of stores written by this build
+pub const STORAGE_VERSION: u32 = 2;
+
+/// One step of the upgrade path
+pub struct Migration {
+    /// Version the migration upgrades; it leaves the store at `from + 1`
+    pub from: u32,
+    pub description: &'static str,
+    run: fn(&MemoryStorage) -> Result<(), Box<dyn std::error::Error>>,
+}
+
+/// Every migration, oldest first
+pub const MIGRATIONS: &[Migration] = &[Migration {
+    from: 1,
+    description: "rewrite logs with a header and checksummed record frames",
+    run: MemoryStorage::upgrade_logs,
+}];
+
+/// Where the files of a store at `version` are copied before migrating it
+pub fn backup_dir(version: u32) -> String {
+    format!("{}/v{}", BACKUP_DIR, version)
+}
+
+/// The format version of the store, detected from its logs if it predates `VERSION`
+pub fn stored_version(storage: &MemoryStorage) -> Result<u32, Box<dyn std::error::Error>> {
+    let backend = storage.backend();
+    if let Some(data) = backend.read_blob(VERSION_BLOB)? {
+        let text = String::from_utf8_lossy(&data);
+        return Ok(text.trim().parse().map_err(|_| format!("{} holds {:?}, not a version", VERSION_BLOB, text.trim()))?);
+    }
+    for log in storage.segments().logs() {
+        let size = backend.size(&log)?;
+        if size > 0 && record::log_format(&backend.read_at(&log, 0, size.min(record::LOG_HEADER_LEN) as usize)?) < record::LOG_FORMAT {
+            return Ok(1);
+        }
+    }
+    Ok(STORAGE_VERSION)
+}
+
+/// Bring the store up to [`STORAGE_VERSION`], returning the version it was at
+pub fn migrate(storage: &MemoryStorage) -> Result<u32, Box<dyn std::error::Error>> {
+    let backend = storage.backend();
+    let found = stored_version(storage)?;
+    if found > STORAGE_VERSION {
+        return Err(format!("store {} has format version {}, newer than this build supports ({})", backend.location(), found, STORAGE_VERSION).into());
+    }
+    if backend.is_read_only() {
+        if found < STORAGE_VERSION {
+            return Err(format!("store {} has format version {}; open it for writing once to upgrade it", backend.location(), found).into());
+        }
+        return Ok(found);
+    }
+
+    let mut version = found;
+    for migration in MIGRATIONS.iter().filter(|migration| migration.from >= found) {
+        let dir = backup_dir(version);
+        for name in storage.store_file_names() {
+            if let Some(data) = backend.read_blob(&name)? {
+                backend.write_blob(&format!("{}/{}", dir, name), &data)?;
+            }
+        }
+        println!("Migrating {} from format version {}: {} (old files in {})", backend.location(), version, migration.description, dir);
+        (migration.run)(storage)?;
+        version = migration.from + 1;
+        backend.write_blob(VERSION_BLOB, format!("{}\n", version).as_bytes())?;
+    }
+    if backend.read_blob(VERSION_BLOB)?.is_none() {
+        backend.write_blob(VERSION_BLOB, format!("{}\n", STORAGE_VERSION).as_bytes())?;
+    }
+    Ok(found)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_migrations_form_a_path_to_the_current_version() {
+        let mut version = 1;
+        for migration in MIGRATIONS {
+            assert_eq!(migration.from, version);
+            version += 1;
+        }
+        assert_eq!(version, STORAGE_VERSION);
+    }
+
+    #[test]
+    fn test_new_stores_start_at_the_current_version() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let storage = MemoryStorage::new(temp_dir.path().to_str().unwrap()).unwrap();
+        assert_eq!(stored_version(&storage).unwrap(), STORAGE_VERSION);
+        assert_eq!(std::fs::read_to_string(temp_dir.path().join(VERSION_BLOB)).unwrap().trim(), STORAGE_VERSION.to_string());
+        assert!(!temp_dir.path().join(BACKUP_DIR).exists());
+
+        std::fs::write(temp_dir.path().join(VERSION_BLOB), "99").unwrap();
+        drop(storage);
+        assert!(MemoryStorage::new(temp_dir.path().to_str().unwrap()).is_err());
+    }
+}
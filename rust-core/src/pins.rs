@@ -0,0 +1,148 @@
+//! Pinned memories
+//!
+//! A memory is pinned while its `pinned` metadata key is set, by
+//! `MindCache::pin` or by saving it with that key. Decay leaves pinned
+//! memories alone whatever their age, TTL or importance: they are never
+//! expired, dropped over a limit or compressed. `QueryFilter::pinned_only`
+//! finds them, e.g. to see what a stricter decay policy would spare.
+
+use crate::storage::MemoryItem;
+use crate::versioning::MemoryUpdate;
+use crate::MindCache;
+
+/// Metadata key marking a memory as pinned; its value is when it was pinned
+pub const PINNED_KEY: &str = "pinned";
+
+/// Whether decay must leave a memory alone because it is pinned
+pub fn is_pinned(memory: &MemoryItem) -> bool {
+    memory.metadata.contains_key(PINNED_KEY)
+}
+
+impl MindCache {
+    /// Pin a memory so decay never removes or compresses it; returns the memory
+    ///
+    /// Pinning a pinned memory changes nothing.
+    pub fn pin(&mut self, memory_id: &str) -> Result<MemoryItem, Box<dyn std::error::Error>> {
+        let memory = self.get_memory(memory_id)?.ok_or_else(|| format!("memory {} not found", memory_id))?;
+        if is_pinned(&memory) {
+            return Ok(memory);
+        }
+        let mut metadata = memory.metadata;
+        metadata.insert(PINNED_KEY.to_string(), self.storage.now().to_rfc3339());
+        self.update_memory(memory_id, MemoryUpdate { metadata: Some(metadata), ..Default::default() })
+    }
+
+    /// Let decay treat a pinned memory like any other again; returns the memory
+    pub fn unpin(&mut self, memory_id: &str) -> Result<MemoryItem, Box<dyn std::error::Error>> {
+        let memory = self.get_memory(memory_id)?.ok_or_else(|| format!("memory {} not found", memory_id))?;
+        if !is_pinned(&memory) {
+            return Ok(memory);
+        }
+        let mut metadata = memory.metadata;
+        metadata.remove(PINNED_KEY);
+        self.update_memory(memory_id, MemoryUpdate { metadata: Some(metadata), ..Default::default() })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use chrono::{Duration, Utc};
+    use crate::events::MemoryEvent;
+    use crate::storage::{MemoryItem, QueryFilter};
+    use crate::{MindCache, MindCacheConfig};
+    use crate::test_support::temp_cache_with;
+    use tempfile::TempDir;
+
+    /// Alice's stale, pinned and kept notes, saved ten hours ago; the first two have a one-hour TTL
+    fn notes() -> (MindCache, TempDir, Vec<String>) {
+        let (mut cache, temp_dir) = temp_cache_with(MindCacheConfig { enable_compression: false, ..Default::default() });
+        let old = Utc::now() - Duration::hours(10);
+        let mut ids = Vec::new();
+        for (content, ttl_hours, importance) in [("stale note", Some(1), 0.1), ("pinned note", Some(1), 0.2), ("kept note", None, 0.6)] {
+            ids.push(cache.storage.save(MemoryItem {
+                user_id: "alice".to_string(),
+                session_id: "s1".to_string(),
+                content: content.to_string(),
+                timestamp: old,
+                ttl_hours,
+                importance,
+                ..Default::default()
+            }).unwrap());
+        }
+        cache.pin(&ids[1]).unwrap();
+        (cache, temp_dir, ids)
+    }
+
+    fn alice() -> QueryFilter {
+        QueryFilter { user_id: Some("alice".to_string()), ..Default::default() }
+    }
+
+    fn ids_of(cache: &MindCache, filter: QueryFilter) -> Vec<String> {
+        let mut ids: Vec<String> = cache.recall_advanced(filter).unwrap().into_iter().map(|memory| memory.id).collect();
+        ids.sort();
+        ids
+    }
+
+    /// Ids of the memories each decay run expires
+    fn record_expiries(cache: &mut MindCache) -> Arc<Mutex<Vec<String>>> {
+        let expired = Arc::new(Mutex::new(Vec::new()));
+        let seen = Arc::clone(&expired);
+        cache.subscribe(move |event: &MemoryEvent| {
+            if let MemoryEvent::MemoryExpired { memory_id, .. } = event {
+                seen.lock().unwrap().push(memory_id.clone());
+            }
+        });
+        expired
+    }
+
+    #[test]
+    fn test_pin_and_unpin_set_the_metadata_key() {
+        let (mut cache, _temp_dir, ids) = notes();
+        assert!(super::is_pinned(&cache.pin(&ids[1]).unwrap()));
+        assert!(!super::is_pinned(&cache.unpin(&ids[1]).unwrap()));
+        assert!(!super::is_pinned(&cache.unpin(&ids[1]).unwrap()));
+        assert!(cache.pin("missing").is_err());
+    }
+
+    #[test]
+    fn test_importance_range_filters() {
+        let (cache, _temp_dir, ids) = notes();
+        let mut low = vec![ids[0].clone(), ids[1].clone()];
+        low.sort();
+        assert_eq!(ids_of(&cache, QueryFilter { max_importance: Some(0.5), ..alice() }), low);
+        assert_eq!(ids_of(&cache, QueryFilter { min_importance: Some(0.15), max_importance: Some(0.5), ..alice() }), vec![ids[1].clone()]);
+    }
+
+    #[test]
+    fn test_ttl_presence_filter() {
+        let (cache, _temp_dir, ids) = notes();
+        assert_eq!(ids_of(&cache, QueryFilter { has_ttl: Some(false), ..alice() }), vec![ids[2].clone()]);
+        assert_eq!(cache.count(QueryFilter { has_ttl: Some(true), ..alice() }).unwrap(), 2);
+    }
+
+    #[test]
+    fn test_pinned_only_filter() {
+        let (mut cache, _temp_dir, ids) = notes();
+        assert_eq!(ids_of(&cache, QueryFilter { pinned_only: true, ..alice() }), vec![ids[1].clone()]);
+        cache.unpin(&ids[1]).unwrap();
+        assert_eq!(cache.count(QueryFilter { pinned_only: true, ..alice() }).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_decay_spares_pinned_memories() {
+        let (mut cache, _temp_dir, ids) = notes();
+        let expired = record_expiries(&mut cache);
+        cache.decay().unwrap();
+        assert_eq!(*expired.lock().unwrap(), vec![ids[0].clone()]);
+    }
+
+    #[test]
+    fn test_unpinned_memories_decay_again() {
+        let (mut cache, _temp_dir, ids) = notes();
+        let expired = record_expiries(&mut cache);
+        cache.unpin(&ids[1]).unwrap();
+        cache.decay().unwrap();
+        assert!(expired.lock().unwrap().contains(&ids[1]));
+    }
+}
@@ -0,0 +1,135 @@
+//! Recall query planning
+//!
+//! `MemoryStorage` can produce recall candidates from several indexes. The
+//! planner estimates how many records each access path would have to read
+//! for a filter and picks the cheapest one. Record reads (disk IO plus
+//! deserialization) dominate recall cost, so the estimate is simply the
+//! number of candidate records.
+
+use std::collections::HashSet;
+use std::fmt;
+use serde::{Deserialize, Serialize};
+
+/// Where recall candidates come from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum AccessPath {
+    /// Every record in the store
+    FullScan,
+    /// All records of one user
+    UserIndex,
+    /// Records of matching sessions
+    SessionIndex,
+    /// Records containing a query keyword
+    InvertedIndex,
+}
+
+impl fmt::Display for AccessPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            AccessPath::FullScan => "full scan",
+            AccessPath::UserIndex => "user index",
+            AccessPath::SessionIndex => "session index",
+            AccessPath::InvertedIndex => "inverted index",
+        };
+        f.write_str(name)
+    }
+}
+
+/// The access path chosen for a recall, with the estimates behind the choice
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QueryPlan {
+    pub access_path: AccessPath,
+    /// Records the chosen path will read
+    pub estimated_candidates: usize,
+    /// Records in the store
+    pub total_records: usize,
+    /// Every applicable path with its estimated candidate count
+    pub considered: Vec<(AccessPath, usize)>,
+}
+
+impl QueryPlan {
+    /// Pick the path with the fewest estimated candidates
+    ///
+    /// Ties go to the earlier entry, so callers list more specific paths first.
+    pub fn choose(total_records: usize, considered: Vec<(AccessPath, usize)>) -> Self {
+        let (access_path, estimated_candidates) = considered
+            .iter()
+            .copied()
+            .fold(None, |best: Option<(AccessPath, usize)>, candidate| match best {
+                Some(best) if best.1 <= candidate.1 => Some(best),
+                _ => Some(candidate),
+            })
+            .unwrap_or((AccessPath::FullScan, total_records));
+
+        QueryPlan {
+            access_path,
+            estimated_candidates,
+            total_records,
+            considered,
+        }
+    }
+}
+
+impl fmt::Display for QueryPlan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} (~{} of {} records)",
+            self.access_path, self.estimated_candidates, self.total_records
+        )?;
+        for (path, estimate) in &self.considered {
+            write!(f, "\n  considered {}: ~{}", path, estimate)?;
+        }
+        Ok(())
+    }
+}
+
+/// Lowercased alphanumeric terms of a memory's content, as stored in the inverted index
+pub fn index_terms(content: &str) -> HashSet<String> {
+    content
+        .to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|term| !term.is_empty())
+        .map(|term| term.to_string())
+        .collect()
+}
+
+/// Whether the inverted index can answer a keyword exactly
+///
+/// Keyword matching is a case-insensitive substring test. A purely
+/// alphanumeric keyword can only occur inside a single indexed term, so the
+/// terms containing it give exactly the matching records. Keywords with
+/// spaces or punctuation can span terms and need a scan.
+pub fn is_indexable_keyword(keyword: &str) -> bool {
+    !keyword.is_empty() && keyword.chars().all(char::is_alphanumeric)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_choose_cheapest_path() {
+        let plan = QueryPlan::choose(100, vec![
+            (AccessPath::InvertedIndex, 3),
+            (AccessPath::UserIndex, 40),
+            (AccessPath::FullScan, 100),
+        ]);
+        assert_eq!(plan.access_path, AccessPath::InvertedIndex);
+        assert_eq!(plan.estimated_candidates, 3);
+
+        let plan = QueryPlan::choose(10, vec![(AccessPath::SessionIndex, 10), (AccessPath::FullScan, 10)]);
+        assert_eq!(plan.access_path, AccessPath::SessionIndex);
+    }
+
+    #[test]
+    fn test_index_terms() {
+        let terms = index_terms("Closed AAPL at $184.20, good discipline!");
+        assert!(terms.contains("aapl"));
+        assert!(terms.contains("184"));
+        assert!(terms.contains("discipline"));
+        assert!(is_indexable_keyword("AAPL"));
+        assert!(!is_indexable_keyword("gold futures"));
+        assert!(!is_indexable_keyword(""));
+    }
+}
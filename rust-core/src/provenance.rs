@@ -0,0 +1,203 @@
+//! Provenance of derived memories
+//!
+//! Session summaries, compressed memories and dedupe merges record which
+//! memories they were built from, so a derived item can always be traced back
+//! to its sources. Records live in `provenance.json` next to the memory log.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, RwLock};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use crate::backend::StorageBackend;
+
+pub const PROVENANCE_BLOB: &str = "provenance.json";
+
+/// How a derived item was produced
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DerivationMethod {
+    SessionSummary,
+    Compression,
+    Dedupe,
+    FactExtraction,
+    /// Daily and user digests rolled up from summaries
+    Digest,
+    /// Derived by the application
+    Other(String),
+}
+
+/// Where a derived item came from
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProvenanceRecord {
+    pub derived_id: String,
+    pub derived_from: Vec<String>,
+    pub method: DerivationMethod,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Persistent map of derived id -> provenance record
+#[derive(Clone)]
+pub struct ProvenanceLog {
+    backend: Arc<dyn StorageBackend>,
+    records: Arc<RwLock<HashMap<String, ProvenanceRecord>>>,
+}
+
+impl ProvenanceLog {
+    pub fn load(backend: Arc<dyn StorageBackend>) -> Result<Self, Box<dyn std::error::Error>> {
+        let records = Self::read_records(backend.as_ref())?;
+        Ok(ProvenanceLog {
+            backend,
+            records: Arc::new(RwLock::new(records)),
+        })
+    }
+
+    /// Re-read the records from disk, e.g. after a restore
+    pub fn reload(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let records = Self::read_records(self.backend.as_ref())?;
+        *self.records.write().unwrap_or_else(|poisoned| poisoned.into_inner()) = records;
+        Ok(())
+    }
+
+    fn read_records(backend: &dyn StorageBackend) -> Result<HashMap<String, ProvenanceRecord>, Box<dyn std::error::Error>> {
+        Ok(match backend.read_blob(PROVENANCE_BLOB)? {
+            Some(data) => serde_json::from_slice::<Vec<ProvenanceRecord>>(&data)?
+                .into_iter()
+                .map(|record| (record.derived_id.clone(), record))
+                .collect(),
+            None => HashMap::new(),
+        })
+    }
+
+    /// Store a record, replacing any earlier one for the same derived id
+    ///
+    /// Recording the same sources and method again is a no-op, so derived
+    /// items that get rebuilt on every run don't rewrite the file each time.
+    pub fn record(&self, record: ProvenanceRecord) -> Result<(), Box<dyn std::error::Error>> {
+        let mut records = self.records.write().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if let Some(existing) = records.get(&record.derived_id) {
+            if existing.method == record.method && existing.derived_from == record.derived_from {
+                return Ok(());
+            }
+        }
+
+        let previous = records.insert(record.derived_id.clone(), record.clone());
+        if let Err(e) = self.persist(&records) {
+            match previous {
+                Some(previous) => records.insert(record.derived_id, previous),
+                None => records.remove(&record.derived_id),
+            };
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    pub fn get(&self, derived_id: &str) -> Option<ProvenanceRecord> {
+        self.records
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .get(derived_id)
+            .cloned()
+    }
+
+    /// Records of every item derived (directly) from `source_id`
+    pub fn derived_from(&self, source_id: &str) -> Vec<ProvenanceRecord> {
+        let mut records: Vec<ProvenanceRecord> = self
+            .records
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .values()
+            .filter(|record| record.derived_from.iter().any(|id| id == source_id))
+            .cloned()
+            .collect();
+        records.sort_by_key(|record| record.created_at);
+        records
+    }
+
+    /// Remove every record that derives from, or describes, one of `ids`
+    ///
+    /// Returns the removed records.
+    pub fn remove_involving(&self, ids: &HashSet<String>) -> Result<Vec<ProvenanceRecord>, Box<dyn std::error::Error>> {
+        let mut records = self.records.write().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let involved = |record: &ProvenanceRecord| {
+            ids.contains(&record.derived_id) || record.derived_from.iter().any(|id| ids.contains(id))
+        };
+        let removed: Vec<ProvenanceRecord> = records.values().filter(|record| involved(record)).cloned().collect();
+        if removed.is_empty() {
+            return Ok(removed);
+        }
+
+        for record in &removed {
+            records.remove(&record.derived_id);
+        }
+        if let Err(e) = self.persist(&records) {
+            for record in &removed {
+                records.insert(record.derived_id.clone(), record.clone());
+            }
+            return Err(e);
+        }
+        Ok(removed)
+    }
+
+    fn persist(&self, records: &HashMap<String, ProvenanceRecord>) -> Result<(), Box<dyn std::error::Error>> {
+        let mut sorted: Vec<&ProvenanceRecord> = records.values().collect();
+        sorted.sort_by(|a, b| a.derived_id.cmp(&b.derived_id));
+        self.backend.write_blob(PROVENANCE_BLOB, &serde_json::to_vec(&sorted)?)?;
+        Ok(())
+    }
+}
+
+/// Stable id for an item derived from a set of sources
+///
+/// The same sources always give the same id, whatever their order.
+pub fn derived_id(prefix: &str, sources: &[String]) -> String {
+    let mut sorted: Vec<&str> = sources.iter().map(String::as_str).collect();
+    sorted.sort_unstable();
+
+    // FNV-1a: stable across builds, unlike the std hasher
+    let hash = sorted.iter().flat_map(|id| id.bytes().chain(std::iter::once(0))).fold(
+        0xcbf29ce484222325u64,
+        |hash, byte| (hash ^ byte as u64).wrapping_mul(0x100000001b3),
+    );
+    format!("{}-{:016x}", prefix, hash)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::FileBackend;
+    use tempfile::TempDir;
+
+    fn record(derived_id: &str, sources: &[&str]) -> ProvenanceRecord {
+        ProvenanceRecord {
+            derived_id: derived_id.to_string(),
+            derived_from: sources.iter().map(|id| id.to_string()).collect(),
+            method: DerivationMethod::Compression,
+            created_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_records_survive_reload() {
+        let temp_dir = TempDir::new().unwrap();
+        let backend: Arc<dyn StorageBackend> = Arc::new(FileBackend::new(temp_dir.path()).unwrap());
+
+        let log = ProvenanceLog::load(Arc::clone(&backend)).unwrap();
+        log.record(record("c1", &["m1", "m2"])).unwrap();
+        log.record(record("c2", &["m2", "m3"])).unwrap();
+
+        let reloaded = ProvenanceLog::load(backend).unwrap();
+        assert_eq!(reloaded.get("c1").unwrap().derived_from, vec!["m1", "m2"]);
+        assert!(reloaded.get("m1").is_none());
+        assert_eq!(reloaded.derived_from("m2").len(), 2);
+    }
+
+    #[test]
+    fn test_derived_id_ignores_source_order() {
+        let a = derived_id("compressed", &["m1".to_string(), "m2".to_string()]);
+        let b = derived_id("compressed", &["m2".to_string(), "m1".to_string()]);
+        let c = derived_id("compressed", &["m1".to_string(), "m3".to_string()]);
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+        assert!(a.starts_with("compressed-"));
+    }
+}
@@ -0,0 +1,237 @@
+//! Boolean keyword queries
+//!
+//! Parses search expressions such as `"stop loss" AND AAPL NOT crypto` into
+//! a query tree. Words and quoted phrases are combined with `AND`, `OR` and
+//! `NOT` (operators are upper case); adjacent terms without an operator are
+//! ANDed and parentheses group. `NOT` binds tightest, then `AND`, then `OR`.
+//!
+//! Words follow the filter's match mode; phrases always match as exact,
+//! case-insensitive substrings.
+
+use std::fmt;
+use crate::matching::MatchMode;
+
+/// A parsed boolean query
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum QueryExpr {
+    Term(String),
+    Phrase(String),
+    And(Box<QueryExpr>, Box<QueryExpr>),
+    Or(Box<QueryExpr>, Box<QueryExpr>),
+    Not(Box<QueryExpr>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Word(String),
+    Phrase(String),
+    And,
+    Or,
+    Not,
+    Open,
+    Close,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Word(word) => write!(f, "'{}'", word),
+            Token::Phrase(phrase) => write!(f, "\"{}\"", phrase),
+            Token::And => f.write_str("AND"),
+            Token::Or => f.write_str("OR"),
+            Token::Not => f.write_str("NOT"),
+            Token::Open => f.write_str("'('"),
+            Token::Close => f.write_str("')'"),
+        }
+    }
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>, Box<dyn std::error::Error>> {
+    let mut tokens = Vec::new();
+    let mut chars = input.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '(' => {
+                chars.next();
+                tokens.push(Token::Open);
+            }
+            ')' => {
+                chars.next();
+                tokens.push(Token::Close);
+            }
+            '"' => {
+                chars.next();
+                let mut phrase = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some(c) => phrase.push(c),
+                        None => return Err("unterminated quote in query".into()),
+                    }
+                }
+                let phrase = phrase.trim().to_lowercase();
+                if phrase.is_empty() {
+                    return Err("empty phrase in query".into());
+                }
+                tokens.push(Token::Phrase(phrase));
+            }
+            _ => {
+                let mut word = String::new();
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || matches!(c, '(' | ')' | '"') {
+                        break;
+                    }
+                    word.push(c);
+                    chars.next();
+                }
+                tokens.push(match word.as_str() {
+                    "AND" => Token::And,
+                    "OR" => Token::Or,
+                    "NOT" => Token::Not,
+                    _ => Token::Word(word.to_lowercase()),
+                });
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn parse_or(&mut self) -> Result<QueryExpr, Box<dyn std::error::Error>> {
+        let mut expr = self.parse_and()?;
+        while self.peek() == Some(&Token::Or) {
+            self.next();
+            expr = QueryExpr::Or(Box::new(expr), Box::new(self.parse_and()?));
+        }
+        Ok(expr)
+    }
+
+    fn parse_and(&mut self) -> Result<QueryExpr, Box<dyn std::error::Error>> {
+        let mut expr = self.parse_unary()?;
+        loop {
+            match self.peek() {
+                Some(Token::And) => {
+                    self.next();
+                }
+                // Adjacent terms are implicitly ANDed
+                Some(Token::Word(_) | Token::Phrase(_) | Token::Not | Token::Open) => {}
+                _ => return Ok(expr),
+            }
+            expr = QueryExpr::And(Box::new(expr), Box::new(self.parse_unary()?));
+        }
+    }
+
+    fn parse_unary(&mut self) -> Result<QueryExpr, Box<dyn std::error::Error>> {
+        match self.next() {
+            Some(Token::Not) => Ok(QueryExpr::Not(Box::new(self.parse_unary()?))),
+            Some(Token::Word(word)) => Ok(QueryExpr::Term(word)),
+            Some(Token::Phrase(phrase)) => Ok(QueryExpr::Phrase(phrase)),
+            Some(Token::Open) => {
+                let expr = self.parse_or()?;
+                match self.next() {
+                    Some(Token::Close) => Ok(expr),
+                    _ => Err("missing ')' in query".into()),
+                }
+            }
+            Some(token) => Err(format!("unexpected {} in query", token).into()),
+            None => Err("query ends where a term was expected".into()),
+        }
+    }
+}
+
+/// Parse a boolean search expression
+pub fn parse(input: &str) -> Result<QueryExpr, Box<dyn std::error::Error>> {
+    let tokens = tokenize(input)?;
+    if tokens.is_empty() {
+        return Err("empty query".into());
+    }
+
+    let mut parser = Parser { tokens, position: 0 };
+    let expr = parser.parse_or()?;
+    if let Some(token) = parser.peek() {
+        return Err(format!("unexpected {} in query", token).into());
+    }
+    Ok(expr)
+}
+
+impl QueryExpr {
+    /// Evaluate against lowercased content
+    pub fn matches(&self, content: &str, match_mode: MatchMode) -> bool {
+        match self {
+            QueryExpr::Term(word) => match_mode.content_matches(content, word),
+            QueryExpr::Phrase(phrase) => content.contains(phrase.as_str()),
+            QueryExpr::And(left, right) => left.matches(content, match_mode) && right.matches(content, match_mode),
+            QueryExpr::Or(left, right) => left.matches(content, match_mode) || right.matches(content, match_mode),
+            QueryExpr::Not(inner) => !inner.matches(content, match_mode),
+        }
+    }
+
+    /// Words and phrases a match may contain, i.e. those not under a `NOT`
+    pub fn positive_terms(&self) -> Vec<&str> {
+        match self {
+            QueryExpr::Term(term) | QueryExpr::Phrase(term) => vec![term.as_str()],
+            QueryExpr::And(left, right) | QueryExpr::Or(left, right) => {
+                let mut terms = left.positive_terms();
+                terms.extend(right.positive_terms());
+                terms
+            }
+            QueryExpr::Not(_) => Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn term(word: &str) -> Box<QueryExpr> {
+        Box::new(QueryExpr::Term(word.to_string()))
+    }
+
+    #[test]
+    fn test_parse_precedence() {
+        let expr = parse("\"Stop Loss\" AND AAPL NOT crypto OR gold").unwrap();
+        assert_eq!(expr, QueryExpr::Or(
+            Box::new(QueryExpr::And(
+                Box::new(QueryExpr::And(Box::new(QueryExpr::Phrase("stop loss".to_string())), term("aapl"))),
+                Box::new(QueryExpr::Not(term("crypto"))),
+            )),
+            term("gold"),
+        ));
+
+        let grouped = parse("NOT (gold OR silver)").unwrap();
+        assert_eq!(grouped, QueryExpr::Not(Box::new(QueryExpr::Or(term("gold"), term("silver")))));
+
+        for bad in ["", "gold AND", "(gold", "gold)", "\"stop loss", "OR gold"] {
+            assert!(parse(bad).is_err(), "{:?} should not parse", bad);
+        }
+    }
+
+    #[test]
+    fn test_matches() {
+        let expr = parse("\"stop loss\" AND aapl NOT crypto").unwrap();
+        assert!(expr.matches("moved the stop loss on aapl", MatchMode::Exact));
+        assert!(!expr.matches("moved the stop on aapl, loss taken", MatchMode::Exact));
+        assert!(!expr.matches("stop loss on aapl and crypto", MatchMode::Exact));
+    }
+}
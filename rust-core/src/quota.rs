@@ -0,0 +1,139 @@
+//! Per-user memory quota checked at save time
+//!
+//! Decay trims users over `max_memories_per_user` only when it runs. With a
+//! [`QuotaPolicy`] other than `Decay`, every save by a user at the limit
+//! either fails with [`QuotaExceeded`] or first evicts one of their memories,
+//! so the limit holds between decay runs too.
+
+use std::collections::HashSet;
+use std::fmt;
+use serde::{Deserialize, Serialize};
+use crate::audit::AuditAction;
+use crate::events::MemoryEvent;
+use crate::MindCache;
+
+/// What a save does when the user already has `max_memories_per_user` memories
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QuotaPolicy {
+    /// Save anyway and leave trimming to the next decay run
+    #[default]
+    Decay,
+    /// Fail the save with [`QuotaExceeded`]
+    Reject,
+    /// Delete the user's least important memory, the oldest among ties
+    EvictLeastImportant,
+    /// Delete the user's oldest memory
+    EvictOldest,
+}
+
+/// A save was refused because the user is at their memory limit
+#[derive(Debug)]
+pub struct QuotaExceeded {
+    pub user_id: String,
+    pub limit: usize,
+}
+
+impl fmt::Display for QuotaExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "user {} has reached the limit of {} memories", self.user_id, self.limit)
+    }
+}
+
+impl std::error::Error for QuotaExceeded {}
+
+impl MindCache {
+    /// Make room for one more memory of `user_id` according to the quota policy
+    pub(crate) fn enforce_quota(&self, user_id: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let limit = self.config.max_memories_per_user;
+        let count = self.storage.user_memory_count(user_id);
+        if count < limit {
+            return Ok(());
+        }
+
+        let excess = count + 1 - limit;
+        let mut records = match self.config.quota_policy {
+            QuotaPolicy::Decay => return Ok(()),
+            QuotaPolicy::Reject => {
+                return Err(Box::new(QuotaExceeded { user_id: user_id.to_string(), limit }));
+            }
+            QuotaPolicy::EvictLeastImportant => {
+                let mut records = self.storage.user_records(user_id)?;
+                records.sort_by(|(_, a), (_, b)| {
+                    a.importance.total_cmp(&b.importance).then(a.timestamp.cmp(&b.timestamp))
+                });
+                records
+            }
+            QuotaPolicy::EvictOldest => {
+                let mut records = self.storage.user_records(user_id)?;
+                records.sort_by_key(|(_, memory)| memory.timestamp);
+                records
+            }
+        };
+        records.truncate(excess);
+
+        let positions: HashSet<usize> = records.iter().map(|(position, _)| *position).collect();
+        self.storage.delete_positions(&positions, AuditAction::Evict)?;
+        for (_, memory) in records {
+            println!("Evicted memory {} of user {} to stay within quota", memory.id, user_id);
+            self.storage.events().emit(MemoryEvent::MemoryExpired {
+                memory_id: memory.id,
+                user_id: memory.user_id,
+                session_id: memory.session_id,
+            });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MindCacheConfig;
+    use crate::test_support::test_config;
+    use tempfile::TempDir;
+
+    fn cache_with(temp_dir: &TempDir, quota_policy: QuotaPolicy) -> MindCache {
+        MindCache::with_config(MindCacheConfig {
+            max_memories_per_user: 3,
+            quota_policy,
+            ..test_config(temp_dir)
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_quota_policies() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut cache = cache_with(&temp_dir, QuotaPolicy::Reject);
+        for i in 0..3 {
+            cache.save_with_options("alice", "s1", &format!("memory {}", i), None, 0.5, None).unwrap();
+        }
+        let err = cache.save("alice", "s1", "one too many", None).unwrap_err();
+        let exceeded = err.downcast_ref::<QuotaExceeded>().expect("quota error");
+        assert_eq!(exceeded.limit, 3);
+        cache.save("bob", "s1", "other users are unaffected", None).unwrap();
+        drop(cache);
+
+        let temp_dir = TempDir::new().unwrap();
+        let mut cache = cache_with(&temp_dir, QuotaPolicy::EvictLeastImportant);
+        cache.save_with_options("alice", "s1", "important", None, 0.9, None).unwrap();
+        let trivial = cache.save_with_options("alice", "s1", "trivial", None, 0.1, None).unwrap();
+        cache.save_with_options("alice", "s1", "useful", None, 0.6, None).unwrap();
+        cache.save_with_options("alice", "s1", "new", None, 0.5, None).unwrap();
+        let remaining = cache.recall("alice", None, None, None).unwrap();
+        assert_eq!(remaining.len(), 3);
+        assert!(remaining.iter().all(|memory| memory.id != trivial));
+        drop(cache);
+
+        let temp_dir = TempDir::new().unwrap();
+        let mut cache = cache_with(&temp_dir, QuotaPolicy::EvictOldest);
+        let oldest = cache.save_with_options("alice", "s1", "first", None, 0.9, None).unwrap();
+        for content in ["second", "third", "fourth"] {
+            cache.save_with_options("alice", "s1", content, None, 0.1, None).unwrap();
+        }
+        let remaining = cache.recall("alice", None, None, None).unwrap();
+        assert_eq!(remaining.len(), 3);
+        assert!(remaining.iter().all(|memory| memory.id != oldest));
+    }
+}
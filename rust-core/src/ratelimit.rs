@@ -0,0 +1,131 @@
+//! Per-user rate limits on saves and recalls
+//!
+//! Each user gets a token bucket per operation holding up to a minute's
+//! worth of calls and refilling continuously, so short bursts are allowed
+//! but the sustained rate stays at `max_saves_per_minute` /
+//! `max_recalls_per_minute`. Buckets live in memory and start full when the
+//! store is opened.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// An operation refused because the user went over their rate limit
+#[derive(Debug)]
+pub struct RateLimited {
+    pub user_id: String,
+    /// `"save"` or `"recall"`
+    pub operation: &'static str,
+    /// How long until the next call would be allowed
+    pub retry_after: Duration,
+}
+
+impl fmt::Display for RateLimited {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "user {} is over the {} rate limit; retry in {:.1}s",
+            self.user_id,
+            self.operation,
+            self.retry_after.as_secs_f64()
+        )
+    }
+}
+
+impl std::error::Error for RateLimited {}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+/// Token buckets for one operation, keyed by user
+#[derive(Debug)]
+pub(crate) struct RateLimiter {
+    operation: &'static str,
+    buckets: Mutex<HashMap<String, Bucket>>,
+}
+
+impl RateLimiter {
+    pub(crate) fn new(operation: &'static str) -> Self {
+        RateLimiter { operation, buckets: Mutex::new(HashMap::new()) }
+    }
+
+    /// Take a token for `user_id`; `None` means no limit
+    pub(crate) fn check(&self, user_id: &str, per_minute: Option<u32>) -> Result<(), RateLimited> {
+        self.check_at(user_id, per_minute, Instant::now())
+    }
+
+    fn check_at(&self, user_id: &str, per_minute: Option<u32>, now: Instant) -> Result<(), RateLimited> {
+        let Some(per_minute) = per_minute else { return Ok(()) };
+        let capacity = per_minute as f64;
+        let per_second = capacity / 60.0;
+
+        let mut buckets = self.buckets.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let bucket = buckets
+            .entry(user_id.to_string())
+            .or_insert(Bucket { tokens: capacity, refilled_at: now });
+        let elapsed = now.saturating_duration_since(bucket.refilled_at).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * per_second).min(capacity);
+        bucket.refilled_at = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(RateLimited {
+                user_id: user_id.to_string(),
+                operation: self.operation,
+                retry_after: Duration::from_secs_f64((1.0 - bucket.tokens) / per_second),
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MindCacheConfig;
+    use crate::test_support::temp_cache_with;
+
+    #[test]
+    fn test_token_bucket_refills_over_time() {
+        let limiter = RateLimiter::new("save");
+        let start = Instant::now();
+        for _ in 0..3 {
+            limiter.check_at("alice", Some(3), start).unwrap();
+        }
+        let limited = limiter.check_at("alice", Some(3), start).unwrap_err();
+        assert_eq!(limited.operation, "save");
+        assert_eq!(limited.retry_after.as_secs_f64().round(), 20.0);
+
+        limiter.check_at("bob", Some(3), start).unwrap();
+        limiter.check_at("alice", None, start).unwrap();
+
+        // One token every 20 seconds at 3 per minute
+        assert!(limiter.check_at("alice", Some(3), start + Duration::from_secs(19)).is_err());
+        limiter.check_at("alice", Some(3), start + Duration::from_secs(21)).unwrap();
+        assert!(limiter.check_at("alice", Some(3), start + Duration::from_secs(22)).is_err());
+    }
+
+    #[test]
+    fn test_configured_limits_apply_per_user() {
+        let (mut cache, _temp_dir) = temp_cache_with(MindCacheConfig {
+            max_saves_per_minute: Some(2),
+            max_recalls_per_minute: Some(1),
+            ..Default::default()
+        });
+
+        cache.save("alice", "s1", "first", None).unwrap();
+        cache.save("alice", "s1", "second", None).unwrap();
+        let err = cache.save("alice", "s1", "third", None).unwrap_err();
+        assert_eq!(err.downcast_ref::<RateLimited>().expect("rate limit error").operation, "save");
+        cache.save("bob", "s1", "bob is not limited by alice", None).unwrap();
+
+        assert_eq!(cache.recall("alice", None, None, None).unwrap().len(), 2);
+        assert!(cache.recall("alice", None, None, None).unwrap_err().is::<RateLimited>());
+        assert!(cache.recall("bob", None, None, None).is_ok());
+    }
+}
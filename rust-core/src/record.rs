@@ -0,0 +1,669 @@
+//! Encoding of memory records in the log
+//!
+//! # Log format
+//!
+//! A log starts with an 8-byte header: the magic `MCLOG\0` followed by the
+//! log format version ([`LOG_FORMAT`]) as a little-endian `u16`. Every record
+//! then has a [`FRAME_LEN`]-byte frame: the little-endian `u32` length of its
+//! payload, a flags byte ([`FLAG_COMPRESSED`], [`FLAG_ENCRYPTED`],
+//! [`FLAG_TOMBSTONE`], [`FLAG_HEADER`]) and the CRC32 of the payload as
+//! stored. The payload is a record version byte followed by the
+//! bincode-encoded item, deflated as a whole when the record is compressed.
+//!
+//! Records with [`FLAG_HEADER`] put a fixed-size [`RecordHeader`] in front
+//! of that, never compressed, copying the fields recall filters on most, so
+//! records a filter rules out are skipped without being decoded. Records
+//! written before it have no header and are decoded in full.
+//!
+//! Logs from before the header (format 1) hold records that are only a `u32`
+//! length and a payload. The oldest of those payloads are a bare bincode
+//! [`MemoryItem`] with the original eight fields; later ones set the top bit
+//! of the length and carry the record version byte. Such logs are rewritten
+//! in the current format the first time the store is opened for writing.
+//!
+//! To add a field to `MemoryItem`, freeze its current layout as a
+//! `MemoryRecordVn` struct here, bump [`RECORD_VERSION`] and add a decode arm
+//! that converts the old layout into a `MemoryItem`.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use chrono::{DateTime, Utc};
+use serde::de::{self, Deserializer, MapAccess, Visitor};
+use serde::{Deserialize, Serialize};
+use crate::storage::{MemoryItem, MemoryType};
+
+/// Version of the records written by this build
+pub const RECORD_VERSION: u8 = 4;
+
+/// Set in the length prefix of versioned records in format 1 logs
+const VERSIONED_FLAG: u32 = 1 << 31;
+
+/// Start of every log written in the current format
+pub const LOG_MAGIC: &[u8; 6] = b"MCLOG\0";
+
+/// Version of the log format written by this build
+pub const LOG_FORMAT: u16 = 2;
+
+/// Size of the header at the start of a log
+pub const LOG_HEADER_LEN: u64 = 8;
+
+/// Bytes before a record's payload: length, flags and CRC32
+pub const FRAME_LEN: usize = 9;
+
+/// The payload is deflated
+pub const FLAG_COMPRESSED: u8 = 1;
+
+/// The payload is encrypted; reserved, this build neither writes nor reads such records
+pub const FLAG_ENCRYPTED: u8 = 1 << 1;
+
+/// The record marks a deletion rather than holding a memory; reserved for
+/// log replay, the index never points at one
+pub const FLAG_TOMBSTONE: u8 = 1 << 2;
+
+/// The payload starts with a [`RecordHeader`]
+pub const FLAG_HEADER: u8 = 1 << 3;
+
+const KNOWN_FLAGS: u8 = FLAG_COMPRESSED | FLAG_ENCRYPTED | FLAG_TOMBSTONE | FLAG_HEADER;
+
+/// Size of a [`RecordHeader`]
+pub const HEADER_LEN: usize = 48;
+
+/// Payloads at least this large are compressed when that makes them smaller
+#[cfg(feature = "compression")]
+const COMPRESS_FROM: usize = 1024;
+
+const CRC_TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 == 1 { 0xedb8_8320 ^ (crc >> 1) } else { crc >> 1 };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+/// CRC-32 (IEEE), as used by zip and PNG
+pub fn crc32(data: &[u8]) -> u32 {
+    !data.iter().fold(!0u32, |crc, &byte| CRC_TABLE[((crc ^ byte as u32) & 0xff) as usize] ^ (crc >> 8))
+}
+
+/// Header to write at the start of a new log
+pub fn log_header() -> [u8; LOG_HEADER_LEN as usize] {
+    let mut header = [0; LOG_HEADER_LEN as usize];
+    header[..6].copy_from_slice(LOG_MAGIC);
+    header[6..].copy_from_slice(&LOG_FORMAT.to_le_bytes());
+    header
+}
+
+/// Format of a log given its first bytes; 1 for logs without a header
+pub fn log_format(start: &[u8]) -> u16 {
+    match start.strip_prefix(LOG_MAGIC.as_slice()) {
+        Some([low, high, ..]) => u16::from_le_bytes([*low, *high]),
+        _ => 1,
+    }
+}
+
+/// The frame in front of a record's payload
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Frame {
+    pub payload_len: usize,
+    pub flags: u8,
+    pub crc: u32,
+}
+
+impl Frame {
+    /// Parse the frame at the start of `bytes`
+    pub fn parse(bytes: &[u8]) -> Result<Frame, Box<dyn std::error::Error>> {
+        let bytes: &[u8; FRAME_LEN] = bytes.get(..FRAME_LEN).and_then(|b| b.try_into().ok()).ok_or("truncated record frame")?;
+        Ok(Frame {
+            payload_len: u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize,
+            flags: bytes[4],
+            crc: u32::from_le_bytes([bytes[5], bytes[6], bytes[7], bytes[8]]),
+        })
+    }
+
+    /// Size of the whole record, frame included
+    pub fn record_len(&self) -> u64 {
+        (FRAME_LEN + self.payload_len) as u64
+    }
+
+    /// Check `payload` against the frame, drop its header and undo its compression
+    fn open<'a>(&self, payload: &'a [u8]) -> Result<Cow<'a, [u8]>, Box<dyn std::error::Error>> {
+        if payload.len() != self.payload_len {
+            return Err(format!("record payload is {} bytes, expected {}", payload.len(), self.payload_len).into());
+        }
+        if crc32(payload) != self.crc {
+            return Err("record checksum mismatch: the log is corrupt".into());
+        }
+        if self.flags & !KNOWN_FLAGS != 0 {
+            return Err(format!("record has unknown flags {:#04x}", self.flags).into());
+        }
+        if self.flags & FLAG_ENCRYPTED != 0 {
+            return Err("record is encrypted, which this build cannot read".into());
+        }
+        if self.flags & FLAG_TOMBSTONE != 0 {
+            return Err("record is a tombstone, not a memory".into());
+        }
+        let payload = if self.flags & FLAG_HEADER != 0 {
+            payload.get(HEADER_LEN..).ok_or("record is shorter than its header")?
+        } else {
+            payload
+        };
+        if self.flags & FLAG_COMPRESSED != 0 {
+            return Ok(Cow::Owned(inflate(payload)?));
+        }
+        Ok(Cow::Borrowed(payload))
+    }
+}
+
+/// Fields of a record readable without decoding it, in fixed little-endian slots
+///
+/// | bytes  | field                                                  |
+/// |--------|--------------------------------------------------------|
+/// | 0..12  | `timestamp`: seconds (`i64`) and nanoseconds (`u32`)   |
+/// | 12..24 | `expires_at`, the same way; `i64::MIN` seconds if none |
+/// | 24..28 | `ttl_hours`; `u32::MAX` if none                        |
+/// | 28..32 | `importance` (`f32`)                                   |
+/// | 32..40 | FNV-1a hash of `user_id`                               |
+/// | 40..48 | FNV-1a hash of `session_id`                            |
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RecordHeader {
+    pub timestamp: DateTime<Utc>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub ttl_hours: Option<u32>,
+    pub importance: f32,
+    pub user_hash: u64,
+    pub session_hash: u64,
+}
+
+/// Hash of a user or session id as kept in record headers
+pub fn key_hash(key: &str) -> u64 {
+    crate::dedupe::fnv1a(key)
+}
+
+impl RecordHeader {
+    pub fn of(memory: &MemoryItem) -> Self {
+        RecordHeader {
+            timestamp: memory.timestamp,
+            expires_at: memory.expires_at,
+            ttl_hours: memory.ttl_hours,
+            importance: memory.importance,
+            user_hash: key_hash(&memory.user_id),
+            session_hash: key_hash(&memory.session_id),
+        }
+    }
+
+    fn to_bytes(self) -> [u8; HEADER_LEN] {
+        let time = |at: Option<DateTime<Utc>>| at.map_or((i64::MIN, 0), |at| (at.timestamp(), at.timestamp_subsec_nanos()));
+        let mut bytes = [0; HEADER_LEN];
+        for (start, (secs, nanos)) in [(0, time(Some(self.timestamp))), (12, time(self.expires_at))] {
+            bytes[start..start + 8].copy_from_slice(&secs.to_le_bytes());
+            bytes[start + 8..start + 12].copy_from_slice(&nanos.to_le_bytes());
+        }
+        bytes[24..28].copy_from_slice(&self.ttl_hours.unwrap_or(u32::MAX).to_le_bytes());
+        bytes[28..32].copy_from_slice(&self.importance.to_le_bytes());
+        bytes[32..40].copy_from_slice(&self.user_hash.to_le_bytes());
+        bytes[40..48].copy_from_slice(&self.session_hash.to_le_bytes());
+        bytes
+    }
+
+    fn parse(bytes: &[u8; HEADER_LEN]) -> Option<Self> {
+        let u32_at = |start: usize| u32::from_le_bytes(bytes[start..start + 4].try_into().unwrap_or_default());
+        let u64_at = |start: usize| u64::from_le_bytes(bytes[start..start + 8].try_into().unwrap_or_default());
+        let time = |start: usize| match u64_at(start) as i64 {
+            i64::MIN => Some(None),
+            secs => DateTime::from_timestamp(secs, u32_at(start + 8)).map(Some),
+        };
+        let ttl_hours = u32_at(24);
+        Some(RecordHeader {
+            timestamp: time(0)??,
+            expires_at: time(12)?,
+            ttl_hours: (ttl_hours != u32::MAX).then_some(ttl_hours),
+            importance: f32::from_bits(u32_at(28)),
+            user_hash: u64_at(32),
+            session_hash: u64_at(40),
+        })
+    }
+
+    /// The header of a record given its frame and payload, if it has one
+    ///
+    /// The checksum is not verified: a header is only used to rule records
+    /// out, and a corrupt record fails to decode anyway.
+    pub fn peek(frame: &Frame, payload: &[u8]) -> Option<Self> {
+        if frame.flags & FLAG_HEADER == 0 {
+            return None;
+        }
+        Self::parse(payload.get(..HEADER_LEN)?.try_into().ok()?)
+    }
+}
+
+/// Layout of records written before versioning (implicitly version 1)
+#[derive(Serialize, Deserialize)]
+struct MemoryRecordV1 {
+    id: String,
+    user_id: String,
+    session_id: String,
+    content: String,
+    metadata: HashMap<String, String>,
+    timestamp: DateTime<Utc>,
+    ttl_hours: Option<u32>,
+    importance: f32,
+}
+
+impl From<MemoryRecordV1> for MemoryItem {
+    fn from(record: MemoryRecordV1) -> Self {
+        MemoryItem {
+            id: record.id,
+            user_id: record.user_id,
+            session_id: record.session_id,
+            content: record.content,
+            metadata: record.metadata,
+            timestamp: record.timestamp,
+            ttl_hours: record.ttl_hours,
+            importance: record.importance,
+            ..Default::default()
+        }
+    }
+}
+
+/// Layout of version 2 records, before `version`
+#[derive(Serialize, Deserialize)]
+struct MemoryRecordV2 {
+    id: String,
+    user_id: String,
+    session_id: String,
+    content: String,
+    metadata: HashMap<String, String>,
+    timestamp: DateTime<Utc>,
+    ttl_hours: Option<u32>,
+    importance: f32,
+    role: Option<String>,
+    memory_type: MemoryType,
+}
+
+impl From<MemoryRecordV2> for MemoryItem {
+    fn from(record: MemoryRecordV2) -> Self {
+        MemoryItem {
+            id: record.id,
+            user_id: record.user_id,
+            session_id: record.session_id,
+            content: record.content,
+            metadata: record.metadata,
+            timestamp: record.timestamp,
+            ttl_hours: record.ttl_hours,
+            importance: record.importance,
+            role: record.role,
+            memory_type: record.memory_type,
+            ..Default::default()
+        }
+    }
+}
+
+/// Layout of version 3 records, before `expires_at`
+#[derive(Serialize, Deserialize)]
+struct MemoryRecordV3 {
+    id: String,
+    user_id: String,
+    session_id: String,
+    content: String,
+    metadata: HashMap<String, String>,
+    timestamp: DateTime<Utc>,
+    ttl_hours: Option<u32>,
+    importance: f32,
+    role: Option<String>,
+    memory_type: MemoryType,
+    version: u64,
+}
+
+impl From<MemoryRecordV3> for MemoryItem {
+    fn from(record: MemoryRecordV3) -> Self {
+        MemoryItem {
+            id: record.id,
+            user_id: record.user_id,
+            session_id: record.session_id,
+            content: record.content,
+            metadata: record.metadata,
+            timestamp: record.timestamp,
+            ttl_hours: record.ttl_hours,
+            importance: record.importance,
+            role: record.role,
+            memory_type: record.memory_type,
+            version: record.version,
+            ..Default::default()
+        }
+    }
+}
+
+/// Current layout with content and metadata skipped rather than decoded
+#[derive(Deserialize)]
+struct SkimmedRecord {
+    id: String,
+    user_id: String,
+    session_id: String,
+    #[allow(dead_code)]
+    content: Skipped,
+    #[allow(dead_code)]
+    metadata: SkippedMap,
+    timestamp: DateTime<Utc>,
+    ttl_hours: Option<u32>,
+    importance: f32,
+    role: Option<String>,
+    memory_type: MemoryType,
+    version: u64,
+    expires_at: Option<DateTime<Utc>>,
+}
+
+/// A length-prefixed value passed over without copying or UTF-8 checks
+struct Skipped;
+
+impl<'de> Deserialize<'de> for Skipped {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct SkipVisitor;
+        impl<'de> Visitor<'de> for SkipVisitor {
+            type Value = Skipped;
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                f.write_str("bytes")
+            }
+            fn visit_bytes<E: de::Error>(self, _: &[u8]) -> Result<Skipped, E> {
+                Ok(Skipped)
+            }
+        }
+        deserializer.deserialize_bytes(SkipVisitor)
+    }
+}
+
+/// A string map passed over entry by entry
+struct SkippedMap;
+
+impl<'de> Deserialize<'de> for SkippedMap {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct SkipMapVisitor;
+        impl<'de> Visitor<'de> for SkipMapVisitor {
+            type Value = SkippedMap;
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                f.write_str("a map")
+            }
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<SkippedMap, A::Error> {
+                while map.next_entry::<Skipped, Skipped>()?.is_some() {}
+                Ok(SkippedMap)
+            }
+        }
+        deserializer.deserialize_map(SkipMapVisitor)
+    }
+}
+
+impl From<SkimmedRecord> for MemoryItem {
+    fn from(record: SkimmedRecord) -> Self {
+        MemoryItem {
+            id: record.id,
+            user_id: record.user_id,
+            session_id: record.session_id,
+            timestamp: record.timestamp,
+            ttl_hours: record.ttl_hours,
+            importance: record.importance,
+            role: record.role,
+            memory_type: record.memory_type,
+            version: record.version,
+            expires_at: record.expires_at,
+            ..Default::default()
+        }
+    }
+}
+
+#[cfg(feature = "compression")]
+fn deflate(payload: Vec<u8>) -> Result<(u8, Vec<u8>), Box<dyn std::error::Error>> {
+    use std::io::Write;
+    if payload.len() < COMPRESS_FROM {
+        return Ok((0, payload));
+    }
+    let mut encoder = flate2::write::DeflateEncoder::new(Vec::new(), flate2::Compression::fast());
+    encoder.write_all(&payload)?;
+    let compressed = encoder.finish()?;
+    Ok(if compressed.len() < payload.len() { (FLAG_COMPRESSED, compressed) } else { (0, payload) })
+}
+
+#[cfg(not(feature = "compression"))]
+fn deflate(payload: Vec<u8>) -> Result<(u8, Vec<u8>), Box<dyn std::error::Error>> {
+    Ok((0, payload))
+}
+
+#[cfg(feature = "compression")]
+fn inflate(payload: &[u8]) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    use std::io::Read;
+    let mut inflated = Vec::new();
+    flate2::read::DeflateDecoder::new(payload).read_to_end(&mut inflated)?;
+    Ok(inflated)
+}
+
+#[cfg(not(feature = "compression"))]
+fn inflate(_payload: &[u8]) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    Err("record is compressed, which needs the compression feature".into())
+}
+
+/// Encode a memory as a complete record, frame included
+pub fn encode(memory: &MemoryItem) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let mut payload = Vec::with_capacity(1 + bincode::serialized_size(memory)? as usize);
+    payload.push(RECORD_VERSION);
+    bincode::serialize_into(&mut payload, memory)?;
+    let (flags, body) = deflate(payload)?;
+    let mut payload = Vec::with_capacity(HEADER_LEN + body.len());
+    payload.extend_from_slice(&RecordHeader::of(memory).to_bytes());
+    payload.extend_from_slice(&body);
+    let flags = flags | FLAG_HEADER;
+    let payload_len = u32::try_from(payload.len()).map_err(|_| "memory is too large to store")?;
+
+    let mut record = Vec::with_capacity(FRAME_LEN + payload.len());
+    record.extend_from_slice(&payload_len.to_le_bytes());
+    record.push(flags);
+    record.extend_from_slice(&crc32(&payload).to_le_bytes());
+    record.extend_from_slice(&payload);
+    Ok(record)
+}
+
+/// Size of the record `encode` would produce
+pub fn encoded_size(memory: &MemoryItem) -> u64 {
+    encode(memory).map_or(0, |record| record.len() as u64)
+}
+
+/// Decode the payload of a record given its frame
+pub fn decode(frame: &Frame, payload: &[u8]) -> Result<MemoryItem, Box<dyn std::error::Error>> {
+    decode_versioned(&frame.open(payload)?)
+}
+
+/// Decode a payload that starts with its record version byte
+fn decode_versioned(payload: &[u8]) -> Result<MemoryItem, Box<dyn std::error::Error>> {
+    let memory: MemoryItem = match payload.split_first() {
+        Some((&RECORD_VERSION, item)) => return Ok(bincode::deserialize(item)?),
+        Some((3, item)) => bincode::deserialize::<MemoryRecordV3>(item)?.into(),
+        Some((2, item)) => bincode::deserialize::<MemoryRecordV2>(item)?.into(),
+        Some((&version, _)) => return Err(format!("record version {} is newer than this build supports", version).into()),
+        None => return Err("empty record".into()),
+    };
+    // Older layouts had no expiry; it follows from the TTL
+    Ok(MemoryItem { expires_at: memory.ttl_expiry(), ..memory })
+}
+
+/// Decode the payload of a record given its frame, leaving `content` and
+/// `metadata` empty
+///
+/// Current-version records skip over both without allocating them; older
+/// layouts are decoded in full and then cleared.
+pub fn decode_skimmed(frame: &Frame, payload: &[u8]) -> Result<MemoryItem, Box<dyn std::error::Error>> {
+    let payload = frame.open(payload)?;
+    match payload.split_first() {
+        Some((&RECORD_VERSION, item)) => Ok(bincode::deserialize::<SkimmedRecord>(item)?.into()),
+        _ => {
+            let mut memory = decode_versioned(&payload)?;
+            memory.content.clear();
+            memory.metadata.clear();
+            Ok(memory)
+        }
+    }
+}
+
+/// Payload length from the length prefix of a record in a format 1 log
+pub fn legacy_payload_len(prefix: u32) -> usize {
+    (prefix & !VERSIONED_FLAG) as usize
+}
+
+/// Decode a record payload from a format 1 log given its length prefix
+pub fn decode_legacy(prefix: u32, payload: &[u8]) -> Result<MemoryItem, Box<dyn std::error::Error>> {
+    if prefix & VERSIONED_FLAG != 0 {
+        return decode_versioned(payload);
+    }
+    let memory: MemoryItem = bincode::deserialize::<MemoryRecordV1>(payload)?.into();
+    Ok(MemoryItem { expires_at: memory.ttl_expiry(), ..memory })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn legacy_memory() -> MemoryRecordV1 {
+        MemoryRecordV1 {
+            id: "m1".to_string(),
+            user_id: "alice".to_string(),
+            session_id: "s1".to_string(),
+            content: "Written before records were versioned".to_string(),
+            metadata: HashMap::new(),
+            timestamp: Utc::now(),
+            ttl_hours: Some(24),
+            importance: 0.7,
+        }
+    }
+
+    fn decode_record(record: &[u8]) -> Result<MemoryItem, Box<dyn std::error::Error>> {
+        decode(&Frame::parse(record)?, &record[FRAME_LEN..])
+    }
+
+    #[test]
+    fn test_legacy_and_versioned_records_decode() {
+        let legacy = bincode::serialize(&legacy_memory()).unwrap();
+        let memory = decode_legacy(legacy.len() as u32, &legacy).unwrap();
+        assert_eq!(memory.id, "m1");
+        assert_eq!(memory.ttl_hours, Some(24));
+
+        let record = encode(&memory).unwrap();
+        assert_eq!(record.len() as u64, encoded_size(&memory));
+        let frame = Frame::parse(&record).unwrap();
+        assert_eq!(frame.record_len(), record.len() as u64);
+        assert_eq!(frame.flags, FLAG_HEADER);
+        let decoded = decode_record(&record).unwrap();
+        assert_eq!(serde_json::to_value(&decoded).unwrap(), serde_json::to_value(&memory).unwrap());
+
+        let v2 = MemoryRecordV2 {
+            id: memory.id.clone(),
+            user_id: memory.user_id.clone(),
+            session_id: memory.session_id.clone(),
+            content: memory.content.clone(),
+            metadata: HashMap::new(),
+            timestamp: memory.timestamp,
+            ttl_hours: None,
+            importance: 0.4,
+            role: Some("user".to_string()),
+            memory_type: MemoryType::Fact,
+        };
+        let mut payload = vec![2];
+        payload.extend(bincode::serialize(&v2).unwrap());
+        let memory = decode_legacy(payload.len() as u32 | VERSIONED_FLAG, &payload).unwrap();
+        assert_eq!(legacy_payload_len(payload.len() as u32 | VERSIONED_FLAG), payload.len());
+        assert_eq!(memory.memory_type, MemoryType::Fact);
+        assert_eq!(memory.version, 0);
+
+        let v3 = MemoryRecordV3 {
+            id: memory.id.clone(),
+            user_id: memory.user_id.clone(),
+            session_id: memory.session_id.clone(),
+            content: memory.content.clone(),
+            metadata: HashMap::new(),
+            timestamp: memory.timestamp,
+            ttl_hours: Some(2),
+            importance: 0.4,
+            role: None,
+            memory_type: MemoryType::Task,
+            version: 5,
+        };
+        let mut payload = vec![3];
+        payload.extend(bincode::serialize(&v3).unwrap());
+        let upgraded = decode_legacy(payload.len() as u32 | VERSIONED_FLAG, &payload).unwrap();
+        assert_eq!(upgraded.version, 5);
+        assert_eq!(upgraded.expires_at, Some(memory.timestamp + chrono::Duration::hours(2)));
+
+        let mut full = memory.clone();
+        full.content = "Long content ".repeat(100);
+        full.metadata.insert("ticker".to_string(), "AAPL".to_string());
+        full.version = 7;
+        let record = encode(&full).unwrap();
+        let skimmed = decode_skimmed(&Frame::parse(&record).unwrap(), &record[FRAME_LEN..]).unwrap();
+        assert_eq!((skimmed.content.as_str(), skimmed.metadata.len()), ("", 0));
+        assert_eq!((skimmed.id.as_str(), skimmed.memory_type, skimmed.version), ("m1", MemoryType::Fact, 7));
+
+        let mut future = vec![RECORD_VERSION + 1];
+        future.extend(bincode::serialize(&memory).unwrap());
+        assert!(decode_versioned(&future).is_err());
+    }
+
+    #[test]
+    fn test_frames_catch_corruption_and_flags() {
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+        assert_eq!(log_format(&log_header()), LOG_FORMAT);
+        assert_eq!(log_format(&1234u32.to_le_bytes()), 1);
+
+        let memory: MemoryItem = legacy_memory().into();
+        let mut record = encode(&memory).unwrap();
+        let last = record.len() - 1;
+        record[last] ^= 1;
+        assert!(decode_record(&record).unwrap_err().to_string().contains("checksum"));
+        record[last] ^= 1;
+        record[4] = FLAG_ENCRYPTED;
+        assert!(decode_record(&record).is_err());
+        record[4] = 1 << 7;
+        assert!(decode_record(&record).is_err());
+        assert!(Frame::parse(&record[..FRAME_LEN - 1]).is_err());
+    }
+
+    #[test]
+    fn test_headers_are_readable_without_decoding() {
+        let mut memory: MemoryItem = legacy_memory().into();
+        memory.expires_at = memory.ttl_expiry();
+        let record = encode(&memory).unwrap();
+        let frame = Frame::parse(&record).unwrap();
+        let header = RecordHeader::peek(&frame, &record[FRAME_LEN..]).unwrap();
+        assert_eq!(header, RecordHeader::of(&memory));
+        assert_eq!((header.timestamp, header.ttl_hours, header.user_hash), (memory.timestamp, Some(24), key_hash("alice")));
+
+        let no_ttl = MemoryItem { ttl_hours: None, expires_at: None, ..memory.clone() };
+        let header = RecordHeader::of(&no_ttl);
+        assert_eq!(RecordHeader::parse(&header.to_bytes()), Some(header));
+
+        // Records written before headers have none, and still decode
+        let mut payload = vec![RECORD_VERSION];
+        payload.extend(bincode::serialize(&memory).unwrap());
+        let mut old = (payload.len() as u32).to_le_bytes().to_vec();
+        old.push(0);
+        old.extend_from_slice(&crc32(&payload).to_le_bytes());
+        old.extend_from_slice(&payload);
+        let frame = Frame::parse(&old).unwrap();
+        assert!(RecordHeader::peek(&frame, &old[FRAME_LEN..]).is_none());
+        assert_eq!(decode_record(&old).unwrap().content, memory.content);
+    }
+
+    #[cfg(feature = "compression")]
+    #[test]
+    fn test_large_records_are_compressed() {
+        let mut memory: MemoryItem = legacy_memory().into();
+        memory.content = "Gold broke out above resistance. ".repeat(200);
+        let record = encode(&memory).unwrap();
+        assert_eq!(Frame::parse(&record).unwrap().flags, FLAG_COMPRESSED | FLAG_HEADER);
+        assert!(record.len() < memory.content.len() / 4);
+        assert_eq!(decode_record(&record).unwrap().content, memory.content);
+        let skimmed = decode_skimmed(&Frame::parse(&record).unwrap(), &record[FRAME_LEN..]).unwrap();
+        assert_eq!((skimmed.id.as_str(), skimmed.content.as_str()), ("m1", ""));
+    }
+}
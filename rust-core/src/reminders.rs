@@ -0,0 +1,174 @@
+//! Reminders: memories that come due at a set time
+//!
+//! A reminder is a `Task` memory whose `due_at` metadata holds an RFC 3339
+//! time. Once that time has passed it shows up in `due_reminders` until it
+//! is dismissed, and `fire_due_reminders` emits a
+//! [`MemoryEvent::ReminderDue`] for it exactly once. The server binary calls
+//! `fire_due_reminders` every [`REMINDER_POLL_INTERVAL_SECS`] seconds; other
+//! hosts call it on their own schedule.
+
+use std::collections::HashMap;
+use chrono::{DateTime, Utc};
+use crate::events::MemoryEvent;
+use crate::storage::{MemoryItem, MemoryType, MetadataCondition, QueryFilter};
+use crate::versioning::MemoryUpdate;
+use crate::MindCache;
+
+/// Metadata key holding when a reminder is due
+pub const DUE_AT_KEY: &str = "due_at";
+
+/// Metadata key set once `ReminderDue` has been emitted for a reminder
+pub const NOTIFIED_AT_KEY: &str = "reminder_notified_at";
+
+/// Session reminders are saved in; the leading underscore keeps it apart
+/// from user sessions, as with [`SUMMARY_SESSION`](crate::SUMMARY_SESSION)
+pub const REMINDER_SESSION: &str = "_reminders";
+
+/// How often the server binary checks for reminders that came due
+pub const REMINDER_POLL_INTERVAL_SECS: u64 = 30;
+
+/// When a memory is due, if it is a reminder
+pub fn due_at(memory: &MemoryItem) -> Option<DateTime<Utc>> {
+    if memory.memory_type != MemoryType::Task {
+        return None;
+    }
+    let due_at = memory.metadata.get(DUE_AT_KEY)?;
+    DateTime::parse_from_rfc3339(due_at).ok().map(|due_at| due_at.with_timezone(&Utc))
+}
+
+impl MindCache {
+    /// Save a reminder that comes due at `due_at`
+    ///
+    /// Reminders are saved with [`MemoryItem::TTL_FOREVER`], so neither a
+    /// default TTL nor the decay policy's maximum age removes them before
+    /// they are due.
+    pub fn save_reminder(&mut self, user_id: &str, content: &str, due_at: DateTime<Utc>) -> Result<String, Box<dyn std::error::Error>> {
+        let mut memory = MemoryItem {
+            user_id: user_id.to_string(),
+            session_id: REMINDER_SESSION.to_string(),
+            content: content.to_string(),
+            metadata: HashMap::from([(DUE_AT_KEY.to_string(), due_at.to_rfc3339())]),
+            timestamp: self.storage.now(),
+            ttl_hours: Some(MemoryItem::TTL_FOREVER),
+            importance: 0.5,
+            memory_type: MemoryType::Task,
+            ..Default::default()
+        };
+        memory.importance = self.default_importance(&memory);
+        self.store(memory)
+    }
+
+    /// A user's reminders that are due at `now`, earliest first
+    pub fn due_reminders(&self, user_id: &str, now: DateTime<Utc>) -> Result<Vec<MemoryItem>, Box<dyn std::error::Error>> {
+        self.reminders_due(Some(user_id), now)
+    }
+
+    /// Stop a reminder from coming due; the memory stays as a plain task
+    pub fn dismiss_reminder(&mut self, memory_id: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let Some(memory) = self.storage.get_memory(memory_id)? else {
+            return Err(format!("memory {} not found", memory_id).into());
+        };
+        if due_at(&memory).is_none() {
+            return Err(format!("memory {} is not a reminder", memory_id).into());
+        }
+
+        let mut metadata = memory.metadata;
+        metadata.remove(DUE_AT_KEY);
+        metadata.remove(NOTIFIED_AT_KEY);
+        self.update_memory(memory_id, MemoryUpdate { metadata: Some(metadata), ..Default::default() })?;
+        Ok(())
+    }
+
+    /// Emit `ReminderDue` for every reminder of any user that came due by
+    /// `now` and has not been announced yet; returns how many fired
+    pub fn fire_due_reminders(&mut self, now: DateTime<Utc>) -> Result<usize, Box<dyn std::error::Error>> {
+        let mut fired = 0;
+        for memory in self.reminders_due(None, now)? {
+            if memory.metadata.contains_key(NOTIFIED_AT_KEY) {
+                continue;
+            }
+            let mut metadata = memory.metadata.clone();
+            metadata.insert(NOTIFIED_AT_KEY.to_string(), now.to_rfc3339());
+            let memory = self.update_memory(&memory.id, MemoryUpdate { metadata: Some(metadata), ..Default::default() })?;
+            println!("Reminder {} for user {} is due", memory.id, memory.user_id);
+            self.storage.events().emit(MemoryEvent::ReminderDue { memory });
+            fired += 1;
+        }
+        Ok(fired)
+    }
+
+    fn reminders_due(&self, user_id: Option<&str>, now: DateTime<Utc>) -> Result<Vec<MemoryItem>, Box<dyn std::error::Error>> {
+        let filter = QueryFilter {
+            user_id: user_id.map(str::to_string),
+            memory_type: Some(MemoryType::Task),
+            metadata_filters: HashMap::from([(DUE_AT_KEY.to_string(), MetadataCondition::Exists)]),
+            ..Default::default()
+        };
+        let mut due: Vec<(DateTime<Utc>, MemoryItem)> = self.storage
+            .recall(filter)?
+            .into_iter()
+            .filter_map(|memory| due_at(&memory).filter(|due_at| *due_at <= now).map(|due_at| (due_at, memory)))
+            .collect();
+        due.sort_by_key(|(due_at, _)| *due_at);
+        Ok(due.into_iter().map(|(_, memory)| memory).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use chrono::Duration;
+    use crate::MindCacheConfig;
+    use crate::test_support::{temp_cache, temp_cache_with};
+
+    #[test]
+    fn test_reminders_come_due_and_fire_once() {
+        let (mut cache, _temp_dir) = temp_cache();
+        let fired = Arc::new(Mutex::new(Vec::new()));
+        let seen = Arc::clone(&fired);
+        cache.subscribe(move |event: &MemoryEvent| {
+            if let MemoryEvent::ReminderDue { memory } = event {
+                seen.lock().unwrap().push(memory.content.clone());
+            }
+        });
+
+        let now = Utc::now();
+        let tomorrow = cache.save_reminder("alice", "Call the dentist", now + Duration::days(1)).unwrap();
+        cache.save_reminder("alice", "Review portfolio", now + Duration::days(7)).unwrap();
+        cache.save_reminder("bob", "Renew passport", now + Duration::days(1)).unwrap();
+        cache.save_typed("alice", "s1", "Not a reminder", MemoryType::Task, None).unwrap();
+
+        assert!(cache.due_reminders("alice", now).unwrap().is_empty());
+        let in_two_days = now + Duration::days(2);
+        let due = cache.due_reminders("alice", in_two_days).unwrap();
+        assert_eq!(due.len(), 1);
+        assert_eq!(due[0].id, tomorrow);
+
+        assert_eq!(cache.fire_due_reminders(in_two_days).unwrap(), 2);
+        assert_eq!(cache.fire_due_reminders(in_two_days).unwrap(), 0);
+        let mut fired_contents = fired.lock().unwrap().clone();
+        fired_contents.sort();
+        assert_eq!(fired_contents, ["Call the dentist", "Renew passport"]);
+
+        // Fired reminders stay due until dismissed
+        assert_eq!(cache.due_reminders("alice", in_two_days).unwrap().len(), 1);
+        cache.dismiss_reminder(&tomorrow).unwrap();
+        assert!(cache.due_reminders("alice", in_two_days).unwrap().is_empty());
+        assert_eq!(cache.due_reminders("alice", now + Duration::days(8)).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_reminders_outlive_the_default_ttl() {
+        let (mut cache, _temp_dir) = temp_cache_with(MindCacheConfig {
+            default_memory_ttl_hours: Some(24),
+            ..Default::default()
+        });
+        let now = Utc::now();
+        let reminder = cache.save_reminder("alice", "Renew passport", now + Duration::days(60)).unwrap();
+        assert!(cache.get_memory(&reminder).unwrap().unwrap().never_expires());
+
+        let report = cache.decay_at(now + Duration::days(59)).unwrap();
+        assert!(!report.affected.contains(&reminder));
+    }
+}
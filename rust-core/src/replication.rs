@@ -0,0 +1,436 @@
+//! Asynchronous replication to a standby directory
+//!
+//! With `replica_path` set, every write the store makes (appends, blob
+//! writes, renames, removals) is queued and applied in the same order to a
+//! second storage directory by a background thread, so the replica is a
+//! byte-for-byte copy of the store lagging only by the queue. If the primary
+//! disk fails, open the replica as `storage_path` and carry on.
+//!
+//! On open the replica is first brought up to date with a full copy of the
+//! store, so a replica that was missing, stale or written by an older run is
+//! fine. Writers never wait for the replica: a replica that is slow or
+//! unreachable (e.g. a network mount that went away) only makes the queue
+//! grow, and failed writes are retried until they succeed. Dropping the
+//! cache drains the queue, without retrying.
+//!
+//! The replica directory is locked like any store, so it can't be opened for
+//! writing while it is being replicated to.
+
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use crate::audit::AUDIT_LOG;
+use crate::backend::{AccessMode, FileBackend, StorageBackend, LOCK_FILE};
+use crate::changes::CHANGES_LOG;
+use crate::history::HISTORY_LOG;
+use crate::vector_sync::VECTOR_SYNC_BLOB;
+use crate::MindCache;
+
+/// How long to wait before retrying a write the replica failed
+const RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// How far a replica is behind the store
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReplicationStatus {
+    /// Where the replica is
+    pub replica: String,
+    /// Writes queued but not applied to the replica yet
+    pub pending_writes: u64,
+    pub applied_writes: u64,
+    pub applied_bytes: u64,
+    /// Whether the replica has received its initial copy of the store
+    pub seeded: bool,
+    pub last_applied_at: Option<DateTime<Utc>>,
+    /// When the oldest write not yet on the replica was made; `None` when caught up
+    pub behind_since: Option<DateTime<Utc>>,
+    /// Why the write being retried failed, if one is
+    pub last_error: Option<String>,
+}
+
+enum Write {
+    Append { name: String, offset: u64, data: Vec<u8> },
+    Flush { name: String },
+    Blob { name: String, data: Vec<u8> },
+    Remove { name: String },
+    Rename { from: String, to: String },
+    Shred { name: String },
+    /// Replace the replica's contents with these files (`None`: absent)
+    Seed { files: Vec<(String, Option<Vec<u8>>)> },
+}
+
+struct Queued {
+    write: Write,
+    queued_at: DateTime<Utc>,
+}
+
+#[derive(Default)]
+struct Progress {
+    queued: u64,
+    applied: u64,
+    applied_bytes: u64,
+    seeded: bool,
+    last_applied_at: Option<DateTime<Utc>>,
+    behind_since: Option<DateTime<Utc>>,
+    last_error: Option<String>,
+}
+
+struct Shared {
+    replica: String,
+    progress: Mutex<Progress>,
+    caught_up: Condvar,
+    /// Set on drop: stop retrying and drain what is left
+    closing: AtomicBool,
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, Progress> {
+        self.progress.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// Handle on a running replication, for status and waiting
+#[derive(Clone)]
+pub struct Replicator {
+    shared: Arc<Shared>,
+}
+
+impl Replicator {
+    pub fn status(&self) -> ReplicationStatus {
+        let progress = self.shared.lock();
+        ReplicationStatus {
+            replica: self.shared.replica.clone(),
+            pending_writes: progress.queued - progress.applied,
+            applied_writes: progress.applied,
+            applied_bytes: progress.applied_bytes,
+            seeded: progress.seeded,
+            last_applied_at: progress.last_applied_at,
+            behind_since: progress.behind_since,
+            last_error: progress.last_error.clone(),
+        }
+    }
+
+    /// Wait until every write queued so far is on the replica; false on timeout
+    pub fn wait(&self, timeout: Duration) -> bool {
+        let progress = self.shared.lock();
+        let target = progress.queued;
+        let (progress, _) = self
+            .shared
+            .caught_up
+            .wait_timeout_while(progress, timeout, |progress| progress.applied < target)
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        progress.applied >= target
+    }
+}
+
+/// Backend passing everything to `primary` and queueing its writes for a replica directory
+pub struct ReplicatingBackend {
+    primary: Arc<dyn StorageBackend>,
+    sender: Mutex<Option<Sender<Queued>>>,
+    worker: Mutex<Option<JoinHandle<()>>>,
+    shared: Arc<Shared>,
+}
+
+impl ReplicatingBackend {
+    /// Start replicating `primary` to the directory at `replica_path`
+    ///
+    /// Fails if the replica directory can't be created or another writer
+    /// holds it. Call [`seed`](Self::seed) before the first write.
+    pub fn new(primary: Arc<dyn StorageBackend>, replica_path: impl AsRef<Path>) -> Result<Self, Box<dyn std::error::Error>> {
+        let replica = FileBackend::open(replica_path.as_ref(), AccessMode::ReadWrite)?;
+        let shared = Arc::new(Shared {
+            replica: replica.location(),
+            progress: Mutex::new(Progress::default()),
+            caught_up: Condvar::new(),
+            closing: AtomicBool::new(false),
+        });
+        let (sender, receiver) = mpsc::channel();
+        let worker = {
+            let (primary, shared) = (Arc::clone(&primary), Arc::clone(&shared));
+            thread::Builder::new()
+                .name("mindcache-replication".to_string())
+                .spawn(move || replicate(receiver, replica, primary, shared))?
+        };
+        Ok(ReplicatingBackend {
+            primary,
+            sender: Mutex::new(Some(sender)),
+            worker: Mutex::new(Some(worker)),
+            shared,
+        })
+    }
+
+    pub fn replicator(&self) -> Replicator {
+        Replicator { shared: Arc::clone(&self.shared) }
+    }
+
+    /// Queue a full copy of the named files, replacing whatever the replica holds
+    pub fn seed(&self, names: &[String]) -> io::Result<()> {
+        let files = names
+            .iter()
+            .map(|name| Ok((name.clone(), self.primary.read_blob(name)?)))
+            .collect::<io::Result<_>>()?;
+        self.queue(Write::Seed { files });
+        Ok(())
+    }
+
+    fn queue(&self, write: Write) {
+        let queued_at = Utc::now();
+        let sender = self.sender.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let Some(sender) = sender.as_ref() else { return };
+        let mut progress = self.shared.lock();
+        if sender.send(Queued { write, queued_at }).is_ok() {
+            progress.queued += 1;
+            progress.behind_since.get_or_insert(queued_at);
+        }
+    }
+}
+
+impl Drop for ReplicatingBackend {
+    fn drop(&mut self) {
+        self.shared.closing.store(true, Ordering::SeqCst);
+        // Closing the channel lets the worker finish the queue and exit
+        drop(self.sender.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).take());
+        if let Some(worker) = self.worker.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+impl StorageBackend for ReplicatingBackend {
+    fn append(&self, name: &str, data: &[u8]) -> io::Result<u64> {
+        let offset = self.primary.append(name, data)?;
+        self.queue(Write::Append { name: name.to_string(), offset, data: data.to_vec() });
+        Ok(offset)
+    }
+
+    fn read_at(&self, name: &str, offset: u64, len: usize) -> io::Result<Vec<u8>> {
+        self.primary.read_at(name, offset, len)
+    }
+
+    fn flush(&self, name: &str) -> io::Result<()> {
+        self.primary.flush(name)?;
+        self.queue(Write::Flush { name: name.to_string() });
+        Ok(())
+    }
+
+    fn read_blob(&self, name: &str) -> io::Result<Option<Vec<u8>>> {
+        self.primary.read_blob(name)
+    }
+
+    fn write_blob(&self, name: &str, data: &[u8]) -> io::Result<()> {
+        self.primary.write_blob(name, data)?;
+        self.queue(Write::Blob { name: name.to_string(), data: data.to_vec() });
+        Ok(())
+    }
+
+    fn size(&self, name: &str) -> io::Result<u64> {
+        self.primary.size(name)
+    }
+
+    fn remove(&self, name: &str) -> io::Result<()> {
+        self.primary.remove(name)?;
+        self.queue(Write::Remove { name: name.to_string() });
+        Ok(())
+    }
+
+    fn rename(&self, from: &str, to: &str) -> io::Result<()> {
+        self.primary.rename(from, to)?;
+        self.queue(Write::Rename { from: from.to_string(), to: to.to_string() });
+        Ok(())
+    }
+
+    fn shred(&self, name: &str) -> io::Result<()> {
+        self.primary.shred(name)?;
+        self.queue(Write::Shred { name: name.to_string() });
+        Ok(())
+    }
+
+    fn location(&self) -> String {
+        self.primary.location()
+    }
+
+    fn is_read_only(&self) -> bool {
+        self.primary.is_read_only()
+    }
+}
+
+/// Worker loop: apply queued writes in order until the channel closes
+fn replicate(receiver: Receiver<Queued>, replica: FileBackend, primary: Arc<dyn StorageBackend>, shared: Arc<Shared>) {
+    for queued in receiver {
+        let bytes = loop {
+            match apply(&replica, primary.as_ref(), &queued.write) {
+                Ok(bytes) => break Some(bytes),
+                Err(e) => {
+                    println!("Warning: replication to {} failed: {}", shared.replica, e);
+                    shared.lock().last_error = Some(e.to_string());
+                    if shared.closing.load(Ordering::SeqCst) {
+                        break None;
+                    }
+                    thread::sleep(RETRY_DELAY);
+                }
+            }
+        };
+
+        let mut progress = shared.lock();
+        progress.applied += 1;
+        if let Some(bytes) = bytes {
+            progress.applied_bytes += bytes;
+            progress.last_applied_at = Some(Utc::now());
+            progress.last_error = None;
+            progress.seeded |= matches!(queued.write, Write::Seed { .. });
+        }
+        // The next write was queued no earlier than this one; its exact time
+        // isn't known until it is received
+        progress.behind_since = (progress.applied < progress.queued).then_some(queued.queued_at);
+        if progress.applied == progress.queued {
+            shared.caught_up.notify_all();
+        }
+    }
+}
+
+/// Apply one write to the replica, returning the bytes written
+fn apply(replica: &FileBackend, primary: &dyn StorageBackend, write: &Write) -> io::Result<u64> {
+    match write {
+        Write::Append { name, offset, data } => {
+            if replica.size(name)? == *offset {
+                replica.append(name, data)?;
+            } else {
+                // The replica's copy diverged (e.g. a failed write was given up
+                // on); the primary's bytes below `offset` never change, so
+                // rebuild it from them
+                let mut healed = primary.read_at(name, 0, *offset as usize)?;
+                healed.extend_from_slice(data);
+                replica.write_blob(name, &healed)?;
+            }
+            Ok(data.len() as u64)
+        }
+        Write::Flush { name } => replica.flush(name).map(|_| 0),
+        Write::Blob { name, data } => replica.write_blob(name, data).map(|_| data.len() as u64),
+        Write::Remove { name } => replica.remove(name).map(|_| 0),
+        Write::Rename { from, to } => replica.rename(from, to).map(|_| 0),
+        Write::Shred { name } => replica.shred(name).map(|_| 0),
+        Write::Seed { files } => {
+            let mut bytes = 0;
+            for (name, data) in files {
+                match data {
+                    Some(data) => {
+                        replica.write_blob(name, data)?;
+                        replica.flush(name)?;
+                        bytes += data.len() as u64;
+                    }
+                    None => replica.remove(name)?,
+                }
+            }
+            // Files the store no longer has, e.g. logs of users erased since the last run
+            let keep: HashSet<&str> = files.iter().map(|(name, _)| name.as_str()).collect();
+            for name in files_under(replica.root(), replica.root())? {
+                if name != LOCK_FILE && !keep.contains(name.as_str()) {
+                    replica.remove(&name)?;
+                }
+            }
+            Ok(bytes)
+        }
+    }
+}
+
+/// Names of the files under `dir`, relative to `root`
+fn files_under(root: &Path, dir: &Path) -> io::Result<Vec<String>> {
+    let mut names = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            names.extend(files_under(root, &path)?);
+        } else if let Ok(relative) = path.strip_prefix(root) {
+            let parts: Vec<String> = relative.components().map(|part| part.as_os_str().to_string_lossy().into_owned()).collect();
+            names.push(parts.join("/"));
+        }
+    }
+    Ok(names)
+}
+
+impl MindCache {
+    /// How far the replica at `replica_path` is behind; `None` without one
+    pub fn replication_status(&self) -> Option<ReplicationStatus> {
+        self.replication.as_ref().map(Replicator::status)
+    }
+
+    /// Wait until every write made so far is on the replica, e.g. before a
+    /// planned failover; false on timeout. True without a replica.
+    pub fn wait_for_replication(&self, timeout: Duration) -> bool {
+        self.replication.as_ref().is_none_or(|replication| replication.wait(timeout))
+    }
+
+    /// Every file a replica needs: the store's logs and indices, and the
+    /// change, history and audit logs
+    pub(crate) fn replicated_file_names(&self) -> Vec<String> {
+        let mut names = self.storage.store_file_names();
+        names.extend([CHANGES_LOG, HISTORY_LOG, AUDIT_LOG, VECTOR_SYNC_BLOB].map(String::from));
+        names
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MindCacheConfig;
+    use crate::test_support::test_config;
+    use tempfile::TempDir;
+
+    fn config(storage: &TempDir, replica: Option<&TempDir>) -> MindCacheConfig {
+        MindCacheConfig {
+            replica_path: replica.map(|replica| replica.path().to_string_lossy().into_owned()),
+            ..test_config(storage)
+        }
+    }
+
+    #[test]
+    fn test_replica_takes_over() {
+        let (primary, replica) = (TempDir::new().unwrap(), TempDir::new().unwrap());
+        // Left over from an earlier run; the seed clears it
+        fs::write(replica.path().join("stale.json"), b"{}").unwrap();
+
+        let mut cache = MindCache::with_config(config(&primary, Some(&replica))).unwrap();
+        cache.save("alice", "s1", "Gold is up", None).unwrap();
+        cache.save("bob", "s2", "Silver is flat", None).unwrap();
+        cache.compact().unwrap();
+        let id = cache.save("alice", "s1", "Bronze is down", None).unwrap();
+        cache.delete_memory_soft(&id).unwrap();
+
+        assert!(cache.wait_for_replication(Duration::from_secs(10)));
+        let status = cache.replication_status().unwrap();
+        assert!(status.seeded);
+        assert_eq!(status.pending_writes, 0);
+        assert!(status.behind_since.is_none() && status.last_error.is_none());
+        drop(cache);
+        assert!(!replica.path().join("stale.json").exists());
+
+        // The primary disk is gone: the replica opens as a store of its own
+        drop(primary);
+        let standby = MindCache::with_config(config(&replica, None)).unwrap();
+        assert!(standby.replication_status().is_none());
+        assert_eq!(standby.recall("alice", None, None, None).unwrap().len(), 1);
+        assert_eq!(standby.recall("bob", Some("silver"), None, None).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_diverged_replica_log_is_healed() {
+        let (primary, replica) = (TempDir::new().unwrap(), TempDir::new().unwrap());
+        let backend = ReplicatingBackend::new(Arc::new(FileBackend::new(primary.path()).unwrap()), replica.path()).unwrap();
+        backend.append("log.bin", b"hello").unwrap();
+        assert!(backend.replicator().wait(Duration::from_secs(10)));
+
+        // Lose the replica's copy behind the worker's back
+        fs::remove_file(replica.path().join("log.bin")).unwrap();
+        backend.append("log.bin", b"world").unwrap();
+        assert!(backend.replicator().wait(Duration::from_secs(10)));
+        assert_eq!(fs::read(replica.path().join("log.bin")).unwrap(), b"helloworld");
+        assert_eq!(backend.replicator().status().applied_bytes, 10);
+    }
+}
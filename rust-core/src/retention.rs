@@ -0,0 +1,218 @@
+//! Per-session retention overrides
+//!
+//! A session can carry its own TTL, memory limit and importance floor, set
+//! with `SessionManager::set_session_retention`. Decay checks them before the
+//! global policy: memories of a session with a TTL expire that many hours
+//! after they were saved whatever their importance, so scratch sessions clean
+//! themselves up, and a TTL of 0 keeps a journal session's memories however
+//! old they get. Memories saved into a session with a floor start at least
+//! that important. Overrides live in `session_retention.json` next to the
+//! memory log.
+
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use crate::backend::StorageBackend;
+use crate::storage::MemoryItem;
+use crate::MindCache;
+
+pub const SESSION_RETENTION_BLOB: &str = "session_retention.json";
+
+/// Retention settings of one session; unset fields fall back to the decay policy
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct SessionRetention {
+    /// Expire the session's memories this many hours after they were saved,
+    /// in place of their own TTL; 0 keeps them forever
+    pub ttl_hours: Option<u32>,
+    /// Keep at most this many memories in the session, dropping the least important first
+    pub max_memories: Option<usize>,
+    /// Lowest importance a memory saved into the session starts with
+    #[serde(default)]
+    pub importance_floor: Option<f32>,
+}
+
+impl SessionRetention {
+    pub fn is_default(&self) -> bool {
+        *self == SessionRetention::default()
+    }
+
+    /// Whether `memory` is past the session TTL at `now`; `None` without one
+    pub fn is_expired(&self, memory: &MemoryItem, now: DateTime<Utc>) -> Option<bool> {
+        if memory.never_expires() {
+            return Some(false);
+        }
+        match self.ttl_hours? {
+            0 => Some(false),
+            ttl_hours => Some(now > memory.timestamp + Duration::hours(ttl_hours as i64)),
+        }
+    }
+
+    /// When `memory` expires under these settings and its own TTL
+    pub fn expiry_of(&self, memory: &MemoryItem) -> Option<DateTime<Utc>> {
+        if memory.never_expires() {
+            return None;
+        }
+        match self.ttl_hours {
+            Some(0) => None,
+            Some(ttl_hours) => Some(memory.timestamp + Duration::hours(ttl_hours as i64)),
+            None => memory.ttl_expiry(),
+        }
+    }
+}
+
+/// Persistent retention overrides, keyed by session id
+#[derive(Clone)]
+pub struct RetentionTable {
+    backend: Arc<dyn StorageBackend>,
+    sessions: Arc<RwLock<HashMap<String, SessionRetention>>>,
+}
+
+impl RetentionTable {
+    pub fn load(backend: Arc<dyn StorageBackend>) -> Result<Self, Box<dyn std::error::Error>> {
+        let sessions = Self::read_sessions(backend.as_ref())?;
+        Ok(RetentionTable { backend, sessions: Arc::new(RwLock::new(sessions)) })
+    }
+
+    /// Re-read the overrides from disk, e.g. after a restore
+    pub fn reload(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let sessions = Self::read_sessions(self.backend.as_ref())?;
+        *self.sessions.write().unwrap_or_else(|poisoned| poisoned.into_inner()) = sessions;
+        Ok(())
+    }
+
+    fn read_sessions(backend: &dyn StorageBackend) -> Result<HashMap<String, SessionRetention>, Box<dyn std::error::Error>> {
+        match backend.read_blob(SESSION_RETENTION_BLOB)? {
+            Some(data) => Ok(serde_json::from_slice(&data)?),
+            None => Ok(HashMap::new()),
+        }
+    }
+
+    /// Set a session's overrides; the default settings remove them
+    pub fn set(&self, session_id: &str, retention: SessionRetention) -> Result<(), Box<dyn std::error::Error>> {
+        let mut sessions = self.sessions.write().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let mut updated = sessions.clone();
+        if retention.is_default() {
+            updated.remove(session_id);
+        } else {
+            updated.insert(session_id.to_string(), retention);
+        }
+        self.backend.write_blob(SESSION_RETENTION_BLOB, &serde_json::to_vec(&updated)?)?;
+        *sessions = updated;
+        Ok(())
+    }
+
+    /// A session's overrides, the default settings if it has none
+    pub fn get(&self, session_id: &str) -> SessionRetention {
+        let sessions = self.sessions.read().unwrap_or_else(|poisoned| poisoned.into_inner());
+        sessions.get(session_id).copied().unwrap_or_default()
+    }
+
+    /// Every session with overrides
+    pub fn all(&self) -> HashMap<String, SessionRetention> {
+        self.sessions.read().unwrap_or_else(|poisoned| poisoned.into_inner()).clone()
+    }
+}
+
+impl MindCache {
+    /// Override the TTL, memory limit and importance floor of a session
+    pub fn set_session_retention(&mut self, session_id: &str, retention: SessionRetention) -> Result<(), Box<dyn std::error::Error>> {
+        self.session_manager.set_session_retention(session_id, retention)
+    }
+
+    /// A session's retention overrides
+    pub fn session_retention(&self, session_id: &str) -> SessionRetention {
+        self.storage.session_retention().get(session_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::MemoryEvent;
+    use crate::MindCacheConfig;
+    use std::sync::Mutex;
+    use crate::test_support::{test_config, temp_cache};
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_session_retention_overrides_policy() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = MindCacheConfig {
+            enable_compression: false,
+            default_memory_ttl_hours: Some(24),
+            ..test_config(&temp_dir)
+        };
+        let mut cache = MindCache::with_config(config.clone()).unwrap();
+        let two_days_ago = Utc::now() - Duration::hours(48);
+        let save = |cache: &mut MindCache, session_id: &str, content: &str, importance: f32| {
+            cache.storage.save(MemoryItem {
+                user_id: "alice".to_string(),
+                session_id: session_id.to_string(),
+                content: content.to_string(),
+                timestamp: two_days_ago,
+                ttl_hours: Some(24),
+                importance,
+                ..Default::default()
+            }).unwrap()
+        };
+        let scratch = save(&mut cache, "scratch", "important but scratch", 0.9);
+        let journal = save(&mut cache, "journal", "old journal entry", 0.1);
+        let mut notes = Vec::new();
+        for (content, importance) in [("note a", 0.8), ("note b", 0.5), ("note c", 0.7)] {
+            notes.push(save(&mut cache, "notes", content, importance));
+        }
+
+        cache.set_session_retention("scratch", SessionRetention { ttl_hours: Some(1), ..Default::default() }).unwrap();
+        cache.set_session_retention("journal", SessionRetention { ttl_hours: Some(0), ..Default::default() }).unwrap();
+        cache.set_session_retention("notes", SessionRetention { ttl_hours: Some(0), max_memories: Some(2), ..Default::default() }).unwrap();
+        // Overrides survive a reopen
+        drop(cache);
+        let mut cache = MindCache::with_config(config).unwrap();
+        assert_eq!(cache.session_retention("journal").ttl_hours, Some(0));
+        assert!(cache.session_retention("other").is_default());
+
+        let expired = Arc::new(Mutex::new(Vec::new()));
+        let seen = Arc::clone(&expired);
+        cache.subscribe(move |event: &MemoryEvent| {
+            if let MemoryEvent::MemoryExpired { memory_id, .. } = event {
+                seen.lock().unwrap().push(memory_id.clone());
+            }
+        });
+        cache.decay().unwrap();
+
+        let run = &cache.decay_history(1).unwrap()[0];
+        assert_eq!(run.affected.expired_ids, std::slice::from_ref(&scratch));
+        assert_eq!(run.affected.over_limit_ids, std::slice::from_ref(&notes[1]));
+        assert!(!expired.lock().unwrap().contains(&journal));
+
+        cache.set_session_retention("notes", SessionRetention::default()).unwrap();
+        assert!(cache.session_retention("notes").is_default());
+    }
+
+    #[test]
+    fn test_every_write_keeps_expires_at_current() {
+        let (mut cache, _temp_dir) = temp_cache();
+        let id = cache.save_with_options("alice", "s1", "Parking spot is B4", None, 0.5, None).unwrap();
+        let expires_at = |cache: &MindCache, id: &str| cache.get_memory(id).unwrap().unwrap().expires_at;
+        let saved_at = cache.get_memory(&id).unwrap().unwrap().timestamp;
+        assert_eq!(expires_at(&cache, &id), None);
+
+        // Retention changes reach memories saved before them
+        cache.set_session_retention("s1", SessionRetention { ttl_hours: Some(1), ..Default::default() }).unwrap();
+        assert_eq!(expires_at(&cache, &id), Some(saved_at + Duration::hours(1)));
+        cache.set_session_retention("s1", SessionRetention::default()).unwrap();
+        assert_eq!(expires_at(&cache, &id), None);
+
+        cache.update_memory(&id, crate::MemoryUpdate { ttl_hours: Some(Some(2)), ..Default::default() }).unwrap();
+        assert_eq!(expires_at(&cache, &id), Some(saved_at + Duration::hours(2)));
+
+        let mut queued = String::new();
+        cache.transaction(|tx| {
+            queued = tx.save_with_options("alice", "s1", "Gate code is 1234", None, 0.5, Some(5));
+            Ok(())
+        }).unwrap();
+        let queued_at = cache.get_memory(&queued).unwrap().unwrap().timestamp;
+        assert_eq!(expires_at(&cache, &queued), Some(queued_at + Duration::hours(5)));
+    }
+}
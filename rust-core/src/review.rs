@@ -0,0 +1,322 @@
+//! Periodic reviews
+//!
+//! A review tells what happened to a user's memories over a period: how
+//! many were saved, how many decay expired and what they were about, for
+//! products that send users "your agent's week in review".
+//! `MindCache::run_due_reviews` reviews every user whose last review is
+//! `review_interval_hours` old and delivers the reviews as
+//! `review_delivery` says: emitted as [`MemoryEvent::ReviewReady`], stored
+//! as [`MemoryType::Summary`] memories in [`REVIEW_SESSION`], or both. When
+//! each user was last reviewed is kept in `reviews.json`.
+//!
+//! The server binary checks for due reviews every
+//! [`REVIEW_POLL_INTERVAL_SECS`] seconds; other hosts call
+//! `run_due_reviews` on their own schedule. Expired counts come from the
+//! decay journal, so memories deleted outside decay are not in them.
+
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use crate::backend::StorageBackend;
+use crate::events::MemoryEvent;
+use crate::storage::{MemoryItem, MemoryType, QueryFilter};
+use crate::topics::UserTopic;
+use crate::MindCache;
+
+pub const REVIEWS_BLOB: &str = "reviews.json";
+
+/// Session that stored reviews are saved under
+pub const REVIEW_SESSION: &str = "_reviews";
+
+/// How often the server binary checks for users due a review
+pub const REVIEW_POLL_INTERVAL_SECS: u64 = 300;
+
+/// Topics kept per review
+const REVIEW_TOPICS: usize = 5;
+
+const REVIEW_FROM_KEY: &str = "review_from";
+const REVIEW_TO_KEY: &str = "review_to";
+
+/// What `run_due_reviews` does with a review
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReviewDelivery {
+    /// Emit [`MemoryEvent::ReviewReady`] for observers to send on
+    #[default]
+    Event,
+    /// Save it as a memory of its user
+    Store,
+    /// Emit it and save it
+    Both,
+}
+
+impl ReviewDelivery {
+    fn stores(self) -> bool {
+        matches!(self, ReviewDelivery::Store | ReviewDelivery::Both)
+    }
+
+    fn emits(self) -> bool {
+        matches!(self, ReviewDelivery::Event | ReviewDelivery::Both)
+    }
+}
+
+/// What happened to a user's memories over a period
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Review {
+    /// Id of the memory the review was stored as; empty if it wasn't
+    pub id: String,
+    pub user_id: String,
+    pub period: (DateTime<Utc>, DateTime<Utc>),
+    /// Memories saved in the period, not counting generated summaries
+    pub new_memories: usize,
+    /// Memories decay expired or dropped over a limit in the period
+    pub expired_memories: usize,
+    /// Words the period's memories mentioned most; `previous_count` is for
+    /// the period of the same length before
+    pub top_topics: Vec<UserTopic>,
+}
+
+impl Review {
+    /// Whether nothing was saved or expired in the period
+    pub fn is_empty(&self) -> bool {
+        self.new_memories == 0 && self.expired_memories == 0
+    }
+
+    /// The review as a short paragraph, e.g. for an email
+    pub fn text(&self) -> String {
+        let mut text = format!(
+            "{} new memories and {} expired between {} and {}.",
+            self.new_memories,
+            self.expired_memories,
+            self.period.0.format("%Y-%m-%d"),
+            self.period.1.format("%Y-%m-%d")
+        );
+        if !self.top_topics.is_empty() {
+            let topics: Vec<String> = self.top_topics.iter().map(|topic| format!("{} ({})", topic.topic, topic.count)).collect();
+            text.push_str(&format!(" Top topics: {}.", topics.join(", ")));
+        }
+        text
+    }
+
+    fn to_memory(&self) -> MemoryItem {
+        MemoryItem {
+            id: self.id.clone(),
+            user_id: self.user_id.clone(),
+            session_id: REVIEW_SESSION.to_string(),
+            content: self.text(),
+            metadata: HashMap::from([
+                (REVIEW_FROM_KEY.to_string(), self.period.0.to_rfc3339()),
+                (REVIEW_TO_KEY.to_string(), self.period.1.to_rfc3339()),
+            ]),
+            timestamp: self.period.1,
+            importance: 0.5,
+            memory_type: MemoryType::Summary,
+            ..Default::default()
+        }
+    }
+}
+
+/// When each user was last reviewed, persisted
+#[derive(Clone)]
+pub struct ReviewLog {
+    backend: Arc<dyn StorageBackend>,
+    reviewed: Arc<RwLock<HashMap<String, DateTime<Utc>>>>,
+}
+
+impl ReviewLog {
+    pub fn load(backend: Arc<dyn StorageBackend>) -> Result<Self, Box<dyn std::error::Error>> {
+        let reviewed = Self::read_reviewed(backend.as_ref())?;
+        Ok(ReviewLog { backend, reviewed: Arc::new(RwLock::new(reviewed)) })
+    }
+
+    /// Re-read the log from disk, e.g. after a restore
+    pub fn reload(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let reviewed = Self::read_reviewed(self.backend.as_ref())?;
+        *self.reviewed.write().unwrap_or_else(|poisoned| poisoned.into_inner()) = reviewed;
+        Ok(())
+    }
+
+    fn read_reviewed(backend: &dyn StorageBackend) -> Result<HashMap<String, DateTime<Utc>>, Box<dyn std::error::Error>> {
+        match backend.read_blob(REVIEWS_BLOB)? {
+            Some(data) => Ok(serde_json::from_slice(&data)?),
+            None => Ok(HashMap::new()),
+        }
+    }
+
+    /// End of the user's last review period
+    pub fn last_reviewed(&self, user_id: &str) -> Option<DateTime<Utc>> {
+        self.reviewed.read().unwrap_or_else(|poisoned| poisoned.into_inner()).get(user_id).copied()
+    }
+
+    pub fn mark_reviewed(&self, user_id: &str, at: DateTime<Utc>) -> Result<(), Box<dyn std::error::Error>> {
+        self.update(|reviewed| {
+            reviewed.insert(user_id.to_string(), at);
+        })
+    }
+
+    pub fn remove_user(&self, user_id: &str) -> Result<(), Box<dyn std::error::Error>> {
+        if self.last_reviewed(user_id).is_none() {
+            return Ok(());
+        }
+        self.update(|reviewed| {
+            reviewed.remove(user_id);
+        })
+    }
+
+    /// Apply `edit` and persist, leaving the log as it was if that fails
+    fn update<F>(&self, edit: F) -> Result<(), Box<dyn std::error::Error>>
+    where
+        F: FnOnce(&mut HashMap<String, DateTime<Utc>>),
+    {
+        let mut reviewed = self.reviewed.write().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let previous = reviewed.clone();
+        edit(&mut reviewed);
+        let persisted = serde_json::to_vec(&*reviewed)
+            .map_err(Box::<dyn std::error::Error>::from)
+            .and_then(|data| Ok(self.backend.write_blob(REVIEWS_BLOB, &data)?));
+        if let Err(e) = persisted {
+            *reviewed = previous;
+            return Err(e);
+        }
+        Ok(())
+    }
+}
+
+impl MindCache {
+    /// Review of a user's memories from `from` up to `to`
+    ///
+    /// Nothing is stored or emitted; `run_due_reviews` does that.
+    pub fn review_user(&self, user_id: &str, from: DateTime<Utc>, to: DateTime<Utc>) -> Result<Review, Box<dyn std::error::Error>> {
+        let period = QueryFilter {
+            user_id: Some(user_id.to_string()),
+            date_from: Some(from),
+            date_to: Some(to),
+            include_superseded: true,
+            ..Default::default()
+        };
+        let saved = self.storage.count(period.clone())?;
+        let generated = self.storage.count(QueryFilter { memory_type: Some(MemoryType::Summary), ..period })?;
+
+        let expired_memories = self
+            .storage
+            .decay_journal()
+            .recent(None)?
+            .iter()
+            .filter(|run| run.started_at >= from && run.started_at <= to)
+            .filter_map(|run| run.affected.expired_by_user.get(user_id))
+            .sum();
+
+        let mut top_topics = self.topics_since(user_id, from, from - (to - from));
+        top_topics.retain(|topic| topic.last_seen <= to);
+        top_topics.truncate(REVIEW_TOPICS);
+
+        Ok(Review {
+            id: String::new(),
+            user_id: user_id.to_string(),
+            period: (from, to),
+            new_memories: saved - generated,
+            expired_memories,
+            top_topics,
+        })
+    }
+
+    /// Review every user last reviewed `review_interval_hours` or more before
+    /// `now`, deliver the reviews and return them
+    ///
+    /// A user's first review covers the interval before `now`. Users with
+    /// nothing new or expired are marked reviewed without a delivery. Does
+    /// nothing unless `review_interval_hours` is set.
+    pub fn run_due_reviews(&mut self, now: DateTime<Utc>) -> Result<Vec<Review>, Box<dyn std::error::Error>> {
+        let Some(hours) = self.config.review_interval_hours else {
+            return Ok(Vec::new());
+        };
+        let interval = Duration::hours(hours as i64);
+        let delivery = self.config.review_delivery;
+
+        let mut users: Vec<String> = self.storage.get_stats().into_keys().collect();
+        users.sort();
+        let mut delivered = Vec::new();
+        for user_id in users {
+            let last_reviewed = self.storage.reviews().last_reviewed(&user_id);
+            if last_reviewed.is_some_and(|last| now - last < interval) {
+                continue;
+            }
+            let mut review = self.review_user(&user_id, last_reviewed.unwrap_or(now - interval), now)?;
+            if !review.is_empty() {
+                if delivery.stores() {
+                    review.id = format!("review-{}-{}", user_id, now.timestamp());
+                    self.storage.save(review.to_memory())?;
+                }
+                if delivery.emits() {
+                    self.storage.events().emit(MemoryEvent::ReviewReady { review: review.clone() });
+                }
+                delivered.push(review);
+            }
+            self.storage.reviews().mark_reviewed(&user_id, now)?;
+        }
+        println!("Delivered {} reviews", delivered.len());
+        Ok(delivered)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+    use crate::MindCacheConfig;
+    use crate::test_support::test_config;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_due_reviews_are_delivered_once_per_interval() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = MindCacheConfig {
+            review_interval_hours: Some(24 * 7),
+            review_delivery: ReviewDelivery::Both,
+            ..test_config(&temp_dir)
+        };
+        let mut cache = MindCache::with_config(config.clone()).unwrap();
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&events);
+        cache.subscribe(move |event: &MemoryEvent| {
+            if let MemoryEvent::ReviewReady { review } = event {
+                sink.lock().unwrap().push(review.user_id.clone());
+            }
+        });
+
+        let now = Utc::now();
+        cache.storage.save(MemoryItem {
+            user_id: "alice".to_string(),
+            session_id: "s1".to_string(),
+            content: "Stale scratch note".to_string(),
+            timestamp: now - Duration::days(60),
+            ttl_hours: Some(1),
+            importance: 0.1,
+            ..Default::default()
+        }).unwrap();
+        for content in ["Bought gold coins", "Gold rallied again", "Booked the dentist"] {
+            cache.save("alice", "s1", content, None).unwrap();
+        }
+        cache.decay().unwrap();
+
+        let reviews = cache.run_due_reviews(now + Duration::minutes(1)).unwrap();
+        assert_eq!(reviews.len(), 1);
+        let review = &reviews[0];
+        assert_eq!((review.new_memories, review.expired_memories), (3, 1));
+        assert_eq!(review.top_topics[0].topic, "gold");
+        assert!(review.text().contains("gold (2)"));
+        assert_eq!(*events.lock().unwrap(), ["alice"]);
+        let stored = cache.get_memory(&review.id).unwrap().unwrap();
+        assert_eq!((stored.session_id.as_str(), stored.memory_type), (REVIEW_SESSION, MemoryType::Summary));
+
+        // Not due again until the interval has passed, also after a reopen
+        assert!(cache.run_due_reviews(now + Duration::days(1)).unwrap().is_empty());
+        drop(cache);
+        let mut cache = MindCache::with_config(config).unwrap();
+        assert!(cache.run_due_reviews(now + Duration::days(6)).unwrap().is_empty());
+        // Nothing new since: reviewed, but nothing to deliver; the stored review isn't new
+        assert!(cache.run_due_reviews(now + Duration::days(8)).unwrap().is_empty());
+        assert!(cache.user_topics("alice", crate::RelativeDuration::days(30)).iter().all(|topic| topic.topic != "expired"));
+    }
+}
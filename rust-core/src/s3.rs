@@ -0,0 +1,303 @@
+//! Object storage backend (S3 and compatible services)
+//!
+//! [`S3Backend`] keeps a store's logs and blobs as objects under a key
+//! prefix, so a store outlives the machine or container that writes it. A
+//! local directory caches the objects: each one is downloaded on first use,
+//! appends go to the local copy, and `flush` uploads the log as a whole.
+//! Blob writes go straight to the bucket. The cache directory can be lost at
+//! any time; only unflushed appends, which a local disk would not have made
+//! durable either, go with it.
+//!
+//! Object stores can't append, so every flush of a log uploads all of it.
+//! Logs are per user (see [`crate::segments`]), which keeps uploads to the
+//! size of one user's memories.
+//!
+//! Like a directory, a bucket prefix must have a single writer. The lock
+//! taken on the cache directory only keeps out writers on the same machine.
+
+use std::collections::HashSet;
+use std::future::Future;
+use std::io;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, MutexGuard};
+use object_store::aws::AmazonS3Builder;
+use object_store::path::Path as ObjectPath;
+use object_store::{ObjectStore, PutPayload};
+use tokio::runtime::Runtime;
+use crate::backend::{AccessMode, FileBackend, StorageBackend};
+
+/// Where an [`S3Backend`] keeps its objects
+///
+/// Unset credentials and region are read from the usual `AWS_*`
+/// environment variables.
+#[derive(Debug, Clone, Default)]
+pub struct S3Config {
+    pub bucket: String,
+    /// Key prefix of the store's objects, e.g. `agents/prod`
+    pub prefix: String,
+    pub region: Option<String>,
+    /// Endpoint of an S3-compatible service such as MinIO or R2
+    pub endpoint: Option<String>,
+    pub access_key_id: Option<String>,
+    pub secret_access_key: Option<String>,
+    /// Allow a plain `http://` endpoint, e.g. a local MinIO
+    pub allow_http: bool,
+    /// Local directory caching the objects
+    pub cache_dir: PathBuf,
+}
+
+/// Runtime driving the object store client, shut down without blocking
+struct ClientRuntime(Option<Runtime>);
+
+impl Drop for ClientRuntime {
+    fn drop(&mut self) {
+        // Dropping a runtime blocks, which panics inside async code
+        if let Some(runtime) = self.0.take() {
+            runtime.shutdown_background();
+        }
+    }
+}
+
+/// Backend storing logs and blobs in an object store
+#[derive(Clone)]
+pub struct S3Backend {
+    store: Arc<dyn ObjectStore>,
+    prefix: String,
+    cache: FileBackend,
+    runtime: Arc<ClientRuntime>,
+    /// Objects whose cached copy is current
+    loaded: Arc<Mutex<HashSet<String>>>,
+    /// Logs with appends not uploaded yet
+    dirty: Arc<Mutex<HashSet<String>>>,
+}
+
+impl S3Backend {
+    /// Connect to the bucket described by `config`
+    pub fn new(config: S3Config) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut builder = AmazonS3Builder::from_env().with_bucket_name(&config.bucket).with_allow_http(config.allow_http);
+        if let Some(region) = &config.region {
+            builder = builder.with_region(region);
+        }
+        if let Some(endpoint) = &config.endpoint {
+            builder = builder.with_endpoint(endpoint);
+        }
+        if let Some(access_key_id) = &config.access_key_id {
+            builder = builder.with_access_key_id(access_key_id);
+        }
+        if let Some(secret_access_key) = &config.secret_access_key {
+            builder = builder.with_secret_access_key(secret_access_key);
+        }
+        Self::with_store(Arc::new(builder.build()?), &config.prefix, &config.cache_dir)
+    }
+
+    /// Use any object store, e.g. `object_store::memory::InMemory` in tests
+    pub fn with_store(store: Arc<dyn ObjectStore>, prefix: &str, cache_dir: impl Into<PathBuf>) -> Result<Self, Box<dyn std::error::Error>> {
+        let runtime = tokio::runtime::Builder::new_multi_thread().worker_threads(1).enable_all().build()?;
+        Ok(S3Backend {
+            store,
+            prefix: prefix.trim_matches('/').to_string(),
+            cache: FileBackend::open(cache_dir.into(), AccessMode::ReadWrite)?,
+            runtime: Arc::new(ClientRuntime(Some(runtime))),
+            loaded: Arc::new(Mutex::new(HashSet::new())),
+            dirty: Arc::new(Mutex::new(HashSet::new())),
+        })
+    }
+
+    fn key(&self, name: &str) -> ObjectPath {
+        if self.prefix.is_empty() {
+            ObjectPath::from(name)
+        } else {
+            ObjectPath::from(format!("{}/{}", self.prefix, name))
+        }
+    }
+
+    /// Run a client call on the backend's runtime and wait for it
+    ///
+    /// Works from sync code and from inside other runtimes alike.
+    fn run<T, F>(&self, future: F) -> io::Result<T>
+    where
+        T: Send + 'static,
+        F: Future<Output = object_store::Result<T>> + Send + 'static,
+    {
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let runtime = self.runtime.0.as_ref().expect("runtime is only taken on drop");
+        runtime.spawn(async move {
+            let _ = sender.send(future.await);
+        });
+        match receiver.recv() {
+            Ok(Ok(value)) => Ok(value),
+            Ok(Err(object_store::Error::NotFound { path, source })) => {
+                Err(io::Error::new(io::ErrorKind::NotFound, format!("{}: {}", path, source)))
+            }
+            Ok(Err(e)) => Err(io::Error::other(e)),
+            Err(e) => Err(io::Error::other(e)),
+        }
+    }
+
+    fn get(&self, name: &str) -> io::Result<Option<Vec<u8>>> {
+        let store = Arc::clone(&self.store);
+        let key = self.key(name);
+        match self.run(async move { store.get(&key).await?.bytes().await }) {
+            Ok(data) => Ok(Some(data.to_vec())),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn put(&self, name: &str, data: Vec<u8>) -> io::Result<()> {
+        let store = Arc::clone(&self.store);
+        let key = self.key(name);
+        self.run(async move { store.put(&key, PutPayload::from(data)).await.map(|_| ()) })
+    }
+
+    fn delete(&self, name: &str) -> io::Result<()> {
+        let store = Arc::clone(&self.store);
+        let key = self.key(name);
+        match self.run(async move { store.delete(&key).await }) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+
+    fn lock_loaded(&self) -> MutexGuard<'_, HashSet<String>> {
+        self.loaded.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn lock_dirty(&self) -> MutexGuard<'_, HashSet<String>> {
+        self.dirty.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Bring the cached copy of `name` in line with the bucket, once
+    fn ensure_cached(&self, name: &str) -> io::Result<()> {
+        let mut loaded = self.lock_loaded();
+        if loaded.contains(name) {
+            return Ok(());
+        }
+        match self.get(name)? {
+            Some(data) => self.cache.write_blob(name, &data)?,
+            None => self.cache.remove(name)?,
+        }
+        loaded.insert(name.to_string());
+        Ok(())
+    }
+}
+
+impl StorageBackend for S3Backend {
+    fn append(&self, name: &str, data: &[u8]) -> io::Result<u64> {
+        self.ensure_cached(name)?;
+        let position = self.cache.append(name, data)?;
+        self.lock_dirty().insert(name.to_string());
+        Ok(position)
+    }
+
+    fn read_at(&self, name: &str, offset: u64, len: usize) -> io::Result<Vec<u8>> {
+        self.ensure_cached(name)?;
+        self.cache.read_at(name, offset, len)
+    }
+
+    fn flush(&self, name: &str) -> io::Result<()> {
+        if !self.lock_dirty().remove(name) {
+            return Ok(());
+        }
+        let data = self.cache.read_blob(name)?.unwrap_or_default();
+        self.put(name, data).inspect_err(|_| {
+            self.lock_dirty().insert(name.to_string());
+        })
+    }
+
+    fn read_blob(&self, name: &str) -> io::Result<Option<Vec<u8>>> {
+        self.ensure_cached(name)?;
+        self.cache.read_blob(name)
+    }
+
+    fn write_blob(&self, name: &str, data: &[u8]) -> io::Result<()> {
+        self.put(name, data.to_vec())?;
+        self.cache.write_blob(name, data)?;
+        self.lock_dirty().remove(name);
+        self.lock_loaded().insert(name.to_string());
+        Ok(())
+    }
+
+    fn size(&self, name: &str) -> io::Result<u64> {
+        self.ensure_cached(name)?;
+        self.cache.size(name)
+    }
+
+    fn remove(&self, name: &str) -> io::Result<()> {
+        self.delete(name)?;
+        self.cache.remove(name)?;
+        self.lock_dirty().remove(name);
+        self.lock_loaded().insert(name.to_string());
+        Ok(())
+    }
+
+    fn rename(&self, from: &str, to: &str) -> io::Result<()> {
+        self.flush(from)?;
+        self.ensure_cached(from)?;
+        let store = Arc::clone(&self.store);
+        let (from_key, to_key) = (self.key(from), self.key(to));
+        self.run(async move { store.copy(&from_key, &to_key).await })?;
+        self.delete(from)?;
+        self.cache.rename(from, to)?;
+        self.lock_loaded().insert(to.to_string());
+        self.lock_dirty().remove(to);
+        Ok(())
+    }
+
+    fn location(&self) -> String {
+        format!("{}/{}", self.store, self.prefix)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{MindCache, MindCacheConfig};
+    use object_store::memory::InMemory;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_objects_survive_losing_the_cache() {
+        let bucket: Arc<dyn ObjectStore> = Arc::new(InMemory::new());
+        let config = MindCacheConfig { auto_decay_enabled: false, ..Default::default() };
+
+        let cache_dir = TempDir::new().unwrap();
+        let backend = S3Backend::with_store(Arc::clone(&bucket), "agents/prod", cache_dir.path()).unwrap();
+        let mut cache = MindCache::with_backend(config.clone(), Arc::new(backend)).unwrap();
+        cache.save("alice", "s1", "Gold is up", None).unwrap();
+        cache.save("bob", "s2", "Silver is flat", None).unwrap();
+        cache.compact().unwrap();
+        cache.save("alice", "s1", "Bronze is down", None).unwrap();
+        drop(cache);
+        drop(cache_dir);
+
+        // A fresh machine with an empty cache sees everything
+        let cache_dir = TempDir::new().unwrap();
+        let backend = S3Backend::with_store(Arc::clone(&bucket), "agents/prod", cache_dir.path()).unwrap();
+        assert!(backend.location().ends_with("/agents/prod"));
+        let cache = MindCache::with_backend(config, Arc::new(backend)).unwrap();
+        assert_eq!(cache.recall("alice", None, None, None).unwrap().len(), 2);
+        assert_eq!(cache.recall("bob", Some("silver"), None, None).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_appends_upload_on_flush() {
+        let bucket: Arc<dyn ObjectStore> = Arc::new(InMemory::new());
+        let cache_dir = TempDir::new().unwrap();
+        let backend = S3Backend::with_store(Arc::clone(&bucket), "", cache_dir.path()).unwrap();
+
+        assert_eq!(backend.append("log.bin", b"hello").unwrap(), 0);
+        assert_eq!(backend.append("log.bin", b"world").unwrap(), 5);
+        assert_eq!(backend.get("log.bin").unwrap(), None);
+        backend.flush("log.bin").unwrap();
+        assert_eq!(backend.get("log.bin").unwrap(), Some(b"helloworld".to_vec()));
+        assert_eq!(backend.read_at("log.bin", 5, 5).unwrap(), b"world");
+
+        backend.write_blob("index.bin", b"a:0").unwrap();
+        backend.rename("index.bin", "moved.bin").unwrap();
+        assert_eq!(backend.get("index.bin").unwrap(), None);
+        assert_eq!(backend.read_blob("moved.bin").unwrap(), Some(b"a:0".to_vec()));
+        backend.remove("log.bin").unwrap();
+        assert_eq!(backend.size("log.bin").unwrap(), 0);
+    }
+}
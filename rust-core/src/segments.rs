@@ -0,0 +1,277 @@
+//! Per-user segment logs
+//!
+//! Each user's records live in their own append-only log,
+//! `users/<hash>/memories.bin`, where `<hash>` is a hex hash of the user id.
+//! Deleting, exporting or compacting one user then only touches that user's
+//! file. Stores written before sharding keep their records in the global
+//! `memories.bin` (segment 0) until the next compaction moves them.
+//!
+//! Compaction and restore replace several logs and index blobs at once. They
+//! stage the new files next to the old ones and commit through a
+//! [`SwapPlan`] recorded in `swap.json`, which is finished or rolled back on
+//! the next open if the process dies midway.
+//!
+//! A record position packs the segment number above [`SEGMENT_SHIFT`] bits
+//! of byte offset, so the rest of the storage layer keeps addressing records
+//! with a single `usize`. `segments.json` maps user ids to segment numbers.
+
+use std::collections::{BTreeMap, HashMap};
+use std::io;
+use std::sync::{Arc, RwLock};
+use serde::{Deserialize, Serialize};
+use crate::backend::StorageBackend;
+use crate::dedupe;
+
+/// Log of stores written before sharding (segment 0)
+pub const LEGACY_LOG: &str = "memories.bin";
+pub const SEGMENTS_BLOB: &str = "segments.json";
+/// Present while a multi-file swap is in progress
+pub const SWAP_BLOB: &str = "swap.json";
+
+/// Suffix of the replacement files staged by a swap
+pub const STAGED_SUFFIX: &str = ".compact";
+
+/// Bits of a position holding the byte offset within its segment (1 TiB)
+pub const SEGMENT_SHIFT: u32 = 40;
+
+const _: () = assert!(usize::BITS >= 64, "segmented record positions need a 64-bit target");
+
+/// Record position of `offset` within `segment`
+pub fn pack(segment: u32, offset: u64) -> usize {
+    ((segment as usize) << SEGMENT_SHIFT) | offset as usize
+}
+
+pub fn segment_of(position: usize) -> u32 {
+    (position >> SEGMENT_SHIFT) as u32
+}
+
+pub fn offset_of(position: usize) -> u64 {
+    (position & ((1 << SEGMENT_SHIFT) - 1)) as u64
+}
+
+/// Name of the log holding a user's records
+pub fn user_log(user_id: &str) -> String {
+    format!("users/{:016x}/{}", dedupe::fnv1a(user_id), LEGACY_LOG)
+}
+
+#[derive(Default)]
+struct Segments {
+    by_user: BTreeMap<String, u32>,
+    /// segment -> log name
+    logs: HashMap<u32, String>,
+}
+
+impl Segments {
+    fn from_users(by_user: BTreeMap<String, u32>) -> Self {
+        let logs = by_user.iter().map(|(user_id, segment)| (*segment, user_log(user_id))).collect();
+        Segments { by_user, logs }
+    }
+}
+
+/// Persistent map of user id -> segment number
+#[derive(Clone)]
+pub struct SegmentTable {
+    backend: Arc<dyn StorageBackend>,
+    segments: Arc<RwLock<Segments>>,
+}
+
+impl SegmentTable {
+    pub fn load(backend: Arc<dyn StorageBackend>) -> Result<Self, Box<dyn std::error::Error>> {
+        let segments = Self::read_segments(backend.as_ref())?;
+        Ok(SegmentTable {
+            backend,
+            segments: Arc::new(RwLock::new(segments)),
+        })
+    }
+
+    /// Re-read the table from disk, e.g. after a restore
+    pub fn reload(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let segments = Self::read_segments(self.backend.as_ref())?;
+        *self.segments.write().unwrap_or_else(|poisoned| poisoned.into_inner()) = segments;
+        Ok(())
+    }
+
+    fn read_segments(backend: &dyn StorageBackend) -> Result<Segments, Box<dyn std::error::Error>> {
+        let by_user = match backend.read_blob(SEGMENTS_BLOB)? {
+            Some(data) => serde_json::from_slice(&data)?,
+            None => BTreeMap::new(),
+        };
+        Ok(Segments::from_users(by_user))
+    }
+
+    /// The user's segment, if they have one
+    pub fn user_segment(&self, user_id: &str) -> Option<u32> {
+        self.segments.read().unwrap_or_else(|poisoned| poisoned.into_inner()).by_user.get(user_id).copied()
+    }
+
+    /// The user's segment, assigning and persisting a new one on first use
+    pub fn segment_for(&self, user_id: &str) -> Result<u32, Box<dyn std::error::Error>> {
+        if let Some(segment) = self.user_segment(user_id) {
+            return Ok(segment);
+        }
+
+        let mut segments = self.segments.write().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if let Some(segment) = segments.by_user.get(user_id) {
+            return Ok(*segment);
+        }
+        let segment = segments.by_user.values().max().map_or(1, |last| last + 1);
+        segments.by_user.insert(user_id.to_string(), segment);
+        if let Err(e) = self.persist(&segments.by_user) {
+            segments.by_user.remove(user_id);
+            return Err(e);
+        }
+        segments.logs.insert(segment, user_log(user_id));
+        Ok(segment)
+    }
+
+    /// Log file of a segment
+    pub fn log_name(&self, segment: u32) -> Option<String> {
+        if segment == 0 {
+            return Some(LEGACY_LOG.to_string());
+        }
+        self.segments.read().unwrap_or_else(|poisoned| poisoned.into_inner()).logs.get(&segment).cloned()
+    }
+
+    /// Every log of the store, the legacy log first
+    pub fn logs(&self) -> Vec<String> {
+        let segments = self.segments.read().unwrap_or_else(|poisoned| poisoned.into_inner());
+        std::iter::once(LEGACY_LOG.to_string())
+            .chain(segments.by_user.keys().map(|user_id| user_log(user_id)))
+            .collect()
+    }
+
+    /// Every segment with its log, the legacy log first
+    pub fn segment_logs(&self) -> Vec<(u32, String)> {
+        let segments = self.segments.read().unwrap_or_else(|poisoned| poisoned.into_inner());
+        std::iter::once((0, LEGACY_LOG.to_string()))
+            .chain(segments.by_user.iter().map(|(user_id, segment)| (*segment, user_log(user_id))))
+            .collect()
+    }
+
+    /// Users that have a segment, in id order
+    pub fn users(&self) -> Vec<String> {
+        self.segments.read().unwrap_or_else(|poisoned| poisoned.into_inner()).by_user.keys().cloned().collect()
+    }
+
+    /// Forget a user's segment; their log must already be gone
+    pub fn remove_user(&self, user_id: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let mut segments = self.segments.write().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let Some(segment) = segments.by_user.remove(user_id) else {
+            return Ok(());
+        };
+        if let Err(e) = self.persist(&segments.by_user) {
+            segments.by_user.insert(user_id.to_string(), segment);
+            return Err(e);
+        }
+        segments.logs.remove(&segment);
+        Ok(())
+    }
+
+    fn persist(&self, by_user: &BTreeMap<String, u32>) -> Result<(), Box<dyn std::error::Error>> {
+        self.backend.write_blob(SEGMENTS_BLOB, &serde_json::to_vec(by_user)?)?;
+        Ok(())
+    }
+}
+
+/// Files replaced together by compaction or restore
+///
+/// Replacements are staged as `<name>.compact` while the plan is recorded as
+/// uncommitted; recording it as committed is the commit point.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct SwapPlan {
+    committed: bool,
+    /// Files with a staged replacement
+    pub replace: Vec<String>,
+    /// Files deleted by the swap
+    pub remove: Vec<String>,
+}
+
+impl SwapPlan {
+    /// Record the plan before anything is staged, so a crash can clean up
+    pub fn begin(replace: Vec<String>, remove: Vec<String>, backend: &dyn StorageBackend) -> Result<Self, Box<dyn std::error::Error>> {
+        let plan = SwapPlan { committed: false, replace, remove };
+        backend.write_blob(SWAP_BLOB, &serde_json::to_vec(&plan)?)?;
+        Ok(plan)
+    }
+
+    /// Where the replacement for `name` is staged
+    pub fn staged(name: &str) -> String {
+        format!("{}{}", name, STAGED_SUFFIX)
+    }
+
+    /// Mark the swap as committed; it will be finished even after a crash
+    pub fn commit(&mut self, backend: &dyn StorageBackend) -> Result<(), Box<dyn std::error::Error>> {
+        self.committed = true;
+        backend.write_blob(SWAP_BLOB, &serde_json::to_vec(self)?)?;
+        Ok(())
+    }
+
+    /// Move the staged files into place; safe to repeat after a crash
+    pub fn apply(&self, backend: &dyn StorageBackend) -> Result<(), Box<dyn std::error::Error>> {
+        for name in &self.replace {
+            match backend.rename(&Self::staged(name), name) {
+                Ok(()) => {}
+                // Already moved before the crash
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        for name in &self.remove {
+            backend.remove(name)?;
+        }
+        backend.remove(SWAP_BLOB)?;
+        Ok(())
+    }
+
+    /// Delete whatever was staged
+    pub fn discard(&self, backend: &dyn StorageBackend) -> Result<(), Box<dyn std::error::Error>> {
+        for name in &self.replace {
+            backend.remove(&Self::staged(name))?;
+        }
+        backend.remove(SWAP_BLOB)?;
+        Ok(())
+    }
+
+    /// Finish or roll back a swap interrupted by a crash; false if there was none
+    pub fn recover(backend: &dyn StorageBackend) -> Result<bool, Box<dyn std::error::Error>> {
+        let Some(data) = backend.read_blob(SWAP_BLOB)? else {
+            return Ok(false);
+        };
+        let plan: SwapPlan = serde_json::from_slice(&data)?;
+        if plan.committed {
+            println!("Finishing interrupted file swap in {}", backend.location());
+            plan.apply(backend)?;
+        } else {
+            println!("Discarding interrupted file swap in {}", backend.location());
+            plan.discard(backend)?;
+        }
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::FileBackend;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_positions_and_segment_table() {
+        let position = pack(3, 1234);
+        assert_eq!((segment_of(position), offset_of(position)), (3, 1234));
+        assert_eq!(pack(0, 99), 99);
+
+        let temp_dir = TempDir::new().unwrap();
+        let backend: Arc<dyn StorageBackend> = Arc::new(FileBackend::new(temp_dir.path()).unwrap());
+        let table = SegmentTable::load(backend.clone()).unwrap();
+        assert_eq!(table.segment_for("alice").unwrap(), 1);
+        assert_eq!(table.segment_for("bob").unwrap(), 2);
+        assert_eq!(table.segment_for("alice").unwrap(), 1);
+        assert_eq!(table.log_name(2), Some(user_log("bob")));
+        assert_ne!(user_log("alice"), user_log("bob"));
+
+        let reloaded = SegmentTable::load(backend).unwrap();
+        assert_eq!(reloaded.user_segment("bob"), Some(2));
+        assert_eq!(reloaded.logs(), vec![LEGACY_LOG.to_string(), user_log("alice"), user_log("bob")]);
+    }
+}
@@ -0,0 +1,531 @@
+//! HTTP/JSON API for running MindCache as a standalone memory service
+//!
+//! Used by the `mindcache-server` binary, and exposed so applications can
+//! mount the routes in their own axum app.
+//!
+//! | Method | Path                    | Body / query                                                |
+//! |--------|-------------------------|-------------------------------------------------------------|
+//! | DELETE | `/users/{id}`           | -                                                           |
+//! | POST   | `/users/{id}/memories`  | `{session_id, content, metadata?, importance?, ttl_hours?}` |
+//! | GET    | `/users/{id}/memories`  | `?query=&session_id=&limit=`                                |
+//! | GET    | `/users/{id}/histogram` | `?bucket=hour\|day\|week` (default `day`)                   |
+//! | POST   | `/sessions`             | `{user_id, name?}`                                          |
+//! | GET    | `/sessions`             | `?user_id=` plus [`SessionQuery`] filters, sort and page   |
+//! | POST   | `/recall`               | a [`QueryFilter`]                                           |
+//! | POST   | `/decay`                | -                                                           |
+//! | POST   | `/compact`              | -                                                           |
+//! | GET    | `/stats`                | -                                                           |
+//! | GET    | `/metrics`              | - (Prometheus text format)                                  |
+//! | GET    | `/health`               | -                                                           |
+//!
+//! Errors are returned as `{"error": "..."}` with a 4xx or 5xx status; a
+//! save refused by the user's quota gets 507, a call over the user's rate
+//! limit 429, content over `max_content_bytes` 413 and a save into an
+//! unknown session under the strict session policy 404.
+//!
+//! With `api_keys` configured, every route but `/health` needs a key in an
+//! `Authorization: Bearer` or `x-api-key` header: reading and writing a
+//! user's memories and sessions need the read or write scope for that user,
+//! and deleting a user, decay, compaction, stats and metrics need the admin
+//! scope. A missing or unknown key gets 401 and a key that does not allow
+//! the call 403 (see [`crate::auth`]).
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+
+use axum::extract::{Path, Query, State};
+use chrono::{DateTime, Utc};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{delete, get, post};
+use axum::{Json, Router};
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::{AuthError, ContentTooLarge, MindCache, QueryFilter, QuotaExceeded, RateLimited, Scope, SessionQuery, SessionSort, TimeBucket, UnknownSession};
+
+/// Response header of `GET /sessions` holding how many sessions matched
+pub const TOTAL_COUNT_HEADER: &str = "x-total-count";
+
+/// Cache shared between request handlers
+pub type SharedCache = Arc<Mutex<MindCache>>;
+
+/// Error response carrying an HTTP status
+#[derive(Debug)]
+pub struct ApiError {
+    status: StatusCode,
+    message: String,
+}
+
+impl ApiError {
+    pub fn bad_request(message: impl Into<String>) -> Self {
+        ApiError { status: StatusCode::BAD_REQUEST, message: message.into() }
+    }
+
+    pub fn internal(message: impl Into<String>) -> Self {
+        ApiError { status: StatusCode::INTERNAL_SERVER_ERROR, message: message.into() }
+    }
+}
+
+impl From<AuthError> for ApiError {
+    fn from(error: AuthError) -> Self {
+        let status = match error {
+            AuthError::MissingKey | AuthError::UnknownKey => StatusCode::UNAUTHORIZED,
+            AuthError::Forbidden(_) => StatusCode::FORBIDDEN,
+        };
+        ApiError { status, message: error.to_string() }
+    }
+}
+
+impl From<Box<dyn std::error::Error>> for ApiError {
+    fn from(error: Box<dyn std::error::Error>) -> Self {
+        if let Some(auth) = error.downcast_ref::<AuthError>() {
+            return auth.clone().into();
+        }
+        if error.is::<QuotaExceeded>() {
+            return ApiError { status: StatusCode::INSUFFICIENT_STORAGE, message: error.to_string() };
+        }
+        if error.is::<RateLimited>() {
+            return ApiError { status: StatusCode::TOO_MANY_REQUESTS, message: error.to_string() };
+        }
+        if error.is::<ContentTooLarge>() {
+            return ApiError { status: StatusCode::PAYLOAD_TOO_LARGE, message: error.to_string() };
+        }
+        if error.is::<UnknownSession>() {
+            return ApiError { status: StatusCode::NOT_FOUND, message: error.to_string() };
+        }
+        ApiError::internal(error.to_string())
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.status, Json(json!({ "error": self.message }))).into_response()
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SaveMemoryRequest {
+    pub session_id: String,
+    pub content: String,
+    #[serde(default)]
+    pub metadata: Option<HashMap<String, String>>,
+    #[serde(default)]
+    pub importance: Option<f32>,
+    #[serde(default)]
+    pub ttl_hours: Option<u32>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ListMemoriesParams {
+    pub query: Option<String>,
+    pub session_id: Option<String>,
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct HistogramParams {
+    pub bucket: Option<TimeBucket>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateSessionRequest {
+    pub user_id: String,
+    pub name: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ListSessionsParams {
+    pub user_id: String,
+    pub name: Option<String>,
+    pub tag: Option<String>,
+    pub active_from: Option<DateTime<Utc>>,
+    pub active_to: Option<DateTime<Utc>>,
+    pub min_memories: Option<usize>,
+    pub sort: Option<SessionSort>,
+    #[serde(default)]
+    pub reverse: bool,
+    #[serde(default)]
+    pub offset: usize,
+    pub limit: Option<usize>,
+}
+
+/// Build the API routes around a shared cache
+pub fn router(cache: SharedCache) -> Router {
+    Router::new()
+        .route("/users/{id}", delete(delete_user))
+        .route("/users/{id}/memories", post(save_memory).get(list_memories))
+        .route("/users/{id}/histogram", get(histogram))
+        .route("/sessions", post(create_session).get(list_sessions))
+        .route("/recall", post(recall))
+        .route("/decay", post(decay))
+        .route("/compact", post(compact))
+        .route("/stats", get(stats))
+        .route("/metrics", get(metrics))
+        .route("/health", get(health))
+        .with_state(cache)
+}
+
+/// Serve the API on `listener` until `shutdown` completes
+pub async fn serve<F>(listener: tokio::net::TcpListener, cache: SharedCache, shutdown: F) -> std::io::Result<()>
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    axum::serve(listener, router(cache))
+        .with_graceful_shutdown(shutdown)
+        .await
+}
+
+/// The API key sent as `Authorization: Bearer <key>` or in `x-api-key`
+fn api_key(headers: &HeaderMap) -> Option<String> {
+    let header = |name| headers.get(name).and_then(|value: &header::HeaderValue| value.to_str().ok());
+    header(header::AUTHORIZATION.as_str())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .or_else(|| header("x-api-key"))
+        .map(str::to_string)
+}
+
+/// Run a cache operation on the blocking pool, since storage does synchronous IO
+async fn with_cache<T, F>(cache: &SharedCache, operation: F) -> Result<T, ApiError>
+where
+    T: Send + 'static,
+    F: FnOnce(&mut MindCache) -> Result<T, ApiError> + Send + 'static,
+{
+    let cache = Arc::clone(cache);
+    tokio::task::spawn_blocking(move || {
+        let mut cache = cache.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        operation(&mut cache)
+    })
+    .await
+    .map_err(|e| ApiError::internal(format!("request task failed: {}", e)))?
+}
+
+async fn save_memory(
+    State(cache): State<SharedCache>,
+    Path(user_id): Path<String>,
+    headers: HeaderMap,
+    Json(request): Json<SaveMemoryRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    if request.content.trim().is_empty() {
+        return Err(ApiError::bad_request("content must not be empty"));
+    }
+    if let Some(importance) = request.importance {
+        if !(0.0..=1.0).contains(&importance) {
+            return Err(ApiError::bad_request("importance must be between 0.0 and 1.0"));
+        }
+    }
+
+    let key = api_key(&headers);
+    let id = with_cache(&cache, move |cache| {
+        let importance = request.importance.unwrap_or_else(|| {
+            cache.default_importance_for(&user_id, &request.session_id, &request.content)
+        });
+        let ttl_hours = request.ttl_hours.or(cache.config().default_memory_ttl_hours);
+        Ok(cache.with_key(key.as_deref())?.save_with_options(
+            &user_id,
+            &request.session_id,
+            &request.content,
+            request.metadata,
+            importance,
+            ttl_hours,
+        )?)
+    })
+    .await?;
+
+    Ok((StatusCode::CREATED, Json(json!({ "id": id }))))
+}
+
+async fn list_memories(
+    State(cache): State<SharedCache>,
+    Path(user_id): Path<String>,
+    Query(params): Query<ListMemoriesParams>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, ApiError> {
+    let key = api_key(&headers);
+    let memories = with_cache(&cache, move |cache| {
+        Ok(cache.with_key(key.as_deref())?.recall(&user_id, params.query.as_deref(), params.session_id.as_deref(), params.limit)?)
+    })
+    .await?;
+    Ok(Json(memories))
+}
+
+async fn delete_user(
+    State(cache): State<SharedCache>,
+    Path(user_id): Path<String>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, ApiError> {
+    let key = api_key(&headers);
+    let report = with_cache(&cache, move |cache| {
+        Ok(cache.with_key(key.as_deref())?.delete_user(&user_id)?)
+    })
+    .await?;
+    Ok(Json(report))
+}
+
+async fn histogram(
+    State(cache): State<SharedCache>,
+    Path(user_id): Path<String>,
+    Query(params): Query<HistogramParams>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, ApiError> {
+    let bucket = params.bucket.unwrap_or(TimeBucket::Day);
+    let key = api_key(&headers);
+    let histogram = with_cache(&cache, move |cache| {
+        let mut scoped = cache.with_key(key.as_deref())?;
+        Ok(scoped.authorize(Scope::Read, Some(&user_id))?.memory_histogram(&user_id, bucket))
+    })
+    .await?;
+    Ok(Json(histogram))
+}
+
+async fn create_session(
+    State(cache): State<SharedCache>,
+    headers: HeaderMap,
+    Json(request): Json<CreateSessionRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let key = api_key(&headers);
+    let session_id = with_cache(&cache, move |cache| {
+        let mut scoped = cache.with_key(key.as_deref())?;
+        Ok(scoped.authorize(Scope::Write, Some(&request.user_id))?.create_session(&request.user_id, request.name.as_deref())?)
+    })
+    .await?;
+    Ok((StatusCode::CREATED, Json(json!({ "session_id": session_id }))))
+}
+
+async fn list_sessions(
+    State(cache): State<SharedCache>,
+    Query(params): Query<ListSessionsParams>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, ApiError> {
+    let key = api_key(&headers);
+    let page = with_cache(&cache, move |cache| {
+        let mut scoped = cache.with_key(key.as_deref())?;
+        let cache = scoped.authorize(Scope::Read, Some(&params.user_id))?;
+        let query = SessionQuery {
+            name_contains: params.name,
+            tag: params.tag,
+            active_from: params.active_from,
+            active_to: params.active_to,
+            min_memories: params.min_memories,
+            sort: params.sort.unwrap_or_default(),
+            reverse: params.reverse,
+            offset: params.offset,
+            limit: params.limit,
+        };
+        Ok(cache.list_sessions(&params.user_id, &query))
+    })
+    .await?;
+    // The body is the page; the count of every match goes in a header
+    Ok(([(TOTAL_COUNT_HEADER, page.total.to_string())], Json(page.sessions)))
+}
+
+async fn recall(
+    State(cache): State<SharedCache>,
+    headers: HeaderMap,
+    Json(filter): Json<QueryFilter>,
+) -> Result<impl IntoResponse, ApiError> {
+    let key = api_key(&headers);
+    let memories = with_cache(&cache, move |cache| {
+        Ok(cache.with_key(key.as_deref())?.recall_advanced(filter)?)
+    })
+    .await?;
+    Ok(Json(memories))
+}
+
+async fn decay(State(cache): State<SharedCache>, headers: HeaderMap) -> Result<impl IntoResponse, ApiError> {
+    let key = api_key(&headers);
+    let stats = with_cache(&cache, move |cache| {
+        Ok(cache.with_key(key.as_deref())?.decay()?)
+    })
+    .await?;
+    Ok(Json(stats))
+}
+
+async fn compact(State(cache): State<SharedCache>, headers: HeaderMap) -> Result<impl IntoResponse, ApiError> {
+    let key = api_key(&headers);
+    let report = with_cache(&cache, move |cache| {
+        Ok(cache.with_key(key.as_deref())?.compact()?)
+    })
+    .await?;
+    Ok(Json(report))
+}
+
+async fn stats(State(cache): State<SharedCache>, headers: HeaderMap) -> Result<impl IntoResponse, ApiError> {
+    let key = api_key(&headers);
+    let stats = with_cache(&cache, move |cache| {
+        let mut scoped = cache.with_key(key.as_deref())?;
+        Ok(scoped.authorize(Scope::Admin, None)?.get_stats()?)
+    })
+    .await?;
+    Ok(Json(stats))
+}
+
+async fn metrics(State(cache): State<SharedCache>, headers: HeaderMap) -> Result<impl IntoResponse, ApiError> {
+    let key = api_key(&headers);
+    let snapshot = with_cache(&cache, move |cache| {
+        let mut scoped = cache.with_key(key.as_deref())?;
+        Ok(scoped.authorize(Scope::Admin, None)?.metrics()?)
+    })
+    .await?;
+    Ok(([(header::CONTENT_TYPE, "text/plain; version=0.0.4")], snapshot.to_prometheus()))
+}
+
+async fn health() -> impl IntoResponse {
+    Json(json!({ "status": "ok" }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MindCacheConfig;
+    use crate::test_support::test_config;
+    use axum::body::Body;
+    use axum::http::Request;
+    use serde_json::Value;
+    use tempfile::TempDir;
+    use tower::ServiceExt;
+
+    fn test_router(temp_dir: &TempDir) -> Router {
+        let cache = MindCache::with_config(test_config(temp_dir)).unwrap();
+        router(Arc::new(Mutex::new(cache)))
+    }
+
+    async fn call(app: &Router, method: &str, uri: &str, body: Option<Value>) -> (StatusCode, Value) {
+        call_with_key(app, None, method, uri, body).await
+    }
+
+    async fn call_with_key(app: &Router, key: Option<&str>, method: &str, uri: &str, body: Option<Value>) -> (StatusCode, Value) {
+        let mut request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header("content-type", "application/json");
+        if let Some(key) = key {
+            request = request.header("authorization", format!("Bearer {}", key));
+        }
+        let request = request
+            .body(body.map_or_else(Body::empty, |b| Body::from(b.to_string())))
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap_or(Value::Null))
+    }
+
+    #[tokio::test]
+    async fn test_memory_and_session_endpoints() {
+        let temp_dir = TempDir::new().unwrap();
+        let app = test_router(&temp_dir);
+
+        let (status, body) = call(&app, "POST", "/sessions", Some(json!({ "user_id": "alice", "name": "Trading" }))).await;
+        assert_eq!(status, StatusCode::CREATED);
+        let session_id = body["session_id"].as_str().unwrap().to_string();
+
+        let (status, body) = call(&app, "POST", "/users/alice/memories", Some(json!({
+            "session_id": session_id,
+            "content": "Bought AAPL at 175",
+            "importance": 0.9
+        }))).await;
+        assert_eq!(status, StatusCode::CREATED);
+        assert!(body["id"].is_string());
+
+        let (status, body) = call(&app, "GET", "/users/alice/memories?query=AAPL", None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body.as_array().unwrap().len(), 1);
+
+        let (_, body) = call(&app, "POST", "/recall", Some(json!({ "user_id": "alice", "min_importance": 0.95 }))).await;
+        assert!(body.as_array().unwrap().is_empty());
+
+        let (status, body) = call(&app, "GET", "/sessions?user_id=alice", None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body.as_array().unwrap().len(), 1);
+        let (_, body) = call(&app, "GET", "/sessions?user_id=alice&name=trad&sort=name&limit=5", None).await;
+        assert_eq!(body[0]["name"], "Trading");
+        let (_, body) = call(&app, "GET", "/sessions?user_id=alice&min_memories=5", None).await;
+        assert!(body.as_array().unwrap().is_empty());
+
+        let (status, body) = call(&app, "GET", "/users/alice/histogram?bucket=week", None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body[0]["count"], 1);
+
+        let (status, body) = call(&app, "GET", "/stats", None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["storage"]["alice"], 1);
+
+        let (status, _) = call(&app, "POST", "/decay", None).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let request = Request::builder().uri("/metrics").body(Body::empty()).unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let text = String::from_utf8(bytes.to_vec()).unwrap();
+        assert!(text.contains("mindcache_saves_total 1\n"), "{}", text);
+        assert!(text.contains("mindcache_decay_runs_total 1\n"), "{}", text);
+        assert!(text.contains("mindcache_memories 1\n"), "{}", text);
+
+        let (status, body) = call(&app, "POST", "/compact", None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["records_kept"], 1);
+
+        let (status, body) = call(&app, "DELETE", "/users/alice", None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["memories_deleted"], 1);
+        let (_, body) = call(&app, "GET", "/users/alice/memories", None).await;
+        assert!(body.as_array().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_api_keys_are_enforced() {
+        let temp_dir = TempDir::new().unwrap();
+        let cache = MindCache::with_config(MindCacheConfig {
+            api_keys: serde_json::from_value(json!([
+                { "key": "alice-rw", "users": ["alice"], "scopes": ["read", "write"] },
+                { "key": "ops", "users": ["*"], "scopes": ["admin"] },
+            ])).unwrap(),
+            ..test_config(&temp_dir)
+        })
+        .unwrap();
+        let app = router(Arc::new(Mutex::new(cache)));
+        let memory = json!({ "session_id": "s1", "content": "Bought AAPL" });
+
+        let (status, _) = call(&app, "POST", "/users/alice/memories", Some(memory.clone())).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let (status, _) = call_with_key(&app, Some("wrong"), "GET", "/users/alice/memories", None).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let (status, _) = call_with_key(&app, Some("alice-rw"), "POST", "/users/alice/memories", Some(memory.clone())).await;
+        assert_eq!(status, StatusCode::CREATED);
+        let (status, _) = call_with_key(&app, Some("alice-rw"), "POST", "/users/bob/memories", Some(memory)).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        let (status, body) = call_with_key(&app, Some("alice-rw"), "POST", "/recall", Some(json!({ "user_id": "alice" }))).await;
+        assert_eq!((status, body.as_array().unwrap().len()), (StatusCode::OK, 1));
+        let (status, _) = call_with_key(&app, Some("alice-rw"), "POST", "/recall", Some(json!({}))).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        let (status, _) = call_with_key(&app, Some("alice-rw"), "POST", "/decay", None).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        let (status, _) = call_with_key(&app, Some("ops"), "POST", "/decay", None).await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, _) = call(&app, "GET", "/health", None).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_invalid_requests_are_rejected() {
+        let temp_dir = TempDir::new().unwrap();
+        let app = test_router(&temp_dir);
+
+        let (status, body) = call(&app, "POST", "/users/alice/memories", Some(json!({
+            "session_id": "s1",
+            "content": "   "
+        }))).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(body["error"].is_string());
+
+        let (status, _) = call(&app, "GET", "/sessions", None).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let quota = ApiError::from(Box::new(QuotaExceeded { user_id: "alice".to_string(), limit: 1 }) as Box<dyn std::error::Error>);
+        assert_eq!(quota.status, StatusCode::INSUFFICIENT_STORAGE);
+    }
+}
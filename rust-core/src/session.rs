@@ -1,7 +1,63 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use crate::storage::{MemoryStorage, MemoryItem, QueryFilter};
+use crate::catalog::SessionRecord;
+use crate::audit::AuditAction;
+use crate::storage::{MemoryStorage, MemoryItem, MemoryType, QueryFilter};
+use crate::events::MemoryEvent;
+use crate::extraction::{self, NAMES_KEY, TICKERS_KEY};
+use crate::matching::MatchMode;
+use crate::provenance::{DerivationMethod, ProvenanceRecord};
+use crate::retention::SessionRetention;
+use crate::stats::SessionStats;
+use crate::stopwords::StopWords;
+use crate::text;
+use crate::summarizer::{self, ExtractiveSummarizer, Summarizer, SummaryKind, SummaryRequest};
+
+/// Session that generated session summaries are saved under, as
+/// [`MemoryType::Summary`] memories, so they are recallable like any other memory
+pub const SUMMARY_SESSION: &str = "_summaries";
+
+/// Metadata key holding the id of the session a stored summary covers
+pub const SUMMARIZED_SESSION_KEY: &str = "summarized_session";
+
+const TOPICS_KEY: &str = "key_topics";
+const MEMORY_COUNT_KEY: &str = "memory_count";
+const FIRST_MEMORY_KEY: &str = "first_memory_at";
+const LAST_MEMORY_KEY: &str = "last_memory_at";
+
+/// Metadata key set on sessions registered by a save under [`SessionPolicy::AutoCreate`]
+pub const AUTO_CREATED_KEY: &str = "auto_created";
+
+/// What `save` does with a session id that has neither been created nor
+/// holds memories of the user yet
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SessionPolicy {
+    /// Accept it; the session exists only through its memories
+    #[default]
+    Implicit,
+    /// Register the session, as `create_session` would, before saving
+    AutoCreate,
+    /// Fail the save with [`UnknownSession`]
+    Strict,
+}
+
+/// A save into a session that does not exist, refused under [`SessionPolicy::Strict`]
+#[derive(Debug)]
+pub struct UnknownSession {
+    pub user_id: String,
+    pub session_id: String,
+}
+
+impl std::fmt::Display for UnknownSession {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "session {} of user {} does not exist; create it before saving into it", self.session_id, self.user_id)
+    }
+}
+
+impl std::error::Error for UnknownSession {}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Session {
@@ -17,6 +73,9 @@ pub struct Session {
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SessionSummary {
+    /// Provenance id of this summary (one per session, stable across regenerations)
+    #[serde(default)]
+    pub id: String,
     pub session_id: String,
     pub user_id: String,
     pub summary_text: String,
@@ -26,10 +85,90 @@ pub struct SessionSummary {
     pub importance_score: f32,
 }
 
+impl SessionSummary {
+    /// Id of the stored summary of `session_id`
+    pub fn memory_id(session_id: &str) -> String {
+        format!("summary-{}", session_id)
+    }
+
+    /// Read a summary back from its stored memory
+    pub fn from_memory(memory: &MemoryItem) -> Option<SessionSummary> {
+        let metadata = &memory.metadata;
+        let timestamp = |key: &str| {
+            DateTime::parse_from_rfc3339(metadata.get(key)?)
+                .ok()
+                .map(|timestamp| timestamp.with_timezone(&Utc))
+        };
+
+        Some(SessionSummary {
+            id: memory.id.clone(),
+            session_id: metadata.get(SUMMARIZED_SESSION_KEY)?.clone(),
+            user_id: memory.user_id.clone(),
+            summary_text: memory.content.clone(),
+            key_topics: metadata
+                .get(TOPICS_KEY)
+                .filter(|topics| !topics.is_empty())
+                .map(|topics| topics.split(',').map(str::to_string).collect())
+                .unwrap_or_default(),
+            memory_count: metadata.get(MEMORY_COUNT_KEY)?.parse().ok()?,
+            date_range: (timestamp(FIRST_MEMORY_KEY)?, timestamp(LAST_MEMORY_KEY)?),
+            importance_score: memory.importance,
+        })
+    }
+
+    fn to_memory(&self) -> MemoryItem {
+        let metadata = HashMap::from([
+            (SUMMARIZED_SESSION_KEY.to_string(), self.session_id.clone()),
+            (TOPICS_KEY.to_string(), self.key_topics.join(",")),
+            (MEMORY_COUNT_KEY.to_string(), self.memory_count.to_string()),
+            (FIRST_MEMORY_KEY.to_string(), self.date_range.0.to_rfc3339()),
+            (LAST_MEMORY_KEY.to_string(), self.date_range.1.to_rfc3339()),
+        ]);
+
+        MemoryItem {
+            id: self.id.clone(),
+            user_id: self.user_id.clone(),
+            session_id: SUMMARY_SESSION.to_string(),
+            content: self.summary_text.clone(),
+            metadata,
+            // Dated by the session's newest memory, so date filters find it with the session
+            timestamp: self.date_range.1,
+            importance: self.importance_score,
+            memory_type: MemoryType::Summary,
+            ..Default::default()
+        }
+    }
+}
+
+/// A session found by `search_sessions_ranked`, with the memories that matched
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionMatch {
+    pub session: Session,
+    /// Higher is more relevant; comparable between results of one search only
+    pub score: f32,
+    /// The best matching memories, most relevant first
+    pub hits: Vec<MemoryHit>,
+}
+
+/// A memory that matched a session search
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MemoryHit {
+    pub memory_id: String,
+    /// Words around the first matching keyword
+    pub snippet: String,
+    /// The search keywords the memory contains
+    pub matched_keywords: Vec<String>,
+    pub score: f32,
+}
+
+/// Words kept on either side of the matched word in a hit's snippet
+const SNIPPET_CONTEXT_WORDS: usize = 6;
+
 #[derive(Clone)]
 pub struct SessionManager {
     storage: MemoryStorage,
     sessions_cache: HashMap<String, Session>,
+    summarizer: Arc<dyn Summarizer>,
 } 
 
 impl SessionManager {
@@ -38,29 +177,79 @@ impl SessionManager {
         SessionManager {
             storage,
             sessions_cache: HashMap::new(),
+            summarizer: Arc::new(ExtractiveSummarizer),
         }
     }
 
+    /// Replace the summarizer used for session summaries
+    pub fn set_summarizer(&mut self, summarizer: Arc<dyn Summarizer>) {
+        self.summarizer = summarizer;
+    }
+
     /// Create a new session for a user
     pub fn create_session(&mut self, user_id: &str, session_name: Option<String>) -> Result<String, Box<dyn std::error::Error>> {
         let session_id = uuid::Uuid::new_v4().to_string();
-        let now = Utc::now();
-        
+        self.register_session(user_id, &session_id, session_name, HashMap::new())?;
+        println!("Created session {} for user {}", session_id, user_id);
+        Ok(session_id)
+    }
+
+    /// The storage the manager reads sessions from
+    pub(crate) fn storage(&self) -> &MemoryStorage {
+        &self.storage
+    }
+
+    /// Register a session under an id chosen by the caller, recording it in `sessions.json`
+    pub fn register_session(&mut self, user_id: &str, session_id: &str, name: Option<String>, metadata: HashMap<String, String>) -> Result<Session, Box<dyn std::error::Error>> {
+        let now = self.storage.now();
         let session = Session {
-            id: session_id.clone(),
+            id: session_id.to_string(),
             user_id: user_id.to_string(),
-            name: session_name,
+            name,
             created_at: now,
             last_active: now,
             memory_count: 0,
             tags: Vec::new(),
-            metadata: HashMap::new(),
+            metadata,
         };
+        self.storage.sessions().set(session_id, SessionRecord {
+            user_id: session.user_id.clone(),
+            name: session.name.clone(),
+            tags: Vec::new(),
+            metadata: session.metadata.clone(),
+            created_at: now,
+        })?;
+        self.sessions_cache.insert(session_id.to_string(), session.clone());
+        Ok(session)
+    }
 
-        self.sessions_cache.insert(session_id.clone(), session);
-        
-        println!("Created session {} for user {}", session_id, user_id);
-        Ok(session_id)
+    /// Whether `session_id` was created for `user_id` or already holds their memories
+    pub fn has_session(&self, user_id: &str, session_id: &str) -> Result<bool, Box<dyn std::error::Error>> {
+        if self.storage.sessions().get(session_id).is_some_and(|record| record.user_id == user_id) {
+            return Ok(true);
+        }
+        self.storage.exists(QueryFilter {
+            user_id: Some(user_id.to_string()),
+            session_id: Some(session_id.to_string()),
+            ..Default::default()
+        })
+    }
+
+    /// Drop every cached and recorded session of a user, returning their ids
+    pub fn forget_user(&mut self, user_id: &str) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        let mut session_ids: Vec<String> = self.sessions_cache.values()
+            .filter(|session| session.user_id == user_id)
+            .map(|session| session.id.clone())
+            .collect();
+        for session_id in &session_ids {
+            self.sessions_cache.remove(session_id);
+        }
+        for session_id in self.storage.sessions().remove_user(user_id)? {
+            if !session_ids.contains(&session_id) {
+                session_ids.push(session_id);
+            }
+        }
+        Ok(session_ids)
     }
 
     /// Get all sessions for a user
@@ -74,23 +263,25 @@ impl SessionManager {
             date_to: None,
             limit: None,
             min_importance: None,
+            ..Default::default()
         };
 
         let memories = self.storage.recall(filter)?;
         let mut session_map: HashMap<String, Session> = HashMap::new();
 
-        // Build sessions from memories
+        // Build sessions from memories, keeping names, tags and metadata of registered ones
         for memory in memories {
             let session = session_map.entry(memory.session_id.clone()).or_insert_with(|| {
+                let registered = self.storage.sessions().get(&memory.session_id);
                 Session {
                     id: memory.session_id.clone(),
                     user_id: memory.user_id.clone(),
-                    name: None,
+                    name: registered.as_ref().and_then(|record| record.name.clone()),
                     created_at: memory.timestamp,
                     last_active: memory.timestamp,
                     memory_count: 0,
-                    tags: Vec::new(),
-                    metadata: HashMap::new(),
+                    tags: registered.as_ref().map(|record| record.tags.clone()).unwrap_or_default(),
+                    metadata: registered.map(|record| record.metadata).unwrap_or_default(),
                 }
             });
 
@@ -120,7 +311,7 @@ impl SessionManager {
         }
 
         let mut sessions: Vec<Session> = session_map.into_values().collect();
-        sessions.sort_by(|a, b| b.last_active.cmp(&a.last_active));
+        sessions.sort_by_key(|s| std::cmp::Reverse(s.last_active));
 
         println!("Found {} sessions for user {}", sessions.len(), user_id);
         Ok(sessions)
@@ -134,21 +325,22 @@ impl SessionManager {
         }
 
         // Reconstruct from memories
-        let memories = self.storage.get_session_memories("", session_id)?;
+        let memories = self.storage.get_memories_by_session(session_id)?;
         if memories.is_empty() {
             return Ok(None);
         }
 
         let first_memory = &memories[0];
+        let registered = self.storage.sessions().get(session_id);
         let mut session = Session {
             id: session_id.to_string(),
             user_id: first_memory.user_id.clone(),
-            name: None,
-            created_at: memories.iter().map(|m| m.timestamp).min().unwrap_or(Utc::now()),
-            last_active: memories.iter().map(|m| m.timestamp).max().unwrap_or(Utc::now()),
+            name: registered.as_ref().and_then(|record| record.name.clone()),
+            created_at: memories.iter().map(|m| m.timestamp).min().unwrap_or_else(|| self.storage.now()),
+            last_active: memories.iter().map(|m| m.timestamp).max().unwrap_or_else(|| self.storage.now()),
             memory_count: memories.len(),
-            tags: Vec::new(),
-            metadata: HashMap::new(),
+            tags: registered.as_ref().map(|record| record.tags.clone()).unwrap_or_default(),
+            metadata: registered.map(|record| record.metadata).unwrap_or_default(),
         };
 
         // Extract tags from all memories
@@ -169,6 +361,19 @@ impl SessionManager {
 
     /// Update session metadata
     pub fn update_session(&mut self, session_id: &str, name: Option<String>, tags: Option<Vec<String>>) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some(mut record) = self.storage.sessions().get(session_id) {
+            if let Some(name) = &name {
+                record.name = Some(name.clone());
+            }
+            if let Some(tags) = &tags {
+                record.tags = tags.clone();
+            }
+            self.storage.sessions().set(session_id, record)?;
+            if !self.sessions_cache.contains_key(session_id) {
+                println!("Updated session {}", session_id);
+                return Ok(());
+            }
+        }
         if let Some(session) = self.sessions_cache.get_mut(session_id) {
             if let Some(name) = name {
                 session.name = Some(name);
@@ -176,7 +381,7 @@ impl SessionManager {
             if let Some(tags) = tags {
                 session.tags = tags;
             }
-            session.last_active = Utc::now();
+            session.last_active = self.storage.now();
             
             println!("Updated session {}", session_id);
             Ok(())
@@ -185,23 +390,54 @@ impl SessionManager {
         }
     }
 
+    /// Override the TTL, memory limit and importance floor of a session;
+    /// `SessionRetention::default()` goes back to the decay policy
+    pub fn set_session_retention(&mut self, session_id: &str, retention: SessionRetention) -> Result<(), Box<dyn std::error::Error>> {
+        if retention.importance_floor.is_some_and(|floor| !(0.0..=1.0).contains(&floor)) {
+            return Err("importance floor must be between 0.0 and 1.0".into());
+        }
+        self.storage.session_retention().set(session_id, retention)?;
+        let refreshed = self.storage.refresh_expiry(session_id)?;
+        println!("Set retention of session {} to {:?}, changing the expiry of {} memories", session_id, retention, refreshed);
+        Ok(())
+    }
+
     /// Delete a session and all its memories
     pub fn delete_session(&mut self, session_id: &str) -> Result<usize, Box<dyn std::error::Error>> {
         // This is a simplified delete - in production you'd want to properly remove from storage
         // For now, we'll just remove from cache and count would-be-deleted memories
         
-        let memories = self.storage.get_session_memories("", session_id)?;
+        let memories = self.storage.get_memories_by_session(session_id)?;
         let deleted_count = memories.len();
         
         self.sessions_cache.remove(session_id);
+        self.storage.sessions().remove(session_id)?;
+        self.storage.summaries().remove(session_id)?;
+        if let Some(position) = self.storage.position_of(&SessionSummary::memory_id(session_id)) {
+            self.storage.delete_positions(&HashSet::from([position]), AuditAction::Delete)?;
+        }
         
         println!("Deleted session {} with {} memories", session_id, deleted_count);
         Ok(deleted_count)
     }
 
     /// Generate session summary using memory content
+    ///
+    /// The summary is also stored as a memory in [`SUMMARY_SESSION`],
+    /// replacing the session's previous one.
     pub fn generate_session_summary(&mut self, session_id: &str) -> Result<SessionSummary, Box<dyn std::error::Error>> {
-        let memories = self.storage.get_session_memories("", session_id)?;
+        if session_id == SUMMARY_SESSION {
+            return Err("the summary session cannot be summarized".into());
+        }
+        let fingerprint = self.storage.session_fingerprint(session_id);
+        if let Some(summary) = fingerprint.and_then(|fingerprint| self.storage.summaries().get(session_id, fingerprint)) {
+            // Cached before summaries were stored, or its memory was deleted since
+            if self.storage.position_of(&summary.id).is_none() {
+                self.store_summary(&summary)?;
+            }
+            return Ok(summary);
+        }
+        let memories = self.storage.get_memories_by_session(session_id)?;
         
         if memories.is_empty() {
             return Err("No memories found for session".into());
@@ -209,33 +445,13 @@ impl SessionManager {
 
         let user_id = memories[0].user_id.clone();
         
-        // Extract key topics from memory content (simple keyword extraction)
-        let mut topic_counts: HashMap<String, usize> = HashMap::new();
-        let mut all_content = String::new();
-        
-        for memory in &memories {
-            all_content.push_str(&memory.content);
-            all_content.push(' ');
-            
-            // Fix: Create owned string first, then split
-            let content_lower = memory.content.to_lowercase();
-            let words: Vec<&str> = content_lower
-                .split_whitespace()
-                .filter(|w| w.len() > 3 && !is_stop_word(w))
-                .collect();
-                
-            for word in words {
-                *topic_counts.entry(word.to_string()).or_insert(0) += 1;
-            }
-        }
-
-        // Get top topics
-        let mut topics: Vec<(String, usize)> = topic_counts.into_iter().collect();
-        topics.sort_by(|a, b| b.1.cmp(&a.1));
-        let key_topics: Vec<String> = topics.into_iter().take(5).map(|(word, _)| word).collect();
+        let key_topics = key_topics(&memories, 5, &self.storage.stop_words());
 
-        // Generate simple summary (first few sentences + key points)
-        let summary_text = self.create_simple_summary(&memories, &key_topics);
+        let summary_text = summarizer::summarize_or_fallback(self.summarizer.as_ref(), &SummaryRequest {
+            kind: SummaryKind::Session,
+            memories: &memories,
+            key_topics: &key_topics,
+        });
 
         // Calculate importance score (average of memory importance)
         let importance_score = memories.iter()
@@ -250,6 +466,7 @@ impl SessionManager {
         );
 
         let summary = SessionSummary {
+            id: SessionSummary::memory_id(session_id),
             session_id: session_id.to_string(),
             user_id,
             summary_text,
@@ -259,20 +476,51 @@ impl SessionManager {
             importance_score,
         };
 
+        self.storage.provenance().record(ProvenanceRecord {
+            derived_id: summary.id.clone(),
+            derived_from: memories.iter().map(|m| m.id.clone()).collect(),
+            method: DerivationMethod::SessionSummary,
+            created_at: self.storage.now(),
+        })?;
+
+        self.store_summary(&summary)?;
+        if let Some(fingerprint) = fingerprint {
+            // Only costs a regeneration next time
+            if let Err(e) = self.storage.summaries().put(fingerprint, summary.clone()) {
+                println!("Could not cache summary for session {}: {}", session_id, e);
+            }
+        }
         println!("Generated summary for session {} with {} memories", session_id, memories.len());
+        self.storage.events().emit(MemoryEvent::SessionSummarized { summary: summary.clone() });
         Ok(summary)
     }
 
+    /// Save the summary's memory, or replace the one stored before
+    fn store_summary(&mut self, summary: &SessionSummary) -> Result<(), Box<dyn std::error::Error>> {
+        let memory = summary.to_memory();
+        match self.storage.position_of(&summary.id) {
+            Some(position) => {
+                self.storage.replace_at(position, memory)?;
+            }
+            None => {
+                self.storage.save(memory)?;
+            }
+        }
+        Ok(())
+    }
+
     /// Get session statistics
-    pub fn get_session_stats(&self) -> HashMap<String, usize> {
-        let mut stats = HashMap::new();
+    pub fn get_session_stats(&self) -> SessionStats {
+        let mut stats = SessionStats {
+            total_sessions: self.sessions_cache.len(),
+            ..Default::default()
+        };
         
         for session in self.sessions_cache.values() {
-            let user_sessions = stats.entry(session.user_id.clone()).or_insert(0);
+            let user_sessions = stats.per_user.entry(session.user_id.clone()).or_insert(0);
             *user_sessions += 1;
         }
         
-        stats.insert("total_sessions".to_string(), self.sessions_cache.len());
         stats
     }
 
@@ -286,6 +534,7 @@ impl SessionManager {
             date_to: None,
             limit: None,
             min_importance: None,
+            ..Default::default()
         };
 
         let memories = self.storage.recall(filter)?;
@@ -302,61 +551,113 @@ impl SessionManager {
             }
         }
 
-        matching_sessions.sort_by(|a, b| b.last_active.cmp(&a.last_active));
+        matching_sessions.sort_by_key(|s| std::cmp::Reverse(s.last_active));
         Ok(matching_sessions)
     }
 
-    // Private helper methods
-    
-    fn create_simple_summary(&self, memories: &[MemoryItem], key_topics: &[String]) -> String {
-        let total_memories = memories.len();
-        let date_span = if memories.len() > 1 {
-            let start = memories.iter().map(|m| m.timestamp).min().unwrap();
-            let end = memories.iter().map(|m| m.timestamp).max().unwrap();
-            let days = (end - start).num_days();
-            format!(" over {} days", days)
-        } else {
-            String::new()
+    /// Search sessions by content, most relevant first, with up to
+    /// `max_hits` matching memories of each
+    ///
+    /// A memory scores the share of the keywords it contains, weighted by
+    /// its importance (half weight at importance 0). A session scores its
+    /// best memory's score plus a diminishing share of each further match,
+    /// so several good matches beat one slightly better one.
+    pub fn search_sessions_ranked(&mut self, user_id: &str, keywords: Vec<String>, max_hits: usize) -> Result<Vec<SessionMatch>, Box<dyn std::error::Error>> {
+        let lowered: Vec<String> = keywords.iter().map(|keyword| keyword.to_lowercase()).filter(|keyword| !keyword.is_empty()).collect();
+        if lowered.is_empty() {
+            return Ok(Vec::new());
+        }
+        let filter = QueryFilter {
+            user_id: Some(user_id.to_string()),
+            keywords: Some(lowered.clone()),
+            ..Default::default()
         };
 
-        let topics_text = if !key_topics.is_empty() {
-            format!(" Key topics: {}.", key_topics.join(", "))
-        } else {
-            String::new()
-        };
+        let mut hits_by_session: HashMap<String, Vec<MemoryHit>> = HashMap::new();
+        for memory in self.storage.recall(filter)? {
+            let content_lower = memory.content.to_lowercase();
+            let matched_keywords: Vec<String> = lowered
+                .iter()
+                .filter(|keyword| MatchMode::Exact.content_matches(&content_lower, keyword))
+                .cloned()
+                .collect();
+            if matched_keywords.is_empty() {
+                continue;
+            }
+            let coverage = matched_keywords.len() as f32 / lowered.len() as f32;
+            let score = coverage * (0.5 + memory.importance.clamp(0.0, 1.0) / 2.0);
+            hits_by_session.entry(memory.session_id.clone()).or_default().push(MemoryHit {
+                memory_id: memory.id.clone(),
+                snippet: snippet(&memory.content, &matched_keywords),
+                matched_keywords,
+                score,
+            });
+        }
 
-        format!(
-            "Session contains {} memories{}.{} Most recent: \"{}\"",
-            total_memories,
-            date_span,
-            topics_text,
-            memories.first().map(|m| {
-                if m.content.len() > 100 {
-                    format!("{}...", &m.content[..100])
-                } else {
-                    m.content.clone()
-                }
-            }).unwrap_or_default()
-        )
+        let mut results = Vec::new();
+        for (session_id, mut hits) in hits_by_session {
+            hits.sort_by(|a, b| b.score.total_cmp(&a.score).then_with(|| a.memory_id.cmp(&b.memory_id)));
+            let score = hits.iter().enumerate().map(|(rank, hit)| hit.score / (rank + 1) as f32).sum();
+            hits.truncate(max_hits);
+            if let Some(session) = self.get_session(&session_id)? {
+                results.push(SessionMatch { session, score, hits });
+            }
+        }
+        results.sort_by(|a, b| b.score.total_cmp(&a.score).then_with(|| b.session.last_active.cmp(&a.session.last_active)));
+        Ok(results)
     }
- 
 }
 
-fn is_stop_word(word: &str) -> bool {
-    matches!(word, 
-        "the" | "and" | "or" | "but" | "in" | "on" | "at" | "to" | "for" | 
-        "of" | "with" | "by" | "from" | "up" | "about" | "into" | "through" | 
-        "during" | "before" | "after" | "above" | "below" | "between" | "among" |
-        "this" | "that" | "these" | "those" | "i" | "you" | "he" | "she" | "it" |
-        "we" | "they" | "am" | "is" | "are" | "was" | "were" | "be" | "been" |
-        "being" | "have" | "has" | "had" | "do" | "does" | "did" | "will" | "would"
-    )
+/// The words of `content` around the first word containing one of `keywords`
+fn snippet(content: &str, keywords: &[String]) -> String {
+    let words: Vec<&str> = content.split_whitespace().collect();
+    let Some(center) = words.iter().position(|word| {
+        let word = word.to_lowercase();
+        keywords.iter().any(|keyword| word.contains(keyword.as_str()))
+    }) else {
+        // Keywords spanning several words only match the whole content
+        return text::preview(content, SNIPPET_CONTEXT_WORDS * 16);
+    };
+    let start = center.saturating_sub(SNIPPET_CONTEXT_WORDS);
+    let end = (center + SNIPPET_CONTEXT_WORDS + 1).min(words.len());
+    let mut snippet = words[start..end].join(" ");
+    if start > 0 {
+        snippet.insert_str(0, &format!("{} ", text::ELLIPSIS));
+    }
+    if end < words.len() {
+        snippet.push_str(&format!(" {}", text::ELLIPSIS));
+    }
+    snippet
+}
+
+/// Most frequent content words across `memories`, most frequent first
+///
+/// Extracted tickers and names (see [`extraction`]) count once more than
+/// their words alone would.
+pub(crate) fn key_topics(memories: &[MemoryItem], limit: usize, stop_words: &StopWords) -> Vec<String> {
+    let mut topic_counts: HashMap<String, usize> = HashMap::new();
+    for memory in memories {
+        let is_stop_word = stop_words.for_text(&memory.content);
+        let content_lower = memory.content.to_lowercase();
+        for word in content_lower.split_whitespace().filter(|w| w.len() > 3 && !is_stop_word(w)) {
+            *topic_counts.entry(word.to_string()).or_insert(0) += 1;
+        }
+        for entity in [TICKERS_KEY, NAMES_KEY].into_iter().flat_map(|key| extraction::entity_values(memory, key)) {
+            *topic_counts.entry(entity.to_lowercase()).or_insert(0) += 1;
+        }
+    }
+
+    let mut topics: Vec<(String, usize)> = topic_counts.into_iter().collect();
+    topics.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    topics.into_iter().take(limit).map(|(word, _)| word).collect()
 }
 
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::storage::MemoryStorage; 
+    use crate::storage::MemoryStorage;
+    use crate::test_support::test_config;
 
     #[test]
     fn test_session_creation_and_retrieval() {
@@ -376,13 +677,92 @@ mod tests {
 
     #[test]
     fn test_session_summary() {
-        let storage = MemoryStorage::new("./test_summary").unwrap();
-        let _session_manager = SessionManager::new(storage);
-        
-        // This test would need actual memories in storage to work properly
-        // In a real scenario, you'd add memories first then test summary generation
-        
+        let mut storage = MemoryStorage::new("./test_summary").unwrap();
+        let mut session_manager = SessionManager::new(storage.clone());
+
+        for content in ["Bought gold futures today", "Gold rallied on inflation data", "Trimmed gold position"] {
+            storage.save(MemoryItem {
+                id: String::new(),
+                user_id: "test_user".to_string(),
+                session_id: "gold_session".to_string(),
+                content: content.to_string(),
+                metadata: HashMap::new(),
+                timestamp: Utc::now(),
+                ttl_hours: None,
+                importance: 0.6,
+                ..Default::default()
+            }).unwrap();
+        }
+
+        // Saves through one storage handle are visible to the session manager's clone
+        let summary = session_manager.generate_session_summary("gold_session").unwrap();
+        assert_eq!(summary.user_id, "test_user");
+        assert_eq!(summary.memory_count, 3);
+        assert_eq!(summary.key_topics.first().map(String::as_str), Some("gold"));
+
         // Cleanup
         std::fs::remove_dir_all("./test_summary").ok();
     }
+
+    #[test]
+    fn test_session_policies_on_save() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let open = |policy: SessionPolicy| crate::MindCache::with_config(crate::MindCacheConfig {
+            session_policy: policy,
+            ..test_config(&temp_dir)
+        }).unwrap();
+
+        let mut cache = open(SessionPolicy::Strict);
+        let error = cache.save("alice", "made-up", "Gold is up", None).unwrap_err();
+        assert!(error.is::<UnknownSession>());
+        let created = cache.create_session("alice", Some("Trading")).unwrap();
+        cache.save("alice", &created, "Gold is up", None).unwrap();
+        // A session belongs to its user
+        assert!(cache.save("bob", &created, "Bob's note", None).is_err());
+        drop(cache);
+
+        // Sessions that hold memories stay known after a reopen
+        let mut cache = open(SessionPolicy::Strict);
+        cache.save("alice", &created, "Silver is flat", None).unwrap();
+        drop(cache);
+
+        let mut cache = open(SessionPolicy::AutoCreate);
+        cache.save("alice", "chat-42", "Rates are on hold", None).unwrap();
+        let sessions = cache.get_user_sessions("alice").unwrap();
+        let auto = sessions.iter().find(|session| session.id == "chat-42").unwrap();
+        assert_eq!(auto.metadata[AUTO_CREATED_KEY], "true");
+        assert_eq!(auto.memory_count, 1);
+        assert!(sessions.iter().find(|session| session.id == created).unwrap().metadata.is_empty());
+    }
+
+    #[test]
+    fn test_ranked_session_search() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let mut storage = MemoryStorage::new(temp_dir.path().to_str().unwrap()).unwrap();
+        let mut manager = SessionManager::new(storage.clone());
+        let mut save = |session_id: &str, content: &str, importance: f32| {
+            storage.save(MemoryItem {
+                user_id: "alice".to_string(),
+                session_id: session_id.to_string(),
+                content: content.to_string(),
+                importance,
+                ..Default::default()
+            }).unwrap()
+        };
+        save("metals", "Gold broke out above resistance while silver lagged behind the move", 0.9);
+        save("metals", "Gold miners rallied too", 0.5);
+        save("stocks", "Bought AAPL, mentioned gold once", 0.2);
+        save("rates", "The Fed held rates", 0.8);
+
+        let results = manager.search_sessions_ranked("alice", vec!["Gold".to_string(), "silver".to_string()], 1).unwrap();
+        let ids: Vec<&str> = results.iter().map(|result| result.session.id.as_str()).collect();
+        assert_eq!(ids, ["metals", "stocks"]);
+        assert!(results[0].score > results[1].score);
+        assert_eq!(results[0].hits.len(), 1);
+        assert_eq!(results[0].hits[0].matched_keywords, ["gold", "silver"]);
+        assert_eq!(results[0].hits[0].snippet, "Gold broke out above resistance while silver ...");
+        assert_eq!(results[1].hits[0].matched_keywords, ["gold"]);
+
+        assert!(manager.search_sessions_ranked("alice", Vec::new(), 3).unwrap().is_empty());
+    }
 }
\ No newline at end of file
@@ -0,0 +1,111 @@
+//! "More like this" lookup
+//!
+//! `MindCache::find_similar` ranks the other memories of a memory's user by
+//! how many content words they share with it (Jaccard overlap of their
+//! indexed terms, stop words left out). Candidates come from the term index,
+//! so only memories sharing at least one word are read. For similarity by
+//! meaning, query the vector store kept up to date by [`crate::vector_sync`].
+
+use std::collections::HashSet;
+use serde::{Deserialize, Serialize};
+use crate::dedupe::{self, DEFAULT_SIMILARITY_THRESHOLD};
+use crate::planner;
+use crate::stopwords::StopWords;
+use crate::storage::MemoryItem;
+use crate::MindCache;
+
+/// Candidates read per result asked for, ranked by words shared before scoring
+const CANDIDATES_PER_RESULT: usize = 5;
+
+/// A memory related to the one looked up
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SimilarMemory {
+    pub memory: MemoryItem,
+    /// Share of the two memories' content words they have in common, 0.0 to 1.0
+    pub score: f32,
+    /// Close enough to count as a duplicate for `MindCache::dedupe`
+    pub near_duplicate: bool,
+}
+
+pub(crate) fn content_terms(content: &str, stop_words: &StopWords) -> HashSet<String> {
+    planner::index_terms(content).into_iter().filter(|term| !stop_words.contains_any(term)).collect()
+}
+
+pub(crate) fn jaccard(a: &HashSet<String>, b: &HashSet<String>) -> f32 {
+    let union = a.union(b).count();
+    if union == 0 {
+        return 0.0;
+    }
+    a.intersection(b).count() as f32 / union as f32
+}
+
+impl MindCache {
+    /// Up to `top_k` of the same user's memories most like `memory_id`, most similar first
+    ///
+    /// Memories sharing no content word with it are never returned.
+    pub fn find_similar(&self, memory_id: &str, top_k: usize) -> Result<Vec<SimilarMemory>, Box<dyn std::error::Error>> {
+        let memory = self.storage.get_memory(memory_id)?.ok_or_else(|| format!("memory {} not found", memory_id))?;
+        let stop_words = self.storage.stop_words();
+        let terms = content_terms(&memory.content, &stop_words);
+        let fingerprint = dedupe::simhash(&memory.content);
+
+        let candidates = self.storage.sharing_terms(&memory.user_id, &terms, top_k.saturating_mul(CANDIDATES_PER_RESULT).saturating_add(1))?;
+        let mut similar: Vec<SimilarMemory> = candidates
+            .into_iter()
+            .filter(|(candidate, _)| candidate.id != memory.id)
+            .map(|(candidate, _)| SimilarMemory {
+                score: jaccard(&terms, &content_terms(&candidate.content, &stop_words)),
+                near_duplicate: dedupe::similarity(fingerprint, dedupe::simhash(&candidate.content)) >= DEFAULT_SIMILARITY_THRESHOLD,
+                memory: candidate,
+            })
+            .collect();
+        similar.sort_by(|a, b| b.score.total_cmp(&a.score).then_with(|| b.memory.timestamp.cmp(&a.memory.timestamp)));
+        similar.truncate(top_k);
+        Ok(similar)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::temp_cache;
+
+    #[test]
+    fn test_find_similar_ranks_by_shared_words() {
+        let (mut cache, _temp_dir) = temp_cache();
+        let note = cache.save("alice", "s1", "Bought gold bullion at the local dealer", None).unwrap();
+        let copy = cache.save("alice", "s2", "Bought gold bullion at the local dealer today", None).unwrap();
+        let related = cache.save("alice", "s1", "Sold some gold coins", None).unwrap();
+
+        let similar = cache.find_similar(&note, 10).unwrap();
+        let ids: Vec<&str> = similar.iter().map(|s| s.memory.id.as_str()).collect();
+        assert_eq!(ids, [copy.as_str(), related.as_str()]);
+        assert!(similar[0].near_duplicate && !similar[1].near_duplicate);
+        assert!(similar[0].score > similar[1].score && similar[1].score > 0.0);
+    }
+
+    #[test]
+    fn test_find_similar_skips_other_users_and_stop_word_overlap() {
+        let (mut cache, _temp_dir) = temp_cache();
+        let note = cache.save("alice", "s1", "Bought gold bullion at the local dealer", None).unwrap();
+        cache.save("alice", "s1", "The garden needs water", None).unwrap();
+        cache.save("bob", "s1", "Bought gold bullion at the local dealer", None).unwrap();
+
+        assert!(cache.find_similar(&note, 10).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_find_similar_respects_top_k() {
+        let (mut cache, _temp_dir) = temp_cache();
+        let note = cache.save("alice", "s1", "Bought gold bullion", None).unwrap();
+        cache.save("alice", "s1", "Sold gold bullion", None).unwrap();
+        cache.save("alice", "s1", "Sold some gold coins", None).unwrap();
+
+        assert_eq!(cache.find_similar(&note, 1).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_find_similar_of_a_missing_memory_fails() {
+        let (cache, _temp_dir) = temp_cache();
+        assert!(cache.find_similar("missing", 5).is_err());
+    }
+}
@@ -0,0 +1,252 @@
+//! Shared memory spaces
+//!
+//! A space is a memory pool several users share, e.g. a team of agents or a
+//! group chat. Its memories are stored under the space's own user id
+//! (`space:<id>`), so indexing, quotas and decay treat it like any other
+//! user, while each member's private memories stay under their own id.
+//! Members are readers, writers or admins, and only admins change who
+//! belongs. Spaces live in `spaces.json` next to the memory log.
+
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use crate::backend::StorageBackend;
+use crate::clock::SharedClock;
+use crate::storage::{MemoryItem, QueryFilter};
+use crate::MindCache;
+
+pub const SPACES_BLOB: &str = "spaces.json";
+
+/// Start of the user id a space's memories are stored under
+pub const SPACE_USER_PREFIX: &str = "space:";
+
+/// Metadata key naming the member who saved a space memory
+pub const AUTHOR_KEY: &str = "space_author";
+
+/// What a member may do in a space, each role allowing what the ones before it do
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SpaceRole {
+    Reader,
+    Writer,
+    /// Also adds, removes and changes members
+    Admin,
+}
+
+impl SpaceRole {
+    pub fn can_write(self) -> bool {
+        self >= SpaceRole::Writer
+    }
+
+    pub fn can_manage(self) -> bool {
+        self == SpaceRole::Admin
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Space {
+    pub id: String,
+    pub name: String,
+    pub members: HashMap<String, SpaceRole>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl Space {
+    pub fn role_of(&self, user_id: &str) -> Option<SpaceRole> {
+        self.members.get(user_id).copied()
+    }
+
+    /// The user id the space's memories are stored under
+    pub fn user_id(&self) -> String {
+        space_user_id(&self.id)
+    }
+}
+
+/// The user id memories of space `space_id` are stored under
+pub fn space_user_id(space_id: &str) -> String {
+    format!("{}{}", SPACE_USER_PREFIX, space_id)
+}
+
+/// Persistent spaces, keyed by id
+#[derive(Clone)]
+pub struct SpaceTable {
+    backend: Arc<dyn StorageBackend>,
+    clock: SharedClock,
+    spaces: Arc<RwLock<HashMap<String, Space>>>,
+}
+
+impl SpaceTable {
+    pub fn load(backend: Arc<dyn StorageBackend>, clock: SharedClock) -> Result<Self, Box<dyn std::error::Error>> {
+        let spaces = Self::read_spaces(backend.as_ref())?;
+        Ok(SpaceTable { backend, clock, spaces: Arc::new(RwLock::new(spaces)) })
+    }
+
+    /// Re-read the spaces from disk, e.g. after a restore
+    pub fn reload(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let spaces = Self::read_spaces(self.backend.as_ref())?;
+        *self.spaces.write().unwrap_or_else(|poisoned| poisoned.into_inner()) = spaces;
+        Ok(())
+    }
+
+    fn read_spaces(backend: &dyn StorageBackend) -> Result<HashMap<String, Space>, Box<dyn std::error::Error>> {
+        match backend.read_blob(SPACES_BLOB)? {
+            Some(data) => Ok(serde_json::from_slice(&data)?),
+            None => Ok(HashMap::new()),
+        }
+    }
+
+    /// Apply `change` to a copy of the spaces, then persist and keep the copy
+    fn update<T>(
+        &self,
+        change: impl FnOnce(&mut HashMap<String, Space>) -> Result<T, Box<dyn std::error::Error>>,
+    ) -> Result<T, Box<dyn std::error::Error>> {
+        let mut spaces = self.spaces.write().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let mut updated = spaces.clone();
+        let result = change(&mut updated)?;
+        self.backend.write_blob(SPACES_BLOB, &serde_json::to_vec(&updated)?)?;
+        *spaces = updated;
+        Ok(result)
+    }
+
+    /// Create a space with `owner_id` as its only member, an admin
+    pub fn create(&self, name: &str, owner_id: &str) -> Result<Space, Box<dyn std::error::Error>> {
+        let space = Space {
+            id: Uuid::new_v4().to_string(),
+            name: name.to_string(),
+            members: HashMap::from([(owner_id.to_string(), SpaceRole::Admin)]),
+            created_at: self.clock.now(),
+        };
+        self.update(|spaces| {
+            spaces.insert(space.id.clone(), space.clone());
+            Ok(space)
+        })
+    }
+
+    pub fn get(&self, space_id: &str) -> Option<Space> {
+        self.spaces.read().unwrap_or_else(|poisoned| poisoned.into_inner()).get(space_id).cloned()
+    }
+
+    /// Give `user_id` a role in a space, or remove them with `None`
+    ///
+    /// A space always keeps at least one admin.
+    pub fn set_member(&self, space_id: &str, user_id: &str, role: Option<SpaceRole>) -> Result<(), Box<dyn std::error::Error>> {
+        self.update(|spaces| {
+            let space = spaces.get_mut(space_id).ok_or_else(|| format!("no space {}", space_id))?;
+            match role {
+                Some(role) => space.members.insert(user_id.to_string(), role),
+                None => space.members.remove(user_id),
+            };
+            if !space.members.values().any(|role| role.can_manage()) {
+                return Err(format!("space {} would be left without an admin", space_id).into());
+            }
+            Ok(())
+        })
+    }
+
+    /// Spaces `user_id` belongs to, oldest first
+    pub fn spaces_of(&self, user_id: &str) -> Vec<Space> {
+        let spaces = self.spaces.read().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let mut member_of: Vec<Space> = spaces.values().filter(|space| space.members.contains_key(user_id)).cloned().collect();
+        member_of.sort_by_key(|space| space.created_at);
+        member_of
+    }
+}
+
+impl MindCache {
+    /// Create a shared space administered by `owner_id`, returning its id
+    pub fn create_space(&mut self, name: &str, owner_id: &str) -> Result<String, Box<dyn std::error::Error>> {
+        Ok(self.storage.spaces().create(name, owner_id)?.id)
+    }
+
+    pub fn space(&self, space_id: &str) -> Option<Space> {
+        self.storage.spaces().get(space_id)
+    }
+
+    /// Spaces `user_id` is a member of
+    pub fn spaces_for(&self, user_id: &str) -> Vec<Space> {
+        self.storage.spaces().spaces_of(user_id)
+    }
+
+    /// Have admin `admin_id` give `user_id` a role in a space, or remove them with `None`
+    pub fn set_space_member(&mut self, space_id: &str, admin_id: &str, user_id: &str, role: Option<SpaceRole>) -> Result<(), Box<dyn std::error::Error>> {
+        self.space_role(space_id, admin_id, SpaceRole::can_manage, "manage")?;
+        self.storage.spaces().set_member(space_id, user_id, role)
+    }
+
+    /// Save a memory into a space on behalf of member `user_id`, who must be able to write
+    ///
+    /// The member is recorded under [`AUTHOR_KEY`] in the metadata.
+    pub fn save_to_space(&mut self, space_id: &str, user_id: &str, session_id: &str, content: &str,
+                         metadata: Option<HashMap<String, String>>) -> Result<String, Box<dyn std::error::Error>> {
+        self.space_role(space_id, user_id, SpaceRole::can_write, "write to")?;
+        let mut metadata = metadata.unwrap_or_default();
+        metadata.insert(AUTHOR_KEY.to_string(), user_id.to_string());
+        self.save(&space_user_id(space_id), session_id, content, Some(metadata))
+    }
+
+    /// Recall from a space as member `user_id`; the filter's users are replaced by the space
+    pub fn recall_space(&self, space_id: &str, user_id: &str, filter: QueryFilter) -> Result<Vec<MemoryItem>, Box<dyn std::error::Error>> {
+        self.space_role(space_id, user_id, |_| true, "read")?;
+        self.recall_advanced(QueryFilter {
+            user_id: Some(space_user_id(space_id)),
+            user_ids: Vec::new(),
+            ..filter
+        })
+    }
+
+    /// Recall `user_id`'s own memories together with those of every space they belong to
+    pub fn recall_with_spaces(&self, user_id: &str, filter: QueryFilter) -> Result<Vec<MemoryItem>, Box<dyn std::error::Error>> {
+        let mut user_ids = vec![user_id.to_string()];
+        user_ids.extend(self.spaces_for(user_id).iter().map(Space::user_id));
+        self.recall_advanced(QueryFilter { user_id: None, user_ids, ..filter })
+    }
+
+    /// `user_id`'s role in a space, if it allows what `allowed` checks
+    fn space_role(&self, space_id: &str, user_id: &str, allowed: impl Fn(SpaceRole) -> bool, action: &str) -> Result<SpaceRole, Box<dyn std::error::Error>> {
+        let space = self.space(space_id).ok_or_else(|| format!("no space {}", space_id))?;
+        match space.role_of(user_id) {
+            Some(role) if allowed(role) => Ok(role),
+            _ => Err(format!("{} cannot {} space {}", user_id, action, space_id).into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::test_config;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_space_members_share_memories() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = test_config(&temp_dir);
+        let mut cache = MindCache::with_config(config.clone()).unwrap();
+        let desk = cache.create_space("Trading desk", "alice").unwrap();
+        cache.set_space_member(&desk, "alice", "bob", Some(SpaceRole::Writer)).unwrap();
+        cache.set_space_member(&desk, "alice", "carol", Some(SpaceRole::Reader)).unwrap();
+        assert!(cache.set_space_member(&desk, "bob", "dave", Some(SpaceRole::Reader)).is_err());
+        assert!(cache.set_space_member(&desk, "alice", "alice", None).is_err());
+
+        cache.save_to_space(&desk, "bob", "morning", "Desk is long gold into CPI", None).unwrap();
+        assert!(cache.save_to_space(&desk, "carol", "morning", "Carol's note", None).is_err());
+        cache.save("carol", "private", "Carol's own gold thesis", None).unwrap();
+
+        // Spaces survive a reopen
+        drop(cache);
+        let cache = MindCache::with_config(config).unwrap();
+        assert_eq!(cache.spaces_for("carol")[0].role_of("carol"), Some(SpaceRole::Reader));
+        assert!(cache.spaces_for("dave").is_empty());
+
+        let shared = cache.recall_space(&desk, "carol", QueryFilter::default()).unwrap();
+        assert_eq!(shared.len(), 1);
+        assert_eq!(shared[0].metadata[AUTHOR_KEY], "bob");
+        assert!(cache.recall_space(&desk, "dave", QueryFilter::default()).is_err());
+
+        let gold = QueryFilter { keywords: Some(vec!["gold".to_string()]), ..Default::default() };
+        assert_eq!(cache.recall_with_spaces("carol", gold.clone()).unwrap().len(), 2);
+        assert_eq!(cache.recall_with_spaces("dave", gold).unwrap().len(), 0);
+    }
+}
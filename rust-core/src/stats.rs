@@ -0,0 +1,89 @@
+//! Typed statistics returned by `MindCache::get_stats`
+//!
+//! Serializes to the same JSON the C API and the server have always returned
+//! (`storage`, `sessions`, `decay` and `cache`), plus the `index`, `files`
+//! and `disk` sections.
+
+use std::collections::HashMap;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use crate::cache::CacheStats;
+use crate::decay::DecayStats;
+
+/// Storage, session, decay, index, file and disk usage statistics of a store
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MindCacheStats {
+    /// Number of memories per user
+    pub storage: HashMap<String, usize>,
+    pub sessions: SessionStats,
+    /// Result of the last decay run
+    pub decay: DecayStats,
+    pub cache: CacheStats,
+    pub index: IndexStats,
+    pub files: FileStats,
+    pub disk: DiskUsage,
+}
+
+impl MindCacheStats {
+    /// Memories stored across all users
+    pub fn total_memories(&self) -> usize {
+        self.storage.values().sum()
+    }
+}
+
+/// Cached sessions, per user and in total
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SessionStats {
+    pub total_sessions: usize,
+    /// Serialized next to `total_sessions`, one key per user
+    #[serde(flatten)]
+    pub per_user: HashMap<String, usize>,
+}
+
+/// Sizes of the in-memory indices
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct IndexStats {
+    pub users: usize,
+    pub sessions: usize,
+    /// Records referenced by the user index
+    pub records: usize,
+    /// Distinct keyword terms, counted per user
+    pub terms: usize,
+    /// Bytes taken by the users' term filters (see [`crate::bloom`])
+    #[serde(default)]
+    pub filter_bytes: usize,
+}
+
+/// Sizes in bytes of the files that make up the store
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FileStats {
+    /// Memory logs, including space `compact` would reclaim
+    pub log_bytes: u64,
+    /// User and session index files
+    pub index_bytes: u64,
+    /// Segment table, provenance, links, trash, session retention and spaces
+    pub metadata_bytes: u64,
+    pub change_log_bytes: u64,
+    /// Superseded versions kept for as-of recall
+    pub history_bytes: u64,
+    /// Audit log and decay journal
+    pub audit_log_bytes: u64,
+    pub total_bytes: u64,
+}
+
+/// Memory count and time span of one session, read from the storage index
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SessionActivity {
+    pub session_id: String,
+    pub memory_count: usize,
+    pub first_memory_at: DateTime<Utc>,
+    pub last_memory_at: DateTime<Utc>,
+}
+
+/// Bytes on disk taken by live memories, per user and per session
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DiskUsage {
+    pub by_user: HashMap<String, u64>,
+    /// Keyed by user, then session
+    pub by_session: HashMap<String, HashMap<String, u64>>,
+}
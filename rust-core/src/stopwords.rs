@@ -0,0 +1,183 @@
+//! Stop words, per language
+//!
+//! Key topics, compression key points, topic clustering, agent recall
+//! keywords and extracted keywords all skip stop words. Built-in lists cover
+//! English, Spanish, French, German, Portuguese and Italian. An instance
+//! either fixes the language (`stop_word_language`) or detects it per text
+//! from which list matches the most words, falling back to English; words
+//! from `extra_stop_words` are skipped whatever the language.
+
+use std::collections::HashSet;
+use std::sync::Arc;
+use serde::{Deserialize, Serialize};
+
+/// Languages with a built-in stop-word list
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Language {
+    English,
+    Spanish,
+    French,
+    German,
+    Portuguese,
+    Italian,
+}
+
+impl Language {
+    pub const ALL: [Language; 6] = [
+        Language::English,
+        Language::Spanish,
+        Language::French,
+        Language::German,
+        Language::Portuguese,
+        Language::Italian,
+    ];
+
+    /// The built-in stop words of this language, lowercase
+    pub fn stop_words(self) -> &'static [&'static str] {
+        match self {
+            Language::English => ENGLISH,
+            Language::Spanish => SPANISH,
+            Language::French => FRENCH,
+            Language::German => GERMAN,
+            Language::Portuguese => PORTUGUESE,
+            Language::Italian => ITALIAN,
+        }
+    }
+}
+
+const ENGLISH: &[&str] = &[
+    "the", "and", "or", "but", "in", "on", "at", "to", "for", "of", "with", "by", "from", "up", "about", "into",
+    "through", "during", "before", "after", "above", "below", "between", "among", "this", "that", "these", "those",
+    "i", "you", "he", "she", "it", "we", "they", "am", "is", "are", "was", "were", "be", "been", "being", "have",
+    "has", "had", "do", "does", "did", "will", "would",
+];
+
+const SPANISH: &[&str] = &[
+    "el", "la", "los", "las", "un", "una", "unos", "unas", "y", "o", "pero", "de", "del", "en", "con", "por", "para",
+    "que", "es", "son", "fue", "era", "está", "están", "este", "esta", "estos", "estas", "ese", "esa", "yo", "tú",
+    "él", "ella", "nosotros", "ellos", "ellas", "se", "su", "sus", "al", "lo", "como", "más", "muy", "sobre",
+    "entre", "hay", "ser", "tiene", "tienen", "cuando", "donde", "porque", "también", "sin", "hasta", "desde",
+    "todo", "todos",
+];
+
+const FRENCH: &[&str] = &[
+    "le", "la", "les", "un", "une", "des", "et", "ou", "mais", "de", "du", "en", "dans", "avec", "par", "pour",
+    "que", "qui", "est", "sont", "été", "était", "ce", "cette", "ces", "je", "tu", "il", "elle", "nous", "vous",
+    "ils", "elles", "se", "sur", "sous", "pas", "plus", "très", "au", "aux", "son", "sa", "ses", "leur", "leurs",
+    "comme", "avoir", "être", "fait", "aussi", "entre", "chez", "quand", "donc", "parce", "tout", "tous",
+];
+
+const GERMAN: &[&str] = &[
+    "der", "die", "das", "den", "dem", "des", "ein", "eine", "einen", "einem", "einer", "und", "oder", "aber", "in",
+    "im", "mit", "von", "vom", "zu", "zum", "zur", "für", "auf", "aus", "bei", "nach", "über", "unter", "ist",
+    "sind", "war", "waren", "sein", "hat", "haben", "ich", "du", "er", "sie", "es", "wir", "ihr", "nicht", "auch",
+    "noch", "sehr", "wie", "wenn", "dass", "weil", "diese", "dieser", "dieses", "kein", "keine", "schon", "nur",
+    "mehr", "sich", "habe",
+];
+
+const PORTUGUESE: &[&str] = &[
+    "o", "a", "os", "as", "um", "uma", "uns", "umas", "e", "ou", "mas", "de", "do", "da", "dos", "das", "em", "no",
+    "na", "nos", "nas", "com", "por", "para", "que", "é", "são", "foi", "era", "está", "este", "esta", "esse",
+    "essa", "eu", "tu", "ele", "ela", "nós", "eles", "elas", "se", "seu", "sua", "como", "mais", "muito", "sobre",
+    "entre", "tem", "também", "quando", "onde", "porque", "sem", "até", "desde", "todo", "todos", "não",
+];
+
+const ITALIAN: &[&str] = &[
+    "il", "lo", "la", "i", "gli", "le", "un", "uno", "una", "e", "o", "ma", "di", "del", "della", "dei", "delle",
+    "in", "nel", "nella", "con", "per", "che", "è", "sono", "era", "questo", "questa", "quello", "quella", "io",
+    "tu", "lui", "lei", "noi", "voi", "loro", "si", "suo", "sua", "come", "più", "molto", "su", "tra", "fra",
+    "anche", "quando", "dove", "perché", "senza", "fino", "tutto", "tutti", "non", "ha", "hanno", "essere", "avere",
+];
+
+/// The language whose stop words make up most of `text`, if any of them appear
+pub fn detect_language(text: &str) -> Option<Language> {
+    let words: Vec<String> = text
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect();
+    Language::ALL
+        .into_iter()
+        .map(|language| {
+            let list = language.stop_words();
+            (language, words.iter().filter(|word| list.contains(&word.as_str())).count())
+        })
+        .filter(|(_, hits)| *hits > 0)
+        // Earlier languages win ties
+        .rev()
+        .max_by_key(|(_, hits)| *hits)
+        .map(|(language, _)| language)
+}
+
+/// An instance's stop-word settings
+#[derive(Debug, Clone, Default)]
+pub struct StopWords {
+    /// `None` detects the language of each text
+    language: Option<Language>,
+    extra: Arc<HashSet<String>>,
+}
+
+impl StopWords {
+    pub fn new<I, S>(language: Option<Language>, extra: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        StopWords {
+            language,
+            extra: Arc::new(extra.into_iter().map(|word| word.as_ref().to_lowercase()).collect()),
+        }
+    }
+
+    /// The language `text` is taken to be in
+    pub fn language_of(&self, text: &str) -> Language {
+        self.language.or_else(|| detect_language(text)).unwrap_or(Language::English)
+    }
+
+    /// Whether a lowercase `word` is a stop word in `language`
+    pub fn contains(&self, language: Language, word: &str) -> bool {
+        language.stop_words().contains(&word) || self.extra.contains(word)
+    }
+
+    /// Whether a lowercase `word` is a stop word in the configured language,
+    /// or in any language if it is detected per text
+    pub fn contains_any(&self, word: &str) -> bool {
+        match self.language {
+            Some(language) => self.contains(language, word),
+            None => Language::ALL.into_iter().any(|language| self.contains(language, word)),
+        }
+    }
+
+    /// A check for the stop words of `text`'s language
+    pub fn for_text(&self, text: &str) -> impl Fn(&str) -> bool + '_ {
+        let language = self.language_of(text);
+        move |word| self.contains(language, word)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_language_detection_and_extras() {
+        assert_eq!(detect_language("The market was up and the dollar fell"), Some(Language::English));
+        assert_eq!(detect_language("Compré acciones de la empresa porque el precio era bajo"), Some(Language::Spanish));
+        assert_eq!(detect_language("Ich habe die Aktien gekauft, weil der Kurs niedrig war"), Some(Language::German));
+        assert_eq!(detect_language("J'ai vendu les actions dans la matinée avec mon courtier"), Some(Language::French));
+        assert_eq!(detect_language("XAUUSD 2350"), None);
+
+        let auto = StopWords::default();
+        let german = "Ich habe die Aktien gekauft";
+        assert!(auto.for_text(german)("habe"));
+        assert!(!auto.for_text("I have the shares")("habe"));
+        assert_eq!(auto.language_of("XAUUSD 2350"), Language::English);
+
+        let fixed = StopWords::new(Some(Language::French), ["Trading"]);
+        assert_eq!(fixed.language_of(german), Language::French);
+        assert!(fixed.for_text(german)("trading"));
+        assert!(fixed.for_text(german)("dans"));
+        assert!(!fixed.for_text(german)("habe"));
+    }
+}
@@ -1,12 +1,106 @@
-use std::collections::HashMap;
-use std::fs::{File, OpenOptions};
-use std::io::{BufRead, BufReader, BufWriter, Write, Seek, SeekFrom};
-use std::path::Path;
+//! Persistent memory storage
+//!
+//! # On-disk format
+//!
+//! A store directory contains:
+//! - `users/<hash>/memories.bin`: one append-only log per user (a segment),
+//!   where `<hash>` is the FNV-1a hash of the user id in hex. A log is a
+//!   header followed by records, each a checksummed frame around a
+//!   bincode-encoded [`MemoryItem`], as described in [`crate::record`]
+//! - `memories.bin`: the single log of stores written before segments, read
+//!   as segment 0 until [`MemoryStorage::compact`] moves its records out
+//! - `segments.json`: the segment number assigned to each user; see
+//!   [`crate::segments`]
+//! - `index.bin`: one `user_id:pos,pos,...` line per user listing that user's
+//!   records, each position packing a segment number and a byte offset
+//! - `session_index.bin`: one `user_id<TAB>session_id<TAB>pos,pos,...` line per
+//!   session. Optional: stores without it are re-indexed from the log on open
+//! - `index.log`: optional changes to the two indices since they were last
+//!   written, replayed on open; see [`crate::index_log`]. Both indices start
+//!   with an `@<generation>` line tying them to their log
+//! - `sessions.json`: optional JSON map of the sessions created through a
+//!   session manager, with their names, tags and metadata; see [`crate::catalog`]
+//! - `VERSION`: the store's format version; see [`crate::migrations`]
+//! - `migrations/v<version>/`: copies of the store's files taken before it
+//!   was migrated from that version; safe to delete once the upgrade is trusted
+//! - `changes.log`: optional change-data-capture log, one JSON
+//!   [`crate::changes::ChangeRecord`] per line; written only when enabled
+//! - `provenance.json`: optional JSON list of [`ProvenanceRecord`]s for
+//!   summaries and other items derived from stored memories
+//! - `links.json`: optional JSON list of [`crate::links::MemoryLink`]s between
+//!   memories, by id
+//! - `facts.json`: optional JSON list of [`crate::facts::Fact`]s, citing the
+//!   memories they came from by id
+//! - `summaries.json`: optional JSON map of cached session summaries; see
+//!   [`crate::summary_cache`]
+//! - `reviews.json`: optional JSON map of when each user was last reviewed;
+//!   see [`crate::review`]
+//! - `LOCK`: held (advisory lock) by the one writer that has the store open,
+//!   and holding its pid; see [`crate::backend::AccessMode`]
+//! - `*.compact` and `swap.json`: present only while a compaction or restore
+//!   runs (or after it was interrupted); resolved on the next open
+//!
+//! # Compatibility guarantee
+//!
+//! Stores written by any released version of this crate must remain readable
+//! by later versions. This is enforced by `tests/compat_tests.rs`, which opens
+//! checked-in fixture stores from previous versions. Any change to the record
+//! layout or to the serialized shape of `MemoryItem` must keep those tests
+//! passing and add a fixture for the new format.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write as _;
+use std::sync::{Arc, Mutex, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::time::Instant;
 use serde::{Deserialize, Serialize};
 use chrono::{DateTime, Utc};
 use uuid::Uuid;
+use crate::backend::{AccessMode, FileBackend, StorageBackend};
+use crate::cache::{CacheStats, MemoryCache, DEFAULT_CACHE_CAPACITY};
+use crate::tiering::{HotTier, HotTierPolicy};
+use crate::bloom::TermFilter;
+use crate::batching::{ReadConsistency, WriteBuffer};
+use crate::clock::{Clock, SharedClock};
+use crate::index_log::{self, IndexDelta, IndexLog, INDEX_LOG};
+use crate::events::{EventBus, MemoryEvent};
+use crate::provenance::{ProvenanceLog, PROVENANCE_BLOB};
+use crate::links::{LinkGraph, LINKS_BLOB};
+use crate::facts::{FactStore, FACTS_BLOB};
+use crate::summary_cache::{SummaryCache, SUMMARIES_BLOB};
+use crate::review::{ReviewLog, REVIEWS_BLOB};
+use crate::dedupe;
+use crate::pins;
+use crate::diversity;
+use crate::supersession::SUPERSEDES;
+use crate::trash::{Trash, TRASH_BLOB};
+use crate::retention::{RetentionTable, SESSION_RETENTION_BLOB};
+use crate::catalog::{SessionCatalog, SESSIONS_BLOB};
+use crate::spaces::{SpaceTable, SPACES_BLOB};
+use crate::stopwords::StopWords;
+use crate::journal::{DecayJournal, DECAY_JOURNAL};
+use crate::changes::{ChangeKind, ChangeLog, CHANGES_LOG};
+use crate::history::{self, VersionHistory, HISTORY_LOG};
+use crate::audit::{AuditAction, AuditLog, AUDIT_LOG};
+use crate::metrics::{Metrics, StoreGauges};
+use crate::stats::{DiskUsage, FileStats, IndexStats, SessionActivity};
+use crate::versioning::VersionConflict;
+#[cfg(doc)]
+use crate::provenance::ProvenanceRecord;
+use crate::planner::{self, AccessPath, QueryPlan};
+use crate::matching::MatchMode;
+use crate::query::{self, QueryExpr};
+use crate::timeline::RelativeDuration;
+use crate::record;
+use crate::segments::{self, SegmentTable, SwapPlan, LEGACY_LOG, SEGMENTS_BLOB};
+use crate::migrations::{self, VERSION_BLOB};
+
+const INDEX_BLOB: &str = "index.bin";
+const SESSION_INDEX_BLOB: &str = "session_index.bin";
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Suffix for the files a pre-sharding compaction wrote before swapping them in
+const COMPACT_SUFFIX: &str = ".compact";
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct MemoryItem {
     pub id: String,
     pub user_id: String,
@@ -14,51 +108,529 @@ pub struct MemoryItem {
     pub content: String,
     pub metadata: HashMap<String, String>,
     pub timestamp: DateTime<Utc>,
+    /// Hours the memory lives for; `None` leaves expiry to the decay policy's
+    /// maximum age, and [`MemoryItem::TTL_FOREVER`] keeps it whatever the policy
     pub ttl_hours: Option<u32>,
     pub importance: f32, // 0.0 to 1.0 for decay prioritization
+    /// Speaker of a conversation turn: "user", "assistant" or "system"
+    #[serde(default)]
+    pub role: Option<String>,
+    #[serde(default)]
+    pub memory_type: MemoryType,
+    /// Bumped by every update, starting from 0; see `MindCache::update_memory_if_version`
+    #[serde(default)]
+    pub version: u64,
+    /// When the memory's TTL runs out, worked out on save from `ttl_hours`
+    /// or its session's retention TTL; `None` if it has neither
+    #[serde(default)]
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+impl MemoryItem {
+    /// `ttl_hours` of a memory that never expires, neither by its own TTL,
+    /// its session's nor the decay policy's maximum age
+    pub const TTL_FOREVER: u32 = 0;
+
+    /// Whether the memory is exempt from expiry
+    pub fn never_expires(&self) -> bool {
+        self.ttl_hours == Some(Self::TTL_FOREVER)
+    }
+
+    /// When `ttl_hours` runs out, counting from `timestamp`
+    pub fn ttl_expiry(&self) -> Option<DateTime<Utc>> {
+        self.ttl_hours
+            .filter(|&ttl_hours| ttl_hours != Self::TTL_FOREVER)
+            .map(|ttl_hours| self.timestamp + chrono::Duration::hours(ttl_hours as i64))
+    }
+}
+
+/// What kind of information a memory holds
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MemoryType {
+    /// Something noticed or said in passing
+    #[default]
+    Observation,
+    /// A durable fact about the user or the world
+    Fact,
+    /// Something to be done
+    Task,
+    /// A dialogue turn, usually with a `role`
+    Message,
+    /// Generated from other memories, e.g. a digest
+    Summary,
+}
+
+impl std::str::FromStr for MemoryType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "observation" => Ok(MemoryType::Observation),
+            "fact" => Ok(MemoryType::Fact),
+            "task" => Ok(MemoryType::Task),
+            "message" => Ok(MemoryType::Message),
+            "summary" => Ok(MemoryType::Summary),
+            other => Err(format!("unknown memory type '{}' (expected observation, fact, task, message or summary)", other)),
+        }
+    }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Entry of `QueryFilter::user_ids` matching every user
+pub const ALL_USERS: &str = "*";
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct QueryFilter {
     pub user_id: Option<String>,
+    /// Only memories of these users; [`ALL_USERS`] matches anyone, which
+    /// `MindCache` allows only with `allow_wildcard_recall`. Combines with
+    /// `user_id`, and empty means no restriction
+    #[serde(default)]
+    pub user_ids: Vec<String>,
     pub session_id: Option<String>,
+    /// Leave out memories of these sessions
+    #[serde(default)]
+    pub exclude_sessions: Vec<String>,
     pub keywords: Option<Vec<String>>,
+    /// Leave out memories containing any of these, compared like `keywords`
+    #[serde(default)]
+    pub exclude_keywords: Vec<String>,
     pub date_from: Option<DateTime<Utc>>,
     pub date_to: Option<DateTime<Utc>>,
     pub limit: Option<usize>,
     pub min_importance: Option<f32>,
+    /// Matches to skip before `limit` applies, for paging through results
+    #[serde(default)]
+    pub offset: Option<usize>,
+    /// How `keywords` are compared with content
+    #[serde(default)]
+    pub match_mode: MatchMode,
+    /// Boolean search expression, e.g. `"stop loss" AND AAPL NOT crypto`
+    ///
+    /// Applied on top of `keywords`; see [`crate::query`] for the syntax.
+    #[serde(default)]
+    pub query: Option<String>,
+    /// Conditions on metadata keys; all of them must hold
+    #[serde(default)]
+    pub metadata_filters: HashMap<String, MetadataCondition>,
+    /// Only memories from this far back until now, e.g. `"7d"`; combines with `date_from`
+    #[serde(default)]
+    pub within: Option<RelativeDuration>,
+    /// Only conversation turns with this role
+    #[serde(default)]
+    pub role: Option<String>,
+    #[serde(default)]
+    pub memory_type: Option<MemoryType>,
+    /// Match memories as they were at this time, including since updated or
+    /// deleted ones; needs version history (see [`crate::history`])
+    #[serde(default)]
+    pub as_of: Option<DateTime<Utc>>,
+    /// Fields to return, e.g. `["timestamp", "importance"]` for a listing;
+    /// the rest come back empty. `None` returns everything, and `id` is
+    /// always returned.
+    ///
+    /// Leaving out both `content` and `metadata` lets recall skip decoding
+    /// them, unless `keywords`, `exclude_keywords`, `query` or
+    /// `metadata_filters` need them.
+    #[serde(default)]
+    pub fields: Option<Vec<MemoryField>>,
+    /// Only memories whose `expires_at` is at or before this time
+    #[serde(default)]
+    pub expiring_before: Option<DateTime<Utc>>,
+    /// Only memories at most this important; with `min_importance`, a range
+    #[serde(default)]
+    pub max_importance: Option<f32>,
+    /// Only memories with (`true`) or without (`false`) their own TTL;
+    /// a TTL of [`MemoryItem::TTL_FOREVER`] counts as one
+    #[serde(default)]
+    pub has_ttl: Option<bool>,
+    /// Only pinned memories, which decay leaves alone (see [`crate::pins`])
+    #[serde(default)]
+    pub pinned_only: bool,
+    /// Whether to flush pending saves before reading (see [`crate::batching`])
+    #[serde(default)]
+    pub consistency: ReadConsistency,
+    /// Also match memories replaced by newer ones (see [`crate::supersession`])
+    #[serde(default)]
+    pub include_superseded: bool,
+    /// Re-rank matches for variety before paging, from 0.0 (by relevance
+    /// only) to 1.0 (least repetitive first); see [`crate::diversity`]
+    #[serde(default)]
+    pub diversity: Option<f32>,
 }
 
-pub struct MemoryStorage {
-    storage_path: String,
-    index_path: String,
-    memory_index: HashMap<String, Vec<usize>>, // user_id -> file positions
+/// A field of [`MemoryItem`], for projecting recall results
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MemoryField {
+    Id,
+    UserId,
+    SessionId,
+    Content,
+    Metadata,
+    Timestamp,
+    TtlHours,
+    Importance,
+    Role,
+    MemoryType,
+    Version,
+    ExpiresAt,
+}
+
+impl MemoryField {
+    /// `memory` with only its id and `fields` kept
+    pub fn project(memory: MemoryItem, fields: &[MemoryField]) -> MemoryItem {
+        let mut projected = MemoryItem { id: memory.id, ..Default::default() };
+        for field in fields {
+            match field {
+                MemoryField::Id => {}
+                MemoryField::UserId => projected.user_id = memory.user_id.clone(),
+                MemoryField::SessionId => projected.session_id = memory.session_id.clone(),
+                MemoryField::Content => projected.content = memory.content.clone(),
+                MemoryField::Metadata => projected.metadata = memory.metadata.clone(),
+                MemoryField::Timestamp => projected.timestamp = memory.timestamp,
+                MemoryField::TtlHours => projected.ttl_hours = memory.ttl_hours,
+                MemoryField::Importance => projected.importance = memory.importance,
+                MemoryField::Role => projected.role = memory.role.clone(),
+                MemoryField::MemoryType => projected.memory_type = memory.memory_type,
+                MemoryField::Version => projected.version = memory.version,
+                MemoryField::ExpiresAt => projected.expires_at = memory.expires_at,
+            }
+        }
+        projected
+    }
+}
+
+impl QueryFilter {
+    /// Whether `user_ids` holds the [`ALL_USERS`] wildcard
+    pub fn is_wildcard(&self) -> bool {
+        self.user_ids.iter().any(|user_id| user_id == ALL_USERS)
+    }
+
+    /// The users the filter is limited to, `None` if it covers everyone
+    pub fn users(&self) -> Option<Vec<&str>> {
+        let listed = (!self.user_ids.is_empty() && !self.is_wildcard())
+            .then(|| self.user_ids.iter().map(String::as_str).collect::<Vec<&str>>());
+        match (self.user_id.as_deref(), listed) {
+            (Some(user_id), Some(listed)) => Some(listed.into_iter().filter(|listed| *listed == user_id).take(1).collect()),
+            (Some(user_id), None) => Some(vec![user_id]),
+            (None, listed) => listed,
+        }
+    }
+
+    /// Whether the filter covers memories of `user_id`
+    pub fn includes_user(&self, user_id: &str) -> bool {
+        self.user_id.as_deref().is_none_or(|only| only == user_id)
+            && (self.user_ids.is_empty() || self.user_ids.iter().any(|listed| listed == ALL_USERS || listed == user_id))
+    }
+}
+
+/// The parts of a [`QueryFilter`] a [`record::RecordHeader`] can settle
+struct HeaderFilter<'a> {
+    filter: &'a QueryFilter,
+    users: Option<HashSet<u64>>,
+    session: Option<u64>,
+}
+
+impl<'a> HeaderFilter<'a> {
+    fn new(filter: &'a QueryFilter) -> Self {
+        HeaderFilter {
+            filter,
+            users: filter.users().map(|users| users.into_iter().map(record::key_hash).collect()),
+            session: filter.session_id.as_deref().map(record::key_hash),
+        }
+    }
+
+    /// False only for records `matches_filter` would reject
+    fn admits(&self, header: &record::RecordHeader) -> bool {
+        let filter = self.filter;
+        self.users.as_ref().is_none_or(|users| users.contains(&header.user_hash))
+            && self.session.is_none_or(|session| session == header.session_hash)
+            && filter.date_from.is_none_or(|from| header.timestamp >= from)
+            && filter.date_to.is_none_or(|to| header.timestamp <= to)
+            && filter.min_importance.is_none_or(|min| header.importance >= min)
+            && filter.max_importance.is_none_or(|max| header.importance <= max)
+            && filter.has_ttl.is_none_or(|has_ttl| header.ttl_hours.is_some() == has_ttl)
+            && filter.expiring_before.is_none_or(|before| header.expires_at.is_some_and(|at| at <= before))
+    }
+}
+
+/// A condition on one metadata value, e.g. `{"equals": "AAPL"}` or `"exists"`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MetadataCondition {
+    /// The key is present, with any value
+    Exists,
+    /// The value is exactly this
+    Equals(String),
+    /// The value contains this, ignoring case
+    Contains(String),
+}
+
+impl MetadataCondition {
+    pub fn matches(&self, value: Option<&String>) -> bool {
+        match (self, value) {
+            (_, None) => false,
+            (MetadataCondition::Exists, Some(_)) => true,
+            (MetadataCondition::Equals(expected), Some(value)) => value == expected,
+            (MetadataCondition::Contains(part), Some(value)) => value.to_lowercase().contains(&part.to_lowercase()),
+        }
+    }
+}
+
+/// Store files by name, as copied by `MemoryStorage::snapshot_files`
+pub type StoreFiles = Vec<(String, Vec<u8>)>;
+
+/// Outcome of a storage compaction
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CompactionReport {
+    pub records_kept: usize,
+    pub bytes_before: u64,
+    pub bytes_after: u64,
+}
+
+impl CompactionReport {
+    pub fn bytes_reclaimed(&self) -> u64 {
+        self.bytes_before.saturating_sub(self.bytes_after)
+    }
+}
+
+/// Position indices into the memory log
+#[derive(Default)]
+struct StorageIndex {
+    by_user: HashMap<String, Vec<usize>>, // user_id -> file positions
+    by_session: HashMap<(String, String), Vec<usize>>, // (user_id, session_id) -> file positions
+    terms: HashMap<String, HashMap<String, Vec<usize>>>, // user_id -> term -> file positions (in memory only)
+    timestamps: HashMap<usize, DateTime<Utc>>, // file position -> memory timestamp (in memory only)
+    ids: HashMap<String, usize>, // memory id -> file position (in memory only)
+    sizes: HashMap<usize, u64>, // file position -> record size in bytes (in memory only)
+    filters: HashMap<String, TermFilter>, // user_id -> trigrams of their terms (in memory only)
+}
+
+impl StorageIndex {
+    fn total_records(&self) -> usize {
+        self.by_user.values().map(Vec::len).sum()
+    }
+
+    fn remove_empty(&mut self) {
+        self.by_user.retain(|_, positions| !positions.is_empty());
+        self.by_session.retain(|_, positions| !positions.is_empty());
+        for terms in self.terms.values_mut() {
+            terms.retain(|_, positions| !positions.is_empty());
+        }
+        self.terms.retain(|_, terms| !terms.is_empty());
+        self.filters.retain(|user_id, _| self.terms.contains_key(user_id));
+    }
+
+    /// Add a record of `size` bytes to the in-memory term, timestamp, id and size indices
+    fn add_record(&mut self, memory: &MemoryItem, position: usize, size: u64) {
+        let user_terms = self.terms.entry(memory.user_id.clone()).or_default();
+        let filter = self.filters.entry(memory.user_id.clone()).or_default();
+        let mut fits = true;
+        for term in planner::index_terms(&memory.content) {
+            fits &= filter.insert_term(&term);
+            user_terms.entry(term).or_default().push(position);
+        }
+        if !fits {
+            *filter = filter.grown(user_terms.keys());
+        }
+        self.timestamps.insert(position, memory.timestamp);
+        self.ids.insert(memory.id.clone(), position);
+        self.sizes.insert(position, size);
+    }
+
+    /// Bytes on disk taken by the records at `positions`
+    fn bytes_at<'a>(&self, positions: impl IntoIterator<Item = &'a usize>) -> u64 {
+        positions.into_iter().filter_map(|position| self.sizes.get(position)).sum()
+    }
+
+    /// Whether a user's records could match the filter's keywords, going by their term filter
+    fn may_match(&self, user_id: &str, filter: &QueryFilter) -> bool {
+        let (Some(keywords), Some(terms)) = (filter.keywords.as_deref(), self.filters.get(user_id)) else {
+            return true;
+        };
+        keywords.iter().any(|keyword| terms.may_match(&keyword.to_lowercase(), filter.match_mode))
+    }
+
+    /// Postings of every term matching one of the keywords, per user in scope
+    fn keyword_postings<'a>(
+        &'a self,
+        user_id: Option<&'a String>,
+        keywords: &'a [String],
+        match_mode: MatchMode,
+    ) -> impl Iterator<Item = &'a Vec<usize>> + 'a {
+        let lowered: Vec<String> = keywords.iter().map(|k| k.to_lowercase()).collect();
+        self.terms
+            .iter()
+            .filter(move |(user, _)| user_id.is_none_or(|u| u == *user))
+            .flat_map(|(_, user_terms)| user_terms.iter())
+            .filter(move |(term, _)| lowered.iter().any(|k| match_mode.term_matches(term, k)))
+            .map(|(_, positions)| positions)
+    }
+
+    /// Every position of the user in scope
+    fn scope_positions(&self, user_id: Option<&String>) -> HashSet<usize> {
+        match user_id {
+            Some(user_id) => self.by_user.get(user_id).into_iter().flatten().copied().collect(),
+            None => self.by_user.values().flatten().copied().collect(),
+        }
+    }
+
+    /// Positions that may satisfy a boolean query
+    ///
+    /// This is a superset of the matches: phrases are narrowed to records
+    /// containing all of their words and `NOT` cannot narrow at all, so
+    /// candidates are still checked against the content.
+    fn query_positions(&self, user_id: Option<&String>, expr: &QueryExpr, match_mode: MatchMode) -> HashSet<usize> {
+        match expr {
+            QueryExpr::Term(word) if planner::is_indexable_keyword(word) => self
+                .keyword_postings(user_id, std::slice::from_ref(word), match_mode)
+                .flatten()
+                .copied()
+                .collect(),
+            QueryExpr::Term(text) | QueryExpr::Phrase(text) => {
+                let mut words = planner::index_terms(text).into_iter();
+                let Some(first) = words.next() else {
+                    return self.scope_positions(user_id);
+                };
+                let postings = |word: String| -> HashSet<usize> {
+                    self.keyword_postings(user_id, &[word], MatchMode::Exact)
+                        .flatten()
+                        .copied()
+                        .collect()
+                };
+                words.fold(postings(first), |positions, word| &positions & &postings(word))
+            }
+            QueryExpr::And(left, right) => {
+                &self.query_positions(user_id, left, match_mode) & &self.query_positions(user_id, right, match_mode)
+            }
+            QueryExpr::Or(left, right) => {
+                &self.query_positions(user_id, left, match_mode) | &self.query_positions(user_id, right, match_mode)
+            }
+            QueryExpr::Not(_) => self.scope_positions(user_id),
+        }
+    }
+}
+
+/// Iterator returned by [`MemoryStorage::recall_iter`]
+///
+/// Holds a handle to the store, so the store stays open (and locked) until
+/// the iterator is dropped.
+pub struct RecallIter {
+    storage: MemoryStorage,
+    ids: std::vec::IntoIter<String>,
+    fields: Option<Vec<MemoryField>>,
+}
+
+impl RecallIter {
+    /// Matches not yet returned, including any deleted since the recall
+    pub fn remaining(&self) -> usize {
+        self.ids.len()
+    }
 }
 
-impl Clone for MemoryStorage {
-    fn clone(&self) -> Self {
-        // Create a new storage instance with the same paths
-        // This is a simplified clone - in production you might want to share the index
-        MemoryStorage {
-            storage_path: self.storage_path.clone(),
-            index_path: self.index_path.clone(),
-            memory_index: self.memory_index.clone(),
+impl Iterator for RecallIter {
+    type Item = Result<MemoryItem, Box<dyn std::error::Error>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        for id in self.ids.by_ref() {
+            match self.storage.get_memory(&id) {
+                Ok(Some(memory)) => {
+                    return Some(Ok(match &self.fields {
+                        Some(fields) => MemoryField::project(memory, fields),
+                        None => memory,
+                    }))
+                }
+                Ok(None) => continue,
+                Err(e) => return Some(Err(e)),
+            }
         }
+        None
     }
 }
 
+/// Handle to a storage directory
+///
+/// Clones share the same index and cache, so a memory saved through one
+/// handle is immediately visible to the session manager and decay engine.
+#[derive(Clone)]
+pub struct MemoryStorage {
+    backend: Arc<dyn StorageBackend>,
+    index: Arc<RwLock<StorageIndex>>,
+    cache: Arc<Mutex<MemoryCache>>,
+    events: EventBus,
+    provenance: ProvenanceLog,
+    links: LinkGraph,
+    facts: FactStore,
+    summaries: SummaryCache,
+    reviews: ReviewLog,
+    trash: Trash,
+    session_retention: RetentionTable,
+    sessions: SessionCatalog,
+    spaces: SpaceTable,
+    stop_words: Arc<RwLock<StopWords>>,
+    clock: SharedClock,
+    changes: ChangeLog,
+    history: VersionHistory,
+    audit: AuditLog,
+    decay_journal: DecayJournal,
+    segments: SegmentTable,
+    metrics: Arc<Metrics>,
+    write_buffer: Arc<WriteBuffer>,
+    index_log: Arc<IndexLog>,
+}
+
 impl MemoryStorage {
     /// Create new storage instance with specified directory
     pub fn new(storage_dir: &str) -> Result<Self, Box<dyn std::error::Error>> {
-        std::fs::create_dir_all(storage_dir)?;
-        
-        let storage_path = format!("{}/memories.bin", storage_dir);
-        let index_path = format!("{}/index.bin", storage_dir);
-        
+        Self::with_cache_capacity(storage_dir, DEFAULT_CACHE_CAPACITY)
+    }
+
+    /// Create new storage instance with an LRU cache of `cache_capacity` items (0 disables it)
+    pub fn with_cache_capacity(storage_dir: &str, cache_capacity: usize) -> Result<Self, Box<dyn std::error::Error>> {
+        let backend = FileBackend::open(storage_dir, AccessMode::ReadWrite)?;
+        Self::with_backend(Arc::new(backend), cache_capacity)
+    }
+
+    /// Create storage on top of a custom backend
+    pub fn with_backend(backend: Arc<dyn StorageBackend>, cache_capacity: usize) -> Result<Self, Box<dyn std::error::Error>> {
+        // Finish or roll back a compaction or restore interrupted by a crash
+        SwapPlan::recover(backend.as_ref())?;
+        let clock = SharedClock::default();
+        let provenance = ProvenanceLog::load(Arc::clone(&backend))?;
+        let links = LinkGraph::load(Arc::clone(&backend), clock.clone())?;
+        let facts = FactStore::load(Arc::clone(&backend), clock.clone())?;
+        let summaries = SummaryCache::load(Arc::clone(&backend))?;
+        let reviews = ReviewLog::load(Arc::clone(&backend))?;
+        let trash = Trash::load(Arc::clone(&backend))?;
+        let session_retention = RetentionTable::load(Arc::clone(&backend))?;
+        let sessions = SessionCatalog::load(Arc::clone(&backend))?;
+        let spaces = SpaceTable::load(Arc::clone(&backend), clock.clone())?;
+        let segments = SegmentTable::load(Arc::clone(&backend))?;
         let mut storage = MemoryStorage {
-            storage_path,
-            index_path,
-            memory_index: HashMap::new(),
+            backend: Arc::clone(&backend),
+            index: Arc::new(RwLock::new(StorageIndex::default())),
+            cache: Arc::new(Mutex::new(MemoryCache::new(cache_capacity))),
+            events: EventBus::default(),
+            provenance,
+            links,
+            facts,
+            summaries,
+            reviews,
+            trash,
+            session_retention,
+            sessions,
+            spaces,
+            stop_words: Arc::new(RwLock::new(StopWords::default())),
+            changes: ChangeLog::new(Arc::clone(&backend), clock.clone()),
+            history: VersionHistory::new(Arc::clone(&backend)),
+            audit: AuditLog::new(Arc::clone(&backend), clock.clone()),
+            clock,
+            decay_journal: DecayJournal::new(Arc::clone(&backend)),
+            segments,
+            metrics: Arc::new(Metrics::default()),
+            write_buffer: Arc::new(WriteBuffer::default()),
+            index_log: Arc::new(IndexLog::default()),
         };
         
         // Load existing index if available
@@ -69,6 +641,22 @@ impl MemoryStorage {
 
     /// Save a memory item to persistent storage
     pub fn save(&mut self, memory: MemoryItem) -> Result<String, Box<dyn std::error::Error>> {
+        self.save_as(memory, AuditAction::Save)
+    }
+
+    /// Save a memory brought in from an export, audited as an import
+    pub(crate) fn import(&mut self, memory: MemoryItem) -> Result<String, Box<dyn std::error::Error>> {
+        self.save_as(memory, AuditAction::Import)
+    }
+
+    pub(crate) fn save_as(&mut self, memory: MemoryItem, action: AuditAction) -> Result<String, Box<dyn std::error::Error>> {
+        let started = Instant::now();
+        let result = self.save_record(memory, action);
+        self.metrics.record_save(started.elapsed(), result.is_ok());
+        result
+    }
+
+    fn save_record(&mut self, memory: MemoryItem, action: AuditAction) -> Result<String, Box<dyn std::error::Error>> {
         // Generate ID if not provided
         let memory_id = if memory.id.is_empty() {
             Uuid::new_v4().to_string()
@@ -78,71 +666,316 @@ impl MemoryStorage {
 
         let mut memory_with_id = memory;
         memory_with_id.id = memory_id.clone();
+        self.set_expiry(&mut memory_with_id);
 
-        // Serialize memory item
-        let serialized = bincode::serialize(&memory_with_id)?;
-        
-        // Open file for appending
-        let mut file = OpenOptions::new()
-            .create(true)
-            .append(true)
-            .open(&self.storage_path)?;
-        
-        // Get current position before writing
-        let position = file.seek(SeekFrom::End(0))?;
-        
-        // Write length prefix + data
-        let len = serialized.len() as u32;
-        file.write_all(&len.to_le_bytes())?;
-        file.write_all(&serialized)?;
-        file.flush()?;
-        
-        // Update index
-        self.memory_index
-            .entry(memory_with_id.user_id.clone())
-            .or_insert_with(Vec::new)
-            .push(position as usize);
+        let record = Self::encode_record(&memory_with_id)?;
+        let segment = self.segments.segment_for(&memory_with_id.user_id)?;
+        let log = segments::user_log(&memory_with_id.user_id);
+
+        // Hold the index lock across the append so compaction can't swap the log mid-save
+        let mut index = self.write_index();
+        let position = segments::pack(segment, self.append_record(&log, &record)?);
+        let batching = self.write_buffer.policy().is_batching();
+        if !batching {
+            self.backend.flush(&log)?;
+        }
         
-        // Persist index
-        self.save_index()?;
+        self.changes.record(ChangeKind::Saved, &memory_with_id)?;
+
+        let delta = IndexDelta::Add {
+            position,
+            user_id: memory_with_id.user_id.clone(),
+            session_id: memory_with_id.session_id.clone(),
+        };
+        if batching {
+            // Persisted with the rest of its batch
+            index.by_user.entry(memory_with_id.user_id.clone()).or_default().push(position);
+            index.by_session.entry((memory_with_id.user_id.clone(), memory_with_id.session_id.clone())).or_default().push(position);
+            index.add_record(&memory_with_id, position, record.len() as u64);
+            if self.write_buffer.add(&log, delta) {
+                if let Err(e) = self.persist_index_edit(&mut index, Vec::new(), |_| {}) {
+                    // The saves stay pending and are retried with the next batch
+                    println!("Warning: flushing pending saves failed: {}", e);
+                }
+            }
+        } else {
+            // Update and persist index
+            let user_key = memory_with_id.user_id.clone();
+            let session_key = (memory_with_id.user_id.clone(), memory_with_id.session_id.clone());
+
+            index.by_user.entry(user_key.clone()).or_default().push(position);
+            index.by_session.entry(session_key.clone()).or_default().push(position);
+
+            if let Err(e) = self.persist_index_edit(&mut index, vec![delta], |_| {}) {
+                // Keep the in-memory index in step with what is on disk; the
+                // orphaned record in the log is never referenced
+                if let Some(positions) = index.by_user.get_mut(&user_key) {
+                    positions.pop();
+                }
+                if let Some(positions) = index.by_session.get_mut(&session_key) {
+                    positions.pop();
+                }
+                // One of the index files may already have been replaced; try to put it back
+                let _ = self.save_index(&index);
+                let _ = self.changes.record(ChangeKind::Deleted, &memory_with_id);
+                return Err(e);
+            }
+
+            index.add_record(&memory_with_id, position, record.len() as u64);
+        }
+        drop(index);
+
+        // Freshly saved memories are likely to be read back soon
+        self.lock_cache().insert(position, memory_with_id.clone(), self.now());
         
         println!("Memory saved: {} for user {}", memory_id, memory_with_id.user_id);
+        self.audit_all(action, [&memory_with_id]);
+        if self.events.has_observers() {
+            self.events.emit(MemoryEvent::MemorySaved { memory: memory_with_id });
+        }
         Ok(memory_id)
     }
 
     /// Recall memories based on query filters
     pub fn recall(&self, filter: QueryFilter) -> Result<Vec<MemoryItem>, Box<dyn std::error::Error>> {
-        let mut results = Vec::new();
-        
-        // If user_id specified, only search that user's memories
-        let user_ids: Vec<String> = if let Some(user_id) = &filter.user_id {
-            vec![user_id.clone()]
+        let started = Instant::now();
+        let results = self.recall_with(filter, |memory| memory);
+        self.metrics.record_recall(started.elapsed(), results.is_ok());
+        let results = results?;
+        println!("Recalled {} memories", results.len());
+        Ok(results)
+    }
+
+    /// Recall results read one at a time, newest first
+    ///
+    /// Only the matching ids are held; each memory is read when the iterator
+    /// reaches it, and memories deleted in the meantime are skipped.
+    pub fn recall_iter(&self, filter: QueryFilter) -> Result<RecallIter, Box<dyn std::error::Error>> {
+        if filter.as_of.is_some() {
+            return Err("as_of is not supported by recall_iter; use recall".into());
+        }
+        let started = Instant::now();
+        let fields = filter.fields.clone();
+        let ids = self.recall_with(filter, |memory| memory.id);
+        self.metrics.record_recall(started.elapsed(), ids.is_ok());
+        let ids = ids?;
+        Ok(RecallIter { storage: self.clone(), ids: ids.into_iter(), fields })
+    }
+
+    /// Number of memories `recall` would return for a filter, paging included
+    ///
+    /// Filters on only user, session and time are answered from the indexes
+    /// without reading any records; others read the candidates, skipping
+    /// content and metadata unless the filter looks at them.
+    pub fn count(&self, mut filter: QueryFilter) -> Result<usize, Box<dyn std::error::Error>> {
+        self.settle(filter.consistency)?;
+        Self::resolve_within(&mut filter, self.now());
+        if !Self::answered_by_index(&filter) {
+            filter.fields = Some(Vec::new());
+            return Ok(self.recall_with(filter, |_| ())?.len());
+        }
+
+        let index = self.read_index();
+        let mut hidden = self.superseded_positions(&index, &filter);
+        hidden.extend(Self::excluded_session_positions(&index, &filter));
+        let access_path = if filter.session_id.is_some() {
+            AccessPath::SessionIndex
+        } else if filter.users().is_some() {
+            AccessPath::UserIndex
         } else {
-            self.memory_index.keys().cloned().collect()
+            AccessPath::FullScan
         };
+        let matches = Self::candidate_positions(&index, access_path, &filter, None)
+            .into_iter()
+            .filter(|position| !hidden.contains(position))
+            .filter(|position| {
+                index.timestamps.get(position).is_some_and(|timestamp| {
+                    filter.date_from.is_none_or(|date_from| *timestamp >= date_from)
+                        && filter.date_to.is_none_or(|date_to| *timestamp <= date_to)
+                })
+            })
+            .count()
+            .saturating_sub(filter.offset.unwrap_or(0));
+        Ok(filter.limit.map_or(matches, |limit| matches.min(limit)))
+    }
 
-        for user_id in user_ids {
-            if let Some(positions) = self.memory_index.get(&user_id) {
-                for &position in positions {
-                    if let Ok(memory) = self.read_memory_at_position(position) {
-                        if self.matches_filter(&memory, &filter) {
-                            results.push(memory);
-                        }
-                    }
+    /// Whether any memory matches a filter
+    pub fn exists(&self, filter: QueryFilter) -> Result<bool, Box<dyn std::error::Error>> {
+        Ok(self.count(QueryFilter { limit: Some(1), ..filter })? > 0)
+    }
+
+    /// Whether the user, session and timestamp indexes alone decide `filter`
+    fn answered_by_index(filter: &QueryFilter) -> bool {
+        filter.keywords.is_none()
+            && filter.exclude_keywords.is_empty()
+            && filter.query.is_none()
+            && filter.min_importance.is_none()
+            && filter.max_importance.is_none()
+            && filter.has_ttl.is_none()
+            && !filter.pinned_only
+            && filter.metadata_filters.is_empty()
+            && filter.role.is_none()
+            && filter.memory_type.is_none()
+            && filter.as_of.is_none()
+            && filter.expiring_before.is_none()
+    }
+
+    /// Fold a relative `within` into `date_from`
+    fn resolve_within(filter: &mut QueryFilter, now: DateTime<Utc>) {
+        if let Some(within) = filter.within.take() {
+            let cutoff = within.before(now);
+            filter.date_from = Some(filter.date_from.map_or(cutoff, |date_from| date_from.max(cutoff)));
+        }
+    }
+
+    /// Evaluate a filter, keeping `keep(memory)` for each match in recall order
+    fn recall_with<T>(&self, mut filter: QueryFilter, keep: impl Fn(MemoryItem) -> T) -> Result<Vec<T>, Box<dyn std::error::Error>> {
+        self.settle(filter.consistency)?;
+        if let Some(weight) = filter.diversity.take() {
+            return self.recall_diverse(filter, weight, keep);
+        }
+        let mut results = Vec::new();
+
+        Self::resolve_within(&mut filter, self.now());
+        let query = filter.query.as_deref().map(query::parse).transpose()?;
+        let fields = filter.fields.take();
+        let keep = |memory| keep(match &fields {
+            Some(fields) => MemoryField::project(memory, fields),
+            None => memory,
+        });
+        let skim = fields.as_deref().is_some_and(|fields| {
+            !fields.contains(&MemoryField::Content) && !fields.contains(&MemoryField::Metadata)
+        }) && filter.keywords.is_none()
+            && filter.exclude_keywords.is_empty()
+            && query.is_none()
+            && filter.metadata_filters.is_empty()
+            && !filter.pinned_only;
+        if let Some(as_of) = filter.as_of {
+            let hidden = self.superseded_ids(&filter);
+            for memory in self.memories_as_of(filter.users().as_deref(), as_of)? {
+                if !hidden.contains(&memory.id) && self.matches_filter(&memory, &filter, query.as_ref()) {
+                    results.push((memory.timestamp, keep(memory)));
+                }
+            }
+            return Ok(Self::sort_and_page(results, &filter));
+        }
+        let positions: Vec<usize> = {
+            let index = self.read_index();
+            let plan = Self::plan_query(&index, &filter, query.as_ref());
+            let mut hidden = self.superseded_positions(&index, &filter);
+            hidden.extend(Self::excluded_session_positions(&index, &filter));
+            let mut positions = Self::candidate_positions(&index, plan.access_path, &filter, query.as_ref());
+            positions.retain(|position| !hidden.contains(position));
+            positions
+        };
+
+        let prefilter = HeaderFilter::new(&filter);
+        for position in positions {
+            if let Ok(Some(memory)) = self.read_candidate(position, &prefilter, skim) {
+                if self.matches_filter(&memory, &filter, query.as_ref()) {
+                    results.push((memory.timestamp, keep(memory)));
                 }
             }
         }
 
+        Ok(Self::sort_and_page(results, &filter))
+    }
+
+    /// `recall_with` for a filter with `diversity` set: every match is read
+    /// in full and re-ranked, then paged and projected
+    fn recall_diverse<T>(&self, filter: QueryFilter, weight: f32, keep: impl Fn(MemoryItem) -> T) -> Result<Vec<T>, Box<dyn std::error::Error>> {
+        if !(0.0..=1.0).contains(&weight) {
+            return Err("diversity must be between 0.0 and 1.0".into());
+        }
+        let offset = filter.offset.unwrap_or(0);
+        let wanted = filter.limit.map_or(usize::MAX, |limit| offset.saturating_add(limit));
+        let search_terms = diversity::search_terms(&filter)?;
+        let fields = filter.fields.clone();
+        let matches = self.recall_with(QueryFilter { offset: None, limit: None, fields: None, ..filter }, std::convert::identity)?;
+
+        Ok(diversity::rerank(matches, &search_terms, weight, wanted, &self.stop_words())
+            .into_iter()
+            .skip(offset)
+            .map(|memory| keep(match &fields {
+                Some(fields) => MemoryField::project(memory, fields),
+                None => memory,
+            }))
+            .collect())
+    }
+
+    /// Ids of the memories `filter` leaves out for having been superseded
+    fn superseded_ids(&self, filter: &QueryFilter) -> HashSet<String> {
+        if filter.include_superseded {
+            return HashSet::new();
+        }
+        self.links.targets(SUPERSEDES)
+    }
+
+    fn superseded_positions(&self, index: &StorageIndex, filter: &QueryFilter) -> HashSet<usize> {
+        self.superseded_ids(filter).iter().filter_map(|id| index.ids.get(id)).copied().collect()
+    }
+
+    fn excluded_session_positions(index: &StorageIndex, filter: &QueryFilter) -> HashSet<usize> {
+        if filter.exclude_sessions.is_empty() {
+            return HashSet::new();
+        }
+        index
+            .by_session
+            .iter()
+            .filter(|((_, session_id), _)| filter.exclude_sessions.contains(session_id))
+            .flat_map(|(_, positions)| positions.iter().copied())
+            .collect()
+    }
+
+    fn sort_and_page<T>(mut results: Vec<(DateTime<Utc>, T)>, filter: &QueryFilter) -> Vec<T> {
         // Sort by timestamp (newest first)
-        results.sort_by(|a, b| b.timestamp.cmp(&a.timestamp));
+        results.sort_by_key(|(timestamp, _)| std::cmp::Reverse(*timestamp));
         
-        // Apply limit
+        // Apply paging
+        if let Some(offset) = filter.offset {
+            results.drain(..offset.min(results.len()));
+        }
         if let Some(limit) = filter.limit {
             results.truncate(limit);
         }
 
-        println!("Recalled {} memories", results.len());
-        Ok(results)
+        results.into_iter().map(|(_, item)| item).collect()
+    }
+
+    /// Every memory of `users` (or of all users) as it was at `at`
+    ///
+    /// The term index only knows current contents, so this reads all of them.
+    fn memories_as_of(&self, users: Option<&[&str]>, at: DateTime<Utc>) -> Result<Vec<MemoryItem>, Box<dyn std::error::Error>> {
+        let positions: Vec<usize> = {
+            let index = self.read_index();
+            match users {
+                Some(users) => users.iter().filter_map(|user_id| index.by_user.get(*user_id)).flatten().copied().collect(),
+                None => index.by_user.values().flatten().copied().collect(),
+            }
+        };
+        let mut versions = self.history.versions()?;
+
+        let mut memories = Vec::new();
+        for position in positions {
+            let memory = self.read_memory_at_position(position)?;
+            let superseded = versions.remove(&memory.id).unwrap_or_default();
+            memories.extend(history::version_at(&superseded, Some(&memory), at));
+        }
+        // Whatever is left and ends in a delete no longer exists
+        for superseded in versions.values() {
+            if superseded.last().is_some_and(|version| version.deleted) {
+                memories.extend(history::version_at(superseded, None, at));
+            }
+        }
+        Ok(memories)
+    }
+
+    /// Show which access path `recall` would use for a filter, and why
+    ///
+    /// A `query` that does not parse is ignored here; `recall` rejects it.
+    pub fn explain(&self, filter: &QueryFilter) -> QueryPlan {
+        let query = filter.query.as_deref().and_then(|q| query::parse(q).ok());
+        Self::plan_query(&self.read_index(), filter, query.as_ref())
     }
 
     /// Get all memories for a specific session
@@ -155,153 +988,1584 @@ impl MemoryStorage {
             date_to: None,
             limit: None,
             min_importance: None,
+            ..Default::default()
         };
         
         self.recall(filter)
     }
 
+    /// Get all memories for a session by session id alone, whichever user owns it
+    pub fn get_memories_by_session(&self, session_id: &str) -> Result<Vec<MemoryItem>, Box<dyn std::error::Error>> {
+        let filter = QueryFilter {
+            user_id: None,
+            session_id: Some(session_id.to_string()),
+            keywords: None,
+            date_from: None,
+            date_to: None,
+            limit: None,
+            min_importance: None,
+            ..Default::default()
+        };
+
+        self.recall(filter)
+    }
+
+    /// Look up a memory by id
+    pub fn get_memory(&self, id: &str) -> Result<Option<MemoryItem>, Box<dyn std::error::Error>> {
+        let position = self.read_index().ids.get(id).copied();
+        position.map(|position| self.read_memory_at_position(position)).transpose()
+    }
+
+    /// Log position of the memory with this id
+    pub(crate) fn position_of(&self, id: &str) -> Option<usize> {
+        self.read_index().ids.get(id).copied()
+    }
+
+    /// The user's memories sharing the most index terms with `content`, most overlap first
+    pub(crate) fn most_similar(&self, user_id: &str, content: &str, limit: usize) -> Result<Vec<MemoryItem>, Box<dyn std::error::Error>> {
+        let shared = self.sharing_terms(user_id, &planner::index_terms(content), limit)?;
+        Ok(shared.into_iter().map(|(memory, _)| memory).collect())
+    }
+
+    /// The user's memories containing the most of `terms`, with how many each contains
+    pub(crate) fn sharing_terms(&self, user_id: &str, terms: &HashSet<String>, limit: usize) -> Result<Vec<(MemoryItem, usize)>, Box<dyn std::error::Error>> {
+        let mut overlap: HashMap<usize, usize> = HashMap::new();
+        {
+            let index = self.read_index();
+            if let Some(user_terms) = index.terms.get(user_id) {
+                for term in terms {
+                    for &position in user_terms.get(term).into_iter().flatten() {
+                        *overlap.entry(position).or_default() += 1;
+                    }
+                }
+            }
+        }
+
+        let mut ranked: Vec<(usize, usize)> = overlap.into_iter().collect();
+        ranked.sort_by_key(|&(position, count)| (std::cmp::Reverse(count), position));
+        ranked
+            .into_iter()
+            .take(limit)
+            .map(|(position, count)| Ok((self.read_memory_at_position(position)?, count)))
+            .collect()
+    }
+
     /// Get memory statistics
     pub fn get_stats(&self) -> HashMap<String, usize> {
         let mut stats = HashMap::new();
         
-        for (user_id, positions) in &self.memory_index {
+        for (user_id, positions) in &self.read_index().by_user {
             stats.insert(user_id.clone(), positions.len());
         }
         
         stats
     }
 
-    /// Clean up expired memories (called by decay system)
-    pub fn cleanup_expired(&mut self) -> Result<usize, Box<dyn std::error::Error>> {
-        let now = Utc::now();
-        let mut removed_count = 0;
-
-        // This is a simplified cleanup - in production, you'd want to rebuild the file
-        // For now, we'll mark expired items by updating their importance to 0
-        for user_id in self.memory_index.keys().cloned().collect::<Vec<_>>() {
-            if let Some(positions) = self.memory_index.get(&user_id).cloned() {
-                for position in positions {
-                    if let Ok(memory) = self.read_memory_at_position(position) {
-                        if let Some(ttl_hours) = memory.ttl_hours {
-                            let expiry = memory.timestamp + chrono::Duration::hours(ttl_hours as i64);
-                            if now > expiry {
-                                removed_count += 1;
-                                // In a real implementation, mark for deletion
-                            }
-                        }
-                    }
-                }
-            }
-        }
+    /// Timestamps of a user's memories, answered from the index without reading records
+    pub fn memory_timestamps(&self, user_id: &str) -> Vec<DateTime<Utc>> {
+        let index = self.read_index();
+        index.by_user
+            .get(user_id)
+            .into_iter()
+            .flatten()
+            .filter_map(|position| index.timestamps.get(position).copied())
+            .collect()
+    }
 
-        println!("Cleaned up {} expired memories", removed_count);
-        Ok(removed_count)
+    /// Timestamps of a user's memories since `since`, by indexed term, leaving out `skip_sessions`
+    pub(crate) fn term_timestamps(&self, user_id: &str, since: DateTime<Utc>, skip_sessions: &[&str]) -> HashMap<String, Vec<DateTime<Utc>>> {
+        let index = self.read_index();
+        let skipped: HashSet<usize> = skip_sessions
+            .iter()
+            .filter_map(|session_id| index.by_session.get(&(user_id.to_string(), session_id.to_string())))
+            .flatten()
+            .copied()
+            .collect();
+        index.terms
+            .get(user_id)
+            .into_iter()
+            .flatten()
+            .filter_map(|(term, positions)| {
+                let timestamps: Vec<DateTime<Utc>> = positions
+                    .iter()
+                    .filter(|position| !skipped.contains(position))
+                    .filter_map(|position| index.timestamps.get(position).copied())
+                    .filter(|timestamp| *timestamp >= since)
+                    .collect();
+                (!timestamps.is_empty()).then(|| (term.clone(), timestamps))
+            })
+            .collect()
     }
 
-    // Private helper methods
+    /// Get LRU cache hit/miss counters
+    pub fn cache_stats(&self) -> CacheStats {
+        self.lock_cache().stats()
+    }
 
-    fn matches_filter(&self, memory: &MemoryItem, filter: &QueryFilter) -> bool {
-        // User ID filter
-        if let Some(ref user_id) = filter.user_id {
-            if memory.user_id != *user_id {
-                return false;
-            }
-        }
+    /// Stop words for key topics, key points and clustering
+    pub fn stop_words(&self) -> StopWords {
+        self.stop_words.read().unwrap_or_else(|poisoned| poisoned.into_inner()).clone()
+    }
 
-        // Session ID filter
-        if let Some(ref session_id) = filter.session_id {
-            if memory.session_id != *session_id {
-                return false;
-            }
-        }
+    pub fn set_stop_words(&self, stop_words: StopWords) {
+        *self.stop_words.write().unwrap_or_else(|poisoned| poisoned.into_inner()) = stop_words;
+    }
 
-        // Date range filter
-        if let Some(date_from) = filter.date_from {
-            if memory.timestamp < date_from {
-                return false;
-            }
-        }
+    /// The current time by the storage's clock (see [`crate::clock`])
+    pub fn now(&self) -> DateTime<Utc> {
+        self.clock.now()
+    }
 
-        if let Some(date_to) = filter.date_to {
-            if memory.timestamp > date_to {
-                return false;
-            }
-        }
+    pub fn set_clock(&self, clock: Arc<dyn Clock>) {
+        self.clock.set(clock);
+    }
 
-        // Importance filter
-        if let Some(min_importance) = filter.min_importance {
-            if memory.importance < min_importance {
-                return false;
-            }
+    /// Saves not yet persisted (see [`crate::batching`])
+    pub fn write_buffer(&self) -> &WriteBuffer {
+        &self.write_buffer
+    }
+
+    /// Persist pending saves: fsync their logs and write the index
+    pub fn flush(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let mut index = self.write_index();
+        if self.write_buffer.pending_saves() > 0 {
+            self.persist_index_edit(&mut index, Vec::new(), |_| {})?;
         }
+        Ok(())
+    }
 
-        // Keyword filter (simple text search)
-        if let Some(ref keywords) = filter.keywords {
-            let content_lower = memory.content.to_lowercase();
-            let found = keywords.iter().any(|keyword| {
-                content_lower.contains(&keyword.to_lowercase())
-            });
-            if !found {
-                return false;
-            }
+    /// Wait for what `consistency` asks of a read
+    fn settle(&self, consistency: ReadConsistency) -> Result<(), Box<dyn std::error::Error>> {
+        match consistency {
+            ReadConsistency::Buffered => Ok(()),
+            ReadConsistency::Strong => self.flush(),
         }
+    }
 
-        true
+    /// Resize the LRU cache (0 disables it)
+    pub fn set_cache_capacity(&self, capacity: usize) {
+        self.lock_cache().set_capacity(capacity);
     }
 
-    fn read_memory_at_position(&self, position: usize) -> Result<MemoryItem, Box<dyn std::error::Error>> {
-        let mut file = File::open(&self.storage_path)?;
-        file.seek(SeekFrom::Start(position as u64))?;
-        
-        // Read length prefix
-        let mut len_bytes = [0u8; 4];
-        std::io::Read::read_exact(&mut file, &mut len_bytes)?;
-        let len = u32::from_le_bytes(len_bytes);
-        
-        // Read data
-        let mut data = vec![0u8; len as usize];
-        std::io::Read::read_exact(&mut file, &mut data)?;
-        
-        // Deserialize
-        let memory: MemoryItem = bincode::deserialize(&data)?;
+    /// Keep recent and important memories in `tier`, or stop with `None`
+    /// (see [`crate::tiering`])
+    pub fn set_hot_tier(&self, tier: Option<Arc<dyn HotTier>>, policy: HotTierPolicy) {
+        self.lock_cache().set_hot_tier(tier, policy);
+    }
+
+    pub fn set_hot_tier_policy(&self, policy: HotTierPolicy) {
+        self.lock_cache().set_hot_tier_policy(policy);
+    }
+
+    /// Move memories that are no longer recent or important enough out of
+    /// the hot tier; returns how many were moved
+    pub fn demote_hot_tier(&self, now: DateTime<Utc>) -> usize {
+        self.lock_cache().demote(now)
+    }
+
+    /// Operation counters and latencies, shared by every clone of this storage
+    pub fn metrics(&self) -> &Metrics {
+        &self.metrics
+    }
+
+    /// Bytes on disk taken by a user's live memories
+    pub fn user_disk_usage(&self, user_id: &str) -> u64 {
+        let index = self.read_index();
+        index.bytes_at(index.by_user.get(user_id).into_iter().flatten())
+    }
+
+    /// Bytes on disk taken by the live memories of one session
+    pub fn session_disk_usage(&self, user_id: &str, session_id: &str) -> u64 {
+        let index = self.read_index();
+        let key = (user_id.to_string(), session_id.to_string());
+        index.bytes_at(index.by_session.get(&key).into_iter().flatten())
+    }
+
+    /// Memory count and time span of each session of a user, from the index
+    pub fn session_activity(&self, user_id: &str) -> Vec<SessionActivity> {
+        let index = self.read_index();
+        index.by_session
+            .iter()
+            .filter(|((user, _), positions)| user == user_id && !positions.is_empty())
+            .map(|((_, session_id), positions)| {
+                let times: Vec<DateTime<Utc>> = positions.iter().filter_map(|position| index.timestamps.get(position).copied()).collect();
+                SessionActivity {
+                    session_id: session_id.clone(),
+                    memory_count: positions.len(),
+                    first_memory_at: times.iter().min().copied().unwrap_or_else(Utc::now),
+                    last_memory_at: times.iter().max().copied().unwrap_or_else(Utc::now),
+                }
+            })
+            .collect()
+    }
+
+    /// Bytes on disk per user and per session
+    ///
+    /// Counts live records only; space held by deleted or superseded records
+    /// until the next `compact` is not attributed to anyone.
+    pub fn disk_usage(&self) -> DiskUsage {
+        let index = self.read_index();
+        let mut usage = DiskUsage::default();
+        for (user_id, positions) in &index.by_user {
+            usage.by_user.insert(user_id.clone(), index.bytes_at(positions));
+        }
+        for ((user_id, session_id), positions) in &index.by_session {
+            usage.by_session
+                .entry(user_id.clone())
+                .or_default()
+                .insert(session_id.clone(), index.bytes_at(positions));
+        }
+        usage
+    }
+
+    /// Sizes of the in-memory indices
+    pub fn index_stats(&self) -> IndexStats {
+        let index = self.read_index();
+        IndexStats {
+            users: index.by_user.len(),
+            sessions: index.by_session.len(),
+            records: index.total_records(),
+            terms: index.terms.values().map(HashMap::len).sum(),
+            filter_bytes: index.filters.values().map(TermFilter::size).sum(),
+        }
+    }
+
+    /// Sizes of the files that make up the store
+    pub fn file_stats(&self) -> Result<FileStats, Box<dyn std::error::Error>> {
+        let sizes = |names: &[&str]| -> std::io::Result<u64> {
+            names.iter().map(|name| self.backend.size(name)).sum()
+        };
+        let log_bytes = self.total_log_size()?;
+        let index_bytes = sizes(&[INDEX_BLOB, SESSION_INDEX_BLOB, INDEX_LOG])?;
+        let metadata_bytes = sizes(&[SEGMENTS_BLOB, PROVENANCE_BLOB, LINKS_BLOB, FACTS_BLOB, SUMMARIES_BLOB, REVIEWS_BLOB, TRASH_BLOB, SESSION_RETENTION_BLOB, SESSIONS_BLOB, SPACES_BLOB])?;
+        let change_log_bytes = sizes(&[CHANGES_LOG])?;
+        let history_bytes = sizes(&[HISTORY_LOG])?;
+        let audit_log_bytes = sizes(&[AUDIT_LOG, DECAY_JOURNAL])?;
+        Ok(FileStats {
+            log_bytes,
+            index_bytes,
+            metadata_bytes,
+            change_log_bytes,
+            history_bytes,
+            audit_log_bytes,
+            total_bytes: log_bytes + index_bytes + metadata_bytes + change_log_bytes + history_bytes + audit_log_bytes,
+        })
+    }
+
+    /// Memory and user counts, log size and cache counters right now
+    pub fn store_gauges(&self) -> Result<StoreGauges, Box<dyn std::error::Error>> {
+        let (memories, users) = {
+            let index = self.read_index();
+            (index.total_records() as u64, index.by_user.len() as u64)
+        };
+        Ok(StoreGauges {
+            memories,
+            users,
+            storage_bytes: self.total_log_size()?,
+            cache: self.cache_stats(),
+        })
+    }
+
+    /// Lifecycle event observers, shared by every clone of this storage
+    pub fn events(&self) -> &EventBus {
+        &self.events
+    }
+
+    /// Provenance records of derived items, shared by every clone of this storage
+    pub fn provenance(&self) -> &ProvenanceLog {
+        &self.provenance
+    }
+
+    /// Links between memories, shared by every clone of this storage
+    pub fn links(&self) -> &LinkGraph {
+        &self.links
+    }
+
+    /// Facts drawn from memories, shared by every clone of this storage
+    pub fn facts(&self) -> &FactStore {
+        &self.facts
+    }
+
+    /// Cached session summaries, shared by every clone of this storage
+    pub fn summaries(&self) -> &SummaryCache {
+        &self.summaries
+    }
+
+    /// When each user was last reviewed, shared by every clone of this storage
+    pub fn reviews(&self) -> &ReviewLog {
+        &self.reviews
+    }
+
+    /// Fingerprint of the records of a session, whichever users hold them
+    ///
+    /// Changes with every save, update or delete in the session. `None` if
+    /// the session holds no memories.
+    pub(crate) fn session_fingerprint(&self, session_id: &str) -> Option<u64> {
+        let index = self.read_index();
+        let mut lists: Vec<(&String, &Vec<usize>)> = index.by_session
+            .iter()
+            .filter(|((_, session), positions)| session == session_id && !positions.is_empty())
+            .map(|((user_id, _), positions)| (user_id, positions))
+            .collect();
+        if lists.is_empty() {
+            return None;
+        }
+        lists.sort();
+        let mut key = String::new();
+        for (user_id, positions) in lists {
+            let mut positions = positions.clone();
+            positions.sort_unstable();
+            key.push_str(user_id);
+            for position in positions {
+                key.push_str(&format!(",{}", position));
+            }
+            key.push('\n');
+        }
+        Some(dedupe::fnv1a(&key))
+    }
+
+    /// Results of past decay runs
+    pub fn decay_journal(&self) -> &DecayJournal {
+        &self.decay_journal
+    }
+
+    /// Soft-deleted memories
+    pub fn trash(&self) -> &Trash {
+        &self.trash
+    }
+
+    /// Per-session retention overrides
+    pub fn session_retention(&self) -> &RetentionTable {
+        &self.session_retention
+    }
+
+    /// Sessions registered through a session manager
+    pub fn sessions(&self) -> &SessionCatalog {
+        &self.sessions
+    }
+
+    /// Shared memory spaces and their members
+    pub fn spaces(&self) -> &SpaceTable {
+        &self.spaces
+    }
+
+    /// Change-data-capture log (disabled until `ChangeLog::enable` is called)
+    pub fn changes(&self) -> &ChangeLog {
+        &self.changes
+    }
+
+    /// Superseded versions kept for as-of recall (disabled until `VersionHistory::enable` is called)
+    pub fn history(&self) -> &VersionHistory {
+        &self.history
+    }
+
+    /// Audit log of mutations (disabled until `AuditLog::enable` is called)
+    pub fn audit(&self) -> &AuditLog {
+        &self.audit
+    }
+
+    /// Audit a mutation that already took effect, so a failure is only reported
+    pub(crate) fn audit_all<'a>(&self, action: AuditAction, memories: impl IntoIterator<Item = &'a MemoryItem>) {
+        for memory in memories {
+            if let Err(e) = self.audit.record(action, memory) {
+                println!("Failed to audit {:?} of memory {}: {}", action, memory.id, e);
+            }
+        }
+    }
+
+    /// Keep versions that were just updated or deleted
+    ///
+    /// The change itself already took effect, so a failure here is only reported.
+    fn record_superseded<'a>(&self, memories: impl IntoIterator<Item = &'a MemoryItem>, deleted: bool) {
+        for memory in memories {
+            if let Err(e) = self.history.record(memory, deleted, self.now()) {
+                println!("Failed to keep superseded version of memory {}: {}", memory.id, e);
+            }
+        }
+    }
+
+    /// Number of memories a user has, answered from the index
+    pub fn user_memory_count(&self, user_id: &str) -> usize {
+        self.read_index().by_user.get(user_id).map_or(0, Vec::len)
+    }
+
+    /// Memories of one user together with their log positions
+    pub(crate) fn user_records(&self, user_id: &str) -> Result<Vec<(usize, MemoryItem)>, Box<dyn std::error::Error>> {
+        let positions = self.read_index().by_user.get(user_id).cloned().unwrap_or_default();
+        let mut records = Vec::with_capacity(positions.len());
+        for position in positions {
+            records.push((position, self.read_memory_at_position(position)?));
+        }
+        Ok(records)
+    }
+
+    /// Unlink records from every index, auditing it as `action`
+    ///
+    /// The records stay in the log, unreachable, until the next `compact`.
+    pub(crate) fn delete_positions(&self, positions: &HashSet<usize>, action: AuditAction) -> Result<(), Box<dyn std::error::Error>> {
+        let mut index = self.write_index();
+
+        let mut deleted = Vec::new();
+        if self.changes.is_enabled() || self.history.is_enabled() || self.audit.is_enabled() {
+            for &position in positions {
+                let memory = self.read_memory_at_position(position)?;
+                self.changes.record(ChangeKind::Deleted, &memory)?;
+                deleted.push(memory);
+            }
+        }
+
+        let deltas = positions.iter().map(|&position| IndexDelta::Remove { position }).collect();
+        let result = self.persist_index_edit(&mut index, deltas, |index| {
+            let retain = |list: &mut Vec<usize>| list.retain(|p| !positions.contains(p));
+            index.by_user.values_mut().for_each(retain);
+            index.by_session.values_mut().for_each(retain);
+            index.terms.values_mut().flat_map(|terms| terms.values_mut()).for_each(retain);
+            index.timestamps.retain(|p, _| !positions.contains(p));
+            index.ids.retain(|_, p| !positions.contains(p));
+            index.sizes.retain(|p, _| !positions.contains(p));
+            index.remove_empty();
+        });
+        if let Err(e) = result {
+            for memory in &deleted {
+                let _ = self.changes.record(ChangeKind::Saved, memory);
+            }
+            return Err(e);
+        }
+        drop(index);
+        self.record_superseded(&deleted, true);
+        self.audit_all(action, &deleted);
+
+        let mut cache = self.lock_cache();
+        for &position in positions {
+            cache.invalidate_position(position);
+        }
+        Ok(())
+    }
+
+    /// Save and delete several memories as one unit
+    ///
+    /// New records are appended first; both index files are then replaced
+    /// together through a [`SwapPlan`], whose commit is the point where the
+    /// whole batch takes effect. A crash before it leaves only unreferenced
+    /// records, which `compact` drops. Fails without changing anything if a
+    /// memory to delete does not exist. Returns the ids of the saved memories.
+    pub(crate) fn apply_batch(&self, saves: Vec<MemoryItem>, deletes: &[String]) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        let mut index = self.write_index();
+
+        let mut deleted = Vec::with_capacity(deletes.len());
+        for id in deletes {
+            let position = *index.ids.get(id).ok_or_else(|| format!("memory {} not found", id))?;
+            deleted.push((position, self.read_memory_at_position(position)?));
+        }
+        let removed: HashSet<usize> = deleted.iter().map(|(position, _)| *position).collect();
+
+        let mut saved = Vec::with_capacity(saves.len());
+        for mut memory in saves {
+            if memory.id.is_empty() {
+                memory.id = Uuid::new_v4().to_string();
+            }
+            self.set_expiry(&mut memory);
+            let record = Self::encode_record(&memory)?;
+            let segment = self.segments.segment_for(&memory.user_id)?;
+            let log = segments::user_log(&memory.user_id);
+            let position = segments::pack(segment, self.append_record(&log, &record)?);
+            self.backend.flush(&log)?;
+            saved.push((position, record.len() as u64, memory));
+        }
+
+        let edit = |index: &mut StorageIndex| {
+            let retain = |list: &mut Vec<usize>| list.retain(|p| !removed.contains(p));
+            index.by_user.values_mut().for_each(retain);
+            index.by_session.values_mut().for_each(retain);
+            for (position, _, memory) in &saved {
+                index.by_user.entry(memory.user_id.clone()).or_default().push(*position);
+                index.by_session
+                    .entry((memory.user_id.clone(), memory.session_id.clone()))
+                    .or_default()
+                    .push(*position);
+            }
+        };
+        let mut persisted = StorageIndex {
+            by_user: index.by_user.clone(),
+            by_session: index.by_session.clone(),
+            ..StorageIndex::default()
+        };
+        edit(&mut persisted);
+        persisted.remove_empty();
+
+        let (_, user_index, session_index) = self.encode_index(&persisted)?;
+        let mut plan = SwapPlan::begin(vec![INDEX_BLOB.to_string(), SESSION_INDEX_BLOB.to_string()], Vec::new(), self.backend.as_ref())?;
+        let staged = [(INDEX_BLOB, user_index), (SESSION_INDEX_BLOB, session_index)]
+            .iter()
+            .try_for_each(|(blob, data)| self.backend.write_blob(&SwapPlan::staged(blob), data.as_bytes()));
+        if let Err(e) = staged.map_err(Box::<dyn std::error::Error>::from).and_then(|_| plan.commit(self.backend.as_ref())) {
+            let _ = plan.discard(self.backend.as_ref());
+            return Err(e);
+        }
+        // Committed: a crash from here on finishes the swap when the store is
+        // reopened, so the change log only hears of the batch now
+        let changes = saved.iter().map(|(_, _, memory)| (ChangeKind::Saved, memory))
+            .chain(deleted.iter().map(|(_, memory)| (ChangeKind::Deleted, memory)));
+        for (kind, memory) in changes {
+            if let Err(e) = self.changes.record(kind, memory) {
+                println!("Warning: failed to record change of memory {}: {}", memory.id, e);
+            }
+        }
+        plan.apply(self.backend.as_ref())?;
+        self.record_superseded(deleted.iter().map(|(_, memory)| memory), true);
+        self.audit_all(AuditAction::Save, saved.iter().map(|(_, _, memory)| memory));
+        self.audit_all(AuditAction::Delete, deleted.iter().map(|(_, memory)| memory));
+
+        edit(&mut index);
+        index.terms.values_mut().flat_map(|terms| terms.values_mut()).for_each(|list| list.retain(|p| !removed.contains(p)));
+        index.timestamps.retain(|p, _| !removed.contains(p));
+        index.ids.retain(|_, p| !removed.contains(p));
+        index.sizes.retain(|p, _| !removed.contains(p));
+        for (position, size, memory) in &saved {
+            index.add_record(memory, *position, *size);
+        }
+        index.remove_empty();
+        drop(index);
+
+        {
+            let mut cache = self.lock_cache();
+            for position in &removed {
+                cache.invalidate_position(*position);
+            }
+            for (position, _, memory) in &saved {
+                cache.insert(*position, memory.clone(), self.now());
+            }
+        }
+
+        println!("Transaction applied: {} saved, {} deleted", saved.len(), deleted.len());
+        let ids = saved.iter().map(|(_, _, memory)| memory.id.clone()).collect();
+        if self.events.has_observers() {
+            for (_, _, memory) in saved {
+                self.events.emit(MemoryEvent::MemorySaved { memory });
+            }
+        }
+        Ok(ids)
+    }
+
+    /// Remove a user from every index and shred their segment log
+    ///
+    /// Records still in a pre-sharding `memories.bin` are moved out by a full
+    /// compaction first, so no copy is left behind in the shared log. Returns
+    /// the erased memories and the size of the shredded log.
+    pub(crate) fn erase_user(&self, user_id: &str) -> Result<(Vec<MemoryItem>, u64), Box<dyn std::error::Error>> {
+        let in_legacy_log = self.read_index().by_user.get(user_id)
+            .is_some_and(|positions| positions.iter().any(|p| segments::segment_of(*p) == 0));
+        if in_legacy_log {
+            self.compact()?;
+        }
+
+        // Held until the log is gone so no save slips in between
+        let mut index = self.write_index();
+        let positions: HashSet<usize> = index.by_user.get(user_id).into_iter().flatten().copied().collect();
+        let mut memories = Vec::with_capacity(positions.len());
+        for &position in &positions {
+            memories.push(self.read_memory_at_position(position)?);
+        }
+        for memory in &memories {
+            self.changes.record(ChangeKind::Deleted, memory)?;
+        }
+
+        let result = self.update_index(&mut index, |index| {
+            index.by_user.remove(user_id);
+            index.by_session.retain(|(user, _), _| user != user_id);
+            index.terms.remove(user_id);
+            index.filters.remove(user_id);
+            index.timestamps.retain(|p, _| !positions.contains(p));
+            index.ids.retain(|_, p| !positions.contains(p));
+            index.sizes.retain(|p, _| !positions.contains(p));
+        });
+        if let Err(e) = result {
+            for memory in &memories {
+                let _ = self.changes.record(ChangeKind::Saved, memory);
+            }
+            return Err(e);
+        }
+        self.audit_all(AuditAction::EraseUser, &memories);
+        {
+            let mut cache = self.lock_cache();
+            for &position in &positions {
+                cache.invalidate_position(position);
+            }
+        }
+
+        let log = segments::user_log(user_id);
+        let bytes_erased = self.backend.size(&log)?;
+        self.backend.shred(&log)?;
+        self.segments.remove_user(user_id)?;
+        drop(index);
+        Ok((memories, bytes_erased))
+    }
+
+    /// Work out when `memory` expires from its TTL and its session's retention;
+    /// every write sets it
+    fn set_expiry(&self, memory: &mut MemoryItem) {
+        memory.expires_at = self.session_retention.get(&memory.session_id).expiry_of(memory);
+    }
+
+    /// Rewrite the memories of `session_id` whose expiry its retention
+    /// settings have changed; returns how many there were
+    pub(crate) fn refresh_expiry(&self, session_id: &str) -> Result<usize, Box<dyn std::error::Error>> {
+        let mut index = self.write_index();
+        let positions: Vec<usize> = index
+            .by_session
+            .iter()
+            .filter(|((_, session), _)| session == session_id)
+            .flat_map(|(_, positions)| positions.iter().copied())
+            .collect();
+        let retention = self.session_retention.get(session_id);
+        let mut refreshed = 0;
+        for position in positions {
+            let memory = self.read_memory_at_position(position)?;
+            if retention.expiry_of(&memory) != memory.expires_at {
+                self.replace_locked(&mut index, position, memory)?;
+                refreshed += 1;
+            }
+        }
+        Ok(refreshed)
+    }
+
+    /// Write a new version of the record at `position` and point the indices at it
+    ///
+    /// The memory must keep its user and session; its `version` is bumped.
+    /// Returns the new position.
+    pub(crate) fn replace_at(&self, position: usize, mut memory: MemoryItem) -> Result<usize, Box<dyn std::error::Error>> {
+        memory.version += 1;
+        let mut index = self.write_index();
+        self.replace_locked(&mut index, position, memory)
+    }
+
+    /// Apply `edit` to a memory and store the result, unless its version is
+    /// no longer `expected_version`
+    ///
+    /// The check and the write happen under the index lock, so of two
+    /// writers that read the same version only the first one succeeds; the
+    /// other gets a [`VersionConflict`]. Returns the updated memory.
+    pub(crate) fn update_memory<F>(&self, memory_id: &str, expected_version: Option<u64>, edit: F) -> Result<MemoryItem, Box<dyn std::error::Error>>
+    where
+        F: FnOnce(&mut MemoryItem),
+    {
+        let mut index = self.write_index();
+        let position = *index.ids.get(memory_id).ok_or_else(|| format!("memory {} not found", memory_id))?;
+        let mut memory = self.read_memory_at_position(position)?;
+        if let Some(expected) = expected_version {
+            if memory.version != expected {
+                return Err(Box::new(VersionConflict {
+                    memory_id: memory_id.to_string(),
+                    expected,
+                    actual: memory.version,
+                }));
+            }
+        }
+
+        let (id, user_id, session_id) = (memory.id.clone(), memory.user_id.clone(), memory.session_id.clone());
+        edit(&mut memory);
+        memory.id = id;
+        memory.user_id = user_id;
+        memory.session_id = session_id;
+        memory.version += 1;
+        self.replace_locked(&mut index, position, memory.clone())?;
+        Ok(memory)
+    }
+
+    /// Store `memory` in place of the memory with its id, version and all
+    ///
+    /// For copies of the memory made elsewhere, e.g. by a merge; the copy
+    /// must keep the memory's user and session.
+    pub(crate) fn overwrite(&self, memory: MemoryItem) -> Result<(), Box<dyn std::error::Error>> {
+        let mut index = self.write_index();
+        let position = *index.ids.get(&memory.id).ok_or_else(|| format!("memory {} not found", memory.id))?;
+        let current = self.read_memory_at_position(position)?;
+        if (&current.user_id, &current.session_id) != (&memory.user_id, &memory.session_id) {
+            return Err(format!("memory {} belongs to another user or session", memory.id).into());
+        }
+        self.replace_locked(&mut index, position, memory)?;
+        Ok(())
+    }
+
+    fn replace_locked(&self, index: &mut StorageIndex, position: usize, mut memory: MemoryItem) -> Result<usize, Box<dyn std::error::Error>> {
+        self.set_expiry(&mut memory);
+        let record = Self::encode_record(&memory)?;
+        let segment = self.segments.segment_for(&memory.user_id)?;
+        let log = segments::user_log(&memory.user_id);
+
+        let previous = if self.history.is_enabled() { Some(self.read_memory_at_position(position)?) } else { None };
+        let new_position = segments::pack(segment, self.append_record(&log, &record)?);
+        self.backend.flush(&log)?;
+        self.changes.record(ChangeKind::Updated, &memory)?;
+
+        // The addition goes first, so a torn batch can't lose the memory
+        let deltas = vec![
+            IndexDelta::Add { position: new_position, user_id: memory.user_id.clone(), session_id: memory.session_id.clone() },
+            IndexDelta::Remove { position },
+        ];
+        let result = self.persist_index_edit(index, deltas, |index| {
+            let swap = |list: &mut Vec<usize>| list.iter_mut().filter(|p| **p == position).for_each(|p| *p = new_position);
+            if let Some(list) = index.by_user.get_mut(&memory.user_id) {
+                swap(list);
+            }
+            if let Some(list) = index.by_session.get_mut(&(memory.user_id.clone(), memory.session_id.clone())) {
+                swap(list);
+            }
+            if let Some(terms) = index.terms.get_mut(&memory.user_id) {
+                terms.values_mut().for_each(|list| list.retain(|p| *p != position));
+                terms.retain(|_, list| !list.is_empty());
+            }
+            index.timestamps.remove(&position);
+            index.sizes.remove(&position);
+            index.add_record(&memory, new_position, record.len() as u64);
+        });
+        if let Err(e) = result {
+            if let Ok(previous) = self.read_memory_at_position(position) {
+                let _ = self.changes.record(ChangeKind::Updated, &previous);
+            }
+            return Err(e);
+        }
+        self.record_superseded(&previous, false);
+        self.audit_all(AuditAction::Update, [&memory]);
+
+        let mut cache = self.lock_cache();
+        cache.invalidate_position(position);
+        cache.insert(new_position, memory, self.now());
+        Ok(new_position)
+    }
+
+    /// Rewrite every user's segment log keeping only indexed records
+    ///
+    /// Records that no index points at (left behind by failed or torn
+    /// writes) are dropped, and records still in a pre-sharding
+    /// `memories.bin` move into their user's segment; that log is deleted
+    /// once nothing points into it. The new logs and indices are staged next
+    /// to the old ones and swapped in together; see [`SwapPlan`].
+    pub fn compact(&self) -> Result<CompactionReport, Box<dyn std::error::Error>> {
+        let mut users: Vec<String> = self.segments.users();
+        users.extend(self.read_index().by_user.keys().cloned());
+        users.sort();
+        users.dedup();
+        self.compact_segments(&users, true)
+    }
+
+    /// Rewrite one user's segment log, leaving every other user's untouched
+    pub fn compact_user(&self, user_id: &str) -> Result<CompactionReport, Box<dyn std::error::Error>> {
+        self.compact_segments(&[user_id.to_string()], false)
+    }
+
+    fn compact_segments(&self, users: &[String], drop_legacy: bool) -> Result<CompactionReport, Box<dyn std::error::Error>> {
+        let mut assigned = Vec::with_capacity(users.len());
+        for user_id in users {
+            assigned.push((user_id, self.segments.segment_for(user_id)?, segments::user_log(user_id)));
+        }
+
+        let mut index = self.write_index();
+        let logs: Vec<String> = assigned.iter().map(|(_, _, log)| log.clone()).collect();
+        let mut bytes_before = 0;
+        for log in &logs {
+            bytes_before += self.backend.size(log)?;
+        }
+        if drop_legacy {
+            bytes_before += self.backend.size(LEGACY_LOG)?;
+        }
+
+        let mut replace = logs;
+        replace.extend([INDEX_BLOB, SESSION_INDEX_BLOB].map(String::from));
+        let mut plan = SwapPlan::begin(replace, Vec::new(), self.backend.as_ref())?;
+        let mut remapped = HashMap::new();
+        for (user_id, segment, log) in &assigned {
+            let mut positions: Vec<usize> = index.by_user.get(*user_id).cloned().unwrap_or_default();
+            positions.sort_unstable();
+            positions.dedup();
+
+            // Start from an empty file so a user with no live records still gets a log
+            let staged_log = SwapPlan::staged(log);
+            self.backend.write_blob(&staged_log, &record::log_header())?;
+            for position in positions {
+                let record = self.read_record_bytes(position)?;
+                let offset = self.backend.append(&staged_log, &record)?;
+                remapped.insert(position, segments::pack(*segment, offset));
+            }
+            self.backend.flush(&staged_log)?;
+        }
+
+        let remap = |list: &Vec<usize>| -> Vec<usize> { list.iter().map(|p| *remapped.get(p).unwrap_or(p)).collect() };
+        let moved = |p: &usize| *remapped.get(p).unwrap_or(p);
+        let compacted = StorageIndex {
+            by_user: index.by_user.iter().map(|(k, v)| (k.clone(), remap(v))).collect(),
+            by_session: index.by_session.iter().map(|(k, v)| (k.clone(), remap(v))).collect(),
+            terms: index.terms
+                .iter()
+                .map(|(user, terms)| (user.clone(), terms.iter().map(|(t, v)| (t.clone(), remap(v))).collect()))
+                .collect(),
+            timestamps: index.timestamps.iter().map(|(p, timestamp)| (moved(p), *timestamp)).collect(),
+            ids: index.ids.iter().map(|(id, p)| (id.clone(), moved(p))).collect(),
+            sizes: index.sizes.iter().map(|(p, size)| (moved(p), *size)).collect(),
+            // Rebuilt without the trigrams of deleted memories
+            filters: index.terms.iter().map(|(user, terms)| (user.clone(), TermFilter::from_terms(terms.keys()))).collect(),
+        };
+        let legacy_unused = !compacted.by_user.values().flatten().any(|p| segments::segment_of(*p) == 0);
+        if drop_legacy && legacy_unused {
+            plan.remove.push(LEGACY_LOG.to_string());
+        }
+
+        let (_, user_index, session_index) = self.encode_index(&compacted)?;
+        for (blob, data) in [(INDEX_BLOB, user_index), (SESSION_INDEX_BLOB, session_index)] {
+            self.backend.write_blob(&SwapPlan::staged(blob), data.as_bytes())?;
+        }
+        plan.commit(self.backend.as_ref())?;
+
+        // Positions changed, so cached entries are keyed wrongly from here on
+        {
+            let mut cache = self.lock_cache();
+            for position in remapped.keys() {
+                cache.invalidate_position(*position);
+            }
+        }
+        *index = compacted;
+        plan.apply(self.backend.as_ref())?;
+
+        let mut bytes_after = 0;
+        for (_, _, log) in &assigned {
+            bytes_after += self.backend.size(log)?;
+        }
+        if drop_legacy {
+            bytes_after += self.backend.size(LEGACY_LOG)?;
+        }
+        let report = CompactionReport {
+            records_kept: remapped.len(),
+            bytes_before,
+            bytes_after,
+        };
+        println!("Compacted storage: kept {} records, reclaimed {} bytes", report.records_kept, report.bytes_reclaimed());
+        Ok(report)
+    }
+
+    /// Bytes that `compact` would reclaim right now
+    pub fn reclaimable_bytes(&self) -> Result<u64, Box<dyn std::error::Error>> {
+        let index = self.read_index();
+        let mut positions: Vec<usize> = index.by_user.values().flatten().copied().collect();
+        positions.sort_unstable();
+        positions.dedup();
+
+        let mut live = 0;
+        for position in positions {
+            live += self.read_frame(position)?.record_len();
+        }
+        for log in self.segments.logs() {
+            if self.backend.size(&log)? > 0 {
+                live += record::LOG_HEADER_LEN;
+            }
+        }
+        Ok(self.total_log_size()?.saturating_sub(live))
+    }
+
+    /// Copy the files that make up the store at a single point in time
+    ///
+    /// Writers are blocked while the copy is taken. The change log is not
+    /// included: it describes this store's history, not its contents.
+    pub fn snapshot_files(&self) -> Result<StoreFiles, Box<dyn std::error::Error>> {
+        let _index = self.read_index();
+        let mut files = Vec::new();
+        for name in self.store_file_names() {
+            if let Some(data) = self.backend.read_blob(&name)? {
+                files.push((name, data));
+            }
+        }
+        Ok(files)
+    }
+
+    /// Names of the files that make up the store, whether they exist or not
+    pub(crate) fn store_file_names(&self) -> Vec<String> {
+        let blobs = [INDEX_BLOB, SESSION_INDEX_BLOB, INDEX_LOG, SEGMENTS_BLOB, VERSION_BLOB, PROVENANCE_BLOB, LINKS_BLOB, FACTS_BLOB, SUMMARIES_BLOB, REVIEWS_BLOB, TRASH_BLOB, SESSION_RETENTION_BLOB, SESSIONS_BLOB, SPACES_BLOB];
+        self.segments.logs().into_iter().chain(blobs.map(String::from)).collect()
+    }
+
+    /// Replace the store's contents with files taken by `snapshot_files`
+    ///
+    /// Logs and indices are swapped in together (see [`SwapPlan`]), so a
+    /// crash midway leaves either the old or the restored store, never a mix
+    /// of the two.
+    pub fn restore_files(&self, files: &[(String, Vec<u8>)]) -> Result<(), Box<dyn std::error::Error>> {
+        let file = |name: &str| files.iter().find(|(n, _)| n == name).map(|(_, data)| data.as_slice());
+        let is_log = |name: &str| name == LEGACY_LOG || (name.starts_with("users/") && name.ends_with(LEGACY_LOG));
+        let logs: Vec<&(String, Vec<u8>)> = files.iter().filter(|(name, _)| is_log(name)).collect();
+        if logs.iter().any(|(_, data)| !data.is_empty()) && file(INDEX_BLOB).is_none() {
+            return Err("backup has a memory log but no index".into());
+        }
+
+        let mut index = self.write_index();
+        let mut replace: Vec<String> = logs.iter().map(|(name, _)| name.clone()).collect();
+        let mut remove: Vec<String> = self.segments.logs().into_iter().filter(|log| !replace.contains(log)).collect();
+        // Stores without a session index rebuild it from the log on open, and
+        // backups from before sharding have no segment table
+        for blob in [SESSION_INDEX_BLOB, INDEX_LOG, SEGMENTS_BLOB] {
+            if file(blob).is_none() {
+                remove.push(blob.to_string());
+            }
+        }
+        let staged_blobs: Vec<&str> = [INDEX_BLOB, SESSION_INDEX_BLOB, INDEX_LOG, SEGMENTS_BLOB]
+            .into_iter()
+            .filter(|blob| *blob == INDEX_BLOB || file(blob).is_some())
+            .collect();
+        replace.extend(staged_blobs.iter().map(|blob| blob.to_string()));
+
+        let mut plan = SwapPlan::begin(replace, remove, self.backend.as_ref())?;
+        for (name, data) in &logs {
+            self.backend.write_blob(&SwapPlan::staged(name), data)?;
+        }
+        for blob in staged_blobs {
+            self.backend.write_blob(&SwapPlan::staged(blob), file(blob).unwrap_or_default())?;
+        }
+        plan.commit(self.backend.as_ref())?;
+        plan.apply(self.backend.as_ref())?;
+
+        for name in [VERSION_BLOB, PROVENANCE_BLOB, LINKS_BLOB, FACTS_BLOB, SUMMARIES_BLOB, REVIEWS_BLOB, TRASH_BLOB, SESSION_RETENTION_BLOB, SESSIONS_BLOB, SPACES_BLOB] {
+            match file(name) {
+                Some(data) => self.backend.write_blob(name, data)?,
+                None => self.backend.remove(name)?,
+            }
+        }
+
+        self.lock_cache().clear();
+        self.segments.reload()?;
+        // Backups of older stores come back in their old format
+        migrations::migrate(self)?;
+        *index = self.read_index_from_disk()?;
+        drop(index);
+        self.provenance.reload()?;
+        self.links.reload()?;
+        self.facts.reload()?;
+        self.summaries.reload()?;
+        self.reviews.reload()?;
+        self.trash.reload()?;
+        self.session_retention.reload()?;
+        self.sessions.reload()?;
+        self.spaces.reload()?;
+        Ok(())
+    }
+
+    // Private helper methods
+
+    /// Candidates the inverted index can give for the keywords and query,
+    /// or `None` when it cannot narrow the search
+    fn index_candidates(index: &StorageIndex, filter: &QueryFilter, query: Option<&QueryExpr>) -> Option<HashSet<usize>> {
+        let user_id = filter.user_id.as_ref();
+        let from_keywords = filter.keywords.as_deref()
+            .filter(|keywords| !keywords.is_empty() && keywords.iter().all(|k| planner::is_indexable_keyword(k)))
+            .map(|keywords| {
+                index
+                    .keyword_postings(user_id, keywords, filter.match_mode)
+                    .flatten()
+                    .copied()
+                    .collect::<HashSet<usize>>()
+            });
+        let from_query = query.map(|expr| index.query_positions(user_id, expr, filter.match_mode));
+
+        match (from_keywords, from_query) {
+            (Some(keywords), Some(query)) => Some(&keywords & &query),
+            (keywords, query) => keywords.or(query),
+        }
+    }
+
+    fn plan_query(index: &StorageIndex, filter: &QueryFilter, query: Option<&QueryExpr>) -> QueryPlan {
+        let total_records = index.total_records();
+        let mut considered = Vec::new();
+
+        if let Some(positions) = Self::index_candidates(index, filter, query) {
+            considered.push((AccessPath::InvertedIndex, positions.len()));
+        }
+
+        if let Some(session_id) = &filter.session_id {
+            let estimate = match &filter.user_id {
+                Some(user_id) if !index.may_match(user_id, filter) => 0,
+                Some(user_id) => index.by_session
+                    .get(&(user_id.clone(), session_id.clone()))
+                    .map_or(0, Vec::len),
+                None => index.by_session
+                    .iter()
+                    .filter(|((uid, sid), _)| sid == session_id && filter.includes_user(uid) && index.may_match(uid, filter))
+                    .map(|(_, positions)| positions.len())
+                    .sum(),
+            };
+            considered.push((AccessPath::SessionIndex, estimate));
+        }
+
+        if let Some(users) = filter.users() {
+            let estimate = users
+                .iter()
+                .filter(|user_id| index.may_match(user_id, filter))
+                .map(|user_id| index.by_user.get(*user_id).map_or(0, Vec::len))
+                .sum();
+            considered.push((AccessPath::UserIndex, estimate));
+        }
+
+        // Users whose term filters rule out the keywords aren't scanned
+        let scanned = index.by_user.iter().filter(|(user_id, _)| index.may_match(user_id, filter)).map(|(_, positions)| positions.len()).sum();
+        considered.push((AccessPath::FullScan, scanned));
+        QueryPlan::choose(total_records, considered)
+    }
+
+    fn candidate_positions(
+        index: &StorageIndex,
+        access_path: AccessPath,
+        filter: &QueryFilter,
+        query: Option<&QueryExpr>,
+    ) -> Vec<usize> {
+        match access_path {
+            AccessPath::InvertedIndex => {
+                let mut positions: Vec<usize> = Self::index_candidates(index, filter, query)
+                    .unwrap_or_default()
+                    .into_iter()
+                    .collect();
+                positions.sort_unstable();
+                positions
+            }
+            AccessPath::SessionIndex => {
+                let session_id = filter.session_id.clone().unwrap_or_default();
+                match &filter.user_id {
+                    Some(user_id) if !index.may_match(user_id, filter) => Vec::new(),
+                    Some(user_id) => index.by_session
+                        .get(&(user_id.clone(), session_id))
+                        .cloned()
+                        .unwrap_or_default(),
+                    None => index.by_session
+                        .iter()
+                        .filter(|((uid, sid), _)| *sid == session_id && filter.includes_user(uid) && index.may_match(uid, filter))
+                        .flat_map(|(_, positions)| positions.iter().copied())
+                        .collect(),
+                }
+            }
+            AccessPath::UserIndex => filter
+                .users()
+                .unwrap_or_default()
+                .into_iter()
+                .filter(|user_id| index.may_match(user_id, filter))
+                .filter_map(|user_id| index.by_user.get(user_id))
+                .flatten()
+                .copied()
+                .collect(),
+            AccessPath::FullScan => index.by_user
+                .iter()
+                .filter(|(user_id, _)| index.may_match(user_id, filter))
+                .flat_map(|(_, positions)| positions.iter().copied())
+                .collect(),
+        }
+    }
+
+    fn matches_filter(&self, memory: &MemoryItem, filter: &QueryFilter, query: Option<&QueryExpr>) -> bool {
+        // User filters
+        if !filter.includes_user(&memory.user_id) {
+            return false;
+        }
+
+        // Session ID filter
+        if let Some(ref session_id) = filter.session_id {
+            if memory.session_id != *session_id {
+                return false;
+            }
+        }
+        if filter.exclude_sessions.contains(&memory.session_id) {
+            return false;
+        }
+
+        // Date range filter
+        if let Some(date_from) = filter.date_from {
+            if memory.timestamp < date_from {
+                return false;
+            }
+        }
+
+        if let Some(date_to) = filter.date_to {
+            if memory.timestamp > date_to {
+                return false;
+            }
+        }
+
+        // Importance filter
+        if let Some(min_importance) = filter.min_importance {
+            if memory.importance < min_importance {
+                return false;
+            }
+        }
+        if filter.max_importance.is_some_and(|max_importance| memory.importance > max_importance) {
+            return false;
+        }
+
+        // TTL and pin filters
+        if filter.has_ttl.is_some_and(|has_ttl| memory.ttl_hours.is_some() != has_ttl) {
+            return false;
+        }
+        if filter.pinned_only && !pins::is_pinned(memory) {
+            return false;
+        }
+
+        // Keyword filter (simple text search)
+        if let Some(ref keywords) = filter.keywords {
+            let content_lower = memory.content.to_lowercase();
+            let found = keywords.iter().any(|keyword| {
+                filter.match_mode.content_matches(&content_lower, &keyword.to_lowercase())
+            });
+            if !found {
+                return false;
+            }
+        }
+        if !filter.exclude_keywords.is_empty() {
+            let content_lower = memory.content.to_lowercase();
+            if filter.exclude_keywords.iter().any(|keyword| filter.match_mode.content_matches(&content_lower, &keyword.to_lowercase())) {
+                return false;
+            }
+        }
+
+        if let Some(expiring_before) = filter.expiring_before {
+            if memory.expires_at.is_none_or(|expires_at| expires_at > expiring_before) {
+                return false;
+            }
+        }
+
+        // Role and type filters
+        if filter.role.is_some() && memory.role != filter.role {
+            return false;
+        }
+        if filter.memory_type.is_some_and(|memory_type| memory.memory_type != memory_type) {
+            return false;
+        }
+
+        // Metadata filters
+        for (key, condition) in &filter.metadata_filters {
+            if !condition.matches(memory.metadata.get(key)) {
+                return false;
+            }
+        }
+
+        // Boolean query
+        if let Some(query) = query {
+            if !query.matches(&memory.content.to_lowercase(), filter.match_mode) {
+                return false;
+            }
+        }
+
+        true
+    }
+
+    fn read_memory_at_position(&self, position: usize) -> Result<MemoryItem, Box<dyn std::error::Error>> {
+        if let Some(memory) = self.lock_cache().get_at_position(position) {
+            return Ok(memory);
+        }
+
+        let memory = self.read_memory_from_disk(position)?;
+        self.lock_cache().insert(position, memory.clone(), self.now());
         Ok(memory)
     }
 
+    /// The memory at `position` for a recall, or `None` if its record
+    /// header already rules it out
+    ///
+    /// With `skim`, content and metadata are left empty and the memory is
+    /// not added to the cache.
+    fn read_candidate(&self, position: usize, prefilter: &HeaderFilter, skim: bool) -> Result<Option<MemoryItem>, Box<dyn std::error::Error>> {
+        if let Some(memory) = self.lock_cache().get_at_position(position) {
+            return Ok(prefilter.admits(&record::RecordHeader::of(&memory)).then_some(memory));
+        }
+        let frame = self.read_frame(position)?;
+        let offset = segments::offset_of(position) + record::FRAME_LEN as u64;
+        let data = self.backend.read_at(&self.log_of(position)?, offset, frame.payload_len)?;
+        if record::RecordHeader::peek(&frame, &data).is_some_and(|header| !prefilter.admits(&header)) {
+            return Ok(None);
+        }
+        if skim {
+            return Ok(Some(record::decode_skimmed(&frame, &data)?));
+        }
+        let memory = record::decode(&frame, &data)?;
+        self.lock_cache().insert(position, memory.clone(), self.now());
+        Ok(Some(memory))
+    }
+
+    fn read_memory_from_disk(&self, position: usize) -> Result<MemoryItem, Box<dyn std::error::Error>> {
+        Ok(self.read_sized_memory_from_disk(position)?.0)
+    }
+
+    /// Read the memory at `position` along with the size of its record
+    fn read_sized_memory_from_disk(&self, position: usize) -> Result<(MemoryItem, u64), Box<dyn std::error::Error>> {
+        let frame = self.read_frame(position)?;
+        let offset = segments::offset_of(position) + record::FRAME_LEN as u64;
+        let data = self.backend.read_at(&self.log_of(position)?, offset, frame.payload_len)?;
+        Ok((record::decode(&frame, &data)?, frame.record_len()))
+    }
+
+    fn encode_record(memory: &MemoryItem) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        // Frame + payload, written as a single append
+        record::encode(memory)
+    }
+
+    /// Append an encoded record to `log`, writing the log's header first if it is new
+    fn append_record(&self, log: &str, record: &[u8]) -> Result<u64, Box<dyn std::error::Error>> {
+        if self.backend.size(log)? == 0 {
+            // Written whole, so a torn append can never leave a log without its header
+            self.backend.write_blob(log, &record::log_header())?;
+        }
+        Ok(self.backend.append(log, record)?)
+    }
+
+    /// Size of a memory's record in the log
+    pub(crate) fn record_size(memory: &MemoryItem) -> u64 {
+        record::encoded_size(memory)
+    }
+
+    /// Persist an edit of the position indices, then apply it to the live index
+    ///
+    /// The live index is left untouched if the edit can't be persisted.
+    fn update_index<F>(&self, index: &mut StorageIndex, edit: F) -> Result<(), Box<dyn std::error::Error>>
+    where
+        F: Fn(&mut StorageIndex),
+    {
+        // The term index is not persisted, so the copy only needs the position lists
+        let mut persisted = StorageIndex {
+            by_user: index.by_user.clone(),
+            by_session: index.by_session.clone(),
+            ..StorageIndex::default()
+        };
+        edit(&mut persisted);
+
+        if let Err(e) = self.save_index(&persisted) {
+            // One of the index files may already have been replaced; try to put it back
+            let _ = self.save_index(index);
+            return Err(e);
+        }
+
+        edit(index);
+        Ok(())
+    }
+
+    /// Persist an edit of the position indices as `deltas` appended to the
+    /// index log, with those of any pending saves, then apply it to the live index
+    ///
+    /// Falls back to rewriting the index when the log is stale or has grown
+    /// as long as the index.
+    fn persist_index_edit<F>(&self, index: &mut StorageIndex, deltas: Vec<IndexDelta>, edit: F) -> Result<(), Box<dyn std::error::Error>>
+    where
+        F: Fn(&mut StorageIndex),
+    {
+        let mut batch = self.write_buffer.pending_deltas();
+        batch.extend(deltas);
+        if batch.is_empty() || !self.index_log.accepts(batch.len(), index.ids.len()) {
+            return self.update_index(index, edit);
+        }
+        // The log must not point at records that could still be lost
+        for log in self.write_buffer.pending_logs() {
+            self.backend.flush(&log)?;
+        }
+        let encoded = index_log::encode(&batch)?;
+        if let Err(e) = self.backend.append(INDEX_LOG, encoded.as_bytes()).and_then(|_| self.backend.flush(INDEX_LOG)) {
+            // Whether the batch reached the disk is unknown; the next edit rewrites the index
+            self.index_log.invalidate();
+            return Err(e.into());
+        }
+        self.index_log.appended(batch.len());
+        self.write_buffer.clear();
+        edit(index);
+        Ok(())
+    }
+
+    fn read_frame(&self, position: usize) -> Result<record::Frame, Box<dyn std::error::Error>> {
+        let frame = self.backend.read_at(&self.log_of(position)?, segments::offset_of(position), record::FRAME_LEN)?;
+        record::Frame::parse(&frame)
+    }
+
+    /// The whole record at `position`, frame included
+    fn read_record_bytes(&self, position: usize) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let len = self.read_frame(position)?.record_len() as usize;
+        Ok(self.backend.read_at(&self.log_of(position)?, segments::offset_of(position), len)?)
+    }
+
+    pub(crate) fn backend(&self) -> &dyn StorageBackend {
+        self.backend.as_ref()
+    }
+
+    pub(crate) fn segments(&self) -> &SegmentTable {
+        &self.segments
+    }
+
+    /// Format version of the store; see [`crate::migrations`]
+    pub fn storage_version(&self) -> Result<u32, Box<dyn std::error::Error>> {
+        migrations::stored_version(self)
+    }
+
+    /// Log file holding the record at `position`
+    fn log_of(&self, position: usize) -> Result<String, Box<dyn std::error::Error>> {
+        let segment = segments::segment_of(position);
+        self.segments
+            .log_name(segment)
+            .ok_or_else(|| format!("record position {} is in unknown segment {}", position, segment).into())
+    }
+
+    /// Combined size of every log
+    fn total_log_size(&self) -> Result<u64, Box<dyn std::error::Error>> {
+        let mut total = 0;
+        for log in self.segments.logs() {
+            total += self.backend.size(&log)?;
+        }
+        Ok(total)
+    }
+
+    /// Resolve a compaction interrupted by a crash in a version from before
+    /// segment logs, which swapped files in without a [`SwapPlan`]
+    fn recover_compaction(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let compact_log = format!("{}{}", LEGACY_LOG, COMPACT_SUFFIX);
+        if self.backend.read_blob(&compact_log)?.is_some() {
+            // The log was never swapped in: the old log and indices are still authoritative
+            println!("Discarding interrupted compaction in {}", self.backend.location());
+            self.backend.remove(&compact_log)?;
+            for blob in [INDEX_BLOB, SESSION_INDEX_BLOB] {
+                self.backend.remove(&format!("{}{}", blob, COMPACT_SUFFIX))?;
+            }
+            return Ok(());
+        }
+
+        // The log was swapped in, so the compacted indices must follow it
+        for blob in [INDEX_BLOB, SESSION_INDEX_BLOB] {
+            let compacted = format!("{}{}", blob, COMPACT_SUFFIX);
+            if self.backend.read_blob(&compacted)?.is_some() {
+                self.backend.rename(&compacted, blob)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn lock_cache(&self) -> std::sync::MutexGuard<'_, MemoryCache> {
+        // A panic while holding the lock cannot leave the cache inconsistent enough to matter
+        self.cache.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn read_index(&self) -> RwLockReadGuard<'_, StorageIndex> {
+        self.index.read().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn write_index(&self) -> RwLockWriteGuard<'_, StorageIndex> {
+        self.index.write().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
     fn load_index(&mut self) -> Result<(), Box<dyn std::error::Error>> {
-        if Path::new(&self.index_path).exists() {
-            let file = File::open(&self.index_path)?;
-            let reader = BufReader::new(file);
-            
-            for line in reader.lines() {
-                let line = line?;
-                let parts: Vec<&str> = line.split(':').collect();
-                if parts.len() == 2 {
-                    let user_id = parts[0].to_string();
-                    let positions: Result<Vec<usize>, _> = parts[1]
-                        .split(',')
-                        .filter(|s| !s.is_empty())
-                        .map(|s| s.parse())
-                        .collect();
-                    
-                    if let Ok(positions) = positions {
-                        self.memory_index.insert(user_id, positions);
-                    }
+        self.recover_compaction()?;
+        migrations::migrate(self)?;
+        let index = self.read_index_from_disk()?;
+        *self.write_index() = index;
+        Ok(())
+    }
+
+    /// Rewrite logs from before the log header (format 1) in the current format
+    ///
+    /// The migration from storage version 1, run before any record is read.
+    /// As in compaction, only indexed records are carried over, and the new
+    /// logs and indices are swapped in together through a [`SwapPlan`].
+    /// Records are re-encoded on the way, so they also move to the current
+    /// record version.
+    pub(crate) fn upgrade_logs(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let mut outdated = Vec::new();
+        for (segment, log) in self.segments.segment_logs() {
+            let size = self.backend.size(&log)?;
+            if size == 0 {
+                continue;
+            }
+            let start = self.backend.read_at(&log, 0, size.min(record::LOG_HEADER_LEN) as usize)?;
+            match record::log_format(&start) {
+                record::LOG_FORMAT => {}
+                format if format > record::LOG_FORMAT => {
+                    return Err(format!("log {} has format {}, newer than this build supports", log, format).into());
                 }
+                _ => outdated.push((segment, log)),
+            }
+        }
+        if outdated.is_empty() {
+            return Ok(());
+        }
+
+        let mut index = StorageIndex::default();
+        if let Some(data) = self.backend.read_blob(INDEX_BLOB)? {
+            Self::parse_user_index(&String::from_utf8_lossy(&data), &mut index);
+        }
+        let session_index = self.backend.read_blob(SESSION_INDEX_BLOB)?;
+        if let Some(data) = &session_index {
+            Self::parse_session_index(&String::from_utf8_lossy(data), &mut index);
+        }
+
+        let mut replace: Vec<String> = outdated.iter().map(|(_, log)| log.clone()).collect();
+        replace.push(INDEX_BLOB.to_string());
+        if session_index.is_some() {
+            replace.push(SESSION_INDEX_BLOB.to_string());
+        }
+        let mut plan = SwapPlan::begin(replace, Vec::new(), self.backend.as_ref())?;
+        let mut remapped = HashMap::new();
+        for (segment, log) in &outdated {
+            let mut positions: Vec<usize> = index.by_user.values().flatten().copied().filter(|p| segments::segment_of(*p) == *segment).collect();
+            positions.sort_unstable();
+            positions.dedup();
+
+            let staged_log = SwapPlan::staged(log);
+            self.backend.write_blob(&staged_log, &record::log_header())?;
+            for position in positions {
+                let offset = segments::offset_of(position);
+                let prefix = self.backend.read_at(log, offset, 4)?;
+                let prefix = u32::from_le_bytes([prefix[0], prefix[1], prefix[2], prefix[3]]);
+                let payload = self.backend.read_at(log, offset + 4, record::legacy_payload_len(prefix))?;
+                let memory = record::decode_legacy(prefix, &payload)?;
+                let offset = self.backend.append(&staged_log, &record::encode(&memory)?)?;
+                remapped.insert(position, segments::pack(*segment, offset));
             }
+            self.backend.flush(&staged_log)?;
+        }
+
+        let remap = |list: &mut Vec<usize>| list.iter_mut().for_each(|p| *p = *remapped.get(p).unwrap_or(p));
+        index.by_user.values_mut().for_each(remap);
+        index.by_session.values_mut().for_each(remap);
+        let (_, user_index, session_index_data) = self.encode_index(&index)?;
+        self.backend.write_blob(&SwapPlan::staged(INDEX_BLOB), user_index.as_bytes())?;
+        if session_index.is_some() {
+            self.backend.write_blob(&SwapPlan::staged(SESSION_INDEX_BLOB), session_index_data.as_bytes())?;
         }
+        plan.commit(self.backend.as_ref())?;
+        plan.apply(self.backend.as_ref())?;
         Ok(())
     }
 
-    fn save_index(&self) -> Result<(), Box<dyn std::error::Error>> {
-        let file = File::create(&self.index_path)?;
-        let mut writer = BufWriter::new(file);
-        
-        for (user_id, positions) in &self.memory_index {
+    fn read_index_from_disk(&self) -> Result<StorageIndex, Box<dyn std::error::Error>> {
+        let mut index = StorageIndex::default();
+        self.index_log.invalidate();
+
+        let mut generation = None;
+        if let Some(data) = self.backend.read_blob(INDEX_BLOB)? {
+            let contents = String::from_utf8_lossy(&data);
+            generation = index_log::generation_of(&contents);
+            Self::parse_user_index(&contents, &mut index);
+        }
+
+        let session_index = self.backend.read_blob(SESSION_INDEX_BLOB)?;
+        if let Some(data) = &session_index {
+            Self::parse_session_index(&String::from_utf8_lossy(data), &mut index);
+        }
+        if let (Some(generation), Some(data)) = (generation, self.backend.read_blob(INDEX_LOG)?) {
+            if let Some((deltas, clean)) = index_log::parse(&String::from_utf8_lossy(&data), generation) {
+                index_log::apply((&mut index.by_user, &mut index.by_session), &deltas);
+                if clean {
+                    self.index_log.started(generation, deltas.len());
+                }
+            }
+        }
+
+        if session_index.is_none() && !index.by_user.is_empty() {
+            // Store predates the session index: rebuild it from the log once
+            self.rebuild_session_index(&mut index);
+            if !self.backend.is_read_only() {
+                self.save_index(&index)?;
+            }
+        }
+        self.rebuild_term_index(&mut index);
+        Ok(index)
+    }
+
+    fn parse_user_index(contents: &str, index: &mut StorageIndex) {
+        for line in contents.lines() {
+            // Positions never hold a colon, but user ids (e.g. of spaces) may
+            if let Some((user_id, positions)) = line.rsplit_once(':') {
+                let positions: Result<Vec<usize>, _> = positions
+                    .split(',')
+                    .filter(|s| !s.is_empty())
+                    .map(|s| s.parse())
+                    .collect();
+
+                if let Ok(positions) = positions {
+                    index.by_user.insert(user_id.to_string(), positions);
+                }
+            }
+        }
+    }
+
+    fn parse_session_index(contents: &str, index: &mut StorageIndex) {
+        for line in contents.lines() {
+            let parts: Vec<&str> = line.split('\t').collect();
+            if parts.len() == 3 {
+                let positions: Result<Vec<usize>, _> = parts[2]
+                    .split(',')
+                    .filter(|s| !s.is_empty())
+                    .map(|s| s.parse())
+                    .collect();
+
+                if let Ok(positions) = positions {
+                    index.by_session.insert((parts[0].to_string(), parts[1].to_string()), positions);
+                }
+            }
+        }
+    }
+
+    fn rebuild_session_index(&self, index: &mut StorageIndex) {
+        index.by_session.clear();
+        for positions in index.by_user.values() {
+            for &position in positions {
+                if let Ok(memory) = self.read_memory_at_position(position) {
+                    index.by_session
+                        .entry((memory.user_id, memory.session_id))
+                        .or_default()
+                        .push(position);
+                }
+            }
+        }
+    }
+
+    fn rebuild_term_index(&self, index: &mut StorageIndex) {
+        // The inverted, timestamp and id indices are not persisted; records are read straight from
+        // the log so opening a store doesn't flood the LRU cache
+        let mut terms = StorageIndex::default();
+        for positions in index.by_user.values() {
+            for &position in positions {
+                if let Ok((memory, size)) = self.read_sized_memory_from_disk(position) {
+                    terms.add_record(&memory, position, size);
+                }
+            }
+        }
+        index.terms = terms.terms;
+        index.timestamps = terms.timestamps;
+        index.ids = terms.ids;
+        index.sizes = terms.sizes;
+        index.filters = terms.filters;
+    }
+
+    /// Encode both indices as a snapshot at a new generation
+    ///
+    /// Whatever replaces the snapshot on disk with this one leaves the index
+    /// log behind, so appending to it stops until `save_index` restarts it.
+    fn encode_index(&self, index: &StorageIndex) -> Result<(u64, String, String), std::fmt::Error> {
+        self.index_log.invalidate();
+        let generation = index_log::new_generation();
+        let mut user_index = index_log::header(generation);
+        for (user_id, positions) in &index.by_user {
             let positions_str: Vec<String> = positions.iter().map(|p| p.to_string()).collect();
-            writeln!(writer, "{}:{}", user_id, positions_str.join(","))?;
+            writeln!(user_index, "{}:{}", user_id, positions_str.join(","))?;
+        }
+
+        let mut session_index = String::new();
+        for ((user_id, session_id), positions) in &index.by_session {
+            let positions_str: Vec<String> = positions.iter().map(|p| p.to_string()).collect();
+            writeln!(session_index, "{}\t{}\t{}", user_id, session_id, positions_str.join(","))?;
+        }
+
+        Ok((generation, user_index, session_index))
+    }
+
+    fn save_index(&self, index: &StorageIndex) -> Result<(), Box<dyn std::error::Error>> {
+        // The index must not point at records that could still be lost
+        for log in self.write_buffer.pending_logs() {
+            self.backend.flush(&log)?;
+        }
+        let (generation, user_index, session_index) = self.encode_index(index)?;
+        // Session index first: if only it lands, the old log replays onto it harmlessly
+        self.backend.write_blob(SESSION_INDEX_BLOB, session_index.as_bytes())?;
+        self.backend.write_blob(INDEX_BLOB, user_index.as_bytes())?;
+        self.write_buffer.clear();
+        // Until this lands the old log is ignored, as it is of the old generation
+        if self.backend.write_blob(INDEX_LOG, index_log::header(generation).as_bytes()).is_ok() {
+            self.index_log.started(generation, 0);
         }
-        
-        writer.flush()?;
         Ok(())
     }
 }
@@ -324,6 +2588,7 @@ mod tests {
             timestamp: Utc::now(),
             ttl_hours: Some(24),
             importance: 0.8,
+            ..Default::default()
         };
 
         let memory_id = storage.save(memory).unwrap();
@@ -337,6 +2602,7 @@ mod tests {
             date_to: None,
             limit: Some(10),
             min_importance: None,
+            ..Default::default()
         };
 
         let results = storage.recall(filter).unwrap();
@@ -346,4 +2612,569 @@ mod tests {
         // Cleanup
         std::fs::remove_dir_all("./test_storage").ok();
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_repeated_recall_hits_cache() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let storage_dir = temp_dir.path().to_str().unwrap();
+
+        let mut writer = MemoryStorage::new(storage_dir).unwrap();
+        writer.save(MemoryItem {
+            id: "".to_string(),
+            user_id: "test_user".to_string(),
+            session_id: "session_1".to_string(),
+            content: "Watching NVDA into earnings".to_string(),
+            metadata: HashMap::new(),
+            timestamp: Utc::now(),
+            ttl_hours: None,
+            importance: 0.5,
+            ..Default::default()
+        }).unwrap();
+
+        // A fresh instance starts with a cold cache
+        drop(writer);
+        let storage = MemoryStorage::with_cache_capacity(storage_dir, 16).unwrap();
+        let filter = QueryFilter {
+            user_id: Some("test_user".to_string()),
+            session_id: None,
+            keywords: None,
+            date_from: None,
+            date_to: None,
+            limit: None,
+            min_importance: None,
+            ..Default::default()
+        };
+
+        storage.recall(filter.clone()).unwrap();
+        storage.recall(filter).unwrap();
+
+        let stats = storage.cache_stats();
+        assert_eq!(stats.misses, 1);
+        assert_eq!(stats.hits, 1);
+        assert_eq!(stats.entries, 1);
+    }
+
+    #[test]
+    fn test_session_index_persists() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let storage_dir = temp_dir.path().to_str().unwrap();
+
+        let mut storage = MemoryStorage::new(storage_dir).unwrap();
+        for (session_id, content) in [("s1", "first"), ("s2", "second"), ("s1", "third")] {
+            storage.save(MemoryItem {
+                id: "".to_string(),
+                user_id: "test_user".to_string(),
+                session_id: session_id.to_string(),
+                content: content.to_string(),
+                metadata: HashMap::new(),
+                timestamp: Utc::now(),
+                ttl_hours: None,
+                importance: 0.5,
+                ..Default::default()
+            }).unwrap();
+        }
+
+        drop(storage);
+        let reopened = MemoryStorage::new(storage_dir).unwrap();
+        assert_eq!(reopened.read_index().by_session.get(&("test_user".to_string(), "s1".to_string())).map(Vec::len), Some(2));
+        assert_eq!(reopened.get_memories_by_session("s1").unwrap().len(), 2);
+        assert_eq!(reopened.get_session_memories("test_user", "s1").unwrap().len(), 2);
+        assert_eq!(reopened.get_session_memories("test_user", "s2").unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_planner_picks_selective_index() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let storage_dir = temp_dir.path().to_str().unwrap();
+
+        let mut storage = MemoryStorage::new(storage_dir).unwrap();
+        for i in 0..20 {
+            let content = if i == 7 { "Closed AAPL position".to_string() } else { format!("Routine note {}", i) };
+            storage.save(MemoryItem {
+                id: "".to_string(),
+                user_id: "test_user".to_string(),
+                session_id: format!("s{}", i % 4),
+                content,
+                metadata: HashMap::new(),
+                timestamp: Utc::now(),
+                ttl_hours: None,
+                importance: 0.5,
+                ..Default::default()
+            }).unwrap();
+        }
+
+        let mut filter = QueryFilter {
+            user_id: Some("test_user".to_string()),
+            session_id: None,
+            keywords: Some(vec!["aapl".to_string()]),
+            date_from: None,
+            date_to: None,
+            limit: None,
+            min_importance: None,
+            ..Default::default()
+        };
+        let plan = storage.explain(&filter);
+        assert_eq!(plan.access_path, AccessPath::InvertedIndex);
+        assert_eq!(plan.estimated_candidates, 1);
+        assert_eq!(storage.recall(filter.clone()).unwrap().len(), 1);
+
+        // Common substrings and multi-word keywords fall back to the narrowest structural index
+        filter.keywords = Some(vec!["note".to_string()]);
+        filter.session_id = Some("s1".to_string());
+        assert_eq!(storage.explain(&filter).access_path, AccessPath::SessionIndex);
+        assert_eq!(storage.recall(filter.clone()).unwrap().len(), 5);
+
+        filter.keywords = Some(vec!["AAPL position".to_string()]);
+        filter.session_id = None;
+        assert_eq!(storage.explain(&filter).access_path, AccessPath::UserIndex);
+        assert_eq!(storage.recall(filter.clone()).unwrap().len(), 1);
+
+        // The inverted index is rebuilt when the store is reopened
+        drop(storage);
+        let reopened = MemoryStorage::new(storage_dir).unwrap();
+        filter.keywords = Some(vec!["APL".to_string()]);
+        assert_eq!(reopened.explain(&filter).access_path, AccessPath::InvertedIndex);
+        assert_eq!(reopened.recall(filter).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_prefix_and_fuzzy_recall() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let mut storage = MemoryStorage::new(temp_dir.path().to_str().unwrap()).unwrap();
+        for content in ["Reviewed my trading journal", "Upgraded the laptop"] {
+            storage.save(MemoryItem {
+                id: "".to_string(),
+                user_id: "test_user".to_string(),
+                session_id: "s1".to_string(),
+                content: content.to_string(),
+                metadata: HashMap::new(),
+                timestamp: Utc::now(),
+                ttl_hours: None,
+                importance: 0.5,
+                ..Default::default()
+            }).unwrap();
+        }
+
+        let mut filter = QueryFilter {
+            user_id: Some("test_user".to_string()),
+            keywords: Some(vec!["tradign".to_string()]),
+            ..Default::default()
+        };
+        assert!(storage.recall(filter.clone()).unwrap().is_empty());
+
+        filter.match_mode = MatchMode::Fuzzy(2);
+        assert_eq!(storage.explain(&filter).access_path, AccessPath::InvertedIndex);
+        let memories = storage.recall(filter.clone()).unwrap();
+        assert_eq!(memories.len(), 1);
+        assert!(memories[0].content.contains("trading"));
+
+        // "rad" is inside "trading" but starts no word
+        filter.keywords = Some(vec!["rad".to_string()]);
+        filter.match_mode = MatchMode::Prefix;
+        assert!(storage.recall(filter.clone()).unwrap().is_empty());
+        filter.keywords = Some(vec!["TRAD".to_string(), "lap".to_string()]);
+        assert_eq!(storage.recall(filter).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_boolean_query_recall() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let mut storage = MemoryStorage::new(temp_dir.path().to_str().unwrap()).unwrap();
+        let contents = [
+            "Set a stop loss on AAPL at 170",
+            "Stop buying AAPL, loss is too big",
+            "Moved the stop loss on crypto and AAPL",
+            "Routine note",
+        ];
+        for content in contents {
+            storage.save(MemoryItem {
+                id: "".to_string(),
+                user_id: "test_user".to_string(),
+                session_id: "s1".to_string(),
+                content: content.to_string(),
+                metadata: HashMap::new(),
+                timestamp: Utc::now(),
+                ttl_hours: None,
+                importance: 0.5,
+                ..Default::default()
+            }).unwrap();
+        }
+
+        let mut filter = QueryFilter {
+            user_id: Some("test_user".to_string()),
+            query: Some("\"stop loss\" AND AAPL NOT crypto".to_string()),
+            ..Default::default()
+        };
+        let plan = storage.explain(&filter);
+        assert_eq!(plan.access_path, AccessPath::InvertedIndex);
+        assert_eq!(plan.estimated_candidates, 3);
+        let memories = storage.recall(filter.clone()).unwrap();
+        assert_eq!(memories.len(), 1);
+        assert_eq!(memories[0].content, contents[0]);
+
+        filter.query = Some("crypto OR routine".to_string());
+        assert_eq!(storage.recall(filter.clone()).unwrap().len(), 2);
+
+        // Keywords still apply alongside the query
+        filter.keywords = Some(vec!["note".to_string()]);
+        assert_eq!(storage.recall(filter.clone()).unwrap().len(), 1);
+
+        filter.query = Some("(stop AND".to_string());
+        assert!(storage.recall(filter).is_err());
+    }
+
+    #[test]
+    fn test_metadata_filters() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let mut storage = MemoryStorage::new(temp_dir.path().to_str().unwrap()).unwrap();
+        let entries = [
+            vec![("category", "trading"), ("asset", "AAPL")],
+            vec![("category", "trading"), ("asset", "GOLD futures")],
+            vec![("category", "personal")],
+        ];
+        for entry in entries {
+            storage.save(MemoryItem {
+                id: "".to_string(),
+                user_id: "test_user".to_string(),
+                session_id: "s1".to_string(),
+                content: "Tagged memory".to_string(),
+                metadata: entry.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect(),
+                timestamp: Utc::now(),
+                ttl_hours: None,
+                importance: 0.5,
+                ..Default::default()
+            }).unwrap();
+        }
+
+        let recall = |conditions: Vec<(&str, MetadataCondition)>| {
+            storage.recall(QueryFilter {
+                user_id: Some("test_user".to_string()),
+                metadata_filters: conditions.into_iter().map(|(k, c)| (k.to_string(), c)).collect(),
+                ..Default::default()
+            }).unwrap().len()
+        };
+        assert_eq!(recall(vec![("category", MetadataCondition::Equals("trading".to_string()))]), 2);
+        assert_eq!(recall(vec![("asset", MetadataCondition::Exists)]), 2);
+        assert_eq!(recall(vec![("asset", MetadataCondition::Contains("gold".to_string()))]), 1);
+        assert_eq!(recall(vec![
+            ("category", MetadataCondition::Equals("trading".to_string())),
+            ("asset", MetadataCondition::Equals("AAPL".to_string())),
+        ]), 1);
+        assert_eq!(recall(vec![("missing", MetadataCondition::Exists)]), 0);
+
+        let parsed: QueryFilter = serde_json::from_str(
+            r#"{"user_id": "test_user", "metadata_filters": {"category": {"equals": "personal"}, "asset": "exists"}}"#,
+        ).unwrap();
+        assert_eq!(parsed.metadata_filters["asset"], MetadataCondition::Exists);
+        assert!(storage.recall(parsed).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_recall_field_projection() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().to_str().unwrap();
+        let mut storage = MemoryStorage::new(path).unwrap();
+        let id = storage.save(MemoryItem {
+            user_id: "test_user".to_string(),
+            session_id: "s1".to_string(),
+            content: "Gold breakout above resistance ".repeat(50),
+            metadata: HashMap::from([("asset".to_string(), "GOLD".to_string())]),
+            timestamp: Utc::now(),
+            importance: 0.8,
+            ..Default::default()
+        }).unwrap();
+
+        // Reopened so nothing is cached and the records are skimmed from disk
+        drop(storage);
+        let storage = MemoryStorage::new(path).unwrap();
+        let listing: QueryFilter = serde_json::from_str(
+            r#"{"user_id": "test_user", "fields": ["timestamp", "importance"]}"#,
+        ).unwrap();
+        let listed = storage.recall(listing.clone()).unwrap();
+        assert_eq!(listed.len(), 1);
+        assert_eq!((listed[0].id.as_str(), listed[0].importance), (id.as_str(), 0.8));
+        assert!(listed[0].content.is_empty() && listed[0].metadata.is_empty() && listed[0].user_id.is_empty());
+        assert_eq!(storage.cache_stats().entries, 0);
+
+        let iterated: Vec<MemoryItem> = storage.recall_iter(listing).unwrap().map(Result::unwrap).collect();
+        assert!(iterated[0].content.is_empty());
+
+        // Filters on content still see it even though it is not returned
+        let searched = storage.recall(QueryFilter {
+            user_id: Some("test_user".to_string()),
+            keywords: Some(vec!["breakout".to_string()]),
+            fields: Some(vec![MemoryField::Metadata]),
+            ..Default::default()
+        }).unwrap();
+        assert_eq!(searched.len(), 1);
+        assert!(searched[0].content.is_empty());
+        assert_eq!(searched[0].metadata["asset"], "GOLD");
+
+        let full = storage.recall(QueryFilter { user_id: Some("test_user".to_string()), ..Default::default() }).unwrap();
+        assert!(full[0].content.starts_with("Gold breakout"));
+    }
+
+    #[test]
+    fn test_expires_at_and_expiring_before() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let mut storage = MemoryStorage::new(temp_dir.path().to_str().unwrap()).unwrap();
+        storage.session_retention().set("scratch", crate::SessionRetention { ttl_hours: Some(1), ..Default::default() }).unwrap();
+        let now = Utc::now();
+        let mut save = |session_id: &str, ttl_hours: Option<u32>| {
+            let id = storage.save(MemoryItem {
+                user_id: "test_user".to_string(),
+                session_id: session_id.to_string(),
+                content: "Expiring memory".to_string(),
+                timestamp: now,
+                ttl_hours,
+                ..Default::default()
+            }).unwrap();
+            storage.get_memory(&id).unwrap().unwrap()
+        };
+        let day = save("s1", Some(24));
+        let forever = save("s1", None);
+        let scratch = save("scratch", Some(24));
+        let scratch_without_ttl = save("scratch", None);
+        assert_eq!(day.expires_at, Some(now + chrono::Duration::hours(24)));
+        assert_eq!(forever.expires_at, None);
+        // The session TTL wins over the memory's own
+        assert_eq!(scratch.expires_at, Some(now + chrono::Duration::hours(1)));
+        assert_eq!(scratch_without_ttl.expires_at, scratch.expires_at);
+
+        let expiring = storage.recall(QueryFilter {
+            user_id: Some("test_user".to_string()),
+            expiring_before: Some(now + chrono::Duration::hours(2)),
+            session_id: Some("scratch".to_string()),
+            has_ttl: Some(true),
+            ..Default::default()
+        }).unwrap();
+        assert_eq!(expiring.len(), 1);
+        assert_eq!(expiring[0].id, scratch.id);
+        assert_eq!(storage.count(QueryFilter {
+            expiring_before: Some(now + chrono::Duration::days(2)),
+            ..Default::default()
+        }).unwrap(), 3);
+
+        // `has_ttl` is about the memory's own TTL, whether records are cached or not
+        let without_ttl = QueryFilter { user_id: Some("test_user".to_string()), has_ttl: Some(false), ..Default::default() };
+        assert_eq!(storage.count(without_ttl.clone()).unwrap(), 2);
+        drop(storage);
+        let storage = MemoryStorage::new(temp_dir.path().to_str().unwrap()).unwrap();
+        assert_eq!(storage.count(without_ttl).unwrap(), 2);
+    }
+
+    #[test]
+    fn test_count_and_exists() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let mut storage = MemoryStorage::new(temp_dir.path().to_str().unwrap()).unwrap();
+        for (session_id, content, minutes_ago) in [("s1", "Bought AAPL", 90), ("s1", "Sold AAPL", 30), ("s2", "Gold rally", 10)] {
+            storage.save(MemoryItem {
+                user_id: "test_user".to_string(),
+                session_id: session_id.to_string(),
+                content: content.to_string(),
+                timestamp: Utc::now() - chrono::Duration::minutes(minutes_ago),
+                importance: 0.5,
+                ..Default::default()
+            }).unwrap();
+        }
+
+        let user = |filter: QueryFilter| QueryFilter { user_id: Some("test_user".to_string()), ..filter };
+        assert_eq!(storage.count(user(QueryFilter::default())).unwrap(), 3);
+        assert_eq!(storage.count(user(QueryFilter { session_id: Some("s1".to_string()), ..Default::default() })).unwrap(), 2);
+        assert_eq!(storage.count(QueryFilter { session_id: Some("s2".to_string()), ..Default::default() }).unwrap(), 1);
+        assert_eq!(storage.count(user(QueryFilter { within: Some("1h".parse().unwrap()), ..Default::default() })).unwrap(), 2);
+        assert_eq!(storage.count(user(QueryFilter { offset: Some(1), limit: Some(5), ..Default::default() })).unwrap(), 2);
+        assert_eq!(storage.count(user(QueryFilter { keywords: Some(vec!["aapl".to_string()]), ..Default::default() })).unwrap(), 2);
+        assert_eq!(storage.count(QueryFilter { user_id: Some("nobody".to_string()), ..Default::default() }).unwrap(), 0);
+
+        assert!(storage.exists(user(QueryFilter { query: Some("gold AND rally".to_string()), ..Default::default() })).unwrap());
+        assert!(!storage.exists(user(QueryFilter { keywords: Some(vec!["crypto".to_string()]), ..Default::default() })).unwrap());
+    }
+
+    #[test]
+    fn test_timestamp_index_and_relative_recall() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let storage_dir = temp_dir.path().to_str().unwrap();
+        let mut storage = MemoryStorage::new(storage_dir).unwrap();
+        for days_ago in [0, 2, 10, 40] {
+            storage.save(MemoryItem {
+                id: "".to_string(),
+                user_id: "test_user".to_string(),
+                session_id: "s1".to_string(),
+                content: format!("Logged {} days ago", days_ago),
+                metadata: HashMap::new(),
+                timestamp: Utc::now() - chrono::Duration::days(days_ago),
+                ttl_hours: None,
+                importance: 0.5,
+                ..Default::default()
+            }).unwrap();
+        }
+
+        let mut filter = QueryFilter {
+            user_id: Some("test_user".to_string()),
+            within: Some(RelativeDuration::weeks(1)),
+            ..Default::default()
+        };
+        assert_eq!(storage.recall(filter.clone()).unwrap().len(), 2);
+        filter.within = Some("30d".parse().unwrap());
+        filter.date_from = Some(Utc::now() - chrono::Duration::days(5));
+        assert_eq!(storage.recall(filter).unwrap().len(), 2);
+
+        // The timestamp index survives deletes, compaction and reopening
+        let oldest = storage.user_records("test_user").unwrap().into_iter()
+            .find(|(_, memory)| memory.content.contains("40"))
+            .map(|(position, _)| position)
+            .unwrap();
+        storage.delete_positions(&HashSet::from([oldest]), AuditAction::Delete).unwrap();
+        assert_eq!(storage.memory_timestamps("test_user").len(), 3);
+        storage.compact().unwrap();
+        assert_eq!(storage.memory_timestamps("test_user").len(), 3);
+        drop(storage);
+        let reopened = MemoryStorage::new(storage_dir).unwrap();
+        let mut timestamps = reopened.memory_timestamps("test_user");
+        timestamps.sort();
+        assert!(timestamps[0] < Utc::now() - chrono::Duration::days(9));
+        assert!(reopened.memory_timestamps("nobody").is_empty());
+    }
+
+    #[test]
+    fn test_users_have_separate_segments() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let storage_dir = temp_dir.path().to_str().unwrap();
+        let mut storage = MemoryStorage::new(storage_dir).unwrap();
+
+        for user in ["alice", "bob"] {
+            for i in 0..3 {
+                storage.save(MemoryItem {
+                    user_id: user.to_string(),
+                    session_id: "s1".to_string(),
+                    content: format!("{} memory {}", user, i),
+                    ..Default::default()
+                }).unwrap();
+            }
+        }
+        let alice_log = temp_dir.path().join(segments::user_log("alice"));
+        let bob_log = temp_dir.path().join(segments::user_log("bob"));
+        assert!(alice_log.exists() && bob_log.exists());
+
+        // Compacting one user leaves the other's log alone
+        let (alice_first, _) = storage.user_records("alice").unwrap().remove(0);
+        let (bob_first, _) = storage.user_records("bob").unwrap().remove(0);
+        storage.delete_positions(&HashSet::from([alice_first, bob_first]), AuditAction::Delete).unwrap();
+        let bob_size = std::fs::metadata(&bob_log).unwrap().len();
+        let report = storage.compact_user("alice").unwrap();
+        assert_eq!(report.records_kept, 2);
+        assert!(report.bytes_reclaimed() > 0);
+        assert_eq!(std::fs::metadata(&bob_log).unwrap().len(), bob_size);
+
+        drop(storage);
+        let reopened = MemoryStorage::new(storage_dir).unwrap();
+        for user in ["alice", "bob"] {
+            assert_eq!(reopened.user_records(user).unwrap().len(), 2);
+        }
+    }
+
+    #[test]
+    fn test_disk_usage_per_user_and_session() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let storage_dir = temp_dir.path().to_str().unwrap();
+        let mut storage = MemoryStorage::new(storage_dir).unwrap();
+
+        let memory = |session: &str, content: &str| MemoryItem {
+            user_id: "alice".to_string(),
+            session_id: session.to_string(),
+            content: content.to_string(),
+            ..Default::default()
+        };
+        for (session, content) in [("s1", "short"), ("s1", "a somewhat longer memory"), ("s2", "another one")] {
+            storage.save(memory(session, content)).unwrap();
+        }
+        storage.save(MemoryItem { user_id: "bob".to_string(), ..memory("s1", "bob's") }).unwrap();
+
+        let alice_log = temp_dir.path().join(segments::user_log("alice"));
+        let alice = storage.user_disk_usage("alice");
+        // Records only: the log's header is not billed to the user
+        assert_eq!(alice + record::LOG_HEADER_LEN, std::fs::metadata(&alice_log).unwrap().len());
+        assert_eq!(storage.session_disk_usage("alice", "s1") + storage.session_disk_usage("alice", "s2"), alice);
+        assert_eq!(storage.disk_usage().by_session["alice"]["s2"], storage.session_disk_usage("alice", "s2"));
+
+        // Deleting a memory takes it off the bill before compaction reclaims the space
+        let (position, deleted) = storage.user_records("alice").unwrap().remove(0);
+        storage.delete_positions(&HashSet::from([position]), AuditAction::Delete).unwrap();
+        let after_delete = storage.user_disk_usage("alice");
+        assert_eq!(after_delete, alice - MemoryStorage::record_size(&deleted));
+
+        storage.compact().unwrap();
+        assert_eq!(storage.user_disk_usage("alice"), after_delete);
+        assert_eq!(std::fs::metadata(&alice_log).unwrap().len(), after_delete + record::LOG_HEADER_LEN);
+
+        drop(storage);
+        let reopened = MemoryStorage::new(storage_dir).unwrap();
+        assert_eq!(reopened.user_disk_usage("alice"), after_delete);
+        assert!(reopened.user_disk_usage("bob") > 0);
+        assert_eq!(reopened.user_disk_usage("carol"), 0);
+    }
+
+    #[test]
+    fn test_index_changes_are_appended_to_the_index_log() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let storage_dir = temp_dir.path().to_str().unwrap();
+        let mut storage = MemoryStorage::new(storage_dir).unwrap();
+        let memory = |content: &str| MemoryItem {
+            user_id: "alice".to_string(),
+            session_id: "s1".to_string(),
+            content: content.to_string(),
+            ..Default::default()
+        };
+        let first = storage.save(memory("Gold is up")).unwrap();
+        let snapshot = std::fs::read(temp_dir.path().join(INDEX_BLOB)).unwrap();
+
+        // Later writes leave the snapshot alone
+        let second = storage.save(memory("Silver is flat")).unwrap();
+        storage.save(memory("Bronze is down")).unwrap();
+        storage.overwrite(MemoryItem { id: first.clone(), ..memory("Gold is up again") }).unwrap();
+        let (position, _) = storage.user_records("alice").unwrap().into_iter().find(|(_, m)| m.id == second).unwrap();
+        storage.delete_positions(&HashSet::from([position]), AuditAction::Delete).unwrap();
+        assert_eq!(std::fs::read(temp_dir.path().join(INDEX_BLOB)).unwrap(), snapshot);
+
+        drop(storage);
+        let reopened = MemoryStorage::new(storage_dir).unwrap();
+        let mut contents: Vec<String> = reopened.user_records("alice").unwrap().into_iter().map(|(_, m)| m.content).collect();
+        contents.sort();
+        assert_eq!(contents, ["Bronze is down", "Gold is up again"]);
+        assert_eq!(reopened.get_session_memories("alice", "s1").unwrap().len(), 2);
+
+        // Compaction writes a new snapshot, leaving the old log stale
+        reopened.compact().unwrap();
+        drop(reopened);
+        let reopened = MemoryStorage::new(storage_dir).unwrap();
+        assert_eq!(reopened.user_records("alice").unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_term_filters_skip_users_without_the_keywords() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let mut storage = MemoryStorage::new(temp_dir.path().to_str().unwrap()).unwrap();
+        let memory = |user: &str, content: String| MemoryItem {
+            user_id: user.to_string(),
+            session_id: "s1".to_string(),
+            content,
+            ..Default::default()
+        };
+        for i in 0..10 {
+            storage.save(memory("alice", format!("Bought AAPL at {}", 100 + i))).unwrap();
+            storage.save(memory("bob", format!("Jazz club night {}", i))).unwrap();
+        }
+
+        // Multi-word keywords need a scan, but only of bob's records
+        let filter = QueryFilter { keywords: Some(vec!["jazz club".to_string()]), ..Default::default() };
+        let plan = storage.explain(&filter);
+        assert_eq!((plan.access_path, plan.estimated_candidates), (AccessPath::FullScan, 10));
+        assert_eq!(storage.recall(filter).unwrap().len(), 10);
+
+        let alice = QueryFilter { user_id: Some("alice".to_string()), keywords: Some(vec!["jazz club".to_string()]), ..Default::default() };
+        assert_eq!(storage.explain(&alice).estimated_candidates, 0);
+        assert!(storage.recall(alice).unwrap().is_empty());
+        assert!(storage.index_stats().filter_bytes > 0);
+    }
+}
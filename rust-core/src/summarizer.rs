@@ -0,0 +1,257 @@
+//! Pluggable summarization
+//!
+//! Session summaries and decay compression both turn a group of memories
+//! into text through a [`Summarizer`]. The default [`ExtractiveSummarizer`]
+//! needs no model: it reports counts and key topics and quotes from the
+//! memories. Applications can plug in an LLM with a closure, or with
+//! [`from_async`] when their client is async.
+//!
+//! If a summarizer returns an error the extractive summary is used instead,
+//! so a flaky model never blocks decay.
+
+use std::future::Future;
+use std::pin::pin;
+use std::sync::Arc;
+use std::task::{Context, Poll, Wake, Waker};
+use std::thread::{self, Thread};
+use serde::{Deserialize, Serialize};
+use crate::storage::MemoryItem;
+use crate::text;
+
+/// Longest quote of each summary in an extractive digest, in graphemes
+const DIGEST_LINE_CHARS: usize = 160;
+
+/// Longest quote of the latest memory in an extractive session summary
+const SESSION_QUOTE_CHARS: usize = 100;
+
+/// Longest joined content in an extractive compression summary
+const COMPRESSION_CHARS: usize = 200;
+
+/// What the summary is for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SummaryKind {
+    /// `summarize_session` and decay's session summaries
+    Session,
+    /// Old, low-importance memories folded together by decay
+    Compression,
+    /// Lower-level summaries rolled up into a daily or user digest; each
+    /// memory's content is one summary
+    Digest,
+}
+
+/// Input to a summarizer
+#[derive(Debug, Clone)]
+pub struct SummaryRequest<'a> {
+    pub kind: SummaryKind,
+    /// The memories to summarize, in storage order
+    pub memories: &'a [MemoryItem],
+    /// Most frequent content words, most frequent first
+    pub key_topics: &'a [String],
+}
+
+/// Turns a group of memories into summary text
+pub trait Summarizer: Send + Sync {
+    fn summarize(&self, request: &SummaryRequest<'_>) -> Result<String, Box<dyn std::error::Error>>;
+}
+
+impl<F> Summarizer for F
+where
+    F: Fn(&SummaryRequest<'_>) -> Result<String, Box<dyn std::error::Error>> + Send + Sync,
+{
+    fn summarize(&self, request: &SummaryRequest<'_>) -> Result<String, Box<dyn std::error::Error>> {
+        self(request)
+    }
+}
+
+/// Summaries built from the memories' own text
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ExtractiveSummarizer;
+
+impl ExtractiveSummarizer {
+    fn session_summary(memories: &[MemoryItem], key_topics: &[String]) -> String {
+        let total_memories = memories.len();
+        let date_span = if memories.len() > 1 {
+            let start = memories.iter().map(|m| m.timestamp).min().unwrap();
+            let end = memories.iter().map(|m| m.timestamp).max().unwrap();
+            let days = (end - start).num_days();
+            format!(" over {} days", days)
+        } else {
+            String::new()
+        };
+
+        let topics_text = if !key_topics.is_empty() {
+            format!(" Key topics: {}.", key_topics.join(", "))
+        } else {
+            String::new()
+        };
+
+        format!(
+            "Session contains {} memories{}.{} Most recent: \"{}\"",
+            total_memories,
+            date_span,
+            topics_text,
+            memories.first().map(|m| text::preview(&m.content, SESSION_QUOTE_CHARS)).unwrap_or_default()
+        )
+    }
+
+    fn compression_summary(memories: &[MemoryItem]) -> String {
+        let combined_content: String = memories.iter()
+            .map(|m| m.content.as_str())
+            .collect::<Vec<&str>>()
+            .join(" | ");
+
+        // First memory + count
+        let truncated = text::truncate(&combined_content, COMPRESSION_CHARS);
+        if truncated.len() < combined_content.len() {
+            format!("{}{} [+{} more memories]",
+                   truncated,
+                   text::ELLIPSIS,
+                   memories.len() - 1)
+        } else {
+            combined_content
+        }
+    }
+
+    fn digest_summary(summaries: &[MemoryItem], key_topics: &[String]) -> String {
+        let mut digest = format!("{} summaries", summaries.len());
+        if let (Some(start), Some(end)) = (
+            summaries.iter().map(|m| m.timestamp).min(),
+            summaries.iter().map(|m| m.timestamp).max(),
+        ) {
+            digest.push_str(&format!(" from {} to {}", start.date_naive(), end.date_naive()));
+        }
+        digest.push('.');
+        if !key_topics.is_empty() {
+            digest.push_str(&format!(" Key topics: {}.", key_topics.join(", ")));
+        }
+
+        for summary in summaries {
+            digest.push_str("\n- ");
+            digest.push_str(&text::preview(&summary.content, DIGEST_LINE_CHARS));
+        }
+        digest
+    }
+
+    /// The summary as a plain function, for fallbacks that can't fail
+    pub fn summary(request: &SummaryRequest<'_>) -> String {
+        match request.kind {
+            SummaryKind::Session => Self::session_summary(request.memories, request.key_topics),
+            SummaryKind::Compression => Self::compression_summary(request.memories),
+            SummaryKind::Digest => Self::digest_summary(request.memories, request.key_topics),
+        }
+    }
+}
+
+impl Summarizer for ExtractiveSummarizer {
+    fn summarize(&self, request: &SummaryRequest<'_>) -> Result<String, Box<dyn std::error::Error>> {
+        Ok(Self::summary(request))
+    }
+}
+
+/// Summarize with `summarizer`, falling back to the extractive summary on error
+pub fn summarize_or_fallback(summarizer: &dyn Summarizer, request: &SummaryRequest<'_>) -> String {
+    match summarizer.summarize(request) {
+        Ok(text) => text,
+        Err(e) => {
+            println!("Summarizer failed, using extractive summary: {}", e);
+            ExtractiveSummarizer::summary(request)
+        }
+    }
+}
+
+/// An owned copy of a request, for async summarizers
+#[derive(Debug, Clone)]
+pub struct OwnedSummaryRequest {
+    pub kind: SummaryKind,
+    pub memories: Vec<MemoryItem>,
+    pub key_topics: Vec<String>,
+}
+
+/// Wrap an async callback, e.g. an HTTP call to an LLM, as a summarizer
+///
+/// Summaries are produced on the calling thread, which blocks until the
+/// future completes. Inside a tokio runtime, call MindCache from
+/// `spawn_blocking` (as the bundled servers do) so runtime-bound clients
+/// still work.
+pub fn from_async<F, Fut>(callback: F) -> impl Summarizer
+where
+    F: Fn(OwnedSummaryRequest) -> Fut + Send + Sync,
+    Fut: Future<Output = Result<String, Box<dyn std::error::Error>>>,
+{
+    move |request: &SummaryRequest<'_>| {
+        block_on(callback(OwnedSummaryRequest {
+            kind: request.kind,
+            memories: request.memories.to_vec(),
+            key_topics: request.key_topics.to_vec(),
+        }))
+    }
+}
+
+struct ThreadWaker(Thread);
+
+impl Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+/// Drive a future to completion on the current thread
+fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = pin!(future);
+    let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+    let mut context = Context::from_waker(&waker);
+    loop {
+        match future.as_mut().poll(&mut context) {
+            Poll::Ready(output) => return output,
+            Poll::Pending => thread::park(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn memories() -> Vec<MemoryItem> {
+        ["Bought gold", "Sold gold"]
+            .iter()
+            .map(|content| MemoryItem { content: content.to_string(), ..Default::default() })
+            .collect()
+    }
+
+    #[test]
+    fn test_callbacks_and_fallback() {
+        let memories = memories();
+        let topics = vec!["gold".to_string()];
+        let request = SummaryRequest { kind: SummaryKind::Session, memories: &memories, key_topics: &topics };
+
+        let extractive = summarize_or_fallback(&ExtractiveSummarizer, &request);
+        assert!(extractive.contains("Key topics: gold"));
+
+        let llm = |request: &SummaryRequest<'_>| -> Result<String, Box<dyn std::error::Error>> {
+            Ok(format!("{} notes about {}", request.memories.len(), request.key_topics.join(", ")))
+        };
+        assert_eq!(summarize_or_fallback(&llm, &request), "2 notes about gold");
+
+        let failing = |_: &SummaryRequest<'_>| -> Result<String, Box<dyn std::error::Error>> { Err("model offline".into()) };
+        assert_eq!(summarize_or_fallback(&failing, &request), extractive);
+
+        let async_llm = from_async(|request: OwnedSummaryRequest| async move {
+            Ok(format!("async summary of {} memories", request.memories.len()))
+        });
+        assert_eq!(summarize_or_fallback(&async_llm, &request), "async summary of 2 memories");
+    }
+
+    #[test]
+    fn test_extractive_summaries_cut_multibyte_content() {
+        let memories: Vec<MemoryItem> = ["黄金价格上涨🚀".repeat(30), "買い増し".repeat(40)]
+            .into_iter()
+            .map(|content| MemoryItem { content, ..Default::default() })
+            .collect();
+        for kind in [SummaryKind::Session, SummaryKind::Compression, SummaryKind::Digest] {
+            let summary = ExtractiveSummarizer.summarize(&SummaryRequest { kind, memories: &memories, key_topics: &[] }).unwrap();
+            assert!(summary.contains(text::ELLIPSIS), "{:?}: {}", kind, summary);
+        }
+    }
+}
@@ -0,0 +1,176 @@
+//! Cached session summaries
+//!
+//! Summarizing a session reads all of its memories and may call out to an
+//! LLM. `summarize_session` keeps each summary in `summaries.json` with a
+//! fingerprint of the session's record positions in the index. Saves,
+//! updates and deletes all change those positions, so a summary is reused
+//! only while the session is exactly as it was summarized, and checking
+//! that reads no records. Compaction renumbers positions and so invalidates
+//! every cached summary.
+//!
+//! Summarizers are set anew on every start, so setting one keeps the cache;
+//! call `MindCache::clear_summary_cache` after switching to a different one.
+
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use serde::{Deserialize, Serialize};
+use crate::backend::StorageBackend;
+use crate::session::SessionSummary;
+use crate::MindCache;
+
+pub const SUMMARIES_BLOB: &str = "summaries.json";
+
+/// A summary and the fingerprint of the session it was made from
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CachedSummary {
+    fingerprint: u64,
+    summary: SessionSummary,
+}
+
+/// Persistent summaries, keyed by session id
+#[derive(Clone)]
+pub struct SummaryCache {
+    backend: Arc<dyn StorageBackend>,
+    entries: Arc<RwLock<HashMap<String, CachedSummary>>>,
+}
+
+impl SummaryCache {
+    pub fn load(backend: Arc<dyn StorageBackend>) -> Result<Self, Box<dyn std::error::Error>> {
+        let entries = Self::read_entries(backend.as_ref())?;
+        Ok(SummaryCache { backend, entries: Arc::new(RwLock::new(entries)) })
+    }
+
+    /// Re-read the cache from disk, e.g. after a restore
+    pub fn reload(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let entries = Self::read_entries(self.backend.as_ref())?;
+        *self.entries.write().unwrap_or_else(|poisoned| poisoned.into_inner()) = entries;
+        Ok(())
+    }
+
+    fn read_entries(backend: &dyn StorageBackend) -> Result<HashMap<String, CachedSummary>, Box<dyn std::error::Error>> {
+        match backend.read_blob(SUMMARIES_BLOB)? {
+            Some(data) => Ok(serde_json::from_slice(&data)?),
+            None => Ok(HashMap::new()),
+        }
+    }
+
+    /// The summary of a session, if it was made at `fingerprint`
+    pub fn get(&self, session_id: &str, fingerprint: u64) -> Option<SessionSummary> {
+        let entries = self.entries.read().unwrap_or_else(|poisoned| poisoned.into_inner());
+        entries
+            .get(session_id)
+            .filter(|entry| entry.fingerprint == fingerprint)
+            .map(|entry| entry.summary.clone())
+    }
+
+    pub fn put(&self, fingerprint: u64, summary: SessionSummary) -> Result<(), Box<dyn std::error::Error>> {
+        self.update(|entries| {
+            entries.insert(summary.session_id.clone(), CachedSummary { fingerprint, summary });
+        })
+    }
+
+    pub fn remove(&self, session_id: &str) -> Result<(), Box<dyn std::error::Error>> {
+        if !self.entries.read().unwrap_or_else(|poisoned| poisoned.into_inner()).contains_key(session_id) {
+            return Ok(());
+        }
+        self.update(|entries| {
+            entries.remove(session_id);
+        })
+    }
+
+    /// Drop the summaries of a user's sessions; returns how many there were
+    pub fn remove_user(&self, user_id: &str) -> Result<usize, Box<dyn std::error::Error>> {
+        let mut removed = 0;
+        self.update(|entries| {
+            let before = entries.len();
+            entries.retain(|_, entry| entry.summary.user_id != user_id);
+            removed = before - entries.len();
+        })?;
+        Ok(removed)
+    }
+
+    pub fn clear(&self) -> Result<(), Box<dyn std::error::Error>> {
+        self.update(HashMap::clear)
+    }
+
+    /// Apply `edit` and persist, leaving the cache as it was if that fails
+    fn update<F>(&self, edit: F) -> Result<(), Box<dyn std::error::Error>>
+    where
+        F: FnOnce(&mut HashMap<String, CachedSummary>),
+    {
+        let mut entries = self.entries.write().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let previous = entries.clone();
+        edit(&mut entries);
+        let persisted = serde_json::to_vec(&*entries)
+            .map_err(Box::<dyn std::error::Error>::from)
+            .and_then(|data| Ok(self.backend.write_blob(SUMMARIES_BLOB, &data)?));
+        if let Err(e) = persisted {
+            *entries = previous;
+            return Err(e);
+        }
+        Ok(())
+    }
+}
+
+impl MindCache {
+    /// Forget every cached session summary, e.g. after switching summarizers
+    pub fn clear_summary_cache(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        self.storage.summaries().clear()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{MindCache, MemoryUpdate};
+    use crate::summarizer::SummaryRequest;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use crate::test_support::test_config;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_summaries_are_reused_until_the_session_changes() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = test_config(&temp_dir);
+        let calls = Arc::new(AtomicUsize::new(0));
+        let open = |calls: &Arc<AtomicUsize>| {
+            let mut cache = MindCache::with_config(config.clone()).unwrap();
+            let calls = Arc::clone(calls);
+            cache.set_summarizer(move |request: &SummaryRequest| {
+                calls.fetch_add(1, Ordering::SeqCst);
+                Ok(format!("{} memories", request.memories.len()))
+            });
+            cache
+        };
+        let mut cache = open(&calls);
+        let first = cache.save("alice", "s1", "Bought gold", None).unwrap();
+        cache.save("alice", "s2", "Other session", None).unwrap();
+
+        assert_eq!(cache.summarize_session("s1").unwrap().summary_text, "1 memories");
+        cache.summarize_session("s1").unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        // Saves elsewhere keep it, saves and updates in the session don't
+        cache.save("alice", "s2", "Still another session", None).unwrap();
+        cache.summarize_session("s1").unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        cache.save("alice", "s1", "Sold silver", None).unwrap();
+        assert_eq!(cache.summarize_session("s1").unwrap().summary_text, "2 memories");
+        cache.update_memory(&first, MemoryUpdate { content: Some("Bought more gold".to_string()), ..Default::default() }).unwrap();
+        cache.summarize_session("s1").unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+
+        // Kept across a restart
+        drop(cache);
+        let mut cache = open(&calls);
+        assert_eq!(cache.summarize_session("s1").unwrap().memory_count, 2);
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+
+        cache.clear_summary_cache().unwrap();
+        cache.summarize_session("s1").unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 4);
+        cache.delete_user("alice").unwrap();
+        assert!(cache.summarize_session("s1").is_err());
+        assert_eq!(std::fs::read_to_string(temp_dir.path().join(super::SUMMARIES_BLOB)).unwrap(), "{}");
+    }
+}
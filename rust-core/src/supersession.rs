@@ -0,0 +1,114 @@
+//! Memories replaced by newer ones
+//!
+//! Facts change: "lives in NYC" stops being true once "moved to Austin" is
+//! saved. `MindCache::supersede` records that with a [`SUPERSEDES`] link
+//! from the new memory to the old one. Recall leaves superseded memories
+//! out unless `QueryFilter::include_superseded` is set, but they stay
+//! stored, decay like any other memory and can be fetched by id or walked
+//! to with `get_related`. Removing the link brings the old memory back.
+
+use crate::MindCache;
+
+/// Relation of the link from a memory to the one it replaced
+pub const SUPERSEDES: &str = "supersedes";
+
+impl MindCache {
+    /// Mark `old_id` as replaced by `new_id`, hiding it from recall
+    ///
+    /// Both memories must belong to the same user, and `new_id` must not be
+    /// superseded itself. Superseding the same memory twice is a no-op.
+    pub fn supersede(&mut self, old_id: &str, new_id: &str) -> Result<(), Box<dyn std::error::Error>> {
+        if old_id == new_id {
+            return Err("a memory cannot supersede itself".into());
+        }
+        let old = self.storage.get_memory(old_id)?.ok_or_else(|| format!("memory {} not found", old_id))?;
+        let new = self.storage.get_memory(new_id)?.ok_or_else(|| format!("memory {} not found", new_id))?;
+        if old.user_id != new.user_id {
+            return Err(format!("memories {} and {} belong to different users", old_id, new_id).into());
+        }
+        let links = self.storage.links();
+        if links.targets(SUPERSEDES).contains(new_id) {
+            return Err(format!("memory {} is itself superseded", new_id).into());
+        }
+        links.link(new_id, old_id, SUPERSEDES)
+    }
+
+    /// The memory that replaced `memory_id`, if it was superseded and its replacement still exists
+    pub fn superseded_by(&self, memory_id: &str) -> Result<Option<String>, Box<dyn std::error::Error>> {
+        for link in self.storage.links().incoming(memory_id).into_iter().rev() {
+            if link.relation == SUPERSEDES && self.storage.get_memory(&link.from_id)?.is_some() {
+                return Ok(Some(link.from_id));
+            }
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::QueryFilter;
+    use crate::test_support::temp_cache;
+    use tempfile::TempDir;
+
+    /// Alice's NYC memory superseded by her Austin one, plus a memory of Bob's
+    fn moved_to_austin() -> (MindCache, TempDir, [String; 3]) {
+        let (mut cache, temp_dir) = temp_cache();
+        let nyc = cache.save("alice", "s1", "Alice lives in NYC", None).unwrap();
+        let austin = cache.save("alice", "s2", "Alice moved to Austin", None).unwrap();
+        let bob = cache.save("bob", "s1", "Bob lives in Boston", None).unwrap();
+        cache.supersede(&nyc, &austin).unwrap();
+        (cache, temp_dir, [nyc, austin, bob])
+    }
+
+    fn alice() -> QueryFilter {
+        QueryFilter { user_id: Some("alice".to_string()), ..Default::default() }
+    }
+
+    #[test]
+    fn test_superseded_memories_leave_default_recall() {
+        let (cache, _temp_dir, [nyc, austin, _]) = moved_to_austin();
+        let recalled = cache.recall("alice", None, None, None).unwrap();
+        assert_eq!(recalled.len(), 1);
+        assert_eq!(recalled[0].id, austin);
+        assert!(cache.recall("alice", Some("NYC"), None, None).unwrap().is_empty());
+        assert_eq!(cache.count(alice()).unwrap(), 1);
+        assert!(cache.get_memory(&nyc).unwrap().is_some());
+    }
+
+    #[test]
+    fn test_include_superseded_recalls_the_history() {
+        let (cache, _temp_dir, _) = moved_to_austin();
+        let with_history = QueryFilter { include_superseded: true, ..alice() };
+        assert_eq!(cache.count(with_history.clone()).unwrap(), 2);
+        assert_eq!(cache.recall_advanced(with_history).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_supersede_rejects_cycles_other_users_and_missing_memories() {
+        let (mut cache, _temp_dir, [nyc, austin, bob]) = moved_to_austin();
+        cache.supersede(&nyc, &austin).unwrap();
+        assert!(cache.supersede(&austin, &nyc).is_err());
+        assert!(cache.supersede(&bob, &austin).is_err());
+        assert!(cache.supersede(&austin, &austin).is_err());
+        assert!(cache.supersede("missing", &austin).is_err());
+    }
+
+    #[test]
+    fn test_supersession_survives_a_reopen() {
+        let (cache, _temp_dir, [nyc, austin, _]) = moved_to_austin();
+        let config = cache.config.clone();
+        drop(cache);
+        let cache = MindCache::with_config(config).unwrap();
+        assert_eq!(cache.superseded_by(&nyc).unwrap(), Some(austin.clone()));
+        assert_eq!(cache.superseded_by(&austin).unwrap(), None);
+        assert_eq!(cache.count(alice()).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_unlinking_brings_the_old_memory_back() {
+        let (mut cache, _temp_dir, [nyc, austin, _]) = moved_to_austin();
+        cache.unlink_memories(&austin, &nyc, Some(SUPERSEDES)).unwrap();
+        assert_eq!(cache.count(alice()).unwrap(), 2);
+    }
+}
@@ -0,0 +1,142 @@
+//! Session templates
+//!
+//! Applications that open many alike sessions, such as a journal per day or
+//! a thread per support ticket, describe them once as a [`SessionTemplate`]
+//! and create each with `create_session_from_template`. The new session
+//! gets the template's name, tags and metadata, and its TTL, memory limit and
+//! importance floor become the session's retention overrides.
+
+use std::collections::HashMap;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use crate::retention::SessionRetention;
+use crate::session::SessionManager;
+use crate::text;
+use crate::MindCache;
+
+/// Session metadata key naming the template a session was created from
+pub const TEMPLATE_KEY: &str = "template";
+
+/// Defaults for sessions created from it
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SessionTemplate {
+    /// Recorded in the metadata of each session under [`TEMPLATE_KEY`]
+    pub name: String,
+    /// Name of each session; `{user}` becomes the user id, `{date}` today's
+    /// date (`YYYY-MM-DD`), `{time}` the time (`HH:MM`) and `{id}` the start
+    /// of the session id. Without one, sessions are unnamed.
+    #[serde(default)]
+    pub name_pattern: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub metadata: HashMap<String, String>,
+    /// Lowest importance a memory saved into the session starts with
+    #[serde(default)]
+    pub importance_floor: Option<f32>,
+    /// Hours the session's memories are kept after being saved; 0 keeps them forever
+    #[serde(default)]
+    pub ttl_hours: Option<u32>,
+    #[serde(default)]
+    pub max_memories: Option<usize>,
+}
+
+impl SessionTemplate {
+    pub fn new(name: impl Into<String>) -> Self {
+        SessionTemplate { name: name.into(), ..Default::default() }
+    }
+
+    /// The retention overrides of sessions created from the template
+    pub fn retention(&self) -> SessionRetention {
+        SessionRetention {
+            ttl_hours: self.ttl_hours,
+            max_memories: self.max_memories,
+            importance_floor: self.importance_floor,
+        }
+    }
+
+    /// The name of a session of `user_id` with id `session_id`, created at `now`
+    pub fn session_name(&self, user_id: &str, session_id: &str, now: DateTime<Utc>) -> Option<String> {
+        let pattern = self.name_pattern.as_ref()?;
+        Some(
+            pattern
+                .replace("{user}", user_id)
+                .replace("{date}", &now.format("%Y-%m-%d").to_string())
+                .replace("{time}", &now.format("%H:%M").to_string())
+                .replace("{id}", text::truncate(session_id, 8)),
+        )
+    }
+}
+
+impl SessionManager {
+    /// Create a session for a user with the name, tags, metadata and
+    /// retention of `template`
+    pub fn create_session_from_template(&mut self, user_id: &str, template: &SessionTemplate) -> Result<String, Box<dyn std::error::Error>> {
+        let session_id = uuid::Uuid::new_v4().to_string();
+        let retention = template.retention();
+        if !retention.is_default() {
+            self.set_session_retention(&session_id, retention)?;
+        }
+        let mut metadata = template.metadata.clone();
+        metadata.insert(TEMPLATE_KEY.to_string(), template.name.clone());
+        let name = template.session_name(user_id, &session_id, self.storage().now());
+        self.register_session(user_id, &session_id, name, metadata)?;
+        if !template.tags.is_empty() {
+            self.update_session(&session_id, None, Some(template.tags.clone()))?;
+        }
+        println!("Created session {} for user {} from template {}", session_id, user_id, template.name);
+        Ok(session_id)
+    }
+}
+
+impl MindCache {
+    /// Create a session set up by `template`; see [`SessionTemplate`]
+    pub fn create_session_from_template(&mut self, user_id: &str, template: &SessionTemplate) -> Result<String, Box<dyn std::error::Error>> {
+        self.session_manager.create_session_from_template(user_id, template)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::test_config;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_sessions_from_template() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = test_config(&temp_dir);
+        let mut cache = MindCache::with_config(config).unwrap();
+        let template = SessionTemplate {
+            name_pattern: Some("Journal {date} ({user})".to_string()),
+            tags: vec!["journal".to_string()],
+            metadata: HashMap::from([("kind".to_string(), "daily".to_string())]),
+            importance_floor: Some(0.6),
+            ttl_hours: Some(0),
+            ..SessionTemplate::new("daily-journal")
+        };
+
+        let session_id = cache.create_session_from_template("alice", &template).unwrap();
+        cache.save("alice", &session_id, "Slept badly", None).unwrap();
+        let session = cache.get_user_sessions("alice").unwrap().into_iter().find(|session| session.id == session_id).unwrap();
+        assert_eq!(session.name.unwrap(), format!("Journal {} (alice)", Utc::now().format("%Y-%m-%d")));
+        assert_eq!(session.tags, ["journal"]);
+        assert_eq!(session.metadata["kind"], "daily");
+        assert_eq!(session.metadata[TEMPLATE_KEY], "daily-journal");
+        assert_eq!(cache.session_retention(&session_id), template.retention());
+        assert!(cache.recall("alice", None, Some(&session_id), None).unwrap()[0].importance >= 0.6);
+
+        let unnamed = cache.create_session_from_template("alice", &SessionTemplate::new("scratch")).unwrap();
+        assert!(cache.session_retention(&unnamed).is_default());
+
+        let invalid = SessionTemplate { importance_floor: Some(2.0), ..SessionTemplate::new("bad") };
+        assert!(cache.create_session_from_template("alice", &invalid).is_err());
+    }
+
+    #[test]
+    fn test_session_name_cuts_ids_at_characters() {
+        let template = SessionTemplate { name_pattern: Some("Chat {id}".to_string()), ..SessionTemplate::new("chat") };
+        assert_eq!(template.session_name("alice", "préférées", Utc::now()).unwrap(), "Chat préférée");
+        assert_eq!(template.session_name("alice", "short", Utc::now()).unwrap(), "Chat short");
+    }
+}
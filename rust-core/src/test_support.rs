@@ -0,0 +1,38 @@
+//! Caches for unit tests
+//!
+//! Every test store lives in its own temp dir with background decay off, so
+//! tests decide when decay runs.
+
+use tempfile::TempDir;
+use crate::{MindCache, MindCacheConfig};
+
+/// The config for a test store in `dir`
+pub(crate) fn test_config(dir: &TempDir) -> MindCacheConfig {
+    MindCacheConfig {
+        storage_path: dir.path().to_string_lossy().into_owned(),
+        auto_decay_enabled: false,
+        ..Default::default()
+    }
+}
+
+/// A cache with the default config in a fresh temp dir
+///
+/// The store is deleted when the returned `TempDir` drops, so keep it bound
+/// for as long as the cache is used.
+pub(crate) fn temp_cache() -> (MindCache, TempDir) {
+    temp_cache_with(MindCacheConfig::default())
+}
+
+/// A cache built from `config` in a fresh temp dir
+///
+/// `storage_path` and `auto_decay_enabled` are overridden as in [`test_config`].
+pub(crate) fn temp_cache_with(config: MindCacheConfig) -> (MindCache, TempDir) {
+    let temp_dir = TempDir::new().unwrap();
+    let cache = MindCache::with_config(MindCacheConfig {
+        storage_path: test_config(&temp_dir).storage_path,
+        auto_decay_enabled: false,
+        ..config
+    })
+    .unwrap();
+    (cache, temp_dir)
+}
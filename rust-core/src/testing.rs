@@ -0,0 +1,280 @@
+//! Error-injection harness for storage backends
+//!
+//! [`FailingStorage`] wraps any [`StorageBackend`] and fails selected
+//! operations on demand, so recovery paths in MindCache and in downstream
+//! custom backends can be tested against realistic IO failures.
+
+use std::io;
+use std::sync::{Arc, Mutex};
+
+use crate::backend::StorageBackend;
+use crate::index_log::INDEX_LOG;
+
+/// Operation a failure can be injected into
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FailurePoint {
+    /// Appending a record to a log
+    Write,
+    /// Making appended records durable
+    Flush,
+    /// Replacing an index blob or appending to the index log
+    IndexPersist,
+    /// Reading records or blobs
+    Read,
+}
+
+/// When an operation should fail
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FailureMode {
+    Never,
+    Always,
+    /// Succeed for the first `n` calls, then fail every call
+    AfterCalls(u64),
+    /// Fail only the `n`th call (1-based)
+    OnCall(u64),
+    /// Fail each call with the given probability (0.0 to 1.0)
+    Rate(f64),
+}
+
+/// Failure configuration for each operation
+#[derive(Debug, Clone, PartialEq)]
+pub struct FailurePlan {
+    pub write: FailureMode,
+    pub flush: FailureMode,
+    pub index_persist: FailureMode,
+    pub read: FailureMode,
+    /// Failed writes still append the first half of the record, like a crash mid-write
+    pub torn_writes: bool,
+    /// Seed for `FailureMode::Rate`, so randomized runs are reproducible
+    pub seed: u64,
+}
+
+impl Default for FailurePlan {
+    fn default() -> Self {
+        FailurePlan {
+            write: FailureMode::Never,
+            flush: FailureMode::Never,
+            index_persist: FailureMode::Never,
+            read: FailureMode::Never,
+            torn_writes: false,
+            seed: 0x5eed,
+        }
+    }
+}
+
+impl FailurePlan {
+    /// Plan that fails a single operation according to `mode`
+    pub fn fail(point: FailurePoint, mode: FailureMode) -> Self {
+        let mut plan = FailurePlan::default();
+        match point {
+            FailurePoint::Write => plan.write = mode,
+            FailurePoint::Flush => plan.flush = mode,
+            FailurePoint::IndexPersist => plan.index_persist = mode,
+            FailurePoint::Read => plan.read = mode,
+        }
+        plan
+    }
+
+    fn mode(&self, point: FailurePoint) -> FailureMode {
+        match point {
+            FailurePoint::Write => self.write,
+            FailurePoint::Flush => self.flush,
+            FailurePoint::IndexPersist => self.index_persist,
+            FailurePoint::Read => self.read,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct FailureState {
+    calls: [u64; 4],
+    injected: u64,
+    rng: u64,
+}
+
+/// Backend wrapper that injects failures according to a [`FailurePlan`]
+pub struct FailingStorage {
+    inner: Arc<dyn StorageBackend>,
+    plan: Mutex<FailurePlan>,
+    state: Mutex<FailureState>,
+}
+
+impl FailingStorage {
+    pub fn new(inner: Arc<dyn StorageBackend>, plan: FailurePlan) -> Self {
+        let state = FailureState {
+            rng: plan.seed.max(1),
+            ..FailureState::default()
+        };
+        FailingStorage {
+            inner,
+            plan: Mutex::new(plan),
+            state: Mutex::new(state),
+        }
+    }
+
+    /// Replace the plan, e.g. to "heal" the backend mid-test (call counters are reset)
+    pub fn set_plan(&self, plan: FailurePlan) {
+        let mut state = self.state.lock().unwrap_or_else(|p| p.into_inner());
+        state.calls = [0; 4];
+        state.rng = plan.seed.max(1);
+        *self.plan.lock().unwrap_or_else(|p| p.into_inner()) = plan;
+    }
+
+    /// Number of calls seen for an operation since the plan was set
+    pub fn calls(&self, point: FailurePoint) -> u64 {
+        self.state.lock().unwrap_or_else(|p| p.into_inner()).calls[slot(point)]
+    }
+
+    /// Total number of failures injected so far
+    pub fn injected_failures(&self) -> u64 {
+        self.state.lock().unwrap_or_else(|p| p.into_inner()).injected
+    }
+
+    /// The wrapped backend, for inspecting what actually reached storage
+    pub fn inner(&self) -> &Arc<dyn StorageBackend> {
+        &self.inner
+    }
+
+    fn check(&self, point: FailurePoint) -> io::Result<()> {
+        let mode = self.plan.lock().unwrap_or_else(|p| p.into_inner()).mode(point);
+        let mut state = self.state.lock().unwrap_or_else(|p| p.into_inner());
+        state.calls[slot(point)] += 1;
+        let call = state.calls[slot(point)];
+
+        let fail = match mode {
+            FailureMode::Never => false,
+            FailureMode::Always => true,
+            FailureMode::AfterCalls(n) => call > n,
+            FailureMode::OnCall(n) => call == n,
+            FailureMode::Rate(rate) => next_unit(&mut state.rng) < rate,
+        };
+
+        if fail {
+            state.injected += 1;
+            return Err(io::Error::other(format!("injected {:?} failure (call {})", point, call)));
+        }
+        Ok(())
+    }
+
+    fn torn_writes(&self) -> bool {
+        self.plan.lock().unwrap_or_else(|p| p.into_inner()).torn_writes
+    }
+}
+
+fn slot(point: FailurePoint) -> usize {
+    match point {
+        FailurePoint::Write => 0,
+        FailurePoint::Flush => 1,
+        FailurePoint::IndexPersist => 2,
+        FailurePoint::Read => 3,
+    }
+}
+
+/// xorshift64*, mapped to [0, 1)
+fn next_unit(state: &mut u64) -> f64 {
+    let mut x = *state;
+    x ^= x >> 12;
+    x ^= x << 25;
+    x ^= x >> 27;
+    *state = x;
+    (x.wrapping_mul(0x2545_f491_4f6c_dd1d) >> 11) as f64 / (1u64 << 53) as f64
+}
+
+impl StorageBackend for FailingStorage {
+    fn append(&self, name: &str, data: &[u8]) -> io::Result<u64> {
+        let point = if name == INDEX_LOG { FailurePoint::IndexPersist } else { FailurePoint::Write };
+        if let Err(e) = self.check(point) {
+            if self.torn_writes() && data.len() > 1 {
+                let _ = self.inner.append(name, &data[..data.len() / 2]);
+            }
+            return Err(e);
+        }
+        self.inner.append(name, data)
+    }
+
+    fn read_at(&self, name: &str, offset: u64, len: usize) -> io::Result<Vec<u8>> {
+        self.check(FailurePoint::Read)?;
+        self.inner.read_at(name, offset, len)
+    }
+
+    fn flush(&self, name: &str) -> io::Result<()> {
+        self.check(FailurePoint::Flush)?;
+        self.inner.flush(name)
+    }
+
+    fn read_blob(&self, name: &str) -> io::Result<Option<Vec<u8>>> {
+        self.check(FailurePoint::Read)?;
+        self.inner.read_blob(name)
+    }
+
+    fn write_blob(&self, name: &str, data: &[u8]) -> io::Result<()> {
+        self.check(FailurePoint::IndexPersist)?;
+        self.inner.write_blob(name, data)
+    }
+
+    fn size(&self, name: &str) -> io::Result<u64> {
+        self.inner.size(name)
+    }
+
+    fn remove(&self, name: &str) -> io::Result<()> {
+        self.inner.remove(name)
+    }
+
+    fn shred(&self, name: &str) -> io::Result<()> {
+        self.inner.shred(name)
+    }
+
+    fn rename(&self, from: &str, to: &str) -> io::Result<()> {
+        // Renames only happen during compaction, which persists index blobs around them
+        self.check(FailurePoint::IndexPersist)?;
+        self.inner.rename(from, to)
+    }
+
+    fn location(&self) -> String {
+        format!("failing({})", self.inner.location())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::FileBackend;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_failure_modes() {
+        let temp_dir = TempDir::new().unwrap();
+        let inner: Arc<dyn StorageBackend> = Arc::new(FileBackend::new(temp_dir.path()).unwrap());
+        let failing = FailingStorage::new(inner, FailurePlan::fail(FailurePoint::Write, FailureMode::OnCall(2)));
+
+        assert!(failing.append("log.bin", b"one").is_ok());
+        assert!(failing.append("log.bin", b"two").is_err());
+        assert!(failing.append("log.bin", b"three").is_ok());
+        assert_eq!(failing.calls(FailurePoint::Write), 3);
+        assert_eq!(failing.injected_failures(), 1);
+
+        failing.set_plan(FailurePlan {
+            write: FailureMode::Always,
+            torn_writes: true,
+            ..FailurePlan::default()
+        });
+        assert!(failing.append("log.bin", b"torn").is_err());
+        assert_eq!(failing.inner().size("log.bin").unwrap(), 3 + 5 + 2);
+    }
+
+    #[test]
+    fn test_rate_is_reproducible() {
+        let temp_dir = TempDir::new().unwrap();
+        let inner: Arc<dyn StorageBackend> = Arc::new(FileBackend::new(temp_dir.path()).unwrap());
+        let plan = FailurePlan::fail(FailurePoint::Flush, FailureMode::Rate(0.5));
+
+        let failing = FailingStorage::new(Arc::clone(&inner), plan.clone());
+        let first: Vec<bool> = (0..32).map(|_| failing.flush("log.bin").is_err()).collect();
+
+        failing.set_plan(plan);
+        let second: Vec<bool> = (0..32).map(|_| failing.flush("log.bin").is_err()).collect();
+
+        assert_eq!(first, second);
+        assert!(first.iter().any(|&f| f) && first.iter().any(|&f| !f));
+    }
+}
@@ -0,0 +1,68 @@
+//! Unicode-safe text truncation
+//!
+//! Previews and summaries cut memory content to a length. Slicing a `str`
+//! at a byte offset panics inside a multi-byte character, and cutting
+//! between `char`s can still split an emoji sequence or a letter from its
+//! accent, so lengths here count grapheme clusters.
+
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Marker appended by [`preview`] to text it shortened
+pub const ELLIPSIS: &str = "...";
+
+/// The first `max_graphemes` grapheme clusters of `text`
+pub fn truncate(text: &str, max_graphemes: usize) -> &str {
+    match text.grapheme_indices(true).nth(max_graphemes) {
+        Some((end, _)) => &text[..end],
+        None => text,
+    }
+}
+
+/// The longest start of `text` of at most `max_bytes` bytes that ends on a
+/// grapheme boundary
+pub fn truncate_bytes(text: &str, max_bytes: usize) -> &str {
+    if text.len() <= max_bytes {
+        return text;
+    }
+    let end = text
+        .grapheme_indices(true)
+        .map(|(start, _)| start)
+        .take_while(|start| *start <= max_bytes)
+        .last()
+        .unwrap_or(0);
+    &text[..end]
+}
+
+/// `text` cut to `max_graphemes` grapheme clusters, with [`ELLIPSIS`]
+/// appended if anything was cut
+pub fn preview(text: &str, max_graphemes: usize) -> String {
+    let truncated = truncate(text, max_graphemes);
+    if truncated.len() < text.len() {
+        format!("{}{}", truncated, ELLIPSIS)
+    } else {
+        text.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_truncation_respects_graphemes() {
+        assert_eq!(truncate("hello", 10), "hello");
+        assert_eq!(truncate("hello", 2), "he");
+        assert_eq!(truncate("黄金价格上涨", 2), "黄金");
+        // A family emoji is several chars joined into one grapheme
+        assert_eq!(truncate("👨‍👩‍👧 family", 1), "👨‍👩‍👧");
+        assert_eq!(truncate("e\u{301}clair", 1), "e\u{301}");
+
+        assert_eq!(preview("短い", 5), "短い");
+        assert_eq!(preview("Gold 🚀🚀🚀", 6), "Gold 🚀...");
+        assert_eq!(preview("", 0), "");
+
+        assert_eq!(truncate_bytes("hello", 5), "hello");
+        assert_eq!(truncate_bytes("黄金价格", 7), "黄金");
+        assert_eq!(truncate_bytes("e\u{301}clair", 2), "");
+    }
+}
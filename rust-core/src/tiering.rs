@@ -0,0 +1,382 @@
+//! Two-tier storage: a hot tier in front of the segment logs
+//!
+//! Recent and important memories are copied into a [`HotTier`], typically
+//! Redis ([`RedisTier`], behind the `redis` feature), so reads that miss the
+//! in-process LRU cache are still served without touching the logs. The logs
+//! stay the source of truth; the hot tier only ever holds copies and can be
+//! flushed at any time.
+//!
+//! A memory is admitted when it is saved, updated or read from the logs and
+//! [`HotTierPolicy`] allows it. Each decay run demotes memories that have
+//! aged past `recent_hours` and fall below `min_importance`; memories that
+//! are deleted, expired or compressed leave the hot tier with them.
+//!
+//! The tier is emptied when it is attached, so it starts cold on every open
+//! and fills as memories are saved and read. Compaction moves records and
+//! drops the memories it moves from the hot tier as well.
+
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use crate::storage::MemoryItem;
+
+/// Where hot memories live, keyed by memory id
+///
+/// Errors are not fatal: a failed read falls back to the logs and a failed
+/// write leaves the memory cold.
+pub trait HotTier: Send + Sync {
+    fn get(&self, id: &str) -> Result<Option<MemoryItem>, Box<dyn std::error::Error>>;
+    fn put(&self, memory: &MemoryItem) -> Result<(), Box<dyn std::error::Error>>;
+    fn remove(&self, id: &str) -> Result<(), Box<dyn std::error::Error>>;
+    /// Drop every memory this tier holds
+    fn clear(&self) -> Result<(), Box<dyn std::error::Error>>;
+}
+
+/// Which memories are kept in the hot tier
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HotTierPolicy {
+    /// Memories at least this important stay hot regardless of age
+    pub min_importance: f32,
+    /// Memories saved within this many hours are hot
+    pub recent_hours: u32,
+    /// Most memories held at once; once full, new memories stay cold
+    /// until decay demotes some
+    pub max_memories: usize,
+}
+
+impl Default for HotTierPolicy {
+    fn default() -> Self {
+        HotTierPolicy { min_importance: 0.7, recent_hours: 24, max_memories: 10_000 }
+    }
+}
+
+impl HotTierPolicy {
+    /// Whether a memory of this importance saved at `timestamp` belongs in the hot tier at `now`
+    pub fn admits(&self, importance: f32, timestamp: DateTime<Utc>, now: DateTime<Utc>) -> bool {
+        importance >= self.min_importance || now - timestamp <= Duration::hours(self.recent_hours as i64)
+    }
+}
+
+/// Hot tier in this process's memory
+///
+/// For tests, and for single-process deployments that want a hot set larger
+/// than the LRU cache without running Redis.
+#[derive(Default)]
+pub struct InMemoryTier {
+    memories: Mutex<HashMap<String, MemoryItem>>,
+}
+
+impl InMemoryTier {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of memories held
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lock().is_empty()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<String, MemoryItem>> {
+        self.memories.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl HotTier for InMemoryTier {
+    fn get(&self, id: &str) -> Result<Option<MemoryItem>, Box<dyn std::error::Error>> {
+        Ok(self.lock().get(id).cloned())
+    }
+
+    fn put(&self, memory: &MemoryItem) -> Result<(), Box<dyn std::error::Error>> {
+        self.lock().insert(memory.id.clone(), memory.clone());
+        Ok(())
+    }
+
+    fn remove(&self, id: &str) -> Result<(), Box<dyn std::error::Error>> {
+        self.lock().remove(id);
+        Ok(())
+    }
+
+    fn clear(&self) -> Result<(), Box<dyn std::error::Error>> {
+        self.lock().clear();
+        Ok(())
+    }
+}
+
+/// Hot tier in Redis, one JSON value per memory
+///
+/// Keys are `{prefix}{memory id}`, plus a set at `{prefix}ids` naming them
+/// so `clear` leaves other keys in the database alone. Give each store its
+/// own prefix when several share a Redis instance.
+#[cfg(feature = "redis")]
+pub struct RedisTier {
+    client: redis::Client,
+    prefix: String,
+    connection: Mutex<Option<redis::Connection>>,
+}
+
+#[cfg(feature = "redis")]
+impl RedisTier {
+    /// Default key prefix
+    pub const DEFAULT_PREFIX: &'static str = "mindcache:hot:";
+
+    /// Connect to the Redis server at `url`, e.g. `redis://127.0.0.1:6379/0`
+    pub fn open(url: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        Self::with_prefix(url, Self::DEFAULT_PREFIX)
+    }
+
+    pub fn with_prefix(url: &str, prefix: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let client = redis::Client::open(url)?;
+        let connection = client.get_connection()?;
+        Ok(RedisTier { client, prefix: prefix.to_string(), connection: Mutex::new(Some(connection)) })
+    }
+
+    fn key(&self, id: &str) -> String {
+        format!("{}{}", self.prefix, id)
+    }
+
+    fn ids_key(&self) -> String {
+        format!("{}ids", self.prefix)
+    }
+
+    /// Run `command` on the shared connection, reconnecting once it has failed
+    fn with_connection<T>(
+        &self,
+        command: impl FnOnce(&mut redis::Connection) -> redis::RedisResult<T>,
+    ) -> Result<T, Box<dyn std::error::Error>> {
+        let mut slot = self.connection.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let connection = match slot.as_mut() {
+            Some(connection) => connection,
+            None => slot.insert(self.client.get_connection()?),
+        };
+        command(connection).map_err(|e| {
+            // A broken connection is replaced on the next call
+            if e.is_io_error() || e.is_connection_dropped() {
+                *slot = None;
+            }
+            e.into()
+        })
+    }
+}
+
+#[cfg(feature = "redis")]
+impl HotTier for RedisTier {
+    fn get(&self, id: &str) -> Result<Option<MemoryItem>, Box<dyn std::error::Error>> {
+        let key = self.key(id);
+        let value: Option<String> = self.with_connection(|connection| redis::cmd("GET").arg(&key).query(connection))?;
+        Ok(value.map(|json| serde_json::from_str(&json)).transpose()?)
+    }
+
+    fn put(&self, memory: &MemoryItem) -> Result<(), Box<dyn std::error::Error>> {
+        let json = serde_json::to_string(memory)?;
+        let (key, ids_key) = (self.key(&memory.id), self.ids_key());
+        self.with_connection(|connection| {
+            redis::pipe().atomic().set(&key, &json).ignore().sadd(&ids_key, &memory.id).ignore().query(connection)
+        })
+    }
+
+    fn remove(&self, id: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let (key, ids_key) = (self.key(id), self.ids_key());
+        self.with_connection(|connection| {
+            redis::pipe().atomic().del(&key).ignore().srem(&ids_key, id).ignore().query(connection)
+        })
+    }
+
+    fn clear(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let ids_key = self.ids_key();
+        let ids: Vec<String> = self.with_connection(|connection| redis::cmd("SMEMBERS").arg(&ids_key).query(connection))?;
+        let mut keys: Vec<String> = ids.iter().map(|id| self.key(id)).collect();
+        keys.push(ids_key);
+        self.with_connection(|connection| redis::cmd("DEL").arg(&keys).query(connection))
+    }
+}
+
+/// What the cache knows about a memory in the hot tier
+struct HotEntry {
+    position: usize,
+    importance: f32,
+    timestamp: DateTime<Utc>,
+}
+
+/// The hot tier attached to a [`MemoryCache`](crate::cache::MemoryCache),
+/// with the bookkeeping needed to look memories up by log position and to
+/// demote them without asking the tier
+pub(crate) struct HotSet {
+    tier: std::sync::Arc<dyn HotTier>,
+    policy: HotTierPolicy,
+    entries: HashMap<String, HotEntry>,
+    ids_by_position: HashMap<usize, String>,
+    hits: u64,
+}
+
+impl HotSet {
+    /// Attach `tier`, dropping whatever it held before
+    pub(crate) fn new(tier: std::sync::Arc<dyn HotTier>, policy: HotTierPolicy) -> Self {
+        if let Err(e) = tier.clear() {
+            println!("Warning: could not clear the hot tier: {}", e);
+        }
+        HotSet { tier, policy, entries: HashMap::new(), ids_by_position: HashMap::new(), hits: 0 }
+    }
+
+    pub(crate) fn set_policy(&mut self, policy: HotTierPolicy) {
+        self.policy = policy;
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub(crate) fn hits(&self) -> u64 {
+        self.hits
+    }
+
+    pub(crate) fn id_at(&self, position: usize) -> Option<&String> {
+        self.ids_by_position.get(&position)
+    }
+
+    /// Fetch a hot memory; tier errors count as misses
+    pub(crate) fn get(&mut self, id: &str) -> Option<(usize, MemoryItem)> {
+        let position = self.entries.get(id)?.position;
+        match self.tier.get(id) {
+            Ok(Some(memory)) => {
+                self.hits += 1;
+                Some((position, memory))
+            }
+            Ok(None) => {
+                // Evicted behind our back, e.g. by a Redis maxmemory policy
+                self.forget(id);
+                None
+            }
+            Err(e) => {
+                println!("Warning: hot tier read of {} failed: {}", id, e);
+                None
+            }
+        }
+    }
+
+    /// Copy a memory into the tier if the policy wants it there at `now`
+    pub(crate) fn admit(&mut self, position: usize, memory: &MemoryItem, now: DateTime<Utc>) {
+        if self.entries.get(&memory.id).is_some_and(|entry| entry.position == position) {
+            return;
+        }
+        self.remove(&memory.id);
+        if self.entries.len() >= self.policy.max_memories || !self.policy.admits(memory.importance, memory.timestamp, now) {
+            return;
+        }
+        if let Err(e) = self.tier.put(memory) {
+            println!("Warning: hot tier write of {} failed: {}", memory.id, e);
+            return;
+        }
+        self.ids_by_position.insert(position, memory.id.clone());
+        self.entries.insert(
+            memory.id.clone(),
+            HotEntry { position, importance: memory.importance, timestamp: memory.timestamp },
+        );
+    }
+
+    /// Drop a memory from the tier
+    pub(crate) fn remove(&mut self, id: &str) {
+        if self.forget(id) {
+            if let Err(e) = self.tier.remove(id) {
+                println!("Warning: hot tier removal of {} failed: {}", id, e);
+            }
+        }
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.entries.clear();
+        self.ids_by_position.clear();
+        if let Err(e) = self.tier.clear() {
+            println!("Warning: could not clear the hot tier: {}", e);
+        }
+    }
+
+    /// Move memories the policy no longer admits at `now` back to the cold tier
+    pub(crate) fn demote(&mut self, now: DateTime<Utc>) -> usize {
+        let cold: Vec<String> = self
+            .entries
+            .iter()
+            .filter(|(_, entry)| !self.policy.admits(entry.importance, entry.timestamp, now))
+            .map(|(id, _)| id.clone())
+            .collect();
+        for id in &cold {
+            self.remove(id);
+        }
+        cold.len()
+    }
+
+    fn forget(&mut self, id: &str) -> bool {
+        match self.entries.remove(id) {
+            Some(entry) => {
+                self.ids_by_position.remove(&entry.position);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use crate::MindCache;
+    use crate::test_support::test_config;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_hot_tier_serves_and_demotes() {
+        let temp_dir = TempDir::new().unwrap();
+        let tier = Arc::new(InMemoryTier::new());
+        let mut cache = MindCache::builder()
+            .storage_path(temp_dir.path().to_string_lossy())
+            .auto_decay(false)
+            .cache_capacity(0)
+            .hot_tier(tier.clone())
+            .build()
+            .unwrap();
+
+        let fresh = cache.save("alice", "s1", "Gold is up", None).unwrap();
+        assert_eq!(tier.len(), 1);
+        let recalled = cache.recall("alice", Some("gold"), None, None).unwrap();
+        assert_eq!(recalled[0].id, fresh);
+        let stats = cache.storage.cache_stats();
+        assert_eq!((stats.hot_entries, stats.hot_hits), (1, 1));
+
+        // Old and unimportant: stays cold
+        let old = MemoryItem {
+            user_id: "alice".to_string(),
+            session_id: "s1".to_string(),
+            content: "Silver was flat".to_string(),
+            importance: 0.2,
+            timestamp: Utc::now() - Duration::hours(48),
+            ..Default::default()
+        };
+        cache.storage.save(old).unwrap();
+        assert_eq!(tier.len(), 1);
+
+        // Once the fresh memory ages past the window, decay demotes it
+        cache.storage.set_hot_tier_policy(HotTierPolicy { recent_hours: 0, ..Default::default() });
+        let stats = cache.decay().unwrap();
+        assert_eq!(stats.memories_demoted, 1);
+        assert!(tier.is_empty());
+        assert_eq!(cache.recall("alice", None, None, None).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_deleted_memories_leave_the_hot_tier() {
+        let temp_dir = TempDir::new().unwrap();
+        let tier = Arc::new(InMemoryTier::new());
+        let mut cache = MindCache::with_config(test_config(&temp_dir)).unwrap();
+        cache.storage.set_hot_tier(Some(tier.clone()), HotTierPolicy::default());
+
+        let id = cache.save("alice", "s1", "Gold is up", None).unwrap();
+        assert_eq!(tier.len(), 1);
+        cache.delete_memory_soft(&id).unwrap();
+        assert!(tier.is_empty());
+    }
+}
@@ -0,0 +1,187 @@
+//! Memory activity over time
+//!
+//! `memory_histogram` counts a user's memories per hour, day or week from
+//! the in-memory timestamp index, so no records are read. `RelativeDuration`
+//! lets recall filters say "the last 7 days" instead of a fixed date.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+use chrono::{DateTime, Datelike, Duration, DurationRound, Utc};
+use serde::{Deserialize, Serialize};
+use crate::MindCache;
+
+/// Width of a histogram bucket
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TimeBucket {
+    Hour,
+    Day,
+    /// Weeks start on Monday, UTC
+    Week,
+}
+
+impl TimeBucket {
+    /// Start of the bucket containing `timestamp`
+    pub fn start_of(self, timestamp: DateTime<Utc>) -> DateTime<Utc> {
+        match self {
+            TimeBucket::Hour => timestamp.duration_trunc(Duration::hours(1)).unwrap_or(timestamp),
+            TimeBucket::Day => timestamp.duration_trunc(Duration::days(1)).unwrap_or(timestamp),
+            TimeBucket::Week => {
+                let day = timestamp.duration_trunc(Duration::days(1)).unwrap_or(timestamp);
+                day - Duration::days(day.weekday().num_days_from_monday() as i64)
+            }
+        }
+    }
+}
+
+impl FromStr for TimeBucket {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "hour" => Ok(TimeBucket::Hour),
+            "day" => Ok(TimeBucket::Day),
+            "week" => Ok(TimeBucket::Week),
+            other => Err(format!("unknown bucket '{}' (expected hour, day or week)", other)),
+        }
+    }
+}
+
+/// Number of memories in one time bucket
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HistogramBucket {
+    pub start: DateTime<Utc>,
+    pub count: usize,
+}
+
+/// A span of time back from now, written like `30m`, `24h`, `7d` or `2w`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct RelativeDuration {
+    amount: u32,
+    unit: char,
+}
+
+impl RelativeDuration {
+    pub fn minutes(amount: u32) -> Self {
+        RelativeDuration { amount, unit: 'm' }
+    }
+
+    pub fn hours(amount: u32) -> Self {
+        RelativeDuration { amount, unit: 'h' }
+    }
+
+    pub fn days(amount: u32) -> Self {
+        RelativeDuration { amount, unit: 'd' }
+    }
+
+    pub fn weeks(amount: u32) -> Self {
+        RelativeDuration { amount, unit: 'w' }
+    }
+
+    pub fn to_duration(self) -> Duration {
+        let amount = self.amount as i64;
+        match self.unit {
+            'm' => Duration::minutes(amount),
+            'h' => Duration::hours(amount),
+            'd' => Duration::days(amount),
+            _ => Duration::weeks(amount),
+        }
+    }
+
+    /// The moment this duration before `now`
+    pub fn before(self, now: DateTime<Utc>) -> DateTime<Utc> {
+        now - self.to_duration()
+    }
+}
+
+impl FromStr for RelativeDuration {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let unit = s.chars().last().ok_or("empty duration")?;
+        if !matches!(unit, 'm' | 'h' | 'd' | 'w') {
+            return Err(format!("duration '{}' must end in m, h, d or w", s));
+        }
+        let amount = s[..s.len() - 1]
+            .parse()
+            .map_err(|_| format!("invalid duration '{}'", s))?;
+        Ok(RelativeDuration { amount, unit })
+    }
+}
+
+impl TryFrom<String> for RelativeDuration {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<RelativeDuration> for String {
+    fn from(duration: RelativeDuration) -> Self {
+        duration.to_string()
+    }
+}
+
+impl fmt::Display for RelativeDuration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", self.amount, self.unit)
+    }
+}
+
+/// Count timestamps per bucket, oldest bucket first; empty buckets are left out
+pub fn histogram(timestamps: impl IntoIterator<Item = DateTime<Utc>>, bucket: TimeBucket) -> Vec<HistogramBucket> {
+    let mut counts: BTreeMap<DateTime<Utc>, usize> = BTreeMap::new();
+    for timestamp in timestamps {
+        *counts.entry(bucket.start_of(timestamp)).or_default() += 1;
+    }
+    counts.into_iter().map(|(start, count)| HistogramBucket { start, count }).collect()
+}
+
+impl MindCache {
+    /// Count a user's memories per time bucket
+    pub fn memory_histogram(&self, user_id: &str, bucket: TimeBucket) -> Vec<HistogramBucket> {
+        histogram(self.storage.memory_timestamps(user_id), bucket)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_histogram_buckets() {
+        // 2024-03-06 is a Wednesday
+        let at = |day: u32, hour: u32| Utc.with_ymd_and_hms(2024, 3, day, hour, 15, 0).unwrap();
+        let timestamps = vec![at(6, 9), at(6, 9), at(6, 17), at(7, 8), at(11, 10)];
+
+        let hours = histogram(timestamps.clone(), TimeBucket::Hour);
+        assert_eq!(hours.len(), 4);
+        assert_eq!(hours[0], HistogramBucket { start: Utc.with_ymd_and_hms(2024, 3, 6, 9, 0, 0).unwrap(), count: 2 });
+
+        let days = histogram(timestamps.clone(), TimeBucket::Day);
+        assert_eq!(days.iter().map(|b| b.count).collect::<Vec<_>>(), vec![3, 1, 1]);
+
+        let weeks = histogram(timestamps, TimeBucket::Week);
+        assert_eq!(weeks, vec![
+            HistogramBucket { start: Utc.with_ymd_and_hms(2024, 3, 4, 0, 0, 0).unwrap(), count: 4 },
+            HistogramBucket { start: Utc.with_ymd_and_hms(2024, 3, 11, 0, 0, 0).unwrap(), count: 1 },
+        ]);
+    }
+
+    #[test]
+    fn test_relative_duration() {
+        let week: RelativeDuration = "7d".parse().unwrap();
+        assert_eq!(week, RelativeDuration::days(7));
+        assert_eq!(week.to_duration(), Duration::weeks(1));
+        assert_eq!(serde_json::to_string(&RelativeDuration::hours(24)).unwrap(), "\"24h\"");
+        assert_eq!(serde_json::from_str::<RelativeDuration>("\"2w\"").unwrap(), RelativeDuration::weeks(2));
+        for bad in ["", "7", "d", "7y", "-1d"] {
+            assert!(bad.parse::<RelativeDuration>().is_err(), "{:?} should not parse", bad);
+        }
+    }
+}
@@ -0,0 +1,121 @@
+//! What a user's memories have been about lately
+//!
+//! `MindCache::user_topics` counts how many of a user's memories in a time
+//! window mention each content word, from the in-memory term index, so no
+//! records are read however large the store. Counts for the window before
+//! are returned alongside, to tell rising topics from fading ones.
+
+use std::collections::HashMap;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use crate::digest::DIGEST_SESSION;
+use crate::review::REVIEW_SESSION;
+use crate::session::SUMMARY_SESSION;
+use crate::timeline::RelativeDuration;
+use crate::MindCache;
+
+/// Topics returned by `user_topics`
+pub const USER_TOPIC_LIMIT: usize = 20;
+
+/// Words shorter than this aren't topics
+const MIN_TOPIC_LEN: usize = 4;
+
+/// Sessions of memories generated from other memories, whose words would count twice
+const GENERATED_SESSIONS: [&str; 3] = [DIGEST_SESSION, SUMMARY_SESSION, REVIEW_SESSION];
+
+/// How often a word came up in a user's memories
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UserTopic {
+    pub topic: String,
+    /// Memories in the window mentioning the topic
+    pub count: usize,
+    /// Memories mentioning it in the window of the same length before
+    pub previous_count: usize,
+    pub last_seen: DateTime<Utc>,
+}
+
+/// Rank topics by their timestamps since `window_start`; earlier ones count as the previous window
+fn rank(term_timestamps: HashMap<String, Vec<DateTime<Utc>>>, window_start: DateTime<Utc>, is_topic: impl Fn(&str) -> bool) -> Vec<UserTopic> {
+    let mut topics: Vec<UserTopic> = term_timestamps
+        .into_iter()
+        .filter(|(term, _)| is_topic(term))
+        .filter_map(|(topic, timestamps)| {
+            let count = timestamps.iter().filter(|timestamp| **timestamp >= window_start).count();
+            let last_seen = timestamps.iter().max().copied()?;
+            (count > 0).then(|| UserTopic { count, previous_count: timestamps.len() - count, last_seen, topic })
+        })
+        .collect();
+    topics.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| b.last_seen.cmp(&a.last_seen)).then_with(|| a.topic.cmp(&b.topic)));
+    topics.truncate(USER_TOPIC_LIMIT);
+    topics
+}
+
+impl MindCache {
+    /// The words a user's memories mentioned most within `window` of now
+    ///
+    /// Stop words, numbers and words under four characters are left out.
+    /// Returns at most [`USER_TOPIC_LIMIT`] topics, most frequent first.
+    pub fn user_topics(&self, user_id: &str, window: RelativeDuration) -> Vec<UserTopic> {
+        let window_start = window.before(self.storage.now());
+        self.topics_since(user_id, window_start, window.before(window_start))
+    }
+
+    /// Topics of memories since `window_start`, with those from `previous_start` on as the previous counts
+    pub(crate) fn topics_since(&self, user_id: &str, window_start: DateTime<Utc>, previous_start: DateTime<Utc>) -> Vec<UserTopic> {
+        let stop_words = self.storage.stop_words();
+        rank(self.storage.term_timestamps(user_id, previous_start, &GENERATED_SESSIONS), window_start, |term| {
+            term.chars().count() >= MIN_TOPIC_LEN && !term.chars().all(|c| c.is_numeric()) && !stop_words.contains_any(term)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::temp_cache;
+
+    fn alice_and_bob() -> (MindCache, tempfile::TempDir) {
+        let (mut cache, temp_dir) = temp_cache();
+        for content in ["Gold rallied after the Fed meeting", "Sold gold at 2350", "Planning the garden for spring"] {
+            cache.save("alice", "s1", content, None).unwrap();
+        }
+        cache.save("bob", "s1", "Gold gold gold", None).unwrap();
+        (cache, temp_dir)
+    }
+
+    #[test]
+    fn test_user_topics_rank_by_the_users_own_mentions() {
+        let (cache, _temp_dir) = alice_and_bob();
+        let topics = cache.user_topics("alice", RelativeDuration::days(30));
+        assert_eq!(topics[0].topic, "gold");
+        assert_eq!((topics[0].count, topics[0].previous_count), (2, 0));
+        assert!(cache.user_topics("carol", RelativeDuration::days(30)).is_empty());
+    }
+
+    #[test]
+    fn test_user_topics_leave_out_stop_words_and_numbers() {
+        let (cache, _temp_dir) = alice_and_bob();
+        let topics = cache.user_topics("alice", RelativeDuration::days(30));
+        let names: Vec<&str> = topics.iter().map(|topic| topic.topic.as_str()).collect();
+        for left_out in ["the", "fed", "after", "2350", "for"] {
+            assert!(!names.contains(&left_out), "{} in {:?}", left_out, names);
+        }
+        assert!(names.contains(&"garden"));
+    }
+
+    #[test]
+    fn test_earlier_mentions_count_towards_the_previous_window_only() {
+        let now = Utc::now();
+        let day = |days: i64| now - chrono::Duration::days(days);
+        let timestamps = HashMap::from([
+            ("gold".to_string(), vec![day(1), day(9), day(10)]),
+            ("garden".to_string(), vec![day(2), day(3)]),
+            ("silver".to_string(), vec![day(9)]),
+        ]);
+        let ranked = rank(timestamps, day(7), |_| true);
+        assert_eq!(ranked.len(), 2);
+        assert_eq!((ranked[0].topic.as_str(), ranked[0].count, ranked[0].previous_count), ("garden", 2, 0));
+        assert_eq!((ranked[1].topic.as_str(), ranked[1].count, ranked[1].previous_count), ("gold", 1, 2));
+        assert_eq!(ranked[1].last_seen, day(1));
+    }
+}
@@ -0,0 +1,260 @@
+//! Saves and deletes applied all together or not at all
+//!
+//! `MindCache::transaction` hands a [`Transaction`] to a closure that queues
+//! operations; nothing touches the store until the closure returns `Ok`, and
+//! then the whole batch is committed with a single index swap. If the closure
+//! fails, or the process crashes before the commit, none of it is applied.
+//!
+//! ```no_run
+//! # use mindcache_core::MindCache;
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! # let mut cache = MindCache::new()?;
+//! # let old_ids: Vec<String> = Vec::new();
+//! cache.transaction(|tx| {
+//!     for id in &old_ids {
+//!         tx.delete(id);
+//!     }
+//!     tx.save("alice", "s1", "Summary of the week", None);
+//!     Ok(())
+//! })?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::{HashMap, HashSet};
+use uuid::Uuid;
+use crate::quota::{QuotaExceeded, QuotaPolicy};
+use crate::storage::MemoryItem;
+use crate::MindCache;
+
+/// Operations queued inside `MindCache::transaction`
+pub struct Transaction<'a> {
+    cache: &'a MindCache,
+    saves: Vec<MemoryItem>,
+    deletes: Vec<String>,
+}
+
+impl Transaction<'_> {
+    /// Queue a save with estimated importance and the default TTL, as
+    /// `MindCache::save` would; returns the id the memory will get
+    pub fn save(&mut self, user_id: &str, session_id: &str, content: &str, metadata: Option<HashMap<String, String>>) -> String {
+        let mut memory = MemoryItem {
+            user_id: user_id.to_string(),
+            session_id: session_id.to_string(),
+            content: content.to_string(),
+            metadata: metadata.unwrap_or_default(),
+            timestamp: self.cache.storage.now(),
+            ttl_hours: self.cache.config.default_memory_ttl_hours,
+            importance: 0.5,
+            ..Default::default()
+        };
+        memory.importance = self.cache.default_importance(&memory);
+        self.save_item(memory)
+    }
+
+    /// Queue a save with explicit importance and TTL
+    pub fn save_with_options(&mut self, user_id: &str, session_id: &str, content: &str,
+                             metadata: Option<HashMap<String, String>>, importance: f32, ttl_hours: Option<u32>) -> String {
+        self.save_item(MemoryItem {
+            user_id: user_id.to_string(),
+            session_id: session_id.to_string(),
+            content: content.to_string(),
+            metadata: metadata.unwrap_or_default(),
+            timestamp: self.cache.storage.now(),
+            ttl_hours,
+            importance: importance.clamp(0.0, 1.0),
+            ..Default::default()
+        })
+    }
+
+    /// Queue a save of a complete memory; an empty id gets a new one
+    pub fn save_item(&mut self, mut memory: MemoryItem) -> String {
+        if memory.id.is_empty() {
+            memory.id = Uuid::new_v4().to_string();
+        }
+        let id = memory.id.clone();
+        self.saves.push(memory);
+        id
+    }
+
+    /// Queue the deletion of an existing memory
+    ///
+    /// The commit fails, applying nothing, if no memory has this id.
+    pub fn delete(&mut self, memory_id: &str) {
+        self.deletes.push(memory_id.to_string());
+    }
+
+    /// Number of queued operations
+    pub fn len(&self) -> usize {
+        self.saves.len() + self.deletes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl MindCache {
+    /// Run `build` to queue saves and deletes, then apply them all at once
+    ///
+    /// Returns whatever `build` returns. If it returns an error nothing is
+    /// applied. Queued saves go through the same checks as `save`: each
+    /// counts against the save rate limit, is admitted by the session
+    /// policy, and is held to the content limit, importance floor and entity
+    /// extraction, so one that `save` would refuse fails the whole batch. A
+    /// chunked save keeps its queued id for its first chunk. With a quota
+    /// policy other than `Decay`, a batch that would leave a user over
+    /// `max_memories_per_user` fails with [`QuotaExceeded`] rather than
+    /// evicting anything.
+    pub fn transaction<T, F>(&mut self, build: F) -> Result<T, Box<dyn std::error::Error>>
+    where
+        F: FnOnce(&mut Transaction<'_>) -> Result<T, Box<dyn std::error::Error>>,
+    {
+        let mut tx = Transaction { cache: self, saves: Vec::new(), deletes: Vec::new() };
+        let output = build(&mut tx)?;
+        let Transaction { saves: queued, mut deletes, .. } = tx;
+        if queued.is_empty() && deletes.is_empty() {
+            return Ok(output);
+        }
+
+        let mut saves = Vec::with_capacity(queued.len());
+        let mut chunked = Vec::new();
+        for memory in queued {
+            self.save_limiter.check(&memory.user_id, self.config.max_saves_per_minute)?;
+            self.admit_session(&memory.user_id, &memory.session_id)?;
+            let queued_id = memory.id.clone();
+            let mut pieces = self.prepare(memory)?;
+            for (i, piece) in pieces.iter_mut().enumerate() {
+                piece.id = if i == 0 { queued_id.clone() } else { Uuid::new_v4().to_string() };
+            }
+            if pieces.len() > 1 {
+                chunked.push(pieces.iter().map(|piece| piece.id.clone()).collect::<Vec<_>>());
+            }
+            saves.extend(pieces);
+        }
+
+        let deleted_ids: HashSet<&String> = deletes.iter().collect();
+        let mut deleted_per_user: HashMap<String, usize> = HashMap::new();
+        for id in deleted_ids {
+            if let Some(memory) = self.storage.get_memory(id)? {
+                *deleted_per_user.entry(memory.user_id).or_default() += 1;
+            }
+        }
+        let mut saved_per_user: HashMap<&str, usize> = HashMap::new();
+        for memory in &saves {
+            *saved_per_user.entry(&memory.user_id).or_default() += 1;
+        }
+        if self.config.quota_policy != QuotaPolicy::Decay {
+            let limit = self.config.max_memories_per_user;
+            for (user_id, added) in saved_per_user {
+                let removed = deleted_per_user.get(user_id).copied().unwrap_or(0);
+                if self.storage.user_memory_count(user_id) + added > limit + removed {
+                    return Err(Box::new(QuotaExceeded { user_id: user_id.to_string(), limit }));
+                }
+            }
+        }
+
+        deletes.sort();
+        deletes.dedup();
+        self.storage.apply_batch(saves, &deletes)?;
+        for ids in chunked {
+            self.link_chunks(&ids)?;
+        }
+        Ok(output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MindCacheConfig;
+    use crate::test_support::{test_config, temp_cache_with};
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_transaction_applies_all_or_nothing() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = test_config(&temp_dir);
+        let mut cache = MindCache::with_config(config.clone()).unwrap();
+        let originals: Vec<String> = (0..5)
+            .map(|i| cache.save("alice", "s1", &format!("Note {}", i), None).unwrap())
+            .collect();
+
+        // A failing closure leaves the store untouched
+        let result: Result<(), _> = cache.transaction(|tx| {
+            tx.delete(&originals[0]);
+            tx.save("alice", "s1", "never saved", None);
+            Err("changed my mind".into())
+        });
+        assert!(result.is_err());
+        assert_eq!(cache.recall("alice", None, None, None).unwrap().len(), 5);
+
+        // So does deleting a memory that does not exist
+        let result = cache.transaction(|tx| {
+            tx.delete(&originals[0]);
+            tx.delete("no-such-memory");
+            Ok(())
+        });
+        assert!(result.is_err());
+        assert_eq!(cache.recall("alice", None, None, None).unwrap().len(), 5);
+
+        // Replace the five notes with one summary
+        let summary_id = cache.transaction(|tx| {
+            for id in &originals {
+                tx.delete(id);
+            }
+            Ok(tx.save_with_options("alice", "s1", "Summary of notes", None, 0.8, None))
+        }).unwrap();
+        let remaining = cache.recall("alice", None, None, None).unwrap();
+        assert_eq!(remaining.len(), 1);
+        assert_eq!(remaining[0].id, summary_id);
+        assert!(cache.get_memory(&originals[0]).unwrap().is_none());
+        assert_eq!(cache.recall("alice", Some("Summary"), None, None).unwrap().len(), 1);
+
+        drop(cache);
+        let reopened = MindCache::with_config(config).unwrap();
+        let remaining = reopened.recall("alice", None, None, None).unwrap();
+        assert_eq!(remaining.len(), 1);
+        assert_eq!(remaining[0].content, "Summary of notes");
+    }
+
+    #[test]
+    fn test_transaction_saves_get_the_same_checks_as_save() {
+        let (mut cache, _temp_dir) = temp_cache_with(MindCacheConfig {
+            max_content_bytes: Some(10),
+            ..Default::default()
+        });
+        let kept = cache.save("alice", "s1", "short", None).unwrap();
+
+        // Content `save` would reject fails the whole batch
+        let result = cache.transaction(|tx| {
+            tx.delete(&kept);
+            tx.save("alice", "s1", "much longer than ten bytes", None);
+            Ok(())
+        });
+        assert!(result.unwrap_err().downcast_ref::<crate::ContentTooLarge>().is_some());
+        assert!(cache.get_memory(&kept).unwrap().is_some());
+
+        // Chunked content keeps its queued id for the first of its linked chunks
+        cache.config.content_limit_policy = crate::ContentLimitPolicy::Chunk;
+        cache.set_session_retention("s1", crate::SessionRetention {
+            importance_floor: Some(0.7),
+            ..Default::default()
+        }).unwrap();
+        let first = cache.transaction(|tx| Ok(tx.save("alice", "s1", "aaaaaaaaa bbbbbbbbb", None))).unwrap();
+        let chunks = cache.chunks_of(&first).unwrap();
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(chunks[0].id, first);
+        assert!(chunks.iter().all(|chunk| chunk.importance == 0.7));
+
+        // So does a session the session policy refuses
+        cache.config.session_policy = crate::SessionPolicy::Strict;
+        let before = cache.recall("alice", None, None, None).unwrap().len();
+        let result = cache.transaction(|tx| {
+            tx.save("alice", "unregistered", "note", None);
+            Ok(())
+        });
+        assert!(result.unwrap_err().downcast_ref::<crate::UnknownSession>().is_some());
+        assert_eq!(cache.recall("alice", None, None, None).unwrap().len(), before);
+    }
+}
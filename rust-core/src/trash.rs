@@ -0,0 +1,236 @@
+//! Soft delete: a per-user trash that memories can be restored from
+//!
+//! `MindCache::delete_memory_soft` moves a memory out of the indices, so
+//! recall no longer sees it, and keeps a copy in `trash.json` next to the
+//! memory log. `restore_memory` puts it back unchanged, id included. Each
+//! decay run purges memories that have been in the trash for longer than
+//! `trash_retention_days`.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, RwLock};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use crate::audit::AuditAction;
+use crate::backend::StorageBackend;
+use crate::storage::MemoryItem;
+use crate::MindCache;
+
+pub const TRASH_BLOB: &str = "trash.json";
+
+/// A soft-deleted memory
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrashedMemory {
+    pub memory: MemoryItem,
+    pub deleted_at: DateTime<Utc>,
+}
+
+/// Persistent trash, keyed by memory id
+#[derive(Clone)]
+pub struct Trash {
+    backend: Arc<dyn StorageBackend>,
+    items: Arc<RwLock<HashMap<String, TrashedMemory>>>,
+}
+
+impl Trash {
+    pub fn load(backend: Arc<dyn StorageBackend>) -> Result<Self, Box<dyn std::error::Error>> {
+        let items = Self::read_items(backend.as_ref())?;
+        Ok(Trash { backend, items: Arc::new(RwLock::new(items)) })
+    }
+
+    /// Re-read the trash from disk, e.g. after a restore
+    pub fn reload(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let items = Self::read_items(self.backend.as_ref())?;
+        *self.items.write().unwrap_or_else(|poisoned| poisoned.into_inner()) = items;
+        Ok(())
+    }
+
+    fn read_items(backend: &dyn StorageBackend) -> Result<HashMap<String, TrashedMemory>, Box<dyn std::error::Error>> {
+        let mut items = HashMap::new();
+        if let Some(data) = backend.read_blob(TRASH_BLOB)? {
+            for item in serde_json::from_slice::<Vec<TrashedMemory>>(&data)? {
+                items.insert(item.memory.id.clone(), item);
+            }
+        }
+        Ok(items)
+    }
+
+    /// Keep a copy of `memory`, deleted at `deleted_at`
+    pub fn put(&self, memory: MemoryItem, deleted_at: DateTime<Utc>) -> Result<(), Box<dyn std::error::Error>> {
+        self.update(|items| {
+            items.insert(memory.id.clone(), TrashedMemory { memory, deleted_at });
+        })
+    }
+
+    /// Take a memory out of the trash
+    pub fn take(&self, memory_id: &str) -> Result<Option<TrashedMemory>, Box<dyn std::error::Error>> {
+        let mut taken = None;
+        self.update(|items| taken = items.remove(memory_id))?;
+        Ok(taken)
+    }
+
+    pub fn get(&self, memory_id: &str) -> Option<TrashedMemory> {
+        self.items.read().unwrap_or_else(|poisoned| poisoned.into_inner()).get(memory_id).cloned()
+    }
+
+    pub fn contains(&self, memory_id: &str) -> bool {
+        self.items.read().unwrap_or_else(|poisoned| poisoned.into_inner()).contains_key(memory_id)
+    }
+
+    /// A user's trashed memories, most recently deleted first
+    pub fn list(&self, user_id: &str) -> Vec<TrashedMemory> {
+        let items = self.items.read().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let mut listed: Vec<TrashedMemory> = items.values().filter(|item| item.memory.user_id == user_id).cloned().collect();
+        listed.sort_by_key(|item| std::cmp::Reverse(item.deleted_at));
+        listed
+    }
+
+    /// Trashed memories matching `find`
+    pub fn find_where<F>(&self, find: F) -> Vec<TrashedMemory>
+    where
+        F: Fn(&TrashedMemory) -> bool,
+    {
+        self.items.read().unwrap_or_else(|poisoned| poisoned.into_inner()).values().filter(|item| find(item)).cloned().collect()
+    }
+
+    /// Permanently drop memories matching `purge`; returns them
+    pub fn purge_where<F>(&self, purge: F) -> Result<Vec<TrashedMemory>, Box<dyn std::error::Error>>
+    where
+        F: Fn(&TrashedMemory) -> bool,
+    {
+        let mut purged = Vec::new();
+        self.update(|items| {
+            let ids: HashSet<String> = items.values().filter(|item| purge(item)).map(|item| item.memory.id.clone()).collect();
+            purged = ids.iter().filter_map(|id| items.remove(id)).collect();
+        })?;
+        Ok(purged)
+    }
+
+    /// Apply `edit` and persist, leaving the trash as it was if that fails
+    fn update<F>(&self, edit: F) -> Result<(), Box<dyn std::error::Error>>
+    where
+        F: FnOnce(&mut HashMap<String, TrashedMemory>),
+    {
+        let mut items = self.items.write().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let previous = items.clone();
+        edit(&mut items);
+        let persisted = serde_json::to_vec(&items.values().collect::<Vec<_>>())
+            .map_err(Box::<dyn std::error::Error>::from)
+            .and_then(|data| Ok(self.backend.write_blob(TRASH_BLOB, &data)?));
+        if let Err(e) = persisted {
+            *items = previous;
+            return Err(e);
+        }
+        Ok(())
+    }
+}
+
+impl MindCache {
+    /// Move a memory to its user's trash
+    ///
+    /// Recall stops returning it straight away; `restore_memory` brings it
+    /// back until it is purged `trash_retention_days` later.
+    pub fn delete_memory_soft(&mut self, memory_id: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let position = self.storage.position_of(memory_id).ok_or_else(|| format!("memory {} not found", memory_id))?;
+        let Some(memory) = self.storage.get_memory(memory_id)? else {
+            return Err(format!("memory {} not found", memory_id).into());
+        };
+
+        self.storage.trash().put(memory, self.storage.now())?;
+        if let Err(e) = self.storage.delete_positions(&HashSet::from([position]), AuditAction::Trash) {
+            let _ = self.storage.trash().take(memory_id);
+            return Err(e);
+        }
+        println!("Moved memory {} to the trash", memory_id);
+        Ok(())
+    }
+
+    /// Put a memory from the trash back where it was
+    pub fn restore_memory(&mut self, memory_id: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let Some(trashed) = self.storage.trash().take(memory_id)? else {
+            return Err(format!("memory {} is not in the trash", memory_id).into());
+        };
+        if let Err(e) = self.storage.save_as(trashed.memory.clone(), AuditAction::Restore) {
+            let _ = self.storage.trash().put(trashed.memory, trashed.deleted_at);
+            return Err(e);
+        }
+        println!("Restored memory {} from the trash", memory_id);
+        Ok(())
+    }
+
+    /// Memories in a user's trash, most recently deleted first
+    pub fn trash(&self, user_id: &str) -> Vec<TrashedMemory> {
+        self.storage.trash().list(user_id)
+    }
+
+    /// Permanently drop everything in a user's trash; returns how many memories were purged
+    pub fn empty_trash(&mut self, user_id: &str) -> Result<usize, Box<dyn std::error::Error>> {
+        let purged = self.storage.trash().purge_where(|item| item.memory.user_id == user_id)?;
+        self.storage.audit_all(AuditAction::Purge, purged.iter().map(|item| &item.memory));
+        Ok(purged.len())
+    }
+
+    /// Ids of the memories `purge_expired_trash(now)` would purge
+    pub(crate) fn expired_trash(&self, now: DateTime<Utc>) -> Vec<String> {
+        let cutoff = now - Duration::days(self.config.trash_retention_days as i64);
+        self.storage.trash().find_where(|item| item.deleted_at < cutoff).into_iter().map(|item| item.memory.id).collect()
+    }
+
+    /// Purge memories trashed more than `trash_retention_days` before `now`; returns their ids
+    pub(crate) fn purge_expired_trash(&self, now: DateTime<Utc>) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        let cutoff = now - Duration::days(self.config.trash_retention_days as i64);
+        let purged = self.storage.trash().purge_where(|item| item.deleted_at < cutoff)?;
+        if !purged.is_empty() {
+            println!("Purged {} memories from the trash", purged.len());
+        }
+        self.storage.audit_all(AuditAction::Purge, purged.iter().map(|item| &item.memory));
+        Ok(purged.into_iter().map(|item| item.memory.id).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MindCacheConfig;
+    use crate::test_support::temp_cache;
+
+    #[test]
+    fn test_soft_delete_hides_from_recall() {
+        let (mut cache, _temp_dir) = temp_cache();
+        let id = cache.save("alice", "s1", "Allergic to peanuts", None).unwrap();
+
+        cache.delete_memory_soft(&id).unwrap();
+        assert!(cache.get_memory(&id).unwrap().is_none());
+        assert_eq!(cache.recall("alice", Some("peanuts"), None, None).unwrap().len(), 0);
+        assert_eq!(cache.trash("alice").len(), 1);
+        assert!(cache.trash("bob").is_empty());
+        assert!(cache.delete_memory_soft("missing").is_err());
+    }
+
+    #[test]
+    fn test_restore_after_reopen_keeps_the_id() {
+        let (mut cache, _temp_dir) = temp_cache();
+        let id = cache.save("alice", "s1", "Allergic to peanuts", None).unwrap();
+        cache.delete_memory_soft(&id).unwrap();
+
+        let config = cache.config.clone();
+        drop(cache);
+        let mut cache = MindCache::with_config(config).unwrap();
+        cache.restore_memory(&id).unwrap();
+        assert_eq!(cache.recall("alice", Some("peanuts"), None, None).unwrap()[0].id, id);
+        assert!(cache.trash("alice").is_empty());
+        assert!(cache.restore_memory(&id).is_err());
+    }
+
+    #[test]
+    fn test_purge_drops_trash_past_retention() {
+        let (mut cache, _temp_dir) = temp_cache();
+        let id = cache.save("alice", "s1", "Likes jazz", None).unwrap();
+        cache.delete_memory_soft(&id).unwrap();
+
+        assert!(cache.purge_expired_trash(Utc::now()).unwrap().is_empty());
+        let config = MindCacheConfig { trash_retention_days: 0, ..cache.config.clone() };
+        cache.update_config(config).unwrap();
+        assert_eq!(cache.purge_expired_trash(Utc::now()).unwrap(), std::slice::from_ref(&id));
+        assert!(cache.restore_memory(&id).is_err());
+    }
+}
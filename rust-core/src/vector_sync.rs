@@ -0,0 +1,326 @@
+//! Mirroring memories into external vector databases
+//!
+//! MindCache stays the source of truth; a [`VectorSync`] replays the change
+//! log (see [`crate::changes`]) into a [`VectorSink`], embedding the content
+//! of saved and updated memories and deleting removed ones. Implement
+//! `VectorSink` over the client of Qdrant, Pinecone, pgvector or any other
+//! store that upserts vectors by id. Each sync resumes after the last change
+//! the sink acknowledged, recorded per sink in `vector_sync.json`, so a sync
+//! that fails midway repeats only the unacknowledged batch.
+//!
+//! The change log only covers changes made while `change_log_enabled` was
+//! on; [`MindCache::resync_vectors`] backfills a sink with every memory.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use crate::changes::{ChangeKind, ChangeRecord};
+use crate::dedupe::fnv1a;
+use crate::planner;
+use crate::storage::{MemoryItem, QueryFilter};
+use crate::MindCache;
+
+/// Blob holding the last change each sink acknowledged, by sink name
+pub const VECTOR_SYNC_BLOB: &str = "vector_sync.json";
+
+/// Turns memory content into a vector
+pub trait Embedder: Send + Sync {
+    fn embed(&self, text: &str) -> Result<Vec<f32>, Box<dyn std::error::Error>>;
+}
+
+impl<F> Embedder for F
+where
+    F: Fn(&str) -> Result<Vec<f32>, Box<dyn std::error::Error>> + Send + Sync,
+{
+    fn embed(&self, text: &str) -> Result<Vec<f32>, Box<dyn std::error::Error>> {
+        self(text)
+    }
+}
+
+/// Embeds text by hashing its words into a fixed number of dimensions
+///
+/// Needs no model, so vectors only match on shared words; plug in a real
+/// embedding model for semantic search.
+#[derive(Debug, Clone, Copy)]
+pub struct HashingEmbedder {
+    pub dimensions: usize,
+}
+
+impl Default for HashingEmbedder {
+    fn default() -> Self {
+        HashingEmbedder { dimensions: 256 }
+    }
+}
+
+impl Embedder for HashingEmbedder {
+    fn embed(&self, text: &str) -> Result<Vec<f32>, Box<dyn std::error::Error>> {
+        let mut vector = vec![0.0; self.dimensions.max(1)];
+        let dimensions = vector.len() as u64;
+        for term in planner::index_terms(text) {
+            vector[(fnv1a(&term) % dimensions) as usize] += 1.0;
+        }
+        let norm = vector.iter().map(|value| value * value).sum::<f32>().sqrt();
+        if norm > 0.0 {
+            vector.iter_mut().for_each(|value| *value /= norm);
+        }
+        Ok(vector)
+    }
+}
+
+/// A memory as written to a vector database
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VectorRecord {
+    /// The memory id, used as the vector's id
+    pub id: String,
+    pub user_id: String,
+    pub session_id: String,
+    pub content: String,
+    pub vector: Vec<f32>,
+    pub importance: f32,
+    pub timestamp: DateTime<Utc>,
+    pub metadata: HashMap<String, String>,
+}
+
+/// A vector database kept in step with the store
+///
+/// Both calls must be idempotent: after a failure, the batch is sent again.
+pub trait VectorSink: Send + Sync {
+    /// Insert the records, replacing any with the same id
+    fn upsert(&self, records: &[VectorRecord]) -> Result<(), Box<dyn std::error::Error>>;
+
+    /// Remove the records with these ids; unknown ids are not an error
+    fn delete(&self, ids: &[String]) -> Result<(), Box<dyn std::error::Error>>;
+}
+
+/// A sink and how to embed memories for it
+#[derive(Clone)]
+pub struct VectorSync {
+    /// Key of the sink's progress in [`VECTOR_SYNC_BLOB`]
+    pub name: String,
+    sink: Arc<dyn VectorSink>,
+    embedder: Arc<dyn Embedder>,
+    /// Changes sent to the sink at a time
+    pub batch_size: usize,
+}
+
+impl VectorSync {
+    pub fn new<S: VectorSink + 'static, E: Embedder + 'static>(name: impl Into<String>, sink: S, embedder: E) -> Self {
+        VectorSync {
+            name: name.into(),
+            sink: Arc::new(sink),
+            embedder: Arc::new(embedder),
+            batch_size: 100,
+        }
+    }
+
+    fn record(&self, memory: &MemoryItem) -> Result<VectorRecord, Box<dyn std::error::Error>> {
+        Ok(VectorRecord {
+            id: memory.id.clone(),
+            user_id: memory.user_id.clone(),
+            session_id: memory.session_id.clone(),
+            content: memory.content.clone(),
+            vector: self.embedder.embed(&memory.content)?,
+            importance: memory.importance,
+            timestamp: memory.timestamp,
+            metadata: memory.metadata.clone(),
+        })
+    }
+
+    /// Send one batch of changes, the last change of each memory winning
+    fn apply(&self, changes: &[ChangeRecord]) -> Result<(usize, usize), Box<dyn std::error::Error>> {
+        let mut latest: HashMap<&str, &ChangeRecord> = HashMap::new();
+        for change in changes {
+            latest.insert(&change.memory_id, change);
+        }
+        let mut upserts = Vec::new();
+        let mut deletes = Vec::new();
+        for change in latest.into_values() {
+            match (&change.kind, &change.memory) {
+                (ChangeKind::Saved | ChangeKind::Updated, Some(memory)) => upserts.push(self.record(memory)?),
+                _ => deletes.push(change.memory_id.clone()),
+            }
+        }
+        upserts.sort_by(|a, b| a.id.cmp(&b.id));
+        deletes.sort();
+        if !upserts.is_empty() {
+            self.sink.upsert(&upserts)?;
+        }
+        if !deletes.is_empty() {
+            self.sink.delete(&deletes)?;
+        }
+        Ok((upserts.len(), deletes.len()))
+    }
+}
+
+/// What one sync sent
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VectorSyncReport {
+    pub upserted: usize,
+    pub deleted: usize,
+    /// Last change the sink has acknowledged
+    pub last_sequence: u64,
+}
+
+impl MindCache {
+    /// Send the changes made since the last sync of `sync` to its sink
+    ///
+    /// Needs `change_log_enabled`.
+    pub fn sync_vectors(&self, sync: &VectorSync) -> Result<VectorSyncReport, Box<dyn std::error::Error>> {
+        if !self.storage.changes().is_enabled() {
+            return Err("vector sync follows the change log; enable change_log_enabled".into());
+        }
+        let mut report = VectorSyncReport { last_sequence: self.vector_sync_position(&sync.name)?, ..Default::default() };
+        let changes = self.changes_since(report.last_sequence)?;
+        for batch in changes.chunks(sync.batch_size.max(1)) {
+            let (upserted, deleted) = sync.apply(batch)?;
+            report.upserted += upserted;
+            report.deleted += deleted;
+            report.last_sequence = batch[batch.len() - 1].sequence;
+            self.set_vector_sync_position(&sync.name, report.last_sequence)?;
+        }
+        if report.upserted + report.deleted > 0 {
+            println!("Synced {} upserts and {} deletes to vector sink {}", report.upserted, report.deleted, sync.name);
+        }
+        Ok(report)
+    }
+
+    /// Send every memory to the sink of `sync`, then continue from the
+    /// latest change
+    ///
+    /// For filling a new sink, or one that missed changes made with the
+    /// change log off. Memories the sink holds but the store no longer does
+    /// are not removed.
+    pub fn resync_vectors(&self, sync: &VectorSync) -> Result<VectorSyncReport, Box<dyn std::error::Error>> {
+        let last_sequence = self.latest_change_sequence();
+        let mut report = VectorSyncReport { last_sequence, ..Default::default() };
+        let mut batch = Vec::with_capacity(sync.batch_size.max(1));
+        for memory in self.storage.recall_iter(QueryFilter::default())? {
+            batch.push(sync.record(&memory?)?);
+            if batch.len() >= sync.batch_size.max(1) {
+                sync.sink.upsert(&batch)?;
+                report.upserted += batch.len();
+                batch.clear();
+            }
+        }
+        if !batch.is_empty() {
+            sync.sink.upsert(&batch)?;
+            report.upserted += batch.len();
+        }
+        self.set_vector_sync_position(&sync.name, last_sequence)?;
+        println!("Resynced {} memories to vector sink {}", report.upserted, sync.name);
+        Ok(report)
+    }
+
+    /// Last change the sink named `name` acknowledged (0 if it never synced)
+    pub fn vector_sync_position(&self, name: &str) -> Result<u64, Box<dyn std::error::Error>> {
+        Ok(self.vector_sync_positions()?.get(name).copied().unwrap_or(0))
+    }
+
+    fn vector_sync_positions(&self) -> Result<HashMap<String, u64>, Box<dyn std::error::Error>> {
+        match self.storage.backend().read_blob(VECTOR_SYNC_BLOB)? {
+            Some(data) => Ok(serde_json::from_slice(&data)?),
+            None => Ok(HashMap::new()),
+        }
+    }
+
+    fn set_vector_sync_position(&self, name: &str, sequence: u64) -> Result<(), Box<dyn std::error::Error>> {
+        let mut positions = self.vector_sync_positions()?;
+        positions.insert(name.to_string(), sequence);
+        self.storage.backend().write_blob(VECTOR_SYNC_BLOB, &serde_json::to_vec(&positions)?)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MindCacheConfig;
+    use std::sync::Mutex;
+    use crate::test_support::{test_config, temp_cache};
+
+    /// Sink keeping records in memory, failing upserts while `failing` is set
+    #[derive(Clone, Default)]
+    struct MemorySink {
+        records: Arc<Mutex<HashMap<String, VectorRecord>>>,
+        failing: Arc<Mutex<bool>>,
+    }
+
+    impl VectorSink for MemorySink {
+        fn upsert(&self, records: &[VectorRecord]) -> Result<(), Box<dyn std::error::Error>> {
+            if *self.failing.lock().unwrap() {
+                return Err("sink unavailable".into());
+            }
+            let mut stored = self.records.lock().unwrap();
+            for record in records {
+                stored.insert(record.id.clone(), record.clone());
+            }
+            Ok(())
+        }
+
+        fn delete(&self, ids: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+            let mut stored = self.records.lock().unwrap();
+            for id in ids {
+                stored.remove(id);
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_hashing_embedder_is_normalized() {
+        let embedder = HashingEmbedder { dimensions: 16 };
+        let vector = embedder.embed("gold gold silver").unwrap();
+        assert_eq!(vector.len(), 16);
+        assert!((vector.iter().map(|value| value * value).sum::<f32>() - 1.0).abs() < 1e-5);
+        assert_eq!(vector, embedder.embed("silver gold").unwrap());
+        assert!(embedder.embed("").unwrap().iter().all(|value| *value == 0.0));
+    }
+
+    #[test]
+    fn test_sync_follows_change_log() {
+        let (mut cache, temp_dir) = temp_cache();
+        let before_log = cache.save("alice", "s1", "Saved before the change log", None).unwrap();
+        drop(cache);
+
+        let mut cache = MindCache::with_config(MindCacheConfig {
+            change_log_enabled: true,
+            ..test_config(&temp_dir)
+        })
+        .unwrap();
+        let sink = MemorySink::default();
+        let mut sync = VectorSync::new("qdrant", sink.clone(), HashingEmbedder::default());
+        sync.batch_size = 2;
+
+        let gold = cache.save("alice", "s1", "Gold is up", None).unwrap();
+        let silver = cache.save("alice", "s1", "Silver is flat", None).unwrap();
+        let dropped = cache.save("bob", "s2", "Temporary note", None).unwrap();
+        cache.delete_memory_soft(&dropped).unwrap();
+
+        let report = cache.sync_vectors(&sync).unwrap();
+        assert_eq!(report.last_sequence, cache.latest_change_sequence());
+        assert_eq!(cache.vector_sync_position("qdrant").unwrap(), report.last_sequence);
+        {
+            let records = sink.records.lock().unwrap();
+            let ids: Vec<&String> = { let mut ids: Vec<&String> = records.keys().collect(); ids.sort(); ids };
+            let mut expected = vec![&gold, &silver];
+            expected.sort();
+            assert_eq!(ids, expected);
+            assert_eq!(records[&gold].vector.len(), 256);
+        }
+
+        // A failed batch is sent again by the next sync
+        let bronze = cache.save("alice", "s1", "Bronze is down", None).unwrap();
+        *sink.failing.lock().unwrap() = true;
+        assert!(cache.sync_vectors(&sync).is_err());
+        *sink.failing.lock().unwrap() = false;
+        assert_eq!(cache.sync_vectors(&sync).unwrap().upserted, 1);
+        assert!(sink.records.lock().unwrap().contains_key(&bronze));
+        assert_eq!(cache.sync_vectors(&sync).unwrap(), VectorSyncReport { last_sequence: report.last_sequence + 1, ..Default::default() });
+
+        // Memories saved with the change log off reach the sink on a resync
+        assert!(!sink.records.lock().unwrap().contains_key(&before_log));
+        assert_eq!(cache.resync_vectors(&sync).unwrap().upserted, 4);
+        assert!(sink.records.lock().unwrap().contains_key(&before_log));
+    }
+}
@@ -0,0 +1,121 @@
+//! Updating memories in place with optimistic concurrency
+//!
+//! Every memory carries a `version` that each update bumps. A writer that
+//! read version `n` passes it to `MindCache::update_memory_if_version`; if
+//! someone else updated the memory in the meantime the call fails with a
+//! [`VersionConflict`] instead of overwriting their edit, and the writer can
+//! re-read and retry.
+
+use std::collections::HashMap;
+use std::fmt;
+use serde::{Deserialize, Serialize};
+use crate::storage::MemoryItem;
+use crate::MindCache;
+
+/// Fields to change on a memory; `None` leaves a field as it is
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MemoryUpdate {
+    pub content: Option<String>,
+    /// Replaces the whole metadata map
+    pub metadata: Option<HashMap<String, String>>,
+    pub importance: Option<f32>,
+    /// `Some(None)` removes the TTL, leaving expiry to the decay policy
+    pub ttl_hours: Option<Option<u32>>,
+}
+
+impl MemoryUpdate {
+    pub(crate) fn apply(self, memory: &mut MemoryItem) {
+        if let Some(content) = self.content {
+            memory.content = content;
+        }
+        if let Some(metadata) = self.metadata {
+            memory.metadata = metadata;
+        }
+        if let Some(importance) = self.importance {
+            memory.importance = importance.clamp(0.0, 1.0);
+        }
+        if let Some(ttl_hours) = self.ttl_hours {
+            memory.ttl_hours = ttl_hours;
+        }
+    }
+}
+
+/// An update was refused because the memory changed since it was read
+#[derive(Debug)]
+pub struct VersionConflict {
+    pub memory_id: String,
+    pub expected: u64,
+    /// Version the memory is at now
+    pub actual: u64,
+}
+
+impl fmt::Display for VersionConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "memory {} is at version {}, not {}",
+            self.memory_id, self.actual, self.expected
+        )
+    }
+}
+
+impl std::error::Error for VersionConflict {}
+
+impl MindCache {
+    /// Change a memory in place, whatever its current version
+    ///
+    /// Returns the updated memory, with its new `version`.
+    pub fn update_memory(&mut self, memory_id: &str, update: MemoryUpdate) -> Result<MemoryItem, Box<dyn std::error::Error>> {
+        self.storage.update_memory(memory_id, None, |memory| update.apply(memory))
+    }
+
+    /// Change a memory only if it is still at `expected_version`
+    ///
+    /// Fails with [`VersionConflict`] if another writer updated it first.
+    pub fn update_memory_if_version(&mut self, memory_id: &str, expected_version: u64, update: MemoryUpdate) -> Result<MemoryItem, Box<dyn std::error::Error>> {
+        self.storage.update_memory(memory_id, Some(expected_version), |memory| update.apply(memory))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::test_config;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_conditional_update_detects_conflicts() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = test_config(&temp_dir);
+        let mut cache = MindCache::with_config(config.clone()).unwrap();
+        let id = cache.save("alice", "s1", "Prefers tea", None).unwrap();
+        let read = cache.get_memory(&id).unwrap().unwrap();
+        assert_eq!(read.version, 0);
+
+        // Two workers read version 0; the first one to write wins
+        let first = cache.update_memory_if_version(&id, read.version, MemoryUpdate {
+            content: Some("Prefers green tea".to_string()),
+            ..Default::default()
+        }).unwrap();
+        assert_eq!(first.version, 1);
+        let err = cache.update_memory_if_version(&id, read.version, MemoryUpdate {
+            content: Some("Prefers coffee".to_string()),
+            ..Default::default()
+        }).unwrap_err();
+        let conflict = err.downcast_ref::<VersionConflict>().expect("version conflict");
+        assert_eq!((conflict.expected, conflict.actual), (0, 1));
+
+        let updated = cache.update_memory(&id, MemoryUpdate { importance: Some(0.9), ..Default::default() }).unwrap();
+        assert_eq!(updated.version, 2);
+        assert_eq!(updated.content, "Prefers green tea");
+        assert_eq!(cache.recall("alice", Some("green"), None, None).unwrap().len(), 1);
+        assert!(cache.recall("alice", Some("coffee"), None, None).unwrap().is_empty());
+        assert!(cache.update_memory("missing", MemoryUpdate::default()).is_err());
+
+        drop(cache);
+        let reopened = MindCache::with_config(config).unwrap();
+        let memory = reopened.get_memory(&id).unwrap().unwrap();
+        assert_eq!(memory.version, 2);
+        assert_eq!(memory.importance, 0.9);
+    }
+}
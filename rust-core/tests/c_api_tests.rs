@@ -63,6 +63,78 @@ fn test_c_api_invalid_config() {
     assert!(null_cache_ptr.is_null(), "Null config should return null");
 }
 
+#[test]
+fn test_c_api_config_validation_errors() {
+    let last_error = || {
+        let error_ptr = mindcache_last_error();
+        assert!(!error_ptr.is_null(), "A failed init should leave an error");
+        let message = unsafe { CStr::from_ptr(error_ptr) }.to_str().unwrap().to_string();
+        mindcache_free_string(error_ptr);
+        message
+    };
+
+    let invalid_json = CString::new("{ invalid json }").unwrap();
+    assert!(mindcache_init_with_config(invalid_json.as_ptr()).is_null());
+    assert!(last_error().contains("not valid JSON"));
+
+    let temp_dir = TempDir::new().expect("Should create temp dir");
+    let storage_path = temp_dir.path().to_str().unwrap().replace("\\", "/");
+    let config = |threshold: f32, max_memories: usize| format!(r#"{{
+        "storage_path": "{}",
+        "auto_decay_enabled": false,
+        "decay_interval_hours": 24,
+        "default_memory_ttl_hours": 720,
+        "enable_compression": false,
+        "max_memories_per_user": {},
+        "importance_threshold": {}
+    }}"#, storage_path, max_memories, threshold);
+
+    let invalid = CString::new(config(1.5, 0)).unwrap();
+    assert!(mindcache_init_with_config(invalid.as_ptr()).is_null());
+    let message = last_error();
+    assert!(message.contains("importance_threshold"), "{}", message);
+    assert!(message.contains("max_memories_per_user"), "{}", message);
+
+    let valid = CString::new(config(0.3, 100)).unwrap();
+    let cache_ptr = mindcache_init_with_config(valid.as_ptr());
+    assert!(!cache_ptr.is_null());
+    assert!(mindcache_last_error().is_null(), "Success should clear the error");
+    mindcache_destroy(cache_ptr);
+}
+
+#[test]
+fn test_c_api_config_with_decay_policy() {
+    let temp_dir = TempDir::new().expect("Should create temp dir");
+    let storage_path = temp_dir.path().to_str().unwrap().replace("\\", "/");
+    let config = |max_age_hours: u32| format!(r#"{{
+        "storage_path": "{}",
+        "auto_decay_enabled": false,
+        "decay_interval_hours": 24,
+        "default_memory_ttl_hours": 720,
+        "enable_compression": true,
+        "max_memories_per_user": 100,
+        "importance_threshold": 0.3,
+        "decay_policy": {{ "max_age_hours": {}, "auto_summarize_sessions": false }}
+    }}"#, storage_path, max_age_hours);
+
+    let invalid = CString::new(config(0)).unwrap();
+    assert!(mindcache_init_with_config(invalid.as_ptr()).is_null());
+    let error_ptr = mindcache_last_error();
+    let message = unsafe { CStr::from_ptr(error_ptr) }.to_str().unwrap().to_string();
+    mindcache_free_string(error_ptr);
+    assert!(message.contains("decay_policy.max_age_hours"), "{}", message);
+
+    let valid = CString::new(config(72)).unwrap();
+    let cache_ptr = mindcache_init_with_config(valid.as_ptr());
+    assert!(!cache_ptr.is_null());
+    let policy = unsafe { &*cache_ptr }.decay_policy();
+    assert_eq!(policy.max_age_hours, 72);
+    assert!(!policy.auto_summarize_sessions);
+    // Fields the policy leaves out take the policy defaults, not the config's
+    assert_eq!(policy.max_memories_per_user, DecayPolicy::default().max_memories_per_user);
+    mindcache_destroy(cache_ptr);
+}
+
 #[test]
 fn test_c_api_save_and_recall() {
     let cache_ptr = mindcache_init();
@@ -229,14 +301,9 @@ fn test_c_api_summarize() {
     println!("Recalled memories: {}", &recall_json[..recall_json.len().min(200)]);
     mindcache_free_string(recall_ptr);
     
-    // Generate summary - but handle the case where it might fail
+    // Generate summary
     let summary_ptr = mindcache_summarize(cache_ptr, session_id.as_ptr());
-    
-    if summary_ptr.is_null() {
-        println!("Summary generation failed - this is a known issue with session isolation");
-        mindcache_destroy(cache_ptr);
-        return; // Skip assertion for now
-    }
+    assert!(!summary_ptr.is_null(), "Summary should be generated for a populated session");
     
     let summary_cstr = unsafe { CStr::from_ptr(summary_ptr) };
     let summary_json = summary_cstr.to_str().expect("Should convert summary");
@@ -290,6 +357,341 @@ fn test_c_api_decay() {
    mindcache_destroy(cache_ptr);
 }
 
+#[test]
+fn test_c_api_dedupe() {
+    let temp_dir = TempDir::new().expect("Should create temp dir");
+    let storage_path = temp_dir.path().to_str().unwrap().replace("\\", "/");
+    let config_json = format!(r#"{{
+        "storage_path": "{}",
+        "auto_decay_enabled": false,
+        "decay_interval_hours": 24,
+        "default_memory_ttl_hours": null,
+        "enable_compression": true,
+        "max_memories_per_user": 1000,
+        "importance_threshold": 0.3
+    }}"#, storage_path);
+    let config_cstring = CString::new(config_json).unwrap();
+    let cache_ptr = mindcache_init_with_config(config_cstring.as_ptr());
+    assert!(!cache_ptr.is_null());
+
+    let user_id = CString::new("dedupe_user").unwrap();
+    let session_id = CString::new("dedupe_session").unwrap();
+    for content in ["Remember to water the plants", "remember to water the plants!"] {
+        let content = CString::new(content).unwrap();
+        let memory_id_ptr = mindcache_save(cache_ptr, user_id.as_ptr(), session_id.as_ptr(), content.as_ptr(), ptr::null());
+        assert!(!memory_id_ptr.is_null());
+        mindcache_free_string(memory_id_ptr);
+    }
+
+    let report_ptr = mindcache_dedupe(cache_ptr, user_id.as_ptr(), 0.9, 1);
+    assert!(!report_ptr.is_null(), "Dry run should return a report");
+    let report: serde_json::Value = serde_json::from_str(unsafe { CStr::from_ptr(report_ptr) }.to_str().unwrap()).unwrap();
+    assert_eq!(report["memories_removed"], 1);
+    assert_eq!(report["dry_run"], true);
+    mindcache_free_string(report_ptr);
+
+    let report_ptr = mindcache_dedupe(cache_ptr, user_id.as_ptr(), 0.9, 0);
+    assert!(!report_ptr.is_null());
+    mindcache_free_string(report_ptr);
+
+    let recall_ptr = mindcache_recall(cache_ptr, user_id.as_ptr(), ptr::null(), ptr::null(), -1);
+    let memories: serde_json::Value = serde_json::from_str(unsafe { CStr::from_ptr(recall_ptr) }.to_str().unwrap()).unwrap();
+    assert_eq!(memories.as_array().unwrap().len(), 1);
+    mindcache_free_string(recall_ptr);
+
+    assert!(mindcache_dedupe(ptr::null_mut(), user_id.as_ptr(), 0.9, 1).is_null());
+    assert!(mindcache_dedupe(cache_ptr, ptr::null(), 0.9, 1).is_null());
+
+    mindcache_destroy(cache_ptr);
+}
+
+#[test]
+fn test_c_api_recall_cursor() {
+    let temp_dir = TempDir::new().expect("Should create temp dir");
+    let storage_path = temp_dir.path().to_str().unwrap().replace("\\", "/");
+    let config_json = format!(r#"{{
+        "storage_path": "{}",
+        "auto_decay_enabled": false,
+        "decay_interval_hours": 24,
+        "default_memory_ttl_hours": null,
+        "enable_compression": true,
+        "max_memories_per_user": 1000,
+        "importance_threshold": 0.3
+    }}"#, storage_path);
+    let config_cstring = CString::new(config_json).unwrap();
+    let cache_ptr = mindcache_init_with_config(config_cstring.as_ptr());
+    assert!(!cache_ptr.is_null());
+
+    let user_id = CString::new("cursor_user").unwrap();
+    let session_id = CString::new("cursor_session").unwrap();
+    for i in 0..5 {
+        let content = CString::new(format!("Cursor memory {}", i)).unwrap();
+        let id_ptr = mindcache_save(cache_ptr, user_id.as_ptr(), session_id.as_ptr(), content.as_ptr(), ptr::null());
+        mindcache_free_string(id_ptr);
+        std::thread::sleep(std::time::Duration::from_millis(2));
+    }
+
+    let filter = CString::new(r#"{"user_id": "cursor_user", "limit": 4}"#).unwrap();
+    let cursor = mindcache_recall_open(cache_ptr, filter.as_ptr());
+    assert!(!cursor.is_null());
+    let mut contents = Vec::new();
+    loop {
+        let memory_ptr = mindcache_recall_next(cursor);
+        if memory_ptr.is_null() {
+            break;
+        }
+        let memory: serde_json::Value = serde_json::from_str(unsafe { CStr::from_ptr(memory_ptr) }.to_str().unwrap()).unwrap();
+        contents.push(memory["content"].as_str().unwrap().to_string());
+        mindcache_free_string(memory_ptr);
+    }
+    assert_eq!(contents, ["Cursor memory 4", "Cursor memory 3", "Cursor memory 2", "Cursor memory 1"]);
+    assert!(mindcache_recall_next(cursor).is_null(), "an exhausted cursor stays exhausted");
+    mindcache_recall_close(cursor);
+
+    let bad_filter = CString::new("{").unwrap();
+    assert!(mindcache_recall_open(cache_ptr, bad_filter.as_ptr()).is_null());
+    assert!(mindcache_recall_open(ptr::null_mut(), filter.as_ptr()).is_null());
+    assert!(mindcache_recall_next(ptr::null_mut()).is_null());
+    mindcache_recall_close(ptr::null_mut());
+
+    mindcache_destroy(cache_ptr);
+}
+
+#[test]
+fn test_c_api_version_and_features() {
+    assert_eq!(mindcache_abi_version(), ABI_VERSION);
+
+    let features_ptr = mindcache_features();
+    assert!(!features_ptr.is_null());
+    let features: serde_json::Value = serde_json::from_str(unsafe { CStr::from_ptr(features_ptr) }.to_str().unwrap()).unwrap();
+    mindcache_free_string(features_ptr);
+
+    assert_eq!(features["abi_version"], ABI_VERSION);
+    assert_eq!(features["version"], env!("CARGO_PKG_VERSION"));
+    let functions: Vec<&str> = features["functions"].as_array().unwrap().iter().map(|f| f.as_str().unwrap()).collect();
+    for function in ["mindcache_init", "mindcache_recall_advanced", "mindcache_save_ex", "mindcache_decay_async", "mindcache_features"] {
+        assert!(functions.contains(&function), "{} should be listed", function);
+    }
+    assert!(features["features"].is_array());
+}
+
+type AsyncOutcome = Result<String, String>;
+
+extern "C" fn send_outcome(user_data: *mut std::ffi::c_void, result_json: *const std::os::raw::c_char, error: *const std::os::raw::c_char) {
+    let sender = unsafe { &*(user_data as *const std::sync::mpsc::Sender<AsyncOutcome>) };
+    let text = |s: *const std::os::raw::c_char| unsafe { CStr::from_ptr(s) }.to_str().unwrap().to_string();
+    let outcome = if error.is_null() { Ok(text(result_json)) } else { Err(text(error)) };
+    sender.send(outcome).unwrap();
+}
+
+#[test]
+fn test_c_api_async_operations() {
+    let temp_dir = TempDir::new().expect("Should create temp dir");
+    let storage_path = temp_dir.path().to_str().unwrap().replace("\\", "/");
+    let config_json = format!(r#"{{
+        "storage_path": "{}",
+        "auto_decay_enabled": false,
+        "decay_interval_hours": 24,
+        "default_memory_ttl_hours": null,
+        "enable_compression": true,
+        "max_memories_per_user": 1000,
+        "importance_threshold": 0.3
+    }}"#, storage_path);
+    let config_cstring = CString::new(config_json).unwrap();
+    let cache_ptr = mindcache_init_with_config(config_cstring.as_ptr());
+    assert!(!cache_ptr.is_null());
+
+    let user_id = CString::new("async_user").unwrap();
+    let session_id = CString::new("async_session").unwrap();
+    let content = CString::new("Saved before the async calls").unwrap();
+    let id_ptr = mindcache_save(cache_ptr, user_id.as_ptr(), session_id.as_ptr(), content.as_ptr(), ptr::null());
+    mindcache_free_string(id_ptr);
+
+    let (sender, receiver) = std::sync::mpsc::channel::<AsyncOutcome>();
+    let user_data = &sender as *const _ as *mut std::ffi::c_void;
+    let wait = || receiver.recv_timeout(std::time::Duration::from_secs(30)).expect("callback should run");
+
+    // One call at a time: the handle is off limits until its callback has run
+    assert_eq!(mindcache_decay_async(cache_ptr, Some(send_outcome), user_data), 0);
+    let stats: serde_json::Value = serde_json::from_str(&wait().unwrap()).unwrap();
+    assert!(stats["total_memories_before"].is_number());
+
+    assert_eq!(mindcache_export_async(cache_ptr, user_id.as_ptr(), Some(send_outcome), user_data), 0);
+    let exported: serde_json::Value = serde_json::from_str(&wait().unwrap()).unwrap();
+    assert_eq!(exported.as_array().unwrap().len(), 1);
+
+    assert_eq!(mindcache_compact_async(cache_ptr, Some(send_outcome), user_data), 0);
+    let report: serde_json::Value = serde_json::from_str(&wait().unwrap()).unwrap();
+    assert_eq!(report["records_kept"], 1);
+
+    assert_eq!(mindcache_decay_async(ptr::null_mut(), Some(send_outcome), user_data), -1);
+    assert_eq!(mindcache_compact_async(cache_ptr, None, user_data), -1);
+    assert_eq!(mindcache_export_async(cache_ptr, ptr::null(), Some(send_outcome), user_data), -1);
+    assert!(receiver.try_recv().is_err(), "rejected calls must not call back");
+
+    mindcache_destroy(cache_ptr);
+}
+
+#[test]
+fn test_c_api_save_ex() {
+    let temp_dir = TempDir::new().expect("Should create temp dir");
+    let storage_path = temp_dir.path().to_str().unwrap().replace("\\", "/");
+    let config_json = format!(r#"{{
+        "storage_path": "{}",
+        "auto_decay_enabled": false,
+        "decay_interval_hours": 24,
+        "default_memory_ttl_hours": null,
+        "enable_compression": true,
+        "max_memories_per_user": 1000,
+        "importance_threshold": 0.3
+    }}"#, storage_path);
+    let config_cstring = CString::new(config_json).unwrap();
+    let cache_ptr = mindcache_init_with_config(config_cstring.as_ptr());
+    assert!(!cache_ptr.is_null());
+
+    let user_id = CString::new("options_user").unwrap();
+    let session_id = CString::new("options_session").unwrap();
+    let content = CString::new("Quarterly review is on Friday").unwrap();
+    let metadata = CString::new(r#"{"source": "calendar"}"#).unwrap();
+
+    let id_ptr = mindcache_save_ex(cache_ptr, user_id.as_ptr(), session_id.as_ptr(), content.as_ptr(), metadata.as_ptr(), 0.9, 48);
+    assert!(!id_ptr.is_null());
+    mindcache_free_string(id_ptr);
+    let id_ptr = mindcache_save_ex(cache_ptr, user_id.as_ptr(), session_id.as_ptr(), content.as_ptr(), ptr::null(), 1.5, -1);
+    assert!(!id_ptr.is_null());
+    mindcache_free_string(id_ptr);
+    let id_ptr = mindcache_save_ex(cache_ptr, user_id.as_ptr(), session_id.as_ptr(), content.as_ptr(), ptr::null(), 0.2, 0);
+    assert!(!id_ptr.is_null());
+    mindcache_free_string(id_ptr);
+
+    let recall_ptr = mindcache_recall(cache_ptr, user_id.as_ptr(), ptr::null(), ptr::null(), -1);
+    let memories: serde_json::Value = serde_json::from_str(unsafe { CStr::from_ptr(recall_ptr) }.to_str().unwrap()).unwrap();
+    mindcache_free_string(recall_ptr);
+    let memories = memories.as_array().unwrap();
+    let with_ttl = memories.iter().find(|m| m["ttl_hours"] == 48).expect("TTL should be kept");
+    assert!((with_ttl["importance"].as_f64().unwrap() - 0.9).abs() < 1e-6);
+    assert_eq!(with_ttl["metadata"]["source"], "calendar");
+    let without_ttl = memories.iter().find(|m| m["ttl_hours"].is_null()).expect("a negative TTL should mean none");
+    assert_eq!(without_ttl["importance"], 1.0);
+    let forever = memories.iter().find(|m| m["ttl_hours"] == 0).expect("0 should keep the memory forever");
+    assert!(forever["expires_at"].is_null());
+
+    let bad_metadata = CString::new("[1, 2]").unwrap();
+    assert!(mindcache_save_ex(cache_ptr, user_id.as_ptr(), session_id.as_ptr(), content.as_ptr(), bad_metadata.as_ptr(), 0.5, 0).is_null());
+    assert!(mindcache_save_ex(cache_ptr, user_id.as_ptr(), session_id.as_ptr(), content.as_ptr(), ptr::null(), f32::NAN, 0).is_null());
+    assert!(mindcache_save_ex(cache_ptr, ptr::null(), session_id.as_ptr(), content.as_ptr(), ptr::null(), 0.5, 0).is_null());
+
+    mindcache_destroy(cache_ptr);
+}
+
+#[test]
+fn test_c_api_recall_advanced() {
+    let temp_dir = TempDir::new().expect("Should create temp dir");
+    let storage_path = temp_dir.path().to_str().unwrap().replace("\\", "/");
+    let config_json = format!(r#"{{
+        "storage_path": "{}",
+        "auto_decay_enabled": false,
+        "decay_interval_hours": 24,
+        "default_memory_ttl_hours": null,
+        "enable_compression": true,
+        "max_memories_per_user": 1000,
+        "importance_threshold": 0.3
+    }}"#, storage_path);
+    let config_cstring = CString::new(config_json).unwrap();
+    let cache_ptr = mindcache_init_with_config(config_cstring.as_ptr());
+    assert!(!cache_ptr.is_null());
+
+    let user_id = CString::new("filter_user").unwrap();
+    let session_id = CString::new("filter_session").unwrap();
+    for i in 0..5 {
+        let content = CString::new(format!("Trade note {}", i)).unwrap();
+        let ticker = if i % 2 == 0 { "AAPL" } else { "TSLA" };
+        let metadata = CString::new(format!(r#"{{"ticker": "{}"}}"#, ticker)).unwrap();
+        let memory_id_ptr = mindcache_save(cache_ptr, user_id.as_ptr(), session_id.as_ptr(), content.as_ptr(), metadata.as_ptr());
+        assert!(!memory_id_ptr.is_null());
+        mindcache_free_string(memory_id_ptr);
+    }
+
+    let recall = |filter: &str| -> Option<Vec<serde_json::Value>> {
+        let filter = CString::new(filter).unwrap();
+        let result_ptr = mindcache_recall_advanced(cache_ptr, filter.as_ptr());
+        if result_ptr.is_null() {
+            return None;
+        }
+        let memories = serde_json::from_str(unsafe { CStr::from_ptr(result_ptr) }.to_str().unwrap()).unwrap();
+        mindcache_free_string(result_ptr);
+        Some(memories)
+    };
+
+    let aapl = recall(r#"{"user_id": "filter_user", "metadata_filters": {"ticker": {"equals": "AAPL"}}}"#).unwrap();
+    assert_eq!(aapl.len(), 3);
+
+    let page = recall(r#"{"user_id": "filter_user", "offset": 3, "limit": 10, "min_importance": 0.1}"#).unwrap();
+    assert_eq!(page.len(), 2);
+
+    let future = recall(r#"{"user_id": "filter_user", "date_from": "2999-01-01T00:00:00Z"}"#).unwrap();
+    assert!(future.is_empty());
+
+    assert!(recall("not json").is_none());
+    assert!(recall(r#"{"limit": "ten"}"#).is_none());
+    assert!(mindcache_recall_advanced(ptr::null_mut(), user_id.as_ptr()).is_null());
+    assert!(mindcache_recall_advanced(cache_ptr, ptr::null()).is_null());
+
+    mindcache_destroy(cache_ptr);
+}
+
+#[test]
+fn test_c_api_backup_and_restore() {
+    let temp_dir = TempDir::new().expect("Should create temp dir");
+    let storage_path = temp_dir.path().join("store").to_str().unwrap().replace("\\", "/");
+    let backup_path = CString::new(temp_dir.path().join("store.bak").to_str().unwrap()).unwrap();
+    let config_json = format!(r#"{{
+        "storage_path": "{}",
+        "auto_decay_enabled": false,
+        "decay_interval_hours": 24,
+        "default_memory_ttl_hours": null,
+        "enable_compression": true,
+        "max_memories_per_user": 1000,
+        "importance_threshold": 0.3
+    }}"#, storage_path);
+    let config_cstring = CString::new(config_json).unwrap();
+    let cache_ptr = mindcache_init_with_config(config_cstring.as_ptr());
+    assert!(!cache_ptr.is_null());
+
+    let user_id = CString::new("backup_user").unwrap();
+    let session_id = CString::new("backup_session").unwrap();
+    let save = |content: &str| {
+        let content = CString::new(content).unwrap();
+        let memory_id_ptr = mindcache_save(cache_ptr, user_id.as_ptr(), session_id.as_ptr(), content.as_ptr(), ptr::null());
+        assert!(!memory_id_ptr.is_null());
+        mindcache_free_string(memory_id_ptr);
+    };
+
+    save("Saved before the backup");
+    let manifest_ptr = mindcache_create_backup(cache_ptr, backup_path.as_ptr());
+    assert!(!manifest_ptr.is_null(), "Backup should return a manifest");
+    let manifest: serde_json::Value = serde_json::from_str(unsafe { CStr::from_ptr(manifest_ptr) }.to_str().unwrap()).unwrap();
+    assert_eq!(manifest["memory_count"], 1);
+    mindcache_free_string(manifest_ptr);
+
+    save("Saved after the backup");
+    let manifest_ptr = mindcache_restore_backup(cache_ptr, backup_path.as_ptr());
+    assert!(!manifest_ptr.is_null(), "Restore should return the manifest");
+    mindcache_free_string(manifest_ptr);
+
+    let recall_ptr = mindcache_recall(cache_ptr, user_id.as_ptr(), ptr::null(), ptr::null(), -1);
+    let memories: serde_json::Value = serde_json::from_str(unsafe { CStr::from_ptr(recall_ptr) }.to_str().unwrap()).unwrap();
+    assert_eq!(memories.as_array().unwrap().len(), 1);
+    mindcache_free_string(recall_ptr);
+
+    let missing = CString::new(temp_dir.path().join("missing.bak").to_str().unwrap()).unwrap();
+    assert!(mindcache_restore_backup(cache_ptr, missing.as_ptr()).is_null());
+    assert!(mindcache_create_backup(ptr::null_mut(), backup_path.as_ptr()).is_null());
+    assert!(mindcache_restore_backup(cache_ptr, ptr::null()).is_null());
+
+    mindcache_destroy(cache_ptr);
+}
+
 #[test]
 fn test_c_api_utf8_handling() {
     let cache_ptr = mindcache_init();
@@ -335,6 +737,68 @@ fn test_c_api_utf8_handling() {
     mindcache_destroy(cache_ptr);
 }
 
+#[test]
+fn test_c_api_calls_with_key_are_checked() {
+    let temp_dir = TempDir::new().expect("Should create temp dir");
+    let storage_path = temp_dir.path().to_str().unwrap().replace("\\", "/");
+    let config_json = format!(r#"{{
+        "storage_path": "{}",
+        "auto_decay_enabled": false,
+        "decay_interval_hours": 24,
+        "default_memory_ttl_hours": 720,
+        "enable_compression": false,
+        "max_memories_per_user": 100,
+        "importance_threshold": 0.3,
+        "api_keys": [
+            {{ "key": "alice-rw", "name": "alice", "users": ["alice"], "scopes": ["read", "write"] }},
+            {{ "key": "ops", "users": ["*"], "scopes": ["admin"] }}
+        ]
+    }}"#, storage_path);
+    let config_cstring = CString::new(config_json).unwrap();
+    let cache_ptr = mindcache_init_with_config(config_cstring.as_ptr());
+    assert!(!cache_ptr.is_null());
+
+    let take = |result: *mut std::os::raw::c_char| {
+        assert!(!result.is_null(), "Call should succeed");
+        let text = unsafe { CStr::from_ptr(result) }.to_str().unwrap().to_string();
+        mindcache_free_string(result);
+        text
+    };
+    let last_error = || take(mindcache_last_error());
+    let (alice_key, ops_key, wrong_key) = (CString::new("alice-rw").unwrap(), CString::new("ops").unwrap(), CString::new("nope").unwrap());
+    let (alice, bob) = (CString::new("alice").unwrap(), CString::new("bob").unwrap());
+    let session = CString::new("s1").unwrap();
+    let content = CString::new("Prefers window seats").unwrap();
+    let save = |key: *const std::os::raw::c_char, user: &CString| {
+        mindcache_save_with_key(cache_ptr, key, user.as_ptr(), session.as_ptr(), content.as_ptr(), ptr::null())
+    };
+
+    assert!(save(ptr::null(), &alice).is_null());
+    assert!(last_error().contains("API key is required"));
+    assert!(save(wrong_key.as_ptr(), &alice).is_null());
+    assert!(last_error().contains("unknown API key"));
+    assert!(save(alice_key.as_ptr(), &bob).is_null());
+    assert!(last_error().contains("does not reach user bob"));
+    take(save(alice_key.as_ptr(), &alice));
+    assert!(mindcache_last_error().is_null(), "Success should clear the error");
+
+    let alice_filter = CString::new(r#"{"user_id": "alice"}"#).unwrap();
+    let everyone = CString::new("{}").unwrap();
+    let memories: serde_json::Value = serde_json::from_str(&take(mindcache_recall_advanced_with_key(cache_ptr, alice_key.as_ptr(), alice_filter.as_ptr()))).unwrap();
+    assert_eq!(memories.as_array().unwrap().len(), 1);
+    assert!(mindcache_recall_advanced_with_key(cache_ptr, alice_key.as_ptr(), everyone.as_ptr()).is_null());
+
+    assert!(mindcache_decay_with_key(cache_ptr, alice_key.as_ptr()).is_null());
+    assert!(last_error().contains("lacks the admin scope"));
+    assert!(mindcache_delete_user_with_key(cache_ptr, alice_key.as_ptr(), alice.as_ptr()).is_null());
+    take(mindcache_decay_with_key(cache_ptr, ops_key.as_ptr()));
+    take(mindcache_compact_with_key(cache_ptr, ops_key.as_ptr()));
+    let report: serde_json::Value = serde_json::from_str(&take(mindcache_delete_user_with_key(cache_ptr, ops_key.as_ptr(), alice.as_ptr()))).unwrap();
+    assert_eq!(report["memories_deleted"], 1);
+
+    mindcache_destroy(cache_ptr);
+}
+
 #[test]
 fn test_c_api_large_data_handling() {
    let cache_ptr = mindcache_init();
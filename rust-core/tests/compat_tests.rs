@@ -0,0 +1,185 @@
+//! On-disk compatibility tests for MindCache
+//!
+//! Each directory under `tests/fixtures/` is a store written by a released
+//! version of the crate, together with the memories it is expected to contain.
+//! Newer versions must open these stores and read every memory back unchanged.
+//! Fixtures are never regenerated: when the format changes, add a new fixture
+//! directory next to the existing ones instead.
+
+use mindcache_core::{MemoryItem, MemoryType, MindCache, MindCacheConfig, QueryFilter};
+use std::path::Path;
+use tempfile::TempDir;
+
+fn config(temp_dir: &TempDir) -> MindCacheConfig {
+    MindCacheConfig {
+        storage_path: temp_dir.path().to_str().unwrap().to_string(),
+        auto_decay_enabled: false,
+        ..Default::default()
+    }
+}
+
+/// Copy a checked-in fixture store into a scratch directory so tests can't modify it
+fn open_fixture(version: &str) -> (MindCache, Vec<MemoryItem>, TempDir) {
+    let fixture_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures").join(version);
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+
+    for file in ["memories.bin", "index.bin"] {
+        std::fs::copy(fixture_dir.join(file), temp_dir.path().join(file))
+            .unwrap_or_else(|e| panic!("Fixture {} is missing {}: {}", version, file, e));
+    }
+
+    let expected_json = std::fs::read_to_string(fixture_dir.join("expected_memories.json"))
+        .expect("Fixture should have expected_memories.json");
+    let mut expected: Vec<MemoryItem> = serde_json::from_str(&expected_json)
+        .expect("expected_memories.json should parse");
+    // Fixtures predate `expires_at`, which is derived from the TTL on read
+    for memory in &mut expected {
+        memory.expires_at = memory.ttl_expiry();
+    }
+
+    let cache = MindCache::with_config(config(&temp_dir)).expect("Fixture store should open");
+
+    (cache, expected, temp_dir)
+}
+
+fn assert_same_memory(actual: &MemoryItem, expected: &MemoryItem) {
+    assert_eq!(
+        serde_json::to_value(actual).unwrap(),
+        serde_json::to_value(expected).unwrap(),
+        "Memory {} changed after reading it back",
+        expected.id
+    );
+}
+
+fn assert_fixture_readable(version: &str) {
+    let (cache, expected, _temp_dir) = open_fixture(version);
+
+    let mut user_ids: Vec<&str> = expected.iter().map(|m| m.user_id.as_str()).collect();
+    user_ids.sort();
+    user_ids.dedup();
+
+    let mut recalled = Vec::new();
+    for user_id in user_ids {
+        recalled.extend(cache.recall(user_id, None, None, None).expect("Recall should succeed"));
+    }
+    assert_eq!(recalled.len(), expected.len(), "Every fixture memory should be recalled");
+
+    for expected_memory in &expected {
+        let actual = recalled
+            .iter()
+            .find(|m| m.id == expected_memory.id)
+            .unwrap_or_else(|| panic!("Memory {} is missing", expected_memory.id));
+        assert_same_memory(actual, expected_memory);
+    }
+}
+
+#[test]
+fn test_v0_1_0_store_is_readable() {
+    assert_fixture_readable("v0_1_0");
+}
+
+#[test]
+fn test_v0_1_0_session_and_keyword_recall() {
+    let (cache, expected, _temp_dir) = open_fixture("v0_1_0");
+
+    let trading = cache.get_session_memories("alice", "trading").expect("Should get session memories");
+    let expected_trading: Vec<_> = expected.iter().filter(|m| m.session_id == "trading").collect();
+    assert_eq!(trading.len(), expected_trading.len());
+
+    // Multi-byte content must survive the round trip intact
+    let japanese = cache.recall("bob", Some("ビットコイン"), None, None).expect("Should recall");
+    assert_eq!(japanese.len(), 1);
+    assert_eq!(japanese[0].metadata.get("lang").map(String::as_str), Some("ja"));
+}
+
+#[test]
+fn test_v0_1_0_store_accepts_new_writes() {
+    let (mut cache, expected, temp_dir) = open_fixture("v0_1_0");
+
+    cache.save("alice", "trading", "Re-entered AAPL on the pullback", None)
+        .expect("Should append to an old store");
+
+    // Reopen from disk to make sure old and new records coexist
+    drop(cache);
+    let reopened = MindCache::with_config(config(&temp_dir)).expect("Store should reopen");
+
+    let alice = reopened.recall("alice", None, None, None).expect("Should recall");
+    let expected_alice = expected.iter().filter(|m| m.user_id == "alice").count();
+    assert_eq!(alice.len(), expected_alice + 1);
+
+    for expected_memory in expected.iter().filter(|m| m.user_id == "alice") {
+        let actual = alice.iter().find(|m| m.id == expected_memory.id).expect("Old memory should remain");
+        assert_same_memory(actual, expected_memory);
+    }
+}
+
+#[test]
+fn test_compaction_moves_single_log_into_user_segments() {
+    let (mut cache, expected, temp_dir) = open_fixture("v0_1_0");
+
+    cache.compact().expect("Should compact an old store");
+    assert!(!temp_dir.path().join("memories.bin").exists());
+    assert!(temp_dir.path().join("segments.json").exists());
+
+    drop(cache);
+    let reopened = MindCache::with_config(config(&temp_dir)).expect("Store should reopen");
+    for expected_memory in &expected {
+        let memories = reopened.recall(&expected_memory.user_id, None, None, None).expect("Should recall");
+        let actual = memories.iter().find(|m| m.id == expected_memory.id).expect("Memory should survive");
+        assert_same_memory(actual, expected_memory);
+    }
+}
+
+#[test]
+fn test_records_v2_store_is_readable() {
+    assert_fixture_readable("records_v2");
+}
+
+#[test]
+fn test_records_v2_roles_and_types_survive() {
+    let (cache, _expected, _temp_dir) = open_fixture("records_v2");
+
+    let filter = QueryFilter {
+        user_id: Some("alice".to_string()),
+        role: Some("user".to_string()),
+        ..Default::default()
+    };
+    let user_turns = cache.recall_advanced(filter).expect("Should recall");
+    assert_eq!(user_turns.len(), 1);
+    assert_eq!(user_turns[0].memory_type, MemoryType::Message);
+
+    let filter = QueryFilter {
+        user_id: Some("bob".to_string()),
+        memory_type: Some(MemoryType::Task),
+        ..Default::default()
+    };
+    assert_eq!(cache.recall_advanced(filter).expect("Should recall").len(), 1);
+}
+
+#[test]
+fn test_old_logs_are_upgraded_on_open() {
+    let (cache, expected, temp_dir) = open_fixture("records_v2");
+    let log = std::fs::read(temp_dir.path().join("memories.bin")).expect("Log should remain");
+    assert_eq!(&log[..8], &mindcache_core::record::log_header(), "Log should be rewritten with a header");
+    drop(cache);
+
+    // The upgraded store opens as is, without a second upgrade
+    let reopened = MindCache::with_config(config(&temp_dir)).expect("Upgraded store should reopen");
+    assert_eq!(std::fs::read(temp_dir.path().join("memories.bin")).unwrap(), log);
+    for expected_memory in &expected {
+        let memories = reopened.recall(&expected_memory.user_id, None, None, None).expect("Should recall");
+        let actual = memories.iter().find(|m| m.id == expected_memory.id).expect("Memory should survive the upgrade");
+        assert_same_memory(actual, expected_memory);
+    }
+}
+
+#[test]
+fn test_migration_backs_up_old_files_and_records_version() {
+    let (cache, _expected, temp_dir) = open_fixture("v0_1_0");
+    assert_eq!(cache.storage_version().unwrap(), mindcache_core::migrations::STORAGE_VERSION);
+
+    let fixture_log = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/v0_1_0/memories.bin");
+    let backup_dir = temp_dir.path().join(mindcache_core::migrations::backup_dir(1));
+    assert_eq!(std::fs::read(backup_dir.join("memories.bin")).unwrap(), std::fs::read(fixture_log).unwrap());
+    assert!(backup_dir.join("index.bin").exists());
+}
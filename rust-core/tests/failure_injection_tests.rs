@@ -0,0 +1,260 @@
+//! Storage failure tests for MindCache
+//!
+//! These run the real engine on top of `FailingStorage` and check that a
+//! failed write never leaves a half-indexed memory behind, either in the
+//! running instance or after reopening the store.
+
+use mindcache_core::testing::{FailingStorage, FailureMode, FailurePlan, FailurePoint};
+use mindcache_core::{FileBackend, MindCache, MindCacheConfig, StorageBackend};
+use std::sync::Arc;
+use tempfile::TempDir;
+
+fn config(temp_dir: &TempDir) -> MindCacheConfig {
+    MindCacheConfig {
+        storage_path: temp_dir.path().to_str().unwrap().to_string(),
+        auto_decay_enabled: false,
+        ..Default::default()
+    }
+}
+
+fn open_failing(temp_dir: &TempDir, plan: FailurePlan) -> (MindCache, Arc<FailingStorage>) {
+    let inner: Arc<dyn StorageBackend> = Arc::new(FileBackend::new(temp_dir.path()).unwrap());
+    let failing = Arc::new(FailingStorage::new(inner, plan));
+    let cache = MindCache::with_backend(config(temp_dir), failing.clone()).unwrap();
+    (cache, failing)
+}
+
+fn reopen(temp_dir: &TempDir) -> MindCache {
+    MindCache::with_config(config(temp_dir)).unwrap()
+}
+
+#[test]
+fn test_write_failure_saves_nothing() {
+    let temp_dir = TempDir::new().unwrap();
+    let (mut cache, failing) = open_failing(&temp_dir, FailurePlan::fail(FailurePoint::Write, FailureMode::OnCall(2)));
+
+    cache.save("alice", "s1", "first memory", None).unwrap();
+    assert!(cache.save("alice", "s1", "second memory", None).is_err());
+    cache.save("alice", "s1", "third memory", None).unwrap();
+    assert_eq!(failing.injected_failures(), 1);
+
+    let contents: Vec<String> = cache.recall("alice", None, None, None).unwrap()
+        .into_iter().map(|m| m.content).collect();
+    assert_eq!(contents.len(), 2);
+    assert!(!contents.contains(&"second memory".to_string()));
+
+    drop(cache);
+    assert_eq!(reopen(&temp_dir).recall("alice", None, None, None).unwrap().len(), 2);
+}
+
+#[test]
+fn test_torn_write_does_not_corrupt_later_records() {
+    let temp_dir = TempDir::new().unwrap();
+    let (mut cache, failing) = open_failing(&temp_dir, FailurePlan::default());
+
+    cache.save("alice", "s1", "before the crash", None).unwrap();
+    failing.set_plan(FailurePlan {
+        write: FailureMode::Always,
+        torn_writes: true,
+        ..FailurePlan::default()
+    });
+    assert!(cache.save("alice", "s1", "torn record", None).is_err());
+
+    failing.set_plan(FailurePlan::default());
+    cache.save("alice", "s1", "after the crash", None).unwrap();
+
+    drop(cache);
+    let mut contents: Vec<String> = reopen(&temp_dir).recall("alice", None, None, None).unwrap()
+        .into_iter().map(|m| m.content).collect();
+    contents.sort();
+    assert_eq!(contents, vec!["after the crash", "before the crash"]);
+}
+
+#[test]
+fn test_flush_failure_is_reported() {
+    let temp_dir = TempDir::new().unwrap();
+    let (mut cache, _failing) = open_failing(&temp_dir, FailurePlan::fail(FailurePoint::Flush, FailureMode::Always));
+
+    assert!(cache.save("alice", "s1", "never durable", None).is_err());
+    assert!(cache.recall("alice", None, None, None).unwrap().is_empty());
+}
+
+#[test]
+fn test_index_persist_failure_rolls_back_index() {
+    let temp_dir = TempDir::new().unwrap();
+    let (mut cache, failing) = open_failing(&temp_dir, FailurePlan::default());
+    cache.save("alice", "s1", "indexed memory", None).unwrap();
+
+    failing.set_plan(FailurePlan::fail(FailurePoint::IndexPersist, FailureMode::Always));
+    assert!(cache.save("alice", "s1", "orphaned memory", None).is_err());
+
+    let memories = cache.recall("alice", None, None, None).unwrap();
+    assert_eq!(memories.len(), 1);
+    assert_eq!(memories[0].content, "indexed memory");
+
+    drop(cache);
+    let reopened = reopen(&temp_dir);
+    let memories = reopened.recall("alice", None, None, None).unwrap();
+    assert_eq!(memories.len(), 1);
+    assert_eq!(memories[0].content, "indexed memory");
+}
+
+#[test]
+fn test_partial_index_persist_is_repaired() {
+    let temp_dir = TempDir::new().unwrap();
+    let (mut cache, failing) = open_failing(&temp_dir, FailurePlan::default());
+    cache.save("alice", "s1", "indexed memory", None).unwrap();
+    // Compaction leaves the index log stale, so the next save rewrites both indices
+    cache.compact().unwrap();
+
+    // session_index.bin is replaced, index.bin fails; the rollback rewrites session_index.bin
+    failing.set_plan(FailurePlan::fail(FailurePoint::IndexPersist, FailureMode::OnCall(2)));
+    assert!(cache.save("alice", "s2", "orphaned memory", None).is_err());
+
+    drop(cache);
+    let reopened = reopen(&temp_dir);
+    assert_eq!(reopened.recall("alice", None, None, None).unwrap().len(), 1);
+    assert!(reopened.get_session_memories("alice", "s2").unwrap().is_empty());
+}
+
+#[test]
+fn test_torn_index_log_append_is_dropped() {
+    let temp_dir = TempDir::new().unwrap();
+    let (mut cache, failing) = open_failing(&temp_dir, FailurePlan::default());
+    cache.save("alice", "s1", "indexed memory", None).unwrap();
+
+    failing.set_plan(FailurePlan { index_persist: FailureMode::Always, torn_writes: true, ..FailurePlan::default() });
+    assert!(cache.save("alice", "s2", "orphaned memory", None).is_err());
+    failing.set_plan(FailurePlan::default());
+    cache.save("alice", "s1", "after the crash", None).unwrap();
+
+    drop(cache);
+    let reopened = reopen(&temp_dir);
+    assert_eq!(reopened.recall("alice", None, None, None).unwrap().len(), 2);
+    assert!(reopened.get_session_memories("alice", "s2").unwrap().is_empty());
+}
+
+#[test]
+fn test_random_failures_keep_store_consistent() {
+    let temp_dir = TempDir::new().unwrap();
+    let (mut cache, failing) = open_failing(&temp_dir, FailurePlan {
+        write: FailureMode::Rate(0.2),
+        flush: FailureMode::Rate(0.1),
+        torn_writes: true,
+        seed: 42,
+        ..FailurePlan::default()
+    });
+
+    let mut saved = 0;
+    for i in 0..50 {
+        if cache.save("alice", "s1", &format!("memory {}", i), None).is_ok() {
+            saved += 1;
+        }
+    }
+    assert!(failing.injected_failures() > 0);
+    assert!(saved > 0);
+    assert_eq!(cache.recall("alice", None, None, None).unwrap().len(), saved);
+
+    drop(cache);
+    assert_eq!(reopen(&temp_dir).recall("alice", None, None, None).unwrap().len(), saved);
+}
+
+/// Store with three live memories and garbage from a torn write and an orphaned record
+fn store_with_garbage(temp_dir: &TempDir) -> (MindCache, Arc<FailingStorage>) {
+    let (mut cache, failing) = open_failing(temp_dir, FailurePlan::default());
+    cache.save("alice", "s1", "first memory", None).unwrap();
+
+    failing.set_plan(FailurePlan { write: FailureMode::Always, torn_writes: true, ..FailurePlan::default() });
+    assert!(cache.save("alice", "s1", "torn memory", None).is_err());
+    failing.set_plan(FailurePlan::fail(FailurePoint::IndexPersist, FailureMode::Always));
+    assert!(cache.save("alice", "s1", "orphaned memory", None).is_err());
+    failing.set_plan(FailurePlan::default());
+
+    cache.save("alice", "s1", "second memory", None).unwrap();
+    cache.save("bob", "s2", "third memory", None).unwrap();
+    (cache, failing)
+}
+
+fn assert_live_memories(cache: &MindCache) {
+    let mut contents: Vec<String> = cache.recall("alice", None, None, None).unwrap()
+        .into_iter().map(|m| m.content).collect();
+    contents.sort();
+    assert_eq!(contents, vec!["first memory", "second memory"]);
+    assert_eq!(cache.get_session_memories("bob", "s2").unwrap().len(), 1);
+    assert_eq!(cache.recall("alice", Some("second"), None, None).unwrap().len(), 1);
+}
+
+#[test]
+fn test_failed_transaction_leaves_no_changes() {
+    let temp_dir = TempDir::new().unwrap();
+    let inner: Arc<dyn StorageBackend> = Arc::new(FileBackend::new(temp_dir.path()).unwrap());
+    let failing = Arc::new(FailingStorage::new(inner, FailurePlan::default()));
+    let mut cache = MindCache::with_backend(MindCacheConfig { change_log_enabled: true, ..config(&temp_dir) }, failing.clone()).unwrap();
+
+    failing.set_plan(FailurePlan::fail(FailurePoint::IndexPersist, FailureMode::Always));
+    assert!(cache.transaction(|tx| {
+        tx.save("alice", "s1", "never committed", None);
+        Ok(())
+    }).is_err());
+    failing.set_plan(FailurePlan::default());
+    assert!(cache.changes_since(0).unwrap().is_empty());
+
+    cache.transaction(|tx| {
+        tx.save("alice", "s1", "committed", None);
+        Ok(())
+    }).unwrap();
+    assert_eq!(cache.changes_since(0).unwrap().len(), 1);
+}
+
+#[test]
+fn test_compaction_drops_unreferenced_records() {
+    let temp_dir = TempDir::new().unwrap();
+    let (mut cache, _failing) = store_with_garbage(&temp_dir);
+
+    let reclaimable = cache.reclaimable_bytes().unwrap();
+    assert!(reclaimable > 0);
+
+    let report = cache.compact().unwrap();
+    assert_eq!(report.records_kept, 3);
+    assert_eq!(report.bytes_reclaimed(), reclaimable);
+    assert_eq!(cache.reclaimable_bytes().unwrap(), 0);
+    assert_live_memories(&cache);
+
+    cache.save("alice", "s1", "after compaction", None).unwrap();
+    drop(cache);
+    let reopened = reopen(&temp_dir);
+    assert_eq!(reopened.recall("alice", None, None, None).unwrap().len(), 3);
+}
+
+#[test]
+fn test_interrupted_compaction_recovers_on_open() {
+    // Compaction persists through ten steps: the swap marker, a staged log per
+    // user, two index blobs, committing the marker and four renames
+    for failing_step in 1..=10 {
+        let temp_dir = TempDir::new().unwrap();
+        let (mut cache, failing) = store_with_garbage(&temp_dir);
+
+        failing.set_plan(FailurePlan::fail(FailurePoint::IndexPersist, FailureMode::OnCall(failing_step)));
+        assert!(cache.compact().is_err(), "step {} should fail", failing_step);
+        drop(cache);
+
+        let reopened = reopen(&temp_dir);
+        assert_live_memories(&reopened);
+        let leftovers = staged_files(temp_dir.path());
+        assert!(leftovers.is_empty(), "step {} left {:?}", failing_step, leftovers);
+    }
+}
+
+/// Staged compaction files and swap markers anywhere under `dir`
+fn staged_files(dir: &std::path::Path) -> Vec<std::path::PathBuf> {
+    let mut found = Vec::new();
+    for entry in std::fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.is_dir() {
+            found.extend(staged_files(&path));
+        } else if path.extension().is_some_and(|ext| ext == "compact") || path.ends_with("swap.json") {
+            found.push(path);
+        }
+    }
+    found
+}
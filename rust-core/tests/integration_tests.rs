@@ -3,7 +3,7 @@
 //! These tests verify that all components work together correctly
 //! and test realistic usage scenarios.
 
-use mindcache_core::{MindCache, MindCacheConfig, QueryFilter}; // Remove DecayPolicy
+use mindcache_core::{MindCache, MindCacheConfig, QueryFilter, StorageLocked, Clock, TestClock}; // Remove DecayPolicy
 use std::collections::HashMap; 
 use tempfile::TempDir;
 
@@ -18,6 +18,7 @@ fn create_test_cache() -> (MindCache, TempDir) {
         enable_compression: true,
         max_memories_per_user: 1000,
         importance_threshold: 0.3,
+        ..Default::default()
     };
     
     let cache = MindCache::with_config(config).expect("Failed to create test cache");
@@ -162,6 +163,7 @@ fn test_advanced_recall_filtering() {
         date_to: None,
         limit: None,
         min_importance: Some(0.7),
+        ..Default::default()
     };
     
     let important_memories = cache.recall_advanced(filter)
@@ -177,6 +179,7 @@ fn test_advanced_recall_filtering() {
         date_to: None,
         limit: Some(2),
         min_importance: None,
+        ..Default::default()
     };
     
     let limited_memories = cache.recall_advanced(filter)
@@ -217,19 +220,25 @@ fn test_session_summary_generation() {
     
     assert_eq!(session_memories.len(), 5, "Should have saved all memories to session");
     
-    // Since summarize_session isn't working properly, let's test the data is there
-    // and skip the summary generation for now
-    println!("Session {} has {} memories", session_id, session_memories.len());
-    
     // Verify content
     assert!(session_memories.iter().any(|m| m.content.contains("gold futures")));
     assert!(session_memories.iter().any(|m| m.content.contains("Federal Reserve")));
     assert!(session_memories.iter().any(|m| m.content.contains("Technology stocks")));
+
+    // Summaries look the session up by id alone
+    let summary = cache.summarize_session(&session_id)
+        .expect("Should summarize session");
+    assert_eq!(summary.session_id, session_id);
+    assert_eq!(summary.user_id, user_id);
+    assert_eq!(summary.memory_count, 5);
+    assert!(!summary.key_topics.is_empty());
 }
 
 #[test]
 fn test_memory_decay_integration() {
     let (mut cache, _temp_dir) = create_test_cache();
+    let clock = TestClock::default();
+    cache.set_clock(clock.clone());
     
     let user_id = "decay_test_user";
     let session_id = cache.create_session(user_id, Some("Decay Test"))
@@ -253,21 +262,28 @@ fn test_memory_decay_integration() {
         .expect("Should get session memories before decay");
     assert_eq!(before_decay.len(), 3);
     
-    // Run decay process
+    // Nothing is past its TTL yet
     let decay_stats = cache.decay()
         .expect("Should run decay process");
+    assert_eq!(decay_stats.memories_expired, 0);
+    assert_eq!(decay_stats.total_memories_before, 3);
     
-    // Verify decay ran (even if no memories were actually removed due to timing)
+    // Two hours on, only the unimportant one-hour memory has expired and is gone
+    clock.advance(chrono::Duration::hours(2));
+    let decay_stats = cache.decay()
+        .expect("Should run decay process");
     println!("Decay stats: expired={}, compressed={}, before={}, after={}", 
              decay_stats.memories_expired,
              decay_stats.memories_compressed,
              decay_stats.total_memories_before,
              decay_stats.total_memories_after);
-    
-    // Just verify the decay process ran without errors
-    // The actual decay behavior depends on timing and TTL settings
-    assert!(decay_stats.total_memories_before >= 0);
-    assert!(decay_stats.total_memories_after >= 0);
+    assert_eq!(decay_stats.memories_expired, 1);
+    assert_eq!(decay_stats.last_decay_run, clock.now());
+    assert_eq!(decay_stats.total_memories_after, decay_stats.total_memories_before - 1);
+    let after_decay = cache.get_session_memories(user_id, &session_id)
+        .expect("Should get session memories after decay");
+    assert_eq!(after_decay.len(), 2);
+    assert!(after_decay.iter().all(|memory| memory.content != "Low importance memory"));
 }
 
 #[test]
@@ -298,33 +314,31 @@ fn test_statistics_accuracy() {
     }
     
     // Get statistics
-    let stats = cache.get_stats();
+    let stats = cache.get_stats().expect("Should get stats");
     
     // Verify storage statistics
-    if let Some(storage_stats) = stats.get("storage") {
-        let storage_map: HashMap<String, usize> = serde_json::from_value(storage_stats.clone())
-            .expect("Should parse storage stats");
-        
-        let total_stored: usize = storage_map.values().sum();
-        assert_eq!(total_stored, total_memories);
-        
-        assert_eq!(storage_map.get("user1").unwrap_or(&0), &5);
-        assert_eq!(storage_map.get("user2").unwrap_or(&0), &3);
-        assert_eq!(storage_map.get("user3").unwrap_or(&0), &7);
-    } else {
-        panic!("Storage stats should be present");
-    }
+    assert_eq!(stats.total_memories(), total_memories);
+    assert_eq!(stats.storage.get("user1").unwrap_or(&0), &5);
+    assert_eq!(stats.storage.get("user2").unwrap_or(&0), &3);
+    assert_eq!(stats.storage.get("user3").unwrap_or(&0), &7);
     
-    // Verify session statistics
-    if let Some(session_stats) = stats.get("sessions") {
-        let session_map: HashMap<String, usize> = serde_json::from_value(session_stats.clone())
-            .expect("Should parse session stats");
-        
-        // Each user should have 1 session
-        for user in &users {
-            assert_eq!(session_map.get(*user).unwrap_or(&0), &1);
-        }
+    // Verify session statistics: each user should have 1 session
+    for user in &users {
+        assert_eq!(stats.sessions.per_user.get(*user).unwrap_or(&0), &1);
     }
+
+    // Index and file sizes
+    assert_eq!(stats.index.users, users.len());
+    assert_eq!(stats.index.records, total_memories);
+    assert!(stats.index.terms > 0);
+    assert!(stats.files.log_bytes > 0 && stats.files.index_bytes > 0);
+    assert!(stats.files.total_bytes >= stats.files.log_bytes + stats.files.index_bytes);
+
+    // The JSON form keeps the sections the C API has always returned
+    let json = serde_json::to_value(&stats).expect("Should serialize stats");
+    assert_eq!(json["storage"]["user2"], 3);
+    assert_eq!(json["sessions"]["user1"], 1);
+    assert_eq!(json["sessions"]["total_sessions"], stats.sessions.total_sessions);
 }
 
 #[test]
@@ -384,6 +398,37 @@ fn test_export_and_import_cycle() {
     } else {
         panic!("Exported data should be a JSON array");
     }
+
+    // Import into a fresh store and verify the memories come back unchanged
+    let (mut imported_cache, _imported_dir) = create_test_cache();
+    let imported = imported_cache.import_memories(&exported_data)
+        .expect("Should import memories");
+    assert_eq!(imported, test_memories.len());
+
+    let recalled = imported_cache.recall(user_id, None, Some(&session_id), None)
+        .expect("Should recall imported memories");
+    assert_eq!(recalled.len(), test_memories.len());
+    for memory in &recalled {
+        assert!(saved_ids.contains(&memory.id));
+    }
+}
+
+#[test]
+fn test_importing_an_export_twice_adds_nothing() {
+    let (mut cache, _temp_dir) = create_test_cache();
+    cache.save("import_user", "s1", "Prefers tea", None).expect("Should save");
+    cache.save("import_user", "s1", "Lives in Austin", None).expect("Should save");
+    let exported = cache.export_user_memories("import_user").expect("Should export");
+
+    // Back into the store it came from
+    assert_eq!(cache.import_memories(&exported).expect("Should import"), 0);
+    assert_eq!(cache.recall("import_user", None, None, None).unwrap().len(), 2);
+
+    // Twice into a fresh store
+    let (mut other, _other_dir) = create_test_cache();
+    assert_eq!(other.import_memories(&exported).expect("Should import"), 2);
+    assert_eq!(other.import_memories(&exported).expect("Should import"), 0);
+    assert_eq!(other.recall("import_user", None, None, None).unwrap().len(), 2);
 }
 
 #[test]
@@ -499,7 +544,7 @@ fn test_configuration_updates() {
     let (mut cache, _temp_dir) = create_test_cache();
     
     // Get initial stats
-    let initial_stats = cache.get_stats();
+    let _initial_stats = cache.get_stats().expect("Should get stats");
     
     // Update configuration
     let new_config = MindCacheConfig {
@@ -510,6 +555,7 @@ fn test_configuration_updates() {
         enable_compression: false,
         max_memories_per_user: 500,
         importance_threshold: 0.5,
+        ..Default::default()
     };
     
     cache.update_config(new_config).expect("Should update config");
@@ -534,7 +580,25 @@ fn test_configuration_updates() {
              decay_stats.total_memories_before,
              decay_stats.total_memories_after);
     
-    let final_stats = cache.get_stats();
+    let final_stats = cache.get_stats().expect("Should get stats");
     // Stats should be available (may or may not be different)
-    assert!(final_stats.contains_key("storage"));
-}
\ No newline at end of file
+    assert_eq!(final_stats.storage.get(user_id), Some(&2));
+}
+
+#[test]
+fn test_single_writer_and_read_only_sidecar() {
+    let (mut cache, _temp_dir) = create_test_cache();
+    cache.save("alice", "s1", "Written by the server", None).expect("Should save");
+
+    let config = cache.config().clone();
+    let err = MindCache::with_config(config.clone()).err().expect("Second writer should be locked out");
+    assert!(err.downcast_ref::<StorageLocked>().is_some(), "unexpected error: {}", err);
+
+    let mut sidecar = MindCache::with_config(MindCacheConfig { read_only: true, ..config.clone() })
+        .expect("Read-only open should not need the lock");
+    assert_eq!(sidecar.recall("alice", None, None, None).unwrap().len(), 1);
+    assert!(sidecar.save("alice", "s1", "Sidecar write", None).is_err());
+
+    drop(cache);
+    MindCache::with_config(config).expect("Lock is released when the writer closes");
+}
@@ -3,8 +3,6 @@
 //! These tests verify that MindCache performs well under various load conditions
 //! and measure key performance metrics.
 
-#[cfg(feature = "benchmarks")]
-use criterion::{black_box, criterion_group, criterion_main, Criterion, BenchmarkId};
 use mindcache_core::{MindCache, MindCacheConfig, QueryFilter};
 use std::collections::HashMap;
 use std::time::{Duration, Instant};
@@ -20,6 +18,7 @@ fn create_test_cache() -> (MindCache, TempDir) {
         enable_compression: true,
         max_memories_per_user: 10000,
         importance_threshold: 0.3,
+        ..Default::default()
     };
     
     let cache = MindCache::with_config(config).expect("Failed to create test cache");
@@ -333,7 +332,7 @@ fn test_storage_file_growth() {
     let session_id = cache.create_session(user_id, Some("Storage Growth Test"))
         .expect("Should create session");
     
-    let storage_file = temp_dir.path().join("memories.bin");
+    let storage_file = temp_dir.path().join(mindcache_core::segments::user_log(user_id));
     
     // Add memories and track file size growth
     let batches = vec![100, 500, 1000];
@@ -371,215 +370,6 @@ fn test_storage_file_growth() {
     }
 }
 
-// Benchmark functions for Criterion (only compiled with benchmarks feature)
-#[cfg(feature = "benchmarks")]
-fn bench_save_operations(c: &mut Criterion) {
-    let (mut cache, _temp_dir) = create_test_cache();
-    let user_id = "bench_user";
-    let session_id = cache.create_session(user_id, Some("Benchmark Session"))
-        .expect("Should create session");
-    
-    c.bench_function("save_memory", |b| {
-        let mut counter = 0;
-        b.iter(|| {
-            let content = format!("Benchmark memory {}", counter);
-           counter += 1;
-           cache.save(black_box(user_id), black_box(&session_id), black_box(&content), black_box(None))
-               .expect("Should save memory")
-       })
-   });
-}
-
-#[cfg(feature = "benchmarks")]
-fn bench_recall_operations(c: &mut Criterion) {
-   let (mut cache, _temp_dir) = create_test_cache();
-   let user_id = "bench_recall_user";
-   let session_id = cache.create_session(user_id, Some("Recall Benchmark"))
-       .expect("Should create session");
-   
-   // Pre-populate with test data
-   for i in 0..1000 {
-       let content = format!("Benchmark recall memory {} about trading stocks", i);
-       cache.save(user_id, &session_id, &content, None)
-           .expect("Should save memory");
-   }
-   
-   let mut group = c.benchmark_group("recall_operations");
-   
-   group.bench_function("recall_with_keyword", |b| {
-       b.iter(|| {
-           cache.recall(black_box(user_id), black_box(Some("trading")), black_box(None), black_box(Some(10)))
-               .expect("Should recall memories")
-       })
-   });
-   
-   group.bench_function("recall_all_session", |b| {
-       b.iter(|| {
-           cache.recall(black_box(user_id), black_box(None), black_box(Some(&session_id)), black_box(Some(50)))
-               .expect("Should recall memories")
-       })
-   });
-   
-   group.bench_function("recall_no_filter", |b| {
-       b.iter(|| {
-           cache.recall(black_box(user_id), black_box(None), black_box(None), black_box(Some(20)))
-               .expect("Should recall memories")
-       })
-   });
-   
-   group.finish();
-}
-
-#[cfg(feature = "benchmarks")]
-fn bench_session_operations(c: &mut Criterion) {
-   let (mut cache, _temp_dir) = create_test_cache();
-   let user_id = "bench_session_user";
-   
-   // Pre-populate with sessions and memories
-   let mut session_ids = Vec::new();
-   for i in 0..10 {
-       let session_id = cache.create_session(user_id, Some(&format!("Benchmark Session {}", i)))
-           .expect("Should create session");
-       
-       for j in 0..50 {
-           let content = format!("Session {} memory {} about various topics", i, j);
-           cache.save(user_id, &session_id, &content, None)
-               .expect("Should save memory");
-       }
-       
-       session_ids.push(session_id);
-   }
-   
-   let mut group = c.benchmark_group("session_operations");
-   
-   group.bench_function("get_user_sessions", |b| {
-       b.iter(|| {
-           cache.get_user_sessions(black_box(user_id))
-               .expect("Should get user sessions")
-       })
-   });
-   
-   group.bench_function("summarize_session", |b| {
-       let session_id = &session_ids[0];
-       b.iter(|| {
-           cache.summarize_session(black_box(session_id))
-               .expect("Should summarize session")
-       })
-   });
-   
-   group.bench_function("search_sessions", |b| {
-       b.iter(|| {
-           cache.search_sessions(black_box(user_id), black_box(vec!["memory".to_string()]))
-               .expect("Should search sessions")
-       })
-   });
-   
-   group.finish();
-}
-
-#[cfg(feature = "benchmarks")]
-fn bench_decay_operations(c: &mut Criterion) {
-   let (mut cache, _temp_dir) = create_test_cache();
-   let user_id = "bench_decay_user";
-   let session_id = cache.create_session(user_id, Some("Decay Benchmark"))
-       .expect("Should create session");
-   
-   c.bench_function("decay_process", |b| {
-       b.iter_batched(
-           || {
-               // Setup: Add memories with different importance levels
-               for i in 0..100 {
-                   let importance = (i % 10) as f32 / 10.0;
-                   let content = format!("Decay benchmark memory {}", i);
-                   cache.save_with_options(user_id, &session_id, &content, None, importance, Some(1))
-                       .expect("Should save memory");
-               }
-           },
-           |_| {
-               cache.decay().expect("Should run decay")
-           },
-           criterion::BatchSize::LargeInput,
-       )
-   });
-}
-
-#[cfg(feature = "benchmarks")]
-fn bench_c_api_operations(c: &mut Criterion) {
-   use std::ffi::CString;
-   use std::ptr;
-   
-   let cache_ptr = mindcache_init();
-   assert!(!cache_ptr.is_null());
-   
-   let user_id = CString::new("c_api_bench_user").unwrap();
-   let session_id = CString::new("c_api_bench_session").unwrap();
-   
-   let mut group = c.benchmark_group("c_api_operations");
-   
-   group.bench_function("c_api_save", |b| {
-       let mut counter = 0;
-       b.iter(|| {
-           let content = CString::new(format!("C API benchmark memory {}", counter)).unwrap();
-           counter += 1;
-           
-           let result = mindcache_save(
-               black_box(cache_ptr),
-               black_box(user_id.as_ptr()),
-               black_box(session_id.as_ptr()),
-               black_box(content.as_ptr()),
-               black_box(ptr::null()),
-           );
-           
-           if !result.is_null() {
-               mindcache_free_string(result);
-           }
-       })
-   });
-   
-   group.bench_function("c_api_recall", |b| {
-       let query = CString::new("benchmark").unwrap();
-       b.iter(|| {
-           let result = mindcache_recall(
-               black_box(cache_ptr),
-               black_box(user_id.as_ptr()),
-               black_box(query.as_ptr()),
-               black_box(ptr::null()),
-               black_box(10),
-           );
-           
-           if !result.is_null() {
-               mindcache_free_string(result);
-           }
-       })
-   });
-   
-   group.bench_function("c_api_get_stats", |b| {
-       b.iter(|| {
-           let result = mindcache_get_stats(black_box(cache_ptr));
-           if !result.is_null() {
-               mindcache_free_string(result);
-           }
-       })
-   });
-   
-   group.finish();
-   
-   mindcache_destroy(cache_ptr);
-}
-
-#[cfg(feature = "benchmarks")]
-criterion_group!(
-   benches,
-   bench_save_operations,
-   bench_recall_operations, 
-   bench_session_operations,
-   bench_decay_operations,
-   bench_c_api_operations
-);
-
-#[cfg(feature = "benchmarks")]
-criterion_main!(benches);
-
 // Additional performance regression tests
 #[test]
 fn test_performance_regression_saves() {